@@ -0,0 +1,460 @@
+// Stockage des pièces jointes
+//
+// Les fichiers sont identifiés par leur SHA-256 et enregistrés sur disque
+// sous `BLOB_STORAGE_DIR/<2 premiers caractères>/<sha256>`. L'envoi se fait
+// par morceaux successifs (reprise possible grâce à `received`) ; un fichier
+// déjà présent n'est jamais renvoyé.
+
+use actix_web::{get, http::StatusCode, post, put, web, HttpResponse, Responder};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use sqlx::{PgPool, Row};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+use crate::models::SyncResponse;
+
+/// Taille maximale d'un morceau accepté par `upload_chunk`
+pub const MAX_CHUNK_SIZE: usize = 4 * 1024 * 1024;
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct BlobStatus {
+    pub sha256: String,
+    pub size: i64,
+    pub received: i64,
+    pub complete: bool,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct StartUpload {
+    pub size: i64,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ChunkQuery {
+    pub offset: i64,
+    pub length: Option<i64>,
+}
+
+fn storage_dir() -> PathBuf {
+    std::env::var("BLOB_STORAGE_DIR")
+        .map(PathBuf::from)
+        .unwrap_or_else(|_| PathBuf::from("storage/blobs"))
+}
+
+fn blob_path(sha256: &str) -> PathBuf {
+    storage_dir().join(&sha256[..2]).join(sha256)
+}
+
+fn partial_path(sha256: &str) -> PathBuf {
+    storage_dir().join("partial").join(sha256)
+}
+
+fn is_valid_hash(sha256: &str) -> bool {
+    sha256.len() == 64 && sha256.chars().all(|c| c.is_ascii_hexdigit() && !c.is_ascii_uppercase())
+}
+
+fn error(status: StatusCode, message: String) -> HttpResponse {
+    HttpResponse::build(status).json(SyncResponse {
+        success: false,
+        message,
+    })
+}
+
+async fn blob_status(pool: &PgPool, sha256: &str) -> Result<Option<BlobStatus>, sqlx::Error> {
+    let row = sqlx::query("SELECT sha256, size, received, complete FROM blobs WHERE sha256 = $1")
+        .bind(sha256)
+        .fetch_optional(pool)
+        .await?;
+
+    Ok(row.map(|row| BlobStatus {
+        sha256: row.get("sha256"),
+        size: row.get("size"),
+        received: row.get("received"),
+        complete: row.get("complete"),
+    }))
+}
+
+#[get("/blobs/{sha256}")]
+pub async fn get_blob_status(pool: web::Data<PgPool>, sha256: web::Path<String>) -> impl Responder {
+    if !is_valid_hash(&sha256) {
+        return error(StatusCode::BAD_REQUEST, "Invalid blob".to_string());
+    }
+    match blob_status(pool.get_ref(), &sha256).await {
+        Ok(Some(status)) => HttpResponse::Ok().json(status),
+        Ok(None) => error(StatusCode::NOT_FOUND, "Blob not found".to_string()),
+        Err(e) => error(StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to get blob: {}", e)),
+    }
+}
+
+/// Déclare un envoi ; renvoie l'état existant si le fichier est déjà connu
+/// (complet ou partiellement reçu) pour que le client reprenne au bon endroit.
+/// Une taille différente de celle déjà connue est refusée : 422 si le fichier
+/// est complet (la taille est alors vérifiée), 409 si un envoi est en cours.
+#[post("/blobs/{sha256}")]
+pub async fn start_blob_upload(
+    pool: web::Data<PgPool>,
+    sha256: web::Path<String>,
+    request: web::Json<StartUpload>,
+) -> impl Responder {
+    if !is_valid_hash(&sha256) || request.size < 0 {
+        return error(StatusCode::BAD_REQUEST, "Invalid blob".to_string());
+    }
+
+    // Une déclaration dont aucun octet n'a encore été reçu peut être corrigée
+    let result = sqlx::query(
+        "INSERT INTO blobs (sha256, size) VALUES ($1, $2)
+         ON CONFLICT (sha256) DO UPDATE SET size = EXCLUDED.size, updated_at = NOW()
+         WHERE NOT blobs.complete AND blobs.received = 0"
+    )
+    .bind(sha256.as_str())
+    .bind(request.size)
+    .execute(pool.get_ref())
+    .await;
+    if let Err(e) = result {
+        return error(StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to start upload: {}", e));
+    }
+
+    let status = match blob_status(pool.get_ref(), &sha256).await {
+        Ok(Some(status)) => status,
+        Ok(None) => return error(StatusCode::NOT_FOUND, "Blob not found".to_string()),
+        Err(e) => return error(StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to get blob: {}", e)),
+    };
+    if status.size != request.size {
+        let code = match status.complete {
+            true => StatusCode::UNPROCESSABLE_ENTITY,
+            false => StatusCode::CONFLICT,
+        };
+        return error(
+            code,
+            format!("Size mismatch: blob {} is {} bytes, not {}", sha256, status.size, request.size),
+        );
+    }
+
+    // Fichier vide : rien à recevoir
+    if request.size == 0 && !status.complete {
+        return finish_upload(pool.get_ref(), &sha256, 0).await;
+    }
+    HttpResponse::Ok().json(status)
+}
+
+/// Reçoit le morceau commençant à `offset`, qui doit suivre exactement les
+/// octets déjà reçus. Le dernier morceau déclenche la vérification du hash.
+#[put("/blobs/{sha256}/chunks")]
+pub async fn upload_blob_chunk(
+    pool: web::Data<PgPool>,
+    sha256: web::Path<String>,
+    query: web::Query<ChunkQuery>,
+    body: web::Bytes,
+) -> impl Responder {
+    if !is_valid_hash(&sha256) {
+        return error(StatusCode::BAD_REQUEST, "Invalid blob".to_string());
+    }
+    let status = match blob_status(pool.get_ref(), &sha256).await {
+        Ok(Some(status)) => status,
+        Ok(None) => return error(StatusCode::NOT_FOUND, "Upload not started".to_string()),
+        Err(e) => return error(StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to get blob: {}", e)),
+    };
+    if status.complete {
+        return HttpResponse::Ok().json(status);
+    }
+    if query.offset != status.received || status.received + body.len() as i64 > status.size {
+        return HttpResponse::Conflict().json(status);
+    }
+
+    // Réserve le morceau avant de l'écrire : de deux envois concurrents du
+    // même fichier, un seul avance `received`, l'autre attend la fin de
+    // l'écriture puis reçoit 409. Une écriture en échec annule la réserve.
+    let received = query.offset + body.len() as i64;
+    let mut tx = match pool.begin().await {
+        Ok(tx) => tx,
+        Err(e) => return error(StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to update blob: {}", e)),
+    };
+    let claimed = sqlx::query(
+        "UPDATE blobs SET received = $1, updated_at = NOW()
+         WHERE sha256 = $2 AND received = $3 AND NOT complete AND $1 <= size"
+    )
+    .bind(received)
+    .bind(sha256.as_str())
+    .bind(query.offset)
+    .execute(&mut *tx)
+    .await;
+    match claimed {
+        Ok(done) if done.rows_affected() == 0 => {
+            let _ = tx.rollback().await;
+            return match blob_status(pool.get_ref(), &sha256).await {
+                Ok(Some(status)) => HttpResponse::Conflict().json(status),
+                Ok(None) => error(StatusCode::NOT_FOUND, "Upload not started".to_string()),
+                Err(e) => error(StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to get blob: {}", e)),
+            };
+        }
+        Ok(_) => {}
+        Err(e) => return error(StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to update blob: {}", e)),
+    }
+
+    let path = partial_path(&sha256);
+    let written = (|| -> std::io::Result<()> {
+        std::fs::create_dir_all(path.parent().unwrap_or(&storage_dir()))?;
+        let mut file = std::fs::OpenOptions::new().create(true).write(true).truncate(false).open(&path)?;
+        file.seek(SeekFrom::Start(query.offset as u64))?;
+        file.write_all(&body)?;
+        file.set_len(query.offset as u64 + body.len() as u64)?;
+        Ok(())
+    })();
+    if let Err(e) = written {
+        return error(StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to write chunk: {}", e));
+    }
+    if let Err(e) = tx.commit().await {
+        return error(StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to update blob: {}", e));
+    }
+
+    if received == status.size {
+        return finish_upload(pool.get_ref(), &sha256, status.size).await;
+    }
+    HttpResponse::Ok().json(BlobStatus { received, ..status })
+}
+
+// Empreinte d'un fichier, lu par blocs plutôt qu'en entier en mémoire
+fn file_sha256(path: &Path) -> std::io::Result<String> {
+    let mut hasher = Sha256::new();
+    std::io::copy(&mut std::fs::File::open(path)?, &mut hasher)?;
+    Ok(hex::encode(hasher.finalize()))
+}
+
+/// Vérifie le fichier reçu et le place dans le stockage définitif ; en cas
+/// d'empreinte différente, l'envoi est oublié (taille déclarée comprise) et
+/// doit être recommencé.
+async fn finish_upload(pool: &PgPool, sha256: &str, size: i64) -> HttpResponse {
+    let partial = partial_path(sha256);
+    let actual = match size {
+        0 => hex::encode(Sha256::digest([])),
+        _ => match file_sha256(&partial) {
+            Ok(actual) => actual,
+            Err(e) => return error(StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to read upload: {}", e)),
+        },
+    };
+
+    if actual != sha256 {
+        let _ = std::fs::remove_file(&partial);
+        let reset = sqlx::query("DELETE FROM blobs WHERE sha256 = $1 AND NOT complete")
+            .bind(sha256)
+            .execute(pool)
+            .await;
+        if let Err(e) = reset {
+            return error(StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to reset upload: {}", e));
+        }
+        return error(StatusCode::UNPROCESSABLE_ENTITY, format!("Checksum mismatch: expected {}, got {}", sha256, actual));
+    }
+
+    let path = blob_path(sha256);
+    let moved = std::fs::create_dir_all(path.parent().unwrap_or(&storage_dir()))
+        .and_then(|_| match size {
+            0 => std::fs::write(&path, []),
+            _ => std::fs::rename(&partial, &path),
+        });
+    if let Err(e) = moved {
+        return error(StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to store blob: {}", e));
+    }
+
+    match sqlx::query(
+        "UPDATE blobs SET received = size, complete = TRUE, updated_at = NOW() WHERE sha256 = $1
+         RETURNING sha256, size, received, complete"
+    )
+    .bind(sha256)
+    .fetch_one(pool)
+    .await
+    {
+        Ok(row) => {
+            println!("📦 Blob stored: {}", sha256);
+            HttpResponse::Ok().json(BlobStatus {
+                sha256: row.get("sha256"),
+                size: row.get("size"),
+                received: row.get("received"),
+                complete: row.get("complete"),
+            })
+        }
+        Err(e) => error(StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to update blob: {}", e)),
+    }
+}
+
+/// Renvoie `length` octets à partir de `offset` d'un fichier complet.
+#[get("/blobs/{sha256}/chunks")]
+pub async fn download_blob_chunk(
+    pool: web::Data<PgPool>,
+    sha256: web::Path<String>,
+    query: web::Query<ChunkQuery>,
+) -> impl Responder {
+    if !is_valid_hash(&sha256) {
+        return error(StatusCode::BAD_REQUEST, "Invalid blob".to_string());
+    }
+    match blob_status(pool.get_ref(), &sha256).await {
+        Ok(Some(status)) if status.complete => {}
+        Ok(_) => return error(StatusCode::NOT_FOUND, "Blob not found".to_string()),
+        Err(e) => return error(StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to get blob: {}", e)),
+    }
+
+    let length = query.length.unwrap_or(MAX_CHUNK_SIZE as i64).clamp(0, MAX_CHUNK_SIZE as i64);
+    let chunk = (|| -> std::io::Result<Vec<u8>> {
+        let mut file = std::fs::File::open(blob_path(&sha256))?;
+        file.seek(SeekFrom::Start(query.offset.max(0) as u64))?;
+        let mut buffer = Vec::with_capacity(length as usize);
+        file.take(length as u64).read_to_end(&mut buffer)?;
+        Ok(buffer)
+    })();
+
+    match chunk {
+        Ok(bytes) => HttpResponse::Ok()
+            .content_type("application/octet-stream")
+            .body(bytes),
+        Err(e) => error(StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to read blob: {}", e)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::{test, App};
+
+    fn hash(bytes: &[u8]) -> String {
+        hex::encode(Sha256::digest(bytes))
+    }
+
+    // Base fournie par TEST_DATABASE_URL, fichiers dans un dossier temporaire
+    async fn open() -> PgPool {
+        let url = std::env::var("TEST_DATABASE_URL").expect("TEST_DATABASE_URL must be set");
+        std::env::set_var("BLOB_STORAGE_DIR", std::env::temp_dir().join("crm-blob-tests"));
+        let pool = PgPool::connect(&url).await.unwrap();
+        crate::db::run_migrations(&pool).await.unwrap();
+        pool
+    }
+
+    macro_rules! blob_app {
+        ($pool:expr) => {
+            test::init_service(
+                App::new()
+                    .app_data(web::Data::new($pool))
+                    .service(get_blob_status)
+                    .service(start_blob_upload)
+                    .service(upload_blob_chunk)
+                    .service(download_blob_chunk),
+            )
+            .await
+        };
+    }
+
+    #[actix_web::test]
+    async fn malformed_hashes_are_rejected() {
+        // Refusé avant tout accès à la base ou au disque
+        let pool = sqlx::postgres::PgPoolOptions::new().connect_lazy("postgres://localhost/unused").unwrap();
+        let app = blob_app!(pool);
+        for uri in ["/blobs/a", "/blobs/a%C3%A9", "/blobs/a/chunks?offset=0"] {
+            let response = test::call_service(&app, test::TestRequest::get().uri(uri).to_request()).await;
+            assert_eq!(response.status(), StatusCode::BAD_REQUEST, "{}", uri);
+        }
+        let request = test::TestRequest::put().uri("/blobs/a%C3%A9/chunks?offset=0").set_payload("x").to_request();
+        assert_eq!(test::call_service(&app, request).await.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[actix_web::test]
+    #[ignore = "requires TEST_DATABASE_URL"]
+    async fn declared_size_must_match() {
+        let app = blob_app!(open().await);
+        let content = uuid::Uuid::new_v4().to_string().into_bytes();
+        let sha256 = hash(&content);
+        let start = |size: usize| {
+            test::TestRequest::post()
+                .uri(&format!("/blobs/{}", sha256))
+                .set_json(serde_json::json!({ "size": size }))
+                .to_request()
+        };
+
+        // Rien reçu : la déclaration peut encore être corrigée
+        assert!(test::call_service(&app, start(content.len() + 1)).await.status().is_success());
+        assert!(test::call_service(&app, start(content.len())).await.status().is_success());
+
+        let chunk = test::TestRequest::put()
+            .uri(&format!("/blobs/{}/chunks?offset=0", sha256))
+            .set_payload(content[..4].to_vec())
+            .to_request();
+        assert!(test::call_service(&app, chunk).await.status().is_success());
+        assert_eq!(test::call_service(&app, start(content.len() + 1)).await.status(), StatusCode::CONFLICT);
+
+        let chunk = test::TestRequest::put()
+            .uri(&format!("/blobs/{}/chunks?offset=4", sha256))
+            .set_payload(content[4..].to_vec())
+            .to_request();
+        let status: BlobStatus = test::call_and_read_body_json(&app, chunk).await;
+        assert!(status.complete);
+        assert_eq!(
+            test::call_service(&app, start(content.len() + 1)).await.status(),
+            StatusCode::UNPROCESSABLE_ENTITY
+        );
+
+        let download = test::TestRequest::get().uri(&format!("/blobs/{}/chunks?offset=0", sha256)).to_request();
+        assert_eq!(test::call_and_read_body(&app, download).await, content);
+    }
+
+    #[actix_web::test]
+    #[ignore = "requires TEST_DATABASE_URL"]
+    async fn corrupted_upload_is_forgotten() {
+        let pool = open().await;
+        let app = blob_app!(pool.clone());
+        let content = uuid::Uuid::new_v4().to_string().into_bytes();
+        let sha256 = hash(&content);
+
+        let start = test::TestRequest::post()
+            .uri(&format!("/blobs/{}", sha256))
+            .set_json(serde_json::json!({ "size": content.len() + 1 }))
+            .to_request();
+        assert!(test::call_service(&app, start).await.status().is_success());
+        let mut wrong = content.clone();
+        wrong.push(b'!');
+        let chunk = test::TestRequest::put()
+            .uri(&format!("/blobs/{}/chunks?offset=0", sha256))
+            .set_payload(wrong)
+            .to_request();
+        assert_eq!(test::call_service(&app, chunk).await.status(), StatusCode::UNPROCESSABLE_ENTITY);
+
+        // La mauvaise taille ne bloque pas l'envoi suivant
+        assert!(blob_status(&pool, &sha256).await.unwrap().is_none());
+        let start = test::TestRequest::post()
+            .uri(&format!("/blobs/{}", sha256))
+            .set_json(serde_json::json!({ "size": content.len() }))
+            .to_request();
+        assert!(test::call_service(&app, start).await.status().is_success());
+    }
+
+    #[actix_web::test]
+    #[ignore = "requires TEST_DATABASE_URL"]
+    async fn concurrent_chunks_are_claimed_once() {
+        let app = blob_app!(open().await);
+        let content = uuid::Uuid::new_v4().to_string().into_bytes();
+        let sha256 = hash(&content);
+        let start = test::TestRequest::post()
+            .uri(&format!("/blobs/{}", sha256))
+            .set_json(serde_json::json!({ "size": content.len() }))
+            .to_request();
+        assert!(test::call_service(&app, start).await.status().is_success());
+
+        // Deux postes envoient le même début de fichier en même temps
+        let chunk = || {
+            test::TestRequest::put()
+                .uri(&format!("/blobs/{}/chunks?offset=0", sha256))
+                .set_payload(content[..4].to_vec())
+                .to_request()
+        };
+        let (first, second) = tokio::join!(test::call_service(&app, chunk()), test::call_service(&app, chunk()));
+        let mut statuses = [first.status(), second.status()];
+        statuses.sort();
+        assert_eq!(statuses, [StatusCode::OK, StatusCode::CONFLICT]);
+
+        let rest = test::TestRequest::put()
+            .uri(&format!("/blobs/{}/chunks?offset=4", sha256))
+            .set_payload(content[4..].to_vec())
+            .to_request();
+        let status: BlobStatus = test::call_and_read_body_json(&app, rest).await;
+        assert!(status.complete);
+        let download = test::TestRequest::get().uri(&format!("/blobs/{}/chunks?offset=0", sha256)).to_request();
+        assert_eq!(test::call_and_read_body(&app, download).await, content);
+    }
+}
@@ -0,0 +1,156 @@
+// Annonce en temps réel des modifications reçues
+//
+// `GET /api/changes?device_id=...` ouvre un flux Server-Sent Events. Chaque
+// enregistrement appliqué par `/api/sync` y est annoncé (`ChangeNotice`) aux
+// autres postes connectés, qui se synchronisent aussitôt au lieu d'attendre
+// leur synchronisation périodique. Les annonces ne sont pas conservées : un
+// poste qui se (re)connecte reçoit `resync` et rattrape son retard par
+// `/api/sync`, comme après avoir manqué des annonces.
+
+use actix_web::http::header;
+use actix_web::web::Bytes;
+use actix_web::{get, web, HttpResponse, Responder};
+use crm_schema::{ChangeNotice, CHANGE_EVENT, RESYNC_EVENT};
+use serde::Deserialize;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+use tokio::sync::broadcast;
+
+/// Annonces en attente d'envoi par poste connecté ; au-delà, le poste reçoit
+/// `resync` à la place de celles perdues
+const CHANNEL_CAPACITY: usize = 256;
+/// Commentaire envoyé sans activité : garde la connexion ouverte à travers
+/// les proxys et libère celle d'un poste disparu
+const KEEP_ALIVE: Duration = Duration::from_secs(15);
+
+#[derive(Debug, Clone)]
+struct Announcement {
+    notice: ChangeNotice,
+    /// Poste à l'origine de la modification, qui n'a pas à la recevoir
+    device_id: Option<String>,
+}
+
+pub struct ChangeFeed {
+    sender: broadcast::Sender<Announcement>,
+    sequence: AtomicU64,
+}
+
+impl ChangeFeed {
+    pub fn new() -> Self {
+        Self {
+            sender: broadcast::channel(CHANNEL_CAPACITY).0,
+            sequence: AtomicU64::new(0),
+        }
+    }
+
+    /// Annonce un enregistrement appliqué, envoyé par `device_id` et reçu à
+    /// `received_at` (horloge du serveur)
+    pub fn publish(&self, table_name: &str, id: &str, device_id: Option<&str>, received_at: i64) -> u64 {
+        let sequence = self.sequence.fetch_add(1, Ordering::SeqCst) + 1;
+        // Erreur seulement quand aucun poste n'écoute
+        let _ = self.sender.send(Announcement {
+            notice: ChangeNotice {
+                sequence,
+                table_name: table_name.to_string(),
+                id: id.to_string(),
+                received_at,
+            },
+            device_id: device_id.map(str::to_string),
+        });
+        sequence
+    }
+}
+
+fn event(name: &str, sequence: Option<u64>, data: &str) -> Bytes {
+    let id = sequence.map(|s| format!("id: {}\n", s)).unwrap_or_default();
+    Bytes::from(format!("{}event: {}\ndata: {}\n\n", id, name, data))
+}
+
+// Prochain morceau du flux d'un poste : `None` quand le serveur s'arrête
+async fn next_event(
+    receiver: &mut broadcast::Receiver<Announcement>,
+    device_id: Option<&str>,
+    keep_alive: &mut tokio::time::Interval,
+) -> Option<Bytes> {
+    loop {
+        tokio::select! {
+            received = receiver.recv() => match received {
+                Ok(announcement) if device_id.is_some() && announcement.device_id.as_deref() == device_id => continue,
+                Ok(announcement) => {
+                    let data = serde_json::to_string(&announcement.notice).unwrap_or_default();
+                    return Some(event(CHANGE_EVENT, Some(announcement.notice.sequence), &data));
+                }
+                Err(broadcast::error::RecvError::Lagged(missed)) => {
+                    println!("⚠️ [ChangeFeed] Device {:?} missed {} notices", device_id, missed);
+                    return Some(event(RESYNC_EVENT, None, "{}"));
+                }
+                Err(broadcast::error::RecvError::Closed) => return None,
+            },
+            _ = keep_alive.tick() => return Some(Bytes::from_static(b": keep-alive\n\n")),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct StreamQuery {
+    pub device_id: Option<String>,
+}
+
+#[get("/changes")]
+pub async fn stream_changes(feed: web::Data<ChangeFeed>, query: web::Query<StreamQuery>) -> impl Responder {
+    let device_id = query.into_inner().device_id;
+    println!("📡 [ChangeFeed] Device {:?} subscribed", device_id);
+
+    let receiver = feed.sender.subscribe();
+    let keep_alive = tokio::time::interval_at(tokio::time::Instant::now() + KEEP_ALIVE, KEEP_ALIVE);
+    let first = event(RESYNC_EVENT, None, "{}");
+    let stream = futures_util::stream::unfold(
+        (Some(first), receiver, device_id, keep_alive),
+        |(first, mut receiver, device_id, mut keep_alive)| async move {
+            let chunk = match first {
+                Some(chunk) => chunk,
+                None => next_event(&mut receiver, device_id.as_deref(), &mut keep_alive).await?,
+            };
+            Some((Ok::<_, actix_web::Error>(chunk), (None, receiver, device_id, keep_alive)))
+        },
+    );
+
+    HttpResponse::Ok()
+        .insert_header((header::CONTENT_TYPE, "text/event-stream"))
+        .insert_header((header::CACHE_CONTROL, "no-cache"))
+        .streaming(stream)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn notices_skip_the_sending_device() {
+        let feed = ChangeFeed::new();
+        let mut receiver = feed.sender.subscribe();
+        let mut keep_alive = tokio::time::interval_at(tokio::time::Instant::now() + KEEP_ALIVE, KEEP_ALIVE);
+
+        feed.publish("proposals", "p-1", Some("laptop-a"), 1_000);
+        let sequence = feed.publish("invoices", "i-1", Some("laptop-b"), 2_000);
+
+        let chunk = next_event(&mut receiver, Some("laptop-a"), &mut keep_alive).await.unwrap();
+        let text = std::str::from_utf8(&chunk).unwrap();
+        assert!(text.starts_with(&format!("id: {}\nevent: change\ndata: ", sequence)));
+        let data = text.lines().find_map(|l| l.strip_prefix("data: ")).unwrap();
+        let notice: ChangeNotice = serde_json::from_str(data).unwrap();
+        assert_eq!((notice.table_name.as_str(), notice.id.as_str(), notice.received_at), ("invoices", "i-1", 2_000));
+    }
+
+    #[tokio::test]
+    async fn lagging_device_is_asked_to_resync() {
+        let feed = ChangeFeed::new();
+        let mut receiver = feed.sender.subscribe();
+        let mut keep_alive = tokio::time::interval_at(tokio::time::Instant::now() + KEEP_ALIVE, KEEP_ALIVE);
+        for i in 0..=CHANNEL_CAPACITY {
+            feed.publish("companies", &i.to_string(), None, 0);
+        }
+        let chunk = next_event(&mut receiver, None, &mut keep_alive).await.unwrap();
+        assert_eq!(&chunk[..], b"event: resync\ndata: {}\n\n");
+    }
+}
@@ -1,27 +1,37 @@
-use sqlx::PgPool;
+// Migrations du schéma Postgres
+//
+// Chaque migration est numérotée, appliquée dans une transaction et
+// enregistrée dans `schema_migrations` avec l'empreinte SHA-256 de son script.
+// Un verrou consultatif Postgres empêche deux instances de migrer en même
+// temps. Les versions 1 à 10 reprennent le schéma créé avant le suivi des
+// versions et restent idempotentes pour adopter une base existante.
 
-pub async fn run_migrations(pool: &PgPool) -> Result<(), sqlx::Error> {
-    // Create users table
-    sqlx::query(
-        "CREATE TABLE IF NOT EXISTS users (
+use chrono::{DateTime, Utc};
+use sha2::{Digest, Sha256};
+use sqlx::{PgConnection, PgPool};
+
+pub struct Migration {
+    pub version: i64,
+    pub name: &'static str,
+    pub up: &'static str,
+    pub down: &'static str,
+}
+
+pub const MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 1,
+        name: "initial",
+        up: "
+        CREATE TABLE IF NOT EXISTS users (
             id TEXT PRIMARY KEY,
             email TEXT UNIQUE NOT NULL,
             name TEXT NOT NULL,
             created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
             updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
-        )"
-    )
-    .execute(pool)
-    .await?;
-
-    // Create index for users
-    sqlx::query("CREATE INDEX IF NOT EXISTS idx_users_email ON users(email)")
-        .execute(pool)
-        .await?;
+        );
+        CREATE INDEX IF NOT EXISTS idx_users_email ON users(email);
 
-    // Create companies table
-    sqlx::query(
-        "CREATE TABLE IF NOT EXISTS companies (
+        CREATE TABLE IF NOT EXISTS companies (
             id TEXT PRIMARY KEY,
             name TEXT UNIQUE NOT NULL,
             website TEXT,
@@ -34,14 +44,9 @@ pub async fn run_migrations(pool: &PgPool) -> Result<(), sqlx::Error> {
             updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
             version INTEGER DEFAULT 1,
             is_deleted INTEGER DEFAULT 0
-        )"
-    )
-    .execute(pool)
-    .await?;
+        );
 
-    // Create company_contacts table
-    sqlx::query(
-        "CREATE TABLE IF NOT EXISTS company_contacts (
+        CREATE TABLE IF NOT EXISTS company_contacts (
             id TEXT PRIMARY KEY,
             company_id TEXT NOT NULL,
             first_name TEXT NOT NULL,
@@ -54,22 +59,11 @@ pub async fn run_migrations(pool: &PgPool) -> Result<(), sqlx::Error> {
             version INTEGER DEFAULT 1,
             is_deleted INTEGER DEFAULT 0,
             FOREIGN KEY (company_id) REFERENCES companies(id) ON DELETE CASCADE
-        )"
-    )
-    .execute(pool)
-    .await?;
-
-    // Create indexes
-    sqlx::query("CREATE INDEX IF NOT EXISTS idx_companies_name ON companies(name)")
-        .execute(pool)
-        .await?;
-    sqlx::query("CREATE INDEX IF NOT EXISTS idx_company_contacts_company_id ON company_contacts(company_id)")
-        .execute(pool)
-        .await?;
+        );
+        CREATE INDEX IF NOT EXISTS idx_companies_name ON companies(name);
+        CREATE INDEX IF NOT EXISTS idx_company_contacts_company_id ON company_contacts(company_id);
 
-    // Create customers table
-    sqlx::query(
-        "CREATE TABLE IF NOT EXISTS customers (
+        CREATE TABLE IF NOT EXISTS customers (
             id TEXT PRIMARY KEY,
             name TEXT NOT NULL,
             email TEXT,
@@ -80,14 +74,9 @@ pub async fn run_migrations(pool: &PgPool) -> Result<(), sqlx::Error> {
             updated_at TIMESTAMPTZ NOT NULL,
             version INTEGER DEFAULT 1,
             is_deleted INTEGER DEFAULT 0
-        )"
-    )
-    .execute(pool)
-    .await?;
+        );
 
-    // Create documents table
-    sqlx::query(
-        "CREATE TABLE IF NOT EXISTS documents (
+        CREATE TABLE IF NOT EXISTS documents (
             id TEXT PRIMARY KEY,
             customer_id TEXT NOT NULL,
             title TEXT NOT NULL,
@@ -99,14 +88,9 @@ pub async fn run_migrations(pool: &PgPool) -> Result<(), sqlx::Error> {
             version INTEGER DEFAULT 1,
             is_deleted INTEGER DEFAULT 0,
             FOREIGN KEY (customer_id) REFERENCES customers(id) ON DELETE CASCADE
-        )"
-    )
-    .execute(pool)
-    .await?;
+        );
 
-    // Create proposals table
-    sqlx::query(
-        "CREATE TABLE IF NOT EXISTS proposals (
+        CREATE TABLE IF NOT EXISTS proposals (
             id TEXT PRIMARY KEY,
             company_id TEXT NOT NULL,
             proposal_number TEXT,
@@ -120,14 +104,9 @@ pub async fn run_migrations(pool: &PgPool) -> Result<(), sqlx::Error> {
             version INTEGER DEFAULT 1,
             is_deleted INTEGER DEFAULT 0,
             FOREIGN KEY (company_id) REFERENCES companies(id) ON DELETE CASCADE
-        )"
-    )
-    .execute(pool)
-    .await?;
+        );
 
-    // Create proposal_products table
-    sqlx::query(
-        "CREATE TABLE IF NOT EXISTS proposal_products (
+        CREATE TABLE IF NOT EXISTS proposal_products (
             id TEXT PRIMARY KEY,
             proposal_id TEXT NOT NULL,
             product_type TEXT NOT NULL,
@@ -149,25 +128,12 @@ pub async fn run_migrations(pool: &PgPool) -> Result<(), sqlx::Error> {
             version INTEGER DEFAULT 1,
             is_deleted INTEGER DEFAULT 0,
             FOREIGN KEY (proposal_id) REFERENCES proposals(id) ON DELETE CASCADE
-        )"
-    )
-    .execute(pool)
-    .await?;
-
-    // Create indexes for proposals
-    sqlx::query("CREATE INDEX IF NOT EXISTS idx_proposals_company_id ON proposals(company_id)")
-        .execute(pool)
-        .await?;
-    sqlx::query("CREATE INDEX IF NOT EXISTS idx_proposals_created_at ON proposals(created_at DESC)")
-        .execute(pool)
-        .await?;
-    sqlx::query("CREATE INDEX IF NOT EXISTS idx_proposal_products_proposal_id ON proposal_products(proposal_id)")
-        .execute(pool)
-        .await?;
+        );
+        CREATE INDEX IF NOT EXISTS idx_proposals_company_id ON proposals(company_id);
+        CREATE INDEX IF NOT EXISTS idx_proposals_created_at ON proposals(created_at DESC);
+        CREATE INDEX IF NOT EXISTS idx_proposal_products_proposal_id ON proposal_products(proposal_id);
 
-    // Create invoices table
-    sqlx::query(
-        "CREATE TABLE IF NOT EXISTS invoices (
+        CREATE TABLE IF NOT EXISTS invoices (
             id TEXT PRIMARY KEY,
             proposal_id TEXT NOT NULL,
             invoice_number TEXT UNIQUE NOT NULL,
@@ -186,74 +152,603 @@ pub async fn run_migrations(pool: &PgPool) -> Result<(), sqlx::Error> {
             version INTEGER DEFAULT 1,
             is_deleted INTEGER DEFAULT 0,
             FOREIGN KEY (proposal_id) REFERENCES proposals(id) ON DELETE CASCADE
+        );
+
+        -- Colonnes de synchronisation des bases antérieures
+        ALTER TABLE companies ADD COLUMN IF NOT EXISTS version INTEGER DEFAULT 1;
+        ALTER TABLE companies ADD COLUMN IF NOT EXISTS is_deleted INTEGER DEFAULT 0;
+        ALTER TABLE company_contacts ADD COLUMN IF NOT EXISTS version INTEGER DEFAULT 1;
+        ALTER TABLE company_contacts ADD COLUMN IF NOT EXISTS is_deleted INTEGER DEFAULT 0;
+        ALTER TABLE customers ADD COLUMN IF NOT EXISTS version INTEGER DEFAULT 1;
+        ALTER TABLE customers ADD COLUMN IF NOT EXISTS is_deleted INTEGER DEFAULT 0;
+        ALTER TABLE customers ADD COLUMN IF NOT EXISTS updated_at TIMESTAMPTZ DEFAULT NOW();
+        ALTER TABLE proposals ADD COLUMN IF NOT EXISTS version INTEGER DEFAULT 1;
+        ALTER TABLE proposals ADD COLUMN IF NOT EXISTS is_deleted INTEGER DEFAULT 0;
+        ALTER TABLE proposal_products ADD COLUMN IF NOT EXISTS version INTEGER DEFAULT 1;
+        ALTER TABLE proposal_products ADD COLUMN IF NOT EXISTS is_deleted INTEGER DEFAULT 0;
+        ALTER TABLE proposal_products ADD COLUMN IF NOT EXISTS updated_at TIMESTAMPTZ DEFAULT NOW();
+        ALTER TABLE invoices ADD COLUMN IF NOT EXISTS version INTEGER DEFAULT 1;
+        ALTER TABLE invoices ADD COLUMN IF NOT EXISTS is_deleted INTEGER DEFAULT 0;
+        ALTER TABLE documents ADD COLUMN IF NOT EXISTS version INTEGER DEFAULT 1;
+        ALTER TABLE documents ADD COLUMN IF NOT EXISTS is_deleted INTEGER DEFAULT 0;
+        ALTER TABLE documents ADD COLUMN IF NOT EXISTS updated_at TIMESTAMPTZ DEFAULT NOW();
+
+        CREATE INDEX IF NOT EXISTS idx_companies_updated_at ON companies(updated_at);
+        CREATE INDEX IF NOT EXISTS idx_companies_is_deleted ON companies(is_deleted);
+        CREATE INDEX IF NOT EXISTS idx_company_contacts_updated_at ON company_contacts(updated_at);
+        CREATE INDEX IF NOT EXISTS idx_company_contacts_is_deleted ON company_contacts(is_deleted);
+        CREATE INDEX IF NOT EXISTS idx_customers_updated_at ON customers(updated_at);
+        CREATE INDEX IF NOT EXISTS idx_customers_is_deleted ON customers(is_deleted);
+        CREATE INDEX IF NOT EXISTS idx_proposals_updated_at ON proposals(updated_at);
+        CREATE INDEX IF NOT EXISTS idx_proposals_is_deleted ON proposals(is_deleted);
+        CREATE INDEX IF NOT EXISTS idx_proposal_products_updated_at ON proposal_products(updated_at);
+        CREATE INDEX IF NOT EXISTS idx_proposal_products_is_deleted ON proposal_products(is_deleted);
+        CREATE INDEX IF NOT EXISTS idx_invoices_updated_at ON invoices(updated_at);
+        CREATE INDEX IF NOT EXISTS idx_invoices_is_deleted ON invoices(is_deleted);
+        CREATE INDEX IF NOT EXISTS idx_documents_updated_at ON documents(updated_at);
+        CREATE INDEX IF NOT EXISTS idx_documents_is_deleted ON documents(is_deleted);
+        ",
+        down: "
+        DROP TABLE IF EXISTS invoices;
+        DROP TABLE IF EXISTS proposal_products;
+        DROP TABLE IF EXISTS proposals;
+        DROP TABLE IF EXISTS documents;
+        DROP TABLE IF EXISTS customers;
+        DROP TABLE IF EXISTS company_contacts;
+        DROP TABLE IF EXISTS companies;
+        DROP TABLE IF EXISTS users;
+        ",
+    },
+    Migration {
+        version: 2,
+        name: "document_templates",
+        // Bibliothèque de modèles, fichier en base64
+        up: "
+        CREATE TABLE IF NOT EXISTS document_templates (
+            id TEXT PRIMARY KEY,
+            name TEXT NOT NULL,
+            kind TEXT NOT NULL,
+            product_type TEXT,
+            language TEXT,
+            company_id TEXT,
+            template_version INTEGER NOT NULL DEFAULT 1,
+            file_name TEXT NOT NULL,
+            content TEXT,
+            checksum TEXT NOT NULL,
+            created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+            updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+            version INTEGER DEFAULT 1,
+            is_deleted INTEGER DEFAULT 0
+        );
+        CREATE INDEX IF NOT EXISTS idx_document_templates_kind ON document_templates(kind, name);
+        CREATE INDEX IF NOT EXISTS idx_document_templates_updated_at ON document_templates(updated_at);
+        ",
+        down: "
+        DROP TABLE IF EXISTS document_templates;
+        ",
+    },
+    Migration {
+        version: 3,
+        name: "documents_company",
+        // Documents générés rattachés à une société plutôt qu'à un client
+        up: "
+        ALTER TABLE documents ADD COLUMN IF NOT EXISTS company_id TEXT;
+        ALTER TABLE documents ALTER COLUMN customer_id DROP NOT NULL;
+        ",
+        // Impossible tant que des documents n'ont pas de client : l'annulation
+        // échoue alors sans rien modifier plutôt que de les supprimer
+        down: "
+        DO $$
+        BEGIN
+            IF EXISTS (SELECT 1 FROM documents WHERE customer_id IS NULL) THEN
+                RAISE EXCEPTION 'Cannot revert documents_company: some documents have no customer_id';
+            END IF;
+        END $$;
+        ALTER TABLE documents ALTER COLUMN customer_id SET NOT NULL;
+        ALTER TABLE documents DROP COLUMN IF EXISTS company_id;
+        ",
+    },
+    Migration {
+        version: 4,
+        name: "documents_archive",
+        // Traçabilité des documents générés (origine, révision, empreinte)
+        up: "
+        ALTER TABLE documents ADD COLUMN IF NOT EXISTS source_type TEXT;
+        ALTER TABLE documents ADD COLUMN IF NOT EXISTS source_id TEXT;
+        ALTER TABLE documents ADD COLUMN IF NOT EXISTS revision BIGINT;
+        ALTER TABLE documents ADD COLUMN IF NOT EXISTS sha256 TEXT;
+        ",
+        down: "
+        ALTER TABLE documents DROP COLUMN IF EXISTS sha256;
+        ALTER TABLE documents DROP COLUMN IF EXISTS revision;
+        ALTER TABLE documents DROP COLUMN IF EXISTS source_id;
+        ALTER TABLE documents DROP COLUMN IF EXISTS source_type;
+        ",
+    },
+    Migration {
+        version: 5,
+        name: "blobs",
+        // Pièces jointes stockées sur disque, identifiées par leur SHA-256 ;
+        // `received` permet de reprendre un envoi interrompu
+        up: "
+        CREATE TABLE IF NOT EXISTS blobs (
+            sha256 TEXT PRIMARY KEY,
+            size BIGINT NOT NULL,
+            received BIGINT NOT NULL DEFAULT 0,
+            complete BOOLEAN NOT NULL DEFAULT FALSE,
+            created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+            updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+        );
+        ",
+        down: "
+        DROP TABLE IF EXISTS blobs;
+        ",
+    },
+    Migration {
+        version: 6,
+        name: "document_links",
+        // Rattachement polymorphe des documents (company, proposal, invoice ou
+        // customer) et reprise des rattachements portés par les colonnes de
+        // documents (mêmes identifiants que côté client :
+        // <entity_type>:<entity_id>:<document_id>)
+        up: "
+        CREATE TABLE IF NOT EXISTS document_links (
+            id TEXT PRIMARY KEY,
+            document_id TEXT NOT NULL,
+            entity_type TEXT NOT NULL,
+            entity_id TEXT NOT NULL,
+            created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+            updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+            version INTEGER DEFAULT 1,
+            is_deleted INTEGER DEFAULT 0
+        );
+        CREATE INDEX IF NOT EXISTS idx_document_links_entity ON document_links(entity_type, entity_id);
+        CREATE INDEX IF NOT EXISTS idx_document_links_updated_at ON document_links(updated_at);
+
+        INSERT INTO document_links (id, document_id, entity_type, entity_id, created_at, updated_at)
+            SELECT 'customer:' || customer_id || ':' || id, id, 'customer', customer_id, created_at, NOW()
+            FROM documents WHERE customer_id IS NOT NULL AND customer_id != ''
+            ON CONFLICT (id) DO NOTHING;
+        INSERT INTO document_links (id, document_id, entity_type, entity_id, created_at, updated_at)
+            SELECT 'company:' || company_id || ':' || id, id, 'company', company_id, created_at, NOW()
+            FROM documents WHERE company_id IS NOT NULL AND company_id != ''
+            ON CONFLICT (id) DO NOTHING;
+        INSERT INTO document_links (id, document_id, entity_type, entity_id, created_at, updated_at)
+            SELECT source_type || ':' || source_id || ':' || id, id, source_type, source_id, created_at, NOW()
+            FROM documents WHERE source_id IS NOT NULL AND source_id != '' AND source_type IS NOT NULL
+            ON CONFLICT (id) DO NOTHING;
+        ",
+        down: "
+        DROP TABLE IF EXISTS document_links;
+        ",
+    },
+    Migration {
+        version: 7,
+        name: "company_merges",
+        up: "
+        ALTER TABLE companies ADD COLUMN IF NOT EXISTS merged_into TEXT;
+        ",
+        down: "
+        ALTER TABLE companies DROP COLUMN IF EXISTS merged_into;
+        ",
+    },
+    Migration {
+        version: 8,
+        name: "activities",
+        // Appels, rendez-vous, e-mails et notes ; participants : liste JSON
+        // d'identifiants de company_contacts
+        up: "
+        CREATE TABLE IF NOT EXISTS activities (
+            id TEXT PRIMARY KEY,
+            company_id TEXT NOT NULL,
+            activity_type TEXT NOT NULL,
+            occurred_at TIMESTAMPTZ NOT NULL,
+            duration_minutes BIGINT,
+            subject TEXT,
+            notes TEXT,
+            proposal_id TEXT,
+            participants TEXT NOT NULL DEFAULT '[]',
+            created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+            updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+            version INTEGER DEFAULT 1,
+            is_deleted INTEGER DEFAULT 0
+        );
+        CREATE INDEX IF NOT EXISTS idx_activities_company ON activities(company_id, occurred_at);
+        CREATE INDEX IF NOT EXISTS idx_activities_updated_at ON activities(updated_at);
+        ",
+        down: "
+        DROP TABLE IF EXISTS activities;
+        ",
+    },
+    Migration {
+        version: 9,
+        name: "trash",
+        // Poste à l'origine d'une suppression, et postes clients :
+        // `acknowledged_until` est le dernier horodatage de synchronisation
+        // reçu, en millisecondes
+        up: "
+        ALTER TABLE companies ADD COLUMN IF NOT EXISTS deleted_by TEXT;
+        ALTER TABLE proposals ADD COLUMN IF NOT EXISTS deleted_by TEXT;
+        ALTER TABLE invoices ADD COLUMN IF NOT EXISTS deleted_by TEXT;
+        ALTER TABLE documents ADD COLUMN IF NOT EXISTS deleted_by TEXT;
+        CREATE TABLE IF NOT EXISTS sync_devices (
+            device_id TEXT PRIMARY KEY,
+            device_name TEXT,
+            acknowledged_until BIGINT NOT NULL DEFAULT 0,
+            last_seen TIMESTAMPTZ NOT NULL DEFAULT NOW()
+        );
+        ",
+        down: "
+        DROP TABLE IF EXISTS sync_devices;
+        ALTER TABLE documents DROP COLUMN IF EXISTS deleted_by;
+        ALTER TABLE invoices DROP COLUMN IF EXISTS deleted_by;
+        ALTER TABLE proposals DROP COLUMN IF EXISTS deleted_by;
+        ALTER TABLE companies DROP COLUMN IF EXISTS deleted_by;
+        ",
+    },
+    Migration {
+        version: 10,
+        name: "received_at",
+        // Horloge du serveur à chaque écriture d'une table synchronisée :
+        // `updated_at` vient des postes et peut être antérieur, les envois
+        // et la purge des suppressions se repèrent sur `received_at`. Le
+        // déclencheur s'exécute aussi en mode réplica
+        up: "
+        CREATE OR REPLACE FUNCTION stamp_received_at() RETURNS trigger AS $$
+        BEGIN
+            NEW.received_at := clock_timestamp();
+            RETURN NEW;
+        END;
+        $$ LANGUAGE plpgsql;
+
+        DO $$
+        DECLARE t TEXT;
+        BEGIN
+            FOREACH t IN ARRAY ARRAY['companies', 'company_contacts', 'customers', 'proposals', 'proposal_products',
+                                     'invoices', 'documents', 'document_templates', 'document_links', 'activities'] LOOP
+                IF NOT EXISTS (SELECT 1 FROM information_schema.columns
+                               WHERE table_schema = current_schema() AND table_name = t
+                                 AND column_name = 'received_at') THEN
+                    EXECUTE format('ALTER TABLE %I ADD COLUMN received_at TIMESTAMPTZ', t);
+                    EXECUTE format('UPDATE %I SET received_at = COALESCE(updated_at, created_at, NOW())', t);
+                    EXECUTE format('ALTER TABLE %I ALTER COLUMN received_at SET DEFAULT clock_timestamp(),
+                                    ALTER COLUMN received_at SET NOT NULL', t);
+                END IF;
+                EXECUTE format('CREATE INDEX IF NOT EXISTS %I ON %I(received_at)', 'idx_' || t || '_received_at', t);
+                EXECUTE format('DROP TRIGGER IF EXISTS %I ON %I', t || '_received_at', t);
+                EXECUTE format('CREATE TRIGGER %I BEFORE INSERT OR UPDATE ON %I
+                                FOR EACH ROW EXECUTE FUNCTION stamp_received_at()', t || '_received_at', t);
+                EXECUTE format('ALTER TABLE %I ENABLE ALWAYS TRIGGER %I', t, t || '_received_at');
+            END LOOP;
+        END $$;
+        ",
+        down: "
+        DO $$
+        DECLARE t TEXT;
+        BEGIN
+            FOREACH t IN ARRAY ARRAY['companies', 'company_contacts', 'customers', 'proposals', 'proposal_products',
+                                     'invoices', 'documents', 'document_templates', 'document_links', 'activities'] LOOP
+                EXECUTE format('DROP TRIGGER IF EXISTS %I ON %I', t || '_received_at', t);
+                EXECUTE format('ALTER TABLE %I DROP COLUMN IF EXISTS received_at', t);
+            END LOOP;
+        END $$;
+        DROP FUNCTION IF EXISTS stamp_received_at();
+        ",
+    },
+    Migration {
+        version: 11,
+        name: "canonical_time",
+        // Instants à la milliseconde, comme sur le fil (tronqués, pas
+        // arrondis) ; dates métier en DATE plutôt qu'à minuit UTC
+        up: "
+        ALTER TABLE companies
+            ALTER COLUMN created_at TYPE TIMESTAMPTZ(3) USING date_trunc('milliseconds', created_at),
+            ALTER COLUMN updated_at TYPE TIMESTAMPTZ(3) USING date_trunc('milliseconds', updated_at);
+        ALTER TABLE company_contacts
+            ALTER COLUMN created_at TYPE TIMESTAMPTZ(3) USING date_trunc('milliseconds', created_at),
+            ALTER COLUMN updated_at TYPE TIMESTAMPTZ(3) USING date_trunc('milliseconds', updated_at);
+        ALTER TABLE customers
+            ALTER COLUMN created_at TYPE TIMESTAMPTZ(3) USING date_trunc('milliseconds', created_at),
+            ALTER COLUMN updated_at TYPE TIMESTAMPTZ(3) USING date_trunc('milliseconds', updated_at);
+        ALTER TABLE proposals
+            ALTER COLUMN created_at TYPE TIMESTAMPTZ(3) USING date_trunc('milliseconds', created_at),
+            ALTER COLUMN updated_at TYPE TIMESTAMPTZ(3) USING date_trunc('milliseconds', updated_at),
+            ALTER COLUMN valid_until TYPE DATE USING (valid_until AT TIME ZONE 'UTC')::date;
+        ALTER TABLE proposal_products
+            ALTER COLUMN created_at TYPE TIMESTAMPTZ(3) USING date_trunc('milliseconds', created_at),
+            ALTER COLUMN updated_at TYPE TIMESTAMPTZ(3) USING date_trunc('milliseconds', updated_at);
+        ALTER TABLE invoices
+            ALTER COLUMN created_at TYPE TIMESTAMPTZ(3) USING date_trunc('milliseconds', created_at),
+            ALTER COLUMN updated_at TYPE TIMESTAMPTZ(3) USING date_trunc('milliseconds', updated_at),
+            ALTER COLUMN issue_date TYPE DATE USING (issue_date AT TIME ZONE 'UTC')::date,
+            ALTER COLUMN due_date TYPE DATE USING (due_date AT TIME ZONE 'UTC')::date,
+            ALTER COLUMN paid_date TYPE DATE USING (paid_date AT TIME ZONE 'UTC')::date,
+            ALTER COLUMN purchase_order_date TYPE DATE USING (purchase_order_date AT TIME ZONE 'UTC')::date;
+        ALTER TABLE documents
+            ALTER COLUMN created_at TYPE TIMESTAMPTZ(3) USING date_trunc('milliseconds', created_at),
+            ALTER COLUMN updated_at TYPE TIMESTAMPTZ(3) USING date_trunc('milliseconds', updated_at);
+        ALTER TABLE document_templates
+            ALTER COLUMN created_at TYPE TIMESTAMPTZ(3) USING date_trunc('milliseconds', created_at),
+            ALTER COLUMN updated_at TYPE TIMESTAMPTZ(3) USING date_trunc('milliseconds', updated_at);
+        ALTER TABLE document_links
+            ALTER COLUMN created_at TYPE TIMESTAMPTZ(3) USING date_trunc('milliseconds', created_at),
+            ALTER COLUMN updated_at TYPE TIMESTAMPTZ(3) USING date_trunc('milliseconds', updated_at);
+        ALTER TABLE activities
+            ALTER COLUMN occurred_at TYPE TIMESTAMPTZ(3) USING date_trunc('milliseconds', occurred_at),
+            ALTER COLUMN created_at TYPE TIMESTAMPTZ(3) USING date_trunc('milliseconds', created_at),
+            ALTER COLUMN updated_at TYPE TIMESTAMPTZ(3) USING date_trunc('milliseconds', updated_at);
+        ",
+        down: "
+        ALTER TABLE invoices
+            ALTER COLUMN issue_date TYPE TIMESTAMPTZ USING issue_date::timestamp AT TIME ZONE 'UTC',
+            ALTER COLUMN due_date TYPE TIMESTAMPTZ USING due_date::timestamp AT TIME ZONE 'UTC',
+            ALTER COLUMN paid_date TYPE TIMESTAMPTZ USING paid_date::timestamp AT TIME ZONE 'UTC',
+            ALTER COLUMN purchase_order_date TYPE TIMESTAMPTZ USING purchase_order_date::timestamp AT TIME ZONE 'UTC';
+        ALTER TABLE proposals
+            ALTER COLUMN valid_until TYPE TIMESTAMPTZ USING valid_until::timestamp AT TIME ZONE 'UTC';
+        ",
+    },
+    Migration {
+        version: 12,
+        name: "org_settings",
+        // Réglages communs à l'organisation, une ligne par clé ; `value` est
+        // un document JSON. Horodatée à la réception comme les autres tables
+        // synchronisées (voir `received_at`)
+        up: "
+        CREATE TABLE IF NOT EXISTS org_settings (
+            id TEXT PRIMARY KEY,
+            value TEXT NOT NULL,
+            created_at TIMESTAMPTZ(3) NOT NULL DEFAULT NOW(),
+            updated_at TIMESTAMPTZ(3) NOT NULL DEFAULT NOW(),
+            version INTEGER DEFAULT 1,
+            is_deleted INTEGER DEFAULT 0,
+            received_at TIMESTAMPTZ NOT NULL DEFAULT clock_timestamp()
+        );
+        CREATE INDEX IF NOT EXISTS idx_org_settings_updated_at ON org_settings(updated_at);
+        CREATE INDEX IF NOT EXISTS idx_org_settings_received_at ON org_settings(received_at);
+        DROP TRIGGER IF EXISTS org_settings_received_at ON org_settings;
+        CREATE TRIGGER org_settings_received_at BEFORE INSERT OR UPDATE ON org_settings
+            FOR EACH ROW EXECUTE FUNCTION stamp_received_at();
+        ALTER TABLE org_settings ENABLE ALWAYS TRIGGER org_settings_received_at;
+        ",
+        down: "
+        DROP TABLE IF EXISTS org_settings;
+        ",
+    },
+];
+
+// Clé du verrou consultatif tenu pendant une migration
+const MIGRATION_LOCK_KEY: i64 = 0x4352_4d5f_6d69_6772;
+
+impl Migration {
+    /// Empreinte SHA-256 du script `up`
+    pub fn checksum(&self) -> String {
+        hex::encode(Sha256::digest(self.up.as_bytes()))
+    }
+}
+
+/// Dernière version connue de ce binaire
+pub fn latest_version() -> i64 {
+    MIGRATIONS.last().map(|m| m.version).unwrap_or(0)
+}
+
+/// État d'une migration, connue du binaire ou seulement de la base
+#[derive(Debug)]
+pub struct MigrationStatus {
+    pub version: i64,
+    pub name: String,
+    pub applied_at: Option<DateTime<Utc>>,
+    /// "applied", "pending", "modified" (script changé depuis son
+    /// application) ou "unknown" (appliquée par un binaire plus récent)
+    pub state: &'static str,
+}
+
+async fn ensure_migrations_table(conn: &mut PgConnection) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        "CREATE TABLE IF NOT EXISTS schema_migrations (
+            version BIGINT PRIMARY KEY,
+            name TEXT NOT NULL,
+            checksum TEXT NOT NULL,
+            applied_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
         )"
     )
-    .execute(pool)
+    .execute(&mut *conn)
     .await?;
+    Ok(())
+}
 
-    // Add sync columns to existing tables (safe to run multiple times)
-    let _ = sqlx::query("ALTER TABLE companies ADD COLUMN IF NOT EXISTS version INTEGER DEFAULT 1").execute(pool).await;
-    let _ = sqlx::query("ALTER TABLE companies ADD COLUMN IF NOT EXISTS is_deleted INTEGER DEFAULT 0").execute(pool).await;
-    let _ = sqlx::query("ALTER TABLE company_contacts ADD COLUMN IF NOT EXISTS version INTEGER DEFAULT 1").execute(pool).await;
-    let _ = sqlx::query("ALTER TABLE company_contacts ADD COLUMN IF NOT EXISTS is_deleted INTEGER DEFAULT 0").execute(pool).await;
-    let _ = sqlx::query("ALTER TABLE customers ADD COLUMN IF NOT EXISTS version INTEGER DEFAULT 1").execute(pool).await;
-    let _ = sqlx::query("ALTER TABLE customers ADD COLUMN IF NOT EXISTS is_deleted INTEGER DEFAULT 0").execute(pool).await;
-    let _ = sqlx::query("ALTER TABLE customers ADD COLUMN IF NOT EXISTS updated_at TIMESTAMPTZ DEFAULT NOW()").execute(pool).await;
-    let _ = sqlx::query("ALTER TABLE proposals ADD COLUMN IF NOT EXISTS version INTEGER DEFAULT 1").execute(pool).await;
-    let _ = sqlx::query("ALTER TABLE proposals ADD COLUMN IF NOT EXISTS is_deleted INTEGER DEFAULT 0").execute(pool).await;
-    let _ = sqlx::query("ALTER TABLE proposal_products ADD COLUMN IF NOT EXISTS version INTEGER DEFAULT 1").execute(pool).await;
-    let _ = sqlx::query("ALTER TABLE proposal_products ADD COLUMN IF NOT EXISTS is_deleted INTEGER DEFAULT 0").execute(pool).await;
-    let _ = sqlx::query("ALTER TABLE proposal_products ADD COLUMN IF NOT EXISTS updated_at TIMESTAMPTZ DEFAULT NOW()").execute(pool).await;
-    let _ = sqlx::query("ALTER TABLE invoices ADD COLUMN IF NOT EXISTS version INTEGER DEFAULT 1").execute(pool).await;
-    let _ = sqlx::query("ALTER TABLE invoices ADD COLUMN IF NOT EXISTS is_deleted INTEGER DEFAULT 0").execute(pool).await;
-    let _ = sqlx::query("ALTER TABLE documents ADD COLUMN IF NOT EXISTS version INTEGER DEFAULT 1").execute(pool).await;
-    let _ = sqlx::query("ALTER TABLE documents ADD COLUMN IF NOT EXISTS is_deleted INTEGER DEFAULT 0").execute(pool).await;
-    let _ = sqlx::query("ALTER TABLE documents ADD COLUMN IF NOT EXISTS updated_at TIMESTAMPTZ DEFAULT NOW()").execute(pool).await;
-
-    // Create sync indexes
-    sqlx::query("CREATE INDEX IF NOT EXISTS idx_companies_updated_at ON companies(updated_at)")
-        .execute(pool)
-        .await?;
-    sqlx::query("CREATE INDEX IF NOT EXISTS idx_companies_is_deleted ON companies(is_deleted)")
-        .execute(pool)
-        .await?;
-    sqlx::query("CREATE INDEX IF NOT EXISTS idx_company_contacts_updated_at ON company_contacts(updated_at)")
-        .execute(pool)
-        .await?;
-    sqlx::query("CREATE INDEX IF NOT EXISTS idx_company_contacts_is_deleted ON company_contacts(is_deleted)")
-        .execute(pool)
-        .await?;
-    sqlx::query("CREATE INDEX IF NOT EXISTS idx_customers_updated_at ON customers(updated_at)")
-        .execute(pool)
-        .await?;
-    sqlx::query("CREATE INDEX IF NOT EXISTS idx_customers_is_deleted ON customers(is_deleted)")
-        .execute(pool)
-        .await?;
-    sqlx::query("CREATE INDEX IF NOT EXISTS idx_proposals_updated_at ON proposals(updated_at)")
-        .execute(pool)
-        .await?;
-    sqlx::query("CREATE INDEX IF NOT EXISTS idx_proposals_is_deleted ON proposals(is_deleted)")
-        .execute(pool)
-        .await?;
-    sqlx::query("CREATE INDEX IF NOT EXISTS idx_proposal_products_updated_at ON proposal_products(updated_at)")
-        .execute(pool)
-        .await?;
-    sqlx::query("CREATE INDEX IF NOT EXISTS idx_proposal_products_is_deleted ON proposal_products(is_deleted)")
-        .execute(pool)
-        .await?;
-    sqlx::query("CREATE INDEX IF NOT EXISTS idx_invoices_updated_at ON invoices(updated_at)")
-        .execute(pool)
-        .await?;
-    sqlx::query("CREATE INDEX IF NOT EXISTS idx_invoices_is_deleted ON invoices(is_deleted)")
-        .execute(pool)
-        .await?;
-    sqlx::query("CREATE INDEX IF NOT EXISTS idx_documents_updated_at ON documents(updated_at)")
-        .execute(pool)
-        .await?;
-    sqlx::query("CREATE INDEX IF NOT EXISTS idx_documents_is_deleted ON documents(is_deleted)")
-        .execute(pool)
+async fn applied_migrations(conn: &mut PgConnection) -> Result<Vec<(i64, String, String, DateTime<Utc>)>, sqlx::Error> {
+    sqlx::query_as("SELECT version, name, checksum, applied_at FROM schema_migrations ORDER BY version")
+        .fetch_all(&mut *conn)
+        .await
+}
+
+pub async fn migration_status(pool: &PgPool) -> Result<Vec<MigrationStatus>, sqlx::Error> {
+    let mut conn = pool.acquire().await?;
+    ensure_migrations_table(&mut conn).await?;
+    let applied = applied_migrations(&mut conn).await?;
+
+    let mut status: Vec<MigrationStatus> = MIGRATIONS
+        .iter()
+        .map(|migration| {
+            let record = applied.iter().find(|(version, _, _, _)| *version == migration.version);
+            MigrationStatus {
+                version: migration.version,
+                name: migration.name.to_string(),
+                applied_at: record.map(|(_, _, _, applied_at)| *applied_at),
+                state: match record {
+                    None => "pending",
+                    Some((_, _, checksum, _)) if *checksum != migration.checksum() => "modified",
+                    Some(_) => "applied",
+                },
+            }
+        })
+        .collect();
+    for (version, name, _, applied_at) in applied {
+        if !MIGRATIONS.iter().any(|m| m.version == version) {
+            status.push(MigrationStatus { version, name, applied_at: Some(applied_at), state: "unknown" });
+        }
+    }
+    Ok(status)
+}
+
+// Refuse une base migrée par un binaire plus récent ou dont une migration
+// appliquée ne correspond plus au script connu
+fn check_applied(applied: &[(i64, String, String, DateTime<Utc>)]) -> Result<(), sqlx::Error> {
+    let latest = latest_version();
+    if let Some((version, name, _, _)) = applied.iter().find(|(version, _, _, _)| *version > latest) {
+        return Err(sqlx::Error::Configuration(
+            format!(
+                "Database schema version {} ({}) is ahead of this binary (version {}); refusing to start",
+                version, name, latest
+            )
+            .into(),
+        ));
+    }
+    for (version, name, checksum, _) in applied {
+        let migration = MIGRATIONS.iter().find(|m| m.version == *version);
+        if migration.map(|m| m.checksum()).as_ref() != Some(checksum) {
+            return Err(sqlx::Error::Configuration(
+                format!("Migration {} ({}) was modified after being applied", version, name).into(),
+            ));
+        }
+    }
+    Ok(())
+}
+
+async fn lock(conn: &mut PgConnection) -> Result<(), sqlx::Error> {
+    println!("🔒 [Migrations] Waiting for migration lock");
+    sqlx::query("SELECT pg_advisory_lock($1)")
+        .bind(MIGRATION_LOCK_KEY)
+        .execute(&mut *conn)
         .await?;
+    Ok(())
+}
 
+async fn unlock(conn: &mut PgConnection) -> Result<(), sqlx::Error> {
+    sqlx::query("SELECT pg_advisory_unlock($1)")
+        .bind(MIGRATION_LOCK_KEY)
+        .execute(&mut *conn)
+        .await?;
     Ok(())
 }
 
+/// Applique les migrations en attente et retourne la version du schéma
+pub async fn run_migrations(pool: &PgPool) -> Result<i64, sqlx::Error> {
+    let mut conn = pool.acquire().await?;
+    lock(&mut conn).await?;
+    let result = migrate_up(&mut conn).await;
+    // Le verrou est lié à la session : il doit être rendu avant que la
+    // connexion retourne au pool
+    let unlocked = unlock(&mut conn).await;
+    let version = result?;
+    unlocked?;
+    Ok(version)
+}
+
+async fn migrate_up(conn: &mut PgConnection) -> Result<i64, sqlx::Error> {
+    ensure_migrations_table(conn).await?;
+    let applied = applied_migrations(conn).await?;
+    check_applied(&applied)?;
+
+    for migration in MIGRATIONS {
+        if applied.iter().any(|(version, _, _, _)| *version == migration.version) {
+            continue;
+        }
+        println!("📋 [Migrations] Applying {} {}", migration.version, migration.name);
+        let mut tx = sqlx::Connection::begin(&mut *conn).await?;
+        sqlx::raw_sql(migration.up).execute(&mut *tx).await?;
+        sqlx::query("INSERT INTO schema_migrations (version, name, checksum) VALUES ($1, $2, $3)")
+            .bind(migration.version)
+            .bind(migration.name)
+            .bind(migration.checksum())
+            .execute(&mut *tx)
+            .await?;
+        tx.commit().await?;
+    }
+    Ok(latest_version())
+}
+
+/// Annule les migrations appliquées au-delà de `target` (par défaut, la
+/// dernière) et retourne la nouvelle version du schéma
+pub async fn revert_migrations(pool: &PgPool, target: Option<i64>) -> Result<i64, sqlx::Error> {
+    let mut conn = pool.acquire().await?;
+    lock(&mut conn).await?;
+    let result = migrate_down(&mut conn, target).await;
+    let unlocked = unlock(&mut conn).await;
+    let version = result?;
+    unlocked?;
+    Ok(version)
+}
+
+// Cible par défaut de `migrate down` : l'avant-dernière version appliquée
+fn previous_version(applied: &[(i64, String, String, DateTime<Utc>)]) -> i64 {
+    applied.iter().rev().nth(1).map(|(version, _, _, _)| *version).unwrap_or(0)
+}
+
+async fn migrate_down(conn: &mut PgConnection, target: Option<i64>) -> Result<i64, sqlx::Error> {
+    ensure_migrations_table(conn).await?;
+    let applied = applied_migrations(conn).await?;
+    check_applied(&applied)?;
+
+    let current = applied.last().map(|(version, _, _, _)| *version).unwrap_or(0);
+    let target = target.unwrap_or_else(|| previous_version(&applied));
+
+    for (version, _, _, _) in applied.iter().rev().filter(|(version, _, _, _)| *version > target) {
+        let Some(migration) = MIGRATIONS.iter().find(|m| m.version == *version) else {
+            continue;
+        };
+        println!("📋 [Migrations] Reverting {} {}", migration.version, migration.name);
+        let mut tx = sqlx::Connection::begin(&mut *conn).await?;
+        sqlx::raw_sql(migration.down).execute(&mut *tx).await?;
+        sqlx::query("DELETE FROM schema_migrations WHERE version = $1")
+            .bind(migration.version)
+            .execute(&mut *tx)
+            .await?;
+        tx.commit().await?;
+    }
+    Ok(target.min(current))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn applied(versions: &[i64]) -> Vec<(i64, String, String, DateTime<Utc>)> {
+        versions
+            .iter()
+            .map(|version| {
+                let migration = MIGRATIONS.iter().find(|m| m.version == *version);
+                let (name, checksum) = match migration {
+                    Some(m) => (m.name.to_string(), m.checksum()),
+                    None => ("future".to_string(), "unknown".to_string()),
+                };
+                (*version, name, checksum, Utc::now())
+            })
+            .collect()
+    }
+
+    #[test]
+    fn applied_migrations_must_match_this_binary() {
+        let all: Vec<i64> = MIGRATIONS.iter().map(|m| m.version).collect();
+        assert!(check_applied(&applied(&all)).is_ok());
+        assert!(check_applied(&applied(&all[..2])).is_ok());
+
+        let ahead = check_applied(&applied(&[1, latest_version() + 1])).unwrap_err();
+        assert!(ahead.to_string().contains("is ahead of this binary"), "{}", ahead);
+
+        let mut modified = applied(&[1, 2]);
+        modified[1].2 = "0".repeat(64);
+        let modified = check_applied(&modified).unwrap_err();
+        assert!(modified.to_string().contains("Migration 2 (document_templates) was modified"), "{}", modified);
+    }
+
+    #[test]
+    fn down_reverts_the_last_applied_migration_by_default() {
+        assert_eq!(previous_version(&applied(&[1, 2, 3])), 2);
+        // Versions non contiguës : la précédente appliquée, pas version - 1
+        assert_eq!(previous_version(&applied(&[1, 4, 7])), 4);
+        assert_eq!(previous_version(&applied(&[1])), 0);
+        assert_eq!(previous_version(&[]), 0);
+    }
+}
@@ -103,10 +103,11 @@ pub async fn sync_document(
             };
 
             let result = sqlx::query(
-                "INSERT INTO documents (id, customer_id, title, document_type, file_path, content, created_at, updated_at)
-                 VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+                "INSERT INTO documents (id, customer_id, company_id, title, document_type, file_path, content, created_at, updated_at)
+                 VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)
                  ON CONFLICT (id) DO UPDATE SET
                     customer_id = EXCLUDED.customer_id,
+                    company_id = EXCLUDED.company_id,
                     title = EXCLUDED.title,
                     document_type = EXCLUDED.document_type,
                     file_path = EXCLUDED.file_path,
@@ -115,6 +116,7 @@ pub async fn sync_document(
             )
             .bind(&document.id)
             .bind(&document.customer_id)
+            .bind(&document.company_id)
             .bind(&document.title)
             .bind(&document.document_type)
             .bind(&document.file_path)
@@ -2,6 +2,8 @@ mod models;
 mod handlers;
 mod db;
 mod sync_service;
+mod blob_store;
+mod change_feed;
 
 use actix_web::{web, App, HttpServer, middleware::Logger};
 use actix_cors::Cors;
@@ -22,18 +24,30 @@ async fn main() -> std::io::Result<()> {
         .await
         .expect("Failed to connect to Postgres");
 
-    // Run migrations
+    // Sous-commande d'administration : `migrate status|up|down [version]`
+    let args: Vec<String> = env::args().skip(1).collect();
+    if args.first().map(String::as_str) == Some("migrate") {
+        return migrate_command(&pool, &args[1..]).await;
+    }
+
+    // Run migrations (refuse de démarrer si le schéma est en avance sur ce binaire)
     db::run_migrations(&pool)
         .await
         .expect("Failed to run migrations");
 
     println!("🚀 Server starting on http://0.0.0.0:8080");
 
+    // Partagé par tous les workers : une annonce atteint chaque poste connecté
+    let change_feed = web::Data::new(change_feed::ChangeFeed::new());
+
     HttpServer::new(move || {
         let cors = Cors::permissive();
 
         App::new()
             .app_data(web::Data::new(pool.clone()))
+            .app_data(change_feed.clone())
+            // Morceaux de pièces jointes (voir blob_store::MAX_CHUNK_SIZE)
+            .app_data(web::PayloadConfig::new(blob_store::MAX_CHUNK_SIZE))
             .wrap(cors)
             .wrap(Logger::default())
             .service(
@@ -41,6 +55,14 @@ async fn main() -> std::io::Result<()> {
                     .service(handlers::health_check)
                     // New unified sync endpoint
                     .route("/sync", web::post().to(sync_service::sync_all))
+                    .service(sync_service::unregister_device)
+                    // Annonce des modifications aux autres postes (Server-Sent Events)
+                    .service(change_feed::stream_changes)
+                    // Attachment storage (chunked upload/download)
+                    .service(blob_store::get_blob_status)
+                    .service(blob_store::start_blob_upload)
+                    .service(blob_store::upload_blob_chunk)
+                    .service(blob_store::download_blob_chunk)
                     // Legacy endpoints (kept for compatibility)
                     .service(handlers::sync_user)
                     .service(handlers::sync_company)
@@ -61,3 +83,38 @@ async fn main() -> std::io::Result<()> {
     .await
 }
 
+async fn migrate_command(pool: &sqlx::PgPool, args: &[String]) -> std::io::Result<()> {
+    let to_io = |e: sqlx::Error| std::io::Error::other(e.to_string());
+
+    match args.first().map(String::as_str) {
+        Some("status") => {
+            for migration in db::migration_status(pool).await.map_err(to_io)? {
+                let applied_at = migration
+                    .applied_at
+                    .map(|date| date.to_rfc3339())
+                    .unwrap_or_default();
+                println!("{:>4}  {:<24} {:<9} {}", migration.version, migration.name, migration.state, applied_at);
+            }
+            println!("Binary schema version: {}", db::latest_version());
+        }
+        Some("up") => {
+            let version = db::run_migrations(pool).await.map_err(to_io)?;
+            println!("✅ Schema at version {}", version);
+        }
+        Some("down") => {
+            let target = match args.get(1) {
+                Some(value) => Some(value.parse::<i64>().map_err(|_| {
+                    std::io::Error::new(std::io::ErrorKind::InvalidInput, format!("Invalid version: {}", value))
+                })?),
+                None => None,
+            };
+            let version = db::revert_migrations(pool, target).await.map_err(to_io)?;
+            println!("✅ Schema at version {}", version);
+        }
+        _ => {
+            eprintln!("Usage: crm-backend-api migrate <status|up|down [version]>");
+            return Err(std::io::Error::new(std::io::ErrorKind::InvalidInput, "Unknown migrate command"));
+        }
+    }
+    Ok(())
+}
@@ -52,7 +52,8 @@ pub struct Customer {
 #[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
 pub struct Document {
     pub id: String,
-    pub customer_id: String,
+    pub customer_id: Option<String>,
+    pub company_id: Option<String>,
     pub title: String,
     pub document_type: String,
     pub file_path: Option<String>,
@@ -88,7 +89,7 @@ pub struct Proposal {
     pub status: String,
     pub total_amount: Option<f64>,
     pub currency: String,
-    pub valid_until: Option<chrono::DateTime<chrono::Utc>>,
+    pub valid_until: Option<chrono::NaiveDate>,
     pub notes: Option<String>,
     pub created_at: chrono::DateTime<chrono::Utc>,
     pub updated_at: chrono::DateTime<chrono::Utc>,
@@ -122,11 +123,11 @@ pub struct Invoice {
     pub status: String,
     pub total_amount: f64,
     pub currency: String,
-    pub issue_date: chrono::DateTime<chrono::Utc>,
-    pub due_date: Option<chrono::DateTime<chrono::Utc>>,
-    pub paid_date: Option<chrono::DateTime<chrono::Utc>>,
+    pub issue_date: chrono::NaiveDate,
+    pub due_date: Option<chrono::NaiveDate>,
+    pub paid_date: Option<chrono::NaiveDate>,
     pub purchase_order: Option<String>,
-    pub purchase_order_date: Option<chrono::DateTime<chrono::Utc>>,
+    pub purchase_order_date: Option<chrono::NaiveDate>,
     pub commercial_in_charge: Option<String>,
     pub notes: Option<String>,
     pub created_at: chrono::DateTime<chrono::Utc>,
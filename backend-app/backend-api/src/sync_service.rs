@@ -1,657 +1,349 @@
-use actix_web::{web, HttpResponse, Result};
-use serde::{Deserialize, Serialize};
+use actix_web::{delete, http::StatusCode, web, HttpResponse, Responder, Result};
+use crate::change_feed::ChangeFeed;
+use chrono::{DateTime, NaiveDate, Utc};
+use crm_schema::tables::Company;
+use crm_schema::{ColumnType, Dialect, Entity, Record, Value, ENTITIES, PROTOCOL_VERSION};
+use sqlx::postgres::PgRow;
 use sqlx::{PgPool, Row};
-use chrono::{DateTime, Utc};
-
-#[derive(Debug, Serialize, Deserialize)]
-pub struct SyncItem {
-    pub table_name: String,
-    pub id: String,
-    pub data: serde_json::Value,
-    pub version: i64,
-    pub is_deleted: bool,
-    pub updated_at: i64,
-}
-
-#[derive(Debug, Serialize, Deserialize)]
-pub struct SyncRequest {
-    pub last_sync_timestamp: i64,
-    pub changes: Vec<SyncItem>,
-}
 
-#[derive(Debug, Serialize, Deserialize)]
-pub struct SyncResponse {
-    pub success: bool,
-    pub message: String,
-    pub remote_changes: Vec<SyncItem>,
-    pub new_timestamp: i64,
-    pub items_synced: i64,
-}
+pub use crm_schema::{SyncItem, SyncItemError, SyncRequest, SyncResponse};
 
 // Helper function to get current timestamp in milliseconds
 fn current_timestamp() -> i64 {
     chrono::Utc::now().timestamp_millis()
 }
 
-// Helper function to apply sync item to PostgreSQL
-async fn apply_sync_item_to_server(pool: &PgPool, item: &SyncItem) -> Result<(), sqlx::Error> {
-    if item.is_deleted {
-        // Delete the item
-        match item.table_name.as_str() {
-            "companies" => {
-                sqlx::query("DELETE FROM companies WHERE id = $1")
-                    .bind(&item.id)
-                    .execute(pool)
-                    .await?;
-            }
-            "company_contacts" => {
-                sqlx::query("DELETE FROM company_contacts WHERE id = $1")
-                    .bind(&item.id)
-                    .execute(pool)
-                    .await?;
-            }
-            "customers" => {
-                sqlx::query("DELETE FROM customers WHERE id = $1")
-                    .bind(&item.id)
-                    .execute(pool)
-                    .await?;
-            }
-            "proposals" => {
-                sqlx::query("DELETE FROM proposals WHERE id = $1")
-                    .bind(&item.id)
-                    .execute(pool)
-                    .await?;
-            }
-            "proposal_products" => {
-                sqlx::query("DELETE FROM proposal_products WHERE id = $1")
-                    .bind(&item.id)
-                    .execute(pool)
-                    .await?;
-            }
-            "invoices" => {
-                sqlx::query("DELETE FROM invoices WHERE id = $1")
-                    .bind(&item.id)
-                    .execute(pool)
-                    .await?;
-            }
-            "documents" => {
-                sqlx::query("DELETE FROM documents WHERE id = $1")
-                    .bind(&item.id)
-                    .execute(pool)
-                    .await?;
-            }
-            _ => {
-                return Err(sqlx::Error::Protocol("Unknown table name".to_string()));
-            }
-        }
-    } else {
-        // Insert or update the item
-        match item.table_name.as_str() {
-            "companies" => {
-                if let Ok(company) = serde_json::from_value::<serde_json::Value>(item.data.clone()) {
-                    sqlx::query(
-                        "INSERT INTO companies (id, name, website, address, city, postal_code, country, description, created_at, updated_at, version, is_deleted) 
-                         VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12)
-                         ON CONFLICT (id) DO UPDATE SET
-                         name = EXCLUDED.name,
-                         website = EXCLUDED.website,
-                         address = EXCLUDED.address,
-                         city = EXCLUDED.city,
-                         postal_code = EXCLUDED.postal_code,
-                         country = EXCLUDED.country,
-                         description = EXCLUDED.description,
-                         updated_at = EXCLUDED.updated_at,
-                         version = EXCLUDED.version,
-                         is_deleted = EXCLUDED.is_deleted"
-                    )
-                    .bind(&item.id)
-                    .bind(company.get("name").and_then(|v| v.as_str()).unwrap_or(""))
-                    .bind(company.get("website").and_then(|v| v.as_str()))
-                    .bind(company.get("address").and_then(|v| v.as_str()))
-                    .bind(company.get("city").and_then(|v| v.as_str()))
-                    .bind(company.get("postal_code").and_then(|v| v.as_str()))
-                    .bind(company.get("country").and_then(|v| v.as_str()))
-                    .bind(company.get("description").and_then(|v| v.as_str()))
-                    .bind(company.get("created_at").and_then(|v| v.as_str()).and_then(|s| s.parse::<i64>().ok()).and_then(|ts| DateTime::<Utc>::from_timestamp_millis(ts)).unwrap_or_else(|| Utc::now()))
-                    .bind(company.get("updated_at").and_then(|v| v.as_str()).and_then(|s| s.parse::<i64>().ok()).and_then(|ts| DateTime::<Utc>::from_timestamp_millis(ts)).unwrap_or_else(|| Utc::now()))
-                    .bind(item.version)
-                    .bind(item.is_deleted as i32)
-                    .execute(pool)
-                    .await?;
-                }
-            }
-            "company_contacts" => {
-                if let Ok(contact) = serde_json::from_value::<serde_json::Value>(item.data.clone()) {
-                    sqlx::query(
-                        "INSERT INTO company_contacts (id, company_id, first_name, last_name, email, phone_number, is_primary, created_at, updated_at, version, is_deleted) 
-                         VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11)
-                         ON CONFLICT (id) DO UPDATE SET
-                         company_id = EXCLUDED.company_id,
-                         first_name = EXCLUDED.first_name,
-                         last_name = EXCLUDED.last_name,
-                         email = EXCLUDED.email,
-                         phone_number = EXCLUDED.phone_number,
-                         is_primary = EXCLUDED.is_primary,
-                         updated_at = EXCLUDED.updated_at,
-                         version = EXCLUDED.version,
-                         is_deleted = EXCLUDED.is_deleted"
-                    )
-                    .bind(&item.id)
-                    .bind(contact.get("company_id").and_then(|v| v.as_str()).unwrap_or(""))
-                    .bind(contact.get("first_name").and_then(|v| v.as_str()).unwrap_or(""))
-                    .bind(contact.get("last_name").and_then(|v| v.as_str()).unwrap_or(""))
-                    .bind(contact.get("email").and_then(|v| v.as_str()).unwrap_or(""))
-                    .bind(contact.get("phone_number").and_then(|v| v.as_str()))
-                    .bind(contact.get("is_primary").and_then(|v| v.as_i64()).unwrap_or(0) != 0)
-                    .bind(contact.get("created_at").and_then(|v| v.as_str()).and_then(|s| s.parse::<i64>().ok()).and_then(|ts| DateTime::<Utc>::from_timestamp_millis(ts)).unwrap_or_else(|| Utc::now()))
-                    .bind(contact.get("updated_at").and_then(|v| v.as_str()).and_then(|s| s.parse::<i64>().ok()).and_then(|ts| DateTime::<Utc>::from_timestamp_millis(ts)).unwrap_or_else(|| Utc::now()))
-                    .bind(item.version)
-                    .bind(item.is_deleted as i32)
-                    .execute(pool)
-                    .await?;
-                }
-            }
-            "customers" => {
-                if let Ok(customer) = serde_json::from_value::<serde_json::Value>(item.data.clone()) {
-                    sqlx::query(
-                        "INSERT INTO customers (id, name, email, phone, address, created_at, updated_at, version, is_deleted) 
-                         VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)
-                         ON CONFLICT (id) DO UPDATE SET
-                         name = EXCLUDED.name,
-                         email = EXCLUDED.email,
-                         phone = EXCLUDED.phone,
-                         address = EXCLUDED.address,
-                         updated_at = EXCLUDED.updated_at,
-                         version = EXCLUDED.version,
-                         is_deleted = EXCLUDED.is_deleted"
-                    )
-                    .bind(&item.id)
-                    .bind(customer.get("name").and_then(|v| v.as_str()).unwrap_or(""))
-                    .bind(customer.get("email").and_then(|v| v.as_str()))
-                    .bind(customer.get("phone").and_then(|v| v.as_str()))
-                    .bind(customer.get("address").and_then(|v| v.as_str()))
-                    .bind(customer.get("created_at").and_then(|v| v.as_str()).and_then(|s| s.parse::<i64>().ok()).and_then(|ts| DateTime::<Utc>::from_timestamp_millis(ts)).unwrap_or_else(|| Utc::now()))
-                    .bind(customer.get("updated_at").and_then(|v| v.as_str()).and_then(|s| s.parse::<i64>().ok()).and_then(|ts| DateTime::<Utc>::from_timestamp_millis(ts)).unwrap_or_else(|| Utc::now()))
-                    .bind(item.version)
-                    .bind(item.is_deleted as i32)
-                    .execute(pool)
-                    .await?;
-                }
-            }
-            "proposals" => {
-                if let Ok(proposal) = serde_json::from_value::<serde_json::Value>(item.data.clone()) {
-                    sqlx::query(
-                        "INSERT INTO proposals (id, company_id, proposal_number, status, total_amount, currency, valid_until, notes, created_at, updated_at, version, is_deleted) 
-                         VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12)
-                         ON CONFLICT (id) DO UPDATE SET
-                         company_id = EXCLUDED.company_id,
-                         proposal_number = EXCLUDED.proposal_number,
-                         status = EXCLUDED.status,
-                         total_amount = EXCLUDED.total_amount,
-                         currency = EXCLUDED.currency,
-                         valid_until = EXCLUDED.valid_until,
-                         notes = EXCLUDED.notes,
-                         updated_at = EXCLUDED.updated_at,
-                         version = EXCLUDED.version,
-                         is_deleted = EXCLUDED.is_deleted"
-                    )
-                    .bind(&item.id)
-                    .bind(proposal.get("company_id").and_then(|v| v.as_str()).unwrap_or(""))
-                    .bind(proposal.get("proposal_number").and_then(|v| v.as_str()))
-                    .bind(proposal.get("status").and_then(|v| v.as_str()).unwrap_or("DRAFT"))
-                    .bind(proposal.get("total_amount").and_then(|v| v.as_f64()).unwrap_or(0.0))
-                    .bind(proposal.get("currency").and_then(|v| v.as_str()).unwrap_or("USD"))
-                    .bind(proposal.get("valid_until").and_then(|v| v.as_str()).and_then(|s| s.parse::<i64>().ok()).and_then(|ts| DateTime::<Utc>::from_timestamp_millis(ts)))
-                    .bind(proposal.get("notes").and_then(|v| v.as_str()))
-                    .bind(proposal.get("created_at").and_then(|v| v.as_str()).and_then(|s| s.parse::<i64>().ok()).and_then(|ts| DateTime::<Utc>::from_timestamp_millis(ts)).unwrap_or_else(|| Utc::now()))
-                    .bind(proposal.get("updated_at").and_then(|v| v.as_str()).and_then(|s| s.parse::<i64>().ok()).and_then(|ts| DateTime::<Utc>::from_timestamp_millis(ts)).unwrap_or_else(|| Utc::now()))
-                    .bind(item.version)
-                    .bind(item.is_deleted as i32)
-                    .execute(pool)
-                    .await?;
-                }
-            }
-            "proposal_products" => {
-                if let Ok(product) = serde_json::from_value::<serde_json::Value>(item.data.clone()) {
-                    sqlx::query(
-                        "INSERT INTO proposal_products (id, proposal_id, product_type, user_count, standalone_count, server_key_count, unit_price, total_price, annual_reduction, training, training_days, training_cost_per_day, training_cost, licence, support, support_years, updated_at, version, is_deleted) 
-                         VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16, $17, $18, $19)
-                         ON CONFLICT (id) DO UPDATE SET
-                         proposal_id = EXCLUDED.proposal_id,
-                         product_type = EXCLUDED.product_type,
-                         user_count = EXCLUDED.user_count,
-                         standalone_count = EXCLUDED.standalone_count,
-                         server_key_count = EXCLUDED.server_key_count,
-                         unit_price = EXCLUDED.unit_price,
-                         total_price = EXCLUDED.total_price,
-                         annual_reduction = EXCLUDED.annual_reduction,
-                         training = EXCLUDED.training,
-                         training_days = EXCLUDED.training_days,
-                         training_cost_per_day = EXCLUDED.training_cost_per_day,
-                         training_cost = EXCLUDED.training_cost,
-                         licence = EXCLUDED.licence,
-                         support = EXCLUDED.support,
-                         support_years = EXCLUDED.support_years,
-                         updated_at = EXCLUDED.updated_at,
-                         version = EXCLUDED.version,
-                         is_deleted = EXCLUDED.is_deleted"
-                    )
-                    .bind(&item.id)
-                    .bind(product.get("proposal_id").and_then(|v| v.as_str()).unwrap_or(""))
-                    .bind(product.get("product_type").and_then(|v| v.as_str()).unwrap_or(""))
-                    .bind(product.get("user_count").and_then(|v| v.as_i64()).unwrap_or(0))
-                    .bind(product.get("standalone_count").and_then(|v| v.as_i64()).unwrap_or(0))
-                    .bind(product.get("server_key_count").and_then(|v| v.as_i64()).unwrap_or(0))
-                    .bind(product.get("unit_price").and_then(|v| v.as_f64()).unwrap_or(0.0))
-                    .bind(product.get("total_price").and_then(|v| v.as_f64()).unwrap_or(0.0))
-                    .bind(product.get("annual_reduction").and_then(|v| v.as_f64()).unwrap_or(0.0))
-                    .bind(product.get("training").and_then(|v| v.as_i64()).unwrap_or(0))
-                    .bind(product.get("training_days").and_then(|v| v.as_i64()).unwrap_or(0))
-                    .bind(product.get("training_cost_per_day").and_then(|v| v.as_f64()).unwrap_or(0.0))
-                    .bind(product.get("training_cost").and_then(|v| v.as_f64()).unwrap_or(0.0))
-                    .bind(product.get("licence").and_then(|v| v.as_i64()).unwrap_or(0))
-                    .bind(product.get("support").and_then(|v| v.as_i64()).unwrap_or(0))
-                    .bind(product.get("support_years").and_then(|v| v.as_i64()).unwrap_or(0))
-                    .bind(product.get("updated_at").and_then(|v| v.as_str()).and_then(|s| s.parse::<i64>().ok()).and_then(|ts| DateTime::<Utc>::from_timestamp_millis(ts)).unwrap_or_else(|| Utc::now()))
-                    .bind(item.version)
-                    .bind(item.is_deleted as i32)
-                    .execute(pool)
-                    .await?;
-                }
-            }
-            "invoices" => {
-                if let Ok(invoice) = serde_json::from_value::<serde_json::Value>(item.data.clone()) {
-                    sqlx::query(
-                        "INSERT INTO invoices (id, proposal_id, invoice_number, status, total_amount, currency, issue_date, due_date, paid_date, purchase_order, purchase_order_date, commercial_in_charge, notes, created_at, updated_at, version, is_deleted) 
-                         VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16, $17)
-                         ON CONFLICT (id) DO UPDATE SET
-                         proposal_id = EXCLUDED.proposal_id,
-                         invoice_number = EXCLUDED.invoice_number,
-                         status = EXCLUDED.status,
-                         total_amount = EXCLUDED.total_amount,
-                         currency = EXCLUDED.currency,
-                         issue_date = EXCLUDED.issue_date,
-                         due_date = EXCLUDED.due_date,
-                         paid_date = EXCLUDED.paid_date,
-                         purchase_order = EXCLUDED.purchase_order,
-                         purchase_order_date = EXCLUDED.purchase_order_date,
-                         commercial_in_charge = EXCLUDED.commercial_in_charge,
-                         notes = EXCLUDED.notes,
-                         updated_at = EXCLUDED.updated_at,
-                         version = EXCLUDED.version,
-                         is_deleted = EXCLUDED.is_deleted"
-                    )
-                    .bind(&item.id)
-                    .bind(invoice.get("proposal_id").and_then(|v| v.as_str()).unwrap_or(""))
-                    .bind(invoice.get("invoice_number").and_then(|v| v.as_str()).unwrap_or(""))
-                    .bind(invoice.get("status").and_then(|v| v.as_str()).unwrap_or("DRAFT"))
-                    .bind(invoice.get("total_amount").and_then(|v| v.as_f64()).unwrap_or(0.0))
-                    .bind(invoice.get("currency").and_then(|v| v.as_str()).unwrap_or("USD"))
-                    .bind(invoice.get("issue_date").and_then(|v| v.as_str()).unwrap_or("").parse::<i64>().ok().and_then(|ts| DateTime::<Utc>::from_timestamp_millis(ts)))
-                    .bind(invoice.get("due_date").and_then(|v| v.as_str()).and_then(|s| s.parse::<i64>().ok()).and_then(|ts| DateTime::<Utc>::from_timestamp_millis(ts)))
-                    .bind(invoice.get("paid_date").and_then(|v| v.as_str()).and_then(|s| s.parse::<i64>().ok()).and_then(|ts| DateTime::<Utc>::from_timestamp_millis(ts)))
-                    .bind(invoice.get("purchase_order").and_then(|v| v.as_str()))
-                    .bind(invoice.get("purchase_order_date").and_then(|v| v.as_str()).and_then(|s| s.parse::<i64>().ok()).and_then(|ts| DateTime::<Utc>::from_timestamp_millis(ts)))
-                    .bind(invoice.get("commercial_in_charge").and_then(|v| v.as_str()))
-                    .bind(invoice.get("notes").and_then(|v| v.as_str()))
-                    .bind(invoice.get("created_at").and_then(|v| v.as_str()).and_then(|s| s.parse::<i64>().ok()).and_then(|ts| DateTime::<Utc>::from_timestamp_millis(ts)).unwrap_or_else(|| Utc::now()))
-                    .bind(invoice.get("updated_at").and_then(|v| v.as_str()).and_then(|s| s.parse::<i64>().ok()).and_then(|ts| DateTime::<Utc>::from_timestamp_millis(ts)).unwrap_or_else(|| Utc::now()))
-                    .bind(item.version)
-                    .bind(item.is_deleted as i32)
-                    .execute(pool)
-                    .await?;
-                }
-            }
-            "documents" => {
-                if let Ok(document) = serde_json::from_value::<serde_json::Value>(item.data.clone()) {
-                    sqlx::query(
-                        "INSERT INTO documents (id, customer_id, title, document_type, file_path, content, created_at, updated_at, version, is_deleted) 
-                         VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)
-                         ON CONFLICT (id) DO UPDATE SET
-                         customer_id = EXCLUDED.customer_id,
-                         title = EXCLUDED.title,
-                         document_type = EXCLUDED.document_type,
-                         file_path = EXCLUDED.file_path,
-                         content = EXCLUDED.content,
-                         updated_at = EXCLUDED.updated_at,
-                         version = EXCLUDED.version,
-                         is_deleted = EXCLUDED.is_deleted"
-                    )
-                    .bind(&item.id)
-                    .bind(document.get("customer_id").and_then(|v| v.as_str()).unwrap_or(""))
-                    .bind(document.get("title").and_then(|v| v.as_str()).unwrap_or(""))
-                    .bind(document.get("document_type").and_then(|v| v.as_str()).unwrap_or(""))
-                    .bind(document.get("file_path").and_then(|v| v.as_str()))
-                    .bind(document.get("content").and_then(|v| v.as_str()))
-                    .bind(document.get("created_at").and_then(|v| v.as_str()).and_then(|s| s.parse::<i64>().ok()).and_then(|ts| DateTime::<Utc>::from_timestamp_millis(ts)).unwrap_or_else(|| Utc::now()))
-                    .bind(document.get("updated_at").and_then(|v| v.as_str()).and_then(|s| s.parse::<i64>().ok()).and_then(|ts| DateTime::<Utc>::from_timestamp_millis(ts)).unwrap_or_else(|| Utc::now()))
-                    .bind(item.version)
-                    .bind(item.is_deleted as i32)
-                    .execute(pool)
-                    .await?;
-                }
-            }
-            _ => {
-                return Err(sqlx::Error::Protocol("Unknown table name".to_string()));
-            }
-        }
+// `received_at` en millisecondes, arrondi vers le bas comme les points de
+// départ des postes
+const RECEIVED_AT_MILLIS: &str = "FLOOR(EXTRACT(EPOCH FROM received_at) * 1000)::BIGINT";
+
+// Rattache à la société survivante les enregistrements d'une société
+// absorbée, y compris ceux envoyés par des postes qui ignoraient la fusion.
+// Tout ou rien : une redirection partielle laisserait des enfants orphelins.
+async fn redirect_merged_company(pool: &PgPool, merged_id: &str, survivor_id: &str) -> Result<(), sqlx::Error> {
+    let mut tx = pool.begin().await?;
+    for table in ["company_contacts", "proposals", "documents", "document_templates", "activities"] {
+        sqlx::query(&format!(
+            "UPDATE {} SET company_id = $1, updated_at = NOW(), version = COALESCE(version, 0) + 1 WHERE company_id = $2",
+            table
+        ))
+        .bind(survivor_id)
+        .bind(merged_id)
+        .execute(&mut *tx)
+        .await?;
     }
-    Ok(())
-}
 
-// Helper function to get changed items from server since timestamp
-async fn get_server_changes_since(pool: &PgPool, since_timestamp: i64) -> Result<Vec<SyncItem>, sqlx::Error> {
-    let mut items = Vec::new();
-    
-    // Get companies
-    let companies = sqlx::query(
-        "SELECT * FROM companies WHERE EXTRACT(EPOCH FROM updated_at) * 1000 > $1 ORDER BY EXTRACT(EPOCH FROM updated_at) * 1000 ASC"
+    // Comme sur le poste : l'identifiant d'un rattachement contient la
+    // société, nouveau lien vers le survivant et suppression de l'ancien
+    sqlx::query(
+        "INSERT INTO document_links (id, document_id, entity_type, entity_id, created_at, updated_at, version, is_deleted)
+         SELECT 'company:' || $1 || ':' || document_id, document_id, 'company', $1, created_at, NOW(), 1, 0
+         FROM document_links WHERE entity_type = 'company' AND entity_id = $2 AND COALESCE(is_deleted, 0) = 0
+         ON CONFLICT (id) DO UPDATE SET is_deleted = 0, updated_at = NOW(), version = COALESCE(document_links.version, 0) + 1"
     )
-    .bind(since_timestamp)
-    .fetch_all(pool)
+    .bind(survivor_id)
+    .bind(merged_id)
+    .execute(&mut *tx)
     .await?;
-
-    for row in companies {
-        let updated_at: chrono::DateTime<chrono::Utc> = row.get("updated_at");
-        let updated_at_ts = updated_at.timestamp_millis();
-        let version: i32 = row.get("version");
-        let is_deleted: i32 = row.get("is_deleted");
-        
-        items.push(SyncItem {
-            table_name: "companies".to_string(),
-            id: row.get("id"),
-            data: serde_json::json!({
-                "id": row.get::<String, _>("id"),
-                "name": row.get::<String, _>("name"),
-                "website": row.get::<Option<String>, _>("website"),
-                "address": row.get::<Option<String>, _>("address"),
-                "city": row.get::<Option<String>, _>("city"),
-                "postal_code": row.get::<Option<String>, _>("postal_code"),
-                "country": row.get::<Option<String>, _>("country"),
-                "description": row.get::<Option<String>, _>("description"),
-                "created_at": row.get::<chrono::DateTime<chrono::Utc>, _>("created_at").to_rfc3339(),
-                "updated_at": row.get::<chrono::DateTime<chrono::Utc>, _>("updated_at").to_rfc3339(),
-                "version": row.get::<i32, _>("version"),
-                "is_deleted": row.get::<i32, _>("is_deleted")
-            }),
-            version: version as i64,
-            is_deleted: is_deleted != 0,
-            updated_at: updated_at_ts,
-        });
-    }
-
-    // Get company_contacts
-    let contacts = sqlx::query(
-        "SELECT * FROM company_contacts WHERE EXTRACT(EPOCH FROM updated_at) * 1000 > $1 ORDER BY EXTRACT(EPOCH FROM updated_at) * 1000 ASC"
+    sqlx::query(
+        "UPDATE document_links SET is_deleted = 1, updated_at = NOW(), version = COALESCE(version, 0) + 1
+         WHERE entity_type = 'company' AND entity_id = $1 AND COALESCE(is_deleted, 0) = 0"
     )
-    .bind(since_timestamp)
-    .fetch_all(pool)
+    .bind(merged_id)
+    .execute(&mut *tx)
     .await?;
 
-    for row in contacts {
-        let updated_at: chrono::DateTime<chrono::Utc> = row.get("updated_at");
-        let updated_at_ts = updated_at.timestamp_millis();
-        let version: i32 = row.get("version");
-        let is_deleted: i32 = row.get("is_deleted");
-        
-        items.push(SyncItem {
-            table_name: "company_contacts".to_string(),
-            id: row.get("id"),
-            data: serde_json::json!({
-                "id": row.get::<String, _>("id"),
-                "company_id": row.get::<String, _>("company_id"),
-                "first_name": row.get::<String, _>("first_name"),
-                "last_name": row.get::<String, _>("last_name"),
-                "email": row.get::<String, _>("email"),
-                "phone_number": row.get::<Option<String>, _>("phone_number"),
-                "is_primary": row.get::<bool, _>("is_primary") as i64,
-                "created_at": row.get::<chrono::DateTime<chrono::Utc>, _>("created_at").to_rfc3339(),
-                "updated_at": row.get::<chrono::DateTime<chrono::Utc>, _>("updated_at").to_rfc3339(),
-                "version": row.get::<i32, _>("version"),
-                "is_deleted": row.get::<i32, _>("is_deleted")
-            }),
-            version: version as i64,
-            is_deleted: is_deleted != 0,
-            updated_at: updated_at_ts,
-        });
-    }
+    tx.commit().await
+}
 
-    // Get customers
-    let customers = sqlx::query(
-        "SELECT * FROM customers WHERE EXTRACT(EPOCH FROM updated_at) * 1000 > $1 ORDER BY EXTRACT(EPOCH FROM updated_at) * 1000 ASC"
+// Tables purgées de leurs suppressions, enfants d'abord : un parent n'est
+// purgé que si plus aucune ligne ne le référence
+const TOMBSTONE_PURGE: &[(&str, &str)] = &[
+    ("document_links", ""),
+    ("activities", ""),
+    ("company_contacts", ""),
+    ("proposal_products", ""),
+    ("invoices", ""),
+    ("documents", ""),
+    ("document_templates", ""),
+    ("org_settings", ""),
+    ("proposals", "AND NOT EXISTS (SELECT 1 FROM proposal_products c WHERE c.proposal_id = t.id)
+     AND NOT EXISTS (SELECT 1 FROM invoices c WHERE c.proposal_id = t.id)"),
+    ("customers", "AND NOT EXISTS (SELECT 1 FROM documents c WHERE c.customer_id = t.id)"),
+    // Les sociétés fusionnées restent : leur redirection sert aux postes en retard
+    ("companies", "AND t.merged_into IS NULL
+     AND NOT EXISTS (SELECT 1 FROM company_contacts c WHERE c.company_id = t.id)
+     AND NOT EXISTS (SELECT 1 FROM proposals c WHERE c.company_id = t.id)"),
+];
+
+// Enregistre le poste et le dernier horodatage qu'il a reçu
+async fn acknowledge_device(pool: &PgPool, device_id: &str, device_name: Option<&str>, acknowledged_until: i64) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        "INSERT INTO sync_devices (device_id, device_name, acknowledged_until, last_seen)
+         VALUES ($1, $2, $3, NOW())
+         ON CONFLICT (device_id) DO UPDATE SET
+         device_name = COALESCE(EXCLUDED.device_name, sync_devices.device_name),
+         acknowledged_until = GREATEST(sync_devices.acknowledged_until, EXCLUDED.acknowledged_until),
+         last_seen = NOW()"
     )
-    .bind(since_timestamp)
-    .fetch_all(pool)
+    .bind(device_id)
+    .bind(device_name)
+    .bind(acknowledged_until)
+    .execute(pool)
     .await?;
+    Ok(())
+}
 
-    for row in customers {
-        let updated_at: chrono::DateTime<chrono::Utc> = row.get("updated_at");
-        let updated_at_ts = updated_at.timestamp_millis();
-        let version: i32 = row.get("version");
-        let is_deleted: i32 = row.get("is_deleted");
-        
-        items.push(SyncItem {
-            table_name: "customers".to_string(),
-            id: row.get("id"),
-            data: serde_json::json!({
-                "id": row.get::<String, _>("id"),
-                "name": row.get::<String, _>("name"),
-                "email": row.get::<Option<String>, _>("email"),
-                "phone": row.get::<Option<String>, _>("phone"),
-                "address": row.get::<Option<String>, _>("address"),
-                "created_at": row.get::<chrono::DateTime<chrono::Utc>, _>("created_at").to_rfc3339(),
-                "updated_at": row.get::<chrono::DateTime<chrono::Utc>, _>("updated_at").to_rfc3339(),
-                "version": row.get::<i32, _>("version"),
-                "is_deleted": row.get::<i32, _>("is_deleted")
-            }),
-            version: version as i64,
-            is_deleted: is_deleted != 0,
-            updated_at: updated_at_ts,
-        });
-    }
+// Nombre de jours lu dans la variable d'environnement `name`
+fn env_days(name: &str, default: i64) -> i64 {
+    std::env::var(name)
+        .ok()
+        .and_then(|v| v.parse::<i64>().ok())
+        .unwrap_or(default)
+}
 
-    // Get proposals
-    let proposals = sqlx::query(
-        "SELECT * FROM proposals WHERE EXTRACT(EPOCH FROM updated_at) * 1000 > $1 ORDER BY EXTRACT(EPOCH FROM updated_at) * 1000 ASC"
+// Supprime définitivement les suppressions plus anciennes que la durée de
+// conservation (TOMBSTONE_RETENTION_DAYS, 30 jours par défaut) et reçues
+// par tous les postes actifs. Un poste sans synchronisation depuis
+// SYNC_DEVICE_EXPIRY_DAYS (90 jours par défaut) est considéré comme retiré
+// et ne bloque plus la purge.
+async fn purge_acknowledged_tombstones(pool: &PgPool) -> Result<u64, sqlx::Error> {
+    let retention_days = env_days("TOMBSTONE_RETENTION_DAYS", 30);
+    let expiry_days = env_days("SYNC_DEVICE_EXPIRY_DAYS", 90);
+    let acknowledged: Option<i64> = sqlx::query_scalar(
+        "SELECT MIN(acknowledged_until) FROM sync_devices WHERE last_seen > NOW() - make_interval(days => $1)"
     )
-    .bind(since_timestamp)
-    .fetch_all(pool)
+    .bind(expiry_days as i32)
+    .fetch_one(pool)
     .await?;
+    let Some(acknowledged) = acknowledged else {
+        return Ok(0);
+    };
+    // Repère serveur (`received_at`) : une suppression antidatée par le
+    // poste reste gardée jusqu'à ce que tous l'aient reçue, et rien de ce
+    // que la requête en cours vient d'écrire ne peut être purgé
+    let purge_before = acknowledged.min(current_timestamp() - retention_days * 24 * 3600 * 1000);
 
-    for row in proposals {
-        let updated_at: chrono::DateTime<chrono::Utc> = row.get("updated_at");
-        let updated_at_ts = updated_at.timestamp_millis();
-        let version: i32 = row.get("version");
-        let is_deleted: i32 = row.get("is_deleted");
-        
-        items.push(SyncItem {
-            table_name: "proposals".to_string(),
-            id: row.get("id"),
-            data: serde_json::json!({
-                "id": row.get::<String, _>("id"),
-                "company_id": row.get::<String, _>("company_id"),
-                "proposal_number": row.get::<Option<String>, _>("proposal_number"),
-                "status": row.get::<String, _>("status"),
-                "total_amount": row.get::<f64, _>("total_amount"),
-                "currency": row.get::<String, _>("currency"),
-                "valid_until": row.get::<Option<String>, _>("valid_until"),
-                "notes": row.get::<Option<String>, _>("notes"),
-                "created_at": row.get::<chrono::DateTime<chrono::Utc>, _>("created_at").to_rfc3339(),
-                "updated_at": row.get::<chrono::DateTime<chrono::Utc>, _>("updated_at").to_rfc3339(),
-                "version": row.get::<i32, _>("version"),
-                "is_deleted": row.get::<i32, _>("is_deleted")
-            }),
-            version: version as i64,
-            is_deleted: is_deleted != 0,
-            updated_at: updated_at_ts,
-        });
+    let mut purged = 0;
+    for (table, condition) in TOMBSTONE_PURGE {
+        purged += sqlx::query(&format!(
+            "DELETE FROM {} t WHERE t.is_deleted = 1 AND t.received_at < to_timestamp($1::FLOAT8 / 1000) {}",
+            table, condition
+        ))
+        .bind(purge_before)
+        .execute(pool)
+        .await?
+        .rows_affected();
     }
+    Ok(purged)
+}
 
-    // Get proposal_products
-    let products = sqlx::query(
-        "SELECT * FROM proposal_products WHERE EXTRACT(EPOCH FROM updated_at) * 1000 > $1 ORDER BY EXTRACT(EPOCH FROM updated_at) * 1000 ASC"
-    )
-    .bind(since_timestamp)
-    .fetch_all(pool)
-    .await?;
+// Lit une ligne Postgres selon la définition partagée des colonnes
+fn read_record(entity: &Entity, row: &PgRow) -> Result<Record, sqlx::Error> {
+    entity
+        .columns
+        .iter()
+        .map(|column| {
+            let name = column.name;
+            let value = match column.ty {
+                ColumnType::Text => row.try_get::<Option<String>, _>(name)?.map(Value::Text),
+                ColumnType::Integer => row.try_get::<Option<i64>, _>(name)?.map(Value::Integer),
+                ColumnType::Real => row.try_get::<Option<f64>, _>(name)?.map(Value::Real),
+                ColumnType::Bool => row.try_get::<Option<bool>, _>(name)?.map(Value::Bool),
+                ColumnType::Timestamp => row
+                    .try_get::<Option<DateTime<Utc>>, _>(name)?
+                    .map(|dt| Value::Timestamp(dt.into())),
+                ColumnType::Date => row.try_get::<Option<NaiveDate>, _>(name)?.map(|d| Value::Date(d.into())),
+                ColumnType::Json => row
+                    .try_get::<Option<String>, _>(name)?
+                    .map(|s| Value::Json(serde_json::from_str(&s).unwrap_or(serde_json::Value::String(s)))),
+            };
+            Ok(value.unwrap_or(Value::Null))
+        })
+        .collect()
+}
 
-    for row in products {
-        let updated_at: chrono::DateTime<chrono::Utc> = row.get("updated_at");
-        let updated_at_ts = updated_at.timestamp_millis();
-        let version: i32 = row.get("version");
-        let is_deleted: i32 = row.get("is_deleted");
-        
-        items.push(SyncItem {
-            table_name: "proposal_products".to_string(),
-            id: row.get("id"),
-            data: serde_json::json!({
-                "id": row.get::<String, _>("id"),
-                "proposal_id": row.get::<String, _>("proposal_id"),
-                "product_type": row.get::<String, _>("product_type"),
-                "user_count": row.get::<i32, _>("user_count") as i64,
-                "standalone_count": row.get::<i32, _>("standalone_count") as i64,
-                "server_key_count": row.get::<i32, _>("server_key_count") as i64,
-                "unit_price": row.get::<f64, _>("unit_price"),
-                "total_price": row.get::<f64, _>("total_price"),
-                "annual_reduction": row.get::<f64, _>("annual_reduction"),
-                "training": row.get::<i32, _>("training") as i64,
-                "training_days": row.get::<i32, _>("training_days") as i64,
-                "training_cost_per_day": row.get::<f64, _>("training_cost_per_day"),
-                "training_cost": row.get::<f64, _>("training_cost"),
-                "licence": row.get::<i32, _>("licence") as i64,
-                "support": row.get::<i32, _>("support") as i64,
-                "support_years": row.get::<i32, _>("support_years") as i64,
-                "created_at": row.get::<chrono::DateTime<chrono::Utc>, _>("updated_at").to_rfc3339(),
-                "updated_at": row.get::<chrono::DateTime<chrono::Utc>, _>("updated_at").to_rfc3339(),
-                "version": row.get::<i32, _>("version"),
-                "is_deleted": row.get::<i32, _>("is_deleted")
-            }),
-            version: version as i64,
-            is_deleted: is_deleted != 0,
-            updated_at: updated_at_ts,
-        });
+// Écrit (ou met à jour) un enregistrement selon la définition partagée et
+// retourne l'horodatage serveur de sa réception, en millisecondes
+async fn upsert_record(pool: &PgPool, entity: &Entity, record: &[Value]) -> Result<i64, sqlx::Error> {
+    let sql = format!("{} RETURNING {}", entity.upsert_sql(Dialect::Postgres), RECEIVED_AT_MILLIS);
+    let mut query = sqlx::query(&sql);
+    for (column, value) in entity.columns.iter().zip(record) {
+        query = match column.ty {
+            ColumnType::Text => query.bind(value.as_str().map(str::to_string)),
+            ColumnType::Json => query.bind(value.to_sqlite_text()),
+            ColumnType::Integer => query.bind(value.as_i64()),
+            ColumnType::Real => query.bind(value.as_f64()),
+            ColumnType::Bool => query.bind(value.as_bool()),
+            ColumnType::Timestamp => query.bind(value.as_timestamp().map(|ts| ts.to_datetime())),
+            ColumnType::Date => query.bind(value.as_date().map(|d| d.naive())),
+        };
     }
+    query.fetch_one(pool).await?.try_get(0)
+}
 
-    // Get invoices
-    let invoices = sqlx::query(
-        "SELECT * FROM invoices WHERE EXTRACT(EPOCH FROM updated_at) * 1000 > $1 ORDER BY EXTRACT(EPOCH FROM updated_at) * 1000 ASC"
-    )
-    .bind(since_timestamp)
-    .fetch_all(pool)
-    .await?;
+// Helper function to apply sync item to PostgreSQL
+async fn apply_sync_item_to_server(pool: &PgPool, item: &SyncItem) -> Result<i64, sqlx::Error> {
+    // Les suppressions sont conservées (is_deleted) pour être transmises aux
+    // autres postes ; elles sont purgées une fois reçues par tous
+    let received_at = upsert_record(pool, item.entity(), &item.clone().into_record()).await?;
 
-    for row in invoices {
-        let updated_at: String = row.get("updated_at");
-        let updated_at_ts = updated_at.parse::<i64>().unwrap_or(0);
-        let version: i32 = row.get("version");
-        let is_deleted: i32 = row.get("is_deleted");
-        
-        items.push(SyncItem {
-            table_name: "invoices".to_string(),
-            id: row.get("id"),
-            data: serde_json::json!({
-                "id": row.get::<String, _>("id"),
-                "proposal_id": row.get::<String, _>("proposal_id"),
-                "invoice_number": row.get::<String, _>("invoice_number"),
-                "status": row.get::<String, _>("status"),
-                "total_amount": row.get::<f64, _>("total_amount"),
-                "currency": row.get::<String, _>("currency"),
-                "issue_date": row.get::<String, _>("issue_date"),
-                "due_date": row.get::<Option<String>, _>("due_date"),
-                "paid_date": row.get::<Option<String>, _>("paid_date"),
-                "purchase_order": row.get::<Option<String>, _>("purchase_order"),
-                "purchase_order_date": row.get::<Option<String>, _>("purchase_order_date"),
-                "commercial_in_charge": row.get::<Option<String>, _>("commercial_in_charge"),
-                "notes": row.get::<Option<String>, _>("notes"),
-                "created_at": row.get::<chrono::DateTime<chrono::Utc>, _>("created_at").to_rfc3339(),
-                "updated_at": row.get::<chrono::DateTime<chrono::Utc>, _>("updated_at").to_rfc3339(),
-                "version": row.get::<i32, _>("version"),
-                "is_deleted": row.get::<i32, _>("is_deleted")
-            }),
-            version: version as i64,
-            is_deleted: is_deleted != 0,
-            updated_at: updated_at_ts,
-        });
+    // Une société fusionnée reste en base, supprimée et redirigée, pour que
+    // les autres postes reçoivent la redirection
+    if let SyncItem::Company(Company { id, merged_into: Some(survivor_id), .. }) = item {
+        redirect_merged_company(pool, id, survivor_id).await?;
+        println!("🔀 [ServerSync] Company {} merged into {}", id, survivor_id);
     }
+    Ok(received_at)
+}
 
-    // Get documents
-    let documents = sqlx::query(
-        "SELECT * FROM documents WHERE EXTRACT(EPOCH FROM updated_at) * 1000 > $1 ORDER BY EXTRACT(EPOCH FROM updated_at) * 1000 ASC"
-    )
-    .bind(since_timestamp)
-    .fetch_all(pool)
-    .await?;
+// Helper function to get changed items from server since timestamp. Les
+// lignes sont choisies sur `received_at`, et non sur le `updated_at` fourni
+// par les postes dont l'horloge peut retarder ; l'horodatage retourné, pris
+// sur l'horloge de la base avant la lecture, est le prochain point de départ
+async fn get_server_changes_since(pool: &PgPool, since_timestamp: i64) -> Result<(Vec<SyncItem>, i64), sqlx::Error> {
+    let mut items = Vec::new();
+    let new_timestamp: i64 = sqlx::query_scalar("SELECT FLOOR(EXTRACT(EPOCH FROM clock_timestamp()) * 1000)::BIGINT")
+        .fetch_one(pool)
+        .await?;
+
+    for entity in ENTITIES {
+        let rows = sqlx::query(&format!(
+            "SELECT {} FROM {} WHERE received_at > to_timestamp($1::FLOAT8 / 1000) ORDER BY received_at ASC",
+            entity.select_list(Dialect::Postgres),
+            entity.table
+        ))
+        .bind(since_timestamp)
+        .fetch_all(pool)
+        .await?;
 
-    for row in documents {
-        let updated_at: String = row.get("updated_at");
-        let updated_at_ts = updated_at.parse::<i64>().unwrap_or(0);
-        let version: i32 = row.get("version");
-        let is_deleted: i32 = row.get("is_deleted");
-        
-        items.push(SyncItem {
-            table_name: "documents".to_string(),
-            id: row.get("id"),
-            data: serde_json::json!({
-                "id": row.get::<String, _>("id"),
-                "customer_id": row.get::<String, _>("customer_id"),
-                "title": row.get::<String, _>("title"),
-                "document_type": row.get::<String, _>("document_type"),
-                "file_path": row.get::<Option<String>, _>("file_path"),
-                "content": row.get::<Option<String>, _>("content"),
-                "created_at": row.get::<chrono::DateTime<chrono::Utc>, _>("created_at").to_rfc3339(),
-                "updated_at": row.get::<chrono::DateTime<chrono::Utc>, _>("updated_at").to_rfc3339(),
-                "version": row.get::<i32, _>("version"),
-                "is_deleted": row.get::<i32, _>("is_deleted")
-            }),
-            version: version as i64,
-            is_deleted: is_deleted != 0,
-            updated_at: updated_at_ts,
-        });
+        for row in rows {
+            let item = SyncItem::from_record(entity, read_record(entity, &row)?)
+                .map_err(|e| sqlx::Error::Decode(format!("{}: {}", entity.table, e).into()))?;
+            items.push(item);
+        }
     }
 
-    Ok(items)
+    Ok((items, new_timestamp))
 }
 
-pub async fn sync_all(pool: web::Data<PgPool>, request: web::Json<SyncRequest>) -> Result<HttpResponse> {
+/// Retire un poste de la liste de synchronisation (poste réformé) : ses
+/// accusés de réception ne retiennent plus la purge des suppressions
+#[delete("/sync/devices/{device_id}")]
+pub async fn unregister_device(pool: web::Data<PgPool>, device_id: web::Path<String>) -> impl Responder {
+    let result = sqlx::query("DELETE FROM sync_devices WHERE device_id = $1")
+        .bind(device_id.as_str())
+        .execute(pool.get_ref())
+        .await;
+    match result {
+        Ok(done) if done.rows_affected() == 0 => HttpResponse::NotFound().body("Device not found"),
+        Ok(_) => {
+            println!("🗑️ [ServerSync] Device {} unregistered", device_id);
+            HttpResponse::NoContent().finish()
+        }
+        Err(e) => HttpResponse::InternalServerError().body(format!("Failed to unregister device: {}", e)),
+    }
+}
+
+pub async fn sync_all(
+    pool: web::Data<PgPool>,
+    feed: web::Data<ChangeFeed>,
+    request: web::Json<SyncRequest>,
+) -> Result<HttpResponse> {
     println!("🔄 [ServerSync] Received sync request with {} changes", request.changes.len());
-    
+
+    // Un poste d'une autre version lirait ou enverrait des données mal formées
+    if request.protocol_version != PROTOCOL_VERSION {
+        let (status, message) = if request.protocol_version < PROTOCOL_VERSION {
+            (
+                StatusCode::UPGRADE_REQUIRED,
+                format!(
+                    "Upgrade required: this client uses sync protocol {} but the server requires {}. Please update the application.",
+                    request.protocol_version, PROTOCOL_VERSION
+                ),
+            )
+        } else {
+            (
+                StatusCode::BAD_REQUEST,
+                format!(
+                    "Server upgrade required: this client uses sync protocol {} but the server only supports {}.",
+                    request.protocol_version, PROTOCOL_VERSION
+                ),
+            )
+        };
+        eprintln!("❌ [ServerSync] {}", message);
+        return Ok(HttpResponse::build(status).json(SyncResponse {
+            protocol_version: PROTOCOL_VERSION,
+            success: false,
+            message,
+            remote_changes: vec![],
+            new_timestamp: request.last_sync_timestamp,
+            items_synced: 0,
+            errors: vec![],
+            upgrade_required: request.protocol_version < PROTOCOL_VERSION,
+        }));
+    }
+
     let mut items_synced = 0;
-    let mut errors = Vec::new();
-    
+    let mut errors: Vec<SyncItemError> = Vec::new();
+
     // Apply client changes to server
-    for item in &request.changes {
-        match apply_sync_item_to_server(&pool, item).await {
-            Ok(_) => {
+    for envelope in &request.changes {
+        let item = match envelope.parse() {
+            Ok(item) => item,
+            Err(invalid) => {
+                eprintln!("❌ [ServerSync] Rejected {}", invalid);
+                errors.extend(invalid.0);
+                continue;
+            }
+        };
+        match apply_sync_item_to_server(&pool, &item).await {
+            Ok(received_at) => {
                 items_synced += 1;
-                println!("✅ [ServerSync] Applied client change: {} {}", item.table_name, item.id);
+                println!("✅ [ServerSync] Applied client change: {} {}", envelope.table_name, envelope.id);
+                feed.publish(&envelope.table_name, &envelope.id, request.device_id.as_deref(), received_at);
             }
             Err(e) => {
-                let error_msg = format!("Failed to apply {} {}: {}", item.table_name, item.id, e);
-                errors.push(error_msg.clone());
-                eprintln!("❌ [ServerSync] {}", error_msg);
+                let error = SyncItemError::item(envelope, format!("Failed to apply: {}", e));
+                eprintln!("❌ [ServerSync] {}", error);
+                errors.push(error);
             }
         }
     }
     
     // Get server changes since last sync
-    let remote_changes = match get_server_changes_since(&pool, request.last_sync_timestamp).await {
-        Ok(changes) => {
+    let (remote_changes, new_timestamp) = match get_server_changes_since(&pool, request.last_sync_timestamp).await {
+        Ok((changes, new_timestamp)) => {
             println!("🔄 [ServerSync] Found {} server changes", changes.len());
-            changes
+            (changes, new_timestamp)
         }
         Err(e) => {
             eprintln!("❌ [ServerSync] Failed to get server changes: {}", e);
             return Ok(HttpResponse::InternalServerError().json(SyncResponse {
+                protocol_version: PROTOCOL_VERSION,
                 success: false,
                 message: format!("Failed to get server changes: {}", e),
                 remote_changes: vec![],
                 new_timestamp: request.last_sync_timestamp,
                 items_synced: 0,
+                errors,
+                upgrade_required: false,
             }));
         }
     };
     
-    let new_timestamp = current_timestamp();
+    // Le poste a reçu tout ce qui précède sa dernière synchronisation
+    if let Some(device_id) = request.device_id.as_deref() {
+        if let Err(e) = acknowledge_device(&pool, device_id, request.device_name.as_deref(), request.last_sync_timestamp).await {
+            eprintln!("❌ [ServerSync] Failed to record device {}: {}", device_id, e);
+        }
+        match purge_acknowledged_tombstones(&pool).await {
+            Ok(0) => {}
+            Ok(purged) => println!("🗑️ [ServerSync] Purged {} acknowledged deletions", purged),
+            Err(e) => eprintln!("❌ [ServerSync] Failed to purge deletions: {}", e),
+        }
+    }
+
     let success = errors.is_empty();
     let message = if success {
         format!("Synchronization completed successfully. {} items synced.", items_synced)
@@ -663,10 +355,270 @@ pub async fn sync_all(pool: web::Data<PgPool>, request: web::Json<SyncRequest>)
              success, items_synced, remote_changes.len());
     
     Ok(HttpResponse::Ok().json(SyncResponse {
+        protocol_version: PROTOCOL_VERSION,
         success,
         message,
         remote_changes,
         new_timestamp,
         items_synced,
+        errors,
+        upgrade_required: false,
     }))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crm_schema::Timestamp;
+    use sqlx::postgres::PgPoolOptions;
+    use sqlx::Executor;
+
+    // Base de test fournie par TEST_DATABASE_URL. Une seule connexion,
+    // contraintes désactivées (superutilisateur requis) : chaque entité est
+    // écrite seule avec des identifiants fictifs
+    async fn open() -> PgPool {
+        let url = std::env::var("TEST_DATABASE_URL").expect("TEST_DATABASE_URL must be set");
+        let pool = PgPoolOptions::new()
+            .max_connections(1)
+            .after_connect(|conn, _| {
+                Box::pin(async move {
+                    conn.execute("SET session_replication_role = replica").await?;
+                    Ok(())
+                })
+            })
+            .connect(&url)
+            .await
+            .unwrap();
+        crate::db::run_migrations(&pool).await.unwrap();
+        pool
+    }
+
+    #[tokio::test]
+    #[ignore = "requires TEST_DATABASE_URL"]
+    async fn server_columns_match_shared_schema() {
+        let pool = open().await;
+        for entity in ENTITIES {
+            let columns: Vec<(String, String)> = sqlx::query_as(
+                "SELECT column_name::TEXT, data_type::TEXT FROM information_schema.columns
+                 WHERE table_schema = current_schema() AND table_name = $1"
+            )
+            .bind(entity.table)
+            .fetch_all(&pool)
+            .await
+            .unwrap();
+            for column in entity.columns {
+                let declared = columns.iter().find(|(name, _)| name == column.name).map(|(_, ty)| ty.as_str());
+                assert!(
+                    declared.is_some_and(|ty| column.ty.storage_types(Dialect::Postgres).contains(&ty)),
+                    "{}.{} declared as {:?}",
+                    entity.table,
+                    column.name,
+                    declared
+                );
+            }
+        }
+    }
+
+    #[tokio::test]
+    #[ignore = "requires TEST_DATABASE_URL"]
+    async fn every_entity_round_trips_through_postgres() {
+        let pool = open().await;
+        let suffix = uuid::Uuid::new_v4().to_string();
+        for entity in ENTITIES {
+            let id = format!("{}-{}", entity.table, suffix);
+            let mut record = entity.sample_record(&id);
+            // Pas de redirection de fusion pour cet aller-retour
+            if let Some(i) = entity.column_index("merged_into") {
+                record[i] = Value::Null;
+            }
+            let item = SyncItem::from_record(entity, record).unwrap();
+            apply_sync_item_to_server(&pool, &item).await.unwrap();
+
+            let (emitted, _) = get_server_changes_since(&pool, 0).await.unwrap();
+            let back = emitted.iter().find(|i| i.entity() == entity && i.id() == id).unwrap();
+            assert_eq!(back, &item, "{}", entity.table);
+
+            sqlx::query(&format!("DELETE FROM {} WHERE id = $1", entity.table))
+                .bind(&id)
+                .execute(&pool)
+                .await
+                .unwrap();
+        }
+    }
+
+    #[tokio::test]
+    #[ignore = "requires TEST_DATABASE_URL"]
+    async fn merged_company_children_and_links_are_redirected() {
+        let pool = open().await;
+        let suffix = uuid::Uuid::new_v4().to_string();
+        let (merged, survivor, contact, document) =
+            (format!("m-{}", suffix), format!("s-{}", suffix), format!("ct-{}", suffix), format!("d-{}", suffix));
+        sqlx::query("INSERT INTO company_contacts (id, company_id, first_name, last_name, email) VALUES ($1, $2, 'A', 'B', 'a@b.test')")
+            .bind(&contact)
+            .bind(&merged)
+            .execute(&pool)
+            .await
+            .unwrap();
+        sqlx::query("INSERT INTO document_links (id, document_id, entity_type, entity_id) VALUES ($1, $2, 'company', $3)")
+            .bind(format!("company:{}:{}", merged, document))
+            .bind(&document)
+            .bind(&merged)
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        redirect_merged_company(&pool, &merged, &survivor).await.unwrap();
+
+        let company_id: String = sqlx::query_scalar("SELECT company_id FROM company_contacts WHERE id = $1")
+            .bind(&contact)
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        assert_eq!(company_id, survivor);
+        let links: Vec<(String, i32)> = sqlx::query_as(
+            "SELECT entity_id, is_deleted FROM document_links WHERE document_id = $1 ORDER BY is_deleted"
+        )
+        .bind(&document)
+        .fetch_all(&pool)
+        .await
+        .unwrap();
+        assert_eq!(links, [(survivor.clone(), 0), (merged.clone(), 1)]);
+
+        sqlx::query("DELETE FROM company_contacts WHERE id = $1").bind(&contact).execute(&pool).await.unwrap();
+        sqlx::query("DELETE FROM document_links WHERE document_id = $1").bind(&document).execute(&pool).await.unwrap();
+    }
+
+    // Antidate la réception d'une ligne, déclencheur suspendu le temps de
+    // l'écriture
+    async fn backdate_received_at(pool: &PgPool, table: &str, id: &str, days: i32) {
+        let mut tx = pool.begin().await.unwrap();
+        for sql in [
+            format!("ALTER TABLE {} DISABLE TRIGGER {}_received_at", table, table),
+            format!("UPDATE {} SET received_at = NOW() - make_interval(days => $1) WHERE id = $2", table),
+            format!("ALTER TABLE {} ENABLE ALWAYS TRIGGER {}_received_at", table, table),
+        ] {
+            sqlx::query(&sql).bind(days).bind(id).execute(&mut *tx).await.unwrap();
+        }
+        tx.commit().await.unwrap();
+    }
+
+    async fn sync(pool: &PgPool, device_id: &str, last_sync_timestamp: i64, changes: Vec<SyncItem>) -> SyncResponse {
+        let request = SyncRequest {
+            protocol_version: PROTOCOL_VERSION,
+            last_sync_timestamp,
+            changes: changes.into_iter().map(Into::into).collect(),
+            device_id: Some(device_id.to_string()),
+            device_name: None,
+        };
+        let response = sync_all(web::Data::new(pool.clone()), web::Data::new(ChangeFeed::new()), web::Json(request))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = actix_web::body::to_bytes(response.into_body()).await.unwrap();
+        serde_json::from_slice(&body).unwrap()
+    }
+
+    #[actix_web::test]
+    #[ignore = "requires TEST_DATABASE_URL"]
+    async fn backdated_tombstones_reach_other_devices() {
+        let pool = open().await;
+        let suffix = uuid::Uuid::new_v4().to_string();
+        let (link, pusher, puller) = (format!("l-{}", suffix), format!("a-{}", suffix), format!("b-{}", suffix));
+        sqlx::query("DELETE FROM sync_devices").execute(&pool).await.unwrap();
+        let since: i64 = sqlx::query_scalar("SELECT FLOOR(EXTRACT(EPOCH FROM clock_timestamp()) * 1000)::BIGINT")
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        acknowledge_device(&pool, &puller, None, since).await.unwrap();
+
+        // Le poste A, dont l'horloge retarde de deux mois, supprime un lien
+        let entity = ENTITIES.iter().find(|e| e.table == "document_links").unwrap();
+        let mut record = entity.sample_record(&link);
+        record[entity.column_index("is_deleted").unwrap()] = Value::Integer(1);
+        record[entity.column_index("updated_at").unwrap()] =
+            Value::Timestamp(Timestamp::from_millis(current_timestamp() - 60 * 24 * 3600 * 1000).unwrap());
+        let tombstone = SyncItem::from_record(entity, record).unwrap();
+        let pushed = sync(&pool, &pusher, since, vec![tombstone.clone()]).await;
+        assert!(pushed.success, "{}", pushed.message);
+
+        // Le poste B la reçoit à sa synchronisation suivante
+        let pulled = sync(&pool, &puller, since, vec![]).await;
+        assert!(pulled.remote_changes.contains(&tombstone));
+
+        sqlx::query("DELETE FROM document_links WHERE id = $1").bind(&link).execute(&pool).await.unwrap();
+        sqlx::query("DELETE FROM sync_devices").execute(&pool).await.unwrap();
+    }
+
+    #[actix_web::test]
+    #[ignore = "requires TEST_DATABASE_URL"]
+    async fn retired_devices_stop_holding_back_the_purge() {
+        let pool = open().await;
+        let suffix = uuid::Uuid::new_v4().to_string();
+        let (link, active, stale, retired) =
+            (format!("l-{}", suffix), format!("active-{}", suffix), format!("stale-{}", suffix), format!("retired-{}", suffix));
+        // La base de test peut contenir d'autres postes qui retiendraient la purge
+        sqlx::query("DELETE FROM sync_devices").execute(&pool).await.unwrap();
+        sqlx::query(
+            "INSERT INTO document_links (id, document_id, entity_type, entity_id, is_deleted, updated_at)
+             VALUES ($1, 'd', 'company', 'c', 1, NOW() - INTERVAL '60 days')"
+        )
+        .bind(&link)
+        .execute(&pool)
+        .await
+        .unwrap();
+        backdate_received_at(&pool, "document_links", &link, 60).await;
+        acknowledge_device(&pool, &active, None, current_timestamp()).await.unwrap();
+        acknowledge_device(&pool, &retired, None, 0).await.unwrap();
+        acknowledge_device(&pool, &stale, None, 0).await.unwrap();
+        sqlx::query("UPDATE sync_devices SET last_seen = NOW() - INTERVAL '365 days' WHERE device_id = $1")
+            .bind(&stale)
+            .execute(&pool)
+            .await
+            .unwrap();
+        let remaining = || async {
+            sqlx::query_scalar::<_, i64>("SELECT COUNT(*) FROM document_links WHERE id = $1")
+                .bind(&link)
+                .fetch_one(&pool)
+                .await
+                .unwrap()
+        };
+
+        // Un poste n'a rien reçu : la suppression est gardée
+        purge_acknowledged_tombstones(&pool).await.unwrap();
+        assert_eq!(remaining().await, 1);
+
+        // Poste retiré à la main ; l'autre a expiré
+        let app = actix_web::test::init_service(
+            actix_web::App::new().app_data(web::Data::new(pool.clone())).service(unregister_device),
+        )
+        .await;
+        let unregister = || actix_web::test::TestRequest::delete().uri(&format!("/sync/devices/{}", retired)).to_request();
+        assert_eq!(actix_web::test::call_service(&app, unregister()).await.status(), StatusCode::NO_CONTENT);
+        assert_eq!(actix_web::test::call_service(&app, unregister()).await.status(), StatusCode::NOT_FOUND);
+
+        purge_acknowledged_tombstones(&pool).await.unwrap();
+        assert_eq!(remaining().await, 0);
+        sqlx::query("DELETE FROM sync_devices").execute(&pool).await.unwrap();
+    }
+
+    #[actix_web::test]
+    async fn old_clients_are_told_to_upgrade() {
+        // Refusé avant tout accès à la base
+        let pool = PgPoolOptions::new().connect_lazy("postgres://localhost/unused").unwrap();
+        let request = SyncRequest {
+            protocol_version: 0,
+            last_sync_timestamp: 42,
+            changes: vec![],
+            device_id: None,
+            device_name: None,
+        };
+        let response = sync_all(web::Data::new(pool), web::Data::new(ChangeFeed::new()), web::Json(request)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::UPGRADE_REQUIRED);
+
+        let body = actix_web::body::to_bytes(response.into_body()).await.unwrap();
+        let body: SyncResponse = serde_json::from_slice(&body).unwrap();
+        assert!(body.upgrade_required);
+        assert!(body.message.starts_with("Upgrade required"));
+        assert_eq!(body.new_timestamp, 42);
+    }
+}
@@ -6,6 +6,7 @@ Intégrée dans l'application Tauri comme sidecar
 
 import os
 import sys
+import socket
 import logging
 from datetime import datetime
 from typing import List, Optional
@@ -18,7 +19,21 @@ from docx import Document
 
 # Configuration - LOCALHOST ONLY
 API_HOST = "127.0.0.1"
-API_PORT = 8001
+DEFAULT_PORT = 8001
+# Ligne écrite sur la sortie standard une fois le port ouvert
+PORT_ANNOUNCE = "SIDECAR_PORT="
+
+
+def parse_port(argv):
+    """Port imposé par l'application (--port N, 0 pour un port libre), 8001 par défaut"""
+    if "--port" in argv:
+        index = argv.index("--port")
+        if index + 1 < len(argv):
+            return int(argv[index + 1])
+    return int(os.environ.get("DOC_API_PORT", DEFAULT_PORT))
+
+
+API_PORT = parse_port(sys.argv)
 TEMPLATE_PATH = os.path.join(os.path.dirname(__file__), "template.docx")
 
 logging.basicConfig(level=logging.INFO)
@@ -700,8 +715,22 @@ async def health():
     """Health check"""
     return {"status": "ok", "version": "1.0.0"}
 
+def bind_socket(port):
+    """Ouvre le port d'écoute avant de démarrer uvicorn : avec le port 0, le
+    système attribue un port libre sans risque qu'un autre processus le prenne
+    entre le choix et l'écoute"""
+    sock = socket.socket(socket.AF_INET, socket.SOCK_STREAM)
+    sock.setsockopt(socket.SOL_SOCKET, socket.SO_REUSEADDR, 1)
+    sock.bind((API_HOST, port))
+    return sock
+
+
 if __name__ == "__main__":
-    logger.info(f"🚀 Document Generator sur {API_HOST}:{API_PORT}")
+    sock = bind_socket(API_PORT)
+    port = sock.getsockname()[1]
+    logger.info(f"🚀 Document Generator sur {API_HOST}:{port}")
     logger.info(f"📄 Template: {TEMPLATE_PATH}")
-    uvicorn.run(app, host=API_HOST, port=API_PORT, log_level="error")
+    # L'application attend cette ligne pour connaître le port
+    print(f"{PORT_ANNOUNCE}{port}", flush=True)
+    uvicorn.Server(uvicorn.Config(app, log_level="error")).run(sockets=[sock])
 
@@ -0,0 +1,206 @@
+use crate::time::{Date, Timestamp};
+use crate::value::{ColumnType, Dialect, FieldError, Value};
+
+/// Colonne synchronisée d'une table
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Column {
+    pub name: &'static str,
+    pub ty: ColumnType,
+    pub nullable: bool,
+}
+
+impl Column {
+    pub const fn required(name: &'static str, ty: ColumnType) -> Self {
+        Self { name, ty, nullable: false }
+    }
+
+    pub const fn optional(name: &'static str, ty: ColumnType) -> Self {
+        Self { name, ty, nullable: true }
+    }
+}
+
+/// Table synchronisée : la même définition sert aux deux moteurs
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Entity {
+    pub table: &'static str,
+    pub columns: &'static [Column],
+}
+
+/// Valeurs d'un enregistrement, dans l'ordre des colonnes de l'entité
+pub type Record = Vec<Value>;
+
+/// Type Rust d'un champ de contenu synchronisé, et sa colonne
+pub trait Field: Sized {
+    const TYPE: ColumnType;
+    const NULLABLE: bool = false;
+
+    fn from_value(value: Value) -> Option<Self>;
+    fn into_value(self) -> Value;
+}
+
+macro_rules! field {
+    ($ty:ty, $column:ident, $variant:ident) => {
+        impl Field for $ty {
+            const TYPE: ColumnType = ColumnType::$column;
+
+            fn from_value(value: Value) -> Option<Self> {
+                match value {
+                    Value::$variant(v) => Some(v),
+                    _ => None,
+                }
+            }
+
+            fn into_value(self) -> Value {
+                Value::$variant(self)
+            }
+        }
+    };
+}
+
+field!(String, Text, Text);
+field!(i64, Integer, Integer);
+field!(f64, Real, Real);
+field!(bool, Bool, Bool);
+field!(Timestamp, Timestamp, Timestamp);
+field!(Date, Date, Date);
+field!(serde_json::Value, Json, Json);
+
+impl<T: Field> Field for Option<T> {
+    const TYPE: ColumnType = T::TYPE;
+    const NULLABLE: bool = true;
+
+    fn from_value(value: Value) -> Option<Self> {
+        match value {
+            Value::Null => Some(None),
+            value => T::from_value(value).map(Some),
+        }
+    }
+
+    fn into_value(self) -> Value {
+        self.map_or(Value::Null, T::into_value)
+    }
+}
+
+/// Entité synchronisée portant ce nom de table
+pub fn entity(table: &str) -> Option<&'static Entity> {
+    crate::tables::ENTITIES.iter().find(|e| e.table == table)
+}
+
+impl Entity {
+    pub fn column_index(&self, name: &str) -> Option<usize> {
+        self.columns.iter().position(|c| c.name == name)
+    }
+
+    /// Valeur d'une colonne dans un enregistrement de cette entité
+    pub fn get<'a>(&self, record: &'a [Value], name: &str) -> Option<&'a Value> {
+        self.column_index(name).and_then(|i| record.get(i))
+    }
+
+    /// "id, name, ..." dans l'ordre des colonnes
+    pub fn column_list(&self) -> String {
+        self.columns.iter().map(|c| c.name).collect::<Vec<_>>().join(", ")
+    }
+
+    /// Colonnes à lire, converties au besoin pour un décodage uniforme
+    pub fn select_list(&self, dialect: Dialect) -> String {
+        self.columns
+            .iter()
+            .map(|c| match (dialect, c.ty) {
+                (Dialect::Postgres, ColumnType::Integer) => format!("{0}::BIGINT AS {0}", c.name),
+                _ => c.name.to_string(),
+            })
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+
+    /// INSERT ... ON CONFLICT(id) DO UPDATE sur toutes les colonnes sauf
+    /// l'identifiant et la date de création
+    pub fn upsert_sql(&self, dialect: Dialect) -> String {
+        let placeholders = (1..=self.columns.len())
+            .map(|i| match dialect {
+                Dialect::Sqlite => "?".to_string(),
+                Dialect::Postgres => format!("${}", i),
+            })
+            .collect::<Vec<_>>()
+            .join(", ");
+        let updates = self
+            .columns
+            .iter()
+            .filter(|c| c.name != "id" && c.name != "created_at")
+            .map(|c| format!("{0} = excluded.{0}", c.name))
+            .collect::<Vec<_>>()
+            .join(", ");
+        format!(
+            "INSERT INTO {} ({}) VALUES ({}) ON CONFLICT(id) DO UPDATE SET {}",
+            self.table,
+            self.column_list(),
+            placeholders,
+            updates
+        )
+    }
+
+    /// Lit un enregistrement depuis le JSON reçu. Chaque champ inconnu, mal
+    /// typé ou obligatoire mais absent est signalé, pas seulement le premier
+    pub fn record_from_json(&self, data: &serde_json::Value) -> Result<Record, Vec<FieldError>> {
+        let Some(object) = data.as_object() else {
+            return Err(vec![FieldError::new("data", "expected a JSON object")]);
+        };
+        let mut errors: Vec<FieldError> = object
+            .keys()
+            .filter(|key| self.column_index(key).is_none())
+            .map(|key| FieldError::new(key, "unknown field"))
+            .collect();
+        let mut record = Record::with_capacity(self.columns.len());
+        for column in self.columns {
+            let value = match object.get(column.name) {
+                None | Some(serde_json::Value::Null) if !column.nullable => {
+                    errors.push(FieldError::new(column.name, "missing field"));
+                    Value::Null
+                }
+                None => Value::Null,
+                Some(json) => Value::from_json(column.ty, json).unwrap_or_else(|e| {
+                    errors.push(FieldError::new(column.name, e));
+                    Value::Null
+                }),
+            };
+            record.push(value);
+        }
+        if errors.is_empty() {
+            Ok(record)
+        } else {
+            Err(errors)
+        }
+    }
+
+    /// Forme JSON transmise sur le fil
+    pub fn record_to_json(&self, record: &[Value]) -> serde_json::Value {
+        let object = self
+            .columns
+            .iter()
+            .zip(record)
+            .map(|(column, value)| (column.name.to_string(), value.to_json()))
+            .collect::<serde_json::Map<_, _>>();
+        serde_json::Value::Object(object)
+    }
+
+    /// Enregistrement d'exemple couvrant chaque colonne, pour les tests
+    /// d'aller-retour des deux moteurs
+    pub fn sample_record(&self, id: &str) -> Record {
+        let day = Date::from_ymd(2024, 2, 29).unwrap();
+        self.columns
+            .iter()
+            .enumerate()
+            .map(|(i, column)| match (column.name, column.ty) {
+                ("id", _) => Value::Text(id.to_string()),
+                ("is_deleted", _) => Value::Integer(0),
+                (_, ColumnType::Text) => Value::Text(format!("{} {}", column.name, id)),
+                (_, ColumnType::Integer) => Value::Integer(i as i64 + 1),
+                (_, ColumnType::Real) => Value::Real(i as f64 + 0.25),
+                (_, ColumnType::Bool) => Value::Bool(true),
+                (_, ColumnType::Timestamp) => Value::Timestamp(Timestamp::from_millis(1_700_000_000_123 + i as i64).unwrap()),
+                (_, ColumnType::Date) => Value::Date(day),
+                (_, ColumnType::Json) => Value::Json(serde_json::json!(["Alice", "Bob"])),
+            })
+            .collect()
+    }
+}
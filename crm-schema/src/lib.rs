@@ -0,0 +1,127 @@
+//! Définition unique des tables synchronisées, partagée par l'application
+//! de bureau (SQLite) et le serveur (Postgres) : contenu typé de chaque
+//! table, colonnes, forme transmise sur le fil et requêtes d'écriture
+//! générées pour chaque moteur.
+
+pub mod entity;
+pub mod sync;
+pub mod tables;
+pub mod time;
+pub mod value;
+
+pub use entity::{entity, Column, Entity, Field, Record};
+pub use sync::{
+    ChangeNotice, SyncEnvelope, SyncItemError, SyncItemErrors, SyncRequest, SyncResponse, CHANGE_EVENT, PROTOCOL_VERSION,
+    RESYNC_EVENT,
+};
+pub use tables::{SyncItem, ENTITIES};
+pub use time::{Date, Timestamp};
+pub use value::{ColumnType, Dialect, FieldError, Value};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample(table: &str, id: &str) -> SyncItem {
+        let entity = entity(table).unwrap();
+        SyncItem::from_record(entity, entity.sample_record(id)).unwrap()
+    }
+
+    #[test]
+    fn every_entity_has_sync_columns() {
+        for entity in ENTITIES {
+            assert_eq!(entity.columns[0].name, "id", "{}", entity.table);
+            for name in ["id", "created_at", "updated_at", "version", "is_deleted"] {
+                let column = entity.columns.iter().find(|c| c.name == name);
+                assert!(column.is_some_and(|c| !c.nullable), "{}.{}", entity.table, name);
+            }
+        }
+    }
+
+    #[test]
+    fn items_round_trip_through_the_wire() {
+        for entity in ENTITIES {
+            let item = sample(entity.table, "sample-1");
+            assert_eq!(item.entity(), entity);
+            let json = serde_json::to_string(&item).unwrap();
+            let back: SyncItem = serde_json::from_str(&json).unwrap();
+            assert_eq!(back, item, "{}", entity.table);
+        }
+    }
+
+    #[test]
+    fn reports_every_invalid_field() {
+        let mut envelope = SyncEnvelope::from(sample("proposal_products", "p-1"));
+        let data = envelope.data.as_object_mut().unwrap();
+        data.remove("product_type");
+        data.insert("user_count".to_string(), serde_json::json!("2"));
+        data.insert("unit_price".to_string(), serde_json::json!("12.5"));
+        data.insert("server_id".to_string(), serde_json::json!(null));
+
+        let mut fields: Vec<String> = envelope.parse().unwrap_err().0.into_iter().filter_map(|e| e.field).collect();
+        fields.sort();
+        assert_eq!(fields, ["product_type", "server_id", "unit_price", "user_count"]);
+    }
+
+    #[test]
+    fn rejects_legacy_wire_formats() {
+        assert!(Value::from_json(ColumnType::Timestamp, &serde_json::json!("2023-11-14T22:13:20.123Z")).is_err());
+        assert!(Value::from_json(ColumnType::Date, &serde_json::json!("")).is_err());
+        assert!(Value::from_json(ColumnType::Integer, &serde_json::json!(1.5)).is_err());
+        assert!(Value::from_json(ColumnType::Bool, &serde_json::json!(2)).is_err());
+
+        let mut envelope = SyncEnvelope::from(sample("companies", "c-1"));
+        envelope.version += 1;
+        let errors = envelope.parse().unwrap_err().0;
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].field.as_deref(), Some("version"));
+
+        envelope.table_name = "users".to_string();
+        assert_eq!(envelope.parse().unwrap_err().0[0].field.as_deref(), Some("table_name"));
+    }
+
+    #[test]
+    fn reads_stored_sqlite_text_leniently() {
+        let ms = 1_700_000_000_123;
+        let expected = Value::Timestamp(Timestamp::from_millis(ms).unwrap());
+        for raw in [ms.to_string().as_str(), "2023-11-14T22:13:20.123Z", "2023-11-14T23:13:20.123+01:00", "2023-11-14 22:13:20.123"] {
+            assert_eq!(Value::from_text(ColumnType::Timestamp, raw).unwrap(), expected);
+        }
+
+        let day = Value::Date(Date::from_ymd(2024, 2, 29).unwrap());
+        // Le jour saisi est conservé, même suivi d'un décalage horaire
+        for raw in ["2024-02-29", "2024-02-29T00:00:00+00:00", "2024-02-29T00:00:00+02:00", "2024-02-29 00:00:00", "1709164800000"] {
+            assert_eq!(Value::from_text(ColumnType::Date, raw).unwrap(), day);
+        }
+        assert_eq!(Value::from_text(ColumnType::Date, "").unwrap(), Value::Null);
+        assert_eq!(Value::from_text(ColumnType::Json, "[\"Alice\"]").unwrap(), Value::Json(serde_json::json!(["Alice"])));
+    }
+
+    #[test]
+    fn canonical_forms_are_strict_and_millisecond_precise() {
+        let instant = chrono::DateTime::parse_from_rfc3339("2023-11-14T22:13:20.123456Z").unwrap().to_utc();
+        let ts = Timestamp::from_datetime(instant);
+        assert_eq!(ts.to_string(), "1700000000123");
+        assert_eq!(Timestamp::parse("1700000000123"), Some(ts));
+        assert_eq!(serde_json::to_value(ts).unwrap(), serde_json::json!("1700000000123"));
+        assert!(Timestamp::parse("2023-11-14T22:13:20.123Z").is_none());
+        assert!(Timestamp::parse("-1").is_none());
+
+        let day = Date::from_ymd(2024, 2, 29).unwrap();
+        assert_eq!(serde_json::from_value::<Date>(serde_json::json!("2024-02-29")).unwrap(), day);
+        assert!(serde_json::from_value::<Date>(serde_json::json!("2024-02-29T00:00:00Z")).is_err());
+        assert!(Date::parse("2024-2-29").is_none());
+        assert!(Date::parse_legacy("29/02/2024").is_none());
+        assert_eq!(day.start_of_day().millis(), 1_709_164_800_000);
+    }
+
+    #[test]
+    fn upsert_keeps_identity_and_creation_date() {
+        let links = entity("document_links").unwrap();
+        let sql = links.upsert_sql(Dialect::Postgres);
+        assert!(sql.contains("VALUES ($1, $2, $3, $4, $5, $6, $7, $8)"));
+        assert!(!sql.contains("id = excluded.id"));
+        assert!(!sql.contains("created_at = excluded"));
+        assert!(links.upsert_sql(Dialect::Sqlite).contains("VALUES (?, ?, ?, ?, ?, ?, ?, ?)"));
+    }
+}
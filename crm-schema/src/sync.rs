@@ -0,0 +1,187 @@
+use crate::tables::SyncItem;
+use crate::value::FieldError;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// Version du protocole `/api/sync`. Un poste d'une autre version est
+/// refusé avec un message explicite plutôt que de corrompre les données
+pub const PROTOCOL_VERSION: u32 = 1;
+
+/// Élément synchronisé tel que transmis : le contenu n'est pas encore vérifié
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SyncEnvelope {
+    pub table_name: String,
+    pub id: String,
+    pub data: serde_json::Value,
+    pub version: i64,
+    pub is_deleted: bool,
+    pub updated_at: i64,
+}
+
+/// Élément reçu refusé, avec le champ en cause (absent quand l'élément
+/// entier est refusé, par exemple par la base)
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SyncItemError {
+    pub table_name: String,
+    pub id: String,
+    pub field: Option<String>,
+    pub message: String,
+}
+
+impl SyncItemError {
+    pub fn item(envelope: &SyncEnvelope, message: impl Into<String>) -> Self {
+        SyncItemError {
+            table_name: envelope.table_name.clone(),
+            id: envelope.id.clone(),
+            field: None,
+            message: message.into(),
+        }
+    }
+}
+
+impl fmt::Display for SyncItemError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.field {
+            Some(field) => write!(f, "{} {}: {}: {}", self.table_name, self.id, field, self.message),
+            None => write!(f, "{} {}: {}", self.table_name, self.id, self.message),
+        }
+    }
+}
+
+/// Ensemble des champs refusés d'un élément
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SyncItemErrors(pub Vec<SyncItemError>);
+
+impl fmt::Display for SyncItemErrors {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let messages: Vec<String> = self.0.iter().map(ToString::to_string).collect();
+        write!(f, "{}", messages.join("; "))
+    }
+}
+
+impl std::error::Error for SyncItemErrors {}
+
+impl SyncEnvelope {
+    fn error(&self, error: FieldError) -> SyncItemError {
+        SyncItemError {
+            table_name: self.table_name.clone(),
+            id: self.id.clone(),
+            field: Some(error.field),
+            message: error.message,
+        }
+    }
+
+    /// Vérifie le contenu : chaque champ doit exister dans la table, avoir le
+    /// type attendu, et l'enveloppe doit correspondre au contenu
+    pub fn parse(&self) -> Result<SyncItem, SyncItemErrors> {
+        let entity = crate::entity(&self.table_name).ok_or_else(|| {
+            SyncItemErrors(vec![self.error(FieldError::new("table_name", "unknown table"))])
+        })?;
+        let record = entity
+            .record_from_json(&self.data)
+            .map_err(|errors| SyncItemErrors(errors.into_iter().map(|e| self.error(e)).collect()))?;
+        let item = SyncItem::from_record(entity, record).map_err(|e| SyncItemErrors(vec![self.error(e)]))?;
+
+        let mut mismatches = Vec::new();
+        if item.id() != self.id {
+            mismatches.push("id");
+        }
+        if item.version() != self.version {
+            mismatches.push("version");
+        }
+        if item.is_deleted() != self.is_deleted {
+            mismatches.push("is_deleted");
+        }
+        if item.updated_at().millis() != self.updated_at {
+            mismatches.push("updated_at");
+        }
+        if mismatches.is_empty() {
+            Ok(item)
+        } else {
+            Err(SyncItemErrors(
+                mismatches
+                    .into_iter()
+                    .map(|field| self.error(FieldError::new(field, "does not match the item envelope")))
+                    .collect(),
+            ))
+        }
+    }
+}
+
+impl From<SyncItem> for SyncEnvelope {
+    fn from(item: SyncItem) -> Self {
+        let entity = item.entity();
+        SyncEnvelope {
+            table_name: entity.table.to_string(),
+            id: item.id().to_string(),
+            version: item.version(),
+            is_deleted: item.is_deleted(),
+            updated_at: item.updated_at().millis(),
+            data: entity.record_to_json(&item.into_record()),
+        }
+    }
+}
+
+impl TryFrom<SyncEnvelope> for SyncItem {
+    type Error = SyncItemErrors;
+
+    fn try_from(envelope: SyncEnvelope) -> Result<Self, Self::Error> {
+        envelope.parse()
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SyncRequest {
+    /// Absent pour les postes antérieurs au protocole versionné
+    #[serde(default)]
+    pub protocol_version: u32,
+    pub last_sync_timestamp: i64,
+    /// Éléments vérifiés un à un par le serveur : un élément refusé
+    /// n'empêche pas l'application des autres
+    pub changes: Vec<SyncEnvelope>,
+    /// Poste émetteur : le serveur en déduit les suppressions reçues par
+    /// tous les postes, qu'il peut alors purger (absent pour les anciens clients)
+    #[serde(default)]
+    pub device_id: Option<String>,
+    #[serde(default)]
+    pub device_name: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SyncResponse {
+    #[serde(default)]
+    pub protocol_version: u32,
+    pub success: bool,
+    pub message: String,
+    pub remote_changes: Vec<SyncItem>,
+    pub new_timestamp: i64,
+    pub items_synced: i64,
+    /// Champs refusés parmi les éléments envoyés
+    #[serde(default)]
+    pub errors: Vec<SyncItemError>,
+    /// Le poste doit être mis à jour avant de pouvoir synchroniser
+    #[serde(default)]
+    pub upgrade_required: bool,
+}
+
+/// Événement du flux `/api/changes` (Server-Sent Events) annonçant une
+/// modification reçue par le serveur, avec une `ChangeNotice` pour données
+pub const CHANGE_EVENT: &str = "change";
+/// Événement envoyé à la connexion, puis si des annonces ont été perdues :
+/// le poste doit se synchroniser sans attendre
+pub const RESYNC_EVENT: &str = "resync";
+
+/// Annonce d'un enregistrement accepté par le serveur ; le poste qui l'a
+/// envoyé ne la reçoit pas
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ChangeNotice {
+    /// Croissant tant que le serveur tourne ; aussi l'`id` de l'événement
+    pub sequence: u64,
+    pub table_name: String,
+    pub id: String,
+    /// Réception par le serveur (`received_at`, millisecondes), repère sur
+    /// lequel `/api/sync` choisit les lignes à renvoyer : la synchronisation
+    /// déclenchée les reprend quelle que soit l'horloge du poste
+    #[serde(default)]
+    pub received_at: i64,
+}
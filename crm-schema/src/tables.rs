@@ -0,0 +1,197 @@
+use crate::entity::{Column, Entity, Field, Record};
+use crate::sync::SyncEnvelope;
+use crate::value::{FieldError, Value};
+use crate::time::{Date, Timestamp};
+use serde::{Deserialize, Serialize};
+
+type Json = serde_json::Value;
+
+// Chaque table synchronisée produit son contenu typé, sa définition de
+// colonnes et sa variante de `SyncItem`
+macro_rules! entities {
+    ($( $(#[$doc:meta])* $table:literal => $name:ident { $( $field:ident: $ty:ty ),* $(,)? } )*) => {
+        $(
+            $(#[$doc])*
+            #[derive(Debug, Clone, PartialEq)]
+            pub struct $name {
+                $( pub $field: $ty, )*
+            }
+
+            impl $name {
+                pub const ENTITY: Entity = Entity {
+                    table: $table,
+                    columns: &[
+                        $( Column { name: stringify!($field), ty: <$ty as Field>::TYPE, nullable: <$ty as Field>::NULLABLE }, )*
+                    ],
+                };
+
+                pub fn into_record(self) -> Record {
+                    vec![ $( Field::into_value(self.$field), )* ]
+                }
+
+                pub fn from_record(record: Record) -> Result<Self, FieldError> {
+                    let mut values = record.into_iter();
+                    Ok(Self {
+                        $(
+                            $field: <$ty as Field>::from_value(values.next().unwrap_or(Value::Null))
+                                .ok_or_else(|| FieldError::new(stringify!($field), "unexpected value"))?,
+                        )*
+                    })
+                }
+            }
+        )*
+
+        /// Tables synchronisées, parents avant enfants
+        pub const ENTITIES: &[Entity] = &[ $( $name::ENTITY, )* ];
+
+        /// Élément synchronisé : le contenu typé d'une ligne, étiqueté par sa
+        /// table. Transmis sous la forme d'une `SyncEnvelope`, vérifiée champ
+        /// par champ à la lecture
+        #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+        #[serde(try_from = "SyncEnvelope", into = "SyncEnvelope")]
+        pub enum SyncItem {
+            $( $name($name), )*
+        }
+
+        impl SyncItem {
+            pub fn entity(&self) -> &'static Entity {
+                match self {
+                    $( SyncItem::$name(_) => &$name::ENTITY, )*
+                }
+            }
+
+            pub fn table_name(&self) -> &'static str {
+                self.entity().table
+            }
+
+            pub fn id(&self) -> &str {
+                match self {
+                    $( SyncItem::$name(item) => &item.id, )*
+                }
+            }
+
+            pub fn version(&self) -> i64 {
+                match self {
+                    $( SyncItem::$name(item) => item.version, )*
+                }
+            }
+
+            pub fn is_deleted(&self) -> bool {
+                match self {
+                    $( SyncItem::$name(item) => item.is_deleted != 0, )*
+                }
+            }
+
+            pub fn updated_at(&self) -> Timestamp {
+                match self {
+                    $( SyncItem::$name(item) => item.updated_at, )*
+                }
+            }
+
+            pub fn into_record(self) -> Record {
+                match self {
+                    $( SyncItem::$name(item) => item.into_record(), )*
+                }
+            }
+
+            /// Élément correspondant à une ligne lue en base
+            pub fn from_record(entity: &Entity, record: Record) -> Result<SyncItem, FieldError> {
+                match entity.table {
+                    $( $table => $name::from_record(record).map(SyncItem::$name), )*
+                    other => Err(FieldError::new("table_name", format!("unknown table {}", other))),
+                }
+            }
+        }
+    };
+}
+
+entities! {
+    /// Société
+    "companies" => Company {
+        id: String, name: String, website: Option<String>, address: Option<String>,
+        city: Option<String>, postal_code: Option<String>, country: Option<String>, description: Option<String>,
+        created_at: Timestamp, updated_at: Timestamp, version: i64, is_deleted: i64,
+        merged_into: Option<String>, deleted_by: Option<String>,
+    }
+
+    /// Contact d'une société
+    "company_contacts" => CompanyContact {
+        id: String, company_id: String, first_name: String, last_name: String,
+        email: String, phone_number: Option<String>, is_primary: bool,
+        created_at: Timestamp, updated_at: Timestamp, version: i64, is_deleted: i64,
+    }
+
+    /// Client (ancien modèle, sans société)
+    "customers" => Customer {
+        id: String, name: String, email: Option<String>, phone: Option<String>,
+        address: Option<String>, notes: Option<String>,
+        created_at: Timestamp, updated_at: Timestamp, version: i64, is_deleted: i64,
+    }
+
+    /// Proposition commerciale
+    "proposals" => Proposal {
+        id: String, company_id: String, proposal_number: Option<String>, status: String,
+        total_amount: Option<f64>, currency: String, valid_until: Option<Date>, notes: Option<String>,
+        created_at: Timestamp, updated_at: Timestamp, version: i64, is_deleted: i64,
+        deleted_by: Option<String>,
+    }
+
+    /// Produit d'une proposition
+    "proposal_products" => ProposalProduct {
+        id: String, proposal_id: String, product_type: String,
+        user_count: i64, standalone_count: i64, server_key_count: i64,
+        unit_price: Option<f64>, total_price: Option<f64>, annual_reduction: f64,
+        training: i64, training_days: i64, training_cost_per_day: f64,
+        training_cost: f64, licence: i64, support: i64, support_years: i64,
+        created_at: Timestamp, updated_at: Timestamp, version: i64, is_deleted: i64,
+    }
+
+    /// Facture
+    "invoices" => Invoice {
+        id: String, proposal_id: String, invoice_number: String, status: String,
+        total_amount: f64, currency: String, issue_date: Date,
+        due_date: Option<Date>, paid_date: Option<Date>, purchase_order: Option<String>, purchase_order_date: Option<Date>,
+        commercial_in_charge: Option<String>, notes: Option<String>,
+        created_at: Timestamp, updated_at: Timestamp, version: i64, is_deleted: i64,
+        deleted_by: Option<String>,
+    }
+
+    /// Document (métadonnées ; le fichier suit par le stockage de blobs)
+    "documents" => Document {
+        id: String, customer_id: Option<String>, company_id: Option<String>, title: String,
+        document_type: String, file_path: Option<String>, content: Option<String>,
+        created_at: Timestamp, updated_at: Timestamp, version: i64, is_deleted: i64,
+        source_type: Option<String>, source_id: Option<String>, revision: Option<i64>, sha256: Option<String>,
+        deleted_by: Option<String>,
+    }
+
+    /// Modèle de document
+    "document_templates" => DocumentTemplate {
+        id: String, name: String, kind: String, product_type: Option<String>,
+        language: Option<String>, company_id: Option<String>, template_version: i64,
+        file_name: String, content: Option<String>, checksum: String,
+        created_at: Timestamp, updated_at: Timestamp, version: i64, is_deleted: i64,
+    }
+
+    /// Lien entre un document et une autre entité
+    "document_links" => DocumentLink {
+        id: String, document_id: String, entity_type: String, entity_id: String,
+        created_at: Timestamp, updated_at: Timestamp, version: i64, is_deleted: i64,
+    }
+
+    /// Appel, réunion, e-mail ou note rattaché à une société
+    "activities" => Activity {
+        id: String, company_id: String, activity_type: String, occurred_at: Timestamp,
+        duration_minutes: Option<i64>, subject: Option<String>, notes: Option<String>, proposal_id: Option<String>,
+        participants: Json,
+        created_at: Timestamp, updated_at: Timestamp, version: i64, is_deleted: i64,
+    }
+
+    /// Réglage commun à l'organisation (devise, échéance, numérotation) :
+    /// une ligne par clé, pour que deux postes modifiant des réglages
+    /// différents ne s'écrasent pas
+    "org_settings" => OrgSetting {
+        id: String, value: Json,
+        created_at: Timestamp, updated_at: Timestamp, version: i64, is_deleted: i64,
+    }
+}
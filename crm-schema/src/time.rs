@@ -0,0 +1,162 @@
+use chrono::{DateTime, NaiveDate, NaiveDateTime, Utc};
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+use std::fmt;
+
+/// Instant UTC à la milliseconde près. Forme canonique, sur le fil comme en
+/// SQLite : le nombre de millisecondes depuis l'époque Unix, en texte
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Timestamp(i64);
+
+impl Timestamp {
+    pub fn now() -> Self {
+        Self::from_datetime(Utc::now())
+    }
+
+    /// `None` hors de la plage représentable par chrono
+    pub fn from_millis(ms: i64) -> Option<Self> {
+        DateTime::<Utc>::from_timestamp_millis(ms).map(|_| Self(ms))
+    }
+
+    /// Tronque à la milliseconde
+    pub fn from_datetime(dt: DateTime<Utc>) -> Self {
+        Self(dt.timestamp_millis())
+    }
+
+    pub fn millis(self) -> i64 {
+        self.0
+    }
+
+    pub fn to_datetime(self) -> DateTime<Utc> {
+        DateTime::<Utc>::from_timestamp_millis(self.0).expect("checked at construction")
+    }
+
+    /// Lit la forme canonique, et elle seule
+    pub fn parse(raw: &str) -> Option<Self> {
+        if raw.is_empty() || !raw.bytes().all(|b| b.is_ascii_digit()) {
+            return None;
+        }
+        raw.parse().ok().and_then(Self::from_millis)
+    }
+
+    /// Lit une valeur enregistrée par une version antérieure : millisecondes,
+    /// RFC 3339 (avec son décalage), format `datetime('now')` de SQLite (UTC)
+    /// ou date seule (minuit UTC)
+    pub fn parse_legacy(raw: &str) -> Option<Self> {
+        let raw = raw.trim();
+        if let Ok(ms) = raw.parse::<i64>() {
+            return Self::from_millis(ms);
+        }
+        if let Ok(dt) = DateTime::parse_from_rfc3339(raw) {
+            return Some(Self::from_datetime(dt.with_timezone(&Utc)));
+        }
+        if let Ok(dt) = NaiveDateTime::parse_from_str(raw, "%Y-%m-%d %H:%M:%S%.f")
+            .or_else(|_| NaiveDateTime::parse_from_str(raw, "%Y-%m-%dT%H:%M:%S%.f"))
+        {
+            return Some(Self::from_datetime(dt.and_utc()));
+        }
+        NaiveDate::parse_from_str(raw, "%Y-%m-%d").ok().map(|d| Date(d).start_of_day())
+    }
+}
+
+impl From<DateTime<Utc>> for Timestamp {
+    fn from(dt: DateTime<Utc>) -> Self {
+        Self::from_datetime(dt)
+    }
+}
+
+impl fmt::Display for Timestamp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl Serialize for Timestamp {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for Timestamp {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let raw = String::deserialize(deserializer)?;
+        Self::parse(&raw)
+            .ok_or_else(|| de::Error::custom(format!("expected a timestamp (milliseconds as a string), got \"{}\"", raw)))
+    }
+}
+
+/// Date métier (échéance, date de facture...), sans heure ni fuseau.
+/// Forme canonique : "YYYY-MM-DD"
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Date(NaiveDate);
+
+impl Date {
+    pub fn from_ymd(year: i32, month: u32, day: u32) -> Option<Self> {
+        NaiveDate::from_ymd_opt(year, month, day).map(Self)
+    }
+
+    pub fn from_naive(date: NaiveDate) -> Self {
+        Self(date)
+    }
+
+    pub fn naive(self) -> NaiveDate {
+        self.0
+    }
+
+    /// Jour courant, en UTC
+    pub fn today() -> Self {
+        Self(Utc::now().date_naive())
+    }
+
+    /// Minuit UTC de ce jour
+    pub fn start_of_day(self) -> Timestamp {
+        Timestamp::from_datetime(self.0.and_hms_opt(0, 0, 0).unwrap().and_utc())
+    }
+
+    /// Lit la forme canonique, et elle seule
+    pub fn parse(raw: &str) -> Option<Self> {
+        if raw.len() != 10 {
+            return None;
+        }
+        NaiveDate::parse_from_str(raw, "%Y-%m-%d").ok().map(Self)
+    }
+
+    /// Lit une valeur enregistrée par une version antérieure ou saisie dans un
+    /// formulaire. Le jour écrit devant un instant ("2024-03-01T00:00:00+01:00")
+    /// est conservé tel quel : c'est celui qu'a choisi l'utilisateur. Seul un
+    /// instant en millisecondes est ramené à son jour UTC
+    pub fn parse_legacy(raw: &str) -> Option<Self> {
+        let raw = raw.trim();
+        if let Some(date) = raw.get(..10).and_then(Self::parse) {
+            let rest = &raw[10..];
+            if rest.is_empty() || rest.starts_with('T') || rest.starts_with(' ') {
+                return Some(date);
+            }
+        }
+        Timestamp::parse(raw).map(|ts| Self(ts.to_datetime().date_naive()))
+    }
+}
+
+impl From<NaiveDate> for Date {
+    fn from(date: NaiveDate) -> Self {
+        Self(date)
+    }
+}
+
+impl fmt::Display for Date {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0.format("%Y-%m-%d"))
+    }
+}
+
+impl Serialize for Date {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for Date {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let raw = String::deserialize(deserializer)?;
+        Self::parse(&raw).ok_or_else(|| de::Error::custom(format!("expected a date (YYYY-MM-DD), got \"{}\"", raw)))
+    }
+}
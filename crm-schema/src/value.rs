@@ -0,0 +1,214 @@
+use crate::time::{Date, Timestamp};
+use std::fmt;
+
+/// Type logique d'une colonne synchronisée, indépendant du moteur
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColumnType {
+    Text,
+    Integer,
+    Real,
+    /// 0/1 en SQLite, BOOLEAN en Postgres
+    Bool,
+    /// Instant : millisecondes (texte) en SQLite, TIMESTAMPTZ en Postgres
+    Timestamp,
+    /// Date métier : "YYYY-MM-DD" en SQLite, DATE en Postgres
+    Date,
+    /// Document JSON stocké en texte, transmis tel quel
+    Json,
+}
+
+/// Moteur de base de données cible
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Dialect {
+    Sqlite,
+    Postgres,
+}
+
+impl ColumnType {
+    /// Forme attendue sur le fil, pour les messages d'erreur
+    pub fn describe(self) -> &'static str {
+        match self {
+            ColumnType::Text => "a string",
+            ColumnType::Integer => "an integer",
+            ColumnType::Real => "a number",
+            ColumnType::Bool => "a boolean (or 0/1)",
+            ColumnType::Timestamp => "a timestamp (milliseconds as a string)",
+            ColumnType::Date => "a date (YYYY-MM-DD)",
+            ColumnType::Json => "a JSON value",
+        }
+    }
+
+    /// Types déclarés acceptés pour cette colonne, tels que rapportés par
+    /// `PRAGMA table_info` (SQLite) ou `information_schema.columns` (Postgres)
+    pub fn storage_types(self, dialect: Dialect) -> &'static [&'static str] {
+        match (dialect, self) {
+            (Dialect::Sqlite, ColumnType::Integer | ColumnType::Bool) => &["INTEGER"],
+            (Dialect::Sqlite, ColumnType::Real) => &["REAL"],
+            (Dialect::Sqlite, _) => &["TEXT"],
+            (Dialect::Postgres, ColumnType::Text | ColumnType::Json) => &["text"],
+            (Dialect::Postgres, ColumnType::Integer) => &["integer", "bigint"],
+            (Dialect::Postgres, ColumnType::Real) => &["double precision"],
+            (Dialect::Postgres, ColumnType::Bool) => &["boolean"],
+            (Dialect::Postgres, ColumnType::Timestamp) => &["timestamp with time zone"],
+            (Dialect::Postgres, ColumnType::Date) => &["date"],
+        }
+    }
+}
+
+/// Valeur typée d'une colonne, lue depuis le JSON ou la base
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Null,
+    Text(String),
+    Integer(i64),
+    Real(f64),
+    Bool(bool),
+    Timestamp(Timestamp),
+    Date(Date),
+    Json(serde_json::Value),
+}
+
+/// Champ refusé à la lecture d'un enregistrement
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FieldError {
+    pub field: String,
+    pub message: String,
+}
+
+impl FieldError {
+    pub fn new(field: &str, message: impl Into<String>) -> Self {
+        Self { field: field.to_string(), message: message.into() }
+    }
+}
+
+impl fmt::Display for FieldError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.field, self.message)
+    }
+}
+
+impl std::error::Error for FieldError {}
+
+impl Value {
+    /// Lit un champ reçu sur le fil, sous sa seule forme canonique (voir
+    /// `to_json`) : aucune conversion implicite
+    pub fn from_json(ty: ColumnType, json: &serde_json::Value) -> Result<Value, String> {
+        use serde_json::Value as J;
+        let value = match (ty, json) {
+            (_, J::Null) => Value::Null,
+            (ColumnType::Text, J::String(s)) => Value::Text(s.clone()),
+            (ColumnType::Integer, J::Number(n)) if n.is_i64() => Value::Integer(n.as_i64().unwrap()),
+            (ColumnType::Real, J::Number(n)) => Value::Real(n.as_f64().unwrap_or_default()),
+            (ColumnType::Bool, J::Bool(b)) => Value::Bool(*b),
+            (ColumnType::Bool, J::Number(n)) if n.as_i64() == Some(0) || n.as_i64() == Some(1) => {
+                Value::Bool(n.as_i64() == Some(1))
+            }
+            (ColumnType::Timestamp, J::String(s)) if !s.is_empty() && s.bytes().all(|b| b.is_ascii_digit()) => {
+                Value::Timestamp(Timestamp::parse(s).ok_or_else(|| format!("timestamp out of range: \"{}\"", s))?)
+            }
+            (ColumnType::Date, J::String(s)) => Value::Date(
+                Date::parse(s).ok_or_else(|| format!("expected a date (YYYY-MM-DD), got \"{}\"", s))?,
+            ),
+            (ColumnType::Json, other) => Value::Json(other.clone()),
+            (ty, other) => return Err(format!("expected {}, got {}", ty.describe(), other)),
+        };
+        Ok(value)
+    }
+
+    /// Lit une valeur texte stockée en SQLite. Les formats antérieurs à la
+    /// migration des instants restent acceptés
+    pub fn from_text(ty: ColumnType, raw: &str) -> Result<Value, String> {
+        if matches!(ty, ColumnType::Timestamp | ColumnType::Date) && raw.trim().is_empty() {
+            // Les formulaires enregistrent parfois une date vide
+            return Ok(Value::Null);
+        }
+        let value = match ty {
+            ColumnType::Text => Value::Text(raw.to_string()),
+            ColumnType::Integer => Value::Integer(raw.trim().parse().map_err(|_| format!("expected an integer, got \"{}\"", raw))?),
+            ColumnType::Real => Value::Real(raw.trim().parse().map_err(|_| format!("expected a number, got \"{}\"", raw))?),
+            ColumnType::Bool => Value::Bool(!matches!(raw.trim(), "" | "0" | "false")),
+            ColumnType::Timestamp => Value::Timestamp(Timestamp::parse_legacy(raw).ok_or_else(|| format!("expected a timestamp, got \"{}\"", raw))?),
+            ColumnType::Date => Value::Date(Date::parse_legacy(raw).ok_or_else(|| format!("expected a date, got \"{}\"", raw))?),
+            ColumnType::Json => Value::Json(serde_json::from_str(raw).map_err(|e| format!("invalid JSON: {}", e))?),
+        };
+        Ok(value)
+    }
+
+    /// Forme transmise sur le fil : instants en millisecondes (texte),
+    /// booléens en 0/1, dates en "YYYY-MM-DD"
+    pub fn to_json(&self) -> serde_json::Value {
+        match self {
+            Value::Null => serde_json::Value::Null,
+            Value::Text(s) => serde_json::json!(s),
+            Value::Integer(i) => serde_json::json!(i),
+            Value::Real(f) => serde_json::json!(f),
+            Value::Bool(b) => serde_json::json!(*b as i64),
+            Value::Timestamp(ts) => serde_json::json!(ts.to_string()),
+            Value::Date(d) => serde_json::json!(d.to_string()),
+            Value::Json(j) => j.clone(),
+        }
+    }
+
+    /// Forme stockée en SQLite pour les colonnes texte (instants, dates, JSON)
+    pub fn to_sqlite_text(&self) -> Option<String> {
+        match self {
+            Value::Null => None,
+            Value::Text(s) => Some(s.clone()),
+            Value::Integer(i) => Some(i.to_string()),
+            Value::Real(f) => Some(f.to_string()),
+            Value::Bool(b) => Some((*b as i64).to_string()),
+            Value::Timestamp(ts) => Some(ts.to_string()),
+            Value::Date(d) => Some(d.to_string()),
+            Value::Json(j) => Some(j.to_string()),
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Value::Text(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    pub fn as_i64(&self) -> Option<i64> {
+        match self {
+            Value::Integer(i) => Some(*i),
+            Value::Bool(b) => Some(*b as i64),
+            _ => None,
+        }
+    }
+
+    pub fn as_f64(&self) -> Option<f64> {
+        match self {
+            Value::Real(f) => Some(*f),
+            Value::Integer(i) => Some(*i as f64),
+            _ => None,
+        }
+    }
+
+    pub fn as_bool(&self) -> Option<bool> {
+        match self {
+            Value::Bool(b) => Some(*b),
+            Value::Integer(i) => Some(*i != 0),
+            _ => None,
+        }
+    }
+
+    pub fn as_timestamp(&self) -> Option<Timestamp> {
+        match self {
+            Value::Timestamp(ts) => Some(*ts),
+            _ => None,
+        }
+    }
+
+    pub fn as_date(&self) -> Option<Date> {
+        match self {
+            Value::Date(d) => Some(*d),
+            _ => None,
+        }
+    }
+
+    pub fn is_null(&self) -> bool {
+        matches!(self, Value::Null)
+    }
+}
@@ -44,6 +44,7 @@ echo "📁 Répertoire temporaire: $TEMP_DIR"
 # Copier les fichiers du backend
 echo "📦 Préparation des fichiers..."
 cp -r backend-app/backend-api $TEMP_DIR/
+cp -r crm-schema $TEMP_DIR/backend-api/
 cp -r backend-app/sidecar-python $TEMP_DIR/
 cp backend-app/docker-compose.yml $TEMP_DIR/
 
@@ -58,7 +59,10 @@ RUN apt-get update && apt-get install -y \
     libssl-dev \
     && rm -rf /var/lib/apt/lists/*
 
-WORKDIR /app
+# Schéma partagé, placé comme dans le dépôt (../../crm-schema)
+COPY crm-schema /build/crm-schema
+
+WORKDIR /build/backend-app/backend-api
 
 # Copier les fichiers de configuration
 COPY Cargo.toml ./
@@ -87,7 +91,7 @@ RUN apt-get update && apt-get install -y \
 WORKDIR /app
 
 # Copier le binaire
-COPY --from=builder /app/target/release/crm-backend-api /app/crm-backend-api
+COPY --from=builder /build/backend-app/backend-api/target/release/crm-backend-api /app/crm-backend-api
 
 # Créer un utilisateur non-root
 RUN useradd -r -s /bin/false appuser && chown -R appuser:appuser /app
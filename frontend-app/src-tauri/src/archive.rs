@@ -0,0 +1,46 @@
+// Archive des documents générés
+//
+// Chaque fichier envoyé au client est conservé sous
+// `<app_data>/archive/<2 premiers caractères>/<sha256>` : le nom est
+// l'empreinte du contenu, ce qui permet de prouver qu'il n'a pas été
+// modifié et évite de stocker deux fois un document identique.
+
+use crate::templates::checksum;
+use std::path::{Path, PathBuf};
+
+pub fn archive_path(app_data_dir: &Path, sha256: &str) -> PathBuf {
+    let prefix = sha256.get(..2).unwrap_or("00");
+    app_data_dir.join("archive").join(prefix).join(sha256)
+}
+
+/// Archive le contenu et renvoie son empreinte SHA-256.
+pub fn store(app_data_dir: &Path, bytes: &[u8]) -> Result<String, String> {
+    let sha256 = checksum(bytes);
+    let path = archive_path(app_data_dir, &sha256);
+    if path.exists() {
+        return Ok(sha256);
+    }
+
+    let parent = path.parent().ok_or("Invalid archive path")?;
+    std::fs::create_dir_all(parent)
+        .map_err(|e| format!("Failed to create archive directory: {}", e))?;
+    // Écriture dans un fichier temporaire puis renommage : une archive
+    // présente est toujours complète
+    let temp = parent.join(format!("{}.tmp", sha256));
+    std::fs::write(&temp, bytes)
+        .map_err(|e| format!("Failed to archive document: {}", e))?;
+    std::fs::rename(&temp, &path)
+        .map_err(|e| format!("Failed to archive document: {}", e))?;
+    Ok(sha256)
+}
+
+/// Contenu archivé, vérifié par son empreinte.
+pub fn read(app_data_dir: &Path, sha256: &str) -> Result<Vec<u8>, String> {
+    let path = archive_path(app_data_dir, sha256);
+    let bytes = std::fs::read(&path)
+        .map_err(|e| format!("Archived document not found ({}): {}", sha256, e))?;
+    if checksum(&bytes) != sha256 {
+        return Err(format!("Archived document {} has been altered (checksum mismatch)", sha256));
+    }
+    Ok(bytes)
+}
@@ -0,0 +1,147 @@
+// Transfert des pièces jointes avec le serveur
+//
+// Les fichiers des documents sont conservés localement dans l'archive
+// (voir `archive`) et envoyés au serveur par morceaux, identifiés par leur
+// SHA-256 : un fichier déjà connu du serveur n'est pas renvoyé, un envoi
+// interrompu reprend là où il s'était arrêté. Les fichiers des collègues ne
+// sont téléchargés qu'à l'ouverture du document.
+
+use crate::templates::checksum;
+use serde::{Deserialize, Serialize};
+
+/// Taille des morceaux envoyés et reçus
+pub const CHUNK_SIZE: usize = 1024 * 1024;
+
+/// État d'un fichier côté serveur
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BlobStatus {
+    pub sha256: String,
+    pub size: i64,
+    pub received: i64,
+    pub complete: bool,
+}
+
+/// État de synchronisation d'une pièce jointe
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AttachmentSyncState {
+    /// Présent localement et sur le serveur
+    Synced,
+    /// Présent localement, pas encore envoyé
+    PendingUpload,
+    /// Sur le serveur seulement, téléchargé à l'ouverture
+    Remote,
+    /// Dernier transfert en échec
+    Error,
+}
+
+async fn blob_status(client: &reqwest::Client, server_url: &str, sha256: &str) -> Result<Option<BlobStatus>, String> {
+    let response = client
+        .get(format!("{}/api/blobs/{}", server_url, sha256))
+        .send()
+        .await
+        .map_err(|e| format!("Failed to reach server: {}", e))?;
+    if response.status() == reqwest::StatusCode::NOT_FOUND {
+        return Ok(None);
+    }
+    if !response.status().is_success() {
+        return Err(format!("Server returned status: {}", response.status()));
+    }
+    response
+        .json()
+        .await
+        .map(Some)
+        .map_err(|e| format!("Invalid blob status: {}", e))
+}
+
+/// Envoie le fichier au serveur s'il ne l'a pas déjà.
+pub async fn upload(client: &reqwest::Client, server_url: &str, sha256: &str, bytes: &[u8]) -> Result<(), String> {
+    let response = client
+        .post(format!("{}/api/blobs/{}", server_url, sha256))
+        .json(&serde_json::json!({ "size": bytes.len() }))
+        .send()
+        .await
+        .map_err(|e| format!("Failed to reach server: {}", e))?;
+    // 409/422 : taille différente de celle déjà déclarée pour ce fichier
+    if !response.status().is_success() {
+        return Err(format!("Server refused upload: {}", response.status()));
+    }
+    let mut status: BlobStatus = response
+        .json()
+        .await
+        .map_err(|e| format!("Invalid blob status: {}", e))?;
+    if status.size != bytes.len() as i64 {
+        return Err(format!("Server expects {} bytes for {}, file has {}", status.size, sha256, bytes.len()));
+    }
+
+    while !status.complete {
+        let offset = status.received.clamp(0, bytes.len() as i64) as usize;
+        let end = (offset + CHUNK_SIZE).min(bytes.len());
+        let response = client
+            .put(format!("{}/api/blobs/{}/chunks?offset={}", server_url, sha256, offset))
+            .header("Content-Type", "application/octet-stream")
+            .body(bytes[offset..end].to_vec())
+            .send()
+            .await
+            .map_err(|e| format!("Failed to upload chunk: {}", e))?;
+
+        let code = response.status();
+        // 409 : le serveur attend un autre offset, on reprend depuis son état
+        if !code.is_success() && code != reqwest::StatusCode::CONFLICT {
+            return Err(format!("Server rejected chunk at {}: {}", offset, code));
+        }
+        let next: BlobStatus = response
+            .json()
+            .await
+            .map_err(|e| format!("Invalid blob status: {}", e))?;
+        // Un morceau accepté ou refusé doit faire avancer l'envoi, sinon on
+        // renverrait le même indéfiniment
+        if !next.complete && next.received <= status.received {
+            return Err(format!(
+                "Upload of {} is not progressing ({} of {} bytes received)",
+                sha256, next.received, next.size
+            ));
+        }
+        status = next;
+    }
+    Ok(())
+}
+
+/// Télécharge un fichier complet du serveur et vérifie son empreinte.
+pub async fn download(client: &reqwest::Client, server_url: &str, sha256: &str) -> Result<Vec<u8>, String> {
+    let status = blob_status(client, server_url, sha256)
+        .await?
+        .filter(|s| s.complete)
+        .ok_or_else(|| format!("Attachment {} is not available on the server", sha256))?;
+
+    let mut bytes = Vec::with_capacity(status.size.max(0) as usize);
+    while (bytes.len() as i64) < status.size {
+        let response = client
+            .get(format!(
+                "{}/api/blobs/{}/chunks?offset={}&length={}",
+                server_url,
+                sha256,
+                bytes.len(),
+                CHUNK_SIZE
+            ))
+            .send()
+            .await
+            .map_err(|e| format!("Failed to download chunk: {}", e))?;
+        if !response.status().is_success() {
+            return Err(format!("Server returned status: {}", response.status()));
+        }
+        let chunk = response
+            .bytes()
+            .await
+            .map_err(|e| format!("Failed to download chunk: {}", e))?;
+        if chunk.is_empty() {
+            return Err(format!("Attachment {} is truncated on the server", sha256));
+        }
+        bytes.extend_from_slice(&chunk);
+    }
+
+    if checksum(&bytes) != sha256 {
+        return Err(format!("Attachment {} is corrupted (checksum mismatch)", sha256));
+    }
+    Ok(bytes)
+}
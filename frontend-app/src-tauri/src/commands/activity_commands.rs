@@ -0,0 +1,345 @@
+use crate::db::{models::{Activity, InvoiceWithDetails, Proposal, TimelineEntry}, queries, DbPool};
+use crate::commands::sync_commands::mark_record_deleted;
+use crate::sync_scheduler::notify_local_change;
+use chrono::{DateTime, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+use tauri::State;
+
+/// Types d'activité acceptés
+pub const ACTIVITY_TYPES: &[&str] = &["call", "meeting", "email", "note"];
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ActivityRequest {
+    pub company_id: String,
+    pub activity_type: String,
+    /// Horodatage en millisecondes ; maintenant si absent
+    pub occurred_at: Option<i64>,
+    pub duration_minutes: Option<i64>,
+    pub subject: Option<String>,
+    pub notes: Option<String>,
+    pub proposal_id: Option<String>,
+    /// Identifiants de contacts de la société
+    #[serde(default)]
+    pub participants: Vec<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct UpdateActivityRequest {
+    pub id: String,
+    pub activity_type: String,
+    pub occurred_at: i64,
+    pub duration_minutes: Option<i64>,
+    pub subject: Option<String>,
+    pub notes: Option<String>,
+    pub proposal_id: Option<String>,
+    #[serde(default)]
+    pub participants: Vec<String>,
+}
+
+/// Vérifie le type, la durée, les participants et la proposition par rapport
+/// à la société de l'activité.
+async fn validate_activity(
+    pool: &DbPool,
+    company_id: &str,
+    activity_type: &str,
+    duration_minutes: Option<i64>,
+    proposal_id: Option<&str>,
+    participants: &[String],
+) -> Result<(), String> {
+    if !ACTIVITY_TYPES.contains(&activity_type) {
+        return Err(format!("Unknown activity type: {}", activity_type));
+    }
+    if duration_minutes.is_some_and(|d| d < 0) {
+        return Err("Duration cannot be negative".to_string());
+    }
+
+    if !participants.is_empty() {
+        let contacts = queries::get_company_contacts(pool, company_id)
+            .await
+            .map_err(|e| format!("Failed to get contacts: {}", e))?;
+        if let Some(unknown) = participants.iter().find(|id| {
+            !contacts.iter().any(|c| &c.id == *id && c.is_deleted.unwrap_or(0) == 0)
+        }) {
+            return Err(format!("Contact {} does not belong to this company", unknown));
+        }
+    }
+
+    if let Some(proposal_id) = proposal_id {
+        let proposal = queries::get_proposal_by_id(pool, proposal_id)
+            .await
+            .map_err(|e| format!("Failed to get proposal: {}", e))?;
+        match proposal {
+            Some(p) if p.company_id == company_id => {}
+            Some(_) => return Err(format!("Proposal {} does not belong to this company", proposal_id)),
+            None => return Err(format!("Proposal not found: {}", proposal_id)),
+        }
+    }
+    Ok(())
+}
+
+/// Participants sans doublon, dans l'ordre donné
+fn unique_participants(participants: Vec<String>) -> Vec<String> {
+    let mut unique: Vec<String> = Vec::with_capacity(participants.len());
+    for id in participants {
+        if !unique.contains(&id) {
+            unique.push(id);
+        }
+    }
+    unique
+}
+
+#[tauri::command]
+pub async fn log_activity(pool: State<'_, DbPool>, request: ActivityRequest) -> Result<Activity, String> {
+    // Une société fusionnée entre-temps est remplacée par son survivant
+    let mut conn = pool
+        .acquire()
+        .await
+        .map_err(|e| format!("Failed to get company: {}", e))?;
+    let company_id = queries::resolve_merged_company(&mut conn, &request.company_id)
+        .await
+        .map_err(|e| format!("Failed to get company: {}", e))?;
+    drop(conn);
+    queries::get_company_by_id(&pool, &company_id)
+        .await
+        .map_err(|e| format!("Failed to get company: {}", e))?
+        .ok_or_else(|| format!("Company not found: {}", company_id))?;
+
+    let participants = unique_participants(request.participants);
+    validate_activity(
+        &pool,
+        &company_id,
+        &request.activity_type,
+        request.duration_minutes,
+        request.proposal_id.as_deref(),
+        &participants,
+    )
+    .await?;
+
+    let activity = queries::create_activity(
+        &pool,
+        &company_id,
+        &request.activity_type,
+        request.occurred_at.unwrap_or_else(|| Utc::now().timestamp_millis()),
+        request.duration_minutes,
+        request.subject,
+        request.notes,
+        request.proposal_id,
+        participants,
+    )
+    .await
+    .map_err(|e| format!("Failed to log activity: {}", e))?;
+
+    println!("📝 Activity {} logged for company {}", activity.activity_type, company_id);
+    notify_local_change();
+    Ok(activity)
+}
+
+#[tauri::command]
+pub async fn update_activity(pool: State<'_, DbPool>, request: UpdateActivityRequest) -> Result<Activity, String> {
+    let existing = queries::get_activity_by_id(&pool, &request.id)
+        .await
+        .map_err(|e| format!("Failed to get activity: {}", e))?
+        .ok_or_else(|| format!("Activity not found: {}", request.id))?;
+
+    let participants = unique_participants(request.participants);
+    validate_activity(
+        &pool,
+        &existing.company_id,
+        &request.activity_type,
+        request.duration_minutes,
+        request.proposal_id.as_deref(),
+        &participants,
+    )
+    .await?;
+
+    let activity = queries::update_activity(
+        &pool,
+        &request.id,
+        &request.activity_type,
+        request.occurred_at,
+        request.duration_minutes,
+        request.subject,
+        request.notes,
+        request.proposal_id,
+        participants,
+    )
+    .await
+    .map_err(|e| format!("Failed to update activity: {}", e))?
+    .ok_or_else(|| format!("Activity not found: {}", request.id))?;
+    notify_local_change();
+    Ok(activity)
+}
+
+#[tauri::command]
+pub async fn delete_activity(pool: State<'_, DbPool>, id: String) -> Result<(), String> {
+    mark_record_deleted(&pool, "activities", &id)
+        .await
+        .map_err(|e| format!("Failed to delete activity: {}", e))
+}
+
+#[tauri::command]
+pub async fn get_activities(
+    pool: State<'_, DbPool>,
+    company_id: String,
+    proposal_id: Option<String>,
+) -> Result<Vec<Activity>, String> {
+    queries::get_activities(&pool, &company_id, proposal_id.as_deref())
+        .await
+        .map_err(|e| format!("Failed to get activities: {}", e))
+}
+
+/// Date d'un enregistrement en millisecondes : horodatage, RFC 3339 ou date
+/// seule ("YYYY-MM-DD", comme les dates de facture)
+fn timestamp_millis(value: &str) -> Option<i64> {
+    let value = value.trim();
+    if let Ok(millis) = value.parse::<i64>() {
+        return Some(millis);
+    }
+    if let Ok(date) = DateTime::parse_from_rfc3339(value) {
+        return Some(date.timestamp_millis());
+    }
+    NaiveDate::parse_from_str(value, "%Y-%m-%d")
+        .ok()
+        .and_then(|d| d.and_hms_opt(0, 0, 0))
+        .map(|d| d.and_utc().timestamp_millis())
+}
+
+/// Historique d'une société, du plus récent au plus ancien : activités,
+/// créations de propositions, émissions et paiements de factures.
+#[tauri::command]
+pub async fn get_company_timeline(pool: State<'_, DbPool>, company_id: String) -> Result<Vec<TimelineEntry>, String> {
+    let activities = queries::get_activities(&pool, &company_id, None)
+        .await
+        .map_err(|e| format!("Failed to get activities: {}", e))?;
+    let proposals = queries::get_proposals_by_company(&pool, &company_id)
+        .await
+        .map_err(|e| format!("Failed to get proposals: {}", e))?;
+    let invoices = queries::get_invoices_by_company(&pool, &company_id)
+        .await
+        .map_err(|e| format!("Failed to get invoices: {}", e))?;
+
+    Ok(build_timeline(activities, proposals, invoices))
+}
+
+fn build_timeline(
+    activities: Vec<Activity>,
+    proposals: Vec<Proposal>,
+    invoices: Vec<InvoiceWithDetails>,
+) -> Vec<TimelineEntry> {
+    let mut timeline = Vec::new();
+    for activity in activities {
+        timeline.push(TimelineEntry {
+            kind: "activity".to_string(),
+            occurred_at: timestamp_millis(&activity.occurred_at).unwrap_or(0),
+            reference: None,
+            status: None,
+            proposal_id: activity.proposal_id.clone(),
+            invoice_id: None,
+            activity: Some(activity),
+        });
+    }
+    for proposal in proposals {
+        timeline.push(TimelineEntry {
+            kind: "proposal_created".to_string(),
+            occurred_at: timestamp_millis(&proposal.created_at).unwrap_or(0),
+            reference: Some(proposal.proposal_number.unwrap_or_else(|| proposal.id.clone())),
+            status: Some(proposal.status),
+            proposal_id: Some(proposal.id),
+            invoice_id: None,
+            activity: None,
+        });
+    }
+    for invoice in invoices {
+        if let Some(paid_at) = invoice.paid_date.as_deref().and_then(timestamp_millis) {
+            timeline.push(TimelineEntry {
+                kind: "invoice_paid".to_string(),
+                occurred_at: paid_at,
+                reference: Some(invoice.invoice_number.clone()),
+                status: Some(invoice.status.clone()),
+                proposal_id: Some(invoice.proposal_id.clone()),
+                invoice_id: Some(invoice.id.clone()),
+                activity: None,
+            });
+        }
+        timeline.push(TimelineEntry {
+            kind: "invoice_issued".to_string(),
+            occurred_at: timestamp_millis(&invoice.issue_date)
+                .or_else(|| timestamp_millis(&invoice.created_at))
+                .unwrap_or(0),
+            reference: Some(invoice.invoice_number),
+            status: Some(invoice.status),
+            proposal_id: Some(invoice.proposal_id),
+            invoice_id: Some(invoice.id),
+            activity: None,
+        });
+    }
+
+    timeline.sort_by_key(|entry| std::cmp::Reverse(entry.occurred_at));
+    timeline
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::models::test_fixtures;
+    use serde_json::json;
+
+    fn activity(occurred_at: &str, subject: Option<&str>) -> Activity {
+        serde_json::from_value(json!({
+            "id": "a-1", "company_id": "c-1", "activity_type": "call", "occurred_at": occurred_at,
+            "duration_minutes": 15, "subject": subject, "notes": null, "proposal_id": "p-1",
+            "participants": [], "created_at": "1", "updated_at": "1", "version": 1, "is_deleted": 0
+        }))
+        .unwrap()
+    }
+
+    fn proposal(number: Option<&str>) -> Proposal {
+        Proposal {
+            proposal_number: number.map(str::to_string),
+            status: "sent".to_string(),
+            created_at: "1751270400000".to_string(),
+            updated_at: "1751270400000".to_string(),
+            ..test_fixtures::proposal("p-1", "c-1")
+        }
+    }
+
+    fn invoice(paid_date: Option<&str>) -> InvoiceWithDetails {
+        InvoiceWithDetails {
+            status: "paid".to_string(),
+            paid_date: paid_date.map(str::to_string),
+            ..test_fixtures::invoice("25070001")
+        }
+    }
+
+    #[test]
+    fn dates_are_read_as_millis_rfc3339_or_day() {
+        assert_eq!(timestamp_millis(" 1751270400000 "), Some(1751270400000));
+        assert_eq!(timestamp_millis("2025-06-30T10:00:00+02:00"), Some(1751270400000));
+        assert_eq!(timestamp_millis("2025-06-30"), Some(1751241600000));
+        assert_eq!(timestamp_millis("hier"), None);
+    }
+
+    #[test]
+    fn timeline_returns_codes_newest_first() {
+        let timeline = build_timeline(
+            vec![activity("1753000000000", Some("Relance"))],
+            vec![proposal(None)],
+            vec![invoice(Some("2025-08-01"))],
+        );
+        let kinds: Vec<&str> = timeline.iter().map(|e| e.kind.as_str()).collect();
+        assert_eq!(kinds, ["invoice_paid", "invoice_issued", "activity", "proposal_created"]);
+
+        assert_eq!(timeline[0].reference.as_deref(), Some("25070001"));
+        assert_eq!(timeline[0].invoice_id.as_deref(), Some("i-1"));
+        // Proposition sans numéro : son identifiant
+        assert_eq!(timeline[3].reference.as_deref(), Some("p-1"));
+        assert_eq!(timeline[3].status.as_deref(), Some("sent"));
+        // Le sujet reste dans l'activité, sans libellé composé
+        assert_eq!(timeline[2].reference, None);
+        assert_eq!(timeline[2].activity.as_ref().and_then(|a| a.subject.as_deref()), Some("Relance"));
+
+        let unpaid = build_timeline(vec![], vec![], vec![invoice(None)]);
+        assert_eq!(unpaid.len(), 1);
+        assert_eq!(unpaid[0].occurred_at, timestamp_millis("2025-07-23").unwrap());
+    }
+}
@@ -0,0 +1,169 @@
+use crate::archive;
+use crate::attachments::{self, AttachmentSyncState};
+use crate::db::{queries, DbPool};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::Path;
+use tauri::State;
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AttachmentState {
+    pub document_id: String,
+    pub sha256: String,
+    pub state: AttachmentSyncState,
+    pub last_error: Option<String>,
+}
+
+#[tauri::command]
+pub async fn get_attachment_states(
+    app_handle: tauri::AppHandle,
+    pool: State<'_, DbPool>,
+) -> Result<Vec<AttachmentState>, String> {
+    let app_dir = app_handle
+        .path_resolver()
+        .app_data_dir()
+        .ok_or("Could not find app data directory")?;
+    let documents = queries::get_attachment_documents(&pool)
+        .await
+        .map_err(|e| format!("Failed to get attachments: {}", e))?;
+    let transfers: HashMap<String, _> = queries::get_attachment_transfers(&pool)
+        .await
+        .map_err(|e| format!("Failed to get attachment transfers: {}", e))?
+        .into_iter()
+        .map(|t| (t.sha256.clone(), t))
+        .collect();
+
+    Ok(documents
+        .into_iter()
+        .filter_map(|document| {
+            let sha256 = document.sha256?;
+            let transfer = transfers.get(&sha256);
+            let local = archive::archive_path(&app_dir, &sha256).exists();
+            let uploaded = transfer.map(|t| t.uploaded != 0).unwrap_or(false);
+            let last_error = transfer.and_then(|t| t.last_error.clone());
+            let state = match (local, uploaded) {
+                _ if last_error.is_some() => AttachmentSyncState::Error,
+                (true, true) => AttachmentSyncState::Synced,
+                (true, false) => AttachmentSyncState::PendingUpload,
+                (false, _) => AttachmentSyncState::Remote,
+            };
+            Some(AttachmentState { document_id: document.id, sha256, state, last_error })
+        })
+        .collect())
+}
+
+#[tauri::command]
+pub async fn upload_attachments(
+    app_handle: tauri::AppHandle,
+    pool: State<'_, DbPool>,
+    server_url: String,
+) -> Result<usize, String> {
+    let app_dir = app_handle
+        .path_resolver()
+        .app_data_dir()
+        .ok_or("Could not find app data directory")?;
+    upload_pending_attachments(&app_dir, &pool, &server_url).await
+}
+
+/// Envoie au serveur les pièces jointes locales qu'il n'a pas encore et
+/// retourne le nombre de fichiers envoyés. Les échecs sont enregistrés par
+/// fichier sans interrompre les autres envois.
+pub async fn upload_pending_attachments(app_dir: &Path, pool: &DbPool, server_url: &str) -> Result<usize, String> {
+    let documents = queries::get_attachment_documents(pool)
+        .await
+        .map_err(|e| format!("Failed to get attachments: {}", e))?;
+    let uploaded: Vec<String> = queries::get_attachment_transfers(pool)
+        .await
+        .map_err(|e| format!("Failed to get attachment transfers: {}", e))?
+        .into_iter()
+        .filter(|t| t.uploaded != 0)
+        .map(|t| t.sha256)
+        .collect();
+
+    let mut pending: Vec<String> = documents
+        .into_iter()
+        .filter_map(|d| d.sha256)
+        .filter(|sha256| !uploaded.contains(sha256))
+        .filter(|sha256| archive::archive_path(app_dir, sha256).exists())
+        .collect();
+    pending.sort();
+    pending.dedup();
+
+    let client = reqwest::Client::new();
+    let mut count = 0;
+    for sha256 in pending {
+        let result = match archive::read(app_dir, &sha256) {
+            Ok(bytes) => attachments::upload(&client, server_url, &sha256, &bytes).await,
+            Err(e) => Err(e),
+        };
+        let error = result.err();
+        match &error {
+            None => {
+                count += 1;
+                println!("📤 [Attachments] Uploaded {}", sha256);
+            }
+            Some(e) => eprintln!("❌ [Attachments] Upload of {} failed: {}", sha256, e),
+        }
+        queries::set_attachment_transfer(pool, &sha256, error)
+            .await
+            .map_err(|e| format!("Failed to record attachment transfer: {}", e))?;
+    }
+
+    Ok(count)
+}
+
+/// Chemin local du fichier d'un document, téléchargé depuis le serveur s'il
+/// n'est pas encore présent sur ce poste. Le fichier est copié sous son nom
+/// d'origine pour pouvoir être ouvert avec l'application associée.
+#[tauri::command]
+pub async fn open_document_attachment(
+    app_handle: tauri::AppHandle,
+    pool: State<'_, DbPool>,
+    id: String,
+    server_url: String,
+) -> Result<String, String> {
+    let app_dir = app_handle
+        .path_resolver()
+        .app_data_dir()
+        .ok_or("Could not find app data directory")?;
+    let document = queries::get_document_by_id(&pool, &id)
+        .await
+        .map_err(|e| format!("Failed to get document: {}", e))?
+        .ok_or("Document not found")?;
+    let sha256 = document.sha256.ok_or("Document has no attachment")?;
+
+    let bytes = match archive::read(&app_dir, &sha256) {
+        Ok(bytes) => bytes,
+        Err(_) => {
+            println!("📥 [Attachments] Downloading {}", sha256);
+            let client = reqwest::Client::new();
+            let bytes = attachments::download(&client, &server_url, &sha256).await?;
+            archive::store(&app_dir, &bytes)?;
+            // Reçu du serveur : inutile de le renvoyer
+            queries::set_attachment_transfer(&pool, &sha256, None)
+                .await
+                .map_err(|e| format!("Failed to record attachment transfer: {}", e))?;
+            bytes
+        }
+    };
+
+    // Nom du fichier d'origine (avec son extension), à défaut le titre
+    let file_name = document
+        .file_path
+        .as_deref()
+        .and_then(|p| Path::new(p).file_name())
+        .or_else(|| Path::new(&document.title).file_name())
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or(sha256.clone());
+    let path = app_dir.join("attachments").join(&sha256).join(file_name);
+    if !path.exists() {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)
+                .map_err(|e| format!("Failed to create attachment directory: {}", e))?;
+        }
+        std::fs::write(&path, &bytes)
+            .map_err(|e| format!("Failed to write attachment: {}", e))?;
+    }
+
+    Ok(path.to_string_lossy().to_string())
+}
@@ -1,6 +1,22 @@
 use serde::{Deserialize, Serialize};
+use std::sync::Mutex;
+use tauri::State;
 use uuid::Uuid;
 
+/// Utilisateur connecté (e-mail), enregistré comme auteur des suppressions
+#[derive(Default)]
+pub struct CurrentUser(Mutex<Option<String>>);
+
+impl CurrentUser {
+    pub fn get(&self) -> Option<String> {
+        self.0.lock().unwrap().clone()
+    }
+
+    fn set(&self, email: Option<String>) {
+        *self.0.lock().unwrap() = email;
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct LoginRequest {
     pub email: String,
@@ -25,12 +41,14 @@ pub struct AuthResponse {
 
 #[tauri::command]
 pub async fn login(
+    user: State<'_, CurrentUser>,
     request: LoginRequest,
 ) -> Result<AuthResponse, String> {
     // Pour l'instant, on accepte n'importe quel email/password
     // Dans une vraie application, on vérifierait le hash du mot de passe
     
     let user_id = Uuid::new_v4().to_string();
+    user.set(Some(request.email.clone()));
     
     Ok(AuthResponse {
         success: true,
@@ -43,12 +61,14 @@ pub async fn login(
 
 #[tauri::command]
 pub async fn register(
+    user: State<'_, CurrentUser>,
     request: RegisterRequest,
 ) -> Result<AuthResponse, String> {
     // Pour l'instant, on accepte n'importe quel email/password
     // Dans une vraie application, on hasherait le mot de passe et on l'enregistrerait
     
     let user_id = Uuid::new_v4().to_string();
+    user.set(Some(request.email.clone()));
     
     Ok(AuthResponse {
         success: true,
@@ -60,8 +80,15 @@ pub async fn register(
 }
 
 #[tauri::command]
-pub async fn logout() -> Result<(), String> {
-    // Pour l'instant, on ne fait rien
+pub async fn logout(user: State<'_, CurrentUser>) -> Result<(), String> {
     // Dans une vraie application, on pourrait invalider un token JWT
+    user.set(None);
     Ok(())
-}
\ No newline at end of file
+}
+
+/// Session rouverte par l'interface (utilisateur mémorisé au démarrage)
+#[tauri::command]
+pub async fn restore_session(user: State<'_, CurrentUser>, email: String) -> Result<(), String> {
+    user.set(Some(email));
+    Ok(())
+}
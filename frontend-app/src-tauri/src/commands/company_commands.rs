@@ -1,10 +1,16 @@
-use crate::db::{models::{Company, CompanyWithContacts}, queries, DbPool};
-use crate::commands::sync_commands::{update_record_metadata, mark_record_deleted};
+use crate::db::{models::{Company, CompanyContact, CompanyMerge, CompanyWithContacts, ListQuery, Page}, queries, DbPool};
+use crate::dedupe::{self, DuplicateCandidate};
+use crate::commands::sync_commands::update_record_metadata;
+use crate::commands::auth_commands::CurrentUser;
+use crate::commands::trash_commands::trash_record;
+use crate::sync_scheduler::notify_local_change;
 use serde::{Deserialize, Serialize};
 use tauri::State;
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct ContactData {
+    /// Contact existant ; absent pour un nouveau contact
+    pub id: Option<String>,
     pub first_name: String,
     pub last_name: String,
     pub email: String,
@@ -43,19 +49,19 @@ pub async fn get_companies(pool: State<'_, DbPool>) -> Result<Vec<CompanyWithCon
         .await
         .map_err(|e| format!("Failed to get companies: {}", e))?;
 
-    let mut companies_with_contacts = Vec::new();
-    for company in companies {
-        let contacts = queries::get_company_contacts(&pool, &company.id)
-            .await
-            .map_err(|e| format!("Failed to get contacts: {}", e))?;
-        
-        companies_with_contacts.push(CompanyWithContacts {
-            company,
-            contacts,
-        });
-    }
+    queries::with_contacts(&pool, companies)
+        .await
+        .map_err(|e| format!("Failed to get contacts: {}", e))
+}
 
-    Ok(companies_with_contacts)
+#[tauri::command]
+pub async fn list_companies(
+    pool: State<'_, DbPool>,
+    query: Option<ListQuery>,
+) -> Result<Page<CompanyWithContacts>, String> {
+    queries::list_companies(&pool, &query.unwrap_or_default())
+        .await
+        .map_err(|e| format!("Failed to list companies: {}", e))
 }
 
 #[tauri::command]
@@ -128,8 +134,13 @@ pub async fn update_company(
     pool: State<'_, DbPool>,
     request: UpdateCompanyRequest,
 ) -> Result<Company, String> {
+    let mut tx = pool
+        .begin()
+        .await
+        .map_err(|e| format!("Failed to update company: {}", e))?;
+
     let company = queries::update_company(
-        &pool,
+        &mut tx,
         &request.id,
         request.name,
         request.website,
@@ -142,51 +153,90 @@ pub async fn update_company(
     .await
     .map_err(|e| format!("Failed to update company: {}", e))?;
 
-    // Delete old contacts and create new ones
-    queries::delete_company_contacts(&pool, &request.id)
+    // Contacts comparés par identifiant : mis à jour, créés ou marqués
+    // supprimés, pour que les autres postes reçoivent les mêmes identifiants
+    let now = chrono::Utc::now().timestamp_millis().to_string();
+    let mut kept_ids = Vec::with_capacity(request.contacts.len());
+    for contact_data in request.contacts {
+        let contact = CompanyContact {
+            id: contact_data.id.unwrap_or_default(),
+            company_id: company.id.clone(),
+            first_name: contact_data.first_name,
+            last_name: contact_data.last_name,
+            email: contact_data.email,
+            phone_number: contact_data.phone_number,
+            is_primary: i64::from(contact_data.is_primary),
+            created_at: now.clone(),
+            updated_at: now.clone(),
+            version: None,
+            is_deleted: None,
+        };
+        let saved = queries::save_company_contact(&mut tx, &contact, &now)
+            .await
+            .map_err(|e| format!("Failed to save contact: {}", e))?;
+        kept_ids.push(saved.id);
+    }
+    queries::delete_company_contacts_except(&mut tx, &company.id, &kept_ids, &now)
         .await
-        .map_err(|e| format!("Failed to delete old contacts: {}", e))?;
+        .map_err(|e| format!("Failed to delete removed contacts: {}", e))?;
 
-    for contact_data in request.contacts {
-        let contact = queries::create_company_contact(
-            &pool,
-            company.id.clone(),
-            contact_data.first_name,
-            contact_data.last_name,
-            contact_data.email,
-            contact_data.phone_number,
-            contact_data.is_primary,
-        )
+    tx.commit()
         .await
-        .map_err(|e| format!("Failed to create contact: {}", e))?;
+        .map_err(|e| format!("Failed to update company: {}", e))?;
+    notify_local_change();
 
-        // Update contact metadata for sync
-        update_record_metadata(&pool, "company_contacts", &contact.id).await
-            .map_err(|e| format!("Failed to update contact metadata: {}", e))?;
-    }
+    Ok(company)
+}
 
-    // Update company metadata for sync
-    update_record_metadata(&pool, "companies", &company.id).await
-        .map_err(|e| format!("Failed to update company metadata: {}", e))?;
+#[tauri::command]
+pub async fn delete_company(pool: State<'_, DbPool>, user: State<'_, CurrentUser>, id: String) -> Result<(), String> {
+    // À la corbeille avec ses contacts et activités
+    trash_record(&pool, &user, "companies", &id).await
+}
 
-    Ok(company)
+
+#[tauri::command]
+pub async fn find_duplicate_companies(
+    pool: State<'_, DbPool>,
+    threshold: Option<f64>,
+) -> Result<Vec<DuplicateCandidate>, String> {
+    let companies = queries::get_all_companies(&pool)
+        .await
+        .map_err(|e| format!("Failed to get companies: {}", e))?;
+
+    Ok(dedupe::find_duplicates(&companies, threshold.unwrap_or(dedupe::DEFAULT_THRESHOLD)))
 }
 
+/// Fusionne `merged_id` dans `survivor_id` ; la fusion est transmise aux
+/// autres postes par la synchronisation.
 #[tauri::command]
-pub async fn delete_company(pool: State<'_, DbPool>, id: String) -> Result<(), String> {
-    // Mark as deleted instead of hard delete for sync
-    mark_record_deleted(&pool, "companies", &id).await
-        .map_err(|e| format!("Failed to mark company as deleted: {}", e))?;
-
-    // Also mark all contacts as deleted
-    let contacts = queries::get_company_contacts(&pool, &id).await
-        .map_err(|e| format!("Failed to get company contacts: {}", e))?;
-    
-    for contact in contacts {
-        mark_record_deleted(&pool, "company_contacts", &contact.id).await
-            .map_err(|e| format!("Failed to mark contact as deleted: {}", e))?;
+pub async fn merge_companies(
+    pool: State<'_, DbPool>,
+    survivor_id: String,
+    merged_id: String,
+) -> Result<CompanyMerge, String> {
+    if survivor_id == merged_id {
+        return Err("Cannot merge a company into itself".to_string());
     }
 
-    Ok(())
+    let merge = queries::merge_companies(&pool, &survivor_id, &merged_id)
+        .await
+        .map_err(|e| match e {
+            sqlx::Error::RowNotFound => "Company not found".to_string(),
+            e => format!("Failed to merge companies: {}", e),
+        })?;
+
+    println!(
+        "🔀 Company {} merged into {} ({} contacts, {} proposals, {} documents moved)",
+        merge.merged_name, survivor_id, merge.contacts_moved, merge.proposals_moved, merge.documents_moved
+    );
+    notify_local_change();
+    Ok(merge)
 }
 
+#[tauri::command]
+pub async fn get_company_merges(pool: State<'_, DbPool>) -> Result<Vec<CompanyMerge>, String> {
+    queries::get_company_merges(&pool)
+        .await
+        .map_err(|e| format!("Failed to get company merges: {}", e))
+}
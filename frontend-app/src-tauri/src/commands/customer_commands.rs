@@ -1,4 +1,5 @@
 use crate::db::{models::Customer, queries, DbPool};
+use crate::sync_scheduler::notify_local_change;
 use serde::{Deserialize, Serialize};
 use tauri::State;
 
@@ -50,8 +51,7 @@ pub async fn create_customer(
     )
     .await
     .map_err(|e| format!("Failed to create customer: {}", e))?;
-
-    // Add to sync queue
+    notify_local_change();
 
     Ok(customer)
 }
@@ -72,16 +72,17 @@ pub async fn update_customer(
     )
     .await
     .map_err(|e| format!("Failed to update customer: {}", e))?;
-
+    notify_local_change();
 
     Ok(customer)
 }
 
 #[tauri::command]
 pub async fn delete_customer(pool: State<'_, DbPool>, id: String) -> Result<(), String> {
-
     queries::delete_customer(&pool, &id)
         .await
-        .map_err(|e| format!("Failed to delete customer: {}", e))
+        .map_err(|e| format!("Failed to delete customer: {}", e))?;
+    notify_local_change();
+    Ok(())
 }
 
@@ -1,10 +1,42 @@
-use crate::db::{models::Document, queries, DbPool};
+use crate::archive;
+use crate::commands::auth_commands::CurrentUser;
+use crate::commands::trash_commands::trash_record;
+use crate::db::{models::{Company, Document, DocumentLink, ListQuery, Page}, queries, DbPool};
+use crate::output::{self, FileNameFields, OutputSettings};
+use crate::settings::LocalSettings;
+use crate::templates::{checksum, KIND_PROPOSAL};
+use crate::sync_scheduler::notify_local_change;
 use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
 use tauri::State;
 
+/// Enregistrements auxquels un document peut être rattaché
+pub const ENTITY_TYPES: &[&str] = &["company", "proposal", "invoice", "customer"];
+
+fn check_entity_type(entity_type: &str) -> Result<(), String> {
+    if ENTITY_TYPES.contains(&entity_type) {
+        Ok(())
+    } else {
+        Err(format!("Unknown entity type: {}", entity_type))
+    }
+}
+
+/// Refuse un rattachement à un enregistrement inconnu ou à la corbeille.
+async fn check_entity(pool: &DbPool, entity_type: &str, entity_id: &str) -> Result<(), String> {
+    check_entity_type(entity_type)?;
+    let exists = queries::entity_exists(pool, entity_type, entity_id)
+        .await
+        .map_err(|e| format!("Failed to check {}: {}", entity_type, e))?;
+    if !exists {
+        return Err(format!("No {} with id {}", entity_type, entity_id));
+    }
+    Ok(())
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct CreateDocumentRequest {
-    pub customer_id: String,
+    pub customer_id: Option<String>,
+    pub company_id: Option<String>,
     pub title: String,
     pub document_type: String,
     pub file_path: Option<String>,
@@ -18,41 +50,348 @@ pub async fn get_documents(pool: State<'_, DbPool>) -> Result<Vec<Document>, Str
         .map_err(|e| format!("Failed to get documents: {}", e))
 }
 
+#[tauri::command]
+pub async fn list_documents(
+    pool: State<'_, DbPool>,
+    query: Option<ListQuery>,
+) -> Result<Page<Document>, String> {
+    queries::list_documents(&pool, &query.unwrap_or_default())
+        .await
+        .map_err(|e| format!("Failed to list documents: {}", e))
+}
+
 #[tauri::command]
 pub async fn get_customer_documents(
     pool: State<'_, DbPool>,
     customer_id: String,
 ) -> Result<Vec<Document>, String> {
-    queries::get_documents_by_customer(&pool, &customer_id)
+    queries::get_documents_by_entity(&pool, "customer", &customer_id)
         .await
         .map_err(|e| format!("Failed to get customer documents: {}", e))
 }
 
 #[tauri::command]
 pub async fn create_document(
+    app_handle: tauri::AppHandle,
     pool: State<'_, DbPool>,
     request: CreateDocumentRequest,
 ) -> Result<Document, String> {
+    // Le fichier indiqué est archivé pour être partagé comme pièce jointe
+    let sha256 = match request.file_path.as_deref().filter(|path| Path::new(path).is_file()) {
+        Some(path) => {
+            let bytes = std::fs::read(path)
+                .map_err(|e| format!("Failed to read attachment: {}", e))?;
+            let app_dir = app_handle
+                .path_resolver()
+                .app_data_dir()
+                .ok_or("Could not find app data directory")?;
+            Some(archive::store(&app_dir, &bytes)?)
+        }
+        None => None,
+    };
+
+    // Client et société ne sont portés que par les rattachements
+    let entities: Vec<(&str, String)> = [("customer", request.customer_id), ("company", request.company_id)]
+        .into_iter()
+        .filter_map(|(entity_type, id)| id.filter(|id| !id.is_empty()).map(|id| (entity_type, id)))
+        .collect();
+    for (entity_type, entity_id) in &entities {
+        check_entity(&pool, entity_type, entity_id).await?;
+    }
+
     let document = queries::create_document(
         &pool,
-        request.customer_id,
         request.title,
         request.document_type,
         request.file_path,
         request.content,
+        sha256,
     )
     .await
     .map_err(|e| format!("Failed to create document: {}", e))?;
 
+    for (entity_type, entity_id) in &entities {
+        queries::link_document(&pool, &document.id, entity_type, entity_id)
+            .await
+            .map_err(|e| format!("Failed to attach document: {}", e))?;
+    }
+    notify_local_change();
 
-    Ok(document)
+    queries::get_document_by_id(&pool, &document.id)
+        .await
+        .map_err(|e| format!("Failed to get document: {}", e))?
+        .ok_or_else(|| "Document not found".to_string())
 }
 
 #[tauri::command]
-pub async fn delete_document(pool: State<'_, DbPool>, id: String) -> Result<(), String> {
+pub async fn get_entity_documents(
+    pool: State<'_, DbPool>,
+    entity_type: String,
+    entity_id: String,
+) -> Result<Vec<Document>, String> {
+    check_entity_type(&entity_type)?;
+    queries::get_documents_by_entity(&pool, &entity_type, &entity_id)
+        .await
+        .map_err(|e| format!("Failed to get documents: {}", e))
+}
 
-    queries::delete_document(&pool, &id)
+#[tauri::command]
+pub async fn get_document_links(
+    pool: State<'_, DbPool>,
+    document_id: String,
+) -> Result<Vec<DocumentLink>, String> {
+    queries::get_document_links(&pool, &document_id)
         .await
-        .map_err(|e| format!("Failed to delete document: {}", e))
+        .map_err(|e| format!("Failed to get document links: {}", e))
 }
 
+#[tauri::command]
+pub async fn attach_document(
+    pool: State<'_, DbPool>,
+    document_id: String,
+    entity_type: String,
+    entity_id: String,
+) -> Result<DocumentLink, String> {
+    queries::get_document_by_id(&pool, &document_id)
+        .await
+        .map_err(|e| format!("Failed to get document: {}", e))?
+        .ok_or("Document not found")?;
+    check_entity(&pool, &entity_type, &entity_id).await?;
+
+    let link = queries::link_document(&pool, &document_id, &entity_type, &entity_id)
+        .await
+        .map_err(|e| format!("Failed to attach document: {}", e))?;
+    notify_local_change();
+    Ok(link)
+}
+
+#[tauri::command]
+pub async fn detach_document(
+    pool: State<'_, DbPool>,
+    document_id: String,
+    entity_type: String,
+    entity_id: String,
+) -> Result<(), String> {
+    check_entity_type(&entity_type)?;
+    let detached = queries::unlink_document(&pool, &document_id, Some((&entity_type, &entity_id)))
+        .await
+        .map_err(|e| format!("Failed to detach document: {}", e))?;
+    if detached == 0 {
+        return Err("Document is not attached to this record".to_string());
+    }
+    notify_local_change();
+    Ok(())
+}
+
+#[tauri::command]
+pub async fn get_company_documents(
+    pool: State<'_, DbPool>,
+    company_id: String,
+) -> Result<Vec<Document>, String> {
+    queries::get_documents_by_entity(&pool, "company", &company_id)
+        .await
+        .map_err(|e| format!("Failed to get company documents: {}", e))
+}
+
+#[tauri::command]
+pub async fn delete_document(pool: State<'_, DbPool>, user: State<'_, CurrentUser>, id: String) -> Result<(), String> {
+    // À la corbeille avec ses rattachements
+    trash_record(&pool, &user, "documents", &id).await
+}
+
+#[tauri::command]
+pub async fn get_output_settings(
+    app_handle: tauri::AppHandle,
+    pool: State<'_, DbPool>,
+) -> Result<OutputSettings, String> {
+    let local = LocalSettings::load(&pool, app_handle.path_resolver().app_data_dir().as_deref()).await?;
+    Ok(local.output)
+}
+
+#[tauri::command]
+pub async fn set_output_settings(
+    app_handle: tauri::AppHandle,
+    pool: State<'_, DbPool>,
+    settings: OutputSettings,
+) -> Result<(), String> {
+    let mut local = LocalSettings::load(&pool, app_handle.path_resolver().app_data_dir().as_deref()).await?;
+    local.output = settings;
+    local.save(&pool).await?;
+    println!("📁 Output settings saved (directory: {:?})", local.output.directory);
+    Ok(())
+}
+
+/// Fichier produit par une commande de génération.
+pub struct GeneratedFile<'a> {
+    /// `templates::KIND_PROPOSAL` ou `templates::KIND_INVOICE`, également
+    /// utilisé comme `source_type` du document archivé
+    pub kind: &'a str,
+    /// Identifiant de la proposition ou de la facture d'origine
+    pub source_id: &'a str,
+    pub company: &'a Company,
+    pub number: &'a str,
+    /// Dernière modification des données du document (millisecondes),
+    /// pour décider si une nouvelle révision est nécessaire
+    pub modified_at: i64,
+    /// Ajouté au nom issu du modèle (ex. `_ubl`)
+    pub suffix: &'a str,
+    pub extension: &'a str,
+}
+
+/// Date de modification la plus récente parmi des `updated_at` en
+/// millisecondes ; les valeurs illisibles sont ignorées.
+pub fn modified_at<'a>(updated_at: impl IntoIterator<Item = &'a str>) -> i64 {
+    updated_at
+        .into_iter()
+        .filter_map(|value| value.trim().parse::<i64>().ok())
+        .max()
+        .unwrap_or(0)
+}
+
+/// Enregistre un document généré dans le dossier de sortie, sous le nom
+/// défini par les réglages et sans écraser de fichier existant. Une copie
+/// est archivée sous son empreinte et tracée dans `documents`, rattachée à
+/// l'enregistrement d'origine et, si l'option est activée, à la société.
+pub async fn save_generated_file(
+    app_handle: &tauri::AppHandle,
+    pool: &DbPool,
+    file: GeneratedFile<'_>,
+    bytes: &[u8],
+) -> Result<PathBuf, String> {
+    let app_dir = app_handle
+        .path_resolver()
+        .app_data_dir()
+        .ok_or("Could not find app data directory")?;
+    let settings = LocalSettings::load(pool, Some(&app_dir)).await?.output;
+    let (pattern, document_type) = if file.kind == KIND_PROPOSAL {
+        (&settings.proposal_pattern, "quote")
+    } else {
+        (&settings.invoice_pattern, "invoice")
+    };
+
+    let revision = queries::next_document_revision(pool, file.kind, file.source_id, file.modified_at)
+        .await
+        .map_err(|e| format!("Failed to get document revision: {}", e))?;
+    let fields = FileNameFields::new(&file.company.name, file.number, revision);
+    let name = format!("{}{}", output::render_pattern(pattern, &fields), file.suffix);
+    let path = output::write_unique(&settings.output_dir()?, &name, file.extension, bytes)?;
+    let sha256 = archive::store(&app_dir, bytes)?;
+
+    let title = path
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or(name);
+    let document = queries::create_archived_document(
+        pool,
+        title,
+        document_type.to_string(),
+        path.to_string_lossy().to_string(),
+        file.kind,
+        file.source_id,
+        revision,
+        sha256.clone(),
+    )
+    .await
+    .map_err(|e| format!("Failed to record document: {}", e))?;
+
+    // Toujours visible depuis la proposition ou la facture d'origine
+    queries::link_document(pool, &document.id, file.kind, file.source_id)
+        .await
+        .map_err(|e| format!("Failed to attach document: {}", e))?;
+    println!("🗄️ Document archived ({})", sha256);
+    notify_local_change();
+
+    if settings.auto_attach {
+        queries::link_document(pool, &document.id, "company", &file.company.id)
+            .await
+            .map_err(|e| format!("Failed to attach document: {}", e))?;
+        println!("📎 Document attached to {}", file.company.name);
+    }
+
+    Ok(path)
+}
+
+#[tauri::command]
+pub async fn get_source_documents(
+    pool: State<'_, DbPool>,
+    source_type: String,
+    source_id: String,
+) -> Result<Vec<Document>, String> {
+    queries::get_documents_by_source(&pool, &source_type, &source_id)
+        .await
+        .map_err(|e| format!("Failed to get documents: {}", e))
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DocumentVerification {
+    pub id: String,
+    pub sha256: String,
+    /// Copie présente dans l'archive et conforme à l'empreinte
+    pub archived: bool,
+    /// Fichier du dossier de sortie toujours identique à l'archive
+    pub file_unchanged: Option<bool>,
+}
+
+/// Vérifie l'archive d'un document généré et, s'il existe encore, le
+/// fichier remis à l'utilisateur.
+#[tauri::command]
+pub async fn verify_document(
+    app_handle: tauri::AppHandle,
+    pool: State<'_, DbPool>,
+    id: String,
+) -> Result<DocumentVerification, String> {
+    let document = archived_document(&pool, &id).await?;
+    let sha256 = document.sha256.clone().unwrap_or_default();
+    let app_dir = app_handle
+        .path_resolver()
+        .app_data_dir()
+        .ok_or("Could not find app data directory")?;
+
+    let archived = archive::read(&app_dir, &sha256).is_ok();
+    let file_unchanged = document
+        .file_path
+        .as_deref()
+        .and_then(|path| std::fs::read(path).ok())
+        .map(|bytes| checksum(&bytes) == sha256);
+
+    Ok(DocumentVerification { id, sha256, archived, file_unchanged })
+}
+
+/// Copie le document archivé, tel qu'envoyé au client, dans le dossier de
+/// sortie et retourne le chemin de la copie.
+#[tauri::command]
+pub async fn restore_document(
+    app_handle: tauri::AppHandle,
+    pool: State<'_, DbPool>,
+    id: String,
+) -> Result<String, String> {
+    let document = archived_document(&pool, &id).await?;
+    let app_dir = app_handle
+        .path_resolver()
+        .app_data_dir()
+        .ok_or("Could not find app data directory")?;
+    let bytes = archive::read(&app_dir, document.sha256.as_deref().unwrap_or_default())?;
+
+    let title = Path::new(&document.title);
+    let name = title
+        .file_stem()
+        .map(|s| s.to_string_lossy().to_string())
+        .unwrap_or(document.id.clone());
+    let extension = title
+        .extension()
+        .map(|e| e.to_string_lossy().to_string())
+        .unwrap_or("bin".to_string());
+
+    let settings = LocalSettings::load(&pool, Some(&app_dir)).await?.output;
+    let path = output::write_unique(&settings.output_dir()?, &name, &extension, &bytes)?;
+    println!("🗄️ Archived document restored: {}", path.display());
+    Ok(path.to_string_lossy().to_string())
+}
+
+async fn archived_document(pool: &DbPool, id: &str) -> Result<Document, String> {
+    queries::get_document_by_id(pool, id)
+        .await
+        .map_err(|e| format!("Failed to get document: {}", e))?
+        .filter(|d| d.sha256.is_some())
+        .ok_or("Archived document not found".to_string())
+}
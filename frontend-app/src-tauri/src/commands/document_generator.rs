@@ -1,100 +1,505 @@
+use crate::commands::document_commands::{modified_at, save_generated_file, GeneratedFile};
+use crate::commands::template_commands::resolve_template;
 use crate::db::{queries, DbPool};
+use crate::db::models::{Company, CompanyContact, Proposal, ProposalProduct};
+use crate::docx::{DocxTemplate, TemplateData};
+use crate::pdf::layout::{Align, Column, DocumentLayout, PdfTemplate};
+use crate::templates::{TemplateCriteria, KIND_PROPOSAL};
+use std::collections::HashMap;
 use tauri::State;
-use serde_json::json;
 
-const DOC_API_URL: &str = "http://127.0.0.1:8001";
+/// Modèle de proposition livré avec l'application
+const PROPOSAL_TEMPLATE: &[u8] = include_bytes!("../../templates/proposal.docx");
+
+/// Placeholders renseignés par `proposal_template_data`.
+pub const PROPOSAL_FIELDS: &[&str] = &[
+    "companyname", "companyadresse", "villename", "zipcode", "offerreference",
+    "offerdate", "offervalidity", "pricetotal", "pricetotalsupport", "notes",
+    "name", "lastname", "email", "phone",
+];
+
+/// Champs d'un produit, utilisables seuls (premier produit) ou préfixés par
+/// le groupe `products` pour répéter une ligne de tableau.
+pub const PRODUCT_FIELDS: &[&str] = &[
+    "product", "user", "licence", "costslicence", "discount", "trainingprice",
+    "traningday", "years", "supportprice", "totalprice",
+];
+
+pub const PRODUCTS_GROUP: &str = "products";
 
 #[tauri::command]
 pub async fn generate_proposal_word(
+    app_handle: tauri::AppHandle,
     pool: State<'_, DbPool>,
     proposal_id: String,
+    template_id: Option<String>,
+    language: Option<String>,
 ) -> Result<String, String> {
     // Get proposal data
     let proposal = queries::get_proposal_by_id(&pool, &proposal_id)
         .await
         .map_err(|e| format!("Failed to get proposal: {}", e))?
         .ok_or("Proposal not found")?;
-    
+
     let products = queries::get_proposal_products(&pool, &proposal_id)
         .await
         .map_err(|e| format!("Failed to get products: {}", e))?;
-    
+
     let company = queries::get_company_by_id(&pool, &proposal.company_id)
         .await
         .map_err(|e| format!("Failed to get company: {}", e))?
         .ok_or("Company not found")?;
-    
+
     let contacts = queries::get_company_contacts(&pool, &company.id)
         .await
         .map_err(|e| format!("Failed to get contacts: {}", e))?;
-    
+
     let primary_contact = contacts.first();
-    
-    // Préparer les données pour l'API
-    let products_data: Vec<_> = products.iter().map(|p| {
-        json!({
-            "product_type": p.product_type,
-            "user_count": p.user_count,
-            "standalone_count": p.standalone_count,
-            "server_key_count": p.server_key_count,
-            "unit_price": p.unit_price,
-            "total_price": p.total_price,
-            "annual_reduction": p.annual_reduction,
-            "licence": p.licence == 1,
-            "training": p.training == 1,
-            "training_days": p.training_days,
-            "training_cost_per_day": p.training_cost_per_day,
-            "support": p.support == 1,
-            "support_years": p.support_years,
-        })
-    }).collect();
-    
+
+    // Modèle de la bibliothèque le plus adapté, sinon le modèle intégré
+    let criteria = TemplateCriteria {
+        company_id: Some(&company.id),
+        product_type: products.first().map(|p| p.product_type.as_str()),
+        language: language.as_deref(),
+    };
+    let library_template = resolve_template(&app_handle, &pool, KIND_PROPOSAL, template_id, criteria).await?;
+    let template_bytes = library_template.as_deref().unwrap_or(PROPOSAL_TEMPLATE);
+
+    // Rendu du document en local à partir du modèle Word
+    let data = proposal_template_data(&proposal, &products, &company, primary_contact);
+    let template = DocxTemplate::from_bytes(template_bytes)?;
+    let bytes = template.render(&data)?;
+
+    // Sauvegarder le fichier
     let proposal_num = proposal.proposal_number.clone().unwrap_or("DRAFT".to_string());
-    
-    let payload = json!({
-        "proposal_number": &proposal_num,
-        "company": {
-            "name": company.name,
-            "address": company.address,
-            "city": company.city,
-            "postal_code": company.postal_code,
-            "country": company.country,
-        },
-        "contact": primary_contact.map(|c| json!({
-            "first_name": c.first_name,
-            "last_name": c.last_name,
-            "email": c.email,
-            "phone_number": c.phone_number,
-        })),
-        "products": products_data,
-        "currency": proposal.currency,
-        "valid_until": proposal.valid_until,
-        "notes": proposal.notes,
-    });
-    
-    // Appeler l'API Python locale
-    let client = reqwest::Client::new();
-    let response = client
-        .post(&format!("{}/generate-word", DOC_API_URL))
-        .json(&payload)
-        .send()
+    let file = GeneratedFile {
+        kind: KIND_PROPOSAL,
+        source_id: &proposal.id,
+        company: &company,
+        number: &proposal_num,
+        modified_at: modified_at(
+            products.iter().map(|p| p.updated_at.as_str())
+                .chain([proposal.updated_at.as_str()]),
+        ),
+        suffix: "",
+        extension: "docx",
+    };
+    let output_path = save_generated_file(&app_handle, &pool, file, &bytes).await?;
+
+    println!("📄 Proposal document generated: {}", output_path.display());
+    Ok(output_path.to_string_lossy().to_string())
+}
+
+#[tauri::command]
+pub async fn generate_proposal_pdf(
+    app_handle: tauri::AppHandle,
+    pool: State<'_, DbPool>,
+    proposal_id: String,
+) -> Result<String, String> {
+    let proposal = queries::get_proposal_by_id(&pool, &proposal_id)
         .await
-        .map_err(|e| format!("Failed to call document API. Is the sidecar running? Error: {}", e))?;
-    
-    if !response.status().is_success() {
-        return Err(format!("Document API error: {}", response.status()));
-    }
-    
-    // Sauvegarder le fichier
-    let filename = format!("Proposal_{}.docx", proposal_num);
-    let home_dir = dirs::home_dir().ok_or("Could not find home directory")?;
-    let output_path = home_dir.join("Downloads").join(&filename);
-    
-    let bytes = response.bytes().await
-        .map_err(|e| format!("Failed to read response: {}", e))?;
-    
-    std::fs::write(&output_path, bytes)
-        .map_err(|e| format!("Failed to save file: {}", e))?;
-    
+        .map_err(|e| format!("Failed to get proposal: {}", e))?
+        .ok_or("Proposal not found")?;
+
+    let products = queries::get_proposal_products(&pool, &proposal_id)
+        .await
+        .map_err(|e| format!("Failed to get products: {}", e))?;
+
+    let company = queries::get_company_by_id(&pool, &proposal.company_id)
+        .await
+        .map_err(|e| format!("Failed to get company: {}", e))?
+        .ok_or("Company not found")?;
+
+    let contacts = queries::get_company_contacts(&pool, &company.id)
+        .await
+        .map_err(|e| format!("Failed to get contacts: {}", e))?;
+
+    let template = PdfTemplate::load(app_handle.path_resolver().app_data_dir())?;
+    let bytes = render_proposal_pdf(&template, &proposal, &products, &company, contacts.first())?;
+
+    let proposal_num = proposal.proposal_number.clone().unwrap_or("DRAFT".to_string());
+    let file = GeneratedFile {
+        kind: KIND_PROPOSAL,
+        source_id: &proposal.id,
+        company: &company,
+        number: &proposal_num,
+        modified_at: modified_at(
+            products.iter().map(|p| p.updated_at.as_str())
+                .chain([proposal.updated_at.as_str()]),
+        ),
+        suffix: "",
+        extension: "pdf",
+    };
+    let output_path = save_generated_file(&app_handle, &pool, file, &bytes).await?;
+
+    println!("📑 Proposal PDF generated: {}", output_path.display());
     Ok(output_path.to_string_lossy().to_string())
 }
+
+/// Proposition PDF/A reprenant les informations du modèle Word : client,
+/// références de l'offre et détail des prix par produit.
+pub fn render_proposal_pdf(
+    template: &PdfTemplate,
+    proposal: &Proposal,
+    products: &[ProposalProduct],
+    company: &Company,
+    contact: Option<&CompanyContact>,
+) -> Result<Vec<u8>, String> {
+    let data = proposal_template_data(proposal, products, company, contact);
+    let value = |key: &str| data.values.get(key).cloned().unwrap_or_default();
+    let reference = value("offerreference");
+
+    let writer = template.writer(format!("Proposal {}", reference))?;
+    let mut layout = DocumentLayout::new(template, writer);
+    layout.title(&format!("Commercial proposal {}", reference));
+    layout.key_values(&[
+        ("Date", value("offerdate")),
+        ("Valid until", value("offervalidity")),
+    ]);
+
+    layout.heading("Customer");
+    layout.paragraph(&company.name, 10.0);
+    let city = format!("{} {}", value("zipcode"), value("villename"));
+    for line in [value("companyadresse"), city, company.country.clone().unwrap_or_default()] {
+        if !line.trim().is_empty() {
+            layout.paragraph(line.trim(), 10.0);
+        }
+    }
+    if let Some(contact) = contact {
+        layout.spacer(6.0);
+        layout.key_values(&[
+            ("Contact", format!("{} {}", contact.first_name, contact.last_name)),
+            ("Email", contact.email.clone()),
+            ("Phone", contact.phone_number.clone().unwrap_or_default()),
+        ]);
+    }
+
+    layout.heading("Offer");
+    let columns = [
+        Column { title: "Product", width: 140.0, align: Align::Left },
+        Column { title: "Licence", width: 80.0, align: Align::Right },
+        Column { title: "Discount", width: 50.0, align: Align::Right },
+        Column { title: "Training", width: 75.0, align: Align::Right },
+        Column { title: "Support", width: 75.0, align: Align::Right },
+        Column { title: "Total", width: 75.0, align: Align::Right },
+    ];
+    let rows: Vec<Vec<String>> = data
+        .rows
+        .get(PRODUCTS_GROUP)
+        .map(|rows| {
+            rows.iter()
+                .map(|row| {
+                    let cell = |key: &str| row.get(key).cloned().unwrap_or_default();
+                    let product = match cell("licence").as_str() {
+                        "0" => cell("product"),
+                        licence => format!("{}\n{}", cell("product"), licence),
+                    };
+                    vec![
+                        product,
+                        cell("costslicence"),
+                        cell("discount"),
+                        cell("trainingprice"),
+                        cell("supportprice"),
+                        cell("totalprice"),
+                    ]
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+    layout.table(&columns, &rows);
+    layout.totals(&[(
+        &format!("Total ({})", proposal.currency),
+        value("pricetotal"),
+        true,
+    )]);
+
+    layout.heading("Notes");
+    layout.paragraph(&value("notes"), 10.0);
+
+    layout.finish().render()
+}
+
+/// Valeurs des placeholders du modèle de proposition. Les champs produit
+/// simples (`{product}`, `{user}`...) décrivent le premier produit ; le
+/// groupe `products` permet de répéter une ligne de tableau par produit.
+pub fn proposal_template_data(
+    proposal: &Proposal,
+    products: &[ProposalProduct],
+    company: &Company,
+    contact: Option<&CompanyContact>,
+) -> TemplateData {
+    let mut data = TemplateData::default();
+    let total_amount: f64 = products.iter().map(|p| p.total_price).sum();
+
+    data.set("companyname", company.name.clone());
+    data.set("companyadresse", company.address.clone().unwrap_or_default());
+    data.set("villename", company.city.clone().unwrap_or_default());
+    data.set("zipcode", company.postal_code.clone().unwrap_or_default());
+    data.set("offerreference", proposal.proposal_number.clone().unwrap_or("DRAFT".to_string()));
+    data.set("offerdate", chrono::Local::now().format("%d/%m/%Y").to_string());
+    data.set("offervalidity", format_validity_date(&proposal.valid_until));
+    data.set("pricetotal", format_amount(total_amount));
+    data.set("pricetotalsupport", format_amount(total_amount));
+    data.set(
+        "notes",
+        proposal.notes.clone().filter(|n| !n.is_empty())
+            .unwrap_or_else(|| format!("Proposal for {}", company.name)),
+    );
+    data.set("name", contact.map(|c| c.first_name.clone()).unwrap_or_default());
+    data.set("lastname", contact.map(|c| c.last_name.clone()).unwrap_or_default());
+    data.set("email", contact.map(|c| c.email.clone()).unwrap_or_default());
+    data.set("phone", contact.and_then(|c| c.phone_number.clone()).unwrap_or_default());
+
+    if let Some(product) = products.first() {
+        for (key, value) in product_values(product) {
+            data.values.insert(key, value);
+        }
+        data.kept_tables = Some(kept_tables(product));
+    }
+
+    data.rows.insert(
+        PRODUCTS_GROUP.to_string(),
+        products.iter().map(product_values).collect(),
+    );
+
+    data
+}
+
+fn product_values(product: &ProposalProduct) -> HashMap<String, String> {
+    let mut values = HashMap::new();
+    values.insert("product".to_string(), product.product_type.clone());
+    values.insert("user".to_string(), product.user_count.to_string());
+    values.insert("licence".to_string(), licence_description(product));
+    values.insert("costslicence".to_string(), format_amount(product.unit_price));
+    values.insert("discount".to_string(), format!("{}%", format_float(product.annual_reduction)));
+    values.insert(
+        "trainingprice".to_string(),
+        if product.training == 1 {
+            format_amount(product.training_days as f64 * product.training_cost_per_day)
+        } else {
+            "0".to_string()
+        },
+    );
+    values.insert("traningday".to_string(), product.training_days.to_string());
+    values.insert("years".to_string(), product.support_years.to_string());
+    values.insert(
+        "supportprice".to_string(),
+        if product.support == 1 {
+            let base_price = product.unit_price * (1.0 - product.annual_reduction / 100.0);
+            format_amount(base_price * 0.20 * product.support_years as f64)
+        } else {
+            "0".to_string()
+        },
+    );
+    values.insert("totalprice".to_string(), format_amount(product.total_price));
+    values
+}
+
+/// Tableaux du modèle à conserver selon le produit : le tableau 0 (en-tête)
+/// est toujours conservé, puis un tableau licence (avec ou sans remise),
+/// le tableau formation et le tableau support du produit.
+fn kept_tables(product: &ProposalProduct) -> Vec<usize> {
+    let discounted = product.annual_reduction > 0.0;
+    let mut keep = vec![0];
+
+    if product.product_type.contains("HTZ Communications") {
+        keep.push(if discounted { 2 } else { 1 });
+    } else if product.product_type.contains("HTZ Warfare") {
+        keep.push(if discounted { 4 } else { 3 });
+    } else if product.product_type.contains("ICS Manager") {
+        keep.push(if discounted { 6 } else { 5 });
+    }
+
+    if product.training == 1 {
+        keep.push(7);
+    }
+
+    if product.support == 1 {
+        if product.product_type.contains("HTZ Communications") {
+            keep.push(10);
+        } else if product.product_type.contains("HTZ Warfare") {
+            keep.push(9);
+        } else if product.product_type.contains("ICS Manager") {
+            keep.push(12);
+        }
+    }
+
+    keep
+}
+
+fn licence_description(product: &ProposalProduct) -> String {
+    if product.licence != 1 {
+        return "0".to_string();
+    }
+
+    let mut parts = Vec::new();
+    if product.standalone_count > 0 {
+        parts.push(format!("{} standalone", product.standalone_count));
+    }
+    if product.server_key_count > 0 {
+        parts.push(format!(
+            "{} server key{}",
+            product.server_key_count,
+            if product.server_key_count > 1 { "s" } else { "" }
+        ));
+    }
+
+    if parts.is_empty() {
+        return format!(
+            "{} user{}",
+            product.user_count,
+            if product.user_count > 1 { "s" } else { "" }
+        );
+    }
+    parts.join(" and ")
+}
+
+fn format_validity_date(valid_until: &str) -> String {
+    if valid_until.is_empty() {
+        return "30 days".to_string();
+    }
+    if valid_until.contains('/') {
+        return valid_until.to_string();
+    }
+    let date_part = valid_until.split('T').next().unwrap_or(valid_until);
+    chrono::NaiveDate::parse_from_str(date_part, "%Y-%m-%d")
+        .map(|d| d.format("%d/%m/%Y").to_string())
+        .unwrap_or_else(|_| valid_until.to_string())
+}
+
+/// Nombre tel que l'affichait le générateur Python (`str(float)`) : 10.0, 12.5
+fn format_float(value: f64) -> String {
+    if value.fract() == 0.0 && value.abs() < 1e16 {
+        format!("{:.1}", value)
+    } else {
+        value.to_string()
+    }
+}
+
+/// Montant avec séparateur de milliers, ex. 12,345.60
+pub fn format_amount(value: f64) -> String {
+    let formatted = format!("{:.2}", value.abs());
+    let (integer, decimals) = formatted.split_once('.').unwrap_or((&formatted, "00"));
+    let mut grouped = String::new();
+    for (i, c) in integer.chars().enumerate() {
+        if i > 0 && (integer.len() - i) % 3 == 0 {
+            grouped.push(',');
+        }
+        grouped.push(c);
+    }
+    let sign = if value < 0.0 && formatted != "0.00" { "-" } else { "" };
+    format!("{}{}.{}", sign, grouped, decimals)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::models::test_fixtures;
+    use std::io::Read;
+
+    fn company() -> Company {
+        Company {
+            address: Some("12 Main Street".to_string()),
+            city: Some("Lyon".to_string()),
+            postal_code: Some("69001".to_string()),
+            country: Some("France".to_string()),
+            ..test_fixtures::company("c-1", "Acme & Co")
+        }
+    }
+
+    fn contact() -> CompanyContact {
+        CompanyContact {
+            phone_number: Some("+33 1 23 45 67 89".to_string()),
+            ..test_fixtures::contact("ct-1", "c-1")
+        }
+    }
+
+    fn proposal() -> Proposal {
+        Proposal {
+            proposal_number: Some("D_Acme_HTZW_250723".to_string()),
+            version: Some(3),
+            ..test_fixtures::proposal("p-1", "c-1")
+        }
+    }
+
+    fn product(product_type: &str, annual_reduction: f64) -> ProposalProduct {
+        ProposalProduct {
+            standalone_count: 2,
+            server_key_count: 1,
+            unit_price: 12345.6,
+            total_price: 15432.1,
+            annual_reduction,
+            training: 1,
+            training_days: 3,
+            training_cost_per_day: 1200.0,
+            training_cost: 3600.0,
+            support: 1,
+            support_years: 2,
+            ..test_fixtures::product(product_type)
+        }
+    }
+
+    // Valeurs produites par le générateur Python (sidecar `/generate-word`)
+    // pour les mêmes données
+    #[test]
+    fn template_values_match_python_generator() {
+        let products = vec![product("HTZ Warfare", 10.0)];
+        let data = proposal_template_data(&proposal(), &products, &company(), Some(&contact()));
+
+        let expected = [
+            ("companyname", "Acme & Co"),
+            ("companyadresse", "12 Main Street"),
+            ("villename", "Lyon"),
+            ("zipcode", "69001"),
+            ("offerreference", "D_Acme_HTZW_250723"),
+            ("offervalidity", "22/08/2025"),
+            ("pricetotal", "15,432.10"),
+            ("pricetotalsupport", "15,432.10"),
+            ("notes", "Proposal for Acme & Co"),
+            ("name", "Ada"),
+            ("lastname", "Lovelace"),
+            ("email", "ada@example.com"),
+            ("phone", "+33 1 23 45 67 89"),
+            ("product", "HTZ Warfare"),
+            ("user", "5"),
+            ("licence", "2 standalone and 1 server key"),
+            ("costslicence", "12,345.60"),
+            ("discount", "10.0%"),
+            ("trainingprice", "3,600.00"),
+            ("traningday", "3"),
+            ("years", "2"),
+            ("supportprice", "4,444.42"),
+        ];
+        for (key, value) in expected {
+            assert_eq!(data.values.get(key).map(String::as_str), Some(value), "{}", key);
+        }
+        assert_eq!(data.kept_tables, Some(vec![0, 4, 7, 9]));
+        assert_eq!(data.rows[PRODUCTS_GROUP].len(), 1);
+    }
+
+    #[test]
+    fn numbers_use_python_formatting() {
+        assert_eq!(format_float(10.0), "10.0");
+        assert_eq!(format_float(0.0), "0.0");
+        assert_eq!(format_float(12.5), "12.5");
+        assert_eq!(format_amount(1234567.891), "1,234,567.89");
+        assert_eq!(format_amount(-0.001), "0.00");
+        assert_eq!(format_amount(-950.0), "-950.00");
+    }
+
+    #[test]
+    fn builtin_template_renders_every_field() {
+        let products = vec![product("HTZ Communications", 0.0)];
+        let data = proposal_template_data(&proposal(), &products, &company(), Some(&contact()));
+        let rendered = DocxTemplate::from_bytes(PROPOSAL_TEMPLATE).unwrap().render(&data).unwrap();
+
+        let remaining = DocxTemplate::from_bytes(&rendered).unwrap().placeholders();
+        assert!(remaining.is_empty(), "placeholders left: {:?}", remaining);
+
+        let mut archive = zip::ZipArchive::new(std::io::Cursor::new(rendered)).unwrap();
+        let mut document = String::new();
+        archive.by_name("word/document.xml").unwrap().read_to_string(&mut document).unwrap();
+        assert!(document.contains("Acme &amp; Co"));
+        assert!(document.contains("D_Acme_HTZW_250723"));
+    }
+}
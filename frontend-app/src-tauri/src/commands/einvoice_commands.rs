@@ -0,0 +1,67 @@
+use crate::commands::document_commands::{modified_at, save_generated_file, GeneratedFile};
+use crate::db::{queries, DbPool};
+use crate::einvoice::{cii, facturx, ubl, EInvoice, EInvoiceFormat, EInvoiceOptions};
+use crate::templates::KIND_INVOICE;
+use tauri::State;
+
+/// Exporte une facture au format structuré demandé (UBL, CII ou Factur-X)
+/// dans le dossier de sortie et retourne le chemin du fichier.
+#[tauri::command]
+pub async fn export_einvoice(
+    app_handle: tauri::AppHandle,
+    pool: State<'_, DbPool>,
+    invoice_id: String,
+    format: EInvoiceFormat,
+    options: EInvoiceOptions,
+) -> Result<String, String> {
+    let invoice = queries::get_invoice_by_id(&pool, &invoice_id).await
+        .map_err(|e| e.to_string())?
+        .ok_or("Invoice not found")?;
+
+    let proposal = queries::get_proposal_by_id(&pool, &invoice.proposal_id).await
+        .map_err(|e| e.to_string())?
+        .ok_or("Proposal not found")?;
+
+    let products = queries::get_proposal_products(&pool, &invoice.proposal_id).await
+        .map_err(|e| e.to_string())?;
+
+    let company = queries::get_company_by_id(&pool, &proposal.company_id).await
+        .map_err(|e| e.to_string())?
+        .ok_or("Company not found")?;
+
+    let contacts = queries::get_company_contacts(&pool, &proposal.company_id).await
+        .map_err(|e| e.to_string())?;
+
+    // Contact de l'acheteur : le contact principal, à défaut le premier
+    let contact = contacts.iter().find(|c| c.is_primary == 1).or(contacts.first());
+
+    let einvoice = EInvoice::from_invoice(&invoice, &products, &company, contact, options)?;
+
+    let bytes = match format {
+        EInvoiceFormat::Ubl => ubl::to_xml(&einvoice).into_bytes(),
+        EInvoiceFormat::Cii => cii::to_xml(&einvoice).into_bytes(),
+        EInvoiceFormat::Facturx => facturx::to_pdf(&einvoice)?,
+    };
+
+    let suffix = match format {
+        EInvoiceFormat::Ubl => "_ubl",
+        EInvoiceFormat::Cii => "_cii",
+        EInvoiceFormat::Facturx => "",
+    };
+    let file = GeneratedFile {
+        kind: KIND_INVOICE,
+        source_id: &invoice.id,
+        company: &company,
+        number: &invoice.invoice_number,
+        modified_at: modified_at(
+            products.iter().map(|p| p.updated_at.as_str())
+                .chain([invoice.updated_at.as_str(), proposal.updated_at.as_str()]),
+        ),
+        suffix,
+        extension: format.file_extension(),
+    };
+    let output_path = save_generated_file(&app_handle, &pool, file, &bytes).await?;
+
+    println!("🧾 E-invoice exported: {}", output_path.display());
+    Ok(output_path.to_string_lossy().to_string())
+}
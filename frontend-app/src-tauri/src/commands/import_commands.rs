@@ -0,0 +1,141 @@
+use crate::db::{queries, DbPool};
+use crate::import::{self, ColumnMapping, CsvPreview, ImportFormat, RowReport, RowStatus};
+use crate::sync_scheduler::notify_local_change;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use tauri::State;
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ImportRequest {
+    pub file_path: String,
+    pub format: ImportFormat,
+    /// Correspondance des colonnes CSV ; proposée d'après les en-têtes si absente
+    pub mapping: Option<ColumnMapping>,
+    /// Valide et rapproche sans rien écrire
+    #[serde(default)]
+    pub dry_run: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ImportReport {
+    pub dry_run: bool,
+    pub rows: Vec<RowReport>,
+    pub companies_created: i64,
+    pub companies_matched: i64,
+    pub contacts_created: i64,
+    pub errors: i64,
+}
+
+fn read_import_file(file_path: &str) -> Result<String, String> {
+    std::fs::read(file_path)
+        .map(|bytes| import::decode(&bytes))
+        .map_err(|e| format!("Failed to read import file: {}", e))
+}
+
+/// En-têtes, correspondance proposée et premières lignes d'un fichier CSV
+#[tauri::command]
+pub async fn preview_csv_import(
+    file_path: String,
+    mapping: Option<ColumnMapping>,
+) -> Result<CsvPreview, String> {
+    let text = read_import_file(&file_path)?;
+    Ok(import::preview_csv(&text, mapping))
+}
+
+/// Importe sociétés et contacts d'un fichier CSV ou vCard. Les lignes en
+/// erreur sont écartées et signalées dans le rapport ; le reste est écrit
+/// en une transaction.
+#[tauri::command]
+pub async fn import_companies(
+    pool: State<'_, DbPool>,
+    request: ImportRequest,
+) -> Result<ImportReport, String> {
+    let text = read_import_file(&request.file_path)?;
+    let records = match request.format {
+        ImportFormat::Csv => {
+            let mapping = request
+                .mapping
+                .unwrap_or_else(|| import::preview_csv(&text, None).mapping);
+            import::csv_records(&text, &mapping)?
+        }
+        ImportFormat::Vcard => import::vcard::parse(&text),
+    };
+
+    let existing = queries::get_all_companies_with_deleted(&pool)
+        .await
+        .map_err(|e| format!("Failed to get companies: {}", e))?;
+    let emails: HashSet<String> = queries::get_contact_emails(&pool)
+        .await
+        .map_err(|e| format!("Failed to get contacts: {}", e))?
+        .into_iter()
+        .collect();
+
+    let plan = import::plan(records, &existing, &emails);
+    let report = ImportReport {
+        dry_run: request.dry_run,
+        companies_created: plan.companies.iter().filter(|c| c.existing_id.is_none()).count() as i64,
+        companies_matched: plan.companies.iter().filter(|c| c.existing_id.is_some()).count() as i64,
+        contacts_created: plan.companies.iter().map(|c| c.contacts.len() as i64).sum(),
+        errors: plan.rows.iter().filter(|r| r.status == RowStatus::Error).count() as i64,
+        rows: plan.rows,
+    };
+    if request.dry_run {
+        return Ok(report);
+    }
+
+    let mut tx = pool.begin().await.map_err(|e| format!("Failed to start import: {}", e))?;
+    for company in &plan.companies {
+        let company_id = match &company.existing_id {
+            Some(id) => {
+                // Société supprimée : survivante de la fusion, ou restaurée
+                let id = queries::resolve_merged_company(&mut tx, id)
+                    .await
+                    .map_err(|e| format!("Failed to resolve company {}: {}", id, e))?;
+                if queries::restore_imported_company(&mut tx, &id)
+                    .await
+                    .map_err(|e| format!("Failed to restore company {}: {}", id, e))?
+                {
+                    println!("♻️ Company {} restored from trash by import", id);
+                }
+                id
+            }
+            None => {
+                let fields = &company.fields;
+                queries::insert_imported_company(
+                    &mut tx,
+                    fields.name.trim(),
+                    fields.website.as_deref(),
+                    fields.address.as_deref(),
+                    fields.city.as_deref(),
+                    fields.postal_code.as_deref(),
+                    fields.country.as_deref(),
+                    fields.description.as_deref(),
+                )
+                .await
+                .map_err(|e| format!("Failed to import company {}: {}", fields.name, e))?
+                .id
+            }
+        };
+        for contact in &company.contacts {
+            queries::insert_imported_contact(
+                &mut tx,
+                &company_id,
+                &contact.first_name,
+                &contact.last_name,
+                &contact.email,
+                contact.phone_number.as_deref(),
+                contact.is_primary,
+            )
+            .await
+            .map_err(|e| format!("Failed to import contact {}: {}", contact.email, e))?;
+        }
+    }
+    tx.commit().await.map_err(|e| format!("Failed to import: {}", e))?;
+    notify_local_change();
+
+    println!(
+        "📥 Import completed: {} companies created, {} matched, {} contacts, {} rows rejected",
+        report.companies_created, report.companies_matched, report.contacts_created, report.errors
+    );
+    Ok(report)
+}
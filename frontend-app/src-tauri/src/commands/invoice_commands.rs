@@ -1,7 +1,19 @@
+use crate::commands::business_date;
 use crate::db::{queries, DbPool};
 use crate::db::models::*;
+use crate::commands::document_generator::format_amount;
+use crate::commands::document_commands::{modified_at, save_generated_file, GeneratedFile};
+use crate::commands::template_commands::resolve_template;
+use crate::commands::auth_commands::CurrentUser;
+use crate::commands::trash_commands::trash_record;
+use crate::settings::OrgSettings;
+use crate::pdf::layout::{Align, Column, DocumentLayout, PdfTemplate};
+use crate::templates::{TemplateCriteria, KIND_INVOICE};
+use crate::xlsx::{CellValue, XlsxTemplate};
+use crate::sync_scheduler::notify_local_change;
 use tauri::State;
 use chrono::Utc;
+use crm_schema::Date;
 
 #[tauri::command]
 pub async fn create_invoice_from_proposal(
@@ -19,15 +31,19 @@ pub async fn create_invoice_from_proposal(
         .ok_or("Proposal not found")?;
 
     // Générer le numéro d'invoice
-    let invoice_number = queries::generate_invoice_number(&pool).await.map_err(|e| e.to_string())?;
+    let org = OrgSettings::load(&pool).await?;
+    let invoice_number = queries::generate_invoice_number(&pool, &org.invoice_number_format)
+        .await
+        .map_err(|e| e.to_string())?;
 
     // Date d'émission = aujourd'hui
-    let issue_date = Utc::now().date_naive().format("%Y-%m-%d").to_string();
+    let today = Date::today();
+    let issue_date = today.to_string();
     
-    // Date d'échéance = 30 jours après l'émission
-    let due_date = Utc::now().date_naive()
-        .checked_add_days(chrono::Days::new(30))
-        .map(|d| d.format("%Y-%m-%d").to_string());
+    // Date d'échéance selon les conditions de paiement de l'organisation
+    let due_date = today.naive()
+        .checked_add_days(chrono::Days::new(org.payment_terms_days.into()))
+        .map(|d| Date::from_naive(d).to_string());
 
     // Créer l'invoice
     let invoice = queries::create_invoice(
@@ -43,6 +59,7 @@ pub async fn create_invoice_from_proposal(
         None, // commercial_in_charge
         proposal.notes, // notes
     ).await.map_err(|e| e.to_string())?;
+    notify_local_change();
 
     Ok(invoice)
 }
@@ -55,6 +72,16 @@ pub async fn get_all_invoices(
     Ok(invoices)
 }
 
+#[tauri::command]
+pub async fn list_invoices(
+    pool: State<'_, DbPool>,
+    query: Option<ListQuery>,
+) -> Result<Page<InvoiceWithDetails>, String> {
+    queries::list_invoices(&pool, &query.unwrap_or_default())
+        .await
+        .map_err(|e| format!("Failed to list invoices: {}", e))
+}
+
 #[tauri::command]
 pub async fn get_invoice_by_id(
     pool: State<'_, DbPool>,
@@ -94,14 +121,15 @@ pub async fn update_invoice(
         status,
         total_amount,
         currency,
-        issue_date,
-        due_date,
-        paid_date,
+        business_date("issue_date", issue_date)?,
+        business_date("due_date", due_date)?,
+        business_date("paid_date", paid_date)?,
         purchase_order,
-        purchase_order_date,
+        business_date("purchase_order_date", purchase_order_date)?,
         commercial_in_charge,
         notes,
     ).await.map_err(|e| e.to_string())?;
+    notify_local_change();
 
     Ok(invoice)
 }
@@ -113,7 +141,7 @@ pub async fn update_invoice_status(
     status: String,
 ) -> Result<Invoice, String> {
     let paid_date = if status == "PAID" {
-        Some(Utc::now().date_naive().format("%Y-%m-%d").to_string())
+        Some(Date::today().to_string())
     } else {
         None
     };
@@ -132,6 +160,7 @@ pub async fn update_invoice_status(
         None, // commercial_in_charge
         None, // notes
     ).await.map_err(|e| e.to_string())?;
+    notify_local_change();
 
     Ok(invoice)
 }
@@ -139,10 +168,10 @@ pub async fn update_invoice_status(
 #[tauri::command]
 pub async fn delete_invoice(
     pool: State<'_, DbPool>,
+    user: State<'_, CurrentUser>,
     id: String,
 ) -> Result<(), String> {
-    queries::delete_invoice(&pool, &id).await.map_err(|e| e.to_string())?;
-    Ok(())
+    trash_record(&pool, &user, "invoices", &id).await
 }
 
 #[tauri::command]
@@ -163,10 +192,17 @@ pub async fn get_invoices_by_company(
     Ok(invoices)
 }
 
+/// Modèle de facture livré avec l'application, remplaçable par
+/// `<app_data>/templates/invoice.xlsx`
+const INVOICE_TEMPLATE: &[u8] = include_bytes!("../../templates/invoice.xlsx");
+
 #[tauri::command]
 pub async fn generate_invoice_excel(
+    app_handle: tauri::AppHandle,
     pool: State<'_, DbPool>,
     invoice_id: String,
+    template_id: Option<String>,
+    language: Option<String>,
 ) -> Result<String, String> {
     // Récupérer l'invoice avec tous les détails
     let invoice = queries::get_invoice_by_id(&pool, &invoice_id).await
@@ -181,91 +217,428 @@ pub async fn generate_invoice_excel(
     let products = queries::get_proposal_products(&pool, &invoice.proposal_id).await
         .map_err(|e| e.to_string())?;
 
-    // Récupérer les détails de la company et du contact
+    // Récupérer les détails de la company
     let company = queries::get_company_by_id(&pool, &proposal.company_id).await
         .map_err(|e| e.to_string())?
         .ok_or("Company not found")?;
 
-    // Get the first contact for the company (or None if no contacts)
-    let contacts = queries::get_company_contacts(&pool, &proposal.company_id).await
-        .map_err(|e| e.to_string())?;
-    let contact = contacts.first().cloned();
-
-    // Préparer les données pour l'API Python
-    let invoice_data = serde_json::json!({
-        "invoice": {
-            "id": invoice.id,
-            "invoice_number": invoice.invoice_number,
-            "status": invoice.status,
-            "total_amount": invoice.total_amount,
-            "currency": invoice.currency,
-            "issue_date": invoice.issue_date,
-            "due_date": invoice.due_date,
-            "paid_date": invoice.paid_date,
-            "purchase_order": invoice.purchase_order,
-            "purchase_order_date": invoice.purchase_order_date,
-            "commercial_in_charge": invoice.commercial_in_charge,
-            "notes": invoice.notes,
-        },
-        "company": {
-            "id": company.id,
-            "name": company.name,
-            "website": company.website,
-            "address": company.address,
-            "city": company.city,
-            "postal_code": company.postal_code,
-            "country": company.country,
-            "description": company.description,
-        },
-        "contact": contact.map(|c| serde_json::json!({
-            "id": c.id,
-            "first_name": c.first_name,
-            "last_name": c.last_name,
-            "email": c.email,
-            "phone_number": c.phone_number,
-        })),
-        "products": products.into_iter().map(|p| serde_json::json!({
-            "id": p.id,
-            "product_type": p.product_type,
-            "user_count": p.user_count,
-            "standalone_count": p.standalone_count,
-            "server_key_count": p.server_key_count,
-            "unit_price": p.unit_price,
-            "total_price": p.total_price,
-            "annual_reduction": p.annual_reduction,
-            "training": p.training,
-            "training_days": p.training_days,
-            "training_cost_per_day": p.training_cost_per_day,
-            "training_cost": p.training_cost,
-            "licence": p.licence,
-            "support": p.support,
-            "support_years": p.support_years,
-        })).collect::<Vec<_>>(),
-    });
-
-    // Appeler l'API Python pour générer l'Excel
-    let client = reqwest::Client::new();
-    let response = client
-        .post("http://127.0.0.1:8001/generate-invoice-excel")
-        .json(&invoice_data)
-        .send()
-        .await
-        .map_err(|e| format!("Failed to call invoice API. Is the sidecar running? Error: {}", e))?;
+    // Modèle de la bibliothèque, sinon modèle utilisateur s'il existe,
+    // sinon le modèle intégré
+    let criteria = TemplateCriteria {
+        company_id: Some(&company.id),
+        product_type: products.first().map(|p| p.product_type.as_str()),
+        language: language.as_deref(),
+    };
+    let library_template = resolve_template(&app_handle, &pool, KIND_INVOICE, template_id, criteria).await?;
+    let custom_template = app_handle.path_resolver()
+        .app_data_dir()
+        .map(|dir| dir.join("templates").join("invoice.xlsx"))
+        .filter(|path| path.exists());
+    let template_bytes = match (library_template, &custom_template) {
+        (Some(bytes), _) => bytes,
+        (None, Some(path)) => std::fs::read(path)
+            .map_err(|e| format!("Failed to read invoice template: {}", e))?,
+        (None, None) => INVOICE_TEMPLATE.to_vec(),
+    };
 
-    if !response.status().is_success() {
-        return Err(format!("Invoice API error: {}", response.status()));
-    }
+    let mut workbook = XlsxTemplate::from_bytes(&template_bytes)?;
+    fill_invoice_workbook(&mut workbook, &invoice, &company, &products)?;
+    let bytes = workbook.to_bytes()?;
 
     // Sauvegarder le fichier Excel
-    let filename = format!("Invoice_{}.xlsx", invoice.invoice_number);
-    let home_dir = dirs::home_dir().ok_or("Could not find home directory")?;
-    let output_path = home_dir.join("Downloads").join(&filename);
+    let file = GeneratedFile {
+        kind: KIND_INVOICE,
+        source_id: &invoice.id,
+        company: &company,
+        number: &invoice.invoice_number,
+        modified_at: modified_at(
+            products.iter().map(|p| p.updated_at.as_str())
+                .chain([invoice.updated_at.as_str(), proposal.updated_at.as_str()]),
+        ),
+        suffix: "",
+        extension: "xlsx",
+    };
+    let output_path = save_generated_file(&app_handle, &pool, file, &bytes).await?;
 
-    let bytes = response.bytes().await
-        .map_err(|e| format!("Failed to read response: {}", e))?;
+    println!("📊 Invoice workbook generated: {}", output_path.display());
+    Ok(output_path.to_string_lossy().to_string())
+}
 
-    std::fs::write(&output_path, bytes)
-        .map_err(|e| format!("Failed to save file: {}", e))?;
+#[tauri::command]
+pub async fn generate_invoice_pdf(
+    app_handle: tauri::AppHandle,
+    pool: State<'_, DbPool>,
+    invoice_id: String,
+) -> Result<String, String> {
+    let invoice = queries::get_invoice_by_id(&pool, &invoice_id).await
+        .map_err(|e| e.to_string())?
+        .ok_or("Invoice not found")?;
+
+    let proposal = queries::get_proposal_by_id(&pool, &invoice.proposal_id).await
+        .map_err(|e| e.to_string())?
+        .ok_or("Proposal not found")?;
+
+    let products = queries::get_proposal_products(&pool, &invoice.proposal_id).await
+        .map_err(|e| e.to_string())?;
 
+    let company = queries::get_company_by_id(&pool, &proposal.company_id).await
+        .map_err(|e| e.to_string())?
+        .ok_or("Company not found")?;
+
+    let template = PdfTemplate::load(app_handle.path_resolver().app_data_dir())?;
+    let bytes = render_invoice_pdf(&template, &invoice, &company, &products)?;
+
+    let file = GeneratedFile {
+        kind: KIND_INVOICE,
+        source_id: &invoice.id,
+        company: &company,
+        number: &invoice.invoice_number,
+        modified_at: modified_at(
+            products.iter().map(|p| p.updated_at.as_str())
+                .chain([invoice.updated_at.as_str(), proposal.updated_at.as_str()]),
+        ),
+        suffix: "",
+        extension: "pdf",
+    };
+    let output_path = save_generated_file(&app_handle, &pool, file, &bytes).await?;
+
+    println!("📑 Invoice PDF generated: {}", output_path.display());
     Ok(output_path.to_string_lossy().to_string())
 }
+
+/// Facture PDF/A avec les mêmes lignes que le classeur Excel.
+pub fn render_invoice_pdf(
+    template: &PdfTemplate,
+    invoice: &InvoiceWithDetails,
+    company: &Company,
+    products: &[ProposalProduct],
+) -> Result<Vec<u8>, String> {
+    let writer = template.writer(format!("Invoice {}", invoice.invoice_number))?;
+    let lines = workbook_lines(invoice, company, products);
+    let total: f64 = lines.iter().map(|l| l.amount).sum();
+    let not_specified = |value: &Option<String>| {
+        value.clone().filter(|v| !v.is_empty()).unwrap_or("Not specified".to_string())
+    };
+
+    let mut layout = DocumentLayout::new(template, writer);
+    layout.title(&format!("Invoice {}", invoice.invoice_number));
+    layout.key_values(&[
+        ("Invoice date", invoice.issue_date.clone()),
+        ("Due date", not_specified(&invoice.due_date)),
+        ("Purchase order", not_specified(&invoice.purchase_order)),
+        ("Purchase order date", not_specified(&invoice.purchase_order_date)),
+        ("Commercial in charge", not_specified(&invoice.commercial_in_charge)),
+        ("Proposal", invoice.proposal_number.clone()),
+    ]);
+
+    layout.heading("Bill to");
+    let address = [
+        company.address.clone(),
+        Some(format!(
+            "{} {}",
+            company.postal_code.clone().unwrap_or_default(),
+            company.city.clone().unwrap_or_default()
+        )),
+        company.country.clone(),
+    ];
+    layout.paragraph(&company.name, 10.0);
+    for line in address.into_iter().flatten().filter(|l| !l.trim().is_empty()) {
+        layout.paragraph(line.trim(), 10.0);
+    }
+
+    layout.heading("Details");
+    let columns = [
+        Column { title: "#", width: 30.0, align: Align::Left },
+        Column { title: "Description", width: 215.0, align: Align::Left },
+        Column { title: "Quantity", width: 70.0, align: Align::Right },
+        Column { title: "Unit price", width: 90.0, align: Align::Right },
+        Column { title: "Amount", width: 90.0, align: Align::Right },
+    ];
+    let rows: Vec<Vec<String>> = lines
+        .iter()
+        .enumerate()
+        .map(|(index, line)| vec![
+            (index + 1).to_string(),
+            line.description.clone(),
+            line.quantity.to_string(),
+            format_amount(unit_price(line)),
+            format_amount(line.amount),
+        ])
+        .collect();
+    layout.table(&columns, &rows);
+    layout.totals(&[(
+        &format!("Total ({})", invoice.currency),
+        format_amount(total),
+        true,
+    )]);
+
+    if let Some(notes) = invoice.notes.as_ref().filter(|n| !n.is_empty()) {
+        layout.heading("Notes");
+        layout.paragraph(notes, 10.0);
+    }
+
+    layout.finish().render()
+}
+
+/// Noms définis reconnus dans un modèle de facture Excel.
+pub const INVOICE_NAMES: &[&str] = &[
+    "InvoiceNumber", "InvoiceDate", "PurchaseOrder", "PurchaseOrderDate",
+    "CommercialInCharge", "CompanyName", "Currency", "InvoiceTotal",
+    "LineNumber", "LineDescription", "LineQuantity", "LineUnitPrice", "LineAmount",
+];
+
+/// Ligne de facture telle qu'affichée dans le classeur
+struct WorkbookLine {
+    description: String,
+    quantity: f64,
+    amount: f64,
+}
+
+/// Remplit le classeur à partir des noms définis du modèle (InvoiceNumber,
+/// InvoiceDate, PurchaseOrder, PurchaseOrderDate, CommercialInCharge,
+/// CompanyName, Currency, InvoiceTotal et, pour les lignes, LineNumber,
+/// LineDescription, LineQuantity, LineUnitPrice, LineAmount). Un modèle sans
+/// noms définis est traité avec la disposition historique de Test.xlsx.
+fn fill_invoice_workbook(
+    workbook: &mut XlsxTemplate,
+    invoice: &InvoiceWithDetails,
+    company: &Company,
+    products: &[ProposalProduct],
+) -> Result<(), String> {
+    let lines = workbook_lines(invoice, company, products);
+    let total: f64 = lines.iter().map(|l| l.amount).sum();
+    let issue_date = Date::parse(&invoice.issue_date).map(Date::naive);
+    let purchase_order_date = invoice.purchase_order_date.as_deref().and_then(Date::parse).map(Date::naive);
+    let purchase_order = invoice.purchase_order.clone().filter(|po| !po.is_empty());
+    let commercial = invoice.commercial_in_charge.clone().filter(|c| !c.is_empty());
+
+    if workbook.defined_name("LineDescription").is_some() || workbook.defined_name("InvoiceNumber").is_some() {
+        let headers = [
+            ("InvoiceNumber", Some(CellValue::Text(invoice.invoice_number.clone()))),
+            ("InvoiceDate", issue_date.map(CellValue::Date)),
+            ("PurchaseOrder", purchase_order.map(CellValue::Text)),
+            ("PurchaseOrderDate", purchase_order_date.map(CellValue::Date)),
+            ("CommercialInCharge", commercial.map(CellValue::Text)),
+            ("CompanyName", Some(CellValue::Text(company.name.clone()))),
+            ("Currency", Some(CellValue::Text(invoice.currency.clone()))),
+            ("InvoiceTotal", Some(CellValue::Number(total))),
+        ];
+        for (name, value) in headers {
+            if let (Some(range), Some(value)) = (workbook.defined_name(name), value) {
+                workbook.set(&range, 0, value)?;
+            }
+        }
+
+        let capacity = workbook.defined_name("LineDescription")
+            .filter(|r| r.rows() > 1)
+            .map(|r| r.rows() as usize);
+        if let Some(capacity) = capacity {
+            if lines.len() > capacity {
+                return Err(format!(
+                    "Invoice has {} lines but the template only has room for {}",
+                    lines.len(), capacity
+                ));
+            }
+        }
+
+        for (index, line) in lines.iter().enumerate() {
+            let offset = index as u32;
+            let columns = [
+                ("LineNumber", CellValue::Number((index + 1) as f64)),
+                ("LineDescription", CellValue::Text(line.description.clone())),
+                ("LineQuantity", CellValue::Number(line.quantity)),
+                ("LineUnitPrice", CellValue::Number(unit_price(line))),
+                ("LineAmount", CellValue::Number(line.amount)),
+            ];
+            for (name, value) in columns {
+                if let Some(range) = workbook.defined_name(name) {
+                    workbook.set(&range, offset, value)?;
+                }
+            }
+        }
+        return Ok(());
+    }
+
+    // Disposition historique : cellules fixes et lignes produit à partir
+    // de la ligne 41, une ligne vide entre deux items. Comme le script
+    // Python qu'elle remplace, elle date la facture du jour (F3) et affiche
+    // la date d'émission comme date de commande (C8) ; les modèles existants
+    // en dépendent, les noms définis ne reprennent pas cette inversion
+    let today = Utc::now().date_naive();
+    let cells = [
+        ("C13", CellValue::Text(company.name.clone())),
+        ("C6", CellValue::Text(format!(
+            "Purchase Order: {}",
+            purchase_order.unwrap_or("Not specified".to_string())
+        ))),
+        ("E3", CellValue::Text(invoice.invoice_number.clone())),
+        ("F3", CellValue::Date(today)),
+        ("C8", CellValue::Text(format!(
+            "Purchase order date: {}",
+            issue_date.map(|d| d.format("%d %B %Y").to_string()).unwrap_or("Not specified".to_string())
+        ))),
+        ("C10", CellValue::Text(format!(
+            "Commercial in charge: {}",
+            commercial.unwrap_or("Not specified".to_string())
+        ))),
+    ];
+    for (reference, value) in cells {
+        let range = workbook.cell(reference)?;
+        workbook.set(&range, 0, value)?;
+    }
+
+    for (index, line) in lines.iter().enumerate() {
+        let row = 41 + index as u32 * 2;
+        let values = [
+            ("A", CellValue::Number((index + 1) as f64)),
+            ("B", CellValue::Text(line.description.clone())),
+            ("D", CellValue::Number(line.quantity)),
+            ("E", CellValue::Number(unit_price(line))),
+            ("F", CellValue::Number(line.amount)),
+        ];
+        for (column, value) in values {
+            let range = workbook.cell(&format!("{}{}", column, row))?;
+            workbook.set(&range, 0, value)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Une ligne par service (licence, formation, support) et par produit ;
+/// une ligne unique au montant de la facture si elle n'a pas de produit.
+fn workbook_lines(
+    invoice: &InvoiceWithDetails,
+    company: &Company,
+    products: &[ProposalProduct],
+) -> Vec<WorkbookLine> {
+    if products.is_empty() {
+        if invoice.total_amount > 0.0 {
+            return vec![WorkbookLine {
+                description: format!("Service pour {}", company.name),
+                quantity: 1.0,
+                amount: invoice.total_amount,
+            }];
+        }
+        return Vec::new();
+    }
+
+    let mut lines = Vec::new();
+    for product in products {
+        let reduced_price = product.unit_price * (1.0 - product.annual_reduction / 100.0);
+
+        // Forfait pour l'ensemble des utilisateurs, comme dans la facture
+        // électronique : quantité 1, le nombre d'utilisateurs est dans le libellé
+        if product.licence == 1 {
+            lines.push(WorkbookLine {
+                description: format!("{} Licence ({} users)", product.product_type, product.user_count),
+                quantity: 1.0,
+                amount: reduced_price,
+            });
+        }
+        if product.training == 1 && product.training_days > 0 {
+            lines.push(WorkbookLine {
+                description: format!("{} Training", product.product_type),
+                quantity: product.training_days as f64,
+                amount: product.training_days as f64 * product.training_cost_per_day,
+            });
+        }
+        if product.support == 1 {
+            lines.push(WorkbookLine {
+                description: format!("{} Support", product.product_type),
+                quantity: product.support_years as f64,
+                amount: reduced_price * 0.20 * product.support_years as f64,
+            });
+        }
+    }
+    lines
+}
+
+fn unit_price(line: &WorkbookLine) -> f64 {
+    if line.quantity > 0.0 {
+        line.amount / line.quantity
+    } else {
+        0.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::models::test_fixtures;
+    use std::io::Read;
+
+    fn invoice() -> InvoiceWithDetails {
+        InvoiceWithDetails {
+            proposal_number: "D_Acme".to_string(),
+            purchase_order: Some("PO-42".to_string()),
+            commercial_in_charge: Some("Alice".to_string()),
+            ..test_fixtures::invoice("25070001")
+        }
+    }
+
+    fn company() -> Company {
+        test_fixtures::company("c-1", "Acme")
+    }
+
+    fn product() -> ProposalProduct {
+        ProposalProduct {
+            annual_reduction: 10.0,
+            training: 1,
+            training_days: 2,
+            training_cost_per_day: 1000.0,
+            training_cost: 2000.0,
+            support: 1,
+            support_years: 3,
+            ..test_fixtures::product("HTZ Warfare")
+        }
+    }
+
+    #[test]
+    fn licence_is_a_single_flat_rate_line() {
+        let lines = workbook_lines(&invoice(), &company(), &[product()]);
+        let summary: Vec<(&str, f64, f64)> = lines
+            .iter()
+            .map(|l| (l.description.as_str(), l.quantity, unit_price(l)))
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                ("HTZ Warfare Licence (5 users)", 1.0, 9000.0),
+                ("HTZ Warfare Training", 2.0, 1000.0),
+                ("HTZ Warfare Support", 3.0, 1800.0),
+            ]
+        );
+    }
+
+    #[test]
+    fn training_without_days_has_no_line() {
+        let mut product = product();
+        product.training_days = 0;
+        let lines = workbook_lines(&invoice(), &company(), &[product]);
+        assert!(lines.iter().all(|l| !l.description.ends_with("Training")));
+        assert_eq!(lines.len(), 2);
+    }
+
+    #[test]
+    fn builtin_template_is_filled_and_recalculated() {
+        let mut workbook = XlsxTemplate::from_bytes(INVOICE_TEMPLATE).unwrap();
+        fill_invoice_workbook(&mut workbook, &invoice(), &company(), &[product()]).unwrap();
+        let bytes = workbook.to_bytes().unwrap();
+
+        let mut archive = zip::ZipArchive::new(std::io::Cursor::new(bytes)).unwrap();
+        let mut content = String::new();
+        archive.by_name("xl/workbook.xml").unwrap().read_to_string(&mut content).unwrap();
+        assert!(content.contains("fullCalcOnLoad=\"1\""));
+
+        let mut sheets = String::new();
+        for i in 0..archive.len() {
+            let mut file = archive.by_index(i).unwrap();
+            if file.name().starts_with("xl/worksheets/") {
+                file.read_to_string(&mut sheets).unwrap();
+            }
+        }
+        assert!(sheets.contains("25070001"));
+        assert!(sheets.contains("HTZ Warfare Licence (5 users)"));
+    }
+}
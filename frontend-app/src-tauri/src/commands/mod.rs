@@ -6,4 +6,24 @@ pub mod proposal_commands;
 pub mod invoice_commands;
 pub mod document_generator;
 pub mod sync_commands;
+pub mod einvoice_commands;
 
+pub mod template_commands;
+pub mod sidecar_commands;
+pub mod attachment_commands;
+pub mod search_commands;
+pub mod import_commands;
+pub mod activity_commands;
+pub mod trash_commands;
+pub mod settings_commands;
+
+/// Date métier reçue d'un formulaire, enregistrée sous sa forme canonique
+/// "YYYY-MM-DD" ; une chaîne vide signifie « pas de date »
+pub(crate) fn business_date(field: &str, value: Option<String>) -> Result<Option<String>, String> {
+    match value.as_deref().map(str::trim) {
+        None | Some("") => Ok(None),
+        Some(raw) => crm_schema::Date::parse_legacy(raw)
+            .map(|date| Some(date.to_string()))
+            .ok_or_else(|| format!("Invalid {}: {}", field, raw)),
+    }
+}
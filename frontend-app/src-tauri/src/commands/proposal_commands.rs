@@ -1,4 +1,9 @@
+use crate::commands::business_date;
+use crate::commands::auth_commands::CurrentUser;
+use crate::commands::trash_commands::trash_record;
 use crate::db::{models::*, queries, DbPool};
+use crate::settings::OrgSettings;
+use crate::sync_scheduler::notify_local_change;
 use serde::{Deserialize, Serialize};
 use tauri::State;
 
@@ -41,48 +46,22 @@ pub struct UpdateProposalRequest {
 
 #[tauri::command]
 pub async fn get_proposals(pool: State<'_, DbPool>) -> Result<Vec<ProposalWithProducts>, String> {
-    let proposals = queries::get_all_proposals(&pool)
+    let proposals = queries::get_all_proposals_with_products(&pool)
         .await
         .map_err(|e| format!("Failed to get proposals: {}", e))?;
 
-    println!("🔄 [get_proposals] Found {} proposals in database", proposals.len());
-
-    let mut proposals_with_products = Vec::new();
-    for proposal in proposals {
-        println!("🔄 [get_proposals] Processing proposal: {} (ID: {})", 
-                 proposal.proposal_number.as_ref().unwrap_or(&"Unknown".to_string()), 
-                 proposal.id);
-        
-        let products = queries::get_proposal_products(&pool, &proposal.id)
-            .await
-            .map_err(|e| format!("Failed to get products: {}", e))?;
-        
-        let company = queries::get_company_by_id(&pool, &proposal.company_id)
-            .await
-            .map_err(|e| format!("Failed to get company: {}", e))?;
-        
-        let company_name = company.map(|c| c.name).unwrap_or_else(|| "Unknown".to_string());
-        
-        println!("🔄 [get_proposals] Proposal {} has {} products, company: {}", 
-                 proposal.proposal_number.as_ref().unwrap_or(&"Unknown".to_string()), 
-                 products.len(), 
-                 company_name);
-        
-        proposals_with_products.push(ProposalWithProducts {
-            proposal,
-            products,
-            company_name,
-        });
-    }
+    println!("✅ [get_proposals] Returning {} proposals with products", proposals.len());
+    Ok(proposals)
+}
 
-    println!("✅ [get_proposals] Returning {} proposals with products", proposals_with_products.len());
-    for (i, pwp) in proposals_with_products.iter().enumerate() {
-        println!("  [{}] Proposal: {} (Company: {})", 
-                 i, 
-                 pwp.proposal.proposal_number.as_ref().unwrap_or(&"Unknown".to_string()),
-                 pwp.company_name);
-    }
-    Ok(proposals_with_products)
+#[tauri::command]
+pub async fn list_proposals(
+    pool: State<'_, DbPool>,
+    query: Option<ListQuery>,
+) -> Result<Page<ProposalWithProducts>, String> {
+    queries::list_proposals(&pool, &query.unwrap_or_default())
+        .await
+        .map_err(|e| format!("Failed to list proposals: {}", e))
 }
 
 #[tauri::command]
@@ -90,13 +69,16 @@ pub async fn create_proposal(
     pool: State<'_, DbPool>,
     request: CreateProposalRequest,
 ) -> Result<Proposal, String> {
+    let valid_until = business_date("valid_until", request.valid_until)?;
+    let org = OrgSettings::load(&pool).await?;
+
     // Create proposal
     let proposal = queries::create_proposal(
         &pool,
         request.company_id.clone(),
         request.status,
         request.currency,
-        request.valid_until,
+        valid_until,
         request.notes,
     )
     .await
@@ -182,6 +164,7 @@ pub async fn create_proposal(
 
     let proposal_number = queries::generate_proposal_number(
         &pool,
+        &org.proposal_number_format,
         &request.company_id,
         &first_product_type,
         has_training,
@@ -203,26 +186,7 @@ pub async fn create_proposal(
         .await
         .map_err(|e| format!("Failed to get updated proposal: {}", e))?
         .ok_or("Proposal not found after creation")?;
-    
-    // Get all products for this proposal to sync with it
-    let _products = queries::get_proposal_products(&pool, &proposal.id)
-        .await
-        .map_err(|e| format!("Failed to get products: {}", e))?;
-    
-    // Add to sync queue - convert valid_until to full DateTime format
-    let mut proposal_json = serde_json::to_value(&updated_proposal)
-        .map_err(|e| format!("Failed to serialize proposal: {}", e))?;
-    
-    // Ensure valid_until is in full DateTime format if present
-    if let Some(valid_until) = proposal_json.get_mut("valid_until") {
-        if let Some(date_str) = valid_until.as_str() {
-            if !date_str.contains('T') {
-                // Convert date to DateTime format
-                *valid_until = serde_json::Value::String(format!("{}T00:00:00+00:00", date_str));
-            }
-        }
-    }
-    
+    notify_local_change();
 
     Ok(updated_proposal)
 }
@@ -233,13 +197,12 @@ pub async fn update_proposal(
     request: UpdateProposalRequest,
 ) -> Result<Proposal, String> {
     println!("🔄 [update_proposal] Updating proposal: {}", request.id);
-    
-    // 1. Supprimer les anciens produits en local
-    queries::delete_proposal_products(&pool, &request.id)
-        .await
-        .map_err(|e| format!("Failed to delete old products: {}", e))?;
-    
-    // 2. Créer les nouveaux produits en local
+    let valid_until = business_date("valid_until", request.valid_until.clone())?;
+    let org = OrgSettings::load(&pool).await?;
+
+    // 1. Calculer les produits (prix recalculés côté backend)
+    let now = chrono::Utc::now().timestamp_millis().to_string();
+    let mut products = Vec::with_capacity(request.products.len());
     let mut total_amount = 0.0;
     let mut has_training = false;
     let mut has_support = false;
@@ -282,67 +245,86 @@ pub async fn update_proposal(
             first_product_type = product_data.product_type.clone();
         }
         
-        // Créer le nouveau produit
-        queries::create_proposal_product(
-            &pool,
-            request.id.clone(),
-            product_data.product_type,
-            user_count as i64,
-            product_data.standalone_count,
-            product_data.server_key_count,
-            base_price,
-            product_total,
-            product_data.annual_reduction,
-            product_data.training,
-            product_data.training_days,
-            product_data.training_cost_per_day,
+        products.push(ProposalProduct {
+            id: product_data.id.unwrap_or_default(),
+            proposal_id: request.id.clone(),
+            product_type: product_data.product_type,
+            user_count,
+            standalone_count: product_data.standalone_count,
+            server_key_count: product_data.server_key_count,
+            unit_price: base_price,
+            total_price: product_total,
+            annual_reduction: product_data.annual_reduction,
+            training: i64::from(product_data.training),
+            training_days: product_data.training_days,
+            training_cost_per_day: product_data.training_cost_per_day,
             training_cost,
-            product_data.licence,
-            product_data.support,
-            product_data.support_years,
-        )
-        .await
-        .map_err(|e| format!("Failed to create product: {}", e))?;
+            licence: i64::from(product_data.licence),
+            support: i64::from(product_data.support),
+            support_years: product_data.support_years,
+            created_at: now.clone(),
+            updated_at: now.clone(),
+            version: None,
+            is_deleted: None,
+        });
         
         total_amount += product_total;
     }
 
-    // 3. Mettre à jour la proposition
-    let now = chrono::Utc::now().timestamp_millis().to_string();
+    // 2. Générer le numéro de proposition
+    let proposal_number = queries::generate_proposal_number(
+        &pool,
+        &org.proposal_number_format,
+        &request.company_id,
+        &first_product_type,
+        has_training,
+        has_support,
+        has_licence,
+    )
+    .await
+    .map_err(|e| format!("Failed to generate number: {}", e))?;
+
+    // 3. Mettre à jour la proposition et ses produits en une transaction :
+    // les produits sont comparés par identifiant (mis à jour, créés ou
+    // marqués supprimés) pour garder les mêmes identifiants sur tous les postes
+    let mut tx = pool
+        .begin()
+        .await
+        .map_err(|e| format!("Failed to update proposal: {}", e))?;
+
     sqlx::query(
         "UPDATE proposals SET company_id = ?, status = ?, currency = ?, valid_until = ?, notes = ?, 
-         total_amount = ?, updated_at = ? WHERE id = ?"
+         total_amount = ?, proposal_number = ?, updated_at = ?, version = COALESCE(version, 0) + 1 WHERE id = ?"
     )
     .bind(&request.company_id)
     .bind(&request.status)
     .bind(&request.currency)
-    .bind(&request.valid_until)
+    .bind(&valid_until)
     .bind(&request.notes)
     .bind(total_amount)
+    .bind(&proposal_number)
     .bind(&now)
     .bind(&request.id)
-    .execute(pool.inner())
+    .execute(&mut *tx)
     .await
     .map_err(|e| format!("Failed to update proposal: {}", e))?;
 
-    // 4. Générer le numéro de proposition si nécessaire
-    let proposal_number = queries::generate_proposal_number(
-        &pool,
-        &request.company_id,
-        &first_product_type,
-        has_training,
-        has_support,
-        has_licence,
-    )
-    .await
-    .map_err(|e| format!("Failed to generate number: {}", e))?;
+    // 4. Enregistrer les produits et supprimer ceux retirés
+    let mut kept_ids = Vec::with_capacity(products.len());
+    for product in &products {
+        let saved = queries::save_proposal_product(&mut tx, product, &now)
+            .await
+            .map_err(|e| format!("Failed to save product: {}", e))?;
+        kept_ids.push(saved.id);
+    }
+    queries::delete_proposal_products_except(&mut tx, &request.id, &kept_ids, &now)
+        .await
+        .map_err(|e| format!("Failed to delete removed products: {}", e))?;
 
-    sqlx::query("UPDATE proposals SET proposal_number = ? WHERE id = ?")
-        .bind(&proposal_number)
-        .bind(&request.id)
-        .execute(pool.inner())
+    tx.commit()
         .await
-        .map_err(|e| format!("Failed to update proposal number: {}", e))?;
+        .map_err(|e| format!("Failed to update proposal: {}", e))?;
+    notify_local_change();
 
     // 5. Récupérer la proposition mise à jour
     let proposal = queries::get_proposal_by_id(&pool, &request.id)
@@ -384,14 +366,13 @@ pub async fn get_proposal(pool: State<'_, DbPool>, id: String) -> Result<Option<
 }
 
 #[tauri::command]
-pub async fn delete_proposal(pool: State<'_, DbPool>, id: String) -> Result<(), String> {
+pub async fn delete_proposal(pool: State<'_, DbPool>, user: State<'_, CurrentUser>, id: String) -> Result<(), String> {
     println!("🔄 [delete_proposal] Deleting proposal: {}", id);
     
-    queries::delete_proposal(&pool, &id)
-        .await
-        .map_err(|e| format!("Failed to delete proposal: {}", e))?;
+    // À la corbeille avec ses produits
+    trash_record(&pool, &user, "proposals", &id).await?;
     
-    println!("✅ [delete_proposal] Proposal {} added to deletion queue", id);
+    println!("✅ [delete_proposal] Proposal {} moved to trash", id);
     Ok(())
 }
 
@@ -433,6 +414,7 @@ pub async fn delete_proposal_product(pool: State<'_, DbPool>, product_id: String
     queries::delete_proposal_product(&pool, &product_id)
         .await
         .map_err(|e| format!("Failed to delete product: {}", e))?;
+    notify_local_change();
     
     println!("✅ [delete_proposal_product] Product {} deleted", product_id);
     Ok(())
@@ -0,0 +1,25 @@
+use crate::db::{models::SearchHit, queries, DbPool};
+use tauri::State;
+
+/// Types de résultats renvoyés par `global_search`
+pub const SEARCH_ENTITY_TYPES: &[&str] = &["company", "contact", "proposal", "invoice", "document"];
+
+const DEFAULT_SEARCH_LIMIT: i64 = 50;
+
+#[tauri::command]
+pub async fn global_search(
+    pool: State<'_, DbPool>,
+    query: String,
+    entity_types: Option<Vec<String>>,
+    limit: Option<i64>,
+) -> Result<Vec<SearchHit>, String> {
+    let entity_types = entity_types.unwrap_or_default();
+    if let Some(unknown) = entity_types.iter().find(|t| !SEARCH_ENTITY_TYPES.contains(&t.as_str())) {
+        return Err(format!("Unknown entity type: {}", unknown));
+    }
+    let limit = limit.unwrap_or(DEFAULT_SEARCH_LIMIT).clamp(1, 500);
+
+    queries::global_search(&pool, &query, &entity_types, limit)
+        .await
+        .map_err(|e| format!("Failed to search: {}", e))
+}
@@ -0,0 +1,171 @@
+use crate::db::{queries, DbPool};
+use crate::settings::{LocalSettings, OrgSettings, ServerProfile, ServerProfiles, Settings, SyncPreferences, DEFAULT_PROFILE};
+use crate::sync_scheduler::{notify_local_change, SyncScheduler, SyncSchedulerConfig};
+use std::path::PathBuf;
+use tauri::State;
+
+pub(crate) fn app_data_dir(app_handle: &tauri::AppHandle) -> Result<PathBuf, String> {
+    app_handle
+        .path_resolver()
+        .app_data_dir()
+        .ok_or_else(|| "Could not find app data directory".to_string())
+}
+
+/// Configuration du planificateur pour le profil actif
+pub(crate) fn scheduler_config(profile: &ServerProfile, sync: &SyncPreferences) -> SyncSchedulerConfig {
+    SyncSchedulerConfig {
+        enabled: sync.enabled,
+        server_url: profile.server_url.clone(),
+        interval_seconds: sync.interval_seconds,
+    }
+}
+
+#[tauri::command]
+pub async fn get_settings(app_handle: tauri::AppHandle, pool: State<'_, DbPool>) -> Result<Settings, String> {
+    let app_dir = app_data_dir(&app_handle)?;
+    Ok(Settings {
+        local: LocalSettings::load(&pool, Some(&app_dir)).await?,
+        org: OrgSettings::load(&pool).await?,
+    })
+}
+
+#[tauri::command]
+pub async fn update_settings(
+    app_handle: tauri::AppHandle,
+    pool: State<'_, DbPool>,
+    scheduler: State<'_, SyncScheduler>,
+    settings: Settings,
+) -> Result<Settings, String> {
+    // Tout est vérifié avant d'enregistrer quoi que ce soit
+    settings.local.output.validate()?;
+    settings.org.validate()?;
+
+    settings.local.save(&pool).await?;
+    let changed = settings.org.save(&pool).await?;
+    println!("⚙️ Settings saved ({} organization settings changed)", changed);
+    if changed > 0 {
+        notify_local_change();
+    }
+
+    let profiles = ServerProfiles::load(&app_data_dir(&app_handle)?)?;
+    scheduler.configure(scheduler_config(profiles.active(), &settings.local.sync));
+    get_settings(app_handle, pool).await
+}
+
+#[tauri::command]
+pub async fn get_server_profiles(app_handle: tauri::AppHandle) -> Result<ServerProfiles, String> {
+    ServerProfiles::load(&app_data_dir(&app_handle)?)
+}
+
+// Une base déjà synchronisée avec un serveur ne doit pas être envoyée à un
+// autre, ses enregistrements s'y mêleraient : l'adresse d'un profil ne
+// change que s'il est actif et n'a jamais été synchronisé. Sinon, créer un
+// nouveau profil, qui aura sa propre base.
+async fn check_server_change(
+    pool: &DbPool,
+    profiles: &ServerProfiles,
+    previous: &ServerProfile,
+    profile: &ServerProfile,
+) -> Result<(), String> {
+    if previous.server_url.is_none() || previous.server_url == profile.server_url {
+        return Ok(());
+    }
+    if previous.name != profiles.active {
+        return Err(format!(
+            "Switch to profile \"{}\" before changing its server",
+            previous.name
+        ));
+    }
+    let metadata = queries::get_sync_metadata(pool)
+        .await
+        .map_err(|e| format!("Failed to get sync metadata: {}", e))?;
+    if metadata.last_sync_timestamp > 0 {
+        return Err(format!(
+            "Profile \"{}\" has already been synchronized with {}; create a new profile for another server",
+            previous.name,
+            previous.server_url.as_deref().unwrap_or_default()
+        ));
+    }
+    Ok(())
+}
+
+/// Crée un profil ou modifie celui du même nom ; le profil actif est
+/// appliqué aussitôt au planificateur
+#[tauri::command]
+pub async fn save_server_profile(
+    app_handle: tauri::AppHandle,
+    pool: State<'_, DbPool>,
+    scheduler: State<'_, SyncScheduler>,
+    profile: ServerProfile,
+) -> Result<ServerProfiles, String> {
+    let app_dir = app_data_dir(&app_handle)?;
+    let profile = profile.normalized()?;
+    let mut profiles = ServerProfiles::load(&app_dir)?;
+    if let Some(previous) = profiles.get(&profile.name) {
+        check_server_change(&pool, &profiles, previous, &profile).await?;
+    }
+
+    profiles.upsert(profile.clone());
+    profiles.save(&app_dir)?;
+    println!("🌐 Server profile {} saved ({:?})", profile.name, profile.server_url);
+
+    if profile.name == profiles.active {
+        let local = LocalSettings::load(&pool, Some(&app_dir)).await?;
+        scheduler.configure(scheduler_config(&profile, &local.sync));
+    }
+    Ok(profiles)
+}
+
+/// Retire un profil de la liste ; sa base reste sur le disque
+#[tauri::command]
+pub async fn delete_server_profile(app_handle: tauri::AppHandle, name: String) -> Result<ServerProfiles, String> {
+    let app_dir = app_data_dir(&app_handle)?;
+    let mut profiles = ServerProfiles::load(&app_dir)?;
+    if name == profiles.active || name == DEFAULT_PROFILE {
+        return Err(format!("Profile \"{}\" cannot be deleted", name));
+    }
+    let before = profiles.profiles.len();
+    profiles.profiles.retain(|p| p.name != name);
+    if profiles.profiles.len() == before {
+        return Err(format!("Unknown server profile: {}", name));
+    }
+    profiles.save(&app_dir)?;
+    println!("🌐 Server profile {} deleted", name);
+    Ok(profiles)
+}
+
+/// Active un autre profil puis redémarre l'application sur sa base. La
+/// synchronisation en cours se termine d'abord et aucune autre ne démarre ;
+/// les modifications non envoyées restent dans la base du profil quitté et
+/// partiront à son prochain usage.
+#[tauri::command]
+pub async fn switch_server_profile(
+    app_handle: tauri::AppHandle,
+    pool: State<'_, DbPool>,
+    scheduler: State<'_, SyncScheduler>,
+    name: String,
+) -> Result<(), String> {
+    let app_dir = app_data_dir(&app_handle)?;
+    let mut profiles = ServerProfiles::load(&app_dir)?;
+    if profiles.get(&name).is_none() {
+        return Err(format!("Unknown server profile: {}", name));
+    }
+    if profiles.active == name {
+        return Ok(());
+    }
+
+    let previous = profiles.active().clone();
+    let sync_stopped = scheduler.suspend().await;
+    profiles.active = name.clone();
+    if let Err(e) = profiles.save(&app_dir) {
+        drop(sync_stopped);
+        let local = LocalSettings::load(&pool, Some(&app_dir)).await?;
+        scheduler.configure(scheduler_config(&previous, &local.sync));
+        return Err(e);
+    }
+    pool.close().await;
+
+    println!("🔀 Switching to server profile {}, restarting...", name);
+    app_handle.restart();
+    Ok(())
+}
@@ -0,0 +1,7 @@
+use crate::sidecar::{SidecarManager, SidecarStatus};
+use tauri::State;
+
+#[tauri::command]
+pub async fn get_sidecar_status(sidecar: State<'_, SidecarManager>) -> Result<SidecarStatus, String> {
+    Ok(sidecar.status())
+}
@@ -1,11 +1,16 @@
+use crate::commands::attachment_commands::upload_pending_attachments;
+use crate::commands::settings_commands::{app_data_dir, save_server_profile};
+use crate::commands::trash_commands::purge_trash;
 use crate::db::{models::*, queries, DbPool};
+use crate::settings::{ServerProfile, ServerProfiles};
+use crate::sync_scheduler::{emit_progress, notify_local_change, SchedulerStatus, SyncScheduler};
+use crm_schema::{SyncEnvelope, SyncItemError, PROTOCOL_VERSION};
 use serde::{Deserialize, Serialize};
 use tauri::State;
 use reqwest;
-use sqlx::Row;
 
 // Helper function to check server connectivity
-async fn check_server_connectivity(server_url: &str) -> bool {
+pub(crate) async fn check_server_connectivity(server_url: &str) -> bool {
     let client = reqwest::Client::new();
     let health_url = format!("{}/api/health", server_url);
     
@@ -36,7 +41,7 @@ pub struct SyncStatus {
     pub server_url: String,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SyncResult {
     pub success: bool,
     pub message: String,
@@ -45,6 +50,13 @@ pub struct SyncResult {
     #[serde(rename = "newTimestamp")]
     pub new_timestamp: i64,
     pub errors: Vec<String>,
+    /// Éléments refusés par le serveur, renvoyés à la prochaine synchronisation
+    #[serde(default)]
+    pub rejected: Vec<String>,
+    /// Problèmes locaux qui n'empêchent pas l'échange avec le serveur
+    /// (lignes illisibles, non envoyées tant qu'elles ne sont pas corrigées)
+    #[serde(default)]
+    pub warnings: Vec<String>,
 }
 
 // Helper function to get current timestamp in milliseconds
@@ -106,10 +118,32 @@ pub async fn update_record_metadata(pool: &DbPool, table_name: &str, id: &str) -
                 .execute(pool)
                 .await?;
         }
+        "document_templates" => {
+            sqlx::query("UPDATE document_templates SET updated_at = ?, version = COALESCE(version, 0) + 1 WHERE id = ?")
+                .bind(&now)
+                .bind(id)
+                .execute(pool)
+                .await?;
+        }
+        "document_links" => {
+            sqlx::query("UPDATE document_links SET updated_at = ?, version = COALESCE(version, 0) + 1 WHERE id = ?")
+                .bind(&now)
+                .bind(id)
+                .execute(pool)
+                .await?;
+        }
+        "activities" => {
+            sqlx::query("UPDATE activities SET updated_at = ?, version = COALESCE(version, 0) + 1 WHERE id = ?")
+                .bind(&now)
+                .bind(id)
+                .execute(pool)
+                .await?;
+        }
         _ => {
             return Err(sqlx::Error::Protocol(format!("Unknown table: {}", table_name)));
         }
     }
+    notify_local_change();
     Ok(())
 }
 
@@ -167,15 +201,37 @@ pub async fn mark_record_deleted(pool: &DbPool, table_name: &str, id: &str) -> R
                 .execute(pool)
                 .await?;
         }
+        "document_templates" => {
+            sqlx::query("UPDATE document_templates SET is_deleted = 1, updated_at = ?, version = COALESCE(version, 0) + 1 WHERE id = ?")
+                .bind(&now)
+                .bind(id)
+                .execute(pool)
+                .await?;
+        }
+        "document_links" => {
+            sqlx::query("UPDATE document_links SET is_deleted = 1, updated_at = ?, version = COALESCE(version, 0) + 1 WHERE id = ?")
+                .bind(&now)
+                .bind(id)
+                .execute(pool)
+                .await?;
+        }
+        "activities" => {
+            sqlx::query("UPDATE activities SET is_deleted = 1, updated_at = ?, version = COALESCE(version, 0) + 1 WHERE id = ?")
+                .bind(&now)
+                .bind(id)
+                .execute(pool)
+                .await?;
+        }
         _ => {
             return Err(sqlx::Error::Protocol(format!("Unknown table: {}", table_name)));
         }
     }
+    notify_local_change();
     Ok(())
 }
 
 #[tauri::command]
-pub async fn get_sync_status(pool: State<'_, DbPool>) -> Result<SyncStatus, String> {
+pub async fn get_sync_status(pool: State<'_, DbPool>, scheduler: State<'_, SyncScheduler>) -> Result<SyncStatus, String> {
     let metadata = queries::get_sync_metadata(&pool)
         .await
         .map_err(|e| format!("Failed to get sync metadata: {}", e))?;
@@ -183,121 +239,99 @@ pub async fn get_sync_status(pool: State<'_, DbPool>) -> Result<SyncStatus, Stri
     println!("📊 [SyncStatus] Sync metadata - last_sync_timestamp: {}, last_sync_version: {}", 
         metadata.last_sync_timestamp, metadata.last_sync_version);
     
-    println!("🔍 [SyncStatus] Checking if first sync (timestamp = 0): {}", metadata.last_sync_timestamp == 0);
-    
-    if metadata.last_sync_timestamp == 0 {
-        println!("⚠️ [SyncStatus] First sync detected (last_sync_timestamp = 0)");
-        println!("🔧 [SyncStatus] Fixing timestamps for existing records...");
-        
-                // Fix timestamps for existing records - convert ISO format to milliseconds
-                let now = current_timestamp();
-                println!("🔧 [SyncStatus] Setting timestamp to: {}", now);
-
-                // Debug: Check what values exist in updated_at
-                let debug_result = sqlx::query("SELECT id, updated_at FROM companies LIMIT 3")
-                    .fetch_all(&*pool)
-                    .await;
-
-                match debug_result {
-                    Ok(rows) => {
-                        println!("🔍 [SyncStatus] Debug - Sample companies updated_at values:");
-                        for row in rows {
-                            let id: String = row.get("id");
-                            let updated_at: String = row.get("updated_at");
-                            println!("  - ID: {}, updated_at: '{}'", id, updated_at);
-                        }
-                    }
-                    Err(e) => {
-                        println!("🔍 [SyncStatus] Debug - Error fetching companies: {}", e);
-                    }
-                }
-
-                // Convert all existing ISO timestamps to milliseconds
-                let result = sqlx::query("UPDATE companies SET updated_at = ? WHERE updated_at LIKE '%-%'")
-                    .bind(now.to_string())
-                    .execute(&*pool)
-                    .await;
-                println!("🔧 [SyncStatus] Companies updated: {:?}", result);
-                let _ = sqlx::query("UPDATE company_contacts SET updated_at = ? WHERE updated_at LIKE '%-%'")
-                    .bind(now.to_string())
-                    .execute(&*pool)
-                    .await;
-                let _ = sqlx::query("UPDATE customers SET updated_at = ? WHERE updated_at LIKE '%-%'")
-                    .bind(now.to_string())
-                    .execute(&*pool)
-                    .await;
-                let _ = sqlx::query("UPDATE proposals SET updated_at = ? WHERE updated_at LIKE '%-%'")
-                    .bind(now.to_string())
-                    .execute(&*pool)
-                    .await;
-                let _ = sqlx::query("UPDATE proposal_products SET updated_at = ? WHERE updated_at LIKE '%-%'")
-                    .bind(now.to_string())
-                    .execute(&*pool)
-                    .await;
-                let _ = sqlx::query("UPDATE invoices SET updated_at = ? WHERE updated_at LIKE '%-%'")
-                    .bind(now.to_string())
-                    .execute(&*pool)
-                    .await;
-                let _ = sqlx::query("UPDATE documents SET updated_at = ? WHERE updated_at LIKE '%-%'")
-                    .bind(now.to_string())
-                    .execute(&*pool)
-                    .await;
-        
-        println!("✅ [SyncStatus] Timestamps fixed for existing records");
-    }
-
     // Count pending changes
-    let changed_items = queries::get_changed_items_since(&pool, metadata.last_sync_timestamp)
+    let changes = queries::get_pending_sync_items(&pool, metadata.last_push_timestamp)
         .await
         .map_err(|e| format!("Failed to get pending changes: {}", e))?;
     
-    let pending_changes = changed_items.len() as i64;
+    let pending_changes = (changes.items.len() + changes.unreadable.len()) as i64;
     
     // Log pending changes for debugging
     if pending_changes > 0 {
         println!("📋 [SyncStatus] Found {} pending changes:", pending_changes);
-        for item in &changed_items {
+        for item in &changes.items {
             println!("  - {}: {} (updated: {}, version: {}, deleted: {})", 
-                item.table_name, item.id, item.updated_at, item.version, item.is_deleted);
+                item.table_name(), item.id(), item.updated_at(), item.version(), item.is_deleted());
+        }
+        for error in &changes.unreadable {
+            println!("  - {} (unreadable)", error);
         }
     } else {
         println!("✅ [SyncStatus] No pending changes found");
     }
 
     // Check server connectivity
-    let server_url = "http://localhost:8080";
-    println!("🔄 [SyncStatus] Getting sync status for server: {}", server_url);
-    let is_online = check_server_connectivity(server_url).await;
+    let server_url = scheduler.server_url().unwrap_or_default();
+    let is_online = if server_url.is_empty() {
+        println!("⚠️ [SyncStatus] No server configured");
+        false
+    } else {
+        println!("🔄 [SyncStatus] Getting sync status for server: {}", server_url);
+        check_server_connectivity(&server_url).await
+    };
     println!("📊 [SyncStatus] Final status - Online: {}, Pending: {}", is_online, pending_changes);
 
     Ok(SyncStatus {
         is_online,
         last_sync: if metadata.last_sync_timestamp > 0 { Some(metadata.last_sync_timestamp) } else { None },
         pending_changes,
-        server_url: server_url.to_string(),
+        server_url,
     })
 }
 
 #[tauri::command]
-pub async fn sync_with_server(pool: State<'_, DbPool>, server_url: String) -> Result<SyncResult, String> {
+pub async fn sync_with_server(
+    app_handle: tauri::AppHandle,
+    pool: State<'_, DbPool>,
+    scheduler: State<'_, SyncScheduler>,
+) -> Result<SyncResult, String> {
+    let server_url = scheduler
+        .server_url()
+        .ok_or("No sync server configured for this profile")?;
+    // Passe par le planificateur : jamais deux synchronisations en même temps
+    scheduler.sync_now(&app_handle, &pool, &server_url).await
+}
+
+/// Envoie les modifications locales puis applique celles du serveur.
+/// Les étapes sont signalées par l'événement `sync:progress`
+pub async fn run_sync(app_handle: &tauri::AppHandle, pool: &DbPool, server_url: &str) -> Result<SyncResult, String> {
     println!("🔄 [Sync] Starting synchronization with server: {}", server_url);
     
     // Get current sync metadata
-    let metadata = queries::get_sync_metadata(&pool)
+    let metadata = queries::get_sync_metadata(pool)
         .await
         .map_err(|e| format!("Failed to get sync metadata: {}", e))?;
 
-    // Get local changes since last sync
-    let local_changes = queries::get_changed_items_since(&pool, metadata.last_sync_timestamp)
+    // Limite des modifications envoyées, prise en heure locale avant de les
+    // lire : une écriture faite pendant la synchronisation partira à la
+    // suivante, même datée avant le `new_timestamp` du serveur (une écriture
+    // dans la même milliseconde que la lecture est renvoyée, sans effet)
+    let push_timestamp = chrono::Utc::now().timestamp_millis() - 1;
+
+    // Get local changes since last push, and those the server rejected
+    let local_changes = queries::get_pending_sync_items(pool, metadata.last_push_timestamp)
         .await
         .map_err(|e| format!("Failed to get local changes: {}", e))?;
 
+    let queries::PendingChanges { items: local_changes, unreadable } = local_changes;
+    for error in &unreadable {
+        eprintln!("⚠️ [Sync] Not sent: {}", error);
+    }
+
     println!("🔄 [Sync] Found {} local changes to sync", local_changes.len());
+    emit_progress(app_handle, "pushing", local_changes.len());
+
+    let (device_id, device_name) = queries::get_device_identity(pool)
+        .await
+        .map_err(|e| format!("Failed to get device identity: {}", e))?;
 
     // Prepare sync request
     let sync_request = SyncRequest {
+        protocol_version: PROTOCOL_VERSION,
         last_sync_timestamp: metadata.last_sync_timestamp,
-        changes: local_changes,
+        changes: local_changes.iter().cloned().map(SyncEnvelope::from).collect(),
+        device_id: Some(device_id),
+        device_name: Some(device_name),
     };
 
     // Send to server
@@ -316,22 +350,72 @@ pub async fn sync_with_server(pool: State<'_, DbPool>, server_url: String) -> Re
                 items_synced: 0,
                 new_timestamp: metadata.last_sync_timestamp,
                 errors: vec![e.to_string()],
+                rejected: vec![],
+                warnings: vec![],
+            });
+        }
+    };
+
+    let status = response.status();
+    let body: serde_json::Value = match response.json().await {
+        Ok(body) => body,
+        Err(e) if status.is_success() => {
+            return Ok(SyncResult {
+                success: false,
+                message: format!("Failed to parse server response: {}", e),
+                items_synced: 0,
+                new_timestamp: metadata.last_sync_timestamp,
+                errors: vec![e.to_string()],
+                rejected: vec![],
+                warnings: vec![],
             });
         }
+        Err(_) => serde_json::Value::Null,
     };
 
-    if !response.status().is_success() {
-        let error_msg = format!("Server returned status: {}", response.status());
+    // Serveur d'une autre version : son contenu ne peut pas être lu
+    let server_protocol = body.get("protocol_version").and_then(|v| v.as_u64()).unwrap_or(0);
+    if status.is_success() && server_protocol != u64::from(PROTOCOL_VERSION) {
+        let error_msg = if server_protocol < u64::from(PROTOCOL_VERSION) {
+            format!(
+                "Server upgrade required: the server uses sync protocol {} but this application requires {}.",
+                server_protocol, PROTOCOL_VERSION
+            )
+        } else {
+            format!(
+                "Upgrade required: the server uses sync protocol {} but this application only supports {}. Please update the application.",
+                server_protocol, PROTOCOL_VERSION
+            )
+        };
+        return Ok(SyncResult {
+            success: false,
+            message: error_msg.clone(),
+            items_synced: 0,
+            new_timestamp: metadata.last_sync_timestamp,
+            errors: vec![error_msg],
+            rejected: vec![],
+            warnings: vec![],
+        });
+    }
+
+    if !status.is_success() {
+        let error_msg = body
+            .get("message")
+            .and_then(|v| v.as_str())
+            .map(str::to_string)
+            .unwrap_or_else(|| format!("Server returned status: {}", status));
         return Ok(SyncResult {
             success: false,
             message: error_msg.clone(),
             items_synced: 0,
             new_timestamp: metadata.last_sync_timestamp,
             errors: vec![error_msg],
+            rejected: vec![],
+            warnings: vec![],
         });
     }
 
-    let sync_response: SyncResponse = match response.json().await {
+    let sync_response: SyncResponse = match serde_json::from_value(body) {
         Ok(response) => response,
         Err(e) => {
             return Ok(SyncResult {
@@ -340,35 +424,41 @@ pub async fn sync_with_server(pool: State<'_, DbPool>, server_url: String) -> Re
                 items_synced: 0,
                 new_timestamp: metadata.last_sync_timestamp,
                 errors: vec![e.to_string()],
+                rejected: vec![],
+                warnings: vec![],
             });
         }
     };
 
-    if !sync_response.success {
-        let error_msg = sync_response.message.clone();
-        return Ok(SyncResult {
-            success: false,
-            message: error_msg.clone(),
-            items_synced: 0,
-            new_timestamp: metadata.last_sync_timestamp,
-            errors: vec![error_msg],
-        });
+    // Éléments refusés par le serveur : renvoyés aux synchronisations
+    // suivantes, sans empêcher la réception des modifications du serveur
+    let rejected: Vec<String> = sync_response.errors.iter().map(ToString::to_string).collect();
+    for error in &rejected {
+        eprintln!("❌ [Sync] Rejected by server: {}", error);
     }
+    let refused: Vec<SyncItemError> = sync_response.errors.iter().chain(&unreadable).cloned().collect();
+    queries::record_sync_rejections(pool, &local_changes, &refused)
+        .await
+        .map_err(|e| format!("Failed to record rejected changes: {}", e))?;
 
     println!("🔄 [Sync] Server returned {} remote changes", sync_response.remote_changes.len());
+    emit_progress(app_handle, "applying", sync_response.remote_changes.len());
 
     // Apply remote changes to local database
     let mut errors = Vec::new();
+    // Lignes illisibles : signalées à chaque synchronisation tant qu'elles ne
+    // sont pas corrigées, sans la faire échouer ni retarder la suivante
+    let mut warnings: Vec<String> = unreadable.iter().map(ToString::to_string).collect();
     let mut items_synced = 0;
 
     for item in &sync_response.remote_changes {
-        match queries::apply_sync_item(&pool, item).await {
+        match queries::apply_sync_item(pool, item).await {
             Ok(_) => {
                 items_synced += 1;
-                println!("✅ [Sync] Applied remote change: {} {}", item.table_name, item.id);
+                println!("✅ [Sync] Applied remote change: {} {}", item.table_name(), item.id());
             }
             Err(e) => {
-                let error_msg = format!("Failed to apply {} {}: {}", item.table_name, item.id, e);
+                let error_msg = format!("Failed to apply {} {}: {}", item.table_name(), item.id(), e);
                 errors.push(error_msg.clone());
                 eprintln!("❌ [Sync] {}", error_msg);
             }
@@ -377,18 +467,37 @@ pub async fn sync_with_server(pool: State<'_, DbPool>, server_url: String) -> Re
 
     // Update sync metadata if sync was successful
     if errors.is_empty() || items_synced > 0 {
-        queries::update_sync_metadata(&pool, sync_response.new_timestamp, metadata.last_sync_version + 1)
+        queries::update_sync_metadata(pool, sync_response.new_timestamp, push_timestamp, metadata.last_sync_version + 1)
             .await
             .map_err(|e| format!("Failed to update sync metadata: {}", e))?;
         
         println!("✅ [Sync] Updated sync metadata to timestamp: {}", sync_response.new_timestamp);
+
+        // Suppressions envoyées au serveur : purgeables après la durée de conservation
+        if let Err(e) = purge_trash(pool, push_timestamp).await {
+            warnings.push(e);
+        }
+    }
+
+    // Fichiers des documents, envoyés après leurs métadonnées
+    if let Some(app_dir) = app_handle.path_resolver().app_data_dir() {
+        emit_progress(app_handle, "attachments", 0);
+        match upload_pending_attachments(&app_dir, pool, server_url).await {
+            Ok(count) if count > 0 => println!("✅ [Sync] Uploaded {} attachments", count),
+            Ok(_) => {}
+            Err(e) => errors.push(e),
+        }
     }
 
     let success = errors.is_empty();
-    let message = if success {
-        format!("Synchronization completed successfully. {} items synced.", items_synced)
-    } else {
+    let message = if !success {
         format!("Synchronization completed with {} errors. {} items synced.", errors.len(), items_synced)
+    } else if !rejected.is_empty() {
+        format!("Synchronization completed, {} changes rejected by the server. {} items synced.", rejected.len(), items_synced)
+    } else if !warnings.is_empty() {
+        format!("Synchronization completed with {} warnings. {} items synced.", warnings.len(), items_synced)
+    } else {
+        format!("Synchronization completed successfully. {} items synced.", items_synced)
     };
 
     Ok(SyncResult {
@@ -397,9 +506,16 @@ pub async fn sync_with_server(pool: State<'_, DbPool>, server_url: String) -> Re
         items_synced,
         new_timestamp: sync_response.new_timestamp,
         errors,
+        rejected,
+        warnings,
     })
 }
 
+#[tauri::command]
+pub async fn get_sync_scheduler_status(scheduler: State<'_, SyncScheduler>) -> Result<SchedulerStatus, String> {
+    Ok(scheduler.status())
+}
+
 #[tauri::command]
 pub async fn force_sync_cleanup(_pool: State<'_, DbPool>) -> Result<String, String> {
     // This function can be used to clean up sync-related data if needed
@@ -407,15 +523,26 @@ pub async fn force_sync_cleanup(_pool: State<'_, DbPool>) -> Result<String, Stri
     Ok("Sync cleanup completed".to_string())
 }
 
+/// Adresse du serveur du profil actif ; vide s'il n'y en a pas
 #[tauri::command]
-pub async fn get_server_url() -> Result<String, String> {
-    // TODO: Implement server URL storage/retrieval
-    Ok("http://localhost:8080".to_string())
+pub async fn get_server_url(app_handle: tauri::AppHandle) -> Result<String, String> {
+    let profiles = ServerProfiles::load(&app_data_dir(&app_handle)?)?;
+    Ok(profiles.active().server_url.clone().unwrap_or_default())
 }
 
+/// Change l'adresse du serveur du profil actif
 #[tauri::command]
-pub async fn set_server_url(url: String) -> Result<(), String> {
-    // TODO: Implement server URL storage
-    println!("Server URL set to: {}", url);
+pub async fn set_server_url(
+    app_handle: tauri::AppHandle,
+    pool: State<'_, DbPool>,
+    scheduler: State<'_, SyncScheduler>,
+    url: String,
+) -> Result<(), String> {
+    let profiles = ServerProfiles::load(&app_data_dir(&app_handle)?)?;
+    let profile = ServerProfile {
+        server_url: Some(url),
+        ..profiles.active().clone()
+    };
+    save_server_profile(app_handle, pool, scheduler, profile).await?;
     Ok(())
 }
@@ -0,0 +1,147 @@
+use crate::commands::sync_commands::mark_record_deleted;
+use crate::db::{models::DocumentTemplate, queries, DbPool};
+use crate::templates::{self, TemplateCriteria, TemplateValidation};
+use crate::sync_scheduler::notify_local_change;
+use std::path::Path;
+use tauri::State;
+
+#[tauri::command]
+pub async fn validate_document_template(
+    file_path: String,
+    kind: String,
+) -> Result<TemplateValidation, String> {
+    let bytes = std::fs::read(&file_path)
+        .map_err(|e| format!("Failed to read template: {}", e))?;
+    templates::validate(&kind, &bytes)
+}
+
+#[tauri::command]
+pub async fn upload_document_template(
+    app_handle: tauri::AppHandle,
+    pool: State<'_, DbPool>,
+    file_path: String,
+    name: String,
+    kind: String,
+    product_type: Option<String>,
+    language: Option<String>,
+    company_id: Option<String>,
+) -> Result<DocumentTemplate, String> {
+    let extension = templates::file_extension(&kind)?;
+    let path = Path::new(&file_path);
+    if !path
+        .extension()
+        .and_then(|e| e.to_str())
+        .map(|e| e.eq_ignore_ascii_case(extension))
+        .unwrap_or(false)
+    {
+        return Err(format!("A {} template must be a .{} file", kind, extension));
+    }
+
+    let bytes = std::fs::read(path)
+        .map_err(|e| format!("Failed to read template: {}", e))?;
+
+    // Refuser un modèle dont les placeholders ne seraient jamais remplis, ou
+    // un classeur privé de noms indispensables
+    let validation = templates::validate(&kind, &bytes)?;
+    if !validation.missing_required.is_empty() {
+        return Err(format!(
+            "Template is missing required names: {}",
+            validation.missing_required.join(", ")
+        ));
+    }
+    if !validation.valid {
+        return Err(format!(
+            "Template contains unknown placeholders: {}",
+            validation.unknown.join(", ")
+        ));
+    }
+
+    let file_name = path
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or(format!("template.{}", extension));
+    let empty_to_none = |value: Option<String>| value.filter(|v| !v.trim().is_empty());
+
+    let template = queries::create_document_template(
+        &pool,
+        name,
+        kind,
+        empty_to_none(product_type),
+        empty_to_none(language),
+        empty_to_none(company_id),
+        file_name,
+        templates::encode(&bytes),
+        templates::checksum(&bytes),
+    )
+    .await
+    .map_err(|e| format!("Failed to save template: {}", e))?;
+
+    templates::materialize(app_handle.path_resolver().app_data_dir().as_deref(), &template)?;
+
+    println!(
+        "🗂️ Template {} v{} added ({} placeholders, {} unused fields)",
+        template.name,
+        template.template_version,
+        validation.placeholders.len(),
+        validation.missing.len()
+    );
+    notify_local_change();
+
+    // Les listes ne transportent pas le contenu
+    Ok(DocumentTemplate { content: None, ..template })
+}
+
+#[tauri::command]
+pub async fn get_document_templates(
+    pool: State<'_, DbPool>,
+    kind: Option<String>,
+) -> Result<Vec<DocumentTemplate>, String> {
+    queries::get_document_templates(&pool, kind.as_deref())
+        .await
+        .map_err(|e| format!("Failed to get templates: {}", e))
+}
+
+#[tauri::command]
+pub async fn delete_document_template(
+    pool: State<'_, DbPool>,
+    id: String,
+) -> Result<(), String> {
+    // Suppression logique pour que la synchronisation la propage
+    mark_record_deleted(&pool, "document_templates", &id)
+        .await
+        .map_err(|e| format!("Failed to delete template: {}", e))
+}
+
+/// Contenu du modèle à utiliser pour une génération : le modèle demandé
+/// explicitement, sinon le plus spécifique de la bibliothèque. `None` si la
+/// bibliothèque ne contient aucun modèle adapté.
+pub async fn resolve_template(
+    app_handle: &tauri::AppHandle,
+    pool: &DbPool,
+    kind: &str,
+    template_id: Option<String>,
+    criteria: TemplateCriteria<'_>,
+) -> Result<Option<Vec<u8>>, String> {
+    let selected_id = match template_id {
+        Some(id) => Some(id),
+        None => {
+            let library = queries::get_document_templates(pool, Some(kind))
+                .await
+                .map_err(|e| format!("Failed to get templates: {}", e))?;
+            templates::select(&library, kind, &criteria).map(|t| t.id.clone())
+        }
+    };
+    let Some(id) = selected_id else {
+        return Ok(None);
+    };
+
+    let template = queries::get_document_template_by_id(pool, &id)
+        .await
+        .map_err(|e| format!("Failed to get template: {}", e))?
+        .filter(|t| t.kind == kind && t.is_deleted.unwrap_or(0) == 0)
+        .ok_or("Template not found")?;
+
+    println!("🗂️ Using template {} v{}", template.name, template.template_version);
+    let bytes = templates::materialize(app_handle.path_resolver().app_data_dir().as_deref(), &template)?;
+    Ok(Some(bytes))
+}
@@ -0,0 +1,56 @@
+use crate::commands::auth_commands::CurrentUser;
+use crate::db::{models::TrashItem, queries, DbPool};
+use crate::sync_scheduler::notify_local_change;
+use tauri::State;
+
+/// Durée de conservation des éléments de la corbeille, en jours ; au-delà,
+/// ils sont purgés dès que la suppression a été envoyée au serveur
+pub const TRASH_RETENTION_DAYS: i64 = 30;
+
+/// Met un enregistrement à la corbeille au nom de l'utilisateur connecté
+pub async fn trash_record(pool: &DbPool, user: &CurrentUser, table_name: &str, id: &str) -> Result<(), String> {
+    let trashed = queries::move_to_trash(pool, table_name, id, user.get().as_deref())
+        .await
+        .map_err(|e| format!("Failed to move record to trash: {}", e))?;
+    if trashed {
+        println!("🗑️ [Trash] {} {} moved to trash", table_name, id);
+        notify_local_change();
+    }
+    Ok(())
+}
+
+/// Purge les éléments de la corbeille plus anciens que la durée de
+/// conservation, s'ils ont été synchronisés (`synced_until`, millisecondes).
+pub async fn purge_trash(pool: &DbPool, synced_until: i64) -> Result<u64, String> {
+    let retention_start = chrono::Utc::now().timestamp_millis() - TRASH_RETENTION_DAYS * 24 * 3600 * 1000;
+    let purged = queries::purge_tombstones(pool, retention_start.min(synced_until))
+        .await
+        .map_err(|e| format!("Failed to purge trash: {}", e))?;
+    if purged > 0 {
+        println!("🗑️ [Trash] {} deleted records purged", purged);
+    }
+    Ok(purged)
+}
+
+#[tauri::command]
+pub async fn get_trash(pool: State<'_, DbPool>) -> Result<Vec<TrashItem>, String> {
+    queries::get_trash(&pool)
+        .await
+        .map_err(|e| format!("Failed to get trash: {}", e))
+}
+
+/// Restaure un élément de la corbeille et ses dépendants ; retourne le
+/// nombre de dépendants restaurés.
+#[tauri::command]
+pub async fn restore_from_trash(pool: State<'_, DbPool>, table_name: String, id: String) -> Result<u64, String> {
+    let restored = queries::restore_from_trash(&pool, &table_name, &id)
+        .await
+        .map_err(|e| match e {
+            sqlx::Error::RowNotFound => format!("Record not found in trash: {} {}", table_name, id),
+            e => format!("Failed to restore record: {}", e),
+        })?;
+
+    println!("♻️ [Trash] {} {} restored with {} dependent records", table_name, id, restored);
+    notify_local_change();
+    Ok(restored)
+}
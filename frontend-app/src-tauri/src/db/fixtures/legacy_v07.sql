@@ -0,0 +1,274 @@
+-- Base créée avant le suivi des versions, schéma équivalent à la version 7
+CREATE TABLE roles (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    name TEXT UNIQUE NOT NULL,
+    description TEXT
+);
+CREATE TABLE users (
+    id TEXT PRIMARY KEY,
+    email TEXT UNIQUE NOT NULL,
+    password_hash TEXT NOT NULL,
+    name TEXT NOT NULL,
+    enabled INTEGER NOT NULL DEFAULT 1,
+    created_at TEXT NOT NULL,
+    updated_at TEXT NOT NULL
+);
+CREATE TABLE user_roles (
+    user_id TEXT NOT NULL,
+    role_id INTEGER NOT NULL,
+    PRIMARY KEY (user_id, role_id),
+    FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE,
+    FOREIGN KEY (role_id) REFERENCES roles(id) ON DELETE CASCADE
+);
+CREATE TABLE companies (
+    id TEXT PRIMARY KEY,
+    name TEXT UNIQUE NOT NULL,
+    website TEXT,
+    address TEXT,
+    city TEXT,
+    postal_code TEXT,
+    country TEXT,
+    description TEXT,
+    created_at TEXT NOT NULL,
+    updated_at TEXT NOT NULL,
+    server_id TEXT
+, version INTEGER DEFAULT 1, is_deleted INTEGER DEFAULT 0);
+CREATE TABLE company_contacts (
+    id TEXT PRIMARY KEY,
+    company_id TEXT NOT NULL,
+    first_name TEXT NOT NULL,
+    last_name TEXT NOT NULL,
+    email TEXT NOT NULL,
+    phone_number TEXT,
+    is_primary INTEGER DEFAULT 0,
+    created_at TEXT NOT NULL,
+    updated_at TEXT NOT NULL, version INTEGER DEFAULT 1, is_deleted INTEGER DEFAULT 0,
+    FOREIGN KEY (company_id) REFERENCES companies(id) ON DELETE CASCADE
+);
+CREATE TABLE licence_pricing (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    product_type TEXT NOT NULL,
+    user_count INTEGER NOT NULL,
+    price_usd REAL NOT NULL,
+    created_at TEXT NOT NULL,
+    updated_at TEXT NOT NULL,
+    UNIQUE(product_type, user_count)
+);
+CREATE TABLE proposals (
+    id TEXT PRIMARY KEY,
+    company_id TEXT NOT NULL,
+    proposal_number TEXT UNIQUE,
+    status TEXT NOT NULL DEFAULT 'DRAFT',
+    total_amount REAL,
+    currency TEXT DEFAULT 'USD',
+    valid_until TEXT,
+    notes TEXT,
+    created_at TEXT NOT NULL,
+    updated_at TEXT NOT NULL, version INTEGER DEFAULT 1, is_deleted INTEGER DEFAULT 0,
+    FOREIGN KEY (company_id) REFERENCES companies(id) ON DELETE CASCADE
+);
+CREATE TABLE proposal_products (
+    id TEXT PRIMARY KEY,
+    proposal_id TEXT NOT NULL,
+    product_type TEXT NOT NULL,
+    user_count INTEGER NOT NULL,
+    standalone_count INTEGER DEFAULT 0,
+    server_key_count INTEGER DEFAULT 0,
+    unit_price REAL,
+    total_price REAL,
+    annual_reduction REAL DEFAULT 0,
+    training INTEGER DEFAULT 0,
+    training_days INTEGER DEFAULT 0,
+    training_cost_per_day REAL DEFAULT 0,
+    training_cost REAL DEFAULT 0,
+    licence INTEGER DEFAULT 0,
+    support INTEGER DEFAULT 0,
+    support_years INTEGER DEFAULT 0,
+    created_at TEXT NOT NULL,
+    updated_at TEXT NOT NULL, version INTEGER DEFAULT 1, is_deleted INTEGER DEFAULT 0,
+    FOREIGN KEY (proposal_id) REFERENCES proposals(id) ON DELETE CASCADE
+);
+CREATE TABLE invoices (
+    id TEXT PRIMARY KEY,
+    proposal_id TEXT NOT NULL,
+    invoice_number TEXT UNIQUE NOT NULL,
+    status TEXT NOT NULL DEFAULT 'DRAFT',
+    total_amount REAL NOT NULL DEFAULT 0.0,
+    currency TEXT NOT NULL DEFAULT 'USD',
+    issue_date TEXT NOT NULL,
+    due_date TEXT,
+    paid_date TEXT,
+    purchase_order TEXT,
+    purchase_order_date TEXT,
+    commercial_in_charge TEXT,
+    notes TEXT,
+    created_at TEXT NOT NULL,
+    updated_at TEXT NOT NULL, version INTEGER DEFAULT 1, is_deleted INTEGER DEFAULT 0,
+    FOREIGN KEY (proposal_id) REFERENCES proposals(id) ON DELETE CASCADE
+);
+CREATE TABLE customers (
+    id TEXT PRIMARY KEY,
+    name TEXT NOT NULL,
+    email TEXT,
+    phone TEXT,
+    address TEXT,
+    notes TEXT,
+    created_at TEXT NOT NULL,
+    updated_at TEXT NOT NULL,
+    server_id TEXT
+, version INTEGER DEFAULT 1, is_deleted INTEGER DEFAULT 0);
+CREATE TABLE documents (
+    id TEXT PRIMARY KEY,
+    customer_id TEXT,
+    company_id TEXT,
+    title TEXT NOT NULL,
+    document_type TEXT NOT NULL,
+    file_path TEXT,
+    content TEXT,
+    created_at TEXT NOT NULL,
+    updated_at TEXT NOT NULL, version INTEGER DEFAULT 1, is_deleted INTEGER DEFAULT 0, source_type TEXT, source_id TEXT, revision INTEGER, sha256 TEXT,
+    FOREIGN KEY (customer_id) REFERENCES customers(id) ON DELETE CASCADE,
+    FOREIGN KEY (company_id) REFERENCES companies(id) ON DELETE CASCADE
+);
+CREATE TABLE document_templates (
+    id TEXT PRIMARY KEY,
+    name TEXT NOT NULL,
+    kind TEXT NOT NULL,
+    product_type TEXT,
+    language TEXT,
+    company_id TEXT,
+    template_version INTEGER NOT NULL DEFAULT 1,
+    file_name TEXT NOT NULL,
+    content TEXT,
+    checksum TEXT NOT NULL,
+    created_at TEXT NOT NULL,
+    updated_at TEXT NOT NULL,
+    version INTEGER DEFAULT 1,
+    is_deleted INTEGER DEFAULT 0
+);
+CREATE INDEX idx_document_templates_kind ON document_templates(kind, name);
+CREATE INDEX idx_document_templates_updated_at ON document_templates(updated_at);
+CREATE TABLE document_links (
+    id TEXT PRIMARY KEY,
+    document_id TEXT NOT NULL,
+    entity_type TEXT NOT NULL,
+    entity_id TEXT NOT NULL,
+    created_at TEXT NOT NULL,
+    updated_at TEXT NOT NULL,
+    version INTEGER DEFAULT 1,
+    is_deleted INTEGER DEFAULT 0
+);
+CREATE INDEX idx_document_links_entity ON document_links(entity_type, entity_id);
+CREATE INDEX idx_document_links_document ON document_links(document_id);
+CREATE INDEX idx_document_links_updated_at ON document_links(updated_at);
+CREATE TABLE attachment_transfers (
+    sha256 TEXT PRIMARY KEY,
+    uploaded INTEGER NOT NULL DEFAULT 0,
+    last_error TEXT,
+    updated_at TEXT NOT NULL
+);
+CREATE TABLE sync_metadata (
+    id INTEGER PRIMARY KEY DEFAULT 1,
+    last_sync_timestamp INTEGER NOT NULL DEFAULT 0,
+    last_sync_version INTEGER NOT NULL DEFAULT 0,
+    updated_at TEXT NOT NULL DEFAULT (strftime('%s', 'now') * 1000)
+);
+CREATE INDEX idx_companies_updated_at ON companies(updated_at);
+CREATE INDEX idx_companies_is_deleted ON companies(is_deleted);
+CREATE INDEX idx_company_contacts_updated_at ON company_contacts(updated_at);
+CREATE INDEX idx_company_contacts_is_deleted ON company_contacts(is_deleted);
+CREATE INDEX idx_customers_updated_at ON customers(updated_at);
+CREATE INDEX idx_customers_is_deleted ON customers(is_deleted);
+CREATE INDEX idx_proposals_updated_at ON proposals(updated_at);
+CREATE INDEX idx_proposals_is_deleted ON proposals(is_deleted);
+CREATE INDEX idx_proposal_products_updated_at ON proposal_products(updated_at);
+CREATE INDEX idx_proposal_products_is_deleted ON proposal_products(is_deleted);
+CREATE INDEX idx_invoices_updated_at ON invoices(updated_at);
+CREATE INDEX idx_invoices_is_deleted ON invoices(is_deleted);
+CREATE INDEX idx_documents_updated_at ON documents(updated_at);
+CREATE INDEX idx_documents_is_deleted ON documents(is_deleted);
+CREATE INDEX idx_documents_company_id ON documents(company_id);
+CREATE INDEX idx_documents_source ON documents(source_type, source_id);
+CREATE VIRTUAL TABLE search_index USING fts5(
+    entity_type UNINDEXED,
+    entity_id UNINDEXED,
+    parent_id UNINDEXED,
+    title,
+    body,
+    tokenize = 'unicode61 remove_diacritics 2'
+);
+CREATE TRIGGER search_companies_ai AFTER INSERT ON companies BEGIN
+    DELETE FROM search_index WHERE entity_type = 'company' AND entity_id = NEW.id;
+    INSERT INTO search_index (entity_type, entity_id, parent_id, title, body)
+        SELECT 'company', NEW.id, NULL, NEW.name, TRIM(COALESCE(NEW.website, '') || ' ' || COALESCE(NEW.address, '') || ' ' || COALESCE(NEW.city, '') || ' ' || COALESCE(NEW.postal_code, '') || ' ' || COALESCE(NEW.country, '') || ' ' || COALESCE(NEW.description, ''))
+        WHERE COALESCE(NEW.is_deleted, 0) = 0;
+END;
+CREATE TRIGGER search_companies_au AFTER UPDATE ON companies BEGIN
+    DELETE FROM search_index WHERE entity_type = 'company' AND entity_id = NEW.id;
+    INSERT INTO search_index (entity_type, entity_id, parent_id, title, body)
+        SELECT 'company', NEW.id, NULL, NEW.name, TRIM(COALESCE(NEW.website, '') || ' ' || COALESCE(NEW.address, '') || ' ' || COALESCE(NEW.city, '') || ' ' || COALESCE(NEW.postal_code, '') || ' ' || COALESCE(NEW.country, '') || ' ' || COALESCE(NEW.description, ''))
+        WHERE COALESCE(NEW.is_deleted, 0) = 0;
+END;
+CREATE TRIGGER search_companies_ad AFTER DELETE ON companies BEGIN
+    DELETE FROM search_index WHERE entity_type = 'company' AND entity_id = OLD.id;
+END;
+CREATE TRIGGER search_company_contacts_ai AFTER INSERT ON company_contacts BEGIN
+    DELETE FROM search_index WHERE entity_type = 'contact' AND entity_id = NEW.id;
+    INSERT INTO search_index (entity_type, entity_id, parent_id, title, body)
+        SELECT 'contact', NEW.id, NEW.company_id, NEW.first_name || ' ' || NEW.last_name, TRIM(COALESCE(NEW.email, '') || ' ' || COALESCE(NEW.phone_number, ''))
+        WHERE COALESCE(NEW.is_deleted, 0) = 0;
+END;
+CREATE TRIGGER search_company_contacts_au AFTER UPDATE ON company_contacts BEGIN
+    DELETE FROM search_index WHERE entity_type = 'contact' AND entity_id = NEW.id;
+    INSERT INTO search_index (entity_type, entity_id, parent_id, title, body)
+        SELECT 'contact', NEW.id, NEW.company_id, NEW.first_name || ' ' || NEW.last_name, TRIM(COALESCE(NEW.email, '') || ' ' || COALESCE(NEW.phone_number, ''))
+        WHERE COALESCE(NEW.is_deleted, 0) = 0;
+END;
+CREATE TRIGGER search_company_contacts_ad AFTER DELETE ON company_contacts BEGIN
+    DELETE FROM search_index WHERE entity_type = 'contact' AND entity_id = OLD.id;
+END;
+CREATE TRIGGER search_proposals_ai AFTER INSERT ON proposals BEGIN
+    DELETE FROM search_index WHERE entity_type = 'proposal' AND entity_id = NEW.id;
+    INSERT INTO search_index (entity_type, entity_id, parent_id, title, body)
+        SELECT 'proposal', NEW.id, NEW.company_id, COALESCE(NEW.proposal_number, ''), COALESCE(NEW.notes, '')
+        WHERE COALESCE(NEW.is_deleted, 0) = 0;
+END;
+CREATE TRIGGER search_proposals_au AFTER UPDATE ON proposals BEGIN
+    DELETE FROM search_index WHERE entity_type = 'proposal' AND entity_id = NEW.id;
+    INSERT INTO search_index (entity_type, entity_id, parent_id, title, body)
+        SELECT 'proposal', NEW.id, NEW.company_id, COALESCE(NEW.proposal_number, ''), COALESCE(NEW.notes, '')
+        WHERE COALESCE(NEW.is_deleted, 0) = 0;
+END;
+CREATE TRIGGER search_proposals_ad AFTER DELETE ON proposals BEGIN
+    DELETE FROM search_index WHERE entity_type = 'proposal' AND entity_id = OLD.id;
+END;
+CREATE TRIGGER search_invoices_ai AFTER INSERT ON invoices BEGIN
+    DELETE FROM search_index WHERE entity_type = 'invoice' AND entity_id = NEW.id;
+    INSERT INTO search_index (entity_type, entity_id, parent_id, title, body)
+        SELECT 'invoice', NEW.id, NEW.proposal_id, NEW.invoice_number, TRIM(COALESCE(NEW.purchase_order, '') || ' ' || COALESCE(NEW.notes, ''))
+        WHERE COALESCE(NEW.is_deleted, 0) = 0;
+END;
+CREATE TRIGGER search_invoices_au AFTER UPDATE ON invoices BEGIN
+    DELETE FROM search_index WHERE entity_type = 'invoice' AND entity_id = NEW.id;
+    INSERT INTO search_index (entity_type, entity_id, parent_id, title, body)
+        SELECT 'invoice', NEW.id, NEW.proposal_id, NEW.invoice_number, TRIM(COALESCE(NEW.purchase_order, '') || ' ' || COALESCE(NEW.notes, ''))
+        WHERE COALESCE(NEW.is_deleted, 0) = 0;
+END;
+CREATE TRIGGER search_invoices_ad AFTER DELETE ON invoices BEGIN
+    DELETE FROM search_index WHERE entity_type = 'invoice' AND entity_id = OLD.id;
+END;
+CREATE TRIGGER search_documents_ai AFTER INSERT ON documents BEGIN
+    DELETE FROM search_index WHERE entity_type = 'document' AND entity_id = NEW.id;
+    INSERT INTO search_index (entity_type, entity_id, parent_id, title, body)
+        SELECT 'document', NEW.id, NULL, NEW.title, COALESCE(NEW.content, '')
+        WHERE COALESCE(NEW.is_deleted, 0) = 0;
+END;
+CREATE TRIGGER search_documents_au AFTER UPDATE ON documents BEGIN
+    DELETE FROM search_index WHERE entity_type = 'document' AND entity_id = NEW.id;
+    INSERT INTO search_index (entity_type, entity_id, parent_id, title, body)
+        SELECT 'document', NEW.id, NULL, NEW.title, COALESCE(NEW.content, '')
+        WHERE COALESCE(NEW.is_deleted, 0) = 0;
+END;
+CREATE TRIGGER search_documents_ad AFTER DELETE ON documents BEGIN
+    DELETE FROM search_index WHERE entity_type = 'document' AND entity_id = OLD.id;
+END;
@@ -0,0 +1,653 @@
+use super::{schema, DbPool};
+use crm_schema::{Date, Timestamp};
+use sha2::{Digest, Sha256};
+use sqlx::SqliteConnection;
+
+/// Étape d'une migration
+pub enum Step {
+    /// Requêtes exécutées telles quelles
+    Sql(&'static str),
+    /// `ALTER TABLE ... ADD COLUMN` exécutés un par un ; une colonne déjà
+    /// présente (base créée avant le suivi des versions) est ignorée
+    AddColumns(&'static str),
+    /// Requêtes exécutées seulement si la colonne n'existe pas encore
+    UnlessColumn {
+        table: &'static str,
+        column: &'static str,
+        sql: &'static str,
+    },
+    /// Réécrit sous leur forme canonique les instants (millisecondes) et les
+    /// dates métier ("YYYY-MM-DD") d'une table. La valeur représentée ne
+    /// change pas ; une valeur illisible est remplacée (voir `normalize_column`)
+    NormalizeTime {
+        table: &'static str,
+        timestamps: &'static [&'static str],
+        dates: &'static [&'static str],
+    },
+}
+
+/// Migration numérotée. Une migration publiée ne doit plus être modifiée :
+/// son empreinte est vérifiée à chaque ouverture de la base.
+pub struct Migration {
+    pub version: i64,
+    pub name: &'static str,
+    pub steps: &'static [Step],
+}
+
+// Les versions 1 à 10 reprennent le schéma des versions de l'application
+// antérieures au suivi des versions ; elles sont idempotentes pour pouvoir
+// adopter une base existante, quel que soit son état.
+pub const MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 1,
+        name: "initial",
+        steps: &[
+            Step::Sql(schema::CREATE_ROLES_TABLE),
+            Step::Sql(schema::INSERT_DEFAULT_ROLES),
+            Step::Sql(schema::CREATE_USERS_TABLE),
+            Step::Sql(schema::CREATE_USER_ROLES_TABLE),
+            Step::Sql(schema::CREATE_COMPANIES_TABLE),
+            Step::Sql(schema::CREATE_COMPANY_CONTACTS_TABLE),
+            Step::Sql(schema::CREATE_LICENCE_PRICING_TABLE),
+            Step::Sql(schema::INSERT_PRICING_DATA),
+            Step::Sql(schema::CREATE_PROPOSALS_TABLE),
+            Step::Sql(schema::CREATE_PROPOSAL_PRODUCTS_TABLE),
+            Step::Sql(schema::MIGRATE_PROPOSAL_PRODUCTS_IDS),
+            Step::Sql(schema::CREATE_INVOICES_TABLE),
+            Step::Sql(schema::CREATE_CUSTOMERS_TABLE),
+            Step::Sql(schema::CREATE_DOCUMENTS_TABLE),
+            Step::Sql(schema::CREATE_SYNC_METADATA_TABLE),
+            Step::AddColumns(schema::ADD_SYNC_COLUMNS_COMPANIES),
+            Step::AddColumns(schema::ADD_SYNC_COLUMNS_COMPANY_CONTACTS),
+            Step::AddColumns(schema::ADD_SYNC_COLUMNS_CUSTOMERS),
+            Step::AddColumns(schema::ADD_SYNC_COLUMNS_PROPOSALS),
+            Step::AddColumns(schema::ADD_SYNC_COLUMNS_PROPOSAL_PRODUCTS),
+            Step::AddColumns(schema::ADD_SYNC_COLUMNS_INVOICES),
+            Step::AddColumns(schema::ADD_SYNC_COLUMNS_DOCUMENTS),
+            Step::Sql(schema::CREATE_SYNC_INDEXES),
+        ],
+    },
+    Migration {
+        version: 2,
+        name: "document_templates",
+        steps: &[Step::Sql(schema::CREATE_DOCUMENT_TEMPLATES_TABLE)],
+    },
+    Migration {
+        version: 3,
+        name: "documents_company",
+        steps: &[
+            Step::UnlessColumn {
+                table: "documents",
+                column: "company_id",
+                sql: schema::MIGRATE_DOCUMENTS_COMPANY,
+            },
+            // La reconstruction de `documents` supprime ses index
+            Step::Sql(schema::CREATE_SYNC_INDEXES),
+            Step::Sql(schema::CREATE_DOCUMENTS_COMPANY_INDEX),
+        ],
+    },
+    Migration {
+        version: 4,
+        name: "documents_archive",
+        steps: &[
+            Step::AddColumns(schema::ADD_ARCHIVE_COLUMNS_DOCUMENTS),
+            Step::Sql(schema::CREATE_DOCUMENTS_SOURCE_INDEX),
+        ],
+    },
+    Migration {
+        version: 5,
+        name: "attachment_transfers",
+        steps: &[Step::Sql(schema::CREATE_ATTACHMENT_TRANSFERS_TABLE)],
+    },
+    Migration {
+        version: 6,
+        name: "document_links",
+        steps: &[
+            Step::Sql(schema::CREATE_DOCUMENT_LINKS_TABLE),
+            Step::Sql(schema::MIGRATE_DOCUMENT_LINKS),
+        ],
+    },
+    Migration {
+        version: 7,
+        name: "search_index",
+        steps: &[
+            Step::Sql(schema::CREATE_SEARCH_INDEX),
+            Step::Sql(schema::CREATE_SEARCH_TRIGGERS),
+            Step::Sql(schema::REBUILD_SEARCH_INDEX),
+        ],
+    },
+    Migration {
+        version: 8,
+        name: "company_merges",
+        steps: &[
+            Step::Sql(schema::CREATE_COMPANY_MERGES_TABLE),
+            Step::AddColumns(schema::ADD_MERGE_COLUMNS_COMPANIES),
+        ],
+    },
+    Migration {
+        version: 9,
+        name: "activities",
+        steps: &[Step::Sql(schema::CREATE_ACTIVITIES_TABLE)],
+    },
+    Migration {
+        version: 10,
+        name: "trash",
+        steps: &[
+            Step::AddColumns(schema::ADD_TRASH_COLUMNS),
+            Step::AddColumns(schema::ADD_DEVICE_COLUMNS_SYNC_METADATA),
+        ],
+    },
+    Migration {
+        version: 11,
+        name: "sync_rejections",
+        steps: &[Step::Sql(schema::CREATE_SYNC_REJECTIONS_TABLE)],
+    },
+    Migration {
+        version: 12,
+        name: "canonical_time",
+        // Les versions antérieures mêlaient millisecondes, RFC 3339 et
+        // `datetime('now')` ; le contenu des lignes n'est pas modifié, donc
+        // ni `updated_at` au sens de la synchronisation ni `version`
+        steps: &[
+            Step::NormalizeTime { table: "companies", timestamps: &["created_at", "updated_at"], dates: &[] },
+            Step::NormalizeTime { table: "company_contacts", timestamps: &["created_at", "updated_at"], dates: &[] },
+            Step::NormalizeTime { table: "customers", timestamps: &["created_at", "updated_at"], dates: &[] },
+            Step::NormalizeTime { table: "proposals", timestamps: &["created_at", "updated_at"], dates: &["valid_until"] },
+            Step::NormalizeTime { table: "proposal_products", timestamps: &["created_at", "updated_at"], dates: &[] },
+            Step::NormalizeTime {
+                table: "invoices",
+                timestamps: &["created_at", "updated_at"],
+                dates: &["issue_date", "due_date", "paid_date", "purchase_order_date"],
+            },
+            Step::NormalizeTime { table: "documents", timestamps: &["created_at", "updated_at"], dates: &[] },
+            Step::NormalizeTime { table: "document_templates", timestamps: &["created_at", "updated_at"], dates: &[] },
+            Step::NormalizeTime { table: "document_links", timestamps: &["created_at", "updated_at"], dates: &[] },
+            Step::NormalizeTime { table: "activities", timestamps: &["occurred_at", "created_at", "updated_at"], dates: &[] },
+        ],
+    },
+    Migration {
+        version: 13,
+        name: "sync_push_timestamp",
+        steps: &[
+            Step::AddColumns(schema::ADD_PUSH_COLUMN_SYNC_METADATA),
+            Step::Sql(schema::INIT_PUSH_TIMESTAMP_SYNC_METADATA),
+        ],
+    },
+    Migration {
+        version: 14,
+        name: "settings",
+        steps: &[
+            Step::Sql(schema::CREATE_SETTINGS_TABLE),
+            Step::Sql(schema::CREATE_ORG_SETTINGS_TABLE),
+        ],
+    },
+];
+
+impl Migration {
+    /// Empreinte SHA-256 du contenu de la migration
+    pub fn checksum(&self) -> String {
+        let mut hasher = Sha256::new();
+        hasher.update(self.version.to_string());
+        hasher.update(self.name);
+        for step in self.steps {
+            match step {
+                Step::Sql(sql) => {
+                    hasher.update("sql");
+                    hasher.update(sql);
+                }
+                Step::AddColumns(sql) => {
+                    hasher.update("add_columns");
+                    hasher.update(sql);
+                }
+                Step::UnlessColumn { table, column, sql } => {
+                    hasher.update("unless_column");
+                    hasher.update(table);
+                    hasher.update(column);
+                    hasher.update(sql);
+                }
+                Step::NormalizeTime { table, timestamps, dates } => {
+                    hasher.update("normalize_time");
+                    hasher.update(table);
+                    hasher.update(timestamps.join(","));
+                    hasher.update(dates.join(","));
+                }
+            }
+        }
+        hex::encode(hasher.finalize())
+    }
+}
+
+async fn column_exists(conn: &mut SqliteConnection, table: &str, column: &str) -> Result<bool, sqlx::Error> {
+    let count: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM pragma_table_info(?) WHERE name = ?")
+        .bind(table)
+        .bind(column)
+        .fetch_one(&mut *conn)
+        .await?;
+    Ok(count > 0)
+}
+
+async fn run_step(conn: &mut SqliteConnection, step: &Step) -> Result<(), sqlx::Error> {
+    match step {
+        Step::Sql(sql) => {
+            sqlx::query(sql).execute(&mut *conn).await?;
+        }
+        Step::AddColumns(sql) => {
+            for statement in sql.split(';').map(str::trim).filter(|s| !s.is_empty()) {
+                match sqlx::query(statement).execute(&mut *conn).await {
+                    Err(sqlx::Error::Database(e)) if e.message().starts_with("duplicate column name") => {}
+                    result => {
+                        result?;
+                    }
+                }
+            }
+        }
+        Step::UnlessColumn { table, column, sql } => {
+            if !column_exists(conn, table, column).await? {
+                sqlx::query(sql).execute(&mut *conn).await?;
+            }
+        }
+        Step::NormalizeTime { table, timestamps, dates } => {
+            for column in *timestamps {
+                normalize_column(conn, table, column, false, |raw| Timestamp::parse_legacy(raw).map(|ts| ts.to_string()))
+                    .await?;
+            }
+            for column in *dates {
+                normalize_column(conn, table, column, true, |raw| Date::parse_legacy(raw).map(|d| d.to_string())).await?;
+            }
+        }
+    }
+    Ok(())
+}
+
+// Réécrit chaque valeur non canonique d'une colonne texte ; une valeur vide
+// (formulaire sans échéance) devient NULL si la colonne l'admet. Une valeur
+// illisible est remplacée par la création de la ligne, à défaut par
+// l'instant présent, ou par NULL si `unreadable_to_null` et que la colonne
+// l'admet : elle empêcherait sinon la lecture de la ligne à la synchronisation.
+async fn normalize_column(
+    conn: &mut SqliteConnection,
+    table: &str,
+    column: &str,
+    unreadable_to_null: bool,
+    canonical: impl Fn(&str) -> Option<String>,
+) -> Result<(), sqlx::Error> {
+    let not_null: Option<bool> = sqlx::query_scalar("SELECT \"notnull\" FROM pragma_table_info(?) WHERE name = ?")
+        .bind(table)
+        .bind(column)
+        .fetch_optional(&mut *conn)
+        .await?;
+    let Some(not_null) = not_null else {
+        return Ok(());
+    };
+    let created_at = match column_exists(conn, table, "created_at").await? {
+        true => "CAST(created_at AS TEXT)",
+        false => "NULL",
+    };
+    let rows: Vec<(i64, String, Option<String>)> = sqlx::query_as(&format!(
+        "SELECT rowid, CAST({0} AS TEXT), {2} FROM {1} WHERE {0} IS NOT NULL",
+        column, table, created_at
+    ))
+    .fetch_all(&mut *conn)
+    .await?;
+
+    let now = chrono::Utc::now().timestamp_millis().to_string();
+    for (rowid, raw, created_at) in rows {
+        let value = if raw.trim().is_empty() && !not_null {
+            None
+        } else {
+            match canonical(&raw) {
+                Some(value) if value == raw => continue,
+                Some(value) => Some(value),
+                None if unreadable_to_null && !not_null => {
+                    println!("  ⚠️ {}.{} row {}: unreadable value {:?} cleared", table, column, rowid, raw);
+                    None
+                }
+                None => {
+                    let fallback = created_at.as_deref().and_then(&canonical).or_else(|| canonical(&now));
+                    println!(
+                        "  ⚠️ {}.{} row {}: unreadable value {:?} replaced by {:?}",
+                        table, column, rowid, raw, fallback
+                    );
+                    fallback
+                }
+            }
+        };
+        sqlx::query(&format!("UPDATE {} SET {} = ? WHERE rowid = ?", table, column))
+            .bind(value)
+            .bind(rowid)
+            .execute(&mut *conn)
+            .await?;
+    }
+    Ok(())
+}
+
+/// Applique les migrations manquantes, chacune dans sa transaction, et
+/// retourne la version du schéma. Refuse une base créée par une version plus
+/// récente de l'application ou dont une migration appliquée a changé.
+pub async fn run_migrations(pool: &DbPool) -> Result<i64, sqlx::Error> {
+    apply_migrations(pool, MIGRATIONS).await
+}
+
+async fn apply_migrations(pool: &DbPool, migrations: &[Migration]) -> Result<i64, sqlx::Error> {
+    sqlx::query(schema::CREATE_SCHEMA_VERSION_TABLE).execute(pool).await?;
+
+    let applied: Vec<(i64, String, String)> =
+        sqlx::query_as("SELECT version, name, checksum FROM schema_version ORDER BY version")
+            .fetch_all(pool)
+            .await?;
+    let latest = migrations.last().map(|m| m.version).unwrap_or(0);
+
+    if let Some((newest, name, _)) = applied.iter().find(|(version, _, _)| *version > latest) {
+        return Err(sqlx::Error::Configuration(
+            format!(
+                "Database schema version {} ({}) is newer than this application (version {}); please update the application",
+                newest, name, latest
+            )
+            .into(),
+        ));
+    }
+    for (version, name, checksum) in &applied {
+        let migration = migrations.iter().find(|m| m.version == *version);
+        if migration.map(|m| m.checksum()) != Some(checksum.clone()) {
+            return Err(sqlx::Error::Configuration(
+                format!("Migration {} ({}) does not match the one applied to this database", version, name).into(),
+            ));
+        }
+    }
+
+    if applied.is_empty() {
+        let has_tables: i64 = sqlx::query_scalar(
+            "SELECT COUNT(*) FROM sqlite_master WHERE type = 'table' AND name = 'companies'"
+        )
+        .fetch_one(pool)
+        .await?;
+        if has_tables > 0 {
+            println!("  📋 Adopting database created before schema versioning");
+        }
+    }
+
+    for migration in migrations {
+        if applied.iter().any(|(version, _, _)| *version == migration.version) {
+            continue;
+        }
+
+        println!("  📋 Running migration {}: {}", migration.version, migration.name);
+        let mut tx = pool.begin().await?;
+        for step in migration.steps {
+            run_step(&mut tx, step).await.map_err(|e| {
+                eprintln!("❌ Migration failed for {}: {}", migration.name, e);
+                e
+            })?;
+        }
+        sqlx::query("INSERT INTO schema_version (version, name, checksum, applied_at) VALUES (?, ?, ?, ?)")
+            .bind(migration.version)
+            .bind(migration.name)
+            .bind(migration.checksum())
+            .bind(chrono::Utc::now().timestamp_millis().to_string())
+            .execute(&mut *tx)
+            .await?;
+        tx.commit().await?;
+    }
+
+    Ok(latest)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sqlx::sqlite::{SqliteConnectOptions, SqlitePool};
+    use std::collections::BTreeMap;
+    use std::path::PathBuf;
+    use std::str::FromStr;
+
+    // Schémas créés par init_database avant le suivi des versions
+    const LEGACY_FIXTURES: &[&str] = &[
+        include_str!("fixtures/legacy_v01.sql"),
+        include_str!("fixtures/legacy_v02.sql"),
+        include_str!("fixtures/legacy_v03.sql"),
+        include_str!("fixtures/legacy_v04.sql"),
+        include_str!("fixtures/legacy_v05.sql"),
+        include_str!("fixtures/legacy_v06.sql"),
+        include_str!("fixtures/legacy_v07.sql"),
+        include_str!("fixtures/legacy_v08.sql"),
+        include_str!("fixtures/legacy_v09.sql"),
+        include_str!("fixtures/legacy_v10.sql"),
+    ];
+
+    // Données limitées aux colonnes présentes dans toutes les versions
+    const SAMPLE_DATA: &str = "
+INSERT INTO customers (id, name, created_at, updated_at) VALUES ('cu1', 'Client', '1', '1');
+INSERT INTO companies (id, name, city, created_at, updated_at) VALUES ('co1', 'Acme Radio', 'Lyon', '1', '1');
+INSERT INTO company_contacts (id, company_id, first_name, last_name, email, created_at, updated_at)
+    VALUES ('ct1', 'co1', 'Jeanne', 'Martin', 'jeanne@acme.test', '1', '1');
+INSERT INTO proposals (id, company_id, proposal_number, created_at, updated_at) VALUES ('pr1', 'co1', 'P-001', '1', '1');
+INSERT INTO proposal_products (id, proposal_id, product_type, user_count, created_at, updated_at)
+    VALUES ('pp1', 'pr1', 'HTZ Warfare', 2, '1', '1');
+INSERT INTO invoices (id, proposal_id, invoice_number, issue_date, created_at, updated_at)
+    VALUES ('in1', 'pr1', 'F-001', '2024-01-01', '1', '1');
+INSERT INTO documents (id, customer_id, title, document_type, created_at, updated_at)
+    VALUES ('do1', 'cu1', 'Contrat', 'contract', '1', '1');
+";
+
+    async fn open(name: &str) -> (DbPool, PathBuf) {
+        let path = std::env::temp_dir().join(format!("crm-migrations-{}-{}.db", name, uuid::Uuid::new_v4()));
+        let options = SqliteConnectOptions::from_str(&format!("sqlite:{}", path.display()))
+            .unwrap()
+            .create_if_missing(true);
+        (SqlitePool::connect_with(options).await.unwrap(), path)
+    }
+
+    async fn columns(pool: &DbPool) -> BTreeMap<String, Vec<String>> {
+        let tables: Vec<String> = sqlx::query_scalar(
+            "SELECT name FROM sqlite_master WHERE type = 'table' AND name NOT LIKE 'sqlite_%' ORDER BY name"
+        )
+        .fetch_all(pool)
+        .await
+        .unwrap();
+        let mut columns = BTreeMap::new();
+        for table in tables {
+            let mut names: Vec<String> = sqlx::query_scalar("SELECT name FROM pragma_table_info(?)")
+                .bind(&table)
+                .fetch_all(pool)
+                .await
+                .unwrap();
+            names.sort();
+            columns.insert(table, names);
+        }
+        columns
+    }
+
+    async fn latest_columns() -> BTreeMap<String, Vec<String>> {
+        let (pool, path) = open("fresh").await;
+        run_migrations(&pool).await.unwrap();
+        let columns = columns(&pool).await;
+        pool.close().await;
+        let _ = std::fs::remove_file(path);
+        columns
+    }
+
+    // `links_backfilled` : les données précèdent la reprise des rattachements
+    async fn assert_migrated(pool: &DbPool, expected: &BTreeMap<String, Vec<String>>, links_backfilled: bool) {
+        assert_eq!(&columns(pool).await, expected);
+
+        let versions: Vec<i64> = sqlx::query_scalar("SELECT version FROM schema_version ORDER BY version")
+            .fetch_all(pool)
+            .await
+            .unwrap();
+        assert_eq!(versions, MIGRATIONS.iter().map(|m| m.version).collect::<Vec<_>>());
+
+        let rows: (i64, i64, i64, i64, i64) = sqlx::query_as(
+            "SELECT (SELECT COUNT(*) FROM companies WHERE is_deleted = 0),
+                    (SELECT COUNT(*) FROM company_contacts WHERE company_id = 'co1'),
+                    (SELECT COUNT(*) FROM proposal_products WHERE proposal_id = 'pr1'),
+                    (SELECT COUNT(*) FROM invoices WHERE proposal_id = 'pr1'),
+                    (SELECT COUNT(*) FROM documents WHERE customer_id = 'cu1')"
+        )
+        .fetch_one(pool)
+        .await
+        .unwrap();
+        assert_eq!(rows, (1, 1, 1, 1, 1));
+
+        let link: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM document_links WHERE id = 'customer:cu1:do1'")
+            .fetch_one(pool)
+            .await
+            .unwrap();
+        assert_eq!(link, links_backfilled as i64);
+        let indexed: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM search_index WHERE search_index MATCH 'acme' AND entity_type = 'company'")
+            .fetch_one(pool)
+            .await
+            .unwrap();
+        assert_eq!(indexed, 1);
+    }
+
+    #[tokio::test]
+    async fn fresh_database_is_idempotent() {
+        let (pool, path) = open("idempotent").await;
+        assert_eq!(run_migrations(&pool).await.unwrap(), MIGRATIONS.last().unwrap().version);
+        assert_eq!(run_migrations(&pool).await.unwrap(), MIGRATIONS.last().unwrap().version);
+        let count: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM schema_version")
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        assert_eq!(count, MIGRATIONS.len() as i64);
+        pool.close().await;
+        let _ = std::fs::remove_file(path);
+    }
+
+    #[tokio::test]
+    async fn migrates_every_legacy_database() {
+        let expected = latest_columns().await;
+        for (index, fixture) in LEGACY_FIXTURES.iter().enumerate() {
+            let (pool, path) = open(&format!("legacy-{}", index + 1)).await;
+            sqlx::query(fixture).execute(&pool).await.unwrap();
+            sqlx::query(SAMPLE_DATA).execute(&pool).await.unwrap();
+
+            run_migrations(&pool).await.unwrap();
+            assert_migrated(&pool, &expected, true).await;
+            pool.close().await;
+            let _ = std::fs::remove_file(path);
+        }
+    }
+
+    #[tokio::test]
+    async fn migrates_from_every_version() {
+        let expected = latest_columns().await;
+        for count in 1..MIGRATIONS.len() {
+            let (pool, path) = open(&format!("version-{}", count)).await;
+            apply_migrations(&pool, &MIGRATIONS[..count]).await.unwrap();
+            sqlx::query(SAMPLE_DATA).execute(&pool).await.unwrap();
+
+            run_migrations(&pool).await.unwrap();
+            assert_migrated(&pool, &expected, MIGRATIONS[count].version <= 6).await;
+            pool.close().await;
+            let _ = std::fs::remove_file(path);
+        }
+    }
+
+    #[tokio::test]
+    async fn converts_legacy_time_values_faithfully() {
+        let (pool, path) = open("time").await;
+        let before_time = MIGRATIONS.iter().position(|m| m.name == "canonical_time").unwrap();
+        apply_migrations(&pool, &MIGRATIONS[..before_time]).await.unwrap();
+        sqlx::query(
+            "INSERT INTO companies (id, name, created_at, updated_at, version) VALUES
+                ('co1', 'Acme', '2023-11-14T23:13:20.123+01:00', '2023-11-14 22:13:20', 3),
+                ('co2', 'Globex', '1700000000123', 'hier', 1),
+                ('co3', 'Initech', 'jamais', 'hier', 1);
+            INSERT INTO proposals (id, company_id, valid_until, created_at, updated_at)
+                VALUES ('pr1', 'co1', '2024-03-01T00:00:00+01:00', '1', '1');
+            INSERT INTO invoices (id, proposal_id, invoice_number, issue_date, due_date, paid_date, created_at, updated_at)
+                VALUES ('in1', 'pr1', 'F-001', '2024-02-29T00:00:00Z', '', '1709164800000', '1', '1'),
+                       ('in2', 'pr1', 'F-002', '2024-02-29', 'bientôt', NULL, '1', '1');"
+        )
+        .execute(&pool)
+        .await
+        .unwrap();
+
+        let before = chrono::Utc::now().timestamp_millis();
+        run_migrations(&pool).await.unwrap();
+
+        let companies: Vec<(String, String, i64)> =
+            sqlx::query_as("SELECT created_at, updated_at, version FROM companies ORDER BY id")
+                .fetch_all(&pool)
+                .await
+                .unwrap();
+        // Valeur illisible : la création de la ligne, à défaut l'instant présent
+        assert_eq!(
+            companies[..2],
+            [
+                ("1700000000123".to_string(), "1700000000000".to_string(), 3),
+                ("1700000000123".to_string(), "1700000000123".to_string(), 1),
+            ]
+        );
+        let (created_at, updated_at, _) = &companies[2];
+        assert!(created_at.parse::<i64>().unwrap() >= before, "{}", created_at);
+        assert_eq!(updated_at, created_at);
+        let valid_until: String = sqlx::query_scalar("SELECT valid_until FROM proposals WHERE id = 'pr1'")
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        assert_eq!(valid_until, "2024-03-01");
+        let dates: (String, Option<String>, String) =
+            sqlx::query_as("SELECT issue_date, due_date, paid_date FROM invoices WHERE id = 'in1'")
+                .fetch_one(&pool)
+                .await
+                .unwrap();
+        assert_eq!(dates, ("2024-02-29".to_string(), None, "2024-02-29".to_string()));
+        let due_date: Option<String> = sqlx::query_scalar("SELECT due_date FROM invoices WHERE id = 'in2'")
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        assert_eq!(due_date, None);
+        pool.close().await;
+        let _ = std::fs::remove_file(path);
+    }
+
+    #[tokio::test]
+    async fn archive_columns_are_added_one_by_one() {
+        let (pool, path) = open("archive-columns").await;
+        // Base interrompue après l'ajout de la première colonne
+        sqlx::query("CREATE TABLE documents (id TEXT PRIMARY KEY, source_type TEXT)")
+            .execute(&pool)
+            .await
+            .unwrap();
+        let mut conn = pool.acquire().await.unwrap();
+        run_step(&mut conn, &Step::AddColumns(schema::ADD_ARCHIVE_COLUMNS_DOCUMENTS)).await.unwrap();
+        for column in ["source_type", "source_id", "revision", "sha256"] {
+            assert!(column_exists(&mut conn, "documents", column).await.unwrap(), "{}", column);
+        }
+
+        // Toute autre erreur qu'une colonne déjà présente fait échouer l'étape
+        sqlx::query("DROP TABLE documents").execute(&mut *conn).await.unwrap();
+        let error = run_step(&mut conn, &Step::AddColumns(schema::ADD_ARCHIVE_COLUMNS_DOCUMENTS))
+            .await
+            .unwrap_err();
+        assert!(error.to_string().contains("no such table"), "{}", error);
+        drop(conn);
+        pool.close().await;
+        let _ = std::fs::remove_file(path);
+    }
+
+    #[tokio::test]
+    async fn refuses_newer_database() {
+        let (pool, path) = open("downgrade").await;
+        run_migrations(&pool).await.unwrap();
+        let older = &MIGRATIONS[..MIGRATIONS.len() - 1];
+        assert!(apply_migrations(&pool, older).await.is_err());
+        pool.close().await;
+        let _ = std::fs::remove_file(path);
+    }
+
+    #[tokio::test]
+    async fn refuses_modified_migration() {
+        let (pool, path) = open("checksum").await;
+        run_migrations(&pool).await.unwrap();
+        sqlx::query("UPDATE schema_version SET checksum = 'modified' WHERE version = 3")
+            .execute(&pool)
+            .await
+            .unwrap();
+        assert!(run_migrations(&pool).await.is_err());
+        pool.close().await;
+        let _ = std::fs::remove_file(path);
+    }
+}
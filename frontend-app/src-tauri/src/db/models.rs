@@ -1,4 +1,5 @@
 use serde::{Deserialize, Serialize};
+use sqlx::types::Json;
 use sqlx::FromRow;
 
 #[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
@@ -34,6 +35,10 @@ pub struct Company {
     pub updated_at: String,
     pub version: Option<i64>,
     pub is_deleted: Option<i64>,
+    /// Société qui remplace celle-ci après une fusion
+    pub merged_into: Option<String>,
+    /// Poste qui l'a mise à la corbeille
+    pub deleted_by: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
@@ -73,6 +78,7 @@ pub struct Proposal {
     pub updated_at: String,
     pub version: Option<i64>,
     pub is_deleted: Option<i64>,
+    pub deleted_by: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
@@ -118,6 +124,7 @@ pub struct Invoice {
     pub updated_at: String,
     pub version: Option<i64>,
     pub is_deleted: Option<i64>,
+    pub deleted_by: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
@@ -148,6 +155,7 @@ pub struct Customer {
     pub email: Option<String>,
     pub phone: Option<String>,
     pub address: Option<String>,
+    pub notes: Option<String>,
     pub created_at: String,
     pub updated_at: String,
     pub version: Option<i64>,
@@ -157,7 +165,9 @@ pub struct Customer {
 #[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
 pub struct Document {
     pub id: String,
-    pub customer_id: String,
+    /// Client et société des rattachements (`document_links`) à la lecture
+    pub customer_id: Option<String>,
+    pub company_id: Option<String>,
     pub title: String,
     pub document_type: String,
     pub file_path: Option<String>,
@@ -166,6 +176,136 @@ pub struct Document {
     pub updated_at: String,
     pub version: Option<i64>,
     pub is_deleted: Option<i64>,
+    /// Enregistrement d'origine d'un document généré ("proposal" ou "invoice")
+    pub source_type: Option<String>,
+    pub source_id: Option<String>,
+    pub revision: Option<i64>,
+    /// Empreinte du fichier conservé dans l'archive
+    pub sha256: Option<String>,
+    pub deleted_by: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct Activity {
+    pub id: String,
+    pub company_id: String,
+    /// "call", "meeting", "email" ou "note"
+    pub activity_type: String,
+    /// Horodatage en millisecondes
+    pub occurred_at: String,
+    pub duration_minutes: Option<i64>,
+    pub subject: Option<String>,
+    pub notes: Option<String>,
+    pub proposal_id: Option<String>,
+    /// Identifiants des contacts de la société ayant participé
+    pub participants: Json<Vec<String>>,
+    pub created_at: String,
+    pub updated_at: String,
+    pub version: Option<i64>,
+    pub is_deleted: Option<i64>,
+}
+
+/// Entrée de l'historique d'une société : une activité, ou un événement
+/// déduit d'une proposition ou d'une facture. Seuls des codes sont renvoyés,
+/// le libellé est composé et traduit par l'interface.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TimelineEntry {
+    /// "activity", "proposal_created", "invoice_issued" ou "invoice_paid"
+    pub kind: String,
+    /// Horodatage en millisecondes
+    pub occurred_at: i64,
+    /// Numéro de la proposition ou de la facture (identifiant à défaut)
+    pub reference: Option<String>,
+    /// Statut de la proposition ou de la facture
+    pub status: Option<String>,
+    pub proposal_id: Option<String>,
+    pub invoice_id: Option<String>,
+    pub activity: Option<Activity>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct CompanyMerge {
+    pub merged_id: String,
+    pub survivor_id: String,
+    pub merged_name: String,
+    pub contacts_moved: i64,
+    pub proposals_moved: i64,
+    pub documents_moved: i64,
+    pub merged_at: String,
+}
+
+/// Résultat de la recherche plein texte
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct SearchHit {
+    /// "company", "contact", "proposal", "invoice" ou "document"
+    pub entity_type: String,
+    pub entity_id: String,
+    /// Société d'un contact ou d'une proposition, proposition d'une facture
+    pub parent_id: Option<String>,
+    /// Titre avec les termes trouvés entourés de `<mark>`
+    pub title: String,
+    /// Extrait du texte autour des termes trouvés
+    pub snippet: String,
+    /// Score bm25 : plus il est bas, plus le résultat est pertinent
+    pub rank: f64,
+}
+
+/// Enregistrement de la corbeille
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct TrashItem {
+    /// "companies", "proposals", "invoices" ou "documents"
+    pub table_name: String,
+    pub id: String,
+    /// Nom, numéro ou titre
+    pub label: String,
+    /// Société d'une proposition, proposition d'une facture, type de document
+    pub detail: Option<String>,
+    /// Horodatage de la suppression en millisecondes
+    pub deleted_at: i64,
+    /// Poste à l'origine de la suppression
+    pub deleted_by: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct DocumentLink {
+    pub id: String,
+    pub document_id: String,
+    /// "company", "proposal", "invoice" ou "customer"
+    pub entity_type: String,
+    pub entity_id: String,
+    pub created_at: String,
+    pub updated_at: String,
+    pub version: Option<i64>,
+    pub is_deleted: Option<i64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct AttachmentTransfer {
+    pub sha256: String,
+    pub uploaded: i64,
+    pub last_error: Option<String>,
+    pub updated_at: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct DocumentTemplate {
+    pub id: String,
+    pub name: String,
+    /// "proposal" (docx) ou "invoice" (xlsx)
+    pub kind: String,
+    pub product_type: Option<String>,
+    pub language: Option<String>,
+    pub company_id: Option<String>,
+    pub template_version: i64,
+    pub file_name: String,
+    /// Fichier encodé en base64, omis dans les listes
+    pub content: Option<String>,
+    /// SHA-256 du fichier
+    pub checksum: String,
+    pub created_at: String,
+    pub updated_at: String,
+    pub version: Option<i64>,
+    pub is_deleted: Option<i64>,
 }
 
 // SyncQueueItem - REMOVED as part of synchronization cleanup
@@ -179,32 +319,14 @@ pub struct SyncMetadata {
     pub last_sync_timestamp: i64,
     pub last_sync_version: i64,
     pub updated_at: String,
+    pub device_id: Option<String>,
+    pub device_name: Option<String>,
+    /// Heure locale (ms) jusqu'à laquelle les modifications ont été envoyées
+    pub last_push_timestamp: i64,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct SyncItem {
-    pub table_name: String,
-    pub id: String,
-    pub data: serde_json::Value,
-    pub version: i64,
-    pub is_deleted: bool,
-    pub updated_at: i64,
-}
-
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct SyncRequest {
-    pub last_sync_timestamp: i64,
-    pub changes: Vec<SyncItem>,
-}
-
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct SyncResponse {
-    pub success: bool,
-    pub message: String,
-    pub remote_changes: Vec<SyncItem>,
-    pub new_timestamp: i64,
-    pub items_synced: i64,
-}
+// Éléments échangés avec le serveur, définis dans le schéma partagé
+pub use crm_schema::{SyncItem, SyncRequest, SyncResponse};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct UserWithRoles {
@@ -234,4 +356,144 @@ pub struct ProposalWithProducts {
     pub proposal: Proposal,
     pub products: Vec<ProposalProduct>,
     pub company_name: String,
-}
\ No newline at end of file
+}
+
+/// Paramètres des commandes de liste (`list_companies`, `list_proposals`,
+/// `list_invoices`, `list_documents`). Les filtres sans objet pour une liste
+/// sont ignorés.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ListQuery {
+    /// Texte cherché (nom, numéro, notes... selon la liste)
+    pub search: Option<String>,
+    pub status: Option<String>,
+    pub company_id: Option<String>,
+    pub document_type: Option<String>,
+    /// Colonne de tri, parmi celles acceptées par la liste
+    pub sort_by: Option<String>,
+    /// Sens du tri ; par défaut, les plus récents d'abord (ordre alphabétique
+    /// pour les sociétés)
+    pub descending: Option<bool>,
+    /// `next_cursor` de la page précédente : la page reprend après la
+    /// dernière ligne lue, même si des lignes ont été ajoutées entre-temps
+    pub cursor: Option<String>,
+    pub limit: Option<i64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Page<T> {
+    pub items: Vec<T>,
+    /// Nombre total d'enregistrements correspondant aux filtres
+    pub total: i64,
+    /// À passer en `cursor` pour la page suivante, absent en fin de liste
+    pub next_cursor: Option<String>,
+}
+
+/// Enregistrements communs aux tests ; chaque test modifie seulement les
+/// champs qu'il vérifie
+#[cfg(test)]
+pub mod test_fixtures {
+    use super::*;
+
+    const CREATED_AT: &str = "1700000000000";
+
+    pub fn company(id: &str, name: &str) -> Company {
+        Company {
+            id: id.to_string(),
+            name: name.to_string(),
+            website: None,
+            address: None,
+            city: None,
+            postal_code: None,
+            country: None,
+            description: None,
+            created_at: CREATED_AT.to_string(),
+            updated_at: CREATED_AT.to_string(),
+            version: Some(1),
+            is_deleted: Some(0),
+            merged_into: None,
+            deleted_by: None,
+        }
+    }
+
+    pub fn contact(id: &str, company_id: &str) -> CompanyContact {
+        CompanyContact {
+            id: id.to_string(),
+            company_id: company_id.to_string(),
+            first_name: "Ada".to_string(),
+            last_name: "Lovelace".to_string(),
+            email: "ada@example.com".to_string(),
+            phone_number: None,
+            is_primary: 1,
+            created_at: CREATED_AT.to_string(),
+            updated_at: CREATED_AT.to_string(),
+            version: Some(1),
+            is_deleted: Some(0),
+        }
+    }
+
+    pub fn proposal(id: &str, company_id: &str) -> Proposal {
+        Proposal {
+            id: id.to_string(),
+            company_id: company_id.to_string(),
+            proposal_number: None,
+            status: "draft".to_string(),
+            total_amount: 0.0,
+            currency: "EUR".to_string(),
+            valid_until: "2025-08-22".to_string(),
+            notes: None,
+            created_at: CREATED_AT.to_string(),
+            updated_at: CREATED_AT.to_string(),
+            version: Some(1),
+            is_deleted: Some(0),
+            deleted_by: None,
+        }
+    }
+
+    /// Licence seule, sans formation ni support
+    pub fn product(product_type: &str) -> ProposalProduct {
+        ProposalProduct {
+            id: "pp-1".to_string(),
+            proposal_id: "p-1".to_string(),
+            product_type: product_type.to_string(),
+            user_count: 5,
+            standalone_count: 0,
+            server_key_count: 0,
+            unit_price: 10000.0,
+            total_price: 0.0,
+            annual_reduction: 0.0,
+            training: 0,
+            training_days: 0,
+            training_cost_per_day: 0.0,
+            training_cost: 0.0,
+            licence: 1,
+            support: 0,
+            support_years: 0,
+            created_at: CREATED_AT.to_string(),
+            updated_at: CREATED_AT.to_string(),
+            version: Some(1),
+            is_deleted: Some(0),
+        }
+    }
+
+    pub fn invoice(invoice_number: &str) -> InvoiceWithDetails {
+        InvoiceWithDetails {
+            id: "i-1".to_string(),
+            proposal_id: "p-1".to_string(),
+            proposal_number: "P-1".to_string(),
+            company_name: "Acme".to_string(),
+            invoice_number: invoice_number.to_string(),
+            status: "draft".to_string(),
+            total_amount: 0.0,
+            currency: "EUR".to_string(),
+            issue_date: "2025-07-23".to_string(),
+            due_date: None,
+            paid_date: None,
+            purchase_order: None,
+            purchase_order_date: None,
+            commercial_in_charge: None,
+            notes: None,
+            created_at: CREATED_AT.to_string(),
+            updated_at: CREATED_AT.to_string(),
+        }
+    }
+}
@@ -1,6 +1,14 @@
 use super::{models::*, DbPool};
+use crate::numbering;
+use base64::Engine;
 use chrono::{Utc, Datelike};
+use std::collections::HashMap;
 use sqlx;
+use sqlx::sqlite::SqliteRow;
+use sqlx::types::Json;
+use sqlx::{Row, TypeInfo, ValueRef};
+use crm_schema::tables::Company as SyncedCompany;
+use crm_schema::{ColumnType, Dialect, Entity, Record, Value as SchemaValue, ENTITIES};
 use uuid::Uuid;
 
 // Role queries
@@ -144,8 +152,8 @@ pub async fn create_company(
     let now = chrono::Utc::now().timestamp_millis().to_string();
 
     let company = sqlx::query_as::<_, Company>(
-        "INSERT INTO companies (id, name, website, address, city, postal_code, country, description, created_at, updated_at, sync_status) 
-         VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, 'pending') RETURNING *"
+        "INSERT INTO companies (id, name, website, address, city, postal_code, country, description, created_at, updated_at) 
+         VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?) RETURNING *"
     )
     .bind(&id)
     .bind(&name)
@@ -164,7 +172,9 @@ pub async fn create_company(
 }
 
 pub async fn get_all_companies(pool: &DbPool) -> Result<Vec<Company>, sqlx::Error> {
-    let companies = sqlx::query_as::<_, Company>("SELECT * FROM companies ORDER BY name ASC")
+    let companies = sqlx::query_as::<_, Company>(
+        "SELECT * FROM companies WHERE COALESCE(is_deleted, 0) = 0 ORDER BY name ASC"
+    )
         .fetch_all(pool)
         .await?;
     Ok(companies)
@@ -179,7 +189,7 @@ pub async fn get_company_by_id(pool: &DbPool, id: &str) -> Result<Option<Company
 }
 
 pub async fn update_company(
-    pool: &DbPool,
+    conn: &mut sqlx::SqliteConnection,
     id: &str,
     name: String,
     website: Option<String>,
@@ -193,7 +203,7 @@ pub async fn update_company(
 
     let company = sqlx::query_as::<_, Company>(
         "UPDATE companies SET name = ?, website = ?, address = ?, city = ?, postal_code = ?, country = ?, description = ?, 
-         updated_at = ?, sync_status = 'pending' WHERE id = ? RETURNING *"
+         updated_at = ?, version = COALESCE(version, 0) + 1 WHERE id = ? RETURNING *"
     )
     .bind(&name)
     .bind(&website)
@@ -204,27 +214,625 @@ pub async fn update_company(
     .bind(&description)
     .bind(&now)
     .bind(id)
-    .fetch_one(pool)
+    .fetch_one(&mut *conn)
     .await?;
 
     Ok(company)
 }
 
-pub async fn delete_company(pool: &DbPool, id: &str) -> Result<(), sqlx::Error> {
-    // Supprimer en local immédiatement
-    sqlx::query("DELETE FROM company_contacts WHERE company_id = ?")
+// Activity queries
+
+pub async fn create_activity(
+    pool: &DbPool,
+    company_id: &str,
+    activity_type: &str,
+    occurred_at: i64,
+    duration_minutes: Option<i64>,
+    subject: Option<String>,
+    notes: Option<String>,
+    proposal_id: Option<String>,
+    participants: Vec<String>,
+) -> Result<Activity, sqlx::Error> {
+    let now = chrono::Utc::now().timestamp_millis().to_string();
+    sqlx::query_as::<_, Activity>(
+        "INSERT INTO activities (id, company_id, activity_type, occurred_at, duration_minutes, subject, notes, proposal_id, participants, created_at, updated_at, version, is_deleted)
+         VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, 1, 0) RETURNING *"
+    )
+    .bind(Uuid::new_v4().to_string())
+    .bind(company_id)
+    .bind(activity_type)
+    .bind(occurred_at.to_string())
+    .bind(duration_minutes)
+    .bind(subject)
+    .bind(notes)
+    .bind(proposal_id)
+    .bind(Json(participants))
+    .bind(&now)
+    .bind(&now)
+    .fetch_one(pool)
+    .await
+}
+
+pub async fn update_activity(
+    pool: &DbPool,
+    id: &str,
+    activity_type: &str,
+    occurred_at: i64,
+    duration_minutes: Option<i64>,
+    subject: Option<String>,
+    notes: Option<String>,
+    proposal_id: Option<String>,
+    participants: Vec<String>,
+) -> Result<Option<Activity>, sqlx::Error> {
+    let now = chrono::Utc::now().timestamp_millis().to_string();
+    sqlx::query_as::<_, Activity>(
+        "UPDATE activities SET activity_type = ?, occurred_at = ?, duration_minutes = ?, subject = ?, notes = ?,
+         proposal_id = ?, participants = ?, updated_at = ?, version = COALESCE(version, 0) + 1
+         WHERE id = ? AND COALESCE(is_deleted, 0) = 0 RETURNING *"
+    )
+    .bind(activity_type)
+    .bind(occurred_at.to_string())
+    .bind(duration_minutes)
+    .bind(subject)
+    .bind(notes)
+    .bind(proposal_id)
+    .bind(Json(participants))
+    .bind(&now)
+    .bind(id)
+    .fetch_optional(pool)
+    .await
+}
+
+pub async fn get_activity_by_id(pool: &DbPool, id: &str) -> Result<Option<Activity>, sqlx::Error> {
+    sqlx::query_as::<_, Activity>("SELECT * FROM activities WHERE id = ? AND COALESCE(is_deleted, 0) = 0")
         .bind(id)
-        .execute(pool)
+        .fetch_optional(pool)
+        .await
+}
+
+/// Activités d'une société, éventuellement limitées à une proposition, les
+/// plus récentes d'abord
+pub async fn get_activities(
+    pool: &DbPool,
+    company_id: &str,
+    proposal_id: Option<&str>,
+) -> Result<Vec<Activity>, sqlx::Error> {
+    sqlx::query_as::<_, Activity>(
+        "SELECT * FROM activities WHERE company_id = ? AND (? IS NULL OR proposal_id = ?) AND COALESCE(is_deleted, 0) = 0
+         ORDER BY CAST(occurred_at AS INTEGER) DESC"
+    )
+    .bind(company_id)
+    .bind(proposal_id)
+    .bind(proposal_id)
+    .fetch_all(pool)
+    .await
+}
+
+// Trash queries
+
+/// Tables de la corbeille, avec les enregistrements dépendants supprimés et
+/// restaurés en même temps (table, colonne de rattachement)
+const TRASH_TABLES: &[(&str, &[(&str, &str)])] = &[
+    ("companies", &[("company_contacts", "company_id"), ("activities", "company_id")]),
+    ("proposals", &[("proposal_products", "proposal_id")]),
+    ("invoices", &[]),
+    ("documents", &[("document_links", "document_id")]),
+];
+
+/// Enregistrement parent à restaurer d'abord (table, colonne, table parente)
+const TRASH_PARENTS: &[(&str, &str, &str)] = &[
+    ("proposals", "company_id", "companies"),
+    ("invoices", "proposal_id", "proposals"),
+];
+
+/// Ordre de purge : les tables dépendantes d'abord, puis chaque table parente
+/// une fois vidée des lignes qui la référencent (clés étrangères en cascade)
+const PURGE_TABLES: &[(&str, &[(&str, &str)])] = &[
+    ("company_contacts", &[]),
+    ("proposal_products", &[]),
+    ("invoices", &[]),
+    ("document_links", &[]),
+    ("activities", &[]),
+    ("document_templates", &[]),
+    ("documents", &[]),
+    ("proposals", &[("proposal_products", "proposal_id"), ("invoices", "proposal_id")]),
+    ("customers", &[("documents", "customer_id")]),
+    ("companies", &[("company_contacts", "company_id"), ("proposals", "company_id"), ("documents", "company_id")]),
+];
+
+fn trash_children(table_name: &str) -> Result<&'static [(&'static str, &'static str)], sqlx::Error> {
+    TRASH_TABLES
+        .iter()
+        .find(|(table, _)| *table == table_name)
+        .map(|(_, children)| *children)
+        .ok_or_else(|| sqlx::Error::Protocol(format!("Unknown table: {}", table_name)))
+}
+
+/// Met un enregistrement et ses dépendants à la corbeille. Retourne `false`
+/// s'il n'existe pas ou s'y trouve déjà.
+pub async fn move_to_trash(pool: &DbPool, table_name: &str, id: &str, deleted_by: Option<&str>) -> Result<bool, sqlx::Error> {
+    let children = trash_children(table_name)?;
+    let now = chrono::Utc::now().timestamp_millis().to_string();
+    let mut tx = pool.begin().await?;
+
+    let trashed = sqlx::query(&format!(
+        "UPDATE {} SET is_deleted = 1, deleted_by = ?, updated_at = ?, version = COALESCE(version, 0) + 1
+         WHERE id = ? AND COALESCE(is_deleted, 0) = 0",
+        table_name
+    ))
+    .bind(deleted_by)
+    .bind(&now)
+    .bind(id)
+    .execute(&mut *tx)
+    .await?
+    .rows_affected();
+    if trashed == 0 {
+        return Ok(false);
+    }
+
+    for (child, column) in children {
+        sqlx::query(&format!(
+            "UPDATE {} SET is_deleted = 1, updated_at = ?, version = COALESCE(version, 0) + 1
+             WHERE {} = ? AND COALESCE(is_deleted, 0) = 0",
+            child, column
+        ))
+        .bind(&now)
+        .bind(id)
+        .execute(&mut *tx)
         .await?;
-    
-    sqlx::query("DELETE FROM companies WHERE id = ?")
+    }
+
+    tx.commit().await?;
+    Ok(true)
+}
+
+/// Sort un enregistrement de la corbeille avec les dépendants supprimés en
+/// même temps que lui (ou après), et retourne le nombre de dépendants
+/// restaurés. Une proposition ou une facture dont le parent est lui-même à
+/// la corbeille n'est pas restaurée.
+pub async fn restore_from_trash(pool: &DbPool, table_name: &str, id: &str) -> Result<u64, sqlx::Error> {
+    let children = trash_children(table_name)?;
+    let mut tx = pool.begin().await?;
+
+    // Les sociétés absorbées par une fusion ne sont pas dans la corbeille
+    let merged_filter = if table_name == "companies" { " AND merged_into IS NULL" } else { "" };
+    let deleted_at: i64 = sqlx::query_scalar(&format!(
+        "SELECT CAST(updated_at AS INTEGER) FROM {} WHERE id = ? AND is_deleted = 1{}",
+        table_name, merged_filter
+    ))
+    .bind(id)
+    .fetch_optional(&mut *tx)
+    .await?
+    .ok_or(sqlx::Error::RowNotFound)?;
+
+    if let Some((_, column, parent)) = TRASH_PARENTS.iter().find(|(table, _, _)| *table == table_name) {
+        let parent_live: i64 = sqlx::query_scalar(&format!(
+            "SELECT COUNT(*) FROM {parent} WHERE COALESCE(is_deleted, 0) = 0
+             AND id = (SELECT {column} FROM {table_name} WHERE id = ?)"
+        ))
         .bind(id)
-        .execute(pool)
+        .fetch_one(&mut *tx)
         .await?;
-    
-    // Enregistrer l'action pour la synchronisation
-    
-    Ok(())
+        if parent_live == 0 {
+            return Err(sqlx::Error::Protocol(format!("Parent record in {} must be restored first", parent)));
+        }
+    }
+
+    let now = chrono::Utc::now().timestamp_millis().to_string();
+    sqlx::query(&format!(
+        "UPDATE {} SET is_deleted = 0, deleted_by = NULL, updated_at = ?, version = COALESCE(version, 0) + 1 WHERE id = ?",
+        table_name
+    ))
+    .bind(&now)
+    .bind(id)
+    .execute(&mut *tx)
+    .await?;
+
+    let mut restored = 0;
+    for (child, column) in children {
+        restored += sqlx::query(&format!(
+            "UPDATE {} SET is_deleted = 0, updated_at = ?, version = COALESCE(version, 0) + 1
+             WHERE {} = ? AND is_deleted = 1 AND CAST(updated_at AS INTEGER) >= ?",
+            child, column
+        ))
+        .bind(&now)
+        .bind(id)
+        .bind(deleted_at)
+        .execute(&mut *tx)
+        .await?
+        .rows_affected();
+    }
+
+    tx.commit().await?;
+    Ok(restored)
+}
+
+/// Contenu de la corbeille, les suppressions les plus récentes d'abord
+pub async fn get_trash(pool: &DbPool) -> Result<Vec<TrashItem>, sqlx::Error> {
+    sqlx::query_as::<_, TrashItem>(
+        "SELECT 'companies' AS table_name, id, name AS label, NULL AS detail,
+                CAST(updated_at AS INTEGER) AS deleted_at, deleted_by
+         FROM companies WHERE is_deleted = 1 AND merged_into IS NULL
+         UNION ALL
+         SELECT 'proposals', p.id, COALESCE(p.proposal_number, p.id), c.name,
+                CAST(p.updated_at AS INTEGER), p.deleted_by
+         FROM proposals p LEFT JOIN companies c ON c.id = p.company_id WHERE p.is_deleted = 1
+         UNION ALL
+         SELECT 'invoices', i.id, i.invoice_number, p.proposal_number,
+                CAST(i.updated_at AS INTEGER), i.deleted_by
+         FROM invoices i LEFT JOIN proposals p ON p.id = i.proposal_id WHERE i.is_deleted = 1
+         UNION ALL
+         SELECT 'documents', id, title, document_type,
+                CAST(updated_at AS INTEGER), deleted_by
+         FROM documents WHERE is_deleted = 1
+         ORDER BY deleted_at DESC"
+    )
+    .fetch_all(pool)
+    .await
+}
+
+/// Supprime définitivement les enregistrements supprimés avant `before`
+/// (millisecondes). Les sociétés absorbées par une fusion sont conservées
+/// pour la redirection, comme les parents encore référencés, et les
+/// suppressions refusées par le serveur, qui doivent lui être renvoyées.
+pub async fn purge_tombstones(pool: &DbPool, before: i64) -> Result<u64, sqlx::Error> {
+    let mut tx = pool.begin().await?;
+    let mut purged = 0;
+    for (table, children) in PURGE_TABLES {
+        let mut sql = format!(
+            "DELETE FROM {table} WHERE is_deleted = 1 AND CAST(updated_at AS INTEGER) < ?
+             AND id NOT IN (SELECT record_id FROM sync_rejections WHERE table_name = ?)"
+        );
+        if *table == "companies" {
+            sql.push_str(" AND merged_into IS NULL");
+        }
+        for (child, column) in children.iter() {
+            sql.push_str(&format!(
+                " AND NOT EXISTS (SELECT 1 FROM {child} WHERE {child}.{column} = {table}.id)"
+            ));
+        }
+        purged += sqlx::query(&sql).bind(before).bind(table).execute(&mut *tx).await?.rows_affected();
+    }
+    tx.commit().await?;
+    Ok(purged)
+}
+
+// Company merge queries
+
+/// Enregistrements déplacés d'une société absorbée vers celle qui la remplace
+#[derive(Debug, Default)]
+pub struct MergeCounts {
+    pub contacts: i64,
+    pub proposals: i64,
+    pub documents: i64,
+    pub activities: i64,
+}
+
+/// Société qui remplace finalement `id`, en suivant les fusions successives
+/// (A absorbée par B, puis B par C).
+pub async fn resolve_merged_company(
+    conn: &mut sqlx::SqliteConnection,
+    id: &str,
+) -> Result<String, sqlx::Error> {
+    let mut current = id.to_string();
+    // Borne de sécurité contre une boucle de redirections
+    for _ in 0..16 {
+        let next: Option<String> = sqlx::query_scalar("SELECT merged_into FROM companies WHERE id = ?")
+            .bind(&current)
+            .fetch_optional(&mut *conn)
+            .await?
+            .flatten();
+        match next {
+            Some(next) if next != current => current = next,
+            _ => break,
+        }
+    }
+    Ok(current)
+}
+
+/// Rattache à `survivor_id` tout ce qui dépend de `merged_id` : contacts
+/// (sauf ceux dont l'e-mail existe déjà chez le survivant, supprimés),
+/// propositions et donc leurs factures, documents, modèles et rattachements
+/// de documents. Chaque ligne modifiée est datée de `now` pour être
+/// synchronisée.
+pub async fn redirect_company(
+    conn: &mut sqlx::SqliteConnection,
+    merged_id: &str,
+    survivor_id: &str,
+    now: &str,
+) -> Result<MergeCounts, sqlx::Error> {
+    sqlx::query(
+        "UPDATE company_contacts SET is_deleted = 1, updated_at = ?, version = COALESCE(version, 0) + 1
+         WHERE company_id = ? AND COALESCE(is_deleted, 0) = 0 AND email != ''
+         AND lower(email) IN (SELECT lower(email) FROM company_contacts WHERE company_id = ? AND COALESCE(is_deleted, 0) = 0)"
+    )
+    .bind(now)
+    .bind(merged_id)
+    .bind(survivor_id)
+    .execute(&mut *conn)
+    .await?;
+
+    // Un seul contact principal : celui du survivant
+    sqlx::query(
+        "UPDATE company_contacts SET is_primary = 0 WHERE company_id = ?
+         AND EXISTS (SELECT 1 FROM company_contacts WHERE company_id = ? AND is_primary = 1 AND COALESCE(is_deleted, 0) = 0)"
+    )
+    .bind(merged_id)
+    .bind(survivor_id)
+    .execute(&mut *conn)
+    .await?;
+
+    let mut counts = MergeCounts::default();
+    let moves = [
+        ("company_contacts", &mut counts.contacts),
+        ("proposals", &mut counts.proposals),
+        ("documents", &mut counts.documents),
+        ("activities", &mut counts.activities),
+    ];
+    for (table, count) in moves {
+        *count = sqlx::query(&format!(
+            "UPDATE {} SET company_id = ?, updated_at = ?, version = COALESCE(version, 0) + 1 WHERE company_id = ?",
+            table
+        ))
+        .bind(survivor_id)
+        .bind(now)
+        .bind(merged_id)
+        .execute(&mut *conn)
+        .await?
+        .rows_affected() as i64;
+    }
+
+    sqlx::query(
+        "UPDATE document_templates SET company_id = ?, updated_at = ?, version = COALESCE(version, 0) + 1 WHERE company_id = ?"
+    )
+    .bind(survivor_id)
+    .bind(now)
+    .bind(merged_id)
+    .execute(&mut *conn)
+    .await?;
+
+    // L'identifiant d'un rattachement contient la société : nouveau lien
+    // vers le survivant, l'ancien est supprimé
+    sqlx::query(
+        "INSERT INTO document_links (id, document_id, entity_type, entity_id, created_at, updated_at, version, is_deleted)
+         SELECT 'company:' || ? || ':' || document_id, document_id, 'company', ?, created_at, ?, 1, 0
+         FROM document_links WHERE entity_type = 'company' AND entity_id = ? AND COALESCE(is_deleted, 0) = 0
+         ON CONFLICT(id) DO UPDATE SET is_deleted = 0, updated_at = excluded.updated_at, version = COALESCE(version, 0) + 1"
+    )
+    .bind(survivor_id)
+    .bind(survivor_id)
+    .bind(now)
+    .bind(merged_id)
+    .execute(&mut *conn)
+    .await?;
+    sqlx::query(
+        "UPDATE document_links SET is_deleted = 1, updated_at = ?, version = COALESCE(version, 0) + 1
+         WHERE entity_type = 'company' AND entity_id = ? AND COALESCE(is_deleted, 0) = 0"
+    )
+    .bind(now)
+    .bind(merged_id)
+    .execute(&mut *conn)
+    .await?;
+
+    Ok(counts)
+}
+
+async fn record_company_merge(
+    conn: &mut sqlx::SqliteConnection,
+    merged_id: &str,
+    survivor_id: &str,
+    merged_name: &str,
+    counts: &MergeCounts,
+    now: &str,
+) -> Result<CompanyMerge, sqlx::Error> {
+    sqlx::query_as::<_, CompanyMerge>(
+        "INSERT INTO company_merges (merged_id, survivor_id, merged_name, contacts_moved, proposals_moved, documents_moved, merged_at)
+         VALUES (?, ?, ?, ?, ?, ?, ?)
+         ON CONFLICT(merged_id) DO UPDATE SET survivor_id = excluded.survivor_id
+         RETURNING *"
+    )
+    .bind(merged_id)
+    .bind(survivor_id)
+    .bind(merged_name)
+    .bind(counts.contacts)
+    .bind(counts.proposals)
+    .bind(counts.documents)
+    .bind(now)
+    .fetch_one(&mut *conn)
+    .await
+}
+
+/// Fusionne `merged_id` dans `survivor_id` : les champs vides du survivant
+/// sont complétés, les enregistrements liés déplacés, et la société absorbée
+/// devient une suppression redirigée vers le survivant.
+pub async fn merge_companies(pool: &DbPool, survivor_id: &str, merged_id: &str) -> Result<CompanyMerge, sqlx::Error> {
+    let now = chrono::Utc::now().timestamp_millis().to_string();
+    let mut tx = pool.begin().await?;
+
+    let merged = sqlx::query_as::<_, Company>("SELECT * FROM companies WHERE id = ? AND COALESCE(is_deleted, 0) = 0")
+        .bind(merged_id)
+        .fetch_optional(&mut *tx)
+        .await?
+        .ok_or(sqlx::Error::RowNotFound)?;
+
+    sqlx::query(
+        "UPDATE companies SET
+         website = COALESCE(NULLIF(website, ''), ?),
+         address = COALESCE(NULLIF(address, ''), ?),
+         city = COALESCE(NULLIF(city, ''), ?),
+         postal_code = COALESCE(NULLIF(postal_code, ''), ?),
+         country = COALESCE(NULLIF(country, ''), ?),
+         description = COALESCE(NULLIF(description, ''), ?),
+         updated_at = ?, version = COALESCE(version, 0) + 1
+         WHERE id = ? AND COALESCE(is_deleted, 0) = 0"
+    )
+    .bind(&merged.website)
+    .bind(&merged.address)
+    .bind(&merged.city)
+    .bind(&merged.postal_code)
+    .bind(&merged.country)
+    .bind(&merged.description)
+    .bind(&now)
+    .bind(survivor_id)
+    .execute(&mut *tx)
+    .await
+    .and_then(|result| match result.rows_affected() {
+        0 => Err(sqlx::Error::RowNotFound),
+        _ => Ok(()),
+    })?;
+
+    let counts = redirect_company(&mut tx, merged_id, survivor_id, &now).await?;
+
+    sqlx::query(
+        "UPDATE companies SET is_deleted = 1, merged_into = ?, updated_at = ?, version = COALESCE(version, 0) + 1 WHERE id = ?"
+    )
+    .bind(survivor_id)
+    .bind(&now)
+    .bind(merged_id)
+    .execute(&mut *tx)
+    .await?;
+    // Les sociétés déjà absorbées par celle-ci suivent
+    sqlx::query("UPDATE companies SET merged_into = ? WHERE merged_into = ?")
+        .bind(survivor_id)
+        .bind(merged_id)
+        .execute(&mut *tx)
+        .await?;
+
+    let merge = record_company_merge(&mut tx, merged_id, survivor_id, &merged.name, &counts, &now).await?;
+    tx.commit().await?;
+    Ok(merge)
+}
+
+/// Applique une fusion reçue par la synchronisation : les enregistrements
+/// locaux, y compris ceux créés hors ligne, suivent la redirection avant que
+/// la société absorbée ne soit enregistrée comme supprimée.
+async fn apply_company_merge(pool: &DbPool, company: &SyncedCompany, merged_into: &str) -> Result<(), sqlx::Error> {
+    let now = chrono::Utc::now().timestamp_millis().to_string();
+    let mut tx = pool.begin().await?;
+
+    let survivor_id = resolve_merged_company(&mut tx, merged_into).await?;
+    let counts = redirect_company(&mut tx, &company.id, &survivor_id, &now).await?;
+
+    sqlx::query(
+        "INSERT INTO companies (id, name, website, address, city, postal_code, country, description, created_at, updated_at, version, is_deleted, merged_into)
+         VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, 1, ?)
+         ON CONFLICT(id) DO UPDATE SET is_deleted = 1, merged_into = excluded.merged_into,
+         updated_at = excluded.updated_at, version = excluded.version"
+    )
+    .bind(&company.id)
+    .bind(&company.name)
+    .bind(&company.website)
+    .bind(&company.address)
+    .bind(&company.city)
+    .bind(&company.postal_code)
+    .bind(&company.country)
+    .bind(&company.description)
+    .bind(company.created_at.to_string())
+    .bind(company.updated_at.to_string())
+    .bind(company.version)
+    .bind(&survivor_id)
+    .execute(&mut *tx)
+    .await?;
+
+    record_company_merge(&mut tx, &company.id, &survivor_id, &company.name, &counts, &now).await?;
+    tx.commit().await
+}
+
+pub async fn get_company_merges(pool: &DbPool) -> Result<Vec<CompanyMerge>, sqlx::Error> {
+    sqlx::query_as::<_, CompanyMerge>("SELECT * FROM company_merges ORDER BY merged_at DESC")
+        .fetch_all(pool)
+        .await
+}
+
+// Import queries
+
+/// Toutes les sociétés, supprimées comprises : un import ne doit pas recréer
+/// un nom déjà pris (UNIQUE) par une société à la corbeille ou fusionnée
+pub async fn get_all_companies_with_deleted(pool: &DbPool) -> Result<Vec<Company>, sqlx::Error> {
+    sqlx::query_as::<_, Company>("SELECT * FROM companies ORDER BY name ASC")
+        .fetch_all(pool)
+        .await
+}
+
+/// Sort de la corbeille une société reprise par un import (sans effet sur
+/// une société active ou fusionnée)
+pub async fn restore_imported_company(conn: &mut sqlx::SqliteConnection, id: &str) -> Result<bool, sqlx::Error> {
+    let now = chrono::Utc::now().timestamp_millis().to_string();
+    let result = sqlx::query(
+        "UPDATE companies SET is_deleted = 0, deleted_by = NULL, updated_at = ?, version = COALESCE(version, 0) + 1
+         WHERE id = ? AND is_deleted = 1 AND merged_into IS NULL"
+    )
+    .bind(&now)
+    .bind(id)
+    .execute(&mut *conn)
+    .await?;
+    Ok(result.rows_affected() > 0)
+}
+
+/// Société créée par un import, datée pour la synchronisation
+pub async fn insert_imported_company(
+    conn: &mut sqlx::SqliteConnection,
+    name: &str,
+    website: Option<&str>,
+    address: Option<&str>,
+    city: Option<&str>,
+    postal_code: Option<&str>,
+    country: Option<&str>,
+    description: Option<&str>,
+) -> Result<Company, sqlx::Error> {
+    let now = chrono::Utc::now().timestamp_millis().to_string();
+    sqlx::query_as::<_, Company>(
+        "INSERT INTO companies (id, name, website, address, city, postal_code, country, description, created_at, updated_at, version, is_deleted)
+         VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, 1, 0) RETURNING *"
+    )
+    .bind(Uuid::new_v4().to_string())
+    .bind(name)
+    .bind(website)
+    .bind(address)
+    .bind(city)
+    .bind(postal_code)
+    .bind(country)
+    .bind(description)
+    .bind(&now)
+    .bind(&now)
+    .fetch_one(&mut *conn)
+    .await
+}
+
+pub async fn insert_imported_contact(
+    conn: &mut sqlx::SqliteConnection,
+    company_id: &str,
+    first_name: &str,
+    last_name: &str,
+    email: &str,
+    phone_number: Option<&str>,
+    is_primary: bool,
+) -> Result<CompanyContact, sqlx::Error> {
+    let now = chrono::Utc::now().timestamp_millis().to_string();
+    sqlx::query_as::<_, CompanyContact>(
+        "INSERT INTO company_contacts (id, company_id, first_name, last_name, email, phone_number, is_primary, created_at, updated_at, version, is_deleted)
+         VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, 1, 0) RETURNING *"
+    )
+    .bind(Uuid::new_v4().to_string())
+    .bind(company_id)
+    .bind(first_name)
+    .bind(last_name)
+    .bind(email)
+    .bind(phone_number)
+    .bind(is_primary as i64)
+    .bind(&now)
+    .bind(&now)
+    .fetch_one(&mut *conn)
+    .await
+}
+
+/// E-mails (en minuscules) des contacts existants
+pub async fn get_contact_emails(pool: &DbPool) -> Result<Vec<String>, sqlx::Error> {
+    sqlx::query_scalar(
+        "SELECT DISTINCT lower(email) FROM company_contacts WHERE email != '' AND COALESCE(is_deleted, 0) = 0"
+    )
+    .fetch_all(pool)
+    .await
 }
 
 // Company Contact queries
@@ -272,21 +880,70 @@ pub async fn get_company_contact_by_id(pool: &DbPool, contact_id: &str) -> Resul
 }
 
 pub async fn get_company_contacts(pool: &DbPool, company_id: &str) -> Result<Vec<CompanyContact>, sqlx::Error> {
-    let contacts = sqlx::query_as::<_, CompanyContact>(
-        "SELECT * FROM company_contacts WHERE company_id = ? ORDER BY is_primary DESC, first_name ASC"
+    get_contacts_for_companies(pool, &[company_id.to_string()]).await
+}
+
+/// Met à jour le contact `contact.id` s'il appartient encore à la société,
+/// sinon le crée avec un nouvel identifiant.
+pub async fn save_company_contact(
+    conn: &mut sqlx::SqliteConnection,
+    contact: &CompanyContact,
+    now: &str,
+) -> Result<CompanyContact, sqlx::Error> {
+    let updated = sqlx::query_as::<_, CompanyContact>(
+        "UPDATE company_contacts SET first_name = ?, last_name = ?, email = ?, phone_number = ?, is_primary = ?,
+         updated_at = ?, version = COALESCE(version, 0) + 1
+         WHERE id = ? AND company_id = ? AND COALESCE(is_deleted, 0) = 0 RETURNING *"
     )
-    .bind(company_id)
-    .fetch_all(pool)
+    .bind(&contact.first_name)
+    .bind(&contact.last_name)
+    .bind(&contact.email)
+    .bind(&contact.phone_number)
+    .bind(contact.is_primary)
+    .bind(now)
+    .bind(&contact.id)
+    .bind(&contact.company_id)
+    .fetch_optional(&mut *conn)
     .await?;
-    Ok(contacts)
+    if let Some(updated) = updated {
+        return Ok(updated);
+    }
+
+    sqlx::query_as::<_, CompanyContact>(
+        "INSERT INTO company_contacts (id, company_id, first_name, last_name, email, phone_number, is_primary, created_at, updated_at, version, is_deleted)
+         VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, 1, 0) RETURNING *"
+    )
+    .bind(Uuid::new_v4().to_string())
+    .bind(&contact.company_id)
+    .bind(&contact.first_name)
+    .bind(&contact.last_name)
+    .bind(&contact.email)
+    .bind(&contact.phone_number)
+    .bind(contact.is_primary)
+    .bind(now)
+    .bind(now)
+    .fetch_one(&mut *conn)
+    .await
 }
 
-pub async fn delete_company_contacts(pool: &DbPool, company_id: &str) -> Result<(), sqlx::Error> {
-    sqlx::query("DELETE FROM company_contacts WHERE company_id = ?")
-        .bind(company_id)
-        .execute(pool)
-        .await?;
-    Ok(())
+/// Marque supprimés les contacts de la société absents de `keep_ids`, pour
+/// que la suppression soit synchronisée.
+pub async fn delete_company_contacts_except(
+    conn: &mut sqlx::SqliteConnection,
+    company_id: &str,
+    keep_ids: &[String],
+    now: &str,
+) -> Result<u64, sqlx::Error> {
+    let sql = format!(
+        "UPDATE company_contacts SET is_deleted = 1, updated_at = ?, version = COALESCE(version, 0) + 1
+         WHERE company_id = ? AND COALESCE(is_deleted, 0) = 0 AND id NOT IN ({})",
+        placeholders(keep_ids.len())
+    );
+    let mut statement = sqlx::query(&sql).bind(now).bind(company_id);
+    for id in keep_ids {
+        statement = statement.bind(id);
+    }
+    Ok(statement.execute(&mut *conn).await?.rows_affected())
 }
 
 // Pricing queries
@@ -385,8 +1042,8 @@ pub async fn create_proposal(
     let now = chrono::Utc::now().timestamp_millis().to_string();
 
     let proposal = sqlx::query_as::<_, Proposal>(
-        "INSERT INTO proposals (id, company_id, status, currency, valid_until, notes, created_at, updated_at, sync_status, total_amount) 
-         VALUES (?, ?, ?, ?, ?, ?, ?, ?, 'pending', 0) RETURNING *"
+        "INSERT INTO proposals (id, company_id, status, currency, valid_until, notes, created_at, updated_at, total_amount) 
+         VALUES (?, ?, ?, ?, ?, ?, ?, ?, 0) RETURNING *"
     )
     .bind(&id)
     .bind(&company_id)
@@ -404,13 +1061,22 @@ pub async fn create_proposal(
 
 pub async fn get_all_proposals(pool: &DbPool) -> Result<Vec<Proposal>, sqlx::Error> {
     let proposals = sqlx::query_as::<_, Proposal>(
-        "SELECT * FROM proposals ORDER BY created_at DESC"
+        "SELECT * FROM proposals WHERE COALESCE(is_deleted, 0) = 0 ORDER BY created_at DESC"
     )
     .fetch_all(pool)
     .await?;
     Ok(proposals)
 }
 
+pub async fn get_proposals_by_company(pool: &DbPool, company_id: &str) -> Result<Vec<Proposal>, sqlx::Error> {
+    sqlx::query_as::<_, Proposal>(
+        "SELECT * FROM proposals WHERE company_id = ? AND COALESCE(is_deleted, 0) = 0 ORDER BY created_at DESC"
+    )
+    .bind(company_id)
+    .fetch_all(pool)
+    .await
+}
+
 pub async fn get_proposal_by_id(pool: &DbPool, id: &str) -> Result<Option<Proposal>, sqlx::Error> {
     let proposal = sqlx::query_as::<_, Proposal>("SELECT * FROM proposals WHERE id = ?")
         .bind(id)
@@ -419,23 +1085,6 @@ pub async fn get_proposal_by_id(pool: &DbPool, id: &str) -> Result<Option<Propos
     Ok(proposal)
 }
 
-pub async fn delete_proposal(pool: &DbPool, id: &str) -> Result<(), sqlx::Error> {
-    // Supprimer en local immédiatement
-    sqlx::query("DELETE FROM proposal_products WHERE proposal_id = ?")
-        .bind(id)
-        .execute(pool)
-        .await?;
-    
-    sqlx::query("DELETE FROM proposals WHERE id = ?")
-        .bind(id)
-        .execute(pool)
-        .await?;
-    
-    // Enregistrer l'action pour la synchronisation
-    
-    Ok(())
-}
-
 pub async fn create_proposal_product(
     pool: &DbPool,
     proposal_id: String,
@@ -546,21 +1195,92 @@ pub async fn update_proposal_product(
 }
 
 pub async fn get_proposal_products(pool: &DbPool, proposal_id: &str) -> Result<Vec<ProposalProduct>, sqlx::Error> {
-    let products = sqlx::query_as::<_, ProposalProduct>(
-        "SELECT * FROM proposal_products WHERE proposal_id = ?"
+    get_products_for_proposals(pool, &[proposal_id.to_string()]).await
+}
+
+/// Met à jour le produit `product.id` s'il fait encore partie de la
+/// proposition, sinon le crée avec un nouvel identifiant.
+pub async fn save_proposal_product(
+    conn: &mut sqlx::SqliteConnection,
+    product: &ProposalProduct,
+    now: &str,
+) -> Result<ProposalProduct, sqlx::Error> {
+    let updated = sqlx::query_as::<_, ProposalProduct>(
+        "UPDATE proposal_products SET 
+            product_type = ?, user_count = ?, standalone_count = ?, server_key_count = ?,
+            unit_price = ?, total_price = ?, annual_reduction = ?, training = ?, training_days = ?,
+            training_cost_per_day = ?, training_cost = ?, licence = ?, support = ?, support_years = ?,
+            updated_at = ?, version = COALESCE(version, 0) + 1
+        WHERE id = ? AND proposal_id = ? AND COALESCE(is_deleted, 0) = 0 RETURNING *"
     )
-    .bind(proposal_id)
-    .fetch_all(pool)
+    .bind(&product.product_type)
+    .bind(product.user_count)
+    .bind(product.standalone_count)
+    .bind(product.server_key_count)
+    .bind(product.unit_price)
+    .bind(product.total_price)
+    .bind(product.annual_reduction)
+    .bind(product.training)
+    .bind(product.training_days)
+    .bind(product.training_cost_per_day)
+    .bind(product.training_cost)
+    .bind(product.licence)
+    .bind(product.support)
+    .bind(product.support_years)
+    .bind(now)
+    .bind(&product.id)
+    .bind(&product.proposal_id)
+    .fetch_optional(&mut *conn)
     .await?;
-    Ok(products)
+    if let Some(updated) = updated {
+        return Ok(updated);
+    }
+
+    sqlx::query_as::<_, ProposalProduct>(
+        "INSERT INTO proposal_products (id, proposal_id, product_type, user_count, standalone_count, server_key_count, 
+         unit_price, total_price, annual_reduction, training, training_days, training_cost_per_day, training_cost, 
+         licence, support, support_years, created_at, updated_at, version, is_deleted) 
+         VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, 1, 0) RETURNING *"
+    )
+    .bind(Uuid::new_v4().to_string())
+    .bind(&product.proposal_id)
+    .bind(&product.product_type)
+    .bind(product.user_count)
+    .bind(product.standalone_count)
+    .bind(product.server_key_count)
+    .bind(product.unit_price)
+    .bind(product.total_price)
+    .bind(product.annual_reduction)
+    .bind(product.training)
+    .bind(product.training_days)
+    .bind(product.training_cost_per_day)
+    .bind(product.training_cost)
+    .bind(product.licence)
+    .bind(product.support)
+    .bind(product.support_years)
+    .bind(now)
+    .bind(now)
+    .fetch_one(&mut *conn)
+    .await
 }
 
-pub async fn delete_proposal_products(pool: &DbPool, proposal_id: &str) -> Result<(), sqlx::Error> {
-    sqlx::query("DELETE FROM proposal_products WHERE proposal_id = ?")
-        .bind(proposal_id)
-        .execute(pool)
-        .await?;
-    Ok(())
+/// Marque supprimés les produits de la proposition absents de `keep_ids`
+pub async fn delete_proposal_products_except(
+    conn: &mut sqlx::SqliteConnection,
+    proposal_id: &str,
+    keep_ids: &[String],
+    now: &str,
+) -> Result<u64, sqlx::Error> {
+    let sql = format!(
+        "UPDATE proposal_products SET is_deleted = 1, updated_at = ?, version = COALESCE(version, 0) + 1
+         WHERE proposal_id = ? AND COALESCE(is_deleted, 0) = 0 AND id NOT IN ({})",
+        placeholders(keep_ids.len())
+    );
+    let mut statement = sqlx::query(&sql).bind(now).bind(proposal_id);
+    for id in keep_ids {
+        statement = statement.bind(id);
+    }
+    Ok(statement.execute(&mut *conn).await?.rows_affected())
 }
 
 pub async fn delete_proposal_product(pool: &DbPool, product_id: &str) -> Result<(), sqlx::Error> {
@@ -584,6 +1304,7 @@ pub async fn update_proposal_total(pool: &DbPool, proposal_id: &str, total: f64)
 
 pub async fn generate_proposal_number(
     pool: &DbPool,
+    format: &str,
     company_id: &str,
     product_type: &str,
     has_training: bool,
@@ -594,11 +1315,6 @@ pub async fn generate_proposal_number(
     let company = get_company_by_id(pool, company_id).await?;
     let company_name = company.map(|c| c.name).unwrap_or_else(|| "Unknown".to_string());
     
-    // Format: D_CompanyName_Product_YYMMDD
-    let first_letter = "D";
-    let now = Utc::now();
-    let date_suffix = format!("{:02}{:02}{:02}", now.year() % 100, now.month(), now.day());
-    
     // Product code
     let product_code = if product_type.contains("HTZ Communications") {
         "HTZc"
@@ -623,7 +1339,11 @@ pub async fn generate_proposal_number(
     let product_tag = tags.join(separator);
     
     let clean_company = company_name.replace(" ", "_").replace("/", "_");
-    let proposal_number = format!("{}_{}_{}_{}", first_letter, clean_company, product_tag, date_suffix);
+    let proposal_number = numbering::render(
+        format,
+        Utc::now().date_naive(),
+        &[("company", &clean_company), ("products", &product_tag)],
+    );
     
     Ok(proposal_number)
 }
@@ -641,8 +1361,8 @@ pub async fn create_customer(
     let now = chrono::Utc::now().timestamp_millis().to_string();
 
     let customer = sqlx::query_as::<_, Customer>(
-        "INSERT INTO customers (id, name, email, phone, address, notes, created_at, updated_at, sync_status) 
-         VALUES (?, ?, ?, ?, ?, ?, ?, ?, 'pending') RETURNING *"
+        "INSERT INTO customers (id, name, email, phone, address, notes, created_at, updated_at) 
+         VALUES (?, ?, ?, ?, ?, ?, ?, ?) RETURNING *"
     )
     .bind(&id)
     .bind(&name)
@@ -688,7 +1408,7 @@ pub async fn update_customer(
 
     let customer = sqlx::query_as::<_, Customer>(
         "UPDATE customers SET name = ?, email = ?, phone = ?, address = ?, notes = ?, 
-         updated_at = ?, sync_status = 'pending' WHERE id = ? RETURNING *"
+         updated_at = ?, version = COALESCE(version, 0) + 1 WHERE id = ? RETURNING *"
     )
     .bind(&name)
     .bind(&email)
@@ -721,75 +1441,435 @@ pub async fn delete_customer(pool: &DbPool, id: &str) -> Result<(), sqlx::Error>
 }
 
 // Document queries
+/// Document saisi ; ses rattachements sont enregistrés à part
+/// (`link_document`).
 pub async fn create_document(
     pool: &DbPool,
-    customer_id: String,
     title: String,
     document_type: String,
     file_path: Option<String>,
     content: Option<String>,
+    sha256: Option<String>,
 ) -> Result<Document, sqlx::Error> {
     let id = Uuid::new_v4().to_string();
     let now = chrono::Utc::now().timestamp_millis().to_string();
 
+    let mut tx = pool.begin().await?;
     let document = sqlx::query_as::<_, Document>(
-        "INSERT INTO documents (id, customer_id, title, document_type, file_path, content, created_at, updated_at, sync_status) 
-         VALUES (?, ?, ?, ?, ?, ?, ?, ?, 'pending') RETURNING *"
+        "INSERT INTO documents (id, title, document_type, file_path, content, created_at, updated_at, sha256) 
+         VALUES (?, ?, ?, ?, ?, ?, ?, ?) RETURNING *"
     )
     .bind(&id)
-    .bind(&customer_id)
     .bind(&title)
     .bind(&document_type)
     .bind(&file_path)
     .bind(&content)
     .bind(&now)
     .bind(&now)
-    .fetch_one(pool)
+    .bind(&sha256)
+    .fetch_one(&mut *tx)
     .await?;
+    tx.commit().await?;
 
     Ok(document)
 }
 
-pub async fn get_all_documents(pool: &DbPool) -> Result<Vec<Document>, sqlx::Error> {
-    let documents = sqlx::query_as::<_, Document>("SELECT * FROM documents ORDER BY created_at DESC")
-        .fetch_all(pool)
-        .await?;
-
-    Ok(documents)
-}
-
-
+/// Document généré, archivé sous son empreinte `sha256`.
+pub async fn create_archived_document(
+    pool: &DbPool,
+    title: String,
+    document_type: String,
+    file_path: String,
+    source_type: &str,
+    source_id: &str,
+    revision: i64,
+    sha256: String,
+) -> Result<Document, sqlx::Error> {
+    let id = Uuid::new_v4().to_string();
+    let now = chrono::Utc::now().timestamp_millis().to_string();
 
-pub async fn get_documents_by_customer(pool: &DbPool, customer_id: &str) -> Result<Vec<Document>, sqlx::Error> {
-    let documents = sqlx::query_as::<_, Document>(
-        "SELECT * FROM documents WHERE customer_id = ? ORDER BY created_at DESC"
+    // Transaction explicite : avec RETURNING, fetch_one ne termine pas
+    // l'instruction et la ligne resterait invisible des autres connexions
+    // jusqu'à la réutilisation de celle-ci (calcul de la révision suivante)
+    let mut tx = pool.begin().await?;
+    let document = sqlx::query_as::<_, Document>(
+        "INSERT INTO documents (id, title, document_type, file_path, created_at, updated_at, source_type, source_id, revision, sha256) 
+         VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?) RETURNING *"
     )
-    .bind(customer_id)
-    .fetch_all(pool)
+    .bind(&id)
+    .bind(&title)
+    .bind(&document_type)
+    .bind(&file_path)
+    .bind(&now)
+    .bind(&now)
+    .bind(source_type)
+    .bind(source_id)
+    .bind(revision)
+    .bind(&sha256)
+    .fetch_one(&mut *tx)
     .await?;
+    tx.commit().await?;
 
-    Ok(documents)
+    Ok(document)
 }
 
-pub async fn delete_document(pool: &DbPool, id: &str) -> Result<(), sqlx::Error> {
-    // Supprimer en local immédiatement
-    sqlx::query("DELETE FROM documents WHERE id = ?")
+/// Révision d'un nouveau document généré pour un enregistrement : celle du
+/// dernier document archivé tant que l'enregistrement n'a pas été modifié
+/// depuis (`modified_at`, en millisecondes), la suivante sinon.
+pub async fn next_document_revision(
+    pool: &DbPool,
+    source_type: &str,
+    source_id: &str,
+    modified_at: i64,
+) -> Result<i64, sqlx::Error> {
+    // Les documents supprimés comptent : un numéro n'est jamais réutilisé
+    let (revision, generated_at): (Option<i64>, Option<i64>) = sqlx::query_as(
+        "SELECT MAX(revision), MAX(CAST(created_at AS INTEGER)) FROM documents
+         WHERE source_type = ? AND source_id = ?"
+    )
+    .bind(source_type)
+    .bind(source_id)
+    .fetch_one(pool)
+    .await?;
+
+    Ok(match (revision, generated_at) {
+        (Some(revision), Some(generated_at)) if generated_at >= modified_at => revision,
+        (Some(revision), _) => revision + 1,
+        (None, _) => 1,
+    })
+}
+
+// Colonnes d'un document ; client et société sont ceux de ses
+// rattachements (les colonnes `customer_id` et `company_id` de la table ne
+// sont plus écrites et ne servent qu'aux postes des versions antérieures)
+const DOCUMENT_COLUMNS: &str = "d.id, \
+     (SELECT l.entity_id FROM document_links l WHERE l.document_id = d.id AND l.entity_type = 'customer'
+      AND COALESCE(l.is_deleted, 0) = 0 ORDER BY l.created_at LIMIT 1) AS customer_id, \
+     (SELECT l.entity_id FROM document_links l WHERE l.document_id = d.id AND l.entity_type = 'company'
+      AND COALESCE(l.is_deleted, 0) = 0 ORDER BY l.created_at LIMIT 1) AS company_id, \
+     d.title, d.document_type, d.file_path, d.content, d.created_at, d.updated_at, d.version, d.is_deleted, \
+     d.source_type, d.source_id, d.revision, d.sha256, d.deleted_by";
+
+pub async fn get_all_documents(pool: &DbPool) -> Result<Vec<Document>, sqlx::Error> {
+    let documents = sqlx::query_as::<_, Document>(&format!(
+        "SELECT {} FROM documents d WHERE COALESCE(d.is_deleted, 0) = 0 ORDER BY d.created_at DESC",
+        DOCUMENT_COLUMNS
+    ))
+    .fetch_all(pool)
+    .await?;
+
+    Ok(documents)
+}
+
+
+
+/// Identifiant déterministe d'un rattachement document ↔ enregistrement.
+pub fn document_link_id(entity_type: &str, entity_id: &str, document_id: &str) -> String {
+    format!("{}:{}:{}", entity_type, entity_id, document_id)
+}
+
+/// Vrai si l'enregistrement auquel rattacher un document existe et n'est
+/// pas à la corbeille.
+pub async fn entity_exists(pool: &DbPool, entity_type: &str, entity_id: &str) -> Result<bool, sqlx::Error> {
+    let table = match entity_type {
+        "company" => "companies",
+        "proposal" => "proposals",
+        "invoice" => "invoices",
+        "customer" => "customers",
+        _ => return Err(sqlx::Error::Protocol(format!("Unknown entity type: {}", entity_type))),
+    };
+    let count: i64 = sqlx::query_scalar(&format!(
+        "SELECT COUNT(*) FROM {} WHERE id = ? AND COALESCE(is_deleted, 0) = 0",
+        table
+    ))
+    .bind(entity_id)
+    .fetch_one(pool)
+    .await?;
+    Ok(count > 0)
+}
+
+/// Documents rattachés à un enregistrement.
+pub async fn get_documents_by_entity(pool: &DbPool, entity_type: &str, entity_id: &str) -> Result<Vec<Document>, sqlx::Error> {
+    let documents = sqlx::query_as::<_, Document>(&format!(
+        "SELECT {} FROM documents d
+         JOIN document_links link ON link.document_id = d.id
+         WHERE link.entity_type = ? AND link.entity_id = ?
+           AND COALESCE(link.is_deleted, 0) = 0 AND COALESCE(d.is_deleted, 0) = 0
+         ORDER BY d.created_at DESC",
+        DOCUMENT_COLUMNS
+    ))
+    .bind(entity_type)
+    .bind(entity_id)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(documents)
+}
+
+/// Rattache un document ; un rattachement supprimé auparavant est rétabli.
+pub async fn link_document(pool: &DbPool, document_id: &str, entity_type: &str, entity_id: &str) -> Result<DocumentLink, sqlx::Error> {
+    let id = document_link_id(entity_type, entity_id, document_id);
+    let now = chrono::Utc::now().timestamp_millis().to_string();
+
+    let link = sqlx::query_as::<_, DocumentLink>(
+        "INSERT INTO document_links (id, document_id, entity_type, entity_id, created_at, updated_at, version, is_deleted)
+         VALUES (?, ?, ?, ?, ?, ?, 1, 0)
+         ON CONFLICT(id) DO UPDATE SET
+         is_deleted = 0,
+         updated_at = excluded.updated_at,
+         version = COALESCE(document_links.version, 0) + 1
+         RETURNING *"
+    )
+    .bind(&id)
+    .bind(document_id)
+    .bind(entity_type)
+    .bind(entity_id)
+    .bind(&now)
+    .bind(&now)
+    .fetch_one(pool)
+    .await?;
+
+    Ok(link)
+}
+
+pub async fn get_document_links(pool: &DbPool, document_id: &str) -> Result<Vec<DocumentLink>, sqlx::Error> {
+    let links = sqlx::query_as::<_, DocumentLink>(
+        "SELECT * FROM document_links WHERE document_id = ? AND COALESCE(is_deleted, 0) = 0 ORDER BY created_at"
+    )
+    .bind(document_id)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(links)
+}
+
+/// Détache les documents de `document_id` (tous les rattachements si
+/// `entity` est `None`). Suppression logique pour la synchronisation.
+pub async fn unlink_document(pool: &DbPool, document_id: &str, entity: Option<(&str, &str)>) -> Result<u64, sqlx::Error> {
+    let now = chrono::Utc::now().timestamp_millis().to_string();
+    let result = match entity {
+        Some((entity_type, entity_id)) => {
+            sqlx::query(
+                "UPDATE document_links SET is_deleted = 1, updated_at = ?, version = COALESCE(version, 0) + 1
+                 WHERE id = ? AND COALESCE(is_deleted, 0) = 0"
+            )
+            .bind(&now)
+            .bind(document_link_id(entity_type, entity_id, document_id))
+            .execute(pool)
+            .await?
+        }
+        None => {
+            sqlx::query(
+                "UPDATE document_links SET is_deleted = 1, updated_at = ?, version = COALESCE(version, 0) + 1
+                 WHERE document_id = ? AND COALESCE(is_deleted, 0) = 0"
+            )
+            .bind(&now)
+            .bind(document_id)
+            .execute(pool)
+            .await?
+        }
+    };
+
+    Ok(result.rows_affected())
+}
+
+pub async fn get_documents_by_source(pool: &DbPool, source_type: &str, source_id: &str) -> Result<Vec<Document>, sqlx::Error> {
+    let documents = sqlx::query_as::<_, Document>(&format!(
+        "SELECT {} FROM documents d WHERE d.source_type = ? AND d.source_id = ? ORDER BY d.created_at DESC",
+        DOCUMENT_COLUMNS
+    ))
+    .bind(source_type)
+    .bind(source_id)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(documents)
+}
+
+pub async fn get_document_by_id(pool: &DbPool, id: &str) -> Result<Option<Document>, sqlx::Error> {
+    let document = sqlx::query_as::<_, Document>(&format!("SELECT {} FROM documents d WHERE d.id = ?", DOCUMENT_COLUMNS))
         .bind(id)
-        .execute(pool)
+        .fetch_optional(pool)
         .await?;
-    
-    // Enregistrer l'action pour la synchronisation
-    
+
+    Ok(document)
+}
+
+/// Documents dont le fichier est géré comme pièce jointe.
+pub async fn get_attachment_documents(pool: &DbPool) -> Result<Vec<Document>, sqlx::Error> {
+    let documents = sqlx::query_as::<_, Document>(&format!(
+        "SELECT {} FROM documents d WHERE d.sha256 IS NOT NULL AND COALESCE(d.is_deleted, 0) = 0 ORDER BY d.created_at DESC",
+        DOCUMENT_COLUMNS
+    ))
+    .fetch_all(pool)
+    .await?;
+
+    Ok(documents)
+}
+
+pub async fn get_attachment_transfers(pool: &DbPool) -> Result<Vec<AttachmentTransfer>, sqlx::Error> {
+    let transfers = sqlx::query_as::<_, AttachmentTransfer>("SELECT * FROM attachment_transfers")
+        .fetch_all(pool)
+        .await?;
+
+    Ok(transfers)
+}
+
+/// Enregistre le résultat d'un envoi ; `error` à `None` marque le fichier
+/// comme présent sur le serveur.
+pub async fn set_attachment_transfer(pool: &DbPool, sha256: &str, error: Option<String>) -> Result<(), sqlx::Error> {
+    let now = chrono::Utc::now().timestamp_millis().to_string();
+
+    sqlx::query(
+        "INSERT INTO attachment_transfers (sha256, uploaded, last_error, updated_at) VALUES (?, ?, ?, ?)
+         ON CONFLICT(sha256) DO UPDATE SET
+         uploaded = MAX(uploaded, excluded.uploaded),
+         last_error = excluded.last_error,
+         updated_at = excluded.updated_at"
+    )
+    .bind(sha256)
+    .bind(error.is_none() as i64)
+    .bind(&error)
+    .bind(&now)
+    .execute(pool)
+    .await?;
+
     Ok(())
 }
 
+// Document template queries
+const TEMPLATE_SUMMARY_COLUMNS: &str = "id, name, kind, product_type, language, company_id, template_version, \
+     file_name, NULL AS content, checksum, created_at, updated_at, version, is_deleted";
+
+pub async fn create_document_template(
+    pool: &DbPool,
+    name: String,
+    kind: String,
+    product_type: Option<String>,
+    language: Option<String>,
+    company_id: Option<String>,
+    file_name: String,
+    content: String,
+    checksum: String,
+) -> Result<DocumentTemplate, sqlx::Error> {
+    let id = Uuid::new_v4().to_string();
+    let now = chrono::Utc::now().timestamp_millis().to_string();
+
+    // Nouvelle version du modèle (kind, name) : le numéro est calculé par
+    // l'INSERT lui-même, dans une transaction, pour que deux imports
+    // simultanés ne reçoivent pas la même version
+    let mut tx = pool.begin().await?;
+    let template = sqlx::query_as::<_, DocumentTemplate>(
+        "INSERT INTO document_templates (id, name, kind, product_type, language, company_id, template_version, file_name, content, checksum, created_at, updated_at, version, is_deleted)
+         SELECT ?, ?, ?, ?, ?, ?, COALESCE(MAX(template_version), 0) + 1, ?, ?, ?, ?, ?, 1, 0
+         FROM document_templates WHERE kind = ? AND name = ?
+         RETURNING *"
+    )
+    .bind(&id)
+    .bind(&name)
+    .bind(&kind)
+    .bind(&product_type)
+    .bind(&language)
+    .bind(&company_id)
+    .bind(&file_name)
+    .bind(&content)
+    .bind(&checksum)
+    .bind(&now)
+    .bind(&now)
+    .bind(&kind)
+    .bind(&name)
+    .fetch_one(&mut *tx)
+    .await?;
+    tx.commit().await?;
+
+    Ok(template)
+}
+
+/// Modèles actifs, sans leur contenu, triés par nom puis version décroissante.
+pub async fn get_document_templates(pool: &DbPool, kind: Option<&str>) -> Result<Vec<DocumentTemplate>, sqlx::Error> {
+    let templates = sqlx::query_as::<_, DocumentTemplate>(&format!(
+        "SELECT {} FROM document_templates
+         WHERE COALESCE(is_deleted, 0) = 0 AND (? IS NULL OR kind = ?)
+         ORDER BY kind, name, template_version DESC",
+        TEMPLATE_SUMMARY_COLUMNS
+    ))
+    .bind(kind)
+    .bind(kind)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(templates)
+}
+
+pub async fn get_document_template_by_id(pool: &DbPool, id: &str) -> Result<Option<DocumentTemplate>, sqlx::Error> {
+    let template = sqlx::query_as::<_, DocumentTemplate>("SELECT * FROM document_templates WHERE id = ?")
+        .bind(id)
+        .fetch_optional(pool)
+        .await?;
+
+    Ok(template)
+}
+
 // add_to_sync_queue - REMOVED as part of synchronization cleanup
 
 // get_pending_sync_items and remove_from_sync_queue - REMOVED as part of synchronization cleanup
 
 // increment_sync_attempts - REMOVED as part of synchronization cleanup
 
-// Settings queries - REMOVED as part of synchronization cleanup
+// Settings queries
+
+/// Réglages de ce poste : section -> document JSON
+pub async fn get_local_settings(pool: &DbPool) -> Result<HashMap<String, String>, sqlx::Error> {
+    let rows: Vec<(String, String)> = sqlx::query_as("SELECT key, value FROM settings")
+        .fetch_all(pool)
+        .await?;
+    Ok(rows.into_iter().collect())
+}
+
+pub async fn set_local_setting(pool: &DbPool, key: &str, value: &str) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        "INSERT INTO settings (key, value, updated_at) VALUES (?, ?, ?)
+         ON CONFLICT(key) DO UPDATE SET value = excluded.value, updated_at = excluded.updated_at"
+    )
+    .bind(key)
+    .bind(value)
+    .bind(Utc::now().timestamp_millis().to_string())
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+/// Réglages de l'organisation : clé -> document JSON
+pub async fn get_org_settings(pool: &DbPool) -> Result<HashMap<String, String>, sqlx::Error> {
+    let rows: Vec<(String, String)> = sqlx::query_as(
+        "SELECT id, value FROM org_settings WHERE COALESCE(is_deleted, 0) = 0"
+    )
+    .fetch_all(pool)
+    .await?;
+    Ok(rows.into_iter().collect())
+}
+
+/// Enregistre un réglage de l'organisation pour la prochaine
+/// synchronisation ; une valeur inchangée n'est pas réécrite, pour ne pas
+/// écraser la modification d'un autre poste. Renvoie `true` si la valeur a
+/// changé.
+pub async fn set_org_setting(pool: &DbPool, key: &str, value: &str) -> Result<bool, sqlx::Error> {
+    let now = Utc::now().timestamp_millis().to_string();
+    let result = sqlx::query(
+        "INSERT INTO org_settings (id, value, created_at, updated_at, version, is_deleted)
+         VALUES (?, ?, ?, ?, 1, 0)
+         ON CONFLICT(id) DO UPDATE SET value = excluded.value, updated_at = excluded.updated_at,
+         version = COALESCE(org_settings.version, 0) + 1, is_deleted = 0
+         WHERE org_settings.value <> excluded.value OR COALESCE(org_settings.is_deleted, 0) <> 0"
+    )
+    .bind(key)
+    .bind(value)
+    .bind(&now)
+    .bind(&now)
+    .execute(pool)
+    .await?;
+    Ok(result.rows_affected() > 0)
+}
 
 // === SYNC QUERIES ===
 pub async fn get_sync_metadata(pool: &DbPool) -> Result<SyncMetadata, sqlx::Error> {
@@ -817,17 +1897,24 @@ pub async fn get_sync_metadata(pool: &DbPool) -> Result<SyncMetadata, sqlx::Erro
                 last_sync_timestamp: 0,
                 last_sync_version: 0,
                 updated_at: now.to_string(),
+                device_id: None,
+                device_name: None,
+                last_push_timestamp: 0,
             })
         }
     }
 }
 
-pub async fn update_sync_metadata(pool: &DbPool, timestamp: i64, version: i64) -> Result<(), sqlx::Error> {
+/// Enregistre une synchronisation réussie : `timestamp` est l'heure du
+/// serveur jusqu'à laquelle ses modifications ont été reçues,
+/// `push_timestamp` l'heure locale jusqu'à laquelle les nôtres ont été envoyées
+pub async fn update_sync_metadata(pool: &DbPool, timestamp: i64, push_timestamp: i64, version: i64) -> Result<(), sqlx::Error> {
     let now = chrono::Utc::now().timestamp_millis();
     sqlx::query(
-        "UPDATE sync_metadata SET last_sync_timestamp = ?, last_sync_version = ?, updated_at = ? WHERE id = 1"
+        "UPDATE sync_metadata SET last_sync_timestamp = ?, last_push_timestamp = ?, last_sync_version = ?, updated_at = ? WHERE id = 1"
     )
     .bind(timestamp)
+    .bind(push_timestamp)
     .bind(version)
     .bind(now.to_string())
     .execute(pool)
@@ -835,393 +1922,218 @@ pub async fn update_sync_metadata(pool: &DbPool, timestamp: i64, version: i64) -
     Ok(())
 }
 
-pub async fn get_changed_items_since(pool: &DbPool, since_timestamp: i64) -> Result<Vec<SyncItem>, sqlx::Error> {
-    let mut items = Vec::new();
-    
-    // Get companies
-    let companies = sqlx::query_as::<_, Company>(
-        "SELECT * FROM companies WHERE updated_at > ? ORDER BY updated_at ASC"
-    )
-    .bind(since_timestamp.to_string())
-    .fetch_all(pool)
-    .await?;
-
-    for company in companies {
-        let updated_at = company.updated_at.parse::<i64>().unwrap_or(0);
-        let version = company.version.unwrap_or(1);
-        let is_deleted = company.is_deleted.unwrap_or(0) != 0;
-        
-        items.push(SyncItem {
-            table_name: "companies".to_string(),
-            id: company.id.clone(),
-            data: serde_json::to_value(company).unwrap_or_default(),
-            version,
-            is_deleted,
-            updated_at,
-        });
+/// Identifiant et nom du poste, créés à la première demande ; le nom par
+/// défaut est celui de la machine.
+pub async fn get_device_identity(pool: &DbPool) -> Result<(String, String), sqlx::Error> {
+    let metadata = get_sync_metadata(pool).await?;
+    if let (Some(id), Some(name)) = (metadata.device_id, metadata.device_name) {
+        return Ok((id, name));
     }
 
-    // Get company_contacts
-    let contacts = sqlx::query_as::<_, CompanyContact>(
-        "SELECT * FROM company_contacts WHERE updated_at > ? ORDER BY updated_at ASC"
-    )
-    .bind(since_timestamp.to_string())
-    .fetch_all(pool)
-    .await?;
+    let id = Uuid::new_v4().to_string();
+    let name = std::env::var("COMPUTERNAME")
+        .or_else(|_| std::env::var("HOSTNAME"))
+        .unwrap_or_else(|_| format!("Poste {}", &id[..8]));
+    sqlx::query("UPDATE sync_metadata SET device_id = ?, device_name = ? WHERE id = 1")
+        .bind(&id)
+        .bind(&name)
+        .execute(pool)
+        .await?;
+    Ok((id, name))
+}
 
-    for contact in contacts {
-        let updated_at = contact.updated_at.parse::<i64>().unwrap_or(0);
-        let version = contact.version.unwrap_or(1);
-        let is_deleted = contact.is_deleted.unwrap_or(0) != 0;
-        
-        items.push(SyncItem {
-            table_name: "company_contacts".to_string(),
-            id: contact.id.clone(),
-            data: serde_json::to_value(contact).unwrap_or_default(),
-            version,
-            is_deleted,
-            updated_at,
-        });
-    }
+// Lit une ligne SQLite selon la définition partagée des colonnes
+fn read_sync_record(entity: &Entity, row: &SqliteRow) -> Result<Record, sqlx::Error> {
+    entity
+        .columns
+        .iter()
+        .map(|column| {
+            let name = column.name;
+            let value = match column.ty {
+                ColumnType::Integer => row.try_get::<Option<i64>, _>(name)?.map(SchemaValue::Integer),
+                ColumnType::Bool => row.try_get::<Option<i64>, _>(name)?.map(|b| SchemaValue::Bool(b != 0)),
+                ColumnType::Real => row.try_get::<Option<f64>, _>(name)?.map(SchemaValue::Real),
+                ColumnType::Text => row.try_get::<Option<String>, _>(name)?.map(SchemaValue::Text),
+                ty => match row.try_get::<Option<String>, _>(name)? {
+                    Some(raw) => Some(SchemaValue::from_text(ty, &raw).map_err(|e| {
+                        sqlx::Error::Decode(format!("{}.{}: {}", entity.table, name, e).into())
+                    })?),
+                    None => None,
+                },
+            };
+            Ok(value.unwrap_or(SchemaValue::Null))
+        })
+        .collect()
+}
 
-    // Get customers
-    let customers = sqlx::query_as::<_, Customer>(
-        "SELECT * FROM customers WHERE updated_at > ? ORDER BY updated_at ASC"
-    )
-    .bind(since_timestamp.to_string())
-    .fetch_all(pool)
-    .await?;
+// Enregistrement à envoyer ; une ligne illisible est retournée comme refus
+// plutôt que de bloquer l'envoi de toutes les autres
+fn read_sync_item(entity: &Entity, row: &SqliteRow) -> Result<SyncItem, crm_schema::SyncItemError> {
+    read_sync_record(entity, row)
+        .and_then(|record| {
+            SyncItem::from_record(entity, record)
+                .map_err(|e| sqlx::Error::Decode(format!("{}: {}", entity.table, e).into()))
+        })
+        .map_err(|e| crm_schema::SyncItemError {
+            table_name: entity.table.to_string(),
+            id: row.try_get("id").unwrap_or_default(),
+            field: None,
+            message: format!("Unreadable local record: {}", e),
+        })
+}
 
-    for customer in customers {
-        let updated_at = customer.updated_at.parse::<i64>().unwrap_or(0);
-        let version = customer.version.unwrap_or(1);
-        let is_deleted = customer.is_deleted.unwrap_or(0) != 0;
-        
-        items.push(SyncItem {
-            table_name: "customers".to_string(),
-            id: customer.id.clone(),
-            data: serde_json::to_value(customer).unwrap_or_default(),
-            version,
-            is_deleted,
-            updated_at,
-        });
+/// Modifications locales à envoyer
+#[derive(Debug, Default)]
+pub struct PendingChanges {
+    pub items: Vec<SyncItem>,
+    /// Lignes illisibles, non envoyées : signalées comme erreurs de la
+    /// synchronisation et conservées dans `sync_rejections` jusqu'à ce
+    /// qu'elles soient corrigées
+    pub unreadable: Vec<crm_schema::SyncItemError>,
+}
+
+impl PendingChanges {
+    fn push(&mut self, entity: &Entity, row: &SqliteRow) {
+        match read_sync_item(entity, row) {
+            Ok(item) => self.items.push(item),
+            Err(error) => self.unreadable.push(error),
+        }
     }
 
-    // Get proposals
-    let proposals = sqlx::query_as::<_, Proposal>(
-        "SELECT * FROM proposals WHERE updated_at > ? ORDER BY updated_at ASC"
-    )
-    .bind(since_timestamp.to_string())
-    .fetch_all(pool)
-    .await?;
+    fn contains(&self, entity: &Entity, id: &str) -> bool {
+        self.items.iter().any(|i| i.entity() == entity && i.id() == id)
+            || self.unreadable.iter().any(|e| e.table_name == entity.table && e.id == id)
+    }
+}
 
-    for proposal in proposals {
-        let updated_at = proposal.updated_at.parse::<i64>().unwrap_or(0);
-        let version = proposal.version.unwrap_or(1);
-        let is_deleted = proposal.is_deleted.unwrap_or(0) != 0;
-        
-        items.push(SyncItem {
-            table_name: "proposals".to_string(),
-            id: proposal.id.clone(),
-            data: serde_json::to_value(proposal).unwrap_or_default(),
-            version,
-            is_deleted,
-            updated_at,
-        });
+// Écrit (ou met à jour) un enregistrement selon la définition partagée
+async fn upsert_sync_record(pool: &DbPool, entity: &Entity, record: &[SchemaValue]) -> Result<(), sqlx::Error> {
+    let sql = entity.upsert_sql(Dialect::Sqlite);
+    let mut query = sqlx::query(&sql);
+    for (column, value) in entity.columns.iter().zip(record) {
+        query = match column.ty {
+            ColumnType::Integer | ColumnType::Bool => query.bind(value.as_i64()),
+            ColumnType::Real => query.bind(value.as_f64()),
+            _ => query.bind(value.to_sqlite_text()),
+        };
     }
+    query.execute(pool).await?;
+    Ok(())
+}
 
-    // Get proposal_products
-    let products = sqlx::query_as::<_, ProposalProduct>(
-        "SELECT * FROM proposal_products WHERE updated_at > ? ORDER BY updated_at ASC"
-    )
-    .bind(since_timestamp.to_string())
-    .fetch_all(pool)
-    .await?;
+pub async fn get_changed_items_since(pool: &DbPool, since_timestamp: i64) -> Result<PendingChanges, sqlx::Error> {
+    let mut changes = PendingChanges::default();
 
-    for product in products {
-        let updated_at = product.updated_at.parse::<i64>().unwrap_or(0);
-        let version = product.version.unwrap_or(1);
-        let is_deleted = product.is_deleted.unwrap_or(0) != 0;
-        
-        items.push(SyncItem {
-            table_name: "proposal_products".to_string(),
-            id: product.id.clone(),
-            data: serde_json::to_value(product).unwrap_or_default(),
-            version,
-            is_deleted,
-            updated_at,
-        });
+    for entity in ENTITIES {
+        let rows = sqlx::query(&format!(
+            "SELECT {} FROM {} WHERE CAST(updated_at AS INTEGER) > ? ORDER BY CAST(updated_at AS INTEGER) ASC",
+            entity.select_list(Dialect::Sqlite),
+            entity.table
+        ))
+        .bind(since_timestamp)
+        .fetch_all(pool)
+        .await?;
+
+        for row in &rows {
+            changes.push(entity, row);
+        }
     }
 
-    // Get invoices
-    let invoices = sqlx::query_as::<_, Invoice>(
-        "SELECT * FROM invoices WHERE updated_at > ? ORDER BY updated_at ASC"
-    )
-    .bind(since_timestamp.to_string())
-    .fetch_all(pool)
-    .await?;
+    Ok(changes)
+}
 
-    for invoice in invoices {
-        let updated_at = invoice.updated_at.parse::<i64>().unwrap_or(0);
-        let version = invoice.version.unwrap_or(1);
-        let is_deleted = invoice.is_deleted.unwrap_or(0) != 0;
-        
-        items.push(SyncItem {
-            table_name: "invoices".to_string(),
-            id: invoice.id.clone(),
-            data: serde_json::to_value(invoice).unwrap_or_default(),
-            version,
-            is_deleted,
-            updated_at,
-        });
+/// Modifications à envoyer : celles postérieures à `since_timestamp`, puis
+/// celles refusées (ou illisibles) lors d'une synchronisation précédente
+pub async fn get_pending_sync_items(pool: &DbPool, since_timestamp: i64) -> Result<PendingChanges, sqlx::Error> {
+    let mut changes = get_changed_items_since(pool, since_timestamp).await?;
+
+    for entity in ENTITIES {
+        let rows = sqlx::query(&format!(
+            "SELECT {} FROM {} WHERE id IN (SELECT record_id FROM sync_rejections WHERE table_name = ?)",
+            entity.select_list(Dialect::Sqlite),
+            entity.table
+        ))
+        .bind(entity.table)
+        .fetch_all(pool)
+        .await?;
+
+        for row in &rows {
+            let id: String = row.try_get("id")?;
+            if !changes.contains(entity, &id) {
+                changes.push(entity, row);
+            }
+        }
     }
 
-    // Get documents
-    let documents = sqlx::query_as::<_, Document>(
-        "SELECT * FROM documents WHERE updated_at > ? ORDER BY updated_at ASC"
-    )
-    .bind(since_timestamp.to_string())
-    .fetch_all(pool)
-    .await?;
+    Ok(changes)
+}
 
-    for document in documents {
-        let updated_at = document.updated_at.parse::<i64>().unwrap_or(0);
-        let version = document.version.unwrap_or(1);
-        let is_deleted = document.is_deleted.unwrap_or(0) != 0;
-        
-        items.push(SyncItem {
-            table_name: "documents".to_string(),
-            id: document.id.clone(),
-            data: serde_json::to_value(document).unwrap_or_default(),
-            version,
-            is_deleted,
-            updated_at,
-        });
+/// Enregistre la réponse du serveur à un envoi : les éléments refusés (par
+/// le serveur, ou illisibles et non envoyés) seront renvoyés à la prochaine
+/// synchronisation, les autres ne le sont plus.
+pub async fn record_sync_rejections(
+    pool: &DbPool,
+    sent: &[SyncItem],
+    rejected: &[crm_schema::SyncItemError],
+) -> Result<(), sqlx::Error> {
+    let now = chrono::Utc::now().timestamp_millis().to_string();
+    let mut tx = pool.begin().await?;
+    let refused = rejected.iter().map(|e| (e.table_name.as_str(), e.id.as_str()));
+    for (table_name, id) in sent.iter().map(|i| (i.table_name(), i.id())).chain(refused) {
+        sqlx::query("DELETE FROM sync_rejections WHERE table_name = ? AND record_id = ?")
+            .bind(table_name)
+            .bind(id)
+            .execute(&mut *tx)
+            .await?;
+    }
+    // Un élément peut être refusé pour plusieurs champs
+    for error in rejected {
+        let message = match &error.field {
+            Some(field) => format!("{}: {}", field, error.message),
+            None => error.message.clone(),
+        };
+        sqlx::query(
+            "INSERT INTO sync_rejections (table_name, record_id, message, rejected_at) VALUES (?, ?, ?, ?)
+             ON CONFLICT (table_name, record_id) DO UPDATE SET message = message || '; ' || excluded.message"
+        )
+        .bind(&error.table_name)
+        .bind(&error.id)
+        .bind(message)
+        .bind(&now)
+        .execute(&mut *tx)
+        .await?;
     }
+    tx.commit().await
+}
 
-    Ok(items)
+/// Instant (ms) de la modification locale la plus récente postérieure à
+/// `since_timestamp`, sans lire les enregistrements ; `None` si rien n'est
+/// en attente d'envoi
+pub async fn latest_change_since(pool: &DbPool, since_timestamp: i64) -> Result<Option<i64>, sqlx::Error> {
+    let mut latest = None;
+    for entity in ENTITIES {
+        let updated_at: Option<i64> = sqlx::query_scalar(&format!(
+            "SELECT MAX(CAST(updated_at AS INTEGER)) FROM {} WHERE CAST(updated_at AS INTEGER) > ?",
+            entity.table
+        ))
+        .bind(since_timestamp)
+        .fetch_one(pool)
+        .await?;
+        latest = latest.max(updated_at);
+    }
+    Ok(latest)
 }
 
 pub async fn apply_sync_item(pool: &DbPool, item: &SyncItem) -> Result<(), sqlx::Error> {
-    if item.is_deleted {
-        // Delete the item
-        match item.table_name.as_str() {
-            "companies" => {
-                sqlx::query("DELETE FROM companies WHERE id = ?")
-                    .bind(&item.id)
-                    .execute(pool)
-                    .await?;
-            }
-            "company_contacts" => {
-                sqlx::query("DELETE FROM company_contacts WHERE id = ?")
-                    .bind(&item.id)
-                    .execute(pool)
-                    .await?;
-            }
-            "customers" => {
-                sqlx::query("DELETE FROM customers WHERE id = ?")
-                    .bind(&item.id)
-                    .execute(pool)
-                    .await?;
-            }
-            "proposals" => {
-                sqlx::query("DELETE FROM proposals WHERE id = ?")
-                    .bind(&item.id)
-                    .execute(pool)
-                    .await?;
-            }
-            "proposal_products" => {
-                sqlx::query("DELETE FROM proposal_products WHERE id = ?")
-                    .bind(&item.id)
-                    .execute(pool)
-                    .await?;
-            }
-            "invoices" => {
-                sqlx::query("DELETE FROM invoices WHERE id = ?")
-                    .bind(&item.id)
-                    .execute(pool)
-                    .await?;
-            }
-            "documents" => {
-                sqlx::query("DELETE FROM documents WHERE id = ?")
-                    .bind(&item.id)
-                    .execute(pool)
-                    .await?;
-            }
-            _ => {
-                return Err(sqlx::Error::Protocol("Unknown table name".to_string()));
-            }
-        }
-    } else {
-        // Insert or replace the item
-        match item.table_name.as_str() {
-            "companies" => {
-                if let Ok(company) = serde_json::from_value::<Company>(item.data.clone()) {
-                    sqlx::query(
-                        "INSERT OR REPLACE INTO companies (id, name, website, address, city, postal_code, country, description, created_at, updated_at, version, is_deleted) 
-                         VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)"
-                    )
-                    .bind(&company.id)
-                    .bind(&company.name)
-                    .bind(&company.website)
-                    .bind(&company.address)
-                    .bind(&company.city)
-                    .bind(&company.postal_code)
-                    .bind(&company.country)
-                    .bind(&company.description)
-                    .bind(&company.created_at)
-                    .bind(item.updated_at.to_string())
-                    .bind(company.version.unwrap_or(1))
-                    .bind(company.is_deleted.unwrap_or(0))
-                    .execute(pool)
-                    .await?;
-                }
-            }
-            "company_contacts" => {
-                if let Ok(contact) = serde_json::from_value::<CompanyContact>(item.data.clone()) {
-                    sqlx::query(
-                        "INSERT OR REPLACE INTO company_contacts (id, company_id, first_name, last_name, email, phone_number, is_primary, created_at, updated_at, version, is_deleted) 
-                         VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)"
-                    )
-                    .bind(&contact.id)
-                    .bind(&contact.company_id)
-                    .bind(&contact.first_name)
-                    .bind(&contact.last_name)
-                    .bind(&contact.email)
-                    .bind(&contact.phone_number)
-                    .bind(contact.is_primary)
-                    .bind(&contact.created_at)
-                    .bind(item.updated_at.to_string())
-                    .bind(contact.version.unwrap_or(1))
-                    .bind(contact.is_deleted.unwrap_or(0))
-                    .execute(pool)
-                    .await?;
-                }
-            }
-            "customers" => {
-                if let Ok(customer) = serde_json::from_value::<Customer>(item.data.clone()) {
-                    sqlx::query(
-                        "INSERT OR REPLACE INTO customers (id, name, email, phone, address, created_at, updated_at, version, is_deleted) 
-                         VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)"
-                    )
-                    .bind(&customer.id)
-                    .bind(&customer.name)
-                    .bind(&customer.email)
-                    .bind(&customer.phone)
-                    .bind(&customer.address)
-                    .bind(&customer.created_at)
-                    .bind(item.updated_at.to_string())
-                    .bind(customer.version.unwrap_or(1))
-                    .bind(customer.is_deleted.unwrap_or(0))
-                    .execute(pool)
-                    .await?;
-                }
-            }
-            "proposals" => {
-                if let Ok(proposal) = serde_json::from_value::<Proposal>(item.data.clone()) {
-                    sqlx::query(
-                        "INSERT OR REPLACE INTO proposals (id, company_id, proposal_number, status, total_amount, currency, valid_until, notes, created_at, updated_at, version, is_deleted) 
-                         VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)"
-                    )
-                    .bind(&proposal.id)
-                    .bind(&proposal.company_id)
-                    .bind(&proposal.proposal_number)
-                    .bind(&proposal.status)
-                    .bind(proposal.total_amount)
-                    .bind(&proposal.currency)
-                    .bind(&proposal.valid_until)
-                    .bind(&proposal.notes)
-                    .bind(&proposal.created_at)
-                    .bind(item.updated_at.to_string())
-                    .bind(proposal.version.unwrap_or(1))
-                    .bind(proposal.is_deleted.unwrap_or(0))
-                    .execute(pool)
-                    .await?;
-                }
-            }
-            "proposal_products" => {
-                if let Ok(product) = serde_json::from_value::<ProposalProduct>(item.data.clone()) {
-                    sqlx::query(
-                        "INSERT OR REPLACE INTO proposal_products (id, proposal_id, product_type, user_count, standalone_count, server_key_count, unit_price, total_price, annual_reduction, training, training_days, training_cost_per_day, training_cost, licence, support, support_years, created_at, updated_at, version, is_deleted) 
-                         VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)"
-                    )
-                    .bind(&product.id)
-                    .bind(&product.proposal_id)
-                    .bind(&product.product_type)
-                    .bind(product.user_count)
-                    .bind(product.standalone_count)
-                    .bind(product.server_key_count)
-                    .bind(product.unit_price)
-                    .bind(product.total_price)
-                    .bind(product.annual_reduction)
-                    .bind(product.training)
-                    .bind(product.training_days)
-                    .bind(product.training_cost_per_day)
-                    .bind(product.training_cost)
-                    .bind(product.licence)
-                    .bind(product.support)
-                    .bind(product.support_years)
-                    .bind(&product.created_at)
-                    .bind(item.updated_at.to_string())
-                    .bind(product.version.unwrap_or(1))
-                    .bind(product.is_deleted.unwrap_or(0))
-                    .execute(pool)
-                    .await?;
-                }
-            }
-            "invoices" => {
-                if let Ok(invoice) = serde_json::from_value::<Invoice>(item.data.clone()) {
-                    sqlx::query(
-                        "INSERT OR REPLACE INTO invoices (id, proposal_id, invoice_number, status, total_amount, currency, issue_date, due_date, paid_date, purchase_order, purchase_order_date, commercial_in_charge, notes, created_at, updated_at, version, is_deleted) 
-                         VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)"
-                    )
-                    .bind(&invoice.id)
-                    .bind(&invoice.proposal_id)
-                    .bind(&invoice.invoice_number)
-                    .bind(&invoice.status)
-                    .bind(invoice.total_amount)
-                    .bind(&invoice.currency)
-                    .bind(&invoice.issue_date)
-                    .bind(&invoice.due_date)
-                    .bind(&invoice.paid_date)
-                    .bind(&invoice.purchase_order)
-                    .bind(&invoice.purchase_order_date)
-                    .bind(&invoice.commercial_in_charge)
-                    .bind(&invoice.notes)
-                    .bind(&invoice.created_at)
-                    .bind(item.updated_at.to_string())
-                    .bind(invoice.version.unwrap_or(1))
-                    .bind(invoice.is_deleted.unwrap_or(0))
-                    .execute(pool)
-                    .await?;
-                }
-            }
-            "documents" => {
-                if let Ok(document) = serde_json::from_value::<Document>(item.data.clone()) {
-                    sqlx::query(
-                        "INSERT OR REPLACE INTO documents (id, customer_id, title, document_type, file_path, content, created_at, updated_at, version, is_deleted) 
-                         VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?)"
-                    )
-                    .bind(&document.id)
-                    .bind(&document.customer_id)
-                    .bind(&document.title)
-                    .bind(&document.document_type)
-                    .bind(&document.file_path)
-                    .bind(&document.content)
-                    .bind(&document.created_at)
-                    .bind(item.updated_at.to_string())
-                    .bind(document.version.unwrap_or(1))
-                    .bind(document.is_deleted.unwrap_or(0))
-                    .execute(pool)
-                    .await?;
-                }
-            }
-            _ => {
-                return Err(sqlx::Error::Protocol("Unknown table name".to_string()));
-            }
-        }
+    // Société absorbée par une fusion : redirection plutôt que suppression,
+    // sinon ses contacts et propositions partiraient en cascade
+    if let SyncItem::Company(company @ SyncedCompany { is_deleted: 1, merged_into: Some(merged_into), .. }) = item {
+        return apply_company_merge(pool, company, merged_into).await;
     }
-    Ok(())
+
+    // Les suppressions sont conservées (is_deleted) : elles apparaissent dans
+    // la corbeille et ne sont purgées qu'une fois reçues par tous les postes
+    upsert_sync_record(pool, item.entity(), &item.clone().into_record()).await
 }
 
 // Invoice queries
@@ -1245,8 +2157,8 @@ pub async fn create_invoice(
         "INSERT INTO invoices (
             id, proposal_id, invoice_number, status, total_amount, currency, 
             issue_date, due_date, purchase_order, purchase_order_date, 
-            commercial_in_charge, notes, created_at, updated_at, sync_status
-        ) VALUES (?, ?, ?, 'DRAFT', ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, 'pending') 
+            commercial_in_charge, notes, created_at, updated_at
+        ) VALUES (?, ?, ?, 'DRAFT', ?, ?, ?, ?, ?, ?, ?, ?, ?, ?) 
         RETURNING *"
     )
     .bind(&id)
@@ -1277,6 +2189,7 @@ pub async fn get_all_invoices(pool: &DbPool) -> Result<Vec<InvoiceWithDetails>,
         FROM invoices i
         JOIN proposals p ON i.proposal_id = p.id
         JOIN companies c ON p.company_id = c.id
+        WHERE COALESCE(i.is_deleted, 0) = 0
         ORDER BY i.created_at DESC"
     )
     .fetch_all(pool)
@@ -1312,7 +2225,7 @@ pub async fn get_invoice_by_proposal_id(pool: &DbPool, proposal_id: &str) -> Res
         FROM invoices i
         JOIN proposals p ON i.proposal_id = p.id
         JOIN companies c ON p.company_id = c.id
-        WHERE i.proposal_id = ?"
+        WHERE i.proposal_id = ? AND COALESCE(i.is_deleted, 0) = 0"
     )
     .bind(proposal_id)
     .fetch_optional(pool)
@@ -1340,66 +2253,66 @@ pub async fn update_invoice(
     // Build dynamic update query
     let _query_parts = vec!["updated_at = ?".to_string()];
     
-    let mut query_builder = sqlx::query("UPDATE invoices SET updated_at = ?, sync_status = 'pending' WHERE id = ?")
+    let mut query_builder = sqlx::query("UPDATE invoices SET updated_at = ?, version = COALESCE(version, 0) + 1 WHERE id = ?")
         .bind(&now)
         .bind(id);
 
     if let Some(status) = status {
-        query_builder = sqlx::query("UPDATE invoices SET updated_at = ?, status = ?, sync_status = 'pending' WHERE id = ?")
+        query_builder = sqlx::query("UPDATE invoices SET updated_at = ?, status = ?, version = COALESCE(version, 0) + 1 WHERE id = ?")
             .bind(&now)
             .bind(status)
             .bind(id);
     }
     if let Some(total_amount) = total_amount {
-        query_builder = sqlx::query("UPDATE invoices SET updated_at = ?, total_amount = ?, sync_status = 'pending' WHERE id = ?")
+        query_builder = sqlx::query("UPDATE invoices SET updated_at = ?, total_amount = ?, version = COALESCE(version, 0) + 1 WHERE id = ?")
             .bind(&now)
             .bind(total_amount)
             .bind(id);
     }
     if let Some(currency) = currency {
-        query_builder = sqlx::query("UPDATE invoices SET updated_at = ?, currency = ?, sync_status = 'pending' WHERE id = ?")
+        query_builder = sqlx::query("UPDATE invoices SET updated_at = ?, currency = ?, version = COALESCE(version, 0) + 1 WHERE id = ?")
             .bind(&now)
             .bind(currency)
             .bind(id);
     }
     if let Some(issue_date) = issue_date {
-        query_builder = sqlx::query("UPDATE invoices SET updated_at = ?, issue_date = ?, sync_status = 'pending' WHERE id = ?")
+        query_builder = sqlx::query("UPDATE invoices SET updated_at = ?, issue_date = ?, version = COALESCE(version, 0) + 1 WHERE id = ?")
             .bind(&now)
             .bind(issue_date)
             .bind(id);
     }
     if let Some(due_date) = due_date {
-        query_builder = sqlx::query("UPDATE invoices SET updated_at = ?, due_date = ?, sync_status = 'pending' WHERE id = ?")
+        query_builder = sqlx::query("UPDATE invoices SET updated_at = ?, due_date = ?, version = COALESCE(version, 0) + 1 WHERE id = ?")
             .bind(&now)
             .bind(due_date)
             .bind(id);
     }
     if let Some(paid_date) = paid_date {
-        query_builder = sqlx::query("UPDATE invoices SET updated_at = ?, paid_date = ?, sync_status = 'pending' WHERE id = ?")
+        query_builder = sqlx::query("UPDATE invoices SET updated_at = ?, paid_date = ?, version = COALESCE(version, 0) + 1 WHERE id = ?")
             .bind(&now)
             .bind(paid_date)
             .bind(id);
     }
     if let Some(purchase_order) = purchase_order {
-        query_builder = sqlx::query("UPDATE invoices SET updated_at = ?, purchase_order = ?, sync_status = 'pending' WHERE id = ?")
+        query_builder = sqlx::query("UPDATE invoices SET updated_at = ?, purchase_order = ?, version = COALESCE(version, 0) + 1 WHERE id = ?")
             .bind(&now)
             .bind(purchase_order)
             .bind(id);
     }
     if let Some(purchase_order_date) = purchase_order_date {
-        query_builder = sqlx::query("UPDATE invoices SET updated_at = ?, purchase_order_date = ?, sync_status = 'pending' WHERE id = ?")
+        query_builder = sqlx::query("UPDATE invoices SET updated_at = ?, purchase_order_date = ?, version = COALESCE(version, 0) + 1 WHERE id = ?")
             .bind(&now)
             .bind(purchase_order_date)
             .bind(id);
     }
     if let Some(commercial_in_charge) = commercial_in_charge {
-        query_builder = sqlx::query("UPDATE invoices SET updated_at = ?, commercial_in_charge = ?, sync_status = 'pending' WHERE id = ?")
+        query_builder = sqlx::query("UPDATE invoices SET updated_at = ?, commercial_in_charge = ?, version = COALESCE(version, 0) + 1 WHERE id = ?")
             .bind(&now)
             .bind(commercial_in_charge)
             .bind(id);
     }
     if let Some(notes) = notes {
-        query_builder = sqlx::query("UPDATE invoices SET updated_at = ?, notes = ?, sync_status = 'pending' WHERE id = ?")
+        query_builder = sqlx::query("UPDATE invoices SET updated_at = ?, notes = ?, version = COALESCE(version, 0) + 1 WHERE id = ?")
             .bind(&now)
             .bind(notes)
             .bind(id);
@@ -1416,15 +2329,6 @@ pub async fn update_invoice(
     Ok(invoice)
 }
 
-pub async fn delete_invoice(pool: &DbPool, id: &str) -> Result<(), sqlx::Error> {
-    sqlx::query("DELETE FROM invoices WHERE id = ?")
-        .bind(id)
-        .execute(pool)
-        .await?;
-
-    Ok(())
-}
-
 pub async fn get_invoices_by_status(pool: &DbPool, status: &str) -> Result<Vec<InvoiceWithDetails>, sqlx::Error> {
     let invoices = sqlx::query_as::<_, InvoiceWithDetails>(
         "SELECT 
@@ -1434,7 +2338,7 @@ pub async fn get_invoices_by_status(pool: &DbPool, status: &str) -> Result<Vec<I
         FROM invoices i
         JOIN proposals p ON i.proposal_id = p.id
         JOIN companies c ON p.company_id = c.id
-        WHERE i.status = ?
+        WHERE i.status = ? AND COALESCE(i.is_deleted, 0) = 0
         ORDER BY i.created_at DESC"
     )
     .bind(status)
@@ -1453,7 +2357,7 @@ pub async fn get_invoices_by_company(pool: &DbPool, company_id: &str) -> Result<
         FROM invoices i
         JOIN proposals p ON i.proposal_id = p.id
         JOIN companies c ON p.company_id = c.id
-        WHERE p.company_id = ?
+        WHERE p.company_id = ? AND COALESCE(i.is_deleted, 0) = 0
         ORDER BY i.created_at DESC"
     )
     .bind(company_id)
@@ -1464,7 +2368,7 @@ pub async fn get_invoices_by_company(pool: &DbPool, company_id: &str) -> Result<
 }
 
 pub async fn invoice_exists_for_proposal(pool: &DbPool, proposal_id: &str) -> Result<bool, sqlx::Error> {
-    let count: (i64,) = sqlx::query_as("SELECT COUNT(*) FROM invoices WHERE proposal_id = ?")
+    let count: (i64,) = sqlx::query_as("SELECT COUNT(*) FROM invoices WHERE proposal_id = ? AND COALESCE(is_deleted, 0) = 0")
         .bind(proposal_id)
         .fetch_one(pool)
         .await?;
@@ -1472,23 +2376,39 @@ pub async fn invoice_exists_for_proposal(pool: &DbPool, proposal_id: &str) -> Re
     Ok(count.0 > 0)
 }
 
-pub async fn generate_invoice_number(pool: &DbPool) -> Result<String, sqlx::Error> {
-    let now = Utc::now();
-    let year = now.year() % 100;
-    let month = now.month();
+/// Prochain numéro de facture : rang de la facture dans la période du
+/// format (`numbering::sequence_period`), en sautant les numéros déjà
+/// attribués (facture supprimée puis purgée, ou reçue d'un autre poste).
+pub async fn generate_invoice_number(pool: &DbPool, format: &str) -> Result<String, sqlx::Error> {
+    let today = Utc::now().date_naive();
+
+    let count: (i64,) = match numbering::sequence_period(format) {
+        Some(period) => {
+            sqlx::query_as("SELECT COUNT(*) FROM invoices WHERE strftime(?, issue_date) = ?")
+                .bind(period)
+                .bind(today.format(period).to_string())
+                .fetch_one(pool)
+                .await?
+        }
+        None => sqlx::query_as("SELECT COUNT(*) FROM invoices").fetch_one(pool).await?,
+    };
 
-    // Count existing invoices for current month
-    let count: (i64,) = sqlx::query_as(
-        "SELECT COUNT(*) FROM invoices 
-         WHERE strftime('%Y', issue_date) = ? AND strftime('%m', issue_date) = ?"
-    )
-    .bind(now.year().to_string())
-    .bind(format!("{:02}", month))
-    .fetch_one(pool)
-    .await?;
+    let mut sequence = count.0 + 1;
+    loop {
+        let number = numbering::render(format, today, &[("SEQ", &format!("{:04}", sequence))]);
+        if !invoice_number_exists(pool, &number).await? {
+            return Ok(number);
+        }
+        sequence += 1;
+    }
+}
 
-    let next_number = count.0 + 1;
-    Ok(format!("{:02}{:02}{:04}", year, month, next_number))
+async fn invoice_number_exists(pool: &DbPool, invoice_number: &str) -> Result<bool, sqlx::Error> {
+    let count: (i64,) = sqlx::query_as("SELECT COUNT(*) FROM invoices WHERE invoice_number = ?")
+        .bind(invoice_number)
+        .fetch_one(pool)
+        .await?;
+    Ok(count.0 > 0)
 }
 
 // add_to_deletion_queue - REMOVED as part of synchronization cleanup
@@ -1506,4 +2426,1180 @@ pub async fn generate_invoice_number(pool: &DbPool) -> Result<String, sqlx::Erro
 // === DIRECT DELETION FUNCTIONS (for sync) - REMOVED ===
 // These functions were removed as part of synchronization cleanup
 
+// Search queries
+
+/// Transforme la saisie de l'utilisateur en requête FTS5 : chaque mot est
+/// cherché comme préfixe et tous doivent être présents. Les guillemets sont
+/// doublés pour que la syntaxe FTS5 (AND, NEAR, *, :) reste du texte.
+pub fn search_match_expression(query: &str) -> Option<String> {
+    let terms: Vec<String> = query
+        .split_whitespace()
+        .map(|term| format!("\"{}\"*", term.replace('"', "\"\"")))
+        .collect();
+    if terms.is_empty() {
+        None
+    } else {
+        Some(terms.join(" "))
+    }
+}
+
+/// Recherche dans l'index plein texte, les correspondances dans le titre
+/// pesant plus que celles du texte.
+pub async fn global_search(
+    pool: &DbPool,
+    query: &str,
+    entity_types: &[String],
+    limit: i64,
+) -> Result<Vec<SearchHit>, sqlx::Error> {
+    let Some(expression) = search_match_expression(query) else {
+        return Ok(Vec::new());
+    };
+
+    let mut sql = String::from(
+        "SELECT entity_type, entity_id, parent_id,
+                highlight(search_index, 3, '<mark>', '</mark>') AS title,
+                snippet(search_index, 4, '<mark>', '</mark>', '…', 16) AS snippet,
+                bm25(search_index, 0.0, 0.0, 0.0, 10.0, 1.0) AS rank
+         FROM search_index
+         WHERE search_index MATCH ?"
+    );
+    if !entity_types.is_empty() {
+        let placeholders = vec!["?"; entity_types.len()].join(", ");
+        sql.push_str(&format!(" AND entity_type IN ({})", placeholders));
+    }
+    sql.push_str(" ORDER BY rank LIMIT ?");
+
+    let mut statement = sqlx::query_as::<_, SearchHit>(&sql).bind(expression);
+    for entity_type in entity_types {
+        statement = statement.bind(entity_type);
+    }
+    let hits = statement.bind(limit).fetch_all(pool).await?;
+
+    Ok(hits)
+}
+
+// List queries
+
+const DEFAULT_PAGE_SIZE: i64 = 50;
+const MAX_PAGE_SIZE: i64 = 500;
+
+const COMPANY_SORTS: &[(&str, &str)] = &[
+    ("name", "c.name COLLATE NOCASE"),
+    ("city", "c.city COLLATE NOCASE"),
+    ("country", "c.country COLLATE NOCASE"),
+    ("created_at", "c.created_at"),
+    ("updated_at", "c.updated_at"),
+];
+
+const PROPOSAL_SORTS: &[(&str, &str)] = &[
+    ("created_at", "p.created_at"),
+    ("updated_at", "p.updated_at"),
+    ("proposal_number", "p.proposal_number"),
+    ("company_name", "COALESCE(c.name, 'Unknown') COLLATE NOCASE"),
+    ("status", "p.status"),
+    ("total_amount", "p.total_amount"),
+    ("valid_until", "p.valid_until"),
+];
+
+const INVOICE_SORTS: &[(&str, &str)] = &[
+    ("created_at", "i.created_at"),
+    ("issue_date", "i.issue_date"),
+    ("due_date", "i.due_date"),
+    ("invoice_number", "i.invoice_number"),
+    ("company_name", "c.name COLLATE NOCASE"),
+    ("status", "i.status"),
+    ("total_amount", "i.total_amount"),
+];
+
+const DOCUMENT_SORTS: &[(&str, &str)] = &[
+    ("created_at", "d.created_at"),
+    ("updated_at", "d.updated_at"),
+    ("title", "d.title COLLATE NOCASE"),
+    ("document_type", "d.document_type"),
+];
+
+/// Clause WHERE construite au fil des filtres, avec ses paramètres
+struct ListFilter {
+    clauses: Vec<String>,
+    binds: Vec<String>,
+}
+
+impl ListFilter {
+    fn new(base: &str) -> Self {
+        Self { clauses: vec![base.to_string()], binds: Vec::new() }
+    }
+
+    fn equals(&mut self, column: &str, value: &Option<String>) {
+        if let Some(value) = value.as_deref().filter(|v| !v.is_empty()) {
+            self.clauses.push(format!("{} = ?", column));
+            self.binds.push(value.to_string());
+        }
+    }
+
+    /// Texte contenu dans l'une des colonnes, sans tenir compte de la casse
+    fn contains(&mut self, columns: &[&str], value: &Option<String>) {
+        if let Some(value) = value.as_deref().map(str::trim).filter(|v| !v.is_empty()) {
+            let pattern = format!(
+                "%{}%",
+                value.replace('\\', "\\\\").replace('%', "\\%").replace('_', "\\_")
+            );
+            let alternatives: Vec<String> = columns
+                .iter()
+                .map(|column| format!("{} LIKE ? ESCAPE '\\'", column))
+                .collect();
+            self.clauses.push(format!("({})", alternatives.join(" OR ")));
+            self.binds.extend(columns.iter().map(|_| pattern.clone()));
+        }
+    }
+
+    fn sql(&self) -> String {
+        format!("WHERE {}", self.clauses.join(" AND "))
+    }
+}
+
+/// Tri d'une liste : expression de tri et identifiant pour départager les
+/// égalités, dans le même sens
+struct ListOrder {
+    expression: &'static str,
+    tiebreak: &'static str,
+    descending: bool,
+}
+
+impl ListOrder {
+    /// Tri demandé, ou par défaut sur la première colonne de `sorts` dans le
+    /// sens `default_descending`
+    fn new(
+        query: &ListQuery,
+        sorts: &[(&'static str, &'static str)],
+        default_descending: bool,
+        tiebreak: &'static str,
+    ) -> Result<Self, sqlx::Error> {
+        let (_, expression) = match query.sort_by.as_deref() {
+            Some(key) => sorts.iter().find(|(name, _)| *name == key).ok_or_else(|| {
+                let names: Vec<&str> = sorts.iter().map(|(name, _)| *name).collect();
+                sqlx::Error::Protocol(format!("Invalid sort_by: {} (expected one of: {})", key, names.join(", ")))
+            })?,
+            None => &sorts[0],
+        };
+        Ok(Self {
+            expression,
+            tiebreak,
+            descending: query.descending.unwrap_or(query.sort_by.is_none() && default_descending),
+        })
+    }
+
+    fn sql(&self) -> String {
+        let direction = if self.descending { "DESC" } else { "ASC" };
+        format!("ORDER BY {} {}, {} {}", self.expression, direction, self.tiebreak, direction)
+    }
+
+    /// Condition des lignes situées après `cursor`. SQLite place les NULL
+    /// en tête en ordre croissant et en fin en ordre décroissant.
+    fn after(&self, cursor: &ListCursor) -> String {
+        let (key, id) = (self.expression, self.tiebreak);
+        match (cursor.key.is_null(), self.descending) {
+            (true, false) => format!("(({key}) IS NULL AND {id} > ? OR ({key}) IS NOT NULL)"),
+            (true, true) => format!("(({key}) IS NULL AND {id} < ?)"),
+            (false, false) => format!("({key} > ? OR {key} = ? AND {id} > ?)"),
+            (false, true) => format!("({key} < ? OR {key} = ? AND {id} < ? OR ({key}) IS NULL)"),
+        }
+    }
+}
+
+/// Valeur de tri et identifiant de la dernière ligne d'une page, transmis au
+/// client sous forme opaque
+#[derive(Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+struct ListCursor {
+    key: serde_json::Value,
+    id: String,
+}
+
+impl ListCursor {
+    fn encode(&self) -> String {
+        base64::engine::general_purpose::URL_SAFE_NO_PAD.encode(serde_json::to_vec(self).unwrap_or_default())
+    }
+
+    fn decode(cursor: &str) -> Result<Self, sqlx::Error> {
+        base64::engine::general_purpose::URL_SAFE_NO_PAD
+            .decode(cursor)
+            .ok()
+            .and_then(|bytes| serde_json::from_slice(&bytes).ok())
+            .ok_or_else(|| sqlx::Error::Protocol(format!("Invalid cursor: {}", cursor)))
+    }
+
+    fn from_row(row: &SqliteRow) -> Result<Self, sqlx::Error> {
+        let raw = row.try_get_raw("sort_key")?;
+        let key = if raw.is_null() {
+            serde_json::Value::Null
+        } else {
+            match raw.type_info().name() {
+                "INTEGER" => row.try_get::<i64, _>("sort_key")?.into(),
+                "REAL" => row.try_get::<f64, _>("sort_key")?.into(),
+                _ => row.try_get::<String, _>("sort_key")?.into(),
+            }
+        };
+        Ok(Self { key, id: row.try_get("sort_id")? })
+    }
+}
+
+/// Exécute la requête de page, après le curseur éventuel, et celle du total
+async fn fetch_page<T>(
+    pool: &DbPool,
+    select: &str,
+    from: &str,
+    filter: &ListFilter,
+    order: &ListOrder,
+    query: &ListQuery,
+) -> Result<(Vec<T>, i64, Option<String>), sqlx::Error>
+where
+    T: for<'r> sqlx::FromRow<'r, SqliteRow> + Send + Unpin,
+{
+    let limit = query.limit.unwrap_or(DEFAULT_PAGE_SIZE).clamp(1, MAX_PAGE_SIZE);
+    let cursor = query
+        .cursor
+        .as_deref()
+        .filter(|c| !c.is_empty())
+        .map(ListCursor::decode)
+        .transpose()?;
+
+    let count_sql = format!("SELECT COUNT(*) {} {}", from, filter.sql());
+    let mut count = sqlx::query_scalar::<_, i64>(&count_sql);
+    for value in &filter.binds {
+        count = count.bind(value);
+    }
+    let total = count.fetch_one(pool).await?;
+
+    let mut condition = filter.sql();
+    if let Some(cursor) = &cursor {
+        condition = format!("{} AND {}", condition, order.after(cursor));
+    }
+    let page_sql = format!(
+        "SELECT {}, {} AS sort_key, {} AS sort_id {} {} {} LIMIT ?",
+        select,
+        order.expression,
+        order.tiebreak,
+        from,
+        condition,
+        order.sql()
+    );
+    let mut page = sqlx::query(&page_sql);
+    for value in &filter.binds {
+        page = page.bind(value);
+    }
+    if let Some(cursor) = &cursor {
+        if !cursor.key.is_null() {
+            for _ in 0..2 {
+                page = match &cursor.key {
+                    serde_json::Value::Number(n) if n.is_i64() => page.bind(n.as_i64()),
+                    serde_json::Value::Number(n) => page.bind(n.as_f64()),
+                    serde_json::Value::String(s) => page.bind(s.clone()),
+                    other => page.bind(other.to_string()),
+                };
+            }
+        }
+        page = page.bind(cursor.id.clone());
+    }
+    // Une ligne de plus pour savoir s'il reste une page
+    let mut rows = page.bind(limit + 1).fetch_all(pool).await?;
+
+    let next_cursor = if rows.len() as i64 > limit {
+        rows.truncate(limit as usize);
+        rows.last().map(ListCursor::from_row).transpose()?.map(|c| c.encode())
+    } else {
+        None
+    };
+    let items = rows.iter().map(T::from_row).collect::<Result<Vec<T>, _>>()?;
+
+    Ok((items, total, next_cursor))
+}
+
+fn placeholders(count: usize) -> String {
+    vec!["?"; count].join(", ")
+}
+
+/// Contacts de plusieurs sociétés en une requête, les mêmes que ceux
+/// renvoyés société par société par `get_company_contacts`
+pub async fn get_contacts_for_companies(
+    pool: &DbPool,
+    company_ids: &[String],
+) -> Result<Vec<CompanyContact>, sqlx::Error> {
+    if company_ids.is_empty() {
+        return Ok(Vec::new());
+    }
+    let sql = format!(
+        "SELECT * FROM company_contacts WHERE company_id IN ({}) AND COALESCE(is_deleted, 0) = 0
+         ORDER BY is_primary DESC, first_name ASC",
+        placeholders(company_ids.len())
+    );
+    let mut statement = sqlx::query_as::<_, CompanyContact>(&sql);
+    for id in company_ids {
+        statement = statement.bind(id);
+    }
+    statement.fetch_all(pool).await
+}
+
+/// Produits de plusieurs propositions en une requête, les mêmes que ceux
+/// renvoyés proposition par proposition par `get_proposal_products`
+pub async fn get_products_for_proposals(
+    pool: &DbPool,
+    proposal_ids: &[String],
+) -> Result<Vec<ProposalProduct>, sqlx::Error> {
+    if proposal_ids.is_empty() {
+        return Ok(Vec::new());
+    }
+    let sql = format!(
+        "SELECT * FROM proposal_products WHERE proposal_id IN ({}) AND COALESCE(is_deleted, 0) = 0",
+        placeholders(proposal_ids.len())
+    );
+    let mut statement = sqlx::query_as::<_, ProposalProduct>(&sql);
+    for id in proposal_ids {
+        statement = statement.bind(id);
+    }
+    statement.fetch_all(pool).await
+}
 
+/// Associe à chaque société ses contacts
+pub async fn with_contacts(
+    pool: &DbPool,
+    companies: Vec<Company>,
+) -> Result<Vec<CompanyWithContacts>, sqlx::Error> {
+    let ids: Vec<String> = companies.iter().map(|c| c.id.clone()).collect();
+    let mut contacts: HashMap<String, Vec<CompanyContact>> = HashMap::new();
+    for contact in get_contacts_for_companies(pool, &ids).await? {
+        contacts.entry(contact.company_id.clone()).or_default().push(contact);
+    }
+
+    Ok(companies
+        .into_iter()
+        .map(|company| CompanyWithContacts {
+            contacts: contacts.remove(&company.id).unwrap_or_default(),
+            company,
+        })
+        .collect())
+}
+
+#[derive(sqlx::FromRow)]
+struct ProposalRow {
+    #[sqlx(flatten)]
+    proposal: Proposal,
+    company_name: String,
+}
+
+/// Associe à chaque proposition ses produits
+async fn with_products(
+    pool: &DbPool,
+    rows: Vec<ProposalRow>,
+) -> Result<Vec<ProposalWithProducts>, sqlx::Error> {
+    let ids: Vec<String> = rows.iter().map(|r| r.proposal.id.clone()).collect();
+    let mut products: HashMap<String, Vec<ProposalProduct>> = HashMap::new();
+    for product in get_products_for_proposals(pool, &ids).await? {
+        products.entry(product.proposal_id.clone()).or_default().push(product);
+    }
+
+    Ok(rows
+        .into_iter()
+        .map(|row| ProposalWithProducts {
+            products: products.remove(&row.proposal.id).unwrap_or_default(),
+            proposal: row.proposal,
+            company_name: row.company_name,
+        })
+        .collect())
+}
+
+pub async fn list_companies(pool: &DbPool, query: &ListQuery) -> Result<Page<CompanyWithContacts>, sqlx::Error> {
+    let mut filter = ListFilter::new("COALESCE(c.is_deleted, 0) = 0");
+    filter.contains(&["c.name", "c.city", "c.country", "c.website"], &query.search);
+    let order = ListOrder::new(query, COMPANY_SORTS, false, "c.id")?;
+
+    let (companies, total, next_cursor) =
+        fetch_page::<Company>(pool, "c.*", "FROM companies c", &filter, &order, query).await?;
+    let items = with_contacts(pool, companies).await?;
+    Ok(Page { items, total, next_cursor })
+}
+
+pub async fn list_proposals(pool: &DbPool, query: &ListQuery) -> Result<Page<ProposalWithProducts>, sqlx::Error> {
+    let mut filter = ListFilter::new("COALESCE(p.is_deleted, 0) = 0");
+    filter.equals("p.status", &query.status);
+    filter.equals("p.company_id", &query.company_id);
+    filter.contains(&["p.proposal_number", "c.name", "p.notes"], &query.search);
+    let order = ListOrder::new(query, PROPOSAL_SORTS, true, "p.id")?;
+
+    let (rows, total, next_cursor) = fetch_page::<ProposalRow>(
+        pool,
+        "p.*, COALESCE(c.name, 'Unknown') AS company_name",
+        "FROM proposals p LEFT JOIN companies c ON p.company_id = c.id",
+        &filter,
+        &order,
+        query,
+    )
+    .await?;
+    let items = with_products(pool, rows).await?;
+    Ok(Page { items, total, next_cursor })
+}
+
+/// Toutes les propositions avec leurs produits et le nom de la société
+pub async fn get_all_proposals_with_products(pool: &DbPool) -> Result<Vec<ProposalWithProducts>, sqlx::Error> {
+    let rows = sqlx::query_as::<_, ProposalRow>(
+        "SELECT p.*, COALESCE(c.name, 'Unknown') AS company_name
+         FROM proposals p LEFT JOIN companies c ON p.company_id = c.id
+         WHERE COALESCE(p.is_deleted, 0) = 0
+         ORDER BY p.created_at DESC"
+    )
+    .fetch_all(pool)
+    .await?;
+    with_products(pool, rows).await
+}
+
+pub async fn list_invoices(pool: &DbPool, query: &ListQuery) -> Result<Page<InvoiceWithDetails>, sqlx::Error> {
+    let mut filter = ListFilter::new("COALESCE(i.is_deleted, 0) = 0");
+    filter.equals("i.status", &query.status);
+    filter.equals("p.company_id", &query.company_id);
+    filter.contains(
+        &["i.invoice_number", "i.purchase_order", "p.proposal_number", "c.name", "i.notes"],
+        &query.search,
+    );
+    let order = ListOrder::new(query, INVOICE_SORTS, true, "i.id")?;
+
+    let (items, total, next_cursor) = fetch_page::<InvoiceWithDetails>(
+        pool,
+        "i.*, p.proposal_number, c.name AS company_name",
+        "FROM invoices i JOIN proposals p ON i.proposal_id = p.id JOIN companies c ON p.company_id = c.id",
+        &filter,
+        &order,
+        query,
+    )
+    .await?;
+    Ok(Page { items, total, next_cursor })
+}
+
+pub async fn list_documents(pool: &DbPool, query: &ListQuery) -> Result<Page<Document>, sqlx::Error> {
+    let mut filter = ListFilter::new("COALESCE(d.is_deleted, 0) = 0");
+    filter.equals("d.document_type", &query.document_type);
+    if let Some(company_id) = query.company_id.as_deref().filter(|id| !id.is_empty()) {
+        filter.clauses.push(
+            "EXISTS (SELECT 1 FROM document_links l WHERE l.document_id = d.id AND l.entity_type = 'company'
+                     AND l.entity_id = ? AND COALESCE(l.is_deleted, 0) = 0)"
+                .to_string(),
+        );
+        filter.binds.push(company_id.to_string());
+    }
+    filter.contains(&["d.title", "d.content"], &query.search);
+    let order = ListOrder::new(query, DOCUMENT_SORTS, true, "d.id")?;
+
+    let (items, total, next_cursor) =
+        fetch_page::<Document>(pool, DOCUMENT_COLUMNS, "FROM documents d", &filter, &order, query).await?;
+    Ok(Page { items, total, next_cursor })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sqlx::sqlite::{SqliteConnectOptions, SqlitePool};
+    use std::str::FromStr;
+
+    // Base migrée, clés étrangères désactivées : chaque entité est écrite
+    // seule avec des identifiants fictifs
+    async fn open() -> (DbPool, std::path::PathBuf) {
+        let path = std::env::temp_dir().join(format!("crm-sync-{}.db", Uuid::new_v4()));
+        let options = SqliteConnectOptions::from_str(&format!("sqlite:{}", path.display()))
+            .unwrap()
+            .create_if_missing(true)
+            .foreign_keys(false);
+        let pool = SqlitePool::connect_with(options).await.unwrap();
+        crate::db::migrations::run_migrations(&pool).await.unwrap();
+        (pool, path)
+    }
+
+    #[tokio::test]
+    async fn local_columns_match_shared_schema() {
+        let (pool, path) = open().await;
+        for entity in ENTITIES {
+            let columns: Vec<(String, String)> = sqlx::query_as("SELECT name, type FROM pragma_table_info(?)")
+                .bind(entity.table)
+                .fetch_all(&pool)
+                .await
+                .unwrap();
+            for column in entity.columns {
+                let declared = columns.iter().find(|(name, _)| name == column.name).map(|(_, ty)| ty.as_str());
+                assert!(
+                    declared.is_some_and(|ty| column.ty.storage_types(Dialect::Sqlite).contains(&ty)),
+                    "{}.{} declared as {:?}",
+                    entity.table,
+                    column.name,
+                    declared
+                );
+            }
+        }
+        pool.close().await;
+        let _ = std::fs::remove_file(path);
+    }
+
+    #[tokio::test]
+    async fn every_entity_round_trips_through_sqlite() {
+        let (pool, path) = open().await;
+        for entity in ENTITIES {
+            let record = entity.sample_record(&format!("{}-1", entity.table));
+            let item = SyncItem::from_record(entity, record).unwrap();
+            apply_sync_item(&pool, &item).await.unwrap();
+
+            let emitted = get_changed_items_since(&pool, 0).await.unwrap().items;
+            let back = emitted.iter().find(|i| i.entity() == entity && i.id() == item.id()).unwrap();
+            assert_eq!(back, &item, "{}", entity.table);
+        }
+
+        // Une date vide saisie localement part comme absente
+        sqlx::query("UPDATE proposals SET valid_until = '' WHERE id = 'proposals-1'")
+            .execute(&pool)
+            .await
+            .unwrap();
+        let items = get_changed_items_since(&pool, 0).await.unwrap().items;
+        let proposal = items.iter().find(|i| i.id() == "proposals-1").unwrap();
+        assert!(matches!(proposal, SyncItem::Proposal(p) if p.valid_until.is_none()));
+
+        pool.close().await;
+        let _ = std::fs::remove_file(path);
+    }
+
+    async fn add_template(pool: &DbPool, name: &str, kind: &str) -> DocumentTemplate {
+        create_document_template(
+            pool,
+            name.to_string(),
+            kind.to_string(),
+            None,
+            None,
+            None,
+            "template.docx".to_string(),
+            "AA==".to_string(),
+            "00".to_string(),
+        )
+        .await
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn template_versions_are_numbered_per_kind_and_name() {
+        let (pool, path) = open().await;
+        assert_eq!(add_template(&pool, "Standard", "proposal").await.template_version, 1);
+        assert_eq!(add_template(&pool, "Standard", "proposal").await.template_version, 2);
+        assert_eq!(add_template(&pool, "Standard", "invoice").await.template_version, 1);
+        assert_eq!(add_template(&pool, "Premium", "proposal").await.template_version, 1);
+
+        // Imports simultanés : chacun reçoit sa propre version
+        let mut imports = tokio::task::JoinSet::new();
+        for _ in 0..8 {
+            let pool = pool.clone();
+            imports.spawn(async move { add_template(&pool, "Concurrent", "proposal").await.template_version });
+        }
+        let mut versions = Vec::new();
+        while let Some(version) = imports.join_next().await {
+            versions.push(version.unwrap());
+        }
+        versions.sort();
+        assert_eq!(versions, (1..=8).collect::<Vec<i64>>());
+
+        let listed = get_document_templates(&pool, Some("proposal")).await.unwrap();
+        assert!(listed.iter().all(|t| t.content.is_none()));
+        pool.close().await;
+        let _ = std::fs::remove_file(path);
+    }
+
+    #[tokio::test]
+    async fn document_revision_follows_source_changes() {
+        let (pool, path) = open().await;
+        let archive = |revision: i64| {
+            create_archived_document(
+                &pool,
+                format!("Proposal_1_r{}.docx", revision),
+                "quote".to_string(),
+                "/tmp/Proposal_1.docx".to_string(),
+                "proposal",
+                "p1",
+                revision,
+                "00".to_string(),
+            )
+        };
+
+        assert_eq!(next_document_revision(&pool, "proposal", "p1", 0).await.unwrap(), 1);
+        let first = archive(1).await.unwrap();
+        let generated_at: i64 = first.created_at.parse().unwrap();
+
+        // Proposition inchangée (ex. PDF après le Word) : même révision
+        assert_eq!(next_document_revision(&pool, "proposal", "p1", generated_at).await.unwrap(), 1);
+        // Modifiée depuis la génération : révision suivante
+        assert_eq!(next_document_revision(&pool, "proposal", "p1", generated_at + 1).await.unwrap(), 2);
+        let second = archive(2).await.unwrap();
+        let generated_at: i64 = second.created_at.parse().unwrap();
+        assert_eq!(next_document_revision(&pool, "proposal", "p1", generated_at).await.unwrap(), 2);
+        // Compteur propre à chaque enregistrement
+        assert_eq!(next_document_revision(&pool, "invoice", "p1", 0).await.unwrap(), 1);
+        pool.close().await;
+        let _ = std::fs::remove_file(path);
+    }
+
+    #[tokio::test]
+    async fn document_company_and_customer_come_from_links() {
+        let (pool, path) = open().await;
+        sqlx::query("INSERT INTO companies (id, name, created_at, updated_at) VALUES ('co1', 'Acme', '1', '1')")
+            .execute(&pool)
+            .await
+            .unwrap();
+        sqlx::query("INSERT INTO customers (id, name, created_at, updated_at, is_deleted) VALUES ('cu1', 'Old', '1', '1', 1)")
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        let document = create_document(&pool, "Contrat".to_string(), "contract".to_string(), None, None, None)
+            .await
+            .unwrap();
+        let (customer_id, company_id): (Option<String>, Option<String>) =
+            sqlx::query_as("SELECT customer_id, company_id FROM documents WHERE id = ?")
+                .bind(&document.id)
+                .fetch_one(&pool)
+                .await
+                .unwrap();
+        assert_eq!((customer_id, company_id), (None, None));
+
+        link_document(&pool, &document.id, "company", "co1").await.unwrap();
+        let read = get_document_by_id(&pool, &document.id).await.unwrap().unwrap();
+        assert_eq!((read.customer_id, read.company_id.as_deref()), (None, Some("co1")));
+        let listed = list_documents(&pool, &ListQuery { company_id: Some("co1".to_string()), ..Default::default() })
+            .await
+            .unwrap();
+        assert_eq!(listed.items[0].company_id.as_deref(), Some("co1"));
+
+        unlink_document(&pool, &document.id, Some(("company", "co1"))).await.unwrap();
+        let read = get_document_by_id(&pool, &document.id).await.unwrap().unwrap();
+        assert_eq!(read.company_id, None);
+
+        assert!(entity_exists(&pool, "company", "co1").await.unwrap());
+        assert!(!entity_exists(&pool, "company", "co2").await.unwrap());
+        // Supprimé
+        assert!(!entity_exists(&pool, "customer", "cu1").await.unwrap());
+        assert!(entity_exists(&pool, "companies", "co1").await.is_err());
+        pool.close().await;
+        let _ = std::fs::remove_file(path);
+    }
+
+    async fn company_ids(pool: &DbPool, query: &ListQuery) -> Result<Vec<String>, sqlx::Error> {
+        let mut ids = Vec::new();
+        let mut query = ListQuery { limit: Some(2), ..query.clone() };
+        loop {
+            let page = list_companies(pool, &query).await?;
+            assert_eq!(page.total, 7);
+            ids.extend(page.items.into_iter().map(|c| c.company.id));
+            match page.next_cursor {
+                Some(cursor) => query.cursor = Some(cursor),
+                None => return Ok(ids),
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn list_pages_follow_keyset_cursor() {
+        let (pool, path) = open().await;
+        for (id, city) in [("a", Some("Lyon")), ("b", None), ("c", Some("lyon")), ("d", Some("Paris")), ("e", None), ("f", Some("Lyon")), ("g", Some("Brest"))] {
+            sqlx::query("INSERT INTO companies (id, name, city, created_at, updated_at) VALUES (?, ?, ?, '1', '1')")
+                .bind(id)
+                .bind(format!("Société {}", id))
+                .bind(city)
+                .execute(&pool)
+                .await
+                .unwrap();
+        }
+
+        // Égalités départagées par l'identifiant, NULL en tête en ordre croissant
+        let by_city = ListQuery { sort_by: Some("city".to_string()), ..Default::default() };
+        assert_eq!(company_ids(&pool, &by_city).await.unwrap(), ["b", "e", "g", "a", "c", "f", "d"]);
+        let by_city_desc = ListQuery { descending: Some(true), ..by_city.clone() };
+        assert_eq!(company_ids(&pool, &by_city_desc).await.unwrap(), ["d", "f", "c", "a", "g", "e", "b"]);
+
+        // Une ligne ajoutée avant le curseur ne décale pas la page suivante
+        let first = list_companies(&pool, &ListQuery { limit: Some(3), ..by_city.clone() }).await.unwrap();
+        sqlx::query("INSERT INTO companies (id, name, city, created_at, updated_at) VALUES ('0', 'Avant', 'Angers', '1', '1')")
+            .execute(&pool)
+            .await
+            .unwrap();
+        let next = ListQuery { limit: Some(10), cursor: first.next_cursor, ..by_city.clone() };
+        let rest: Vec<String> = list_companies(&pool, &next).await.unwrap().items.into_iter().map(|c| c.company.id).collect();
+        assert_eq!(rest, ["a", "c", "f", "d"]);
+
+        let error = list_companies(&pool, &ListQuery { sort_by: Some("name; DROP TABLE companies".to_string()), ..Default::default() })
+            .await
+            .unwrap_err()
+            .to_string();
+        assert!(error.contains("Invalid sort_by") && error.contains("name, city"), "{}", error);
+        let error = list_companies(&pool, &ListQuery { cursor: Some("12".to_string()), ..Default::default() })
+            .await
+            .unwrap_err();
+        assert!(error.to_string().contains("Invalid cursor"));
+        pool.close().await;
+        let _ = std::fs::remove_file(path);
+    }
+
+    #[tokio::test]
+    async fn list_pages_resume_after_numeric_and_joined_keys() {
+        let (pool, path) = open().await;
+        let execute = |sql: &'static str| sqlx::query(sql).execute(&pool);
+        execute("INSERT INTO companies (id, name, created_at, updated_at) VALUES ('co1', 'beta', '1', '1'), ('co2', 'Alpha', '1', '1')").await.unwrap();
+        execute("INSERT INTO proposals (id, company_id, proposal_number, status, total_amount, created_at, updated_at) VALUES
+                 ('p1', 'co1', 'P-1', 'draft', 0.30000000000000004, '1', '1'),
+                 ('p2', 'co2', 'P-2', 'draft', 0.3, '2', '2'),
+                 ('p3', 'co1', 'P-3', 'draft', 12, '3', '3'),
+                 ('p4', 'gone', 'P-4', 'draft', 0.3, '4', '4')").await.unwrap();
+
+        let mut seen = Vec::new();
+        for sort_by in ["total_amount", "company_name", "created_at"] {
+            let mut query = ListQuery { sort_by: Some(sort_by.to_string()), limit: Some(1), ..Default::default() };
+            let mut ids = Vec::new();
+            loop {
+                let page = list_proposals(&pool, &query).await.unwrap();
+                ids.extend(page.items.into_iter().map(|p| p.proposal.id));
+                match page.next_cursor {
+                    Some(cursor) => query.cursor = Some(cursor),
+                    None => break,
+                }
+            }
+            seen.push(ids);
+        }
+        assert_eq!(seen[0], ["p2", "p4", "p1", "p3"]);
+        assert_eq!(seen[1], ["p2", "p1", "p3", "p4"]);
+        assert_eq!(seen[2], ["p1", "p2", "p3", "p4"]);
+        pool.close().await;
+        let _ = std::fs::remove_file(path);
+    }
+
+    #[tokio::test]
+    async fn batch_getters_return_the_same_rows_as_single_getters() {
+        let (pool, path) = open().await;
+        let execute = |sql: &'static str| sqlx::query(sql).execute(&pool);
+        execute("INSERT INTO companies (id, name, created_at, updated_at) VALUES ('co1', 'Acme', '1', '1'), ('co2', 'Globex', '1', '1')").await.unwrap();
+        execute("INSERT INTO company_contacts (id, company_id, first_name, last_name, email, is_primary, created_at, updated_at, is_deleted) VALUES
+                 ('ct1', 'co1', 'Bob', 'A', 'bob@acme.test', 0, '1', '1', 0),
+                 ('ct2', 'co1', 'Alice', 'B', 'alice@acme.test', 1, '1', '1', 0),
+                 ('ct3', 'co1', 'Old', 'C', 'old@acme.test', 0, '1', '1', 1),
+                 ('ct4', 'co2', 'Carl', 'D', 'carl@globex.test', 0, '1', '1', NULL)").await.unwrap();
+        execute("INSERT INTO proposals (id, company_id, proposal_number, status, total_amount, created_at, updated_at, is_deleted) VALUES
+                 ('p1', 'co1', 'P-1', 'draft', 0, '1', '1', 0),
+                 ('p2', 'co2', 'P-2', 'draft', 0, '2', '2', 1)").await.unwrap();
+        execute("INSERT INTO proposal_products (id, proposal_id, product_type, user_count, created_at, updated_at, is_deleted) VALUES
+                 ('pp1', 'p1', 'licence', 1, '1', '1', 0),
+                 ('pp2', 'p1', 'support', 1, '1', '1', 1)").await.unwrap();
+
+        let companies = with_contacts(&pool, get_all_companies(&pool).await.unwrap()).await.unwrap();
+        for company in &companies {
+            let single = get_company_contacts(&pool, &company.company.id).await.unwrap();
+            let ids = |contacts: &[CompanyContact]| contacts.iter().map(|c| c.id.clone()).collect::<Vec<_>>();
+            assert_eq!(ids(&company.contacts), ids(&single));
+        }
+        assert_eq!(companies[0].contacts.iter().map(|c| c.id.as_str()).collect::<Vec<_>>(), ["ct2", "ct1"]);
+
+        // Comme `get_all_proposals` : pas de proposition à la corbeille
+        let proposals = get_all_proposals_with_products(&pool).await.unwrap();
+        assert_eq!(proposals.len(), get_all_proposals(&pool).await.unwrap().len());
+        assert_eq!(proposals[0].company_name, "Acme");
+        let single = get_proposal_products(&pool, "p1").await.unwrap();
+        assert_eq!(proposals[0].products.len(), single.len());
+        assert_eq!(single[0].id, "pp1");
+        pool.close().await;
+        let _ = std::fs::remove_file(path);
+    }
+
+    #[tokio::test]
+    async fn import_reuses_trashed_and_merged_companies() {
+        let (pool, path) = open().await;
+        sqlx::query(
+            "INSERT INTO companies (id, name, created_at, updated_at, is_deleted, merged_into) VALUES
+             ('old', 'Globex', '1', '1', 1, NULL), ('a', 'Initech', '1', '1', 1, 'b'), ('b', 'Initech Group', '1', '1', 0, NULL)"
+        )
+        .execute(&pool)
+        .await
+        .unwrap();
+        assert_eq!(get_all_companies_with_deleted(&pool).await.unwrap().len(), 3);
+
+        let mut conn = pool.acquire().await.unwrap();
+        assert!(restore_imported_company(&mut conn, "old").await.unwrap());
+        assert!(!restore_imported_company(&mut conn, "old").await.unwrap());
+        let survivor = resolve_merged_company(&mut conn, "a").await.unwrap();
+        assert_eq!(survivor, "b");
+        assert!(!restore_imported_company(&mut conn, "a").await.unwrap());
+        drop(conn);
+
+        let names: Vec<String> = get_all_companies(&pool).await.unwrap().into_iter().map(|c| c.name).collect();
+        assert_eq!(names, ["Globex", "Initech Group"]);
+        pool.close().await;
+        let _ = std::fs::remove_file(path);
+    }
+
+    #[tokio::test]
+    async fn trash_records_the_signed_in_user() {
+        let (pool, path) = open().await;
+        sqlx::query(
+            "INSERT INTO companies (id, name, created_at, updated_at) VALUES ('a', 'Acme', '1', '1'), ('b', 'Globex', '1', '1')"
+        )
+        .execute(&pool)
+        .await
+        .unwrap();
+
+        assert!(move_to_trash(&pool, "companies", "a", Some("ada@example.com")).await.unwrap());
+        assert!(!move_to_trash(&pool, "companies", "a", Some("grace@example.com")).await.unwrap());
+        // Sans session ouverte, l'auteur reste inconnu
+        assert!(move_to_trash(&pool, "companies", "b", None).await.unwrap());
+
+        let authors: Vec<(String, Option<String>)> =
+            sqlx::query_as("SELECT id, deleted_by FROM companies ORDER BY id").fetch_all(&pool).await.unwrap();
+        assert_eq!(authors, [("a".to_string(), Some("ada@example.com".to_string())), ("b".to_string(), None)]);
+        pool.close().await;
+        let _ = std::fs::remove_file(path);
+    }
+
+    #[tokio::test]
+    async fn changes_compare_as_numbers_and_report_unreadable_rows() {
+        let (pool, path) = open().await;
+        // En texte, '999' > '1000'
+        sqlx::query(
+            "INSERT INTO companies (id, name, created_at, updated_at) VALUES
+             ('old', 'Acme', '1', '999'), ('new', 'Globex', '1', '10000'), ('bad', 'Initech', 'hier', '20000')"
+        )
+        .execute(&pool)
+        .await
+        .unwrap();
+
+        let changed = get_changed_items_since(&pool, 1000).await.unwrap();
+        assert_eq!(changed.items.iter().map(|i| i.id()).collect::<Vec<_>>(), ["new"]);
+        assert_eq!(changed.unreadable.iter().map(|e| e.id.as_str()).collect::<Vec<_>>(), ["bad"]);
+        assert_eq!(latest_change_since(&pool, 1000).await.unwrap(), Some(20000));
+        assert_eq!(latest_change_since(&pool, 20000).await.unwrap(), None);
+
+        // Non envoyée, la ligne illisible reste en attente après la
+        // synchronisation, puis part une fois corrigée
+        record_sync_rejections(&pool, &changed.items, &changed.unreadable).await.unwrap();
+        record_sync_rejections(&pool, &changed.items, &changed.unreadable).await.unwrap();
+        let pending = get_pending_sync_items(&pool, 30000).await.unwrap();
+        assert!(pending.items.is_empty());
+        assert_eq!(pending.unreadable, changed.unreadable);
+        sqlx::query("UPDATE companies SET created_at = '1' WHERE id = 'bad'").execute(&pool).await.unwrap();
+        let pending = get_pending_sync_items(&pool, 30000).await.unwrap();
+        assert_eq!(pending.items.iter().map(|i| i.id()).collect::<Vec<_>>(), ["bad"]);
+        assert!(pending.unreadable.is_empty());
+        pool.close().await;
+        let _ = std::fs::remove_file(path);
+    }
+
+    #[tokio::test]
+    async fn rejected_changes_are_sent_again_until_accepted() {
+        let (pool, path) = open().await;
+        sqlx::query(
+            "INSERT INTO companies (id, name, created_at, updated_at) VALUES ('a', 'Acme', '1', '1000'), ('b', 'Globex', '1', '1000')"
+        )
+        .execute(&pool)
+        .await
+        .unwrap();
+        let sent = get_pending_sync_items(&pool, 0).await.unwrap().items;
+        assert_eq!(sent.len(), 2);
+
+        let rejection = |field: Option<&str>, message: &str| crm_schema::SyncItemError {
+            table_name: "companies".to_string(),
+            id: "a".to_string(),
+            field: field.map(str::to_string),
+            message: message.to_string(),
+        };
+        record_sync_rejections(&pool, &sent, &[rejection(Some("name"), "too long"), rejection(None, "constraint")])
+            .await
+            .unwrap();
+        let message: String = sqlx::query_scalar("SELECT message FROM sync_rejections WHERE record_id = 'a'")
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        assert_eq!(message, "name: too long; constraint");
+
+        // Plus rien de modifié depuis la synchronisation : seul le refus repart
+        let pending = get_pending_sync_items(&pool, 2000).await.unwrap().items;
+        assert_eq!(pending.iter().map(|i| i.id()).collect::<Vec<_>>(), ["a"]);
+        // Modifié depuis : envoyé une seule fois
+        sqlx::query("UPDATE companies SET updated_at = '3000' WHERE id = 'a'").execute(&pool).await.unwrap();
+        assert_eq!(get_pending_sync_items(&pool, 2000).await.unwrap().items.len(), 1);
+
+        record_sync_rejections(&pool, &pending, &[]).await.unwrap();
+        assert!(get_pending_sync_items(&pool, 4000).await.unwrap().items.is_empty());
+        pool.close().await;
+        let _ = std::fs::remove_file(path);
+    }
+
+    #[tokio::test]
+    async fn rejected_deletions_are_not_purged() {
+        let (pool, path) = open().await;
+        sqlx::query(
+            "INSERT INTO companies (id, name, created_at, updated_at, is_deleted) VALUES ('a', 'Acme', '1', '1000', 1), ('b', 'Globex', '1', '1000', 1)"
+        )
+        .execute(&pool)
+        .await
+        .unwrap();
+        let sent = get_pending_sync_items(&pool, 0).await.unwrap().items;
+        let rejection = crm_schema::SyncItemError {
+            table_name: "companies".to_string(),
+            id: "a".to_string(),
+            field: None,
+            message: "constraint".to_string(),
+        };
+        record_sync_rejections(&pool, &sent, &[rejection]).await.unwrap();
+
+        assert_eq!(purge_tombstones(&pool, 2000).await.unwrap(), 1);
+        let remaining: Vec<String> = sqlx::query_scalar("SELECT id FROM companies").fetch_all(&pool).await.unwrap();
+        assert_eq!(remaining, ["a"]);
+        pool.close().await;
+        let _ = std::fs::remove_file(path);
+    }
+
+    #[tokio::test]
+    async fn write_during_sync_is_sent_next_time() {
+        let (pool, path) = open().await;
+        sqlx::query("INSERT INTO companies (id, name, created_at, updated_at) VALUES ('a', 'Acme', '1', '1000')")
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        // Même enchaînement que run_sync, avec une écriture locale entre
+        // l'envoi et l'enregistrement de la synchronisation, datée avant
+        // l'heure (en avance) renvoyée par le serveur
+        let metadata = get_sync_metadata(&pool).await.unwrap();
+        let push_timestamp = 1999;
+        let sent = get_pending_sync_items(&pool, metadata.last_push_timestamp).await.unwrap().items;
+        assert_eq!(sent.iter().map(|i| i.id()).collect::<Vec<_>>(), ["a"]);
+        sqlx::query("INSERT INTO companies (id, name, created_at, updated_at) VALUES ('b', 'Globex', '1', '2000')")
+            .execute(&pool)
+            .await
+            .unwrap();
+        update_sync_metadata(&pool, 5000, push_timestamp, metadata.last_sync_version + 1).await.unwrap();
+
+        let metadata = get_sync_metadata(&pool).await.unwrap();
+        assert_eq!((metadata.last_sync_timestamp, metadata.last_push_timestamp), (5000, 1999));
+        let pending = get_pending_sync_items(&pool, metadata.last_push_timestamp).await.unwrap().items;
+        assert_eq!(pending.iter().map(|i| i.id()).collect::<Vec<_>>(), ["b"]);
+        assert_eq!(latest_change_since(&pool, metadata.last_push_timestamp).await.unwrap(), Some(2000));
+        pool.close().await;
+        let _ = std::fs::remove_file(path);
+    }
+
+    // Même enchaînement que la commande update_company, à partir des
+    // contacts relus comme le fait le formulaire
+    async fn save_contacts(pool: &DbPool, company_id: &str, contacts: Vec<CompanyContact>) -> Vec<String> {
+        let now = chrono::Utc::now().timestamp_millis().to_string();
+        let mut tx = pool.begin().await.unwrap();
+        let mut kept_ids = Vec::new();
+        for contact in contacts {
+            kept_ids.push(save_company_contact(&mut tx, &contact, &now).await.unwrap().id);
+        }
+        delete_company_contacts_except(&mut tx, company_id, &kept_ids, &now).await.unwrap();
+        tx.commit().await.unwrap();
+        kept_ids
+    }
+
+    #[tokio::test]
+    async fn saving_a_company_twice_keeps_contact_ids() {
+        let (pool, path) = open().await;
+        sqlx::query("INSERT INTO companies (id, name, created_at, updated_at) VALUES ('co1', 'Acme', '1', '1')")
+            .execute(&pool)
+            .await
+            .unwrap();
+        for (id, email) in [("ct1", "ada@acme.test"), ("ct2", "bob@acme.test")] {
+            sqlx::query("INSERT INTO company_contacts (id, company_id, first_name, last_name, email, is_primary, created_at, updated_at) VALUES (?, 'co1', 'A', 'B', ?, 0, '1', '1')")
+                .bind(id)
+                .bind(email)
+                .execute(&pool)
+                .await
+                .unwrap();
+        }
+
+        let first = save_contacts(&pool, "co1", get_company_contacts(&pool, "co1").await.unwrap()).await;
+        let second = save_contacts(&pool, "co1", get_company_contacts(&pool, "co1").await.unwrap()).await;
+        assert_eq!(first, second);
+        let mut ids = second.clone();
+        ids.sort();
+        assert_eq!(ids, ["ct1", "ct2"]);
+
+        // Sans identifiant, un contact est recréé et l'ancien supprimé
+        let mut contacts = get_company_contacts(&pool, "co1").await.unwrap();
+        contacts.retain(|c| c.id == "ct1");
+        contacts.push(CompanyContact { id: String::new(), email: "new@acme.test".to_string(), ..contacts[0].clone() });
+        let third = save_contacts(&pool, "co1", contacts).await;
+        assert_eq!(third[0], "ct1");
+        assert_ne!(third[1], "ct2");
+        let remaining: Vec<String> = get_company_contacts(&pool, "co1").await.unwrap().into_iter().map(|c| c.email).collect();
+        assert_eq!(remaining.len(), 2);
+        assert!(remaining.contains(&"new@acme.test".to_string()) && !remaining.contains(&"bob@acme.test".to_string()));
+        pool.close().await;
+        let _ = std::fs::remove_file(path);
+    }
+
+    #[test]
+    fn search_terms_are_quoted_prefixes() {
+        assert_eq!(search_match_expression(""), None);
+        assert_eq!(search_match_expression("  \t "), None);
+        assert_eq!(search_match_expression("acme  corp"), Some("\"acme\"* \"corp\"*".to_string()));
+        assert_eq!(search_match_expression("say \"hi\""), Some("\"say\"* \"\"\"hi\"\"\"*".to_string()));
+        assert_eq!(search_match_expression("title:x"), Some("\"title:x\"*".to_string()));
+    }
+
+    async fn search_ids(pool: &DbPool, query: &str) -> Vec<String> {
+        global_search(pool, query, &[], 20)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|hit| hit.entity_id)
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn fts_operators_are_searched_as_text() {
+        let (pool, path) = open().await;
+        sqlx::query("INSERT INTO companies (id, name, description, created_at, updated_at) VALUES ('co1', 'Acme OR Partners', 'NEAR the station', '1', '1')")
+            .execute(&pool)
+            .await
+            .unwrap();
+        // Aucune de ces saisies ne doit produire d'erreur de syntaxe FTS5
+        for query in ["OR", "AND NOT", "NEAR(a b)", "acme*", "-acme", "title:acme", "\"acme", "(acme", "^acme", "a + b"] {
+            assert!(global_search(&pool, query, &[], 20).await.is_ok(), "{}", query);
+        }
+        assert_eq!(search_ids(&pool, "or partners").await, vec!["co1"]);
+        assert_eq!(search_ids(&pool, "near").await, vec!["co1"]);
+        assert!(search_ids(&pool, "acme NOT").await.is_empty());
+        pool.close().await;
+        let _ = std::fs::remove_file(path);
+    }
+
+    #[tokio::test]
+    async fn search_index_follows_table_changes() {
+        let (pool, path) = open().await;
+        let execute = |sql: &'static str| sqlx::query(sql).execute(&pool);
+        execute("INSERT INTO companies (id, name, city, created_at, updated_at) VALUES ('co1', 'Société Générale', 'Lyon', '1', '1')").await.unwrap();
+        execute("INSERT INTO companies (id, name, description, created_at, updated_at) VALUES ('co2', 'Globex', 'Partenaire de Générale', '1', '1')").await.unwrap();
+        execute("INSERT INTO proposals (id, company_id, proposal_number, status, total_amount, created_at, updated_at) VALUES ('pr1', 'co1', 'P-2025-042', 'draft', 0, '1', '1')").await.unwrap();
+
+        // Préfixes, sans accents, titre avant le texte
+        assert_eq!(search_ids(&pool, "societe gen").await, vec!["co1"]);
+        assert_eq!(search_ids(&pool, "generale").await, vec!["co1", "co2"]);
+        assert_eq!(search_ids(&pool, "lyon").await, vec!["co1"]);
+        let hits = global_search(&pool, "P-2025", &["proposal".to_string()], 20).await.unwrap();
+        assert_eq!(hits[0].parent_id.as_deref(), Some("co1"));
+        assert!(hits[0].title.contains("<mark>"));
+
+        execute("UPDATE companies SET name = 'SG Group' WHERE id = 'co1'").await.unwrap();
+        assert_eq!(search_ids(&pool, "societe").await, Vec::<String>::new());
+        assert_eq!(search_ids(&pool, "sg group").await, vec!["co1"]);
+
+        // Corbeille : retiré de l'index, puis rétabli
+        execute("UPDATE companies SET is_deleted = 1 WHERE id = 'co1'").await.unwrap();
+        assert!(search_ids(&pool, "sg").await.is_empty());
+        execute("UPDATE companies SET is_deleted = 0 WHERE id = 'co1'").await.unwrap();
+        assert_eq!(search_ids(&pool, "sg").await, vec!["co1"]);
+
+        execute("DELETE FROM proposals WHERE id = 'pr1'").await.unwrap();
+        assert!(search_ids(&pool, "P-2025").await.is_empty());
+        let entries: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM search_index").fetch_one(&pool).await.unwrap();
+        assert_eq!(entries, 2);
+        pool.close().await;
+        let _ = std::fs::remove_file(path);
+    }
+
+    #[tokio::test]
+    async fn latest_change_ignores_synced_rows() {
+        let (pool, path) = open().await;
+        assert_eq!(latest_change_since(&pool, 0).await.unwrap(), None);
+        for (id, updated_at) in [("co1", "1700000000000"), ("co2", "1700000005000")] {
+            sqlx::query("INSERT INTO companies (id, name, created_at, updated_at) VALUES (?, ?, ?, ?)")
+                .bind(id)
+                .bind(id)
+                .bind(updated_at)
+                .bind(updated_at)
+                .execute(&pool)
+                .await
+                .unwrap();
+        }
+        sqlx::query("INSERT INTO activities (id, company_id, activity_type, occurred_at, created_at, updated_at) VALUES ('a1', 'co1', 'call', '1', '1', '1700000003000')")
+            .execute(&pool)
+            .await
+            .unwrap();
+        assert_eq!(latest_change_since(&pool, 0).await.unwrap(), Some(1_700_000_005_000));
+        assert_eq!(latest_change_since(&pool, 1_700_000_005_000).await.unwrap(), None);
+        pool.close().await;
+        let _ = std::fs::remove_file(path);
+    }
+
+    #[tokio::test]
+    async fn org_settings_are_only_rewritten_when_changed() {
+        let (pool, path) = open().await;
+        assert!(set_org_setting(&pool, "default_currency", "\"EUR\"").await.unwrap());
+        assert!(!set_org_setting(&pool, "default_currency", "\"EUR\"").await.unwrap());
+        assert!(set_org_setting(&pool, "default_currency", "\"CHF\"").await.unwrap());
+
+        let version: i64 = sqlx::query_scalar("SELECT version FROM org_settings WHERE id = 'default_currency'")
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        assert_eq!(version, 2);
+        assert_eq!(get_org_settings(&pool).await.unwrap()["default_currency"], "\"CHF\"");
+        // Envoyé à la prochaine synchronisation comme toute modification
+        assert!(latest_change_since(&pool, 0).await.unwrap().is_some());
+
+        set_local_setting(&pool, "sync", "{}").await.unwrap();
+        set_local_setting(&pool, "sync", "{\"enabled\":false}").await.unwrap();
+        assert_eq!(get_local_settings(&pool).await.unwrap()["sync"], "{\"enabled\":false}");
+        pool.close().await;
+        let _ = std::fs::remove_file(path);
+    }
+
+    #[tokio::test]
+    async fn numbers_follow_configured_formats() {
+        let (pool, path) = open().await;
+        let today = Utc::now().date_naive();
+        let legacy = format!("{:02}{:02}0001", today.year() % 100, today.month());
+        assert_eq!(generate_invoice_number(&pool, numbering::DEFAULT_INVOICE_FORMAT).await.unwrap(), legacy);
+        assert_eq!(
+            generate_invoice_number(&pool, "FA-{YYYY}-{SEQ}").await.unwrap(),
+            format!("FA-{}-0001", today.year())
+        );
+        assert!(numbering::validate_format("FA-{YYYY}", numbering::INVOICE_FIELDS).is_err());
+
+        // Le rang n'est compté que sur les périodes présentes dans le format
+        let last_year = chrono::NaiveDate::from_ymd_opt(today.year() - 1, 6, 1).unwrap();
+        let invoice = |id: &str, number: &str, issue_date: chrono::NaiveDate| {
+            sqlx::query(
+                "INSERT INTO invoices (id, proposal_id, invoice_number, issue_date, created_at, updated_at) VALUES (?, 'pr1', ?, ?, '1', '1')"
+            )
+            .bind(id.to_string())
+            .bind(number.to_string())
+            .bind(issue_date.to_string())
+            .execute(&pool)
+        };
+        invoice("i1", "INV-0001", last_year).await.unwrap();
+        assert_eq!(generate_invoice_number(&pool, "INV-{SEQ}").await.unwrap(), "INV-0002");
+        assert_eq!(generate_invoice_number(&pool, numbering::DEFAULT_INVOICE_FORMAT).await.unwrap(), legacy);
+        let other_month = chrono::NaiveDate::from_ymd_opt(today.year(), if today.month() == 12 { 1 } else { 12 }, 1).unwrap();
+        invoice("i2", &format!("{}-0001", today.year()), other_month).await.unwrap();
+        assert_eq!(
+            generate_invoice_number(&pool, "{YYYY}-{SEQ}").await.unwrap(),
+            format!("{}-0002", today.year())
+        );
+        // Jamais un numéro déjà attribué, même si le compte est plus bas
+        invoice("i3", "INV-0003", last_year).await.unwrap();
+        sqlx::query("DELETE FROM invoices WHERE id = 'i1'").execute(&pool).await.unwrap();
+        assert_eq!(generate_invoice_number(&pool, "INV-{SEQ}").await.unwrap(), "INV-0002");
+        invoice("i4", "INV-0002", last_year).await.unwrap();
+        assert_eq!(generate_invoice_number(&pool, "INV-{SEQ}").await.unwrap(), "INV-0004");
+        sqlx::query("DELETE FROM invoices").execute(&pool).await.unwrap();
+
+        assert!(numbering::validate_format("{company}-{SEQ}", numbering::INVOICE_FIELDS).is_err());
+        assert!(numbering::validate_format(numbering::DEFAULT_PROPOSAL_FORMAT, numbering::PROPOSAL_FIELDS).is_ok());
+
+        sqlx::query("INSERT INTO companies (id, name, created_at, updated_at) VALUES ('co1', 'Acme Corp', '1', '1')")
+            .execute(&pool)
+            .await
+            .unwrap();
+        let number = generate_proposal_number(&pool, numbering::DEFAULT_PROPOSAL_FORMAT, "co1", "HTZ Communications", true, false, true)
+            .await
+            .unwrap();
+        assert_eq!(number, format!("D_Acme_Corp_HTZc+training_{}", today.format("%y%m%d")));
+        pool.close().await;
+        let _ = std::fs::remove_file(path);
+    }
+}
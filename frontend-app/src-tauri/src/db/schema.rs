@@ -236,20 +236,295 @@ CREATE TABLE IF NOT EXISTS invoices (
 pub const CREATE_DOCUMENTS_TABLE: &str = "
 CREATE TABLE IF NOT EXISTS documents (
     id TEXT PRIMARY KEY,
-    customer_id TEXT NOT NULL,
+    customer_id TEXT,
+    company_id TEXT,
     title TEXT NOT NULL,
     document_type TEXT NOT NULL,
     file_path TEXT,
     content TEXT,
     created_at TEXT NOT NULL,
     updated_at TEXT NOT NULL,
-    FOREIGN KEY (customer_id) REFERENCES customers(id) ON DELETE CASCADE
+    FOREIGN KEY (customer_id) REFERENCES customers(id) ON DELETE CASCADE,
+    FOREIGN KEY (company_id) REFERENCES companies(id) ON DELETE CASCADE
+);
+";
+
+// Les documents générés sont rattachés à une société plutôt qu'à un client :
+// SQLite ne sait pas rendre `customer_id` facultatif, la table est recréée
+// pour les bases créées avant l'ajout de `company_id`.
+pub const MIGRATE_DOCUMENTS_COMPANY: &str = "
+CREATE TABLE documents_new (
+    id TEXT PRIMARY KEY,
+    customer_id TEXT,
+    company_id TEXT,
+    title TEXT NOT NULL,
+    document_type TEXT NOT NULL,
+    file_path TEXT,
+    content TEXT,
+    created_at TEXT NOT NULL,
+    updated_at TEXT NOT NULL,
+    version INTEGER DEFAULT 1,
+    is_deleted INTEGER DEFAULT 0,
+    FOREIGN KEY (customer_id) REFERENCES customers(id) ON DELETE CASCADE,
+    FOREIGN KEY (company_id) REFERENCES companies(id) ON DELETE CASCADE
+);
+INSERT INTO documents_new (id, customer_id, title, document_type, file_path, content, created_at, updated_at, version, is_deleted)
+    SELECT id, customer_id, title, document_type, file_path, content, created_at, updated_at, version, is_deleted FROM documents;
+DROP TABLE documents;
+ALTER TABLE documents_new RENAME TO documents;
+";
+
+// Bibliothèque de modèles de documents : chaque import crée une nouvelle
+// version (template_version) d'un modèle identifié par (kind, name). Le
+// fichier est conservé en base64 dans `content` pour être synchronisé.
+pub const CREATE_DOCUMENT_TEMPLATES_TABLE: &str = "
+CREATE TABLE IF NOT EXISTS document_templates (
+    id TEXT PRIMARY KEY,
+    name TEXT NOT NULL,
+    kind TEXT NOT NULL,
+    product_type TEXT,
+    language TEXT,
+    company_id TEXT,
+    template_version INTEGER NOT NULL DEFAULT 1,
+    file_name TEXT NOT NULL,
+    content TEXT,
+    checksum TEXT NOT NULL,
+    created_at TEXT NOT NULL,
+    updated_at TEXT NOT NULL,
+    version INTEGER DEFAULT 1,
+    is_deleted INTEGER DEFAULT 0
+);
+CREATE INDEX IF NOT EXISTS idx_document_templates_kind ON document_templates(kind, name);
+CREATE INDEX IF NOT EXISTS idx_document_templates_updated_at ON document_templates(updated_at);
+";
+
+// Rattachement des documents aux enregistrements (société, proposition,
+// facture ou ancien client). L'identifiant est déterministe
+// (`<entity_type>:<entity_id>:<document_id>`) pour qu'un même rattachement
+// fait sur deux postes ne crée qu'une ligne après synchronisation.
+pub const CREATE_DOCUMENT_LINKS_TABLE: &str = "
+CREATE TABLE IF NOT EXISTS document_links (
+    id TEXT PRIMARY KEY,
+    document_id TEXT NOT NULL,
+    entity_type TEXT NOT NULL,
+    entity_id TEXT NOT NULL,
+    created_at TEXT NOT NULL,
+    updated_at TEXT NOT NULL,
+    version INTEGER DEFAULT 1,
+    is_deleted INTEGER DEFAULT 0
+);
+CREATE INDEX IF NOT EXISTS idx_document_links_entity ON document_links(entity_type, entity_id);
+CREATE INDEX IF NOT EXISTS idx_document_links_document ON document_links(document_id);
+CREATE INDEX IF NOT EXISTS idx_document_links_updated_at ON document_links(updated_at);
+";
+
+// Reprise des rattachements portés par les colonnes de `documents` (client,
+// société, enregistrement d'origine). Sans effet sur les liens existants,
+// y compris ceux détachés depuis.
+pub const MIGRATE_DOCUMENT_LINKS: &str = "
+INSERT OR IGNORE INTO document_links (id, document_id, entity_type, entity_id, created_at, updated_at)
+    SELECT 'customer:' || customer_id || ':' || id, id, 'customer', customer_id, created_at, strftime('%s', 'now') * 1000
+    FROM documents WHERE customer_id IS NOT NULL AND customer_id != '';
+INSERT OR IGNORE INTO document_links (id, document_id, entity_type, entity_id, created_at, updated_at)
+    SELECT 'company:' || company_id || ':' || id, id, 'company', company_id, created_at, strftime('%s', 'now') * 1000
+    FROM documents WHERE company_id IS NOT NULL AND company_id != '';
+INSERT OR IGNORE INTO document_links (id, document_id, entity_type, entity_id, created_at, updated_at)
+    SELECT source_type || ':' || source_id || ':' || id, id, source_type, source_id, created_at, strftime('%s', 'now') * 1000
+    FROM documents WHERE source_type IS NOT NULL AND source_id IS NOT NULL;
+";
+
+// État local des transferts de pièces jointes, par empreinte. Non
+// synchronisé : chaque poste suit ses propres envois.
+pub const CREATE_ATTACHMENT_TRANSFERS_TABLE: &str = "
+CREATE TABLE IF NOT EXISTS attachment_transfers (
+    sha256 TEXT PRIMARY KEY,
+    uploaded INTEGER NOT NULL DEFAULT 0,
+    last_error TEXT,
+    updated_at TEXT NOT NULL
+);
+";
+
+// Historique des échanges avec une société (appels, rendez-vous, e-mails,
+// notes). Les participants sont une liste JSON d'identifiants de
+// company_contacts, synchronisée avec l'activité.
+pub const CREATE_ACTIVITIES_TABLE: &str = "
+CREATE TABLE IF NOT EXISTS activities (
+    id TEXT PRIMARY KEY,
+    company_id TEXT NOT NULL,
+    activity_type TEXT NOT NULL,
+    occurred_at TEXT NOT NULL,
+    duration_minutes INTEGER,
+    subject TEXT,
+    notes TEXT,
+    proposal_id TEXT,
+    participants TEXT NOT NULL DEFAULT '[]',
+    created_at TEXT NOT NULL,
+    updated_at TEXT NOT NULL,
+    version INTEGER DEFAULT 1,
+    is_deleted INTEGER DEFAULT 0
+);
+CREATE INDEX IF NOT EXISTS idx_activities_company ON activities(company_id, occurred_at);
+CREATE INDEX IF NOT EXISTS idx_activities_proposal ON activities(proposal_id);
+CREATE INDEX IF NOT EXISTS idx_activities_updated_at ON activities(updated_at);
+";
+
+// Journal des fusions de sociétés faites sur ce poste ou reçues par la
+// synchronisation. Une société ne pouvant être absorbée qu'une fois, elle
+// sert de clé.
+pub const CREATE_COMPANY_MERGES_TABLE: &str = "
+CREATE TABLE IF NOT EXISTS company_merges (
+    merged_id TEXT PRIMARY KEY,
+    survivor_id TEXT NOT NULL,
+    merged_name TEXT NOT NULL,
+    contacts_moved INTEGER NOT NULL DEFAULT 0,
+    proposals_moved INTEGER NOT NULL DEFAULT 0,
+    documents_moved INTEGER NOT NULL DEFAULT 0,
+    merged_at TEXT NOT NULL
+);
+CREATE INDEX IF NOT EXISTS idx_company_merges_survivor ON company_merges(survivor_id);
+";
+
+// Recherche plein texte (FTS5) sur les sociétés, contacts, propositions,
+// factures et documents. L'index est tenu à jour par des triggers ; les
+// enregistrements supprimés (is_deleted) en sont retirés. La ligne est
+// supprimée avant chaque insertion car `INSERT OR REPLACE` (synchronisation)
+// ne déclenche pas les triggers DELETE.
+pub const CREATE_SEARCH_INDEX: &str = "
+CREATE VIRTUAL TABLE IF NOT EXISTS search_index USING fts5(
+    entity_type UNINDEXED,
+    entity_id UNINDEXED,
+    parent_id UNINDEXED,
+    title,
+    body,
+    tokenize = 'unicode61 remove_diacritics 2'
 );
 ";
 
+// La reconstruction d'une table supprime ses triggers : une migration qui
+// reconstruit une de ces tables doit les recréer.
+pub const CREATE_SEARCH_TRIGGERS: &str = "
+CREATE TRIGGER IF NOT EXISTS search_companies_ai AFTER INSERT ON companies BEGIN
+    DELETE FROM search_index WHERE entity_type = 'company' AND entity_id = NEW.id;
+    INSERT INTO search_index (entity_type, entity_id, parent_id, title, body)
+        SELECT 'company', NEW.id, NULL, NEW.name, TRIM(COALESCE(NEW.website, '') || ' ' || COALESCE(NEW.address, '') || ' ' || COALESCE(NEW.city, '') || ' ' || COALESCE(NEW.postal_code, '') || ' ' || COALESCE(NEW.country, '') || ' ' || COALESCE(NEW.description, ''))
+        WHERE COALESCE(NEW.is_deleted, 0) = 0;
+END;
+CREATE TRIGGER IF NOT EXISTS search_companies_au AFTER UPDATE ON companies BEGIN
+    DELETE FROM search_index WHERE entity_type = 'company' AND entity_id = NEW.id;
+    INSERT INTO search_index (entity_type, entity_id, parent_id, title, body)
+        SELECT 'company', NEW.id, NULL, NEW.name, TRIM(COALESCE(NEW.website, '') || ' ' || COALESCE(NEW.address, '') || ' ' || COALESCE(NEW.city, '') || ' ' || COALESCE(NEW.postal_code, '') || ' ' || COALESCE(NEW.country, '') || ' ' || COALESCE(NEW.description, ''))
+        WHERE COALESCE(NEW.is_deleted, 0) = 0;
+END;
+CREATE TRIGGER IF NOT EXISTS search_companies_ad AFTER DELETE ON companies BEGIN
+    DELETE FROM search_index WHERE entity_type = 'company' AND entity_id = OLD.id;
+END;
+CREATE TRIGGER IF NOT EXISTS search_company_contacts_ai AFTER INSERT ON company_contacts BEGIN
+    DELETE FROM search_index WHERE entity_type = 'contact' AND entity_id = NEW.id;
+    INSERT INTO search_index (entity_type, entity_id, parent_id, title, body)
+        SELECT 'contact', NEW.id, NEW.company_id, NEW.first_name || ' ' || NEW.last_name, TRIM(COALESCE(NEW.email, '') || ' ' || COALESCE(NEW.phone_number, ''))
+        WHERE COALESCE(NEW.is_deleted, 0) = 0;
+END;
+CREATE TRIGGER IF NOT EXISTS search_company_contacts_au AFTER UPDATE ON company_contacts BEGIN
+    DELETE FROM search_index WHERE entity_type = 'contact' AND entity_id = NEW.id;
+    INSERT INTO search_index (entity_type, entity_id, parent_id, title, body)
+        SELECT 'contact', NEW.id, NEW.company_id, NEW.first_name || ' ' || NEW.last_name, TRIM(COALESCE(NEW.email, '') || ' ' || COALESCE(NEW.phone_number, ''))
+        WHERE COALESCE(NEW.is_deleted, 0) = 0;
+END;
+CREATE TRIGGER IF NOT EXISTS search_company_contacts_ad AFTER DELETE ON company_contacts BEGIN
+    DELETE FROM search_index WHERE entity_type = 'contact' AND entity_id = OLD.id;
+END;
+CREATE TRIGGER IF NOT EXISTS search_proposals_ai AFTER INSERT ON proposals BEGIN
+    DELETE FROM search_index WHERE entity_type = 'proposal' AND entity_id = NEW.id;
+    INSERT INTO search_index (entity_type, entity_id, parent_id, title, body)
+        SELECT 'proposal', NEW.id, NEW.company_id, COALESCE(NEW.proposal_number, ''), COALESCE(NEW.notes, '')
+        WHERE COALESCE(NEW.is_deleted, 0) = 0;
+END;
+CREATE TRIGGER IF NOT EXISTS search_proposals_au AFTER UPDATE ON proposals BEGIN
+    DELETE FROM search_index WHERE entity_type = 'proposal' AND entity_id = NEW.id;
+    INSERT INTO search_index (entity_type, entity_id, parent_id, title, body)
+        SELECT 'proposal', NEW.id, NEW.company_id, COALESCE(NEW.proposal_number, ''), COALESCE(NEW.notes, '')
+        WHERE COALESCE(NEW.is_deleted, 0) = 0;
+END;
+CREATE TRIGGER IF NOT EXISTS search_proposals_ad AFTER DELETE ON proposals BEGIN
+    DELETE FROM search_index WHERE entity_type = 'proposal' AND entity_id = OLD.id;
+END;
+CREATE TRIGGER IF NOT EXISTS search_invoices_ai AFTER INSERT ON invoices BEGIN
+    DELETE FROM search_index WHERE entity_type = 'invoice' AND entity_id = NEW.id;
+    INSERT INTO search_index (entity_type, entity_id, parent_id, title, body)
+        SELECT 'invoice', NEW.id, NEW.proposal_id, NEW.invoice_number, TRIM(COALESCE(NEW.purchase_order, '') || ' ' || COALESCE(NEW.notes, ''))
+        WHERE COALESCE(NEW.is_deleted, 0) = 0;
+END;
+CREATE TRIGGER IF NOT EXISTS search_invoices_au AFTER UPDATE ON invoices BEGIN
+    DELETE FROM search_index WHERE entity_type = 'invoice' AND entity_id = NEW.id;
+    INSERT INTO search_index (entity_type, entity_id, parent_id, title, body)
+        SELECT 'invoice', NEW.id, NEW.proposal_id, NEW.invoice_number, TRIM(COALESCE(NEW.purchase_order, '') || ' ' || COALESCE(NEW.notes, ''))
+        WHERE COALESCE(NEW.is_deleted, 0) = 0;
+END;
+CREATE TRIGGER IF NOT EXISTS search_invoices_ad AFTER DELETE ON invoices BEGIN
+    DELETE FROM search_index WHERE entity_type = 'invoice' AND entity_id = OLD.id;
+END;
+CREATE TRIGGER IF NOT EXISTS search_documents_ai AFTER INSERT ON documents BEGIN
+    DELETE FROM search_index WHERE entity_type = 'document' AND entity_id = NEW.id;
+    INSERT INTO search_index (entity_type, entity_id, parent_id, title, body)
+        SELECT 'document', NEW.id, NULL, NEW.title, COALESCE(NEW.content, '')
+        WHERE COALESCE(NEW.is_deleted, 0) = 0;
+END;
+CREATE TRIGGER IF NOT EXISTS search_documents_au AFTER UPDATE ON documents BEGIN
+    DELETE FROM search_index WHERE entity_type = 'document' AND entity_id = NEW.id;
+    INSERT INTO search_index (entity_type, entity_id, parent_id, title, body)
+        SELECT 'document', NEW.id, NULL, NEW.title, COALESCE(NEW.content, '')
+        WHERE COALESCE(NEW.is_deleted, 0) = 0;
+END;
+CREATE TRIGGER IF NOT EXISTS search_documents_ad AFTER DELETE ON documents BEGIN
+    DELETE FROM search_index WHERE entity_type = 'document' AND entity_id = OLD.id;
+END;
+";
+
+// Remplissage initial de l'index à partir des données existantes
+pub const REBUILD_SEARCH_INDEX: &str = "
+DELETE FROM search_index;
+INSERT INTO search_index (entity_type, entity_id, parent_id, title, body)
+    SELECT 'company', id, NULL, name, TRIM(COALESCE(website, '') || ' ' || COALESCE(address, '') || ' ' || COALESCE(city, '') || ' ' || COALESCE(postal_code, '') || ' ' || COALESCE(country, '') || ' ' || COALESCE(description, ''))
+    FROM companies WHERE COALESCE(is_deleted, 0) = 0;
+INSERT INTO search_index (entity_type, entity_id, parent_id, title, body)
+    SELECT 'contact', id, company_id, first_name || ' ' || last_name, TRIM(COALESCE(email, '') || ' ' || COALESCE(phone_number, ''))
+    FROM company_contacts WHERE COALESCE(is_deleted, 0) = 0;
+INSERT INTO search_index (entity_type, entity_id, parent_id, title, body)
+    SELECT 'proposal', id, company_id, COALESCE(proposal_number, ''), COALESCE(notes, '')
+    FROM proposals WHERE COALESCE(is_deleted, 0) = 0;
+INSERT INTO search_index (entity_type, entity_id, parent_id, title, body)
+    SELECT 'invoice', id, proposal_id, invoice_number, TRIM(COALESCE(purchase_order, '') || ' ' || COALESCE(notes, ''))
+    FROM invoices WHERE COALESCE(is_deleted, 0) = 0;
+INSERT INTO search_index (entity_type, entity_id, parent_id, title, body)
+    SELECT 'document', id, NULL, title, COALESCE(content, '')
+    FROM documents WHERE COALESCE(is_deleted, 0) = 0;
+";
+
 // SYNC_QUEUE_TABLE - REMOVED as part of synchronization cleanup
 
-// SETTINGS_TABLE - REMOVED as part of synchronization cleanup
+// Réglages propres à ce poste et à ce profil de serveur (dossier de sortie,
+// synchronisation automatique) : une ligne par section, valeur JSON. Jamais
+// synchronisés, contrairement à `org_settings`.
+pub const CREATE_SETTINGS_TABLE: &str = "
+CREATE TABLE IF NOT EXISTS settings (
+    key TEXT PRIMARY KEY,
+    value TEXT NOT NULL,
+    updated_at TEXT NOT NULL
+);
+";
+
+// Réglages communs à l'organisation, synchronisés : une ligne par clé
+pub const CREATE_ORG_SETTINGS_TABLE: &str = "
+CREATE TABLE IF NOT EXISTS org_settings (
+    id TEXT PRIMARY KEY,
+    value TEXT NOT NULL,
+    created_at TEXT NOT NULL,
+    updated_at TEXT NOT NULL,
+    version INTEGER DEFAULT 1,
+    is_deleted INTEGER DEFAULT 0
+);
+CREATE INDEX IF NOT EXISTS idx_org_settings_updated_at ON org_settings(updated_at);
+";
 
 // === SYNC METADATA TABLE ===
 pub const CREATE_SYNC_METADATA_TABLE: &str = "
@@ -261,6 +536,18 @@ CREATE TABLE IF NOT EXISTS sync_metadata (
 );
 ";
 
+// Éléments refusés par le serveur, renvoyés à chaque synchronisation
+// jusqu'à ce qu'il les accepte
+pub const CREATE_SYNC_REJECTIONS_TABLE: &str = "
+CREATE TABLE IF NOT EXISTS sync_rejections (
+    table_name TEXT NOT NULL,
+    record_id TEXT NOT NULL,
+    message TEXT NOT NULL,
+    rejected_at TEXT NOT NULL,
+    PRIMARY KEY (table_name, record_id)
+);
+";
+
 // === SYNC COLUMNS MIGRATIONS ===
 pub const ADD_SYNC_COLUMNS_COMPANIES: &str = "
 ALTER TABLE companies ADD COLUMN version INTEGER DEFAULT 1;
@@ -297,6 +584,47 @@ ALTER TABLE documents ADD COLUMN version INTEGER DEFAULT 1;
 ALTER TABLE documents ADD COLUMN is_deleted INTEGER DEFAULT 0;
 ";
 
+// Traçabilité des documents générés : enregistrement source (proposition ou
+// facture), révision et empreinte SHA-256 du fichier archivé
+pub const ADD_ARCHIVE_COLUMNS_DOCUMENTS: &str = "
+ALTER TABLE documents ADD COLUMN source_type TEXT;
+ALTER TABLE documents ADD COLUMN source_id TEXT;
+ALTER TABLE documents ADD COLUMN revision INTEGER;
+ALTER TABLE documents ADD COLUMN sha256 TEXT;
+";
+
+// Société absorbée par une fusion : conservée supprimée, avec l'identifiant
+// de la société qui la remplace (redirection propagée par la synchronisation)
+pub const ADD_MERGE_COLUMNS_COMPANIES: &str = "
+ALTER TABLE companies ADD COLUMN merged_into TEXT;
+";
+
+// Corbeille : poste à l'origine de la suppression (la date est celle de la
+// dernière modification, `updated_at`)
+pub const ADD_TRASH_COLUMNS: &str = "
+ALTER TABLE companies ADD COLUMN deleted_by TEXT;
+ALTER TABLE proposals ADD COLUMN deleted_by TEXT;
+ALTER TABLE invoices ADD COLUMN deleted_by TEXT;
+ALTER TABLE documents ADD COLUMN deleted_by TEXT;
+";
+
+// Identité du poste, transmise au serveur à chaque synchronisation
+pub const ADD_DEVICE_COLUMNS_SYNC_METADATA: &str = "
+ALTER TABLE sync_metadata ADD COLUMN device_id TEXT;
+ALTER TABLE sync_metadata ADD COLUMN device_name TEXT;
+";
+
+// Modifications locales envoyées jusqu'à cet instant, en heure locale ;
+// `last_sync_timestamp` reste l'heure du serveur jusqu'à laquelle ses
+// modifications ont été reçues. Une base existante repart de cette dernière
+pub const ADD_PUSH_COLUMN_SYNC_METADATA: &str = "
+ALTER TABLE sync_metadata ADD COLUMN last_push_timestamp INTEGER NOT NULL DEFAULT 0;
+";
+
+pub const INIT_PUSH_TIMESTAMP_SYNC_METADATA: &str = "
+UPDATE sync_metadata SET last_push_timestamp = last_sync_timestamp;
+";
+
 // === INDEXES FOR SYNC ===
 pub const CREATE_SYNC_INDEXES: &str = "
 CREATE INDEX IF NOT EXISTS idx_companies_updated_at ON companies(updated_at);
@@ -315,3 +643,23 @@ CREATE INDEX IF NOT EXISTS idx_documents_updated_at ON documents(updated_at);
 CREATE INDEX IF NOT EXISTS idx_documents_is_deleted ON documents(is_deleted);
 ";
 
+pub const CREATE_DOCUMENTS_COMPANY_INDEX: &str = "
+CREATE INDEX IF NOT EXISTS idx_documents_company_id ON documents(company_id);
+";
+
+pub const CREATE_DOCUMENTS_SOURCE_INDEX: &str = "
+CREATE INDEX IF NOT EXISTS idx_documents_source ON documents(source_type, source_id);
+";
+
+// === SCHEMA VERSION TABLE ===
+// Migrations appliquées (voir migrations.rs), avec l'empreinte de leur
+// contenu au moment de l'application
+pub const CREATE_SCHEMA_VERSION_TABLE: &str = "
+CREATE TABLE IF NOT EXISTS schema_version (
+    version INTEGER PRIMARY KEY,
+    name TEXT NOT NULL,
+    checksum TEXT NOT NULL,
+    applied_at TEXT NOT NULL
+);
+";
+
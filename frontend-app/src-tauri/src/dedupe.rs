@@ -0,0 +1,301 @@
+// Détection des sociétés en double
+//
+// Les postes créent chacun leurs sociétés hors ligne : « ACME », « Acme SAS »
+// et « ACME S.A.S. » finissent par coexister après synchronisation. Les noms
+// sont comparés une fois normalisés (casse, accents, ponctuation, formes
+// juridiques), complétés par le domaine du site web et le code postal.
+
+use crate::db::models::Company;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeSet, HashMap};
+
+/// Formes juridiques et mots sans valeur distinctive, ignorés dans les noms
+const LEGAL_FORMS: &[&str] = &[
+    "sa", "sas", "sasu", "sarl", "eurl", "sci", "snc", "scop", "inc", "incorporated", "llc", "llp",
+    "ltd", "limited", "plc", "gmbh", "ag", "kg", "bv", "nv", "spa", "srl", "sl", "corp",
+    "corporation", "co", "company", "cie", "the", "et", "and",
+];
+
+/// Score à partir duquel deux sociétés sont proposées comme doublons
+pub const DEFAULT_THRESHOLD: f64 = 0.8;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DuplicateCandidate {
+    pub company_id: String,
+    pub company_name: String,
+    pub duplicate_id: String,
+    pub duplicate_name: String,
+    /// Entre 0 et 1
+    pub score: f64,
+    /// "same_name", "similar_name", "same_domain", "same_postal_code" ou
+    /// "nearby_postal_code"
+    pub reasons: Vec<String>,
+}
+
+fn fold_accent(c: char) -> char {
+    match c {
+        'à' | 'á' | 'â' | 'ã' | 'ä' | 'å' => 'a',
+        'ç' => 'c',
+        'è' | 'é' | 'ê' | 'ë' => 'e',
+        'ì' | 'í' | 'î' | 'ï' => 'i',
+        'ñ' => 'n',
+        'ò' | 'ó' | 'ô' | 'õ' | 'ö' | 'ø' => 'o',
+        'ù' | 'ú' | 'û' | 'ü' => 'u',
+        'ý' | 'ÿ' => 'y',
+        _ => c,
+    }
+}
+
+/// Nom réduit à ses mots distinctifs : "ACME S.A.S." et "Acme" donnent "acme".
+pub fn normalize_name(name: &str) -> String {
+    let folded: String = name
+        .to_lowercase()
+        .chars()
+        .map(fold_accent)
+        // "S.A.S." -> "sas"
+        .filter(|c| *c != '.')
+        .map(|c| if c.is_alphanumeric() { c } else { ' ' })
+        .collect();
+    let words: Vec<&str> = folded.split_whitespace().collect();
+    let distinctive: Vec<&str> = words
+        .iter()
+        .copied()
+        .filter(|w| !LEGAL_FORMS.contains(w))
+        .collect();
+
+    // Une société qui ne s'appelle que "SAS" garde son nom
+    if distinctive.is_empty() { words } else { distinctive }.join(" ")
+}
+
+/// Domaine d'un site web, sans protocole, chemin, port ni "www."
+pub fn website_domain(website: &str) -> Option<String> {
+    let lower = website.trim().to_lowercase();
+    let without_scheme = lower.split_once("://").map(|(_, rest)| rest).unwrap_or(&lower);
+    let host = without_scheme
+        .split(['/', '?', '#'])
+        .next()
+        .unwrap_or_default()
+        .split(':')
+        .next()
+        .unwrap_or_default();
+    let host = host.strip_prefix("www.").unwrap_or(host);
+    if host.contains('.') {
+        Some(host.to_string())
+    } else {
+        None
+    }
+}
+
+fn normalize_postal_code(postal_code: &str) -> String {
+    postal_code
+        .chars()
+        .filter(|c| c.is_alphanumeric())
+        .collect::<String>()
+        .to_uppercase()
+}
+
+/// 1 pour un même code postal, 0,5 pour la même zone (deux premiers
+/// caractères, le département en France), 0 sinon
+pub fn postal_similarity(a: &str, b: &str) -> f64 {
+    let (a, b) = (normalize_postal_code(a), normalize_postal_code(b));
+    if a.is_empty() || b.is_empty() {
+        0.0
+    } else if a == b {
+        1.0
+    } else if a.chars().count() >= 2 && a.chars().take(2).eq(b.chars().take(2)) {
+        0.5
+    } else {
+        0.0
+    }
+}
+
+fn levenshtein(a: &[char], b: &[char]) -> usize {
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.iter().enumerate() {
+        let mut current = vec![i + 1; b.len() + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(ca != cb);
+            current[j + 1] = substitution.min(previous[j + 1] + 1).min(current[j] + 1);
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
+
+/// Similarité de deux noms normalisés, entre 0 et 1
+pub fn name_similarity(a: &str, b: &str) -> f64 {
+    let a: Vec<char> = a.chars().filter(|c| *c != ' ').collect();
+    let b: Vec<char> = b.chars().filter(|c| *c != ' ').collect();
+    let longest = a.len().max(b.len());
+    if longest == 0 {
+        return 0.0;
+    }
+    1.0 - levenshtein(&a, &b) as f64 / longest as f64
+}
+
+struct Profile<'a> {
+    company: &'a Company,
+    name: String,
+    domain: Option<String>,
+    postal_code: String,
+}
+
+/// Compare les paires de sociétés partageant un début de nom, un domaine ou
+/// un code postal, et retourne celles dont le score atteint `threshold`,
+/// les plus probables d'abord.
+pub fn find_duplicates(companies: &[Company], threshold: f64) -> Vec<DuplicateCandidate> {
+    let profiles: Vec<Profile> = companies
+        .iter()
+        .filter(|c| c.is_deleted.unwrap_or(0) == 0)
+        .map(|company| Profile {
+            company,
+            name: normalize_name(&company.name),
+            domain: company.website.as_deref().and_then(website_domain),
+            postal_code: company.postal_code.clone().unwrap_or_default(),
+        })
+        .collect();
+
+    // Regroupement préalable pour éviter de comparer toutes les paires
+    let mut blocks: HashMap<String, Vec<usize>> = HashMap::new();
+    for (index, profile) in profiles.iter().enumerate() {
+        let prefix: String = profile.name.chars().filter(|c| *c != ' ').take(3).collect();
+        if !prefix.is_empty() {
+            blocks.entry(format!("name:{}", prefix)).or_default().push(index);
+        }
+        if let Some(domain) = &profile.domain {
+            blocks.entry(format!("domain:{}", domain)).or_default().push(index);
+        }
+        let postal_code = normalize_postal_code(&profile.postal_code);
+        if !postal_code.is_empty() {
+            blocks.entry(format!("postal:{}", postal_code)).or_default().push(index);
+        }
+    }
+    let mut pairs = BTreeSet::new();
+    for members in blocks.values() {
+        for (i, a) in members.iter().enumerate() {
+            for b in &members[i + 1..] {
+                pairs.insert((*a.min(b), *a.max(b)));
+            }
+        }
+    }
+
+    let mut candidates: Vec<DuplicateCandidate> = pairs
+        .into_iter()
+        .filter_map(|(a, b)| {
+            let (a, b) = (&profiles[a], &profiles[b]);
+            let mut reasons = Vec::new();
+
+            let similarity = name_similarity(&a.name, &b.name);
+            if a.name == b.name {
+                reasons.push("same_name");
+            } else if similarity >= 0.75 {
+                reasons.push("similar_name");
+            }
+            let same_domain = a.domain.is_some() && a.domain == b.domain;
+            if same_domain {
+                reasons.push("same_domain");
+            }
+            let postal = postal_similarity(&a.postal_code, &b.postal_code);
+            if postal == 1.0 {
+                reasons.push("same_postal_code");
+            } else if postal > 0.0 {
+                reasons.push("nearby_postal_code");
+            }
+
+            let mut score = similarity;
+            if same_domain {
+                score = score.max(0.9) + 0.1;
+            }
+            score = (score + 0.1 * postal).min(1.0);
+            if score < threshold {
+                return None;
+            }
+
+            Some(DuplicateCandidate {
+                company_id: a.company.id.clone(),
+                company_name: a.company.name.clone(),
+                duplicate_id: b.company.id.clone(),
+                duplicate_name: b.company.name.clone(),
+                score,
+                reasons: reasons.into_iter().map(String::from).collect(),
+            })
+        })
+        .collect();
+
+    candidates.sort_by(|a, b| b.score.total_cmp(&a.score));
+    candidates
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::models::test_fixtures;
+
+    fn company(id: &str, name: &str, website: Option<&str>, postal_code: Option<&str>) -> Company {
+        Company {
+            website: website.map(str::to_string),
+            postal_code: postal_code.map(str::to_string),
+            ..test_fixtures::company(id, name)
+        }
+    }
+
+    #[test]
+    fn names_are_reduced_to_distinctive_words() {
+        assert_eq!(normalize_name("ACME S.A.S."), "acme");
+        assert_eq!(normalize_name("The Acme Company, Ltd"), "acme");
+        assert_eq!(normalize_name("Société Générale"), "societe generale");
+        assert_eq!(normalize_name("Dupont & Fils SARL"), "dupont fils");
+        assert_eq!(normalize_name("SAS"), "sas");
+        assert_eq!(normalize_name("  "), "");
+    }
+
+    #[test]
+    fn website_domain_drops_scheme_path_port_and_www() {
+        assert_eq!(website_domain("https://www.Acme.fr/contact?x=1").as_deref(), Some("acme.fr"));
+        assert_eq!(website_domain("acme.fr:8080").as_deref(), Some("acme.fr"));
+        assert_eq!(website_domain("http://shop.acme.co.uk#top").as_deref(), Some("shop.acme.co.uk"));
+        assert_eq!(website_domain("localhost"), None);
+        assert_eq!(website_domain(""), None);
+    }
+
+    #[test]
+    fn postal_codes_compare_by_zone_without_panicking() {
+        assert_eq!(postal_similarity("69 001", "69001"), 1.0);
+        assert_eq!(postal_similarity("69001", "69100"), 0.5);
+        assert_eq!(postal_similarity("69001", "75001"), 0.0);
+        assert_eq!(postal_similarity("", "75001"), 0.0);
+        // Caractères sur plusieurs octets
+        assert_eq!(postal_similarity("ÉÈ1", "ÉÈ2"), 0.5);
+        assert_eq!(postal_similarity("1É", "1É2"), 0.5);
+        assert_eq!(postal_similarity("Éa", "É"), 0.0);
+        assert_eq!(postal_similarity("a", "ab"), 0.0);
+    }
+
+    #[test]
+    fn duplicates_are_ranked_by_score() {
+        let mut deleted = company("5", "Acme", None, None);
+        deleted.is_deleted = Some(1);
+        let companies = vec![
+            company("1", "ACME S.A.S.", Some("https://acme.fr"), Some("69001")),
+            company("2", "Acme", Some("www.acme.fr"), Some("69002")),
+            company("3", "Acmee SARL", None, Some("75001")),
+            company("4", "Globex", Some("acme.fr"), None),
+            deleted,
+            company("6", "Initech", None, Some("69001")),
+        ];
+        let found = find_duplicates(&companies, DEFAULT_THRESHOLD);
+        let pairs: Vec<(&str, &str)> =
+            found.iter().map(|c| (c.company_id.as_str(), c.duplicate_id.as_str())).collect();
+
+        assert_eq!(pairs[0], ("1", "2"));
+        assert_eq!(found[0].score, 1.0);
+        assert_eq!(found[0].reasons, ["same_name", "same_domain", "nearby_postal_code"]);
+        assert!(pairs.contains(&("1", "3")) && pairs.contains(&("2", "3")));
+        // Même domaine mais nom différent : signalé quand même
+        assert!(pairs.contains(&("1", "4")));
+        // Société à la corbeille et simple voisin de code postal ignorés
+        assert!(pairs.iter().all(|(a, b)| *a != "5" && *b != "5" && *a != "6" && *b != "6"));
+        assert!(found.windows(2).all(|w| w[0].score >= w[1].score));
+        assert!(find_duplicates(&companies, 1.01).is_empty());
+    }
+}
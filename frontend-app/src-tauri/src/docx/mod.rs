@@ -0,0 +1,218 @@
+// Rendu de modèles Word (.docx) sans dépendance externe
+//
+// Les placeholders `{cle}` sont remplacés dans le corps, les en-têtes et
+// les pieds de page, même lorsque Word les a découpés en plusieurs runs.
+// Une ligne de tableau contenant `{groupe.champ}` est répétée pour chaque
+// élément du groupe (et supprimée si le groupe est vide).
+
+pub(crate) mod xml;
+
+use std::collections::HashMap;
+use std::io::{Cursor, Read, Write};
+
+/// Données injectées dans un modèle.
+#[derive(Debug, Clone, Default)]
+pub struct TemplateData {
+    pub values: HashMap<String, String>,
+    pub rows: HashMap<String, Vec<HashMap<String, String>>>,
+    /// Si renseigné, seuls ces tableaux de premier niveau du corps (par
+    /// index) sont conservés ; les autres sont supprimés avec leur titre.
+    pub kept_tables: Option<Vec<usize>>,
+}
+
+impl TemplateData {
+    pub fn set(&mut self, key: &str, value: impl Into<String>) {
+        self.values.insert(key.to_string(), value.into());
+    }
+}
+
+pub struct DocxTemplate {
+    parts: Vec<(String, Vec<u8>)>,
+}
+
+impl DocxTemplate {
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, String> {
+        let mut archive = zip::ZipArchive::new(Cursor::new(bytes))
+            .map_err(|e| format!("Failed to open template: {}", e))?;
+        let mut parts = Vec::with_capacity(archive.len());
+        for i in 0..archive.len() {
+            let mut file = archive
+                .by_index(i)
+                .map_err(|e| format!("Failed to read template entry: {}", e))?;
+            let mut content = Vec::new();
+            file.read_to_end(&mut content)
+                .map_err(|e| format!("Failed to read template entry: {}", e))?;
+            parts.push((file.name().to_string(), content));
+        }
+        if !parts.iter().any(|(name, _)| name == "word/document.xml") {
+            return Err("Template is not a Word document (word/document.xml missing)".to_string());
+        }
+        Ok(Self { parts })
+    }
+
+    /// Liste des placeholders présents dans le modèle, sans doublon.
+    pub fn placeholders(&self) -> Vec<String> {
+        let mut found = Vec::new();
+        for (name, content) in &self.parts {
+            if !is_text_part(name) {
+                continue;
+            }
+            let source = String::from_utf8_lossy(content);
+            for paragraph in xml::elements(&source, "w:p") {
+                let text = xml::paragraph_text(&source[paragraph]);
+                for key in xml::placeholder_keys(&text) {
+                    if !found.contains(&key) {
+                        found.push(key);
+                    }
+                }
+            }
+        }
+        found
+    }
+
+    pub fn render(&self, data: &TemplateData) -> Result<Vec<u8>, String> {
+        let mut buffer = Cursor::new(Vec::new());
+        {
+            let mut writer = zip::ZipWriter::new(&mut buffer);
+            let options = zip::write::FileOptions::default()
+                .compression_method(zip::CompressionMethod::Deflated);
+
+            for (name, content) in &self.parts {
+                let output = if is_text_part(name) {
+                    let source = std::str::from_utf8(content)
+                        .map_err(|e| format!("Invalid UTF-8 in {}: {}", name, e))?;
+                    let mut rendered = render_part(source, data);
+                    if let Some(kept) = data.kept_tables.as_ref().filter(|_| name == "word/document.xml") {
+                        rendered = keep_tables(&rendered, kept);
+                    }
+                    rendered.into_bytes()
+                } else {
+                    content.clone()
+                };
+
+                writer
+                    .start_file(name.as_str(), options)
+                    .map_err(|e| format!("Failed to write {}: {}", name, e))?;
+                writer
+                    .write_all(&output)
+                    .map_err(|e| format!("Failed to write {}: {}", name, e))?;
+            }
+
+            writer
+                .finish()
+                .map_err(|e| format!("Failed to finalize document: {}", e))?;
+        }
+        Ok(buffer.into_inner())
+    }
+}
+
+fn is_text_part(name: &str) -> bool {
+    name == "word/document.xml"
+        || name == "word/footnotes.xml"
+        || name == "word/endnotes.xml"
+        || (name.starts_with("word/header") && name.ends_with(".xml"))
+        || (name.starts_with("word/footer") && name.ends_with(".xml"))
+}
+
+fn render_part(source: &str, data: &TemplateData) -> String {
+    let source = repeat_rows(source, data);
+    let mut output = String::with_capacity(source.len());
+    let mut last = 0;
+    for range in xml::elements(&source, "w:p") {
+        output.push_str(&source[last..range.start]);
+        output.push_str(&xml::replace_in_paragraph(&source[range.clone()], &data.values));
+        last = range.end;
+    }
+    output.push_str(&source[last..]);
+    output
+}
+
+/// Duplique les lignes `<w:tr>` qui référencent un groupe de `rows`.
+fn repeat_rows(source: &str, data: &TemplateData) -> String {
+    if data.rows.is_empty() {
+        return source.to_string();
+    }
+
+    let mut output = String::with_capacity(source.len());
+    let mut last = 0;
+    for range in xml::elements(source, "w:tr") {
+        let row = &source[range.clone()];
+        let text = xml::paragraph_text(row);
+        let group = xml::placeholder_keys(&text).into_iter().find_map(|key| {
+            let (prefix, _) = key.split_once('.')?;
+            data.rows.get_key_value(prefix)
+        });
+
+        let Some((prefix, items)) = group else {
+            continue;
+        };
+
+        output.push_str(&source[last..range.start]);
+        for item in items {
+            let values: HashMap<String, String> = item
+                .iter()
+                .map(|(field, value)| (format!("{}.{}", prefix, field), value.clone()))
+                .collect();
+            let mut copy = String::with_capacity(row.len());
+            let mut row_last = 0;
+            for paragraph in xml::elements(row, "w:p") {
+                copy.push_str(&row[row_last..paragraph.start]);
+                copy.push_str(&xml::replace_in_paragraph(&row[paragraph.clone()], &values));
+                row_last = paragraph.end;
+            }
+            copy.push_str(&row[row_last..]);
+            output.push_str(&copy);
+        }
+        last = range.end;
+    }
+    output.push_str(&source[last..]);
+    output
+}
+
+/// Supprime les tableaux du corps non conservés avec le titre qui les
+/// précède : les paragraphes vides remontés jusqu'au premier paragraphe
+/// non vide inclus.
+fn keep_tables(source: &str, kept: &[usize]) -> String {
+    let Some(body) = xml::elements(source, "w:body").into_iter().next() else {
+        return source.to_string();
+    };
+    let inner_start = source[body.clone()].find('>').map(|i| body.start + i + 1).unwrap_or(body.start);
+    let inner_end = body.end - "</w:body>".len();
+    let children = xml::children(source, inner_start..inner_end);
+
+    let mut removed = vec![false; children.len()];
+    let mut table_index = 0;
+    for (position, child) in children.iter().enumerate() {
+        if xml::tag_name(&source[child.clone()]) != "w:tbl" {
+            continue;
+        }
+        if !kept.contains(&table_index) {
+            removed[position] = true;
+            for previous in (0..position).rev() {
+                let element = &source[children[previous].clone()];
+                match xml::tag_name(element) {
+                    "w:tbl" | "w:sectPr" => break,
+                    "w:p" => {
+                        removed[previous] = true;
+                        if !xml::paragraph_text(element).trim().is_empty() {
+                            break;
+                        }
+                    }
+                    _ => removed[previous] = true,
+                }
+            }
+        }
+        table_index += 1;
+    }
+
+    let mut output = String::with_capacity(source.len());
+    let mut last = 0;
+    for (child, remove) in children.iter().zip(removed) {
+        if remove {
+            output.push_str(&source[last..child.start]);
+            last = child.end;
+        }
+    }
+    output.push_str(&source[last..]);
+    output
+}
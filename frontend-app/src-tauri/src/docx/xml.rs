@@ -0,0 +1,259 @@
+// Parcours minimal du XML WordprocessingML : repérage d'éléments par nom,
+// texte des paragraphes et remplacement des placeholders entre runs.
+
+use std::collections::HashMap;
+use std::ops::Range;
+
+struct Tag<'a> {
+    start: usize,
+    end: usize,
+    name: &'a str,
+    closing: bool,
+    self_closing: bool,
+}
+
+/// Balises de `source[range]`, hors déclarations, commentaires et texte.
+fn tags(source: &str, range: Range<usize>) -> Vec<Tag<'_>> {
+    let mut result = Vec::new();
+    let mut pos = range.start;
+    while let Some(offset) = source[pos..range.end].find('<') {
+        let start = pos + offset;
+        let rest = &source[start..range.end];
+        let end = if rest.starts_with("<!--") {
+            rest.find("-->").map(|i| start + i + 3)
+        } else {
+            rest.find('>').map(|i| start + i + 1)
+        };
+        let Some(end) = end else { break };
+        pos = end;
+
+        if rest.starts_with("<?") || rest.starts_with("<!") {
+            continue;
+        }
+        let closing = rest.starts_with("</");
+        let name_start = if closing { start + 2 } else { start + 1 };
+        let name_end = source[name_start..end]
+            .find(|c: char| c.is_whitespace() || c == '>' || c == '/')
+            .map(|i| name_start + i)
+            .unwrap_or(end);
+        result.push(Tag {
+            start,
+            end,
+            name: &source[name_start..name_end],
+            closing,
+            self_closing: !closing && source[..end].ends_with("/>"),
+        });
+    }
+    result
+}
+
+/// Plages des éléments `name` les plus externes (un élément imbriqué dans
+/// un élément du même nom n'est pas renvoyé séparément).
+pub fn elements(source: &str, name: &str) -> Vec<Range<usize>> {
+    let mut result = Vec::new();
+    let mut depth = 0;
+    let mut start = 0;
+    for tag in tags(source, 0..source.len()) {
+        if tag.name != name {
+            continue;
+        }
+        if tag.closing {
+            if depth > 0 {
+                depth -= 1;
+                if depth == 0 {
+                    result.push(start..tag.end);
+                }
+            }
+        } else if tag.self_closing {
+            if depth == 0 {
+                result.push(tag.start..tag.end);
+            }
+        } else {
+            if depth == 0 {
+                start = tag.start;
+            }
+            depth += 1;
+        }
+    }
+    result
+}
+
+/// Éléments enfants directs contenus dans `source[range]`.
+pub fn children(source: &str, range: Range<usize>) -> Vec<Range<usize>> {
+    let mut result = Vec::new();
+    let mut depth = 0;
+    let mut start = 0;
+    for tag in tags(source, range) {
+        if tag.closing {
+            depth -= 1;
+            if depth == 0 {
+                result.push(start..tag.end);
+            }
+        } else if tag.self_closing {
+            if depth == 0 {
+                result.push(tag.start..tag.end);
+            }
+        } else {
+            if depth == 0 {
+                start = tag.start;
+            }
+            depth += 1;
+        }
+    }
+    result
+}
+
+/// Nom de l'élément racine d'un fragment, ex. `w:tbl`.
+pub fn tag_name(element: &str) -> &str {
+    let rest = element.strip_prefix('<').unwrap_or(element);
+    let end = rest
+        .find(|c: char| c.is_whitespace() || c == '>' || c == '/')
+        .unwrap_or(rest.len());
+    &rest[..end]
+}
+
+/// Nœuds `<w:t>` : (début de la balise ouvrante, plage du contenu).
+fn text_nodes(fragment: &str) -> Vec<(usize, Range<usize>)> {
+    let mut nodes = Vec::new();
+    let mut open: Option<(usize, usize)> = None;
+    for tag in tags(fragment, 0..fragment.len()) {
+        if tag.name != "w:t" || tag.self_closing {
+            continue;
+        }
+        if tag.closing {
+            if let Some((tag_start, content_start)) = open.take() {
+                nodes.push((tag_start, content_start..tag.start));
+            }
+        } else {
+            open = Some((tag.start, tag.end));
+        }
+    }
+    nodes
+}
+
+/// Texte visible d'un fragment (concaténation des `<w:t>`).
+pub fn paragraph_text(fragment: &str) -> String {
+    text_nodes(fragment)
+        .into_iter()
+        .map(|(_, content)| unescape(&fragment[content]))
+        .collect()
+}
+
+/// Clés des placeholders `{cle}` présents dans un texte.
+pub fn placeholder_keys(text: &str) -> Vec<String> {
+    let mut keys = Vec::new();
+    let mut rest = text;
+    while let Some(open) = rest.find('{') {
+        rest = &rest[open + 1..];
+        if let Some(close) = rest.find('}') {
+            let key = &rest[..close];
+            if is_key(key) {
+                keys.push(key.to_string());
+                rest = &rest[close + 1..];
+            }
+        }
+    }
+    keys
+}
+
+fn is_key(key: &str) -> bool {
+    !key.is_empty()
+        && key
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '.')
+}
+
+/// Remplace les placeholders connus d'un paragraphe. Le texte de remplacement
+/// est placé dans le run qui contient l'accolade ouvrante afin de garder sa
+/// mise en forme ; le reste du placeholder est retiré des runs suivants.
+pub fn replace_in_paragraph(paragraph: &str, values: &HashMap<String, String>) -> String {
+    let nodes = text_nodes(paragraph);
+    if nodes.is_empty() {
+        return paragraph.to_string();
+    }
+    let mut contents: Vec<String> = nodes
+        .iter()
+        .map(|(_, content)| paragraph[content.clone()].to_string())
+        .collect();
+    let mut modified = vec![false; contents.len()];
+
+    let mut cursor = 0;
+    loop {
+        let joined: String = contents.concat();
+        let Some(open) = joined[cursor..].find('{').map(|i| cursor + i) else {
+            break;
+        };
+        let Some(close) = joined[open..].find('}').map(|i| open + i) else {
+            break;
+        };
+        let key = &joined[open + 1..close];
+        let Some(value) = values.get(key).filter(|_| is_key(key)) else {
+            cursor = open + 1;
+            continue;
+        };
+        let replacement = escape(value);
+
+        let (first, first_offset) = locate(&contents, open);
+        let (last, last_offset) = locate(&contents, close);
+        if first == last {
+            contents[first].replace_range(first_offset..=last_offset, &replacement);
+        } else {
+            contents[first].replace_range(first_offset.., &replacement);
+            for content in contents.iter_mut().take(last).skip(first + 1) {
+                content.clear();
+            }
+            contents[last].replace_range(..=last_offset, "");
+        }
+        for flag in modified.iter_mut().take(last + 1).skip(first) {
+            *flag = true;
+        }
+        cursor = open + replacement.len();
+    }
+
+    if !modified.iter().any(|m| *m) {
+        return paragraph.to_string();
+    }
+
+    let mut output = String::with_capacity(paragraph.len());
+    let mut last = 0;
+    for (index, (tag_start, content)) in nodes.iter().enumerate() {
+        if modified[index] {
+            output.push_str(&paragraph[last..*tag_start]);
+            output.push_str("<w:t xml:space=\"preserve\">");
+        } else {
+            output.push_str(&paragraph[last..content.start]);
+        }
+        output.push_str(&contents[index]);
+        last = content.end;
+    }
+    output.push_str(&paragraph[last..]);
+    output
+}
+
+/// Position (nœud, décalage) d'un octet du texte concaténé.
+fn locate(contents: &[String], position: usize) -> (usize, usize) {
+    let mut offset = position;
+    for (index, content) in contents.iter().enumerate() {
+        if offset < content.len() {
+            return (index, offset);
+        }
+        offset -= content.len();
+    }
+    (contents.len() - 1, offset)
+}
+
+pub fn escape(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+fn unescape(value: &str) -> String {
+    value
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&amp;", "&")
+}
@@ -0,0 +1,185 @@
+// Sérialisation UN/CEFACT Cross Industry Invoice D16B (profil Factur-X EN 16931)
+
+use super::{
+    amount, element, escape_xml, quantity, EInvoice, Party, EN16931_CUSTOMIZATION_ID,
+    INVOICE_TYPE_CODE, UNIT_CODE_ONE,
+};
+use chrono::NaiveDate;
+use std::fmt::Write;
+
+pub fn to_xml(invoice: &EInvoice) -> String {
+    let mut xml = String::new();
+
+    xml.push_str("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    xml.push_str(
+        "<rsm:CrossIndustryInvoice \
+         xmlns:rsm=\"urn:un:unece:uncefact:data:standard:CrossIndustryInvoice:100\" \
+         xmlns:ram=\"urn:un:unece:uncefact:data:standard:ReusableAggregateBusinessInformationEntity:100\" \
+         xmlns:qdt=\"urn:un:unece:uncefact:data:standard:QualifiedDataType:100\" \
+         xmlns:udt=\"urn:un:unece:uncefact:data:standard:UnqualifiedDataType:100\">\n",
+    );
+
+    xml.push_str("  <rsm:ExchangedDocumentContext>\n");
+    xml.push_str("    <ram:GuidelineSpecifiedDocumentContextParameter>\n");
+    element(&mut xml, 3, "ram:ID", EN16931_CUSTOMIZATION_ID);
+    xml.push_str("    </ram:GuidelineSpecifiedDocumentContextParameter>\n");
+    xml.push_str("  </rsm:ExchangedDocumentContext>\n");
+
+    xml.push_str("  <rsm:ExchangedDocument>\n");
+    element(&mut xml, 2, "ram:ID", &invoice.number);
+    element(&mut xml, 2, "ram:TypeCode", INVOICE_TYPE_CODE);
+    xml.push_str("    <ram:IssueDateTime>\n");
+    date_time_string(&mut xml, 3, invoice.issue_date);
+    xml.push_str("    </ram:IssueDateTime>\n");
+    if let Some(note) = &invoice.note {
+        xml.push_str("    <ram:IncludedNote>\n");
+        element(&mut xml, 3, "ram:Content", note);
+        xml.push_str("    </ram:IncludedNote>\n");
+    }
+    xml.push_str("  </rsm:ExchangedDocument>\n");
+
+    xml.push_str("  <rsm:SupplyChainTradeTransaction>\n");
+
+    for line in &invoice.lines {
+        xml.push_str("    <ram:IncludedSupplyChainTradeLineItem>\n");
+        xml.push_str("      <ram:AssociatedDocumentLineDocument>\n");
+        element(&mut xml, 4, "ram:LineID", &line.id);
+        xml.push_str("      </ram:AssociatedDocumentLineDocument>\n");
+        xml.push_str("      <ram:SpecifiedTradeProduct>\n");
+        element(&mut xml, 4, "ram:Name", &line.name);
+        xml.push_str("      </ram:SpecifiedTradeProduct>\n");
+        xml.push_str("      <ram:SpecifiedLineTradeAgreement>\n");
+        xml.push_str("        <ram:NetPriceProductTradePrice>\n");
+        element(&mut xml, 5, "ram:ChargeAmount", &amount(line.unit_price));
+        xml.push_str("        </ram:NetPriceProductTradePrice>\n");
+        xml.push_str("      </ram:SpecifiedLineTradeAgreement>\n");
+        xml.push_str("      <ram:SpecifiedLineTradeDelivery>\n");
+        let _ = writeln!(xml, "        <ram:BilledQuantity unitCode=\"{}\">{}</ram:BilledQuantity>", UNIT_CODE_ONE, quantity(line.quantity));
+        xml.push_str("      </ram:SpecifiedLineTradeDelivery>\n");
+        xml.push_str("      <ram:SpecifiedLineTradeSettlement>\n");
+        xml.push_str("        <ram:ApplicableTradeTax>\n");
+        element(&mut xml, 5, "ram:TypeCode", "VAT");
+        element(&mut xml, 5, "ram:CategoryCode", invoice.vat_category.code());
+        element(&mut xml, 5, "ram:RateApplicablePercent", &amount(invoice.vat_rate));
+        xml.push_str("        </ram:ApplicableTradeTax>\n");
+        xml.push_str("        <ram:SpecifiedTradeSettlementLineMonetarySummation>\n");
+        element(&mut xml, 5, "ram:LineTotalAmount", &amount(line.net_amount));
+        xml.push_str("        </ram:SpecifiedTradeSettlementLineMonetarySummation>\n");
+        xml.push_str("      </ram:SpecifiedLineTradeSettlement>\n");
+        xml.push_str("    </ram:IncludedSupplyChainTradeLineItem>\n");
+    }
+
+    xml.push_str("    <ram:ApplicableHeaderTradeAgreement>\n");
+    if let Some(po) = &invoice.purchase_order {
+        element(&mut xml, 3, "ram:BuyerReference", po);
+    }
+    trade_party(&mut xml, "ram:SellerTradeParty", &invoice.seller);
+    trade_party(&mut xml, "ram:BuyerTradeParty", &invoice.buyer);
+    if let Some(po) = &invoice.purchase_order {
+        xml.push_str("      <ram:BuyerOrderReferencedDocument>\n");
+        element(&mut xml, 4, "ram:IssuerAssignedID", po);
+        xml.push_str("      </ram:BuyerOrderReferencedDocument>\n");
+    }
+    xml.push_str("    </ram:ApplicableHeaderTradeAgreement>\n");
+
+    xml.push_str("    <ram:ApplicableHeaderTradeDelivery/>\n");
+
+    xml.push_str("    <ram:ApplicableHeaderTradeSettlement>\n");
+    element(&mut xml, 3, "ram:InvoiceCurrencyCode", &invoice.currency);
+    xml.push_str("      <ram:ApplicableTradeTax>\n");
+    element(&mut xml, 4, "ram:CalculatedAmount", &amount(invoice.tax_total()));
+    element(&mut xml, 4, "ram:TypeCode", "VAT");
+    if let Some(reason) = &invoice.vat_exemption_reason {
+        element(&mut xml, 4, "ram:ExemptionReason", reason);
+    }
+    element(&mut xml, 4, "ram:BasisAmount", &amount(invoice.line_total()));
+    element(&mut xml, 4, "ram:CategoryCode", invoice.vat_category.code());
+    element(&mut xml, 4, "ram:RateApplicablePercent", &amount(invoice.vat_rate));
+    xml.push_str("      </ram:ApplicableTradeTax>\n");
+
+    if invoice.payment_terms.is_some() || invoice.due_date.is_some() {
+        xml.push_str("      <ram:SpecifiedTradePaymentTerms>\n");
+        if let Some(terms) = &invoice.payment_terms {
+            element(&mut xml, 4, "ram:Description", terms);
+        }
+        if let Some(due) = invoice.due_date {
+            xml.push_str("        <ram:DueDateDateTime>\n");
+            date_time_string(&mut xml, 5, due);
+            xml.push_str("        </ram:DueDateDateTime>\n");
+        }
+        xml.push_str("      </ram:SpecifiedTradePaymentTerms>\n");
+    }
+
+    xml.push_str("      <ram:SpecifiedTradeSettlementHeaderMonetarySummation>\n");
+    element(&mut xml, 4, "ram:LineTotalAmount", &amount(invoice.line_total()));
+    element(&mut xml, 4, "ram:TaxBasisTotalAmount", &amount(invoice.line_total()));
+    let _ = writeln!(
+        xml,
+        "        <ram:TaxTotalAmount currencyID=\"{}\">{}</ram:TaxTotalAmount>",
+        escape_xml(&invoice.currency),
+        amount(invoice.tax_total())
+    );
+    element(&mut xml, 4, "ram:GrandTotalAmount", &amount(invoice.grand_total()));
+    element(&mut xml, 4, "ram:DuePayableAmount", &amount(invoice.grand_total()));
+    xml.push_str("      </ram:SpecifiedTradeSettlementHeaderMonetarySummation>\n");
+    xml.push_str("    </ram:ApplicableHeaderTradeSettlement>\n");
+
+    xml.push_str("  </rsm:SupplyChainTradeTransaction>\n");
+    xml.push_str("</rsm:CrossIndustryInvoice>\n");
+    xml
+}
+
+fn trade_party(xml: &mut String, tag: &str, party: &Party) {
+    let _ = writeln!(xml, "      <{}>", tag);
+    element(xml, 4, "ram:Name", &party.name);
+    if let Some(legal_id) = &party.legal_id {
+        xml.push_str("        <ram:SpecifiedLegalOrganization>\n");
+        element(xml, 5, "ram:ID", legal_id);
+        xml.push_str("        </ram:SpecifiedLegalOrganization>\n");
+    }
+    if party.contact_name.is_some() || party.email.is_some() || party.phone.is_some() {
+        xml.push_str("        <ram:DefinedTradeContact>\n");
+        if let Some(name) = &party.contact_name {
+            element(xml, 5, "ram:PersonName", name);
+        }
+        if let Some(phone) = &party.phone {
+            xml.push_str("          <ram:TelephoneUniversalCommunication>\n");
+            element(xml, 6, "ram:CompleteNumber", phone);
+            xml.push_str("          </ram:TelephoneUniversalCommunication>\n");
+        }
+        if let Some(email) = &party.email {
+            xml.push_str("          <ram:EmailURIUniversalCommunication>\n");
+            element(xml, 6, "ram:URIID", email);
+            xml.push_str("          </ram:EmailURIUniversalCommunication>\n");
+        }
+        xml.push_str("        </ram:DefinedTradeContact>\n");
+    }
+    xml.push_str("        <ram:PostalTradeAddress>\n");
+    if let Some(zip) = &party.postal_code {
+        element(xml, 5, "ram:PostcodeCode", zip);
+    }
+    if let Some(street) = &party.address {
+        element(xml, 5, "ram:LineOne", street);
+    }
+    if let Some(city) = &party.city {
+        element(xml, 5, "ram:CityName", city);
+    }
+    element(xml, 5, "ram:CountryID", &party.country_code);
+    xml.push_str("        </ram:PostalTradeAddress>\n");
+    if let Some(vat_id) = &party.vat_id {
+        xml.push_str("        <ram:SpecifiedTaxRegistration>\n");
+        let _ = writeln!(xml, "          <ram:ID schemeID=\"VA\">{}</ram:ID>", escape_xml(vat_id));
+        xml.push_str("        </ram:SpecifiedTaxRegistration>\n");
+    }
+    let _ = writeln!(xml, "      </{}>", tag);
+}
+
+/// Date au format 102 (AAAAMMJJ) exigé par CII.
+fn date_time_string(xml: &mut String, depth: usize, date: NaiveDate) {
+    let _ = writeln!(
+        xml,
+        "{}<udt:DateTimeString format=\"102\">{}</udt:DateTimeString>",
+        "  ".repeat(depth),
+        date.format("%Y%m%d")
+    );
+}
@@ -0,0 +1,137 @@
+// Factur-X (profil EN 16931) : PDF/A-3 lisible qui embarque le XML CII
+// sous le nom imposé `factur-x.xml`.
+
+use super::{amount, cii, quantity, EInvoice};
+use crate::pdf::font::FontSet;
+use crate::pdf::{Attachment, PdfPage, PdfWriter, PAGE_HEIGHT};
+
+pub const ATTACHMENT_NAME: &str = "factur-x.xml";
+
+/// Schéma d'extension XMP requis par PDF/A pour déclarer l'espace de noms fx.
+const FX_XMP_EXTENSION: &str = "<rdf:Description rdf:about=\"\" \
+xmlns:pdfaExtension=\"http://www.aiim.org/pdfa/ns/extension/\" \
+xmlns:pdfaSchema=\"http://www.aiim.org/pdfa/ns/schema#\" \
+xmlns:pdfaProperty=\"http://www.aiim.org/pdfa/ns/property#\">\n\
+<pdfaExtension:schemas><rdf:Bag><rdf:li rdf:parseType=\"Resource\">\n\
+<pdfaSchema:schema>Factur-X PDFA Extension Schema</pdfaSchema:schema>\n\
+<pdfaSchema:namespaceURI>urn:factur-x:pdfa:CrossIndustryDocument:invoice:1p0#</pdfaSchema:namespaceURI>\n\
+<pdfaSchema:prefix>fx</pdfaSchema:prefix>\n\
+<pdfaSchema:property><rdf:Seq>\n\
+<rdf:li rdf:parseType=\"Resource\"><pdfaProperty:name>DocumentFileName</pdfaProperty:name><pdfaProperty:valueType>Text</pdfaProperty:valueType><pdfaProperty:category>external</pdfaProperty:category><pdfaProperty:description>Name of the embedded XML invoice file</pdfaProperty:description></rdf:li>\n\
+<rdf:li rdf:parseType=\"Resource\"><pdfaProperty:name>DocumentType</pdfaProperty:name><pdfaProperty:valueType>Text</pdfaProperty:valueType><pdfaProperty:category>external</pdfaProperty:category><pdfaProperty:description>INVOICE</pdfaProperty:description></rdf:li>\n\
+<rdf:li rdf:parseType=\"Resource\"><pdfaProperty:name>Version</pdfaProperty:name><pdfaProperty:valueType>Text</pdfaProperty:valueType><pdfaProperty:category>external</pdfaProperty:category><pdfaProperty:description>Version of the Factur-X XML schema</pdfaProperty:description></rdf:li>\n\
+<rdf:li rdf:parseType=\"Resource\"><pdfaProperty:name>ConformanceLevel</pdfaProperty:name><pdfaProperty:valueType>Text</pdfaProperty:valueType><pdfaProperty:category>external</pdfaProperty:category><pdfaProperty:description>Conformance level of the embedded XML invoice</pdfaProperty:description></rdf:li>\n\
+</rdf:Seq></pdfaSchema:property>\n\
+</rdf:li></rdf:Bag></pdfaExtension:schemas>\n\
+</rdf:Description>\n\
+<rdf:Description rdf:about=\"\" xmlns:fx=\"urn:factur-x:pdfa:CrossIndustryDocument:invoice:1p0#\">\n\
+<fx:DocumentType>INVOICE</fx:DocumentType>\n\
+<fx:DocumentFileName>factur-x.xml</fx:DocumentFileName>\n\
+<fx:Version>1.0</fx:Version>\n\
+<fx:ConformanceLevel>EN 16931</fx:ConformanceLevel>\n\
+</rdf:Description>\n";
+
+pub fn to_pdf(invoice: &EInvoice) -> Result<Vec<u8>, String> {
+    let xml = cii::to_xml(invoice);
+
+    let mut writer = PdfWriter::new(
+        format!("Invoice {}", invoice.number),
+        invoice.seller.name.clone(),
+    );
+    // Factur-X exige un PDF/A-3 : polices embarquées
+    writer.fonts = FontSet::bundled();
+    if !writer.is_pdfa() {
+        return Err("Factur-X requires embedded fonts for PDF/A-3".to_string());
+    }
+    writer.pages.push(summary_page(invoice));
+    writer.attachments.push(Attachment {
+        file_name: ATTACHMENT_NAME.to_string(),
+        mime_type: "text/xml".to_string(),
+        description: "Factur-X invoice".to_string(),
+        relationship: "Data".to_string(),
+        data: xml.into_bytes(),
+    });
+    writer.extra_xmp = Some(FX_XMP_EXTENSION.to_string());
+    writer.render()
+}
+
+/// Représentation visuelle de la facture ; la valeur légale est portée
+/// par le XML embarqué.
+fn summary_page(invoice: &EInvoice) -> PdfPage {
+    let mut page = PdfPage::default();
+    let mut y = PAGE_HEIGHT - 60.0;
+
+    page.text(50.0, y, 18.0, true, format!("INVOICE {}", invoice.number));
+    y -= 24.0;
+    page.text(50.0, y, 10.0, false, format!("Issue date: {}", invoice.issue_date.format("%Y-%m-%d")));
+    if let Some(due) = invoice.due_date {
+        y -= 14.0;
+        page.text(50.0, y, 10.0, false, format!("Due date: {}", due.format("%Y-%m-%d")));
+    }
+    if let Some(po) = &invoice.purchase_order {
+        y -= 14.0;
+        page.text(50.0, y, 10.0, false, format!("Purchase order: {}", po));
+    }
+
+    y -= 30.0;
+    let top = y;
+    for (x, title, party) in [(50.0, "Seller", &invoice.seller), (310.0, "Buyer", &invoice.buyer)] {
+        let mut py = top;
+        page.text(x, py, 11.0, true, title);
+        py -= 14.0;
+        page.text(x, py, 10.0, false, party.name.clone());
+        let city_line = [party.postal_code.as_deref(), party.city.as_deref()]
+            .iter()
+            .flatten()
+            .cloned()
+            .collect::<Vec<_>>()
+            .join(" ");
+        for text in [party.address.clone(), Some(city_line), Some(party.country_code.clone())]
+            .into_iter()
+            .flatten()
+            .filter(|t| !t.is_empty())
+        {
+            py -= 12.0;
+            page.text(x, py, 10.0, false, text);
+        }
+        if let Some(vat_id) = &party.vat_id {
+            py -= 12.0;
+            page.text(x, py, 10.0, false, format!("VAT: {}", vat_id));
+        }
+        y = y.min(py);
+    }
+
+    y -= 36.0;
+    page.text(50.0, y, 10.0, true, "Description");
+    page.text(330.0, y, 10.0, true, "Qty");
+    page.text(390.0, y, 10.0, true, "Unit price");
+    page.text(480.0, y, 10.0, true, "Amount");
+    for line in &invoice.lines {
+        y -= 16.0;
+        page.text(50.0, y, 10.0, false, line.name.clone());
+        page.text(330.0, y, 10.0, false, quantity(line.quantity));
+        page.text(390.0, y, 10.0, false, amount(line.unit_price));
+        page.text(480.0, y, 10.0, false, amount(line.net_amount));
+    }
+
+    y -= 30.0;
+    page.text(330.0, y, 10.0, false, "Total excl. VAT");
+    page.text(480.0, y, 10.0, false, format!("{} {}", amount(invoice.line_total()), invoice.currency));
+    y -= 14.0;
+    page.text(330.0, y, 10.0, false, format!("VAT {} %", amount(invoice.vat_rate)));
+    page.text(480.0, y, 10.0, false, format!("{} {}", amount(invoice.tax_total()), invoice.currency));
+    y -= 14.0;
+    page.text(330.0, y, 10.0, true, "Total due");
+    page.text(480.0, y, 10.0, true, format!("{} {}", amount(invoice.grand_total()), invoice.currency));
+
+    if let Some(reason) = &invoice.vat_exemption_reason {
+        y -= 24.0;
+        page.text(50.0, y, 9.0, false, reason.clone());
+    }
+    if let Some(terms) = &invoice.payment_terms {
+        y -= 14.0;
+        page.text(50.0, y, 9.0, false, format!("Payment terms: {}", terms));
+    }
+
+    page
+}
@@ -0,0 +1,152 @@
+// Sérialisation UBL 2.1 (OASIS Invoice-2), ordre des éléments conforme à l'XSD
+
+use super::{
+    amount, element, escape_xml, quantity, EInvoice, Party, EN16931_CUSTOMIZATION_ID,
+    INVOICE_TYPE_CODE, UNIT_CODE_ONE,
+};
+use std::fmt::Write;
+
+pub fn to_xml(invoice: &EInvoice) -> String {
+    let cur = escape_xml(&invoice.currency);
+    let mut xml = String::new();
+
+    xml.push_str("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    xml.push_str(
+        "<Invoice xmlns=\"urn:oasis:names:specification:ubl:schema:xsd:Invoice-2\" \
+         xmlns:cac=\"urn:oasis:names:specification:ubl:schema:xsd:CommonAggregateComponents-2\" \
+         xmlns:cbc=\"urn:oasis:names:specification:ubl:schema:xsd:CommonBasicComponents-2\">\n",
+    );
+    element(&mut xml, 1, "cbc:CustomizationID", EN16931_CUSTOMIZATION_ID);
+    element(&mut xml, 1, "cbc:ID", &invoice.number);
+    element(&mut xml, 1, "cbc:IssueDate", &invoice.issue_date.format("%Y-%m-%d").to_string());
+    if let Some(due) = invoice.due_date {
+        element(&mut xml, 1, "cbc:DueDate", &due.format("%Y-%m-%d").to_string());
+    }
+    element(&mut xml, 1, "cbc:InvoiceTypeCode", INVOICE_TYPE_CODE);
+    if let Some(note) = &invoice.note {
+        element(&mut xml, 1, "cbc:Note", note);
+    }
+    element(&mut xml, 1, "cbc:DocumentCurrencyCode", &invoice.currency);
+    if let Some(po) = &invoice.purchase_order {
+        element(&mut xml, 1, "cbc:BuyerReference", po);
+        xml.push_str("  <cac:OrderReference>\n");
+        element(&mut xml, 2, "cbc:ID", po);
+        xml.push_str("  </cac:OrderReference>\n");
+    }
+
+    xml.push_str("  <cac:AccountingSupplierParty>\n");
+    party(&mut xml, &invoice.seller);
+    xml.push_str("  </cac:AccountingSupplierParty>\n");
+    xml.push_str("  <cac:AccountingCustomerParty>\n");
+    party(&mut xml, &invoice.buyer);
+    xml.push_str("  </cac:AccountingCustomerParty>\n");
+
+    if let Some(terms) = &invoice.payment_terms {
+        xml.push_str("  <cac:PaymentTerms>\n");
+        element(&mut xml, 2, "cbc:Note", terms);
+        xml.push_str("  </cac:PaymentTerms>\n");
+    }
+
+    xml.push_str("  <cac:TaxTotal>\n");
+    let _ = writeln!(xml, "    <cbc:TaxAmount currencyID=\"{}\">{}</cbc:TaxAmount>", cur, amount(invoice.tax_total()));
+    xml.push_str("    <cac:TaxSubtotal>\n");
+    let _ = writeln!(xml, "      <cbc:TaxableAmount currencyID=\"{}\">{}</cbc:TaxableAmount>", cur, amount(invoice.line_total()));
+    let _ = writeln!(xml, "      <cbc:TaxAmount currencyID=\"{}\">{}</cbc:TaxAmount>", cur, amount(invoice.tax_total()));
+    xml.push_str("      <cac:TaxCategory>\n");
+    element(&mut xml, 4, "cbc:ID", invoice.vat_category.code());
+    element(&mut xml, 4, "cbc:Percent", &amount(invoice.vat_rate));
+    if let Some(reason) = &invoice.vat_exemption_reason {
+        element(&mut xml, 4, "cbc:TaxExemptionReason", reason);
+    }
+    tax_scheme(&mut xml, 4);
+    xml.push_str("      </cac:TaxCategory>\n");
+    xml.push_str("    </cac:TaxSubtotal>\n");
+    xml.push_str("  </cac:TaxTotal>\n");
+
+    xml.push_str("  <cac:LegalMonetaryTotal>\n");
+    let _ = writeln!(xml, "    <cbc:LineExtensionAmount currencyID=\"{}\">{}</cbc:LineExtensionAmount>", cur, amount(invoice.line_total()));
+    let _ = writeln!(xml, "    <cbc:TaxExclusiveAmount currencyID=\"{}\">{}</cbc:TaxExclusiveAmount>", cur, amount(invoice.line_total()));
+    let _ = writeln!(xml, "    <cbc:TaxInclusiveAmount currencyID=\"{}\">{}</cbc:TaxInclusiveAmount>", cur, amount(invoice.grand_total()));
+    let _ = writeln!(xml, "    <cbc:PayableAmount currencyID=\"{}\">{}</cbc:PayableAmount>", cur, amount(invoice.grand_total()));
+    xml.push_str("  </cac:LegalMonetaryTotal>\n");
+
+    for line in &invoice.lines {
+        xml.push_str("  <cac:InvoiceLine>\n");
+        element(&mut xml, 2, "cbc:ID", &line.id);
+        let _ = writeln!(xml, "    <cbc:InvoicedQuantity unitCode=\"{}\">{}</cbc:InvoicedQuantity>", UNIT_CODE_ONE, quantity(line.quantity));
+        let _ = writeln!(xml, "    <cbc:LineExtensionAmount currencyID=\"{}\">{}</cbc:LineExtensionAmount>", cur, amount(line.net_amount));
+        xml.push_str("    <cac:Item>\n");
+        element(&mut xml, 3, "cbc:Name", &line.name);
+        xml.push_str("      <cac:ClassifiedTaxCategory>\n");
+        element(&mut xml, 4, "cbc:ID", invoice.vat_category.code());
+        element(&mut xml, 4, "cbc:Percent", &amount(invoice.vat_rate));
+        tax_scheme(&mut xml, 4);
+        xml.push_str("      </cac:ClassifiedTaxCategory>\n");
+        xml.push_str("    </cac:Item>\n");
+        xml.push_str("    <cac:Price>\n");
+        let _ = writeln!(xml, "      <cbc:PriceAmount currencyID=\"{}\">{}</cbc:PriceAmount>", cur, amount(line.unit_price));
+        xml.push_str("    </cac:Price>\n");
+        xml.push_str("  </cac:InvoiceLine>\n");
+    }
+
+    xml.push_str("</Invoice>\n");
+    xml
+}
+
+fn party(xml: &mut String, party: &Party) {
+    xml.push_str("    <cac:Party>\n");
+    xml.push_str("      <cac:PartyName>\n");
+    element(xml, 4, "cbc:Name", &party.name);
+    xml.push_str("      </cac:PartyName>\n");
+
+    xml.push_str("      <cac:PostalAddress>\n");
+    if let Some(street) = &party.address {
+        element(xml, 4, "cbc:StreetName", street);
+    }
+    if let Some(city) = &party.city {
+        element(xml, 4, "cbc:CityName", city);
+    }
+    if let Some(zip) = &party.postal_code {
+        element(xml, 4, "cbc:PostalZone", zip);
+    }
+    xml.push_str("        <cac:Country>\n");
+    element(xml, 5, "cbc:IdentificationCode", &party.country_code);
+    xml.push_str("        </cac:Country>\n");
+    xml.push_str("      </cac:PostalAddress>\n");
+
+    if let Some(vat_id) = &party.vat_id {
+        xml.push_str("      <cac:PartyTaxScheme>\n");
+        element(xml, 4, "cbc:CompanyID", vat_id);
+        tax_scheme(xml, 4);
+        xml.push_str("      </cac:PartyTaxScheme>\n");
+    }
+
+    xml.push_str("      <cac:PartyLegalEntity>\n");
+    element(xml, 4, "cbc:RegistrationName", &party.name);
+    if let Some(legal_id) = &party.legal_id {
+        element(xml, 4, "cbc:CompanyID", legal_id);
+    }
+    xml.push_str("      </cac:PartyLegalEntity>\n");
+
+    if party.contact_name.is_some() || party.email.is_some() || party.phone.is_some() {
+        xml.push_str("      <cac:Contact>\n");
+        if let Some(name) = &party.contact_name {
+            element(xml, 4, "cbc:Name", name);
+        }
+        if let Some(phone) = &party.phone {
+            element(xml, 4, "cbc:Telephone", phone);
+        }
+        if let Some(email) = &party.email {
+            element(xml, 4, "cbc:ElectronicMail", email);
+        }
+        xml.push_str("      </cac:Contact>\n");
+    }
+    xml.push_str("    </cac:Party>\n");
+}
+
+fn tax_scheme(xml: &mut String, depth: usize) {
+    let indent = "  ".repeat(depth);
+    let _ = writeln!(xml, "{}<cac:TaxScheme>", indent);
+    element(xml, depth + 1, "cbc:ID", "VAT");
+    let _ = writeln!(xml, "{}</cac:TaxScheme>", indent);
+}
@@ -0,0 +1,110 @@
+// Lecture de fichiers CSV (RFC 4180) : champs entre guillemets, guillemets
+// doublés, retours à la ligne dans un champ. Le séparateur (virgule,
+// point-virgule des exports Excel français, tabulation) est détecté sur la
+// ligne d'en-tête.
+
+/// Séparateurs reconnus, par ordre de préférence à égalité
+const DELIMITERS: [char; 3] = [',', ';', '\t'];
+
+/// Devine le séparateur d'après la première ligne, hors guillemets.
+pub fn detect_delimiter(text: &str) -> char {
+    let mut counts = [0usize; DELIMITERS.len()];
+    let mut quoted = false;
+    for c in text.chars() {
+        match c {
+            '"' => quoted = !quoted,
+            '\n' | '\r' if !quoted => break,
+            _ if !quoted => {
+                if let Some(i) = DELIMITERS.iter().position(|d| *d == c) {
+                    counts[i] += 1;
+                }
+            }
+            _ => {}
+        }
+    }
+    // max_by_key garde le dernier maximum : parcours inversé pour préférer la virgule
+    DELIMITERS
+        .iter()
+        .zip(counts)
+        .rev()
+        .max_by_key(|(_, count)| *count)
+        .map(|(d, _)| *d)
+        .unwrap_or(',')
+}
+
+/// Découpe le texte en lignes de champs ; les lignes vides sont ignorées.
+pub fn parse(text: &str, delimiter: char) -> Vec<Vec<String>> {
+    let text = text.strip_prefix('\u{feff}').unwrap_or(text);
+    let mut rows = Vec::new();
+    let mut row = Vec::new();
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut chars = text.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        if quoted {
+            match c {
+                '"' if chars.peek() == Some(&'"') => {
+                    field.push('"');
+                    chars.next();
+                }
+                '"' => quoted = false,
+                _ => field.push(c),
+            }
+            continue;
+        }
+        match c {
+            '"' if field.is_empty() => quoted = true,
+            '\r' => {}
+            '\n' => {
+                row.push(std::mem::take(&mut field));
+                if row.iter().any(|f| !f.trim().is_empty()) {
+                    rows.push(std::mem::take(&mut row));
+                } else {
+                    row.clear();
+                }
+            }
+            _ if c == delimiter => row.push(std::mem::take(&mut field)),
+            _ => field.push(c),
+        }
+    }
+    row.push(field);
+    if row.iter().any(|f| !f.trim().is_empty()) {
+        rows.push(row);
+    }
+    rows
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn delimiter_is_detected_outside_quotes() {
+        assert_eq!(detect_delimiter("Société;Ville;Code postal\nAcme;Lyon;69001"), ';');
+        assert_eq!(detect_delimiter("\"Nom; complet\",Ville\n"), ',');
+        assert_eq!(detect_delimiter("a\tb\tc"), '\t');
+        // À égalité, la virgule
+        assert_eq!(detect_delimiter("a,b;c"), ',');
+        assert_eq!(detect_delimiter(""), ',');
+    }
+
+    #[test]
+    fn quoted_fields_keep_delimiters_quotes_and_newlines() {
+        let text = "\u{feff}name;notes\r\n\"Acme; Co\";\"Dit \"\"le meilleur\"\"\r\nsur deux lignes\"\r\n;\r\n\r\nGlobex;";
+        assert_eq!(
+            parse(text, ';'),
+            vec![
+                vec!["name".to_string(), "notes".to_string()],
+                vec!["Acme; Co".to_string(), "Dit \"le meilleur\"\r\nsur deux lignes".to_string()],
+                vec!["Globex".to_string(), String::new()],
+            ]
+        );
+    }
+
+    #[test]
+    fn last_line_without_newline_is_kept() {
+        assert_eq!(parse("a,b\n1,2", ','), vec![vec!["a", "b"], vec!["1", "2"]]);
+        assert!(parse("\n \n", ',').is_empty());
+    }
+}
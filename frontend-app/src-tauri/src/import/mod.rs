@@ -0,0 +1,445 @@
+// Import de sociétés et de contacts depuis des fichiers CSV ou vCard
+//
+// Le fichier est d'abord transformé en `ImportRecord` (une ligne CSV ou une
+// carte vCard), puis `plan` valide chaque entrée, regroupe les contacts par
+// société et rapproche les sociétés de celles déjà en base. Le plan est
+// ensuite écrit en une transaction, sauf en simulation.
+
+pub mod csv;
+pub mod vcard;
+
+use crate::db::models::Company;
+use crate::dedupe;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+
+/// Champs qu'une colonne CSV peut alimenter
+pub const IMPORT_FIELDS: &[&str] = &[
+    "company_name", "website", "address", "city", "postal_code", "country", "description",
+    "first_name", "last_name", "email", "phone_number", "is_primary",
+];
+
+/// En-têtes reconnus pour proposer une correspondance (comparés en minuscules)
+const FIELD_ALIASES: &[(&str, &[&str])] = &[
+    ("company_name", &["company", "company name", "organization", "organisation", "société", "societe", "entreprise", "raison sociale", "nom de la société"]),
+    ("website", &["website", "web", "url", "site", "site web", "site internet"]),
+    ("address", &["address", "street", "adresse", "rue"]),
+    ("city", &["city", "town", "ville"]),
+    ("postal_code", &["postal code", "postcode", "zip", "zip code", "code postal", "cp"]),
+    ("country", &["country", "pays"]),
+    ("description", &["description", "notes", "note", "commentaire", "commentaires"]),
+    ("first_name", &["first name", "firstname", "given name", "prénom", "prenom"]),
+    ("last_name", &["last name", "lastname", "surname", "family name", "nom", "nom de famille"]),
+    ("email", &["email", "e-mail", "mail", "email address", "courriel", "adresse e-mail"]),
+    ("phone_number", &["phone", "phone number", "telephone", "téléphone", "tel", "tél", "mobile", "portable"]),
+    ("is_primary", &["primary", "is primary", "main contact", "principal", "contact principal"]),
+];
+
+/// Nombre de lignes renvoyées par l'aperçu
+pub const PREVIEW_ROWS: usize = 20;
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ImportFormat {
+    Csv,
+    Vcard,
+}
+
+/// Champ d'import -> en-tête de colonne du fichier
+pub type ColumnMapping = HashMap<String, String>;
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct CompanyFields {
+    pub name: String,
+    pub website: Option<String>,
+    pub address: Option<String>,
+    pub city: Option<String>,
+    pub postal_code: Option<String>,
+    pub country: Option<String>,
+    pub description: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ContactFields {
+    pub first_name: String,
+    pub last_name: String,
+    pub email: String,
+    pub phone_number: Option<String>,
+    pub is_primary: bool,
+}
+
+/// Une ligne CSV ou une carte vCard
+#[derive(Debug, Clone, Default)]
+pub struct ImportRecord {
+    /// Numéro de ligne du tableur (en-tête = 1) ou rang de la carte
+    pub row: usize,
+    pub company: CompanyFields,
+    pub contact: Option<ContactFields>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CsvPreview {
+    pub delimiter: String,
+    pub headers: Vec<String>,
+    /// Correspondance fournie, ou proposée d'après les en-têtes
+    pub mapping: ColumnMapping,
+    pub rows: Vec<Vec<String>>,
+    pub total_rows: usize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RowStatus {
+    Ok,
+    /// Importée en partie (contact ignoré) ou à vérifier
+    Warning,
+    /// Non importée
+    Error,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RowReport {
+    pub row: usize,
+    pub status: RowStatus,
+    pub company_name: String,
+    pub contact_name: Option<String>,
+    /// Société existante à laquelle la ligne est rattachée
+    pub matched_company_id: Option<String>,
+    pub messages: Vec<String>,
+}
+
+/// Société à créer (ou existante) et contacts à lui ajouter
+#[derive(Debug, Clone)]
+pub struct PlannedCompany {
+    pub existing_id: Option<String>,
+    pub fields: CompanyFields,
+    pub contacts: Vec<ContactFields>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct ImportPlan {
+    pub companies: Vec<PlannedCompany>,
+    pub rows: Vec<RowReport>,
+}
+
+/// Texte du fichier : UTF-8, sinon Windows-1252 (exports Excel)
+pub fn decode(bytes: &[u8]) -> String {
+    match std::str::from_utf8(bytes) {
+        Ok(text) => text.to_string(),
+        Err(_) => encoding_rs::WINDOWS_1252.decode_without_bom_handling(bytes).0.into_owned(),
+    }
+}
+
+/// Propose une colonne pour chaque champ dont l'en-tête est reconnu.
+pub fn suggest_mapping(headers: &[String]) -> ColumnMapping {
+    let mut mapping = ColumnMapping::new();
+    for header in headers {
+        let normalized = header.trim().to_lowercase().replace(['_', '-'], " ");
+        let field = FIELD_ALIASES.iter().find(|(field, aliases)| {
+            *field == normalized.replace(' ', "_")
+                || aliases.iter().any(|alias| alias.replace('-', " ") == normalized)
+        });
+        if let Some((field, _)) = field {
+            mapping.entry(field.to_string()).or_insert_with(|| header.clone());
+        }
+    }
+    mapping
+}
+
+pub fn preview_csv(text: &str, mapping: Option<ColumnMapping>) -> CsvPreview {
+    let delimiter = csv::detect_delimiter(text);
+    let mut rows = csv::parse(text, delimiter);
+    let headers = if rows.is_empty() { Vec::new() } else { rows.remove(0) };
+    let mapping = mapping.unwrap_or_else(|| suggest_mapping(&headers));
+
+    CsvPreview {
+        delimiter: delimiter.to_string(),
+        total_rows: rows.len(),
+        rows: rows.into_iter().take(PREVIEW_ROWS).collect(),
+        headers,
+        mapping,
+    }
+}
+
+fn parse_bool(value: &str) -> bool {
+    matches!(
+        value.trim().to_lowercase().as_str(),
+        "1" | "true" | "yes" | "y" | "x" | "oui" | "o" | "vrai"
+    )
+}
+
+/// Applique la correspondance de colonnes aux lignes du fichier.
+pub fn csv_records(text: &str, mapping: &ColumnMapping) -> Result<Vec<ImportRecord>, String> {
+    if let Some(field) = mapping.keys().find(|f| !IMPORT_FIELDS.contains(&f.as_str())) {
+        return Err(format!("Unknown import field: {}", field));
+    }
+    if !mapping.contains_key("company_name") {
+        return Err("The company name column must be mapped".to_string());
+    }
+
+    let delimiter = csv::detect_delimiter(text);
+    let mut rows = csv::parse(text, delimiter);
+    if rows.is_empty() {
+        return Ok(Vec::new());
+    }
+    let headers = rows.remove(0);
+    let mut columns = HashMap::new();
+    for (field, header) in mapping {
+        let index = headers
+            .iter()
+            .position(|h| h.trim() == header.trim())
+            .ok_or_else(|| format!("Column not found: {}", header))?;
+        columns.insert(field.as_str(), index);
+    }
+
+    Ok(rows
+        .into_iter()
+        .enumerate()
+        .map(|(i, row)| {
+            let value = |field: &str| -> Option<String> {
+                columns
+                    .get(field)
+                    .and_then(|index| row.get(*index))
+                    .map(|v| v.trim().to_string())
+                    .filter(|v| !v.is_empty())
+            };
+            let company = CompanyFields {
+                name: value("company_name").unwrap_or_default(),
+                website: value("website"),
+                address: value("address"),
+                city: value("city"),
+                postal_code: value("postal_code"),
+                country: value("country"),
+                description: value("description"),
+            };
+            let contact = ContactFields {
+                first_name: value("first_name").unwrap_or_default(),
+                last_name: value("last_name").unwrap_or_default(),
+                email: value("email").unwrap_or_default(),
+                phone_number: value("phone_number"),
+                is_primary: value("is_primary").is_some_and(|v| parse_bool(&v)),
+            };
+            let has_contact = !contact.first_name.is_empty()
+                || !contact.last_name.is_empty()
+                || !contact.email.is_empty();
+
+            ImportRecord {
+                // En-tête en ligne 1
+                row: i + 2,
+                company,
+                contact: has_contact.then_some(contact),
+            }
+        })
+        .collect())
+}
+
+fn is_valid_email(email: &str) -> bool {
+    match email.split_once('@') {
+        Some((local, domain)) => {
+            !local.is_empty()
+                && domain.contains('.')
+                && !domain.starts_with('.')
+                && !domain.ends_with('.')
+                && !email.contains(char::is_whitespace)
+        }
+        None => false,
+    }
+}
+
+/// Valide les entrées et prépare l'écriture : les lignes d'une même société
+/// (nom normalisé) sont regroupées, une société déjà en base (même nom
+/// normalisé ou même domaine) reçoit seulement les nouveaux contacts, et un
+/// contact dont l'e-mail est déjà connu est ignoré. `existing` comprend les
+/// sociétés supprimées : leur nom reste pris, l'import les reprend (société
+/// survivante d'une fusion, ou restauration depuis la corbeille).
+pub fn plan(records: Vec<ImportRecord>, existing: &[Company], existing_emails: &HashSet<String>) -> ImportPlan {
+    let is_deleted = |c: &Company| c.is_deleted.unwrap_or(0) != 0;
+    let mut by_name: HashMap<String, &Company> = HashMap::new();
+    let mut by_domain: HashMap<String, &Company> = HashMap::new();
+    // Sociétés actives d'abord : elles l'emportent sur une homonyme supprimée
+    let (live, deleted): (Vec<&Company>, Vec<&Company>) = existing.iter().partition(|c| !is_deleted(c));
+    for company in live.iter().chain(&deleted).copied() {
+        by_name.entry(dedupe::normalize_name(&company.name)).or_insert(company);
+        if let Some(domain) = company.website.as_deref().and_then(dedupe::website_domain) {
+            by_domain.entry(domain).or_insert(company);
+        }
+    }
+
+    let mut plan = ImportPlan::default();
+    let mut planned: HashMap<String, usize> = HashMap::new();
+    let mut seen_emails = existing_emails.clone();
+
+    for record in records {
+        let mut report = RowReport {
+            row: record.row,
+            status: RowStatus::Ok,
+            company_name: record.company.name.clone(),
+            contact_name: record
+                .contact
+                .as_ref()
+                .map(|c| format!("{} {}", c.first_name, c.last_name).trim().to_string()),
+            matched_company_id: None,
+            messages: Vec::new(),
+        };
+
+        if record.company.name.trim().is_empty() {
+            report.status = RowStatus::Error;
+            report.messages.push("Company name is missing".to_string());
+            plan.rows.push(report);
+            continue;
+        }
+        if let Some(contact) = record.contact.as_ref().filter(|c| !c.email.is_empty()) {
+            if !is_valid_email(&contact.email) {
+                report.status = RowStatus::Error;
+                report.messages.push(format!("Invalid email: {}", contact.email));
+                plan.rows.push(report);
+                continue;
+            }
+        }
+
+        let key = dedupe::normalize_name(&record.company.name);
+        let index = match planned.get(&key) {
+            Some(index) => *index,
+            None => {
+                let domain = record.company.website.as_deref().and_then(dedupe::website_domain);
+                let matched = by_name
+                    .get(&key)
+                    .or_else(|| domain.as_ref().and_then(|d| by_domain.get(d)))
+                    .copied();
+                if matched.is_none() {
+                    let similar = live.iter().find(|c| {
+                        dedupe::name_similarity(&dedupe::normalize_name(&c.name), &key) >= dedupe::DEFAULT_THRESHOLD
+                    });
+                    if let Some(similar) = similar {
+                        report.status = RowStatus::Warning;
+                        report.messages.push(format!("Similar to existing company {}", similar.name));
+                    }
+                }
+                match matched.filter(|c| is_deleted(c)) {
+                    Some(Company { merged_into: Some(_), name, .. }) => {
+                        report.messages.push(format!("{} was merged, contacts go to the surviving company", name))
+                    }
+                    Some(company) => report.messages.push(format!("{} is in the trash and will be restored", company.name)),
+                    None => {}
+                }
+                plan.companies.push(PlannedCompany {
+                    existing_id: matched.map(|c| c.id.clone()),
+                    fields: record.company.clone(),
+                    contacts: Vec::new(),
+                });
+                planned.insert(key, plan.companies.len() - 1);
+                plan.companies.len() - 1
+            }
+        };
+
+        let company = &mut plan.companies[index];
+        if let Some(existing_id) = &company.existing_id {
+            report.matched_company_id = Some(existing_id.clone());
+            report.messages.push("Matched an existing company".to_string());
+        }
+
+        if let Some(contact) = record.contact {
+            let email = contact.email.to_lowercase();
+            if !email.is_empty() && !seen_emails.insert(email) {
+                report.status = RowStatus::Warning;
+                report.messages.push(format!("Contact {} already exists, skipped", contact.email));
+            } else {
+                if contact.email.is_empty() {
+                    report.status = RowStatus::Warning;
+                    report.messages.push("Contact has no email".to_string());
+                }
+                company.contacts.push(contact);
+            }
+        }
+        plan.rows.push(report);
+    }
+
+    // Une société existante garde son contact principal, une nouvelle en a
+    // toujours un
+    for company in plan.companies.iter_mut() {
+        if company.existing_id.is_some() {
+            company.contacts.iter_mut().for_each(|c| c.is_primary = false);
+        } else if !company.contacts.iter().any(|c| c.is_primary) {
+            if let Some(first) = company.contacts.first_mut() {
+                first.is_primary = true;
+            }
+        }
+    }
+    plan
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::models::test_fixtures;
+
+    fn company(id: &str, name: &str, is_deleted: i64, merged_into: Option<&str>) -> Company {
+        Company {
+            is_deleted: Some(is_deleted),
+            merged_into: merged_into.map(str::to_string),
+            ..test_fixtures::company(id, name)
+        }
+    }
+
+    fn record(row: usize, company: &str, email: &str) -> ImportRecord {
+        ImportRecord {
+            row,
+            company: CompanyFields { name: company.to_string(), ..Default::default() },
+            contact: Some(ContactFields { last_name: "Contact".to_string(), email: email.to_string(), ..Default::default() }),
+        }
+    }
+
+    #[test]
+    fn windows_1252_is_decoded() {
+        assert_eq!(decode("déjà".as_bytes()), "déjà");
+        assert_eq!(decode(b"Cr\xe9ances \x80 \x8aarl \x9cuvre \x93ok\x94 \x85"), "Créances € Šarl œuvre “ok” …");
+    }
+
+    #[test]
+    fn csv_columns_are_mapped_from_headers() {
+        let text = "Société;Prénom;Nom;E-mail;Principal\nAcme SAS;Ada;Lovelace;ada@acme.test;oui\n;;;;\nGlobex;;;;\n";
+        let preview = preview_csv(text, None);
+        assert_eq!(preview.delimiter, ";");
+        assert_eq!(preview.mapping.get("company_name").map(String::as_str), Some("Société"));
+        assert_eq!(preview.mapping.get("email").map(String::as_str), Some("E-mail"));
+
+        let records = csv_records(text, &preview.mapping).unwrap();
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0].row, 2);
+        let contact = records[0].contact.as_ref().unwrap();
+        assert_eq!((contact.first_name.as_str(), contact.is_primary), ("Ada", true));
+        assert!(records[1].contact.is_none());
+
+        let mut mapping = preview.mapping.clone();
+        mapping.insert("fax".to_string(), "Fax".to_string());
+        assert!(csv_records(text, &mapping).unwrap_err().contains("Unknown import field"));
+        mapping.remove("fax");
+        mapping.remove("company_name");
+        assert!(csv_records(text, &mapping).is_err());
+    }
+
+    #[test]
+    fn deleted_companies_are_reused_instead_of_recreated() {
+        let existing = vec![
+            company("live", "Acme", 0, None),
+            company("trashed", "Acme", 1, None),
+            company("old", "Globex SARL", 1, None),
+            company("merged", "Initech", 1, Some("survivor")),
+        ];
+        let records = vec![
+            record(2, "ACME", "a@acme.test"),
+            record(3, "Globex", "b@globex.test"),
+            record(4, "Initech SA", "c@initech.test"),
+            record(5, "Hooli", "not-an-email"),
+        ];
+        let plan = plan(records, &existing, &HashSet::new());
+
+        let ids: Vec<Option<&str>> = plan.companies.iter().map(|c| c.existing_id.as_deref()).collect();
+        assert_eq!(ids, [Some("live"), Some("old"), Some("merged")]);
+        assert!(plan.rows[1].messages.iter().any(|m| m.contains("trash")));
+        assert!(plan.rows[2].messages.iter().any(|m| m.contains("merged")));
+        assert_eq!(plan.rows[3].status, RowStatus::Error);
+        // Contacts ajoutés à une société existante : pas de nouveau principal
+        assert!(plan.companies.iter().flat_map(|c| &c.contacts).all(|c| !c.is_primary));
+    }
+}
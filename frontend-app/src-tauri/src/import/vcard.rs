@@ -0,0 +1,260 @@
+// Lecture de fichiers vCard 3.0 et 4.0 (RFC 2426, RFC 6350)
+//
+// Seules les propriétés utiles au CRM sont retenues : nom, société, e-mail,
+// téléphone, adresse, site web et note. Chaque carte devient un contact
+// rattaché à la société de sa propriété ORG.
+
+use super::{ContactFields, CompanyFields, ImportRecord};
+
+/// Propriété d'une carte : nom en majuscules, paramètres, valeur brute
+struct Property {
+    name: String,
+    params: Vec<(String, String)>,
+    value: String,
+}
+
+impl Property {
+    fn is_preferred(&self) -> bool {
+        self.params.iter().any(|(name, value)| {
+            (name == "PREF" && value != "0")
+                || (name == "TYPE" && value.split(',').any(|t| t.eq_ignore_ascii_case("pref")))
+        })
+    }
+}
+
+/// Lignes dépliées : une ligne commençant par un espace ou une tabulation
+/// prolonge la précédente.
+fn unfold(text: &str) -> Vec<String> {
+    let mut lines: Vec<String> = Vec::new();
+    for line in text.lines() {
+        match (line.strip_prefix(' ').or_else(|| line.strip_prefix('\t')), lines.last_mut()) {
+            (Some(continuation), Some(last)) => last.push_str(continuation),
+            _ => lines.push(line.to_string()),
+        }
+    }
+    lines
+}
+
+fn parse_property(line: &str) -> Option<Property> {
+    // Le séparateur est le premier ':' hors guillemets (paramètres)
+    let mut quoted = false;
+    let split = line.char_indices().find(|(_, c)| {
+        if *c == '"' {
+            quoted = !quoted;
+        }
+        *c == ':' && !quoted
+    })?;
+    let (head, value) = (&line[..split.0], &line[split.0 + 1..]);
+
+    let mut parts = head.split(';');
+    let name = parts.next()?.trim();
+    // Préfixe de groupe ("item1.EMAIL")
+    let name = name.rsplit('.').next().unwrap_or(name).to_uppercase();
+    let params = parts
+        .map(|param| match param.split_once('=') {
+            Some((key, value)) => (key.trim().to_uppercase(), value.trim_matches('"').to_string()),
+            // vCard 2.1 : "TEL;WORK:..."
+            None => ("TYPE".to_string(), param.trim().to_string()),
+        })
+        .collect();
+
+    Some(Property { name, params, value: value.to_string() })
+}
+
+/// Découpe une valeur structurée sur `;` en tenant compte des échappements,
+/// puis retire ceux-ci.
+fn components(value: &str) -> Vec<String> {
+    let mut parts = vec![String::new()];
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => match chars.next() {
+                Some('n') | Some('N') => parts.last_mut().unwrap().push('\n'),
+                Some(other) => parts.last_mut().unwrap().push(other),
+                None => {}
+            },
+            ';' => parts.push(String::new()),
+            _ => parts.last_mut().unwrap().push(c),
+        }
+    }
+    parts.into_iter().map(|p| p.trim().to_string()).collect()
+}
+
+/// Valeur texte simple, échappements retirés
+fn text(value: &str) -> String {
+    let mut result = String::new();
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => match chars.next() {
+                Some('n') | Some('N') => result.push('\n'),
+                Some(other) => result.push(other),
+                None => {}
+            },
+            _ => result.push(c),
+        }
+    }
+    result.trim().to_string()
+}
+
+fn non_empty(value: String) -> Option<String> {
+    Some(value).filter(|v| !v.is_empty())
+}
+
+/// Valeur préférée d'une propriété répétée (PREF, sinon la première)
+fn preferred<'a>(properties: &'a [Property], name: &str) -> Option<&'a Property> {
+    let mut matching = properties.iter().filter(|p| p.name == name);
+    let first = matching.clone().next();
+    matching.find(|p| p.is_preferred()).or(first)
+}
+
+fn card_to_record(row: usize, properties: &[Property]) -> ImportRecord {
+    let mut record = ImportRecord { row, ..Default::default() };
+
+    let (mut first_name, mut last_name) = match preferred(properties, "N") {
+        Some(n) => {
+            let parts = components(&n.value);
+            (
+                parts.get(1).cloned().unwrap_or_default(),
+                parts.first().cloned().unwrap_or_default(),
+            )
+        }
+        None => (String::new(), String::new()),
+    };
+    // Sans N, le nom affiché est découpé au dernier espace
+    if first_name.is_empty() && last_name.is_empty() {
+        if let Some(full) = preferred(properties, "FN").map(|p| text(&p.value)) {
+            match full.rsplit_once(' ') {
+                Some((first, last)) => (first_name, last_name) = (first.to_string(), last.to_string()),
+                None => last_name = full,
+            }
+        }
+    }
+
+    let company = preferred(properties, "ORG")
+        .and_then(|p| components(&p.value).into_iter().next())
+        .unwrap_or_default();
+    let mut fields = CompanyFields { name: company, ..Default::default() };
+    if let Some(adr) = preferred(properties, "ADR") {
+        // ADR : boîte postale ; complément ; rue ; ville ; région ; code postal ; pays
+        let parts = components(&adr.value);
+        let street: Vec<String> = parts.iter().take(3).filter(|p| !p.is_empty()).cloned().collect();
+        fields.address = non_empty(street.join(", "));
+        fields.city = parts.get(3).cloned().and_then(non_empty);
+        fields.postal_code = parts.get(5).cloned().and_then(non_empty);
+        fields.country = parts.get(6).cloned().and_then(non_empty);
+    }
+    fields.website = preferred(properties, "URL").map(|p| text(&p.value)).and_then(non_empty);
+    fields.description = preferred(properties, "NOTE").map(|p| text(&p.value)).and_then(non_empty);
+    record.company = fields;
+
+    let email = preferred(properties, "EMAIL").map(|p| text(&p.value)).unwrap_or_default();
+    let phone = preferred(properties, "TEL")
+        .map(|p| text(&p.value))
+        // vCard 4 : "tel:+33..."
+        .map(|v| v.strip_prefix("tel:").map(str::to_string).unwrap_or(v))
+        .and_then(non_empty);
+    if !first_name.is_empty() || !last_name.is_empty() || !email.is_empty() {
+        record.contact = Some(ContactFields {
+            first_name,
+            last_name,
+            email,
+            phone_number: phone,
+            is_primary: false,
+        });
+    }
+    record
+}
+
+/// Une entrée par carte, numérotée à partir de 1 dans l'ordre du fichier.
+pub fn parse(text: &str) -> Vec<ImportRecord> {
+    let mut records = Vec::new();
+    let mut card: Option<Vec<Property>> = None;
+
+    for line in unfold(text) {
+        let Some(property) = parse_property(line.trim_end()) else {
+            continue;
+        };
+        match (property.name.as_str(), property.value.trim().to_uppercase().as_str()) {
+            ("BEGIN", "VCARD") => card = Some(Vec::new()),
+            ("END", "VCARD") => {
+                if let Some(properties) = card.take() {
+                    records.push(card_to_record(records.len() + 1, &properties));
+                }
+            }
+            _ => {
+                if let Some(properties) = card.as_mut() {
+                    properties.push(property);
+                }
+            }
+        }
+    }
+    records
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CARDS: &str = "BEGIN:VCARD\r
+VERSION:3.0\r
+N:Lovelace;Ada;;;\r
+FN:Ada Lovelace\r
+ORG:Analytical Engines\\, Ltd;R&D\r
+EMAIL;TYPE=work:ada@old.example\r
+item1.EMAIL;TYPE=INTERNET,pref:ada@engines.example\r
+TEL;WORK:+44 20 1234\r
+ADR;TYPE=work:;Bât. B;12 Main Street;London;;EC1;United Kingdom\r
+URL:https://engines.example\r
+NOTE:Première ligne\\nseconde ligne, très lo\r
+ ngue\r
+END:VCARD\r
+BEGIN:VCARD\r
+VERSION:4.0\r
+FN:Grace Brewster Hopper\r
+TEL;VALUE=uri;PREF=1:tel:+1-555-0100\r
+END:VCARD\r
+BEGIN:VCARD\r
+ORG:Sans contact\r
+END:VCARD\r
+";
+
+    #[test]
+    fn cards_become_company_and_contact_records() {
+        let records = parse(CARDS);
+        assert_eq!(records.len(), 3);
+
+        let ada = &records[0];
+        assert_eq!(ada.row, 1);
+        assert_eq!(ada.company.name, "Analytical Engines, Ltd");
+        assert_eq!(ada.company.address.as_deref(), Some("Bât. B, 12 Main Street"));
+        assert_eq!(ada.company.city.as_deref(), Some("London"));
+        assert_eq!(ada.company.postal_code.as_deref(), Some("EC1"));
+        assert_eq!(ada.company.country.as_deref(), Some("United Kingdom"));
+        assert_eq!(ada.company.website.as_deref(), Some("https://engines.example"));
+        assert_eq!(ada.company.description.as_deref(), Some("Première ligne\nseconde ligne, très longue"));
+        let contact = ada.contact.as_ref().unwrap();
+        assert_eq!((contact.first_name.as_str(), contact.last_name.as_str()), ("Ada", "Lovelace"));
+        assert_eq!(contact.email, "ada@engines.example");
+        assert_eq!(contact.phone_number.as_deref(), Some("+44 20 1234"));
+
+        // Sans N, le nom affiché est coupé au dernier espace
+        let grace = records[1].contact.as_ref().unwrap();
+        assert_eq!((grace.first_name.as_str(), grace.last_name.as_str()), ("Grace Brewster", "Hopper"));
+        assert_eq!(grace.phone_number.as_deref(), Some("+1-555-0100"));
+        assert!(records[1].company.name.is_empty());
+
+        assert_eq!(records[2].company.name, "Sans contact");
+        assert!(records[2].contact.is_none());
+    }
+
+    #[test]
+    fn quoted_parameters_and_lines_outside_cards_are_handled() {
+        let property = parse_property("EMAIL;TYPE=\"work:pref\":a@b.example").unwrap();
+        assert_eq!(property.name, "EMAIL");
+        assert_eq!(property.value, "a@b.example");
+        assert!(!property.is_preferred());
+        assert!(parse_property("no separator").is_none());
+        assert!(parse("FN:Orphan\nEND:VCARD\n").is_empty());
+    }
+}
@@ -1,14 +1,30 @@
 // Prevents additional console window on Windows in release, DO NOT REMOVE!!
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
+mod archive;
+mod attachments;
 mod auth;
 mod commands;
 mod db;
+mod dedupe;
+mod docx;
+mod einvoice;
+mod import;
+mod numbering;
+mod output;
+mod pdf;
+mod settings;
 mod sidecar;
+mod sync_scheduler;
+mod templates;
+mod xlsx;
 
 use commands::{
     auth_commands::*, company_commands::*, customer_commands::*, document_commands::*, 
-    proposal_commands::*, invoice_commands::*, document_generator::generate_proposal_word, sync_commands::*,
+    proposal_commands::*, invoice_commands::*, document_generator::{generate_proposal_word, generate_proposal_pdf}, sync_commands::*,
+    einvoice_commands::export_einvoice, template_commands::*, sidecar_commands::get_sidecar_status,
+    attachment_commands::*, search_commands::global_search, import_commands::*,
+    activity_commands::*, trash_commands::{get_trash, restore_from_trash}, settings_commands::*,
 };
 use tauri::Manager;
 
@@ -19,6 +35,7 @@ fn greet(name: &str) -> String {
 
 fn main() {
     tauri::Builder::default()
+        .manage(commands::auth_commands::CurrentUser::default())
         .setup(|app| {
             println!("🚀 Starting Taury CRM application...");
             
@@ -38,10 +55,18 @@ fn main() {
                 println!("✅ App directory already exists");
             }
 
+            // Profil de serveur actif : il désigne la base à ouvrir
+            let (profiles, error) = settings::ServerProfiles::load_or_default(&app_dir);
+            if let Some(e) = error {
+                eprintln!("❌ Failed to load server profiles, using the default profile: {}", e);
+            }
+            let profile = profiles.active().clone();
+            println!("🌐 Server profile: {} ({})", profile.name, profile.database_file());
+
             // Initialize database
             tauri::async_runtime::block_on(async move {
                 println!("🔧 Initializing database...");
-                let pool = db::init_database(app_dir)
+                let pool = db::init_database(app_dir.clone(), &profile.database_file())
                     .await
                     .map_err(|e| {
                         eprintln!("❌ Database initialization failed: {}", e);
@@ -49,19 +74,26 @@ fn main() {
                     })
                     .expect("Failed to initialize database");
 
-                app.manage(pool);
+                app.manage(pool.clone());
                 println!("✅ Database pool managed");
                 
-                // Lancer le sidecar Python pour génération de documents
+                // Lancer le sidecar Python (API de documents historique)
                 println!("🐍 Starting document generator sidecar...");
+                // Supervisé en tâche de fond : l'état est exposé par get_sidecar_status
                 let sidecar_manager = sidecar::SidecarManager::new();
-                if let Err(e) = sidecar_manager.start().await {
-                    eprintln!("⚠️ Failed to start document generator: {}", e);
-                    eprintln!("   Word generation will not be available.");
-                } else {
-                    println!("✅ Document generator started successfully");
-                }
+                sidecar_manager.start();
                 app.manage(sidecar_manager);
+
+                // Synchronisation en tâche de fond, active une fois le serveur configuré
+                let sync_scheduler = sync_scheduler::SyncScheduler::new();
+                match settings::LocalSettings::load(&pool, Some(&app_dir)).await {
+                    Ok(local) => {
+                        sync_scheduler.configure(commands::settings_commands::scheduler_config(&profile, &local.sync));
+                    }
+                    Err(e) => eprintln!("❌ Failed to load settings, background sync disabled: {}", e),
+                }
+                sync_scheduler.start(app.handle());
+                app.manage(sync_scheduler);
             });
 
             println!("✅ Application setup completed");
@@ -73,14 +105,32 @@ fn main() {
             register,
             login,
             logout,
+            restore_session,
             // Company commands
             get_companies,
+            list_companies,
             get_company,
             create_company,
             update_company,
             delete_company,
+            find_duplicate_companies,
+            merge_companies,
+            get_company_merges,
+            // Import
+            preview_csv_import,
+            import_companies,
+            // Activities
+            log_activity,
+            update_activity,
+            delete_activity,
+            get_activities,
+            get_company_timeline,
+            // Trash
+            get_trash,
+            restore_from_trash,
             // Proposal commands
             get_proposals,
+            list_proposals,
             get_proposal,
             create_proposal,
             update_proposal,
@@ -91,6 +141,7 @@ fn main() {
             // Invoice commands
             create_invoice_from_proposal,
             get_all_invoices,
+            list_invoices,
             get_invoice_by_id,
             get_invoice_by_proposal_id,
             update_invoice,
@@ -99,6 +150,8 @@ fn main() {
             get_invoices_by_status,
             get_invoices_by_company,
             generate_invoice_excel,
+            generate_invoice_pdf,
+            export_einvoice,
             // Customer commands
             get_customers,
             get_customer,
@@ -107,14 +160,47 @@ fn main() {
             delete_customer,
             // Document commands
             get_documents,
+            list_documents,
             get_customer_documents,
+            get_company_documents,
+            get_entity_documents,
+            get_document_links,
+            attach_document,
+            detach_document,
             create_document,
             delete_document,
+            get_output_settings,
+            set_output_settings,
+            get_source_documents,
+            verify_document,
+            restore_document,
+            // Attachments
+            get_attachment_states,
+            upload_attachments,
+            open_document_attachment,
             // Document generation
             generate_proposal_word,
+            generate_proposal_pdf,
+            // Template library
+            get_document_templates,
+            validate_document_template,
+            upload_document_template,
+            delete_document_template,
+            // Search
+            global_search,
+            // Sidecar
+            get_sidecar_status,
+            // Settings
+            get_settings,
+            update_settings,
+            get_server_profiles,
+            save_server_profile,
+            delete_server_profile,
+            switch_server_profile,
             // Sync commands
             get_sync_status,
             sync_with_server,
+            get_sync_scheduler_status,
             force_sync_cleanup,
             get_server_url,
             set_server_url,
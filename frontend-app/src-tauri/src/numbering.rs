@@ -0,0 +1,71 @@
+// Numérotation des propositions et des factures
+//
+// Formats définis dans les réglages de l'organisation (`OrgSettings`). Les
+// formats par défaut reproduisent les numéros des versions précédentes.
+
+use chrono::{Datelike, NaiveDate};
+
+/// `2404` puis rang de la facture dans le mois sur 4 chiffres : `24040001`
+pub const DEFAULT_INVOICE_FORMAT: &str = "{YY}{MM}{SEQ}";
+/// `D_Acme_Corp_HTZc+training_240415`
+pub const DEFAULT_PROPOSAL_FORMAT: &str = "D_{company}_{products}_{YY}{MM}{DD}";
+
+/// Placeholders utilisables dans un numéro de facture.
+pub const INVOICE_FIELDS: &[&str] = &["YYYY", "YY", "MM", "DD", "SEQ"];
+/// Placeholders utilisables dans un numéro de proposition.
+pub const PROPOSAL_FIELDS: &[&str] = &["YYYY", "YY", "MM", "DD", "company", "products"];
+
+/// Refuse les placeholders inconnus, les accolades non fermées et, pour
+/// les factures, un format sans `{SEQ}` qui donnerait le même numéro à
+/// toutes les factures du mois.
+pub fn validate_format(format: &str, fields: &[&str]) -> Result<(), String> {
+    let mut rest = format;
+    while let Some(start) = rest.find('{') {
+        let end = rest[start..]
+            .find('}')
+            .ok_or_else(|| format!("Unclosed placeholder in number format: {}", format))?;
+        let name = &rest[start + 1..start + end];
+        if !fields.contains(&name) {
+            return Err(format!(
+                "Unknown placeholder {{{}}} (expected one of: {})",
+                name,
+                fields.join(", ")
+            ));
+        }
+        rest = &rest[start + end + 1..];
+    }
+    if format.trim().is_empty() {
+        return Err("Number format cannot be empty".to_string());
+    }
+    if fields.contains(&"SEQ") && !format.contains("{SEQ}") {
+        return Err("Invoice number format must contain {SEQ}".to_string());
+    }
+    Ok(())
+}
+
+/// Période sur laquelle `{SEQ}` est compté, au format `strftime` : le rang
+/// ne repart à un que si le numéro contient la période, sans quoi
+/// `INV-{SEQ}` ou `{YYYY}-{SEQ}` redonneraient chaque mois les mêmes numéros.
+/// Par mois avec l'année et le mois, par année avec l'année seule, sans
+/// limite (`None`) sinon.
+pub fn sequence_period(format: &str) -> Option<&'static str> {
+    let year = format.contains("{YYYY}") || format.contains("{YY}");
+    match (year, format.contains("{MM}")) {
+        (true, true) => Some("%Y-%m"),
+        (true, false) => Some("%Y"),
+        (false, _) => None,
+    }
+}
+
+/// Numéro obtenu en remplaçant les placeholders de date et ceux de `values`.
+pub fn render(format: &str, date: NaiveDate, values: &[(&str, &str)]) -> String {
+    let mut number = format
+        .replace("{YYYY}", &format!("{:04}", date.year()))
+        .replace("{YY}", &format!("{:02}", date.year() % 100))
+        .replace("{MM}", &format!("{:02}", date.month()))
+        .replace("{DD}", &format!("{:02}", date.day()));
+    for (name, value) in values {
+        number = number.replace(&format!("{{{}}}", name), value);
+    }
+    number
+}
@@ -0,0 +1,165 @@
+// Emplacement et nommage des documents générés
+//
+// Réglages de l'utilisateur, enregistrés avec les réglages du poste
+// (`settings::LocalSettings`) : dossier de sortie (Téléchargements par
+// défaut), modèle de nom de fichier par type de document et rattachement
+// automatique à la fiche client.
+
+use serde::{Deserialize, Serialize};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+const LEGACY_SETTINGS_FILE: &str = "output_settings.json";
+
+/// Placeholders utilisables dans les modèles de nom de fichier.
+pub const PATTERN_FIELDS: &[&str] = &["company", "number", "revision", "date"];
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct OutputSettings {
+    /// Dossier de sortie ; `None` pour le dossier Téléchargements
+    pub directory: Option<String>,
+    pub proposal_pattern: String,
+    pub invoice_pattern: String,
+    /// Enregistrer chaque fichier généré dans `documents`, lié au client
+    pub auto_attach: bool,
+}
+
+impl Default for OutputSettings {
+    fn default() -> Self {
+        Self {
+            directory: None,
+            proposal_pattern: "Proposal_{number}".to_string(),
+            invoice_pattern: "Invoice_{number}".to_string(),
+            auto_attach: false,
+        }
+    }
+}
+
+impl OutputSettings {
+    /// Réglages enregistrés par les versions antérieures au stockage des
+    /// réglages en base, s'ils existent.
+    pub fn load_legacy(app_data_dir: Option<&Path>) -> Result<Option<Self>, String> {
+        match app_data_dir
+            .map(|dir| dir.join(LEGACY_SETTINGS_FILE))
+            .filter(|path| path.exists())
+        {
+            Some(path) => {
+                let content = std::fs::read_to_string(&path)
+                    .map_err(|e| format!("Failed to read output settings: {}", e))?;
+                serde_json::from_str(&content)
+                    .map(Some)
+                    .map_err(|e| format!("Invalid output settings: {}", e))
+            }
+            None => Ok(None),
+        }
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        validate_pattern(&self.proposal_pattern)?;
+        validate_pattern(&self.invoice_pattern)
+    }
+
+    /// Dossier de sortie, créé s'il n'existe pas.
+    pub fn output_dir(&self) -> Result<PathBuf, String> {
+        let dir = match self.directory.as_deref().filter(|d| !d.trim().is_empty()) {
+            Some(dir) => PathBuf::from(dir),
+            None => dirs::download_dir()
+                .or_else(|| dirs::home_dir().map(|home| home.join("Downloads")))
+                .ok_or("Could not find home directory")?,
+        };
+        std::fs::create_dir_all(&dir)
+            .map_err(|e| format!("Failed to create output directory {}: {}", dir.display(), e))?;
+        Ok(dir)
+    }
+}
+
+/// Valeurs des placeholders pour un document.
+#[derive(Debug, Clone)]
+pub struct FileNameFields<'a> {
+    pub company: &'a str,
+    pub number: &'a str,
+    pub revision: i64,
+    /// Date de génération au format AAAA-MM-JJ
+    pub date: String,
+}
+
+impl<'a> FileNameFields<'a> {
+    pub fn new(company: &'a str, number: &'a str, revision: i64) -> Self {
+        Self {
+            company,
+            number,
+            revision,
+            date: chrono::Local::now().format("%Y-%m-%d").to_string(),
+        }
+    }
+}
+
+/// Refuse les placeholders inconnus ou les accolades non fermées.
+pub fn validate_pattern(pattern: &str) -> Result<(), String> {
+    let mut rest = pattern;
+    while let Some(start) = rest.find('{') {
+        let end = rest[start..]
+            .find('}')
+            .ok_or_else(|| format!("Unclosed placeholder in pattern: {}", pattern))?;
+        let name = &rest[start + 1..start + end];
+        if !PATTERN_FIELDS.contains(&name) {
+            return Err(format!(
+                "Unknown placeholder {{{}}} (expected one of: {})",
+                name,
+                PATTERN_FIELDS.join(", ")
+            ));
+        }
+        rest = &rest[start + end + 1..];
+    }
+    if pattern.trim().is_empty() {
+        return Err("File name pattern cannot be empty".to_string());
+    }
+    Ok(())
+}
+
+/// Nom de fichier (sans extension) obtenu en remplaçant les placeholders ;
+/// les caractères interdits par les systèmes de fichiers sont remplacés.
+pub fn render_pattern(pattern: &str, fields: &FileNameFields) -> String {
+    let name = pattern
+        .replace("{company}", fields.company)
+        .replace("{number}", fields.number)
+        .replace("{revision}", &fields.revision.to_string())
+        .replace("{date}", &fields.date);
+    let name: String = name
+        .chars()
+        .map(|c| match c {
+            '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|' => '_',
+            c if c.is_control() => '_',
+            c => c,
+        })
+        .collect();
+    let name = name.trim().trim_end_matches('.').to_string();
+    if name.is_empty() {
+        "document".to_string()
+    } else {
+        name
+    }
+}
+
+/// Écrit le fichier sans jamais en écraser un autre : `name.ext`, sinon
+/// `name (2).ext`, `name (3).ext`...
+pub fn write_unique(dir: &Path, name: &str, extension: &str, bytes: &[u8]) -> Result<PathBuf, String> {
+    let mut counter = 1;
+    loop {
+        let file_name = match counter {
+            1 => format!("{}.{}", name, extension),
+            n => format!("{} ({}).{}", name, n, extension),
+        };
+        let path = dir.join(file_name);
+        match std::fs::OpenOptions::new().write(true).create_new(true).open(&path) {
+            Ok(mut file) => {
+                file.write_all(bytes)
+                    .map_err(|e| format!("Failed to save file: {}", e))?;
+                return Ok(path);
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => counter += 1,
+            Err(e) => return Err(format!("Failed to save file: {}", e)),
+        }
+    }
+}
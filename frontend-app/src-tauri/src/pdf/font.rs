@@ -0,0 +1,205 @@
+// Polices TrueType embarquées (exigence PDF/A) en encodage WinAnsi
+
+use std::path::Path;
+
+/// DejaVu Sans, livrée avec l'application (licence dans `fonts/LICENSE`) :
+/// tout PDF peut embarquer ses polices, sans dépendre du système.
+const BUNDLED_REGULAR: &[u8] = include_bytes!("../../fonts/DejaVuSans.ttf");
+const BUNDLED_BOLD: &[u8] = include_bytes!("../../fonts/DejaVuSans-Bold.ttf");
+
+/// Caractères de la plage 0x80-0x9F de WinAnsiEncoding.
+const WIN_ANSI_HIGH: &[(u8, char)] = &[
+    (0x80, '€'), (0x82, '‚'), (0x83, 'ƒ'), (0x84, '„'), (0x85, '…'), (0x86, '†'),
+    (0x87, '‡'), (0x88, 'ˆ'), (0x89, '‰'), (0x8A, 'Š'), (0x8B, '‹'), (0x8C, 'Œ'),
+    (0x8E, 'Ž'), (0x91, '‘'), (0x92, '’'), (0x93, '“'), (0x94, '”'), (0x95, '•'),
+    (0x96, '–'), (0x97, '—'), (0x98, '˜'), (0x99, '™'), (0x9A, 'š'), (0x9B, '›'),
+    (0x9C, 'œ'), (0x9E, 'ž'), (0x9F, 'Ÿ'),
+];
+
+pub const FIRST_CHAR: u8 = 32;
+pub const LAST_CHAR: u8 = 255;
+
+#[derive(Debug, Clone)]
+pub struct TrueTypeFont {
+    pub data: Vec<u8>,
+    pub name: String,
+    /// Chasse des codes FIRST_CHAR..=LAST_CHAR en millièmes de cadratin
+    pub widths: Vec<i64>,
+    pub bbox: [i64; 4],
+    pub ascent: i64,
+    pub descent: i64,
+    pub cap_height: i64,
+    pub italic_angle: f32,
+}
+
+impl TrueTypeFont {
+    pub fn from_file(path: &Path) -> Result<Self, String> {
+        let data = std::fs::read(path)
+            .map_err(|e| format!("Failed to read font {}: {}", path.display(), e))?;
+        let fallback_name = path
+            .file_stem()
+            .map(|s| s.to_string_lossy().to_string())
+            .unwrap_or("Font".to_string());
+        Self::from_bytes(data, &fallback_name)
+    }
+
+    pub fn from_bytes(data: Vec<u8>, fallback_name: &str) -> Result<Self, String> {
+        let face = ttf_parser::Face::parse(&data, 0)
+            .map_err(|e| format!("Invalid TrueType font: {}", e))?;
+        let units = face.units_per_em() as f32;
+        let scale = |v: i16| (v as f32 * 1000.0 / units).round() as i64;
+
+        let missing_width = face
+            .glyph_hor_advance(ttf_parser::GlyphId(0))
+            .unwrap_or(0);
+        let widths = (FIRST_CHAR..=LAST_CHAR)
+            .map(|code| {
+                let advance = win_ansi_char(code)
+                    .and_then(|c| face.glyph_index(c))
+                    .and_then(|g| face.glyph_hor_advance(g))
+                    .unwrap_or(missing_width);
+                (advance as f32 * 1000.0 / units).round() as i64
+            })
+            .collect();
+
+        let name = face
+            .names()
+            .into_iter()
+            .find(|n| n.name_id == ttf_parser::name_id::POST_SCRIPT_NAME)
+            .and_then(|n| n.to_string())
+            .unwrap_or_else(|| fallback_name.to_string())
+            .chars()
+            .filter(|c| c.is_ascii_alphanumeric() || *c == '-')
+            .collect::<String>();
+
+        let bbox = face.global_bounding_box();
+        Ok(Self {
+            name,
+            widths,
+            bbox: [scale(bbox.x_min), scale(bbox.y_min), scale(bbox.x_max), scale(bbox.y_max)],
+            ascent: scale(face.ascender()),
+            descent: scale(face.descender()),
+            cap_height: face.capital_height().map(scale).unwrap_or_else(|| scale(face.ascender())),
+            italic_angle: face.italic_angle().unwrap_or(0.0),
+            data,
+        })
+    }
+
+    fn text_width(&self, text: &str, size: f32) -> f32 {
+        encode_win_ansi(text)
+            .into_iter()
+            .map(|code| {
+                if code < FIRST_CHAR {
+                    0
+                } else {
+                    self.widths[(code - FIRST_CHAR) as usize]
+                }
+            })
+            .sum::<i64>() as f32
+            * size
+            / 1000.0
+    }
+}
+
+/// Police normale et grasse d'un document. Sans police TrueType (valeur par
+/// défaut), les polices standard Helvetica sont utilisées et le PDF n'est
+/// pas PDF/A.
+#[derive(Debug, Clone, Default)]
+pub struct FontSet {
+    pub regular: Option<TrueTypeFont>,
+    pub bold: Option<TrueTypeFont>,
+}
+
+impl FontSet {
+    /// Charge les polices indiquées, ou à défaut la police livrée.
+    pub fn load(regular: Option<&Path>, bold: Option<&Path>) -> Result<Self, String> {
+        match regular {
+            Some(path) => {
+                let regular = TrueTypeFont::from_file(path)?;
+                let bold = match bold {
+                    Some(path) => Some(TrueTypeFont::from_file(path)?),
+                    None => None,
+                };
+                Ok(Self { regular: Some(regular), bold })
+            }
+            None => Ok(Self::bundled()),
+        }
+    }
+
+    pub fn bundled() -> Self {
+        let load = |data: &[u8], name: &str| {
+            TrueTypeFont::from_bytes(data.to_vec(), name).expect("bundled font is a valid TrueType font")
+        };
+        Self {
+            regular: Some(load(BUNDLED_REGULAR, "DejaVuSans")),
+            bold: Some(load(BUNDLED_BOLD, "DejaVuSans-Bold")),
+        }
+    }
+
+    pub fn is_embedded(&self) -> bool {
+        self.regular.is_some()
+    }
+
+    /// Police grasse, ou la police normale si aucune graisse n'est disponible.
+    pub fn bold_font(&self) -> Option<&TrueTypeFont> {
+        self.bold.as_ref().or(self.regular.as_ref())
+    }
+
+    pub fn text_width(&self, text: &str, size: f32, bold: bool) -> f32 {
+        let font = if bold { self.bold_font() } else { self.regular.as_ref() };
+        match font {
+            Some(font) => font.text_width(text, size),
+            // Approximation de la chasse moyenne d'Helvetica
+            None => text.chars().count() as f32 * size * if bold { 0.58 } else { 0.52 },
+        }
+    }
+}
+
+pub fn win_ansi_char(code: u8) -> Option<char> {
+    match code {
+        0x80..=0x9F => WIN_ANSI_HIGH.iter().find(|(c, _)| *c == code).map(|(_, ch)| *ch),
+        _ => char::from_u32(code as u32),
+    }
+}
+
+/// Encode le texte en WinAnsi ; les caractères hors de cette table sont
+/// remplacés par '?'.
+pub fn encode_win_ansi(text: &str) -> Vec<u8> {
+    text.chars()
+        .map(|c| match c as u32 {
+            0x20..=0x7E | 0xA0..=0xFF => c as u32 as u8,
+            _ => WIN_ANSI_HIGH
+                .iter()
+                .find(|(_, ch)| *ch == c)
+                .map(|(code, _)| *code)
+                .unwrap_or(b'?'),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bundled_fonts_are_embedded() {
+        let fonts = FontSet::bundled();
+        assert!(fonts.is_embedded());
+        let regular = fonts.regular.as_ref().unwrap();
+        let bold = fonts.bold.as_ref().unwrap();
+        assert_eq!((regular.name.as_str(), bold.name.as_str()), ("DejaVuSans", "DejaVuSans-Bold"));
+        assert_eq!(regular.widths.len(), (LAST_CHAR - FIRST_CHAR) as usize + 1);
+        assert!(fonts.text_width("Invoice", 10.0, true) > fonts.text_width("Invoice", 10.0, false));
+    }
+
+    #[test]
+    fn text_is_encoded_in_win_ansi() {
+        assert_eq!(encode_win_ansi("Aé€–Œ"), vec![b'A', 0xE9, 0x80, 0x96, 0x8C]);
+        assert_eq!(encode_win_ansi("→"), vec![b'?']);
+        for code in (FIRST_CHAR..=LAST_CHAR).filter(|c| *c != 0x7F) {
+            if let Some(c) = win_ansi_char(code) {
+                assert_eq!(encode_win_ansi(&c.to_string()), vec![code], "{:#x}", code);
+            }
+        }
+    }
+}
@@ -0,0 +1,156 @@
+// Profil ICC sRGB (v2, matrice + courbes) pour l'OutputIntent PDF/A :
+// primaires et blanc de la norme IEC 61966-2.1 adaptés à D50, courbe de
+// transfert sRGB (segment linéaire puis puissance 2,4) échantillonnée sur
+// 1024 points comme dans le profil de référence.
+
+pub const DESCRIPTION: &str = "sRGB IEC61966-2.1";
+
+pub fn srgb_profile() -> Vec<u8> {
+    let tags: Vec<(&[u8; 4], Vec<u8>)> = vec![
+        (b"desc", description_tag(DESCRIPTION)),
+        (b"cprt", text_tag("No copyright, use freely")),
+        (b"wtpt", xyz_tag(0.9505, 1.0, 1.0891)),
+        (b"rXYZ", xyz_tag(0.4361, 0.2225, 0.0139)),
+        (b"gXYZ", xyz_tag(0.3851, 0.7169, 0.0971)),
+        (b"bXYZ", xyz_tag(0.1431, 0.0606, 0.7141)),
+        (b"rTRC", srgb_curve_tag()),
+        (b"gTRC", srgb_curve_tag()),
+        (b"bTRC", srgb_curve_tag()),
+    ];
+
+    let table_size = 4 + tags.len() * 12;
+    let mut offset = 128 + table_size;
+    let mut table = Vec::with_capacity(table_size);
+    let mut data = Vec::new();
+    table.extend_from_slice(&(tags.len() as u32).to_be_bytes());
+    for (signature, content) in &tags {
+        table.extend_from_slice(*signature);
+        table.extend_from_slice(&(offset as u32).to_be_bytes());
+        table.extend_from_slice(&(content.len() as u32).to_be_bytes());
+        data.extend_from_slice(content);
+        while data.len() % 4 != 0 {
+            data.push(0);
+        }
+        offset = 128 + table_size + data.len();
+    }
+
+    let size = 128 + table.len() + data.len();
+    let mut profile = Vec::with_capacity(size);
+    profile.extend_from_slice(&(size as u32).to_be_bytes());
+    profile.extend_from_slice(&[0; 4]); // CMM
+    profile.extend_from_slice(&[0x02, 0x10, 0x00, 0x00]); // version 2.1
+    profile.extend_from_slice(b"mntr");
+    profile.extend_from_slice(b"RGB ");
+    profile.extend_from_slice(b"XYZ ");
+    for part in [2024u16, 1, 1, 0, 0, 0] {
+        profile.extend_from_slice(&part.to_be_bytes());
+    }
+    profile.extend_from_slice(b"acsp");
+    profile.extend_from_slice(&[0; 24]); // plateforme, drapeaux, fabricant, modèle, attributs
+    profile.extend_from_slice(&0u32.to_be_bytes()); // intention perceptuelle
+    profile.extend_from_slice(&s15_fixed16(0.9642));
+    profile.extend_from_slice(&s15_fixed16(1.0));
+    profile.extend_from_slice(&s15_fixed16(0.8249));
+    profile.resize(128, 0);
+    profile.extend_from_slice(&table);
+    profile.extend_from_slice(&data);
+    profile
+}
+
+fn s15_fixed16(value: f64) -> [u8; 4] {
+    ((value * 65536.0).round() as i32).to_be_bytes()
+}
+
+fn xyz_tag(x: f64, y: f64, z: f64) -> Vec<u8> {
+    let mut tag = b"XYZ \0\0\0\0".to_vec();
+    tag.extend_from_slice(&s15_fixed16(x));
+    tag.extend_from_slice(&s15_fixed16(y));
+    tag.extend_from_slice(&s15_fixed16(z));
+    tag
+}
+
+/// Points de la courbe de transfert
+const CURVE_POINTS: usize = 1024;
+
+/// Valeur linéaire d'une composante sRGB (IEC 61966-2.1)
+fn srgb_to_linear(value: f64) -> f64 {
+    if value <= 0.04045 {
+        value / 12.92
+    } else {
+        ((value + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+fn srgb_curve_tag() -> Vec<u8> {
+    let mut tag = b"curv\0\0\0\0".to_vec();
+    tag.extend_from_slice(&(CURVE_POINTS as u32).to_be_bytes());
+    for i in 0..CURVE_POINTS {
+        let linear = srgb_to_linear(i as f64 / (CURVE_POINTS - 1) as f64);
+        tag.extend_from_slice(&((linear * 65535.0).round() as u16).to_be_bytes());
+    }
+    tag
+}
+
+fn text_tag(text: &str) -> Vec<u8> {
+    let mut tag = b"text\0\0\0\0".to_vec();
+    tag.extend_from_slice(text.as_bytes());
+    tag.push(0);
+    tag
+}
+
+fn description_tag(text: &str) -> Vec<u8> {
+    let mut tag = b"desc\0\0\0\0".to_vec();
+    tag.extend_from_slice(&((text.len() + 1) as u32).to_be_bytes());
+    tag.extend_from_slice(text.as_bytes());
+    tag.push(0);
+    tag.extend_from_slice(&[0; 8]); // Unicode : langue et longueur
+    tag.extend_from_slice(&[0; 3]); // ScriptCode : code et longueur
+    tag.extend_from_slice(&[0; 67]);
+    tag
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn u32_at(bytes: &[u8], offset: usize) -> u32 {
+        u32::from_be_bytes(bytes[offset..offset + 4].try_into().unwrap())
+    }
+
+    fn tag<'a>(profile: &'a [u8], signature: &[u8; 4]) -> &'a [u8] {
+        let count = u32_at(profile, 128) as usize;
+        (0..count)
+            .map(|i| 132 + i * 12)
+            .find(|entry| &profile[*entry..*entry + 4] == signature)
+            .map(|entry| {
+                let offset = u32_at(profile, entry + 4) as usize;
+                &profile[offset..offset + u32_at(profile, entry + 8) as usize]
+            })
+            .unwrap()
+    }
+
+    #[test]
+    fn profile_is_well_formed() {
+        let profile = srgb_profile();
+        assert_eq!(u32_at(&profile, 0) as usize, profile.len());
+        assert_eq!(&profile[36..40], b"acsp");
+        assert_eq!((&profile[12..16], &profile[16..20]), (&b"mntr"[..], &b"RGB "[..]));
+        assert!(tag(&profile, b"desc").windows(DESCRIPTION.len()).any(|w| w == DESCRIPTION.as_bytes()));
+    }
+
+    #[test]
+    fn transfer_curve_is_srgb() {
+        let profile = srgb_profile();
+        let curve = tag(&profile, b"gTRC");
+        assert_eq!(&curve[..4], b"curv");
+        assert_eq!(u32_at(curve, 8) as usize, CURVE_POINTS);
+        let point = |i: usize| u16::from_be_bytes([curve[12 + i * 2], curve[13 + i * 2]]) as f64 / 65535.0;
+        assert_eq!(point(0), 0.0);
+        assert_eq!(point(CURVE_POINTS - 1), 1.0);
+        // 50 % sRGB : 21,4 % de luminance (0,218 avec un simple gamma 2,2)
+        let middle = point(511) + (point(512) - point(511)) * 0.5;
+        assert!((middle - 0.2140).abs() < 0.0005, "{}", middle);
+        // Segment linéaire près du noir
+        assert!((point(10) - (10.0 / 1023.0) / 12.92).abs() < 0.00002);
+    }
+}
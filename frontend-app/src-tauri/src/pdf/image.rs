@@ -0,0 +1,110 @@
+// Images raster (logo) : JPEG repris tel quel, PNG décodé en pixels bruts
+// avec la couche alpha en masque adouci.
+
+use std::path::Path;
+
+#[derive(Debug, Clone)]
+pub struct PdfImage {
+    pub width: u32,
+    pub height: u32,
+    /// DeviceRGB ou DeviceGray
+    pub color_space: &'static str,
+    pub data: Vec<u8>,
+    /// Données JPEG à passer au filtre DCTDecode sans décodage
+    pub is_jpeg: bool,
+    /// Canal alpha (8 bits par pixel) éventuel
+    pub alpha: Option<Vec<u8>>,
+}
+
+impl PdfImage {
+    pub fn from_file(path: &Path) -> Result<Self, String> {
+        let bytes = std::fs::read(path)
+            .map_err(|e| format!("Failed to read image {}: {}", path.display(), e))?;
+        Self::from_bytes(bytes)
+    }
+
+    pub fn from_bytes(bytes: Vec<u8>) -> Result<Self, String> {
+        if bytes.starts_with(&[0xFF, 0xD8]) {
+            from_jpeg(bytes)
+        } else if bytes.starts_with(b"\x89PNG") {
+            from_png(&bytes)
+        } else {
+            Err("Unsupported image format (expected JPEG or PNG)".to_string())
+        }
+    }
+}
+
+fn from_jpeg(bytes: Vec<u8>) -> Result<PdfImage, String> {
+    let mut pos = 2;
+    while pos + 4 <= bytes.len() {
+        if bytes[pos] != 0xFF {
+            pos += 1;
+            continue;
+        }
+        let marker = bytes[pos + 1];
+        let length = u16::from_be_bytes([bytes[pos + 2], bytes[pos + 3]]) as usize;
+        let is_frame = matches!(marker, 0xC0..=0xCF) && !matches!(marker, 0xC4 | 0xC8 | 0xCC);
+        if is_frame && pos + 10 <= bytes.len() {
+            let height = u16::from_be_bytes([bytes[pos + 5], bytes[pos + 6]]) as u32;
+            let width = u16::from_be_bytes([bytes[pos + 7], bytes[pos + 8]]) as u32;
+            let color_space = match bytes[pos + 9] {
+                1 => "DeviceGray",
+                3 => "DeviceRGB",
+                n => return Err(format!("Unsupported JPEG with {} color components", n)),
+            };
+            return Ok(PdfImage {
+                width,
+                height,
+                color_space,
+                data: bytes,
+                is_jpeg: true,
+                alpha: None,
+            });
+        }
+        pos += 2 + length;
+    }
+    Err("Invalid JPEG: no frame header found".to_string())
+}
+
+fn from_png(bytes: &[u8]) -> Result<PdfImage, String> {
+    let mut decoder = png::Decoder::new(bytes);
+    decoder.set_transformations(png::Transformations::normalize_to_color8());
+    let mut reader = decoder
+        .read_info()
+        .map_err(|e| format!("Invalid PNG: {}", e))?;
+    let mut buffer = vec![0; reader.output_buffer_size()];
+    let frame = reader
+        .next_frame(&mut buffer)
+        .map_err(|e| format!("Invalid PNG: {}", e))?;
+    buffer.truncate(frame.buffer_size());
+
+    let (color_space, channels, has_alpha) = match frame.color_type {
+        png::ColorType::Grayscale => ("DeviceGray", 1, false),
+        png::ColorType::GrayscaleAlpha => ("DeviceGray", 2, true),
+        png::ColorType::Rgb => ("DeviceRGB", 3, false),
+        png::ColorType::Rgba => ("DeviceRGB", 4, true),
+        png::ColorType::Indexed => return Err("Unsupported indexed PNG".to_string()),
+    };
+
+    let (data, alpha) = if has_alpha {
+        let color_channels = channels - 1;
+        let mut data = Vec::with_capacity(buffer.len() / channels * color_channels);
+        let mut alpha = Vec::with_capacity(buffer.len() / channels);
+        for pixel in buffer.chunks_exact(channels) {
+            data.extend_from_slice(&pixel[..color_channels]);
+            alpha.push(pixel[color_channels]);
+        }
+        (data, Some(alpha))
+    } else {
+        (buffer, None)
+    };
+
+    Ok(PdfImage {
+        width: frame.width,
+        height: frame.height,
+        color_space,
+        data,
+        is_jpeg: false,
+        alpha,
+    })
+}
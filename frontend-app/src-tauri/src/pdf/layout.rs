@@ -0,0 +1,340 @@
+// Mise en page des documents PDF (propositions, factures) : contenu en flux
+// avec sauts de page automatiques, en-tête avec logo et coordonnées,
+// pied de page avec mentions légales et numérotation.
+
+use super::font::FontSet;
+use super::image::PdfImage;
+use super::{Color, PdfPage, PdfWriter, PAGE_HEIGHT, PAGE_WIDTH};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+const MARGIN: f32 = 50.0;
+const CONTENT_TOP: f32 = PAGE_HEIGHT - 125.0;
+const CONTENT_BOTTOM: f32 = 95.0;
+const LOGO_HEIGHT: f32 = 45.0;
+/// Lignes de mentions légales affichées, entre le filet (y = 70) et le
+/// numéro de page (y = 25)
+const MAX_FOOTER_LINES: usize = 3;
+
+/// Charte graphique des PDF générés, lue depuis
+/// `<app_data>/templates/pdf_template.json`. Les chemins relatifs (logo,
+/// polices) sont résolus depuis le dossier du fichier.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PdfTemplate {
+    pub company_name: String,
+    pub address_lines: Vec<String>,
+    /// Logo JPEG ou PNG
+    pub logo_path: Option<String>,
+    /// Couleur principale au format #RRGGBB
+    pub primary_color: String,
+    /// Mentions légales du pied de page, trois lignes au plus
+    pub footer_lines: Vec<String>,
+    /// Polices TrueType ; à défaut, la police livrée avec l'application
+    pub font_regular: Option<String>,
+    pub font_bold: Option<String>,
+}
+
+impl Default for PdfTemplate {
+    fn default() -> Self {
+        Self {
+            company_name: "ATDI".to_string(),
+            address_lines: Vec::new(),
+            logo_path: None,
+            primary_color: "#1F4E79".to_string(),
+            footer_lines: Vec::new(),
+            font_regular: None,
+            font_bold: None,
+        }
+    }
+}
+
+impl PdfTemplate {
+    pub fn from_file(path: &Path) -> Result<Self, String> {
+        let content = std::fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read PDF template: {}", e))?;
+        let mut template: Self = serde_json::from_str(&content)
+            .map_err(|e| format!("Invalid PDF template: {}", e))?;
+
+        let base = path.parent().unwrap_or(Path::new("."));
+        let resolve = |value: &mut Option<String>| {
+            if let Some(relative) = value.as_ref().filter(|v| Path::new(v).is_relative()) {
+                *value = Some(base.join(relative).to_string_lossy().to_string());
+            }
+        };
+        resolve(&mut template.logo_path);
+        resolve(&mut template.font_regular);
+        resolve(&mut template.font_bold);
+        Ok(template)
+    }
+
+    /// Modèle de l'utilisateur s'il existe, sinon la charte par défaut.
+    pub fn load(app_data_dir: Option<PathBuf>) -> Result<Self, String> {
+        match app_data_dir
+            .map(|dir| dir.join("templates").join("pdf_template.json"))
+            .filter(|path| path.exists())
+        {
+            Some(path) => Self::from_file(&path),
+            None => Ok(Self::default()),
+        }
+    }
+
+    /// Writer avec les polices et le logo de la charte.
+    pub fn writer(&self, title: impl Into<String>) -> Result<PdfWriter, String> {
+        let mut writer = PdfWriter::new(title, self.company_name.clone());
+        writer.fonts = FontSet::load(
+            self.font_regular.as_deref().map(Path::new),
+            self.font_bold.as_deref().map(Path::new),
+        )?;
+        if let Some(logo) = &self.logo_path {
+            writer.images.push(PdfImage::from_file(Path::new(logo))?);
+        }
+        Ok(writer)
+    }
+
+    pub fn color(&self) -> Color {
+        Color::from_hex(&self.primary_color).unwrap_or(Color(0.12, 0.31, 0.47))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Align {
+    Left,
+    Right,
+}
+
+pub struct Column<'a> {
+    pub title: &'a str,
+    pub width: f32,
+    pub align: Align,
+}
+
+/// Contenu en flux d'un document : chaque bloc est placé sous le précédent
+/// et passe à la page suivante quand la place manque.
+pub struct DocumentLayout<'a> {
+    template: &'a PdfTemplate,
+    writer: PdfWriter,
+    pages: Vec<PdfPage>,
+    y: f32,
+}
+
+impl<'a> DocumentLayout<'a> {
+    pub fn new(template: &'a PdfTemplate, writer: PdfWriter) -> Self {
+        Self {
+            template,
+            writer,
+            pages: vec![PdfPage::default()],
+            y: CONTENT_TOP,
+        }
+    }
+
+    fn page(&mut self) -> &mut PdfPage {
+        self.pages.last_mut().expect("at least one page")
+    }
+
+    fn ensure_space(&mut self, height: f32) {
+        if self.y - height < CONTENT_BOTTOM {
+            self.pages.push(PdfPage::default());
+            self.y = CONTENT_TOP;
+        }
+    }
+
+    pub fn spacer(&mut self, height: f32) {
+        self.y -= height;
+    }
+
+    pub fn title(&mut self, text: &str) {
+        self.ensure_space(30.0);
+        self.y -= 20.0;
+        let color = self.template.color();
+        let y = self.y;
+        self.page().colored_text(MARGIN, y, 20.0, true, color, text);
+        self.y -= 14.0;
+    }
+
+    pub fn heading(&mut self, text: &str) {
+        self.ensure_space(40.0);
+        self.y -= 18.0;
+        let color = self.template.color();
+        let y = self.y;
+        self.page().colored_text(MARGIN, y, 12.0, true, color, text);
+        self.page().line((MARGIN, y - 4.0), (PAGE_WIDTH - MARGIN, y - 4.0), 0.5, color);
+        self.y -= 12.0;
+    }
+
+    pub fn paragraph(&mut self, text: &str, size: f32) {
+        let width = PAGE_WIDTH - 2.0 * MARGIN;
+        for line in self.wrap(text, size, false, width) {
+            self.ensure_space(size * 1.4);
+            self.y -= size * 1.4;
+            let y = self.y;
+            self.page().text(MARGIN, y, size, false, line);
+        }
+    }
+
+    /// Libellés en gras suivis de leur valeur, une paire par ligne.
+    pub fn key_values(&mut self, pairs: &[(&str, String)]) {
+        let label_width = pairs
+            .iter()
+            .map(|(label, _)| self.writer.fonts.text_width(label, 10.0, true))
+            .fold(0.0, f32::max)
+            + 12.0;
+        for (label, value) in pairs {
+            let lines = self.wrap(value, 10.0, false, PAGE_WIDTH - 2.0 * MARGIN - label_width);
+            self.ensure_space(14.0 * lines.len() as f32);
+            self.y -= 14.0;
+            let y = self.y;
+            self.page().text(MARGIN, y, 10.0, true, *label);
+            for (index, line) in lines.into_iter().enumerate() {
+                if index > 0 {
+                    self.y -= 14.0;
+                }
+                let y = self.y;
+                self.page().text(MARGIN + label_width, y, 10.0, false, line);
+            }
+        }
+    }
+
+    /// Tableau avec ligne d'en-tête colorée, répétée après chaque saut de page.
+    pub fn table(&mut self, columns: &[Column], rows: &[Vec<String>]) {
+        self.table_header(columns);
+        for (row_index, row) in rows.iter().enumerate() {
+            let cells: Vec<Vec<String>> = columns
+                .iter()
+                .zip(row)
+                .map(|(column, value)| self.wrap(value, 9.5, false, column.width - 8.0))
+                .collect();
+            let line_count = cells.iter().map(|c| c.len()).max().unwrap_or(1).max(1);
+            let height = line_count as f32 * 13.0 + 6.0;
+
+            if self.y - height < CONTENT_BOTTOM {
+                self.pages.push(PdfPage::default());
+                self.y = CONTENT_TOP;
+                self.table_header(columns);
+            }
+
+            let top = self.y;
+            if row_index % 2 == 1 {
+                self.page().rect(MARGIN, top - height, table_width(columns), height, Color(0.95, 0.95, 0.95));
+            }
+            let mut x = MARGIN;
+            for (column, lines) in columns.iter().zip(&cells) {
+                for (index, line) in lines.iter().enumerate() {
+                    let y = top - 13.0 * (index as f32 + 1.0);
+                    let text_x = match column.align {
+                        Align::Left => x + 4.0,
+                        Align::Right => x + column.width - 4.0 - self.writer.fonts.text_width(line, 9.5, false),
+                    };
+                    self.page().text(text_x, y, 9.5, false, line.clone());
+                }
+                x += column.width;
+            }
+            self.y = top - height;
+        }
+        self.y -= 6.0;
+    }
+
+    fn table_header(&mut self, columns: &[Column]) {
+        self.ensure_space(40.0);
+        let color = self.template.color();
+        let top = self.y - 6.0;
+        self.page().rect(MARGIN, top - 18.0, table_width(columns), 18.0, color);
+        let mut x = MARGIN;
+        for column in columns {
+            let text_x = match column.align {
+                Align::Left => x + 4.0,
+                Align::Right => x + column.width - 4.0 - self.writer.fonts.text_width(column.title, 9.5, true),
+            };
+            self.page().colored_text(text_x, top - 13.0, 9.5, true, Color::WHITE, column.title);
+            x += column.width;
+        }
+        self.y = top - 18.0;
+    }
+
+    /// Lignes de totaux alignées à droite.
+    pub fn totals(&mut self, lines: &[(&str, String, bool)]) {
+        let right = PAGE_WIDTH - MARGIN;
+        for (label, value, bold) in lines {
+            self.ensure_space(16.0);
+            self.y -= 16.0;
+            let y = self.y;
+            let value_width = self.writer.fonts.text_width(value, 10.0, *bold);
+            let label_width = self.writer.fonts.text_width(label, 10.0, *bold);
+            self.page().text(right - value_width, y, 10.0, *bold, value.clone());
+            self.page().text(right - 130.0 - label_width, y, 10.0, *bold, *label);
+        }
+    }
+
+    /// Ajoute en-tête et pied de page sur chaque page et rend le writer
+    /// complété ; le logo est la première image du writer.
+    pub fn finish(mut self) -> PdfWriter {
+        let logo = self.writer.images.first().map(|image| (0, image.width, image.height));
+        let total = self.pages.len();
+        let color = self.template.color();
+        let template = self.template;
+        let fonts = &self.writer.fonts;
+
+        for (index, page) in self.pages.iter_mut().enumerate() {
+            // En-tête : logo à gauche, coordonnées à droite
+            let header_top = PAGE_HEIGHT - 40.0;
+            if let Some((image, width, height)) = logo {
+                let logo_width = LOGO_HEIGHT * width as f32 / height.max(1) as f32;
+                page.image(image, MARGIN, header_top - LOGO_HEIGHT, logo_width.min(200.0), LOGO_HEIGHT);
+            }
+            let right = PAGE_WIDTH - MARGIN;
+            let mut y = header_top - 10.0;
+            let name_width = fonts.text_width(&template.company_name, 11.0, true);
+            page.colored_text(right - name_width, y, 11.0, true, color, template.company_name.clone());
+            for line in &template.address_lines {
+                y -= 11.0;
+                let width = fonts.text_width(line, 8.5, false);
+                page.colored_text(right - width, y, 8.5, false, Color::GRAY, line.clone());
+            }
+            let separator = header_top - LOGO_HEIGHT - 12.0;
+            page.line((MARGIN, separator), (right, separator), 0.8, color);
+
+            // Pied de page : mentions légales centrées et numéro de page
+            page.line((MARGIN, 70.0), (right, 70.0), 0.5, Color::GRAY);
+            let mut y = 58.0;
+            for line in template.footer_lines.iter().take(MAX_FOOTER_LINES) {
+                let width = fonts.text_width(line, 7.5, false);
+                page.colored_text((PAGE_WIDTH - width) / 2.0, y, 7.5, false, Color::GRAY, line.clone());
+                y -= 10.0;
+            }
+            let number = format!("Page {} / {}", index + 1, total);
+            let width = fonts.text_width(&number, 8.0, false);
+            page.colored_text(right - width, 25.0, 8.0, false, Color::GRAY, number);
+        }
+
+        self.writer.pages.append(&mut self.pages);
+        self.writer
+    }
+
+    /// Découpe un texte en lignes tenant dans `width` (retours à la ligne
+    /// explicites conservés).
+    fn wrap(&self, text: &str, size: f32, bold: bool, width: f32) -> Vec<String> {
+        let mut lines = Vec::new();
+        for paragraph in text.split('\n') {
+            let mut current = String::new();
+            for word in paragraph.split_whitespace() {
+                let candidate = if current.is_empty() {
+                    word.to_string()
+                } else {
+                    format!("{} {}", current, word)
+                };
+                if !current.is_empty() && self.writer.fonts.text_width(&candidate, size, bold) > width {
+                    lines.push(std::mem::take(&mut current));
+                    current = word.to_string();
+                } else {
+                    current = candidate;
+                }
+            }
+            lines.push(current);
+        }
+        lines
+    }
+}
+
+fn table_width(columns: &[Column]) -> f32 {
+    columns.iter().map(|c| c.width).sum()
+}
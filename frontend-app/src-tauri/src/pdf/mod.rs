@@ -0,0 +1,523 @@
+// Écriture PDF (lopdf) : pages de texte et de formes, images, polices
+// TrueType embarquées, pièces jointes PDF/A-3 et métadonnées XMP.
+// Utilisée par l'export Factur-X et les exports PDF des documents.
+
+pub mod font;
+mod icc;
+pub mod image;
+pub mod layout;
+
+use font::{encode_win_ansi, FontSet, TrueTypeFont, FIRST_CHAR, LAST_CHAR};
+use image::PdfImage;
+use lopdf::{dictionary, Dictionary, Document, Object, ObjectId, Stream, StringFormat};
+use lopdf::content::{Content, Operation};
+
+/// Format A4 en points PDF.
+pub const PAGE_WIDTH: f32 = 595.0;
+pub const PAGE_HEIGHT: f32 = 842.0;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Color(pub f32, pub f32, pub f32);
+
+impl Color {
+    pub const BLACK: Color = Color(0.0, 0.0, 0.0);
+    pub const WHITE: Color = Color(1.0, 1.0, 1.0);
+    pub const GRAY: Color = Color(0.45, 0.45, 0.45);
+
+    /// Couleur au format `#RRGGBB`.
+    pub fn from_hex(value: &str) -> Option<Color> {
+        let hex = value.trim().trim_start_matches('#');
+        if hex.len() != 6 {
+            return None;
+        }
+        let channel = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16).ok().map(|v| v as f32 / 255.0);
+        Some(Color(channel(0)?, channel(2)?, channel(4)?))
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum PageItem {
+    Text { x: f32, y: f32, size: f32, bold: bool, color: Color, text: String },
+    Rect { x: f32, y: f32, width: f32, height: f32, color: Color },
+    Line { from: (f32, f32), to: (f32, f32), width: f32, color: Color },
+    /// Image enregistrée dans `PdfWriter::images` à l'index donné
+    Image { index: usize, x: f32, y: f32, width: f32, height: f32 },
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct PdfPage {
+    pub items: Vec<PageItem>,
+}
+
+impl PdfPage {
+    pub fn text(&mut self, x: f32, y: f32, size: f32, bold: bool, text: impl Into<String>) {
+        self.colored_text(x, y, size, bold, Color::BLACK, text);
+    }
+
+    pub fn colored_text(&mut self, x: f32, y: f32, size: f32, bold: bool, color: Color, text: impl Into<String>) {
+        self.items.push(PageItem::Text { x, y, size, bold, color, text: text.into() });
+    }
+
+    pub fn rect(&mut self, x: f32, y: f32, width: f32, height: f32, color: Color) {
+        self.items.push(PageItem::Rect { x, y, width, height, color });
+    }
+
+    pub fn line(&mut self, from: (f32, f32), to: (f32, f32), width: f32, color: Color) {
+        self.items.push(PageItem::Line { from, to, width, color });
+    }
+
+    pub fn image(&mut self, index: usize, x: f32, y: f32, width: f32, height: f32) {
+        self.items.push(PageItem::Image { index, x, y, width, height });
+    }
+}
+
+/// Fichier associé (PDF/A-3, clause 6.8) embarqué dans le document.
+#[derive(Debug, Clone)]
+pub struct Attachment {
+    pub file_name: String,
+    pub mime_type: String,
+    pub description: String,
+    /// Valeur de /AFRelationship : Data, Source, Alternative...
+    pub relationship: String,
+    pub data: Vec<u8>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct PdfWriter {
+    pub title: String,
+    pub author: String,
+    pub pages: Vec<PdfPage>,
+    pub attachments: Vec<Attachment>,
+    pub images: Vec<PdfImage>,
+    pub fonts: FontSet,
+    /// Blocs rdf:Description supplémentaires insérés dans le paquet XMP
+    pub extra_xmp: Option<String>,
+}
+
+impl PdfWriter {
+    pub fn new(title: impl Into<String>, author: impl Into<String>) -> Self {
+        Self {
+            title: title.into(),
+            author: author.into(),
+            ..Default::default()
+        }
+    }
+
+    /// Le document est déclaré PDF/A-3B seulement si ses polices sont
+    /// embarquées ; sinon il reste un PDF standard.
+    pub fn is_pdfa(&self) -> bool {
+        self.fonts.is_embedded()
+    }
+
+    pub fn render(&self) -> Result<Vec<u8>, String> {
+        let mut doc = Document::with_version("1.7");
+        let pages_id = doc.new_object_id();
+
+        let (regular_id, bold_id) = match &self.fonts.regular {
+            Some(regular) => {
+                let regular_id = add_truetype_font(&mut doc, regular);
+                let bold_id = match &self.fonts.bold {
+                    Some(bold) => add_truetype_font(&mut doc, bold),
+                    None => regular_id,
+                };
+                (regular_id, bold_id)
+            }
+            None => (
+                add_standard_font(&mut doc, "Helvetica"),
+                add_standard_font(&mut doc, "Helvetica-Bold"),
+            ),
+        };
+
+        let mut xobjects = Dictionary::new();
+        for (index, image) in self.images.iter().enumerate() {
+            let image_id = add_image(&mut doc, image);
+            xobjects.set(format!("Im{}", index), image_id);
+        }
+
+        let resources_id = doc.add_object(dictionary! {
+            "Font" => dictionary! {
+                "F1" => regular_id,
+                "F2" => bold_id,
+            },
+            "XObject" => xobjects,
+        });
+
+        let mut page_ids: Vec<Object> = Vec::new();
+        let pages: Vec<PdfPage> = if self.pages.is_empty() {
+            vec![PdfPage::default()]
+        } else {
+            self.pages.clone()
+        };
+
+        for page in &pages {
+            let operations: Vec<Operation> = page.items.iter().flat_map(item_operations).collect();
+            let content = Content { operations }
+                .encode()
+                .map_err(|e| format!("Failed to encode page content: {}", e))?;
+            let mut content_stream = Stream::new(dictionary! {}, content);
+            let _ = content_stream.compress();
+            let content_id = doc.add_object(content_stream);
+            let page_id = doc.add_object(dictionary! {
+                "Type" => "Page",
+                "Parent" => pages_id,
+                "Contents" => content_id,
+                "Resources" => resources_id,
+                "MediaBox" => vec![0.into(), 0.into(), PAGE_WIDTH.into(), PAGE_HEIGHT.into()],
+            });
+            page_ids.push(page_id.into());
+        }
+
+        let page_count = page_ids.len() as i64;
+        doc.objects.insert(
+            pages_id,
+            Object::Dictionary(dictionary! {
+                "Type" => "Pages",
+                "Kids" => page_ids,
+                "Count" => page_count,
+            }),
+        );
+
+        // Même instant, à la seconde, dans le dictionnaire Info et le XMP :
+        // PDF/A exige que les deux concordent
+        let now = chrono::Utc::now();
+        let pdf_date = now.format("D:%Y%m%d%H%M%S+00'00'").to_string();
+        let xmp_date = now.format("%Y-%m-%dT%H:%M:%S+00:00").to_string();
+
+        let mut file_specs: Vec<(String, ObjectId)> = Vec::new();
+        for attachment in &self.attachments {
+            let mut stream = Stream::new(
+                dictionary! {
+                    "Type" => "EmbeddedFile",
+                    "Subtype" => Object::Name(attachment.mime_type.clone().into_bytes()),
+                    "Params" => dictionary! {
+                        "Size" => attachment.data.len() as i64,
+                        "ModDate" => Object::string_literal(pdf_date.clone()),
+                    },
+                },
+                attachment.data.clone(),
+            );
+            let _ = stream.compress();
+            let file_id = doc.add_object(stream);
+            let spec_id = doc.add_object(dictionary! {
+                "Type" => "Filespec",
+                "F" => text_string(&attachment.file_name),
+                "UF" => text_string(&attachment.file_name),
+                "Desc" => text_string(&attachment.description),
+                "AFRelationship" => Object::Name(attachment.relationship.clone().into_bytes()),
+                "EF" => dictionary! {
+                    "F" => file_id,
+                    "UF" => file_id,
+                },
+            });
+            file_specs.push((attachment.file_name.clone(), spec_id));
+        }
+
+        let xmp = self.xmp_packet(&xmp_date);
+        let metadata_id = doc.add_object(Stream::new(
+            dictionary! {
+                "Type" => "Metadata",
+                "Subtype" => "XML",
+            },
+            xmp.into_bytes(),
+        ));
+
+        let mut catalog = dictionary! {
+            "Type" => "Catalog",
+            "Pages" => pages_id,
+            "Metadata" => metadata_id,
+        };
+        if self.is_pdfa() {
+            let profile = icc::srgb_profile();
+            let profile_id = doc.add_object(Stream::new(dictionary! { "N" => 3 }, profile));
+            catalog.set(
+                "OutputIntents",
+                vec![Object::Dictionary(dictionary! {
+                    "Type" => "OutputIntent",
+                    "S" => "GTS_PDFA1",
+                    "OutputConditionIdentifier" => Object::string_literal(icc::DESCRIPTION),
+                    "Info" => Object::string_literal(icc::DESCRIPTION),
+                    "DestOutputProfile" => profile_id,
+                })],
+            );
+        }
+        if !file_specs.is_empty() {
+            let mut names: Vec<Object> = Vec::new();
+            for (name, id) in &file_specs {
+                names.push(text_string(name));
+                names.push((*id).into());
+            }
+            catalog.set(
+                "Names",
+                dictionary! {
+                    "EmbeddedFiles" => dictionary! { "Names" => names },
+                },
+            );
+            catalog.set(
+                "AF",
+                file_specs.iter().map(|(_, id)| Object::from(*id)).collect::<Vec<Object>>(),
+            );
+        }
+        let catalog_id = doc.add_object(catalog);
+
+        let info_id = doc.add_object(dictionary! {
+            "Title" => text_string(&self.title),
+            "Author" => text_string(&self.author),
+            "Producer" => Object::string_literal("Taury CRM"),
+            "CreationDate" => Object::string_literal(pdf_date.clone()),
+            "ModDate" => Object::string_literal(pdf_date),
+        });
+
+        doc.trailer.set("Root", catalog_id);
+        doc.trailer.set("Info", info_id);
+        let file_id = Object::String(
+            uuid::Uuid::new_v4().as_bytes().to_vec(),
+            StringFormat::Hexadecimal,
+        );
+        doc.trailer.set("ID", vec![file_id.clone(), file_id]);
+
+        let mut bytes = Vec::new();
+        doc.save_to(&mut bytes)
+            .map_err(|e| format!("Failed to write PDF: {}", e))?;
+        Ok(bytes)
+    }
+
+    fn xmp_packet(&self, created: &str) -> String {
+        let pdfa = if self.is_pdfa() {
+            "<rdf:Description rdf:about=\"\" xmlns:pdfaid=\"http://www.aiim.org/pdfa/ns/id/\">\n\
+<pdfaid:part>3</pdfaid:part>\n\
+<pdfaid:conformance>B</pdfaid:conformance>\n\
+</rdf:Description>\n"
+        } else {
+            ""
+        };
+        format!(
+            "<?xpacket begin=\"\u{feff}\" id=\"W5M0MpCehiHzreSzNTczkc9d\"?>\n\
+<x:xmpmeta xmlns:x=\"adobe:ns:meta/\">\n\
+<rdf:RDF xmlns:rdf=\"http://www.w3.org/1999/02/22-rdf-syntax-ns#\">\n\
+{pdfa}\
+<rdf:Description rdf:about=\"\" xmlns:dc=\"http://purl.org/dc/elements/1.1/\">\n\
+<dc:title><rdf:Alt><rdf:li xml:lang=\"x-default\">{title}</rdf:li></rdf:Alt></dc:title>\n\
+<dc:creator><rdf:Seq><rdf:li>{author}</rdf:li></rdf:Seq></dc:creator>\n\
+</rdf:Description>\n\
+<rdf:Description rdf:about=\"\" xmlns:xmp=\"http://ns.adobe.com/xap/1.0/\" xmlns:pdf=\"http://ns.adobe.com/pdf/1.3/\">\n\
+<xmp:CreateDate>{created}</xmp:CreateDate>\n\
+<xmp:ModifyDate>{created}</xmp:ModifyDate>\n\
+<pdf:Producer>Taury CRM</pdf:Producer>\n\
+</rdf:Description>\n\
+{extra}\
+</rdf:RDF>\n\
+</x:xmpmeta>\n\
+<?xpacket end=\"w\"?>",
+            pdfa = pdfa,
+            title = crate::einvoice::escape_xml(&self.title),
+            author = crate::einvoice::escape_xml(&self.author),
+            created = created,
+            extra = self.extra_xmp.as_deref().unwrap_or(""),
+        )
+    }
+}
+
+fn item_operations(item: &PageItem) -> Vec<Operation> {
+    let rgb = |color: &Color| vec![color.0.into(), color.1.into(), color.2.into()];
+    match item {
+        PageItem::Text { x, y, size, bold, color, text } => vec![
+            Operation::new("rg", rgb(color)),
+            Operation::new("BT", vec![]),
+            Operation::new("Tf", vec![if *bold { "F2" } else { "F1" }.into(), (*size).into()]),
+            Operation::new("Td", vec![(*x).into(), (*y).into()]),
+            Operation::new("Tj", vec![Object::String(encode_win_ansi(text), StringFormat::Literal)]),
+            Operation::new("ET", vec![]),
+        ],
+        PageItem::Rect { x, y, width, height, color } => vec![
+            Operation::new("rg", rgb(color)),
+            Operation::new("re", vec![(*x).into(), (*y).into(), (*width).into(), (*height).into()]),
+            Operation::new("f", vec![]),
+        ],
+        PageItem::Line { from, to, width, color } => vec![
+            Operation::new("RG", rgb(color)),
+            Operation::new("w", vec![(*width).into()]),
+            Operation::new("m", vec![from.0.into(), from.1.into()]),
+            Operation::new("l", vec![to.0.into(), to.1.into()]),
+            Operation::new("S", vec![]),
+        ],
+        PageItem::Image { index, x, y, width, height } => vec![
+            Operation::new("q", vec![]),
+            Operation::new(
+                "cm",
+                vec![(*width).into(), 0.into(), 0.into(), (*height).into(), (*x).into(), (*y).into()],
+            ),
+            Operation::new("Do", vec![Object::Name(format!("Im{}", index).into_bytes())]),
+            Operation::new("Q", vec![]),
+        ],
+    }
+}
+
+fn add_standard_font(doc: &mut Document, name: &str) -> ObjectId {
+    doc.add_object(dictionary! {
+        "Type" => "Font",
+        "Subtype" => "Type1",
+        "BaseFont" => Object::Name(name.as_bytes().to_vec()),
+        "Encoding" => "WinAnsiEncoding",
+    })
+}
+
+fn add_truetype_font(doc: &mut Document, font: &TrueTypeFont) -> ObjectId {
+    let mut file = Stream::new(
+        dictionary! { "Length1" => font.data.len() as i64 },
+        font.data.clone(),
+    );
+    let _ = file.compress();
+    let file_id = doc.add_object(file);
+
+    let descriptor_id = doc.add_object(dictionary! {
+        "Type" => "FontDescriptor",
+        "FontName" => Object::Name(font.name.clone().into_bytes()),
+        // Police non symbolique (bit 6)
+        "Flags" => 32,
+        "FontBBox" => font.bbox.iter().map(|v| Object::Integer(*v)).collect::<Vec<Object>>(),
+        "ItalicAngle" => font.italic_angle,
+        "Ascent" => font.ascent,
+        "Descent" => font.descent,
+        "CapHeight" => font.cap_height,
+        "StemV" => 80,
+        "FontFile2" => file_id,
+    });
+
+    doc.add_object(dictionary! {
+        "Type" => "Font",
+        "Subtype" => "TrueType",
+        "BaseFont" => Object::Name(font.name.clone().into_bytes()),
+        "FirstChar" => FIRST_CHAR as i64,
+        "LastChar" => LAST_CHAR as i64,
+        "Widths" => font.widths.iter().map(|w| Object::Integer(*w)).collect::<Vec<Object>>(),
+        "Encoding" => "WinAnsiEncoding",
+        "FontDescriptor" => descriptor_id,
+    })
+}
+
+fn add_image(doc: &mut Document, image: &PdfImage) -> ObjectId {
+    let mut dict = dictionary! {
+        "Type" => "XObject",
+        "Subtype" => "Image",
+        "Width" => image.width as i64,
+        "Height" => image.height as i64,
+        "ColorSpace" => Object::Name(image.color_space.as_bytes().to_vec()),
+        "BitsPerComponent" => 8,
+    };
+
+    if let Some(alpha) = &image.alpha {
+        let mut mask = Stream::new(
+            dictionary! {
+                "Type" => "XObject",
+                "Subtype" => "Image",
+                "Width" => image.width as i64,
+                "Height" => image.height as i64,
+                "ColorSpace" => "DeviceGray",
+                "BitsPerComponent" => 8,
+            },
+            alpha.clone(),
+        );
+        let _ = mask.compress();
+        dict.set("SMask", doc.add_object(mask));
+    }
+
+    if image.is_jpeg {
+        dict.set("Filter", "DCTDecode");
+        doc.add_object(Stream::new(dict, image.data.clone()).with_compression(false))
+    } else {
+        let mut stream = Stream::new(dict, image.data.clone());
+        let _ = stream.compress();
+        doc.add_object(stream)
+    }
+}
+
+/// Chaîne de texte PDF : littérale si ASCII, sinon UTF-16BE avec BOM.
+fn text_string(value: &str) -> Object {
+    if value.is_ascii() {
+        Object::string_literal(value)
+    } else {
+        let mut bytes = vec![0xFE, 0xFF];
+        for unit in value.encode_utf16() {
+            bytes.extend_from_slice(&unit.to_be_bytes());
+        }
+        Object::String(bytes, StringFormat::Hexadecimal)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rendered(fonts: FontSet) -> Document {
+        let mut writer = PdfWriter::new("Invoice 25070001", "Taury SAS");
+        writer.fonts = fonts;
+        let mut page = PdfPage::default();
+        page.text(50.0, 700.0, 12.0, false, "Total : 1 234,00 €");
+        page.text(50.0, 680.0, 12.0, true, "Œuvre – “quoted”");
+        writer.pages.push(page);
+        Document::load_mem(&writer.render().unwrap()).unwrap()
+    }
+
+    fn metadata(doc: &Document) -> String {
+        let id = doc.catalog().unwrap().get(b"Metadata").unwrap().as_reference().unwrap();
+        let stream = doc.get_object(id).unwrap().as_stream().unwrap();
+        String::from_utf8(stream.content.clone()).unwrap()
+    }
+
+    fn info_date(doc: &Document, key: &[u8]) -> String {
+        let id = doc.trailer.get(b"Info").unwrap().as_reference().unwrap();
+        let info = doc.get_object(id).unwrap().as_dict().unwrap();
+        String::from_utf8(info.get(key).unwrap().as_str().unwrap().to_vec()).unwrap()
+    }
+
+    fn xmp_value<'a>(xmp: &'a str, tag: &str) -> &'a str {
+        let start = xmp.find(&format!("<{}>", tag)).unwrap() + tag.len() + 2;
+        let end = xmp[start..].find('<').unwrap() + start;
+        &xmp[start..end]
+    }
+
+    #[test]
+    fn embedded_fonts_make_a_pdfa_document() {
+        let doc = rendered(FontSet::bundled());
+        let catalog = doc.catalog().unwrap();
+        let intents = catalog.get(b"OutputIntents").unwrap().as_array().unwrap();
+        assert_eq!(intents.len(), 1);
+
+        let fonts: Vec<&Dictionary> = doc
+            .objects
+            .values()
+            .filter_map(|o| o.as_dict().ok())
+            .filter(|d| d.get(b"Type").and_then(Object::as_name).ok() == Some(b"Font".as_slice()))
+            .collect();
+        assert_eq!(fonts.len(), 2);
+        for font in fonts {
+            assert_eq!(font.get(b"Subtype").unwrap().as_name().unwrap(), b"TrueType");
+            let descriptor = doc.get_object(font.get(b"FontDescriptor").unwrap().as_reference().unwrap()).unwrap();
+            assert!(descriptor.as_dict().unwrap().get(b"FontFile2").is_ok());
+        }
+
+        let xmp = metadata(&doc);
+        assert_eq!(xmp_value(&xmp, "pdfaid:part"), "3");
+        assert_eq!(xmp_value(&xmp, "pdfaid:conformance"), "B");
+    }
+
+    #[test]
+    fn xmp_dates_match_info_dates() {
+        let doc = rendered(FontSet::bundled());
+        let xmp = metadata(&doc);
+        let created = chrono::DateTime::parse_from_rfc3339(xmp_value(&xmp, "xmp:CreateDate")).unwrap();
+        assert_eq!(
+            created.format("D:%Y%m%d%H%M%S+00'00'").to_string(),
+            info_date(&doc, b"CreationDate")
+        );
+        assert_eq!(xmp_value(&xmp, "xmp:CreateDate"), xmp_value(&xmp, "xmp:ModifyDate"));
+        assert!(!xmp_value(&xmp, "xmp:CreateDate").contains('.'));
+    }
+
+    #[test]
+    fn standard_fonts_are_not_declared_pdfa() {
+        let doc = rendered(FontSet::default());
+        assert!(doc.catalog().unwrap().get(b"OutputIntents").is_err());
+        assert!(!metadata(&doc).contains("pdfaid"));
+    }
+}
@@ -0,0 +1,431 @@
+// Réglages de l'application
+//
+// - Profils de serveur (`<app_data>/server_profiles.json`) : chaque profil a
+//   sa propre base, pour que les données de production et de test ne se
+//   mêlent jamais. Le fichier est lu avant d'ouvrir la base, il ne peut
+//   donc pas y être stocké.
+// - Réglages du poste (`LocalSettings`) : table `settings` de la base du
+//   profil actif, jamais synchronisée.
+// - Réglages de l'organisation (`OrgSettings`) : table `org_settings`,
+//   synchronisée, une ligne par réglage.
+
+use crate::db::{queries, DbPool};
+use crate::numbering;
+use crate::output::OutputSettings;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::Path;
+
+const PROFILES_FILE: &str = "server_profiles.json";
+
+/// Profil créé au premier lancement ; sa base reste `crm.db`, celle des
+/// versions sans profils.
+pub const DEFAULT_PROFILE: &str = "default";
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ServerProfile {
+    /// `prod`, `test`... : lettres minuscules, chiffres, `-` ou `_`
+    pub name: String,
+    /// Serveur de synchronisation ; `None` pour travailler sans serveur
+    pub server_url: Option<String>,
+    /// Référence des identifiants du serveur (entrée du trousseau système,
+    /// variable d'environnement...) ; le secret n'est jamais enregistré ici
+    pub credentials_ref: Option<String>,
+}
+
+impl ServerProfile {
+    /// Fichier de la base du profil, dans le dossier de l'application
+    pub fn database_file(&self) -> String {
+        if self.name == DEFAULT_PROFILE {
+            "crm.db".to_string()
+        } else {
+            format!("crm-{}.db", self.name)
+        }
+    }
+
+    /// Profil vérifié, adresse sans `/` final et champs vides à `None`
+    pub fn normalized(self) -> Result<Self, String> {
+        let name = self.name.trim().to_string();
+        if name.is_empty()
+            || name.len() > 32
+            || !name.chars().all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-' || c == '_')
+        {
+            return Err(format!(
+                "Invalid profile name \"{}\" (lowercase letters, digits, - and _, at most 32 characters)",
+                name
+            ));
+        }
+        let server_url = self
+            .server_url
+            .map(|url| url.trim().trim_end_matches('/').to_string())
+            .filter(|url| !url.is_empty());
+        if let Some(url) = &server_url {
+            if !url.starts_with("http://") && !url.starts_with("https://") {
+                return Err(format!("Invalid server URL: {}", url));
+            }
+        }
+        let credentials_ref = self
+            .credentials_ref
+            .map(|r| r.trim().to_string())
+            .filter(|r| !r.is_empty());
+        Ok(Self { name, server_url, credentials_ref })
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ServerProfiles {
+    /// Nom du profil dont la base est ouverte
+    pub active: String,
+    pub profiles: Vec<ServerProfile>,
+}
+
+impl Default for ServerProfiles {
+    fn default() -> Self {
+        Self {
+            active: DEFAULT_PROFILE.to_string(),
+            profiles: vec![ServerProfile {
+                name: DEFAULT_PROFILE.to_string(),
+                server_url: None,
+                credentials_ref: None,
+            }],
+        }
+    }
+}
+
+impl ServerProfiles {
+    /// Profils enregistrés, sinon le seul profil par défaut.
+    pub fn load(app_data_dir: &Path) -> Result<Self, String> {
+        let path = app_data_dir.join(PROFILES_FILE);
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let content = std::fs::read_to_string(&path)
+            .map_err(|e| format!("Failed to read server profiles: {}", e))?;
+        let profiles: Self = serde_json::from_str(&content)
+            .map_err(|e| format!("Invalid server profiles: {}", e))?;
+        if profiles.get(&profiles.active).is_none() {
+            return Err(format!("Active server profile \"{}\" does not exist", profiles.active));
+        }
+        Ok(profiles)
+    }
+
+    /// Comme `load`, mais un fichier illisible ne bloque pas le démarrage :
+    /// il est mis de côté (`server_profiles.json.invalid`) pour être
+    /// récupéré à la main, et le profil par défaut le remplace. L'erreur est
+    /// renvoyée pour être signalée.
+    pub fn load_or_default(app_data_dir: &Path) -> (Self, Option<String>) {
+        match Self::load(app_data_dir) {
+            Ok(profiles) => (profiles, None),
+            Err(e) => {
+                let kept = app_data_dir.join(format!("{}.invalid", PROFILES_FILE));
+                let error = match std::fs::rename(app_data_dir.join(PROFILES_FILE), &kept) {
+                    Ok(()) => format!("{} (file kept as {})", e, kept.display()),
+                    Err(rename) => format!("{} (file could not be set aside: {})", e, rename),
+                };
+                (Self::default(), Some(error))
+            }
+        }
+    }
+
+    /// Écrit dans un fichier temporaire puis le renomme : une interruption
+    /// ne laisse jamais un fichier tronqué, qui empêcherait d'ouvrir la base
+    pub fn save(&self, app_data_dir: &Path) -> Result<(), String> {
+        let content = serde_json::to_string_pretty(self)
+            .map_err(|e| format!("Failed to serialize server profiles: {}", e))?;
+        std::fs::create_dir_all(app_data_dir)
+            .map_err(|e| format!("Failed to create app data directory: {}", e))?;
+        let temp = app_data_dir.join(format!("{}.tmp", PROFILES_FILE));
+        std::fs::write(&temp, content)
+            .map_err(|e| format!("Failed to save server profiles: {}", e))?;
+        std::fs::rename(&temp, app_data_dir.join(PROFILES_FILE))
+            .map_err(|e| format!("Failed to save server profiles: {}", e))
+    }
+
+    pub fn get(&self, name: &str) -> Option<&ServerProfile> {
+        self.profiles.iter().find(|p| p.name == name)
+    }
+
+    pub fn active(&self) -> &ServerProfile {
+        self.get(&self.active).expect("active profile checked on load")
+    }
+
+    /// Ajoute le profil ou remplace celui du même nom
+    pub fn upsert(&mut self, profile: ServerProfile) {
+        match self.profiles.iter_mut().find(|p| p.name == profile.name) {
+            Some(existing) => *existing = profile,
+            None => self.profiles.push(profile),
+        }
+    }
+}
+
+/// Intervalle par défaut entre deux synchronisations périodiques
+pub const DEFAULT_SYNC_INTERVAL_SECONDS: u64 = 300;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SyncPreferences {
+    /// Synchronisation automatique en tâche de fond
+    pub enabled: bool,
+    pub interval_seconds: u64,
+}
+
+impl Default for SyncPreferences {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            interval_seconds: DEFAULT_SYNC_INTERVAL_SECONDS,
+        }
+    }
+}
+
+const OUTPUT_SECTION: &str = "output";
+const SYNC_SECTION: &str = "sync";
+
+/// Réglages de ce poste pour le profil actif
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LocalSettings {
+    pub output: OutputSettings,
+    pub sync: SyncPreferences,
+}
+
+impl LocalSettings {
+    /// Réglages enregistrés, complétés par les valeurs par défaut. Le
+    /// fichier `output_settings.json` des versions précédentes est repris
+    /// à la première lecture.
+    pub async fn load(pool: &DbPool, app_data_dir: Option<&Path>) -> Result<Self, String> {
+        let sections = queries::get_local_settings(pool)
+            .await
+            .map_err(|e| format!("Failed to load settings: {}", e))?;
+
+        let output = match sections.get(OUTPUT_SECTION) {
+            Some(_) => section(&sections, OUTPUT_SECTION),
+            None => match OutputSettings::load_legacy(app_data_dir)? {
+                Some(legacy) => {
+                    store_section(pool, OUTPUT_SECTION, &legacy).await?;
+                    println!("📁 Output settings imported from output_settings.json");
+                    legacy
+                }
+                None => OutputSettings::default(),
+            },
+        };
+        Ok(Self {
+            output,
+            sync: section(&sections, SYNC_SECTION),
+        })
+    }
+
+    pub async fn save(&self, pool: &DbPool) -> Result<(), String> {
+        self.output.validate()?;
+        store_section(pool, OUTPUT_SECTION, &self.output).await?;
+        store_section(pool, SYNC_SECTION, &self.sync).await
+    }
+}
+
+// Section illisible (version plus récente, édition manuelle) : valeurs par
+// défaut plutôt qu'une application inutilisable
+fn section<T: DeserializeOwned + Default>(sections: &HashMap<String, String>, key: &str) -> T {
+    match sections.get(key).map(|value| serde_json::from_str(value)) {
+        Some(Ok(value)) => value,
+        Some(Err(e)) => {
+            eprintln!("⚠️ Ignoring invalid \"{}\" settings: {}", key, e);
+            T::default()
+        }
+        None => T::default(),
+    }
+}
+
+async fn store_section<T: Serialize>(pool: &DbPool, key: &str, value: &T) -> Result<(), String> {
+    let value = serde_json::to_string(value)
+        .map_err(|e| format!("Failed to serialize {} settings: {}", key, e))?;
+    queries::set_local_setting(pool, key, &value)
+        .await
+        .map_err(|e| format!("Failed to save {} settings: {}", key, e))
+}
+
+/// Réglages communs à l'organisation, partagés par la synchronisation
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct OrgSettings {
+    /// Devise proposée pour une nouvelle proposition (code ISO 4217)
+    pub default_currency: String,
+    /// Échéance d'une facture, en jours après son émission
+    pub payment_terms_days: u32,
+    /// Voir `numbering::INVOICE_FIELDS`
+    pub invoice_number_format: String,
+    /// Voir `numbering::PROPOSAL_FIELDS`
+    pub proposal_number_format: String,
+}
+
+impl Default for OrgSettings {
+    fn default() -> Self {
+        Self {
+            default_currency: "USD".to_string(),
+            payment_terms_days: 30,
+            invoice_number_format: numbering::DEFAULT_INVOICE_FORMAT.to_string(),
+            proposal_number_format: numbering::DEFAULT_PROPOSAL_FORMAT.to_string(),
+        }
+    }
+}
+
+impl OrgSettings {
+    /// Chaque réglage est lu séparément : une valeur absente ou illisible
+    /// garde sa valeur par défaut sans invalider les autres
+    pub async fn load(pool: &DbPool) -> Result<Self, String> {
+        let stored = queries::get_org_settings(pool)
+            .await
+            .map_err(|e| format!("Failed to load organization settings: {}", e))?;
+
+        let mut settings = serde_json::to_value(Self::default())
+            .map_err(|e| format!("Failed to serialize organization settings: {}", e))?;
+        let keys: Vec<String> = settings.as_object().map(|o| o.keys().cloned().collect()).unwrap_or_default();
+        for key in keys {
+            let Some(raw) = stored.get(&key) else {
+                continue;
+            };
+            let mut candidate = settings.clone();
+            let accepted = serde_json::from_str::<serde_json::Value>(raw).ok().and_then(|value| {
+                candidate[key.as_str()] = value;
+                serde_json::from_value::<Self>(candidate.clone()).ok().filter(|s| s.validate().is_ok())
+            });
+            match accepted {
+                Some(_) => settings = candidate,
+                None => eprintln!("⚠️ Ignoring invalid organization setting {}: {}", key, raw),
+            }
+        }
+        serde_json::from_value(settings).map_err(|e| format!("Invalid organization settings: {}", e))
+    }
+
+    /// Enregistre les réglages modifiés ; ils partent à la prochaine
+    /// synchronisation. Renvoie le nombre de réglages modifiés.
+    pub async fn save(&self, pool: &DbPool) -> Result<usize, String> {
+        self.validate()?;
+        let value = serde_json::to_value(self)
+            .map_err(|e| format!("Failed to serialize organization settings: {}", e))?;
+        let mut changed = 0;
+        for (key, value) in value.as_object().into_iter().flatten() {
+            if queries::set_org_setting(pool, key, &value.to_string())
+                .await
+                .map_err(|e| format!("Failed to save organization setting {}: {}", key, e))?
+            {
+                changed += 1;
+            }
+        }
+        Ok(changed)
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        if self.default_currency.len() != 3 || !self.default_currency.chars().all(|c| c.is_ascii_uppercase()) {
+            return Err(format!("Invalid currency code: {}", self.default_currency));
+        }
+        if self.payment_terms_days > 365 {
+            return Err(format!("Payment terms cannot exceed 365 days: {}", self.payment_terms_days));
+        }
+        numbering::validate_format(&self.invoice_number_format, numbering::INVOICE_FIELDS)?;
+        numbering::validate_format(&self.proposal_number_format, numbering::PROPOSAL_FIELDS)
+    }
+}
+
+/// Réglages présentés ensemble par la page Paramètres
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Settings {
+    pub local: LocalSettings,
+    pub org: OrgSettings,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sqlx::sqlite::{SqliteConnectOptions, SqlitePool};
+    use std::str::FromStr;
+
+    fn profile(name: &str, server_url: Option<&str>, credentials_ref: Option<&str>) -> ServerProfile {
+        ServerProfile {
+            name: name.to_string(),
+            server_url: server_url.map(str::to_string),
+            credentials_ref: credentials_ref.map(str::to_string),
+        }
+    }
+
+    #[test]
+    fn profiles_are_trimmed_and_checked() {
+        assert_eq!(
+            profile(" prod-2 ", Some(" https://crm.example.com/ "), Some(" keychain:crm ")).normalized(),
+            Ok(profile("prod-2", Some("https://crm.example.com"), Some("keychain:crm")))
+        );
+        // Champs vides : pas de serveur, pas d'identifiants
+        assert_eq!(profile("test", Some("  "), Some("")).normalized(), Ok(profile("test", None, None)));
+
+        assert!(profile("Prod", None, None).normalized().is_err());
+        assert!(profile("mon profil", None, None).normalized().is_err());
+        assert!(profile("   ", None, None).normalized().is_err());
+        assert!(profile(&"a".repeat(33), None, None).normalized().is_err());
+        assert!(profile(&"a".repeat(32), None, None).normalized().is_ok());
+        assert!(profile("prod", Some("ftp://crm.example.com"), None).normalized().is_err());
+        assert!(profile("prod", Some("crm.example.com"), None).normalized().is_err());
+    }
+
+    #[test]
+    fn unreadable_profiles_fall_back_to_the_default_profile() {
+        let dir = std::env::temp_dir().join(format!("crm-profiles-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+
+        // Pas de fichier : profil par défaut, sans erreur
+        let (profiles, error) = ServerProfiles::load_or_default(&dir);
+        assert_eq!(profiles.active().name, DEFAULT_PROFILE);
+        assert!(error.is_none());
+
+        std::fs::write(dir.join(PROFILES_FILE), "{ \"active\": ").unwrap();
+        let (profiles, error) = ServerProfiles::load_or_default(&dir);
+        assert_eq!(profiles.active().name, DEFAULT_PROFILE);
+        assert!(error.unwrap().contains("Invalid server profiles"));
+        // Le fichier est gardé à part et ne bloque plus les lectures suivantes
+        assert!(!dir.join(PROFILES_FILE).exists());
+        assert_eq!(
+            std::fs::read_to_string(dir.join(format!("{}.invalid", PROFILES_FILE))).unwrap(),
+            "{ \"active\": "
+        );
+        assert!(ServerProfiles::load(&dir).is_ok());
+
+        // Profil actif inconnu
+        std::fs::write(dir.join(PROFILES_FILE), r#"{"active": "prod", "profiles": []}"#).unwrap();
+        let (profiles, error) = ServerProfiles::load_or_default(&dir);
+        assert_eq!(profiles.active().name, DEFAULT_PROFILE);
+        assert!(error.unwrap().contains("does not exist"));
+
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[tokio::test]
+    async fn invalid_organization_settings_keep_their_default_one_by_one() {
+        let path = std::env::temp_dir().join(format!("crm-settings-{}.db", uuid::Uuid::new_v4()));
+        let options = SqliteConnectOptions::from_str(&format!("sqlite:{}", path.display()))
+            .unwrap()
+            .create_if_missing(true);
+        let pool = SqlitePool::connect_with(options).await.unwrap();
+        crate::db::migrations::run_migrations(&pool).await.unwrap();
+
+        assert_eq!(OrgSettings::load(&pool).await.unwrap(), OrgSettings::default());
+
+        queries::set_org_setting(&pool, "default_currency", "\"EUR\"").await.unwrap();
+        // Hors limites, JSON illisible, mauvais type
+        queries::set_org_setting(&pool, "payment_terms_days", "400").await.unwrap();
+        queries::set_org_setting(&pool, "invoice_number_format", "{INV").await.unwrap();
+        queries::set_org_setting(&pool, "proposal_number_format", "42").await.unwrap();
+
+        let settings = OrgSettings::load(&pool).await.unwrap();
+        assert_eq!(
+            settings,
+            OrgSettings {
+                default_currency: "EUR".to_string(),
+                ..OrgSettings::default()
+            }
+        );
+
+        queries::set_org_setting(&pool, "payment_terms_days", "45").await.unwrap();
+        assert_eq!(OrgSettings::load(&pool).await.unwrap().payment_terms_days, 45);
+
+        pool.close().await;
+        std::fs::remove_file(&path).ok();
+    }
+}
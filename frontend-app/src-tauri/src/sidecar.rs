@@ -1,80 +1,322 @@
-use tauri::api::process::{Command, CommandEvent};
-use std::sync::Arc;
-use tokio::sync::Mutex;
+use serde::{Deserialize, Serialize};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tauri::api::process::{Command, CommandChild, CommandEvent};
+use tauri::async_runtime::Receiver;
 
+/// Redémarrages tolérés dans la fenêtre avant d'abandonner (boucle de crash)
+const MAX_RESTARTS: usize = 5;
+const RESTART_WINDOW: Duration = Duration::from_secs(60);
+/// Délai maximal d'attente de la route /health après un lancement
+const READY_TIMEOUT: Duration = Duration::from_secs(30);
+/// Ligne écrite par le sidecar une fois son port ouvert
+const PORT_ANNOUNCE: &str = "SIDECAR_PORT=";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SidecarState {
+    Starting,
+    Running,
+    Restarting,
+    Failed,
+    Stopped,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SidecarStatus {
+    pub state: SidecarState,
+    pub port: Option<u16>,
+    /// Adresse de l'API, renseignée une fois le sidecar prêt
+    pub url: Option<String>,
+    pub restarts: u32,
+    pub last_error: Option<String>,
+}
+
+struct Inner {
+    child: Option<CommandChild>,
+    status: SidecarStatus,
+    /// Dates des arrêts inattendus récents
+    crashes: Vec<Instant>,
+    /// Numéro du lancement en cours, pour ignorer un processus remplacé
+    launch: u64,
+    stopping: bool,
+}
+
+/// Supervise le sidecar Python : port libre attribué par le système au
+/// sidecar lui-même (`--port 0`), attente de /health avec backoff,
+/// redémarrage automatique après un arrêt inattendu ou un démarrage trop
+/// long, dans la limite de MAX_RESTARTS par RESTART_WINDOW.
 pub struct SidecarManager {
-    child: Arc<Mutex<Option<tauri::api::process::CommandChild>>>,
+    inner: Arc<Mutex<Inner>>,
 }
 
 impl Drop for SidecarManager {
     fn drop(&mut self) {
         // Tuer le processus Python quand l'app se ferme
-        let child = self.child.clone();
-        tokio::spawn(async move {
-            let mut child_lock = child.lock().await;
-            if let Some(child_process) = child_lock.take() {
+        if let Ok(mut inner) = self.inner.lock() {
+            inner.stopping = true;
+            inner.status.state = SidecarState::Stopped;
+            if let Some(child_process) = inner.child.take() {
                 let _ = child_process.kill();
                 println!("🛑 Document Generator arrêté");
             }
-        });
+        }
     }
 }
 
 impl SidecarManager {
     pub fn new() -> Self {
         Self {
-            child: Arc::new(Mutex::new(None)),
+            inner: Arc::new(Mutex::new(Inner {
+                child: None,
+                status: SidecarStatus {
+                    state: SidecarState::Stopped,
+                    port: None,
+                    url: None,
+                    restarts: 0,
+                    last_error: None,
+                },
+                crashes: Vec::new(),
+                launch: 0,
+                stopping: false,
+            })),
         }
     }
-    
-    pub async fn start(&self) -> Result<(), String> {
-        // Lancer le sidecar Python
-        let (mut rx, child) = Command::new_sidecar("doc-generator")
-            .map_err(|e| format!("Failed to create sidecar command: {}", e))?
-            .spawn()
-            .map_err(|e| format!("Failed to spawn sidecar: {}", e))?;
-        
-        // Stocker le child process
-        let mut child_lock = self.child.lock().await;
-        *child_lock = Some(child);
-        drop(child_lock);
-        
-        // Logger les events du sidecar (en async)
-        tokio::spawn(async move {
-            while let Some(event) = rx.recv().await {
-                match event {
-                    CommandEvent::Stdout(line) => {
-                        println!("[Doc Generator] {}", line);
-                    }
-                    CommandEvent::Stderr(line) => {
-                        eprintln!("[Doc Generator ERROR] {}", line);
-                    }
-                    CommandEvent::Error(err) => {
-                        eprintln!("[Doc Generator FATAL] {}", err);
-                    }
-                    CommandEvent::Terminated(payload) => {
-                        println!("[Doc Generator] Terminated with code: {:?}", payload.code);
-                        break;
-                    }
-                    _ => {}
+
+    /// Lance la supervision en tâche de fond ; l'état est consultable avec
+    /// `status()` sans bloquer le démarrage de l'application.
+    pub fn start(&self) {
+        let inner = self.inner.clone();
+        if let Ok(mut guard) = inner.lock() {
+            guard.stopping = false;
+            guard.status.state = SidecarState::Starting;
+        }
+        tauri::async_runtime::spawn(supervise(inner));
+    }
+
+    pub fn status(&self) -> SidecarStatus {
+        self.inner
+            .lock()
+            .map(|inner| inner.status.clone())
+            .unwrap_or_else(|e| e.into_inner().status.clone())
+    }
+}
+
+async fn supervise(inner: Arc<Mutex<Inner>>) {
+    loop {
+        // Port 0 : le sidecar ouvre lui-même un port libre et l'annonce
+        let spawned = Command::new_sidecar("doc-generator")
+            .map_err(|e| format!("Failed to create sidecar command: {}", e))
+            .and_then(|command| {
+                command
+                    .args(["--port", "0"])
+                    .spawn()
+                    .map_err(|e| format!("Failed to spawn sidecar: {}", e))
+            });
+        // Un lancement refusé (binaire verrouillé un instant par un
+        // antivirus ou une mise à jour) compte comme un arrêt inattendu
+        let error = match spawned {
+            Ok((rx, child)) => run(&inner, rx, child).await,
+            Err(e) => {
+                eprintln!("❌ {}", e);
+                e
+            }
+        };
+
+        let Some(delay) = restart_delay_after(&inner, error) else {
+            return;
+        };
+        println!("🔁 Restarting Document Generator in {}s", delay.as_secs());
+        tokio::time::sleep(delay).await;
+        if lock(&inner).stopping {
+            return;
+        }
+        lock(&inner).status.state = SidecarState::Starting;
+    }
+}
+
+/// Suit un lancement jusqu'à l'arrêt du processus ; retourne l'erreur à
+/// afficher si cet arrêt n'était pas demandé.
+async fn run(inner: &Arc<Mutex<Inner>>, mut rx: Receiver<CommandEvent>, child: CommandChild) -> String {
+    let launch = {
+        let mut guard = lock(inner);
+        guard.child = Some(child);
+        guard.launch += 1;
+        guard.status.port = None;
+        guard.status.url = None;
+        guard.launch
+    };
+    println!("🐍 Document Generator lancé");
+    tauri::async_runtime::spawn(wait_ready(inner.clone(), launch));
+
+    // Logger les events du sidecar jusqu'à son arrêt
+    let mut exit_code = None;
+    while let Some(event) = rx.recv().await {
+        match event {
+            CommandEvent::Stdout(line) => {
+                if let Some(port) = announced_port(&line) {
+                    lock(inner).status.port = Some(port);
+                    println!("🐍 Document Generator à l'écoute sur le port {}", port);
+                } else {
+                    println!("[Doc Generator] {}", line);
                 }
             }
-        });
-        
-        // Attendre un peu que l'API démarre
-        tokio::time::sleep(tokio::time::Duration::from_secs(2)).await;
-        
-        // Vérifier que l'API répond
-        match reqwest::get("http://127.0.0.1:8001/health").await {
-            Ok(resp) if resp.status().is_success() => {
-                println!("✅ Document Generator API démarrée");
-                Ok(())
+            CommandEvent::Stderr(line) => {
+                eprintln!("[Doc Generator ERROR] {}", line);
             }
-            _ => {
-                println!("⚠️ Document Generator démarré mais pas encore prêt");
-                Ok(())
+            CommandEvent::Error(err) => {
+                eprintln!("[Doc Generator FATAL] {}", err);
             }
+            CommandEvent::Terminated(payload) => {
+                println!("[Doc Generator] Terminated with code: {:?}", payload.code);
+                exit_code = payload.code;
+                break;
+            }
+            _ => {}
         }
     }
+
+    let mut guard = lock(inner);
+    guard.child = None;
+    guard.status.url = None;
+    format!("Sidecar exited with code {:?}", exit_code)
 }
 
+/// Enregistre un arrêt inattendu ou un lancement refusé ; retourne
+/// l'attente avant le prochain lancement, ou `None` si la supervision
+/// s'arrête (arrêt demandé, ou plus de MAX_RESTARTS dans RESTART_WINDOW).
+fn restart_delay_after(inner: &Arc<Mutex<Inner>>, error: String) -> Option<Duration> {
+    let mut guard = lock(inner);
+    if guard.stopping {
+        guard.status.state = SidecarState::Stopped;
+        return None;
+    }
+
+    let now = Instant::now();
+    guard.crashes.retain(|at| now.duration_since(*at) < RESTART_WINDOW);
+    guard.crashes.push(now);
+    // Un arrêt par wait_ready a déjà renseigné son erreur
+    if guard.status.state != SidecarState::Failed {
+        guard.status.last_error = Some(error);
+    }
+    if guard.crashes.len() > MAX_RESTARTS {
+        guard.status.state = SidecarState::Failed;
+        eprintln!(
+            "❌ Document Generator crashed {} times in {}s, giving up",
+            guard.crashes.len(),
+            RESTART_WINDOW.as_secs()
+        );
+        return None;
+    }
+
+    guard.status.state = SidecarState::Restarting;
+    guard.status.restarts += 1;
+    Some(restart_delay(guard.crashes.len()))
+}
+
+/// Interroge /health avec un intervalle croissant jusqu'à READY_TIMEOUT ;
+/// au-delà, le processus est arrêté et la supervision le relance.
+async fn wait_ready(inner: Arc<Mutex<Inner>>, launch: u64) {
+    let client = reqwest::Client::new();
+    let started = Instant::now();
+    let mut delay = Duration::from_millis(100);
+
+    while started.elapsed() < READY_TIMEOUT {
+        tokio::time::sleep(delay).await;
+        delay = (delay * 2).min(Duration::from_secs(2));
+
+        // Le processus a été remplacé ou arrêté entre-temps
+        let port = {
+            let guard = lock(&inner);
+            if guard.launch != launch || guard.child.is_none() {
+                return;
+            }
+            guard.status.port
+        };
+        // Port pas encore annoncé
+        let Some(port) = port else { continue };
+
+        let url = format!("http://127.0.0.1:{}", port);
+        let healthy = client
+            .get(format!("{}/health", url))
+            .timeout(Duration::from_secs(2))
+            .send()
+            .await
+            .map(|resp| resp.status().is_success())
+            .unwrap_or(false);
+        if healthy {
+            let mut guard = lock(&inner);
+            if guard.launch == launch {
+                guard.status.state = SidecarState::Running;
+                guard.status.url = Some(url.clone());
+                guard.status.last_error = None;
+                println!("✅ Document Generator API démarrée sur {}", url);
+            }
+            return;
+        }
+    }
+
+    let message = format!("Sidecar not ready after {}s", READY_TIMEOUT.as_secs());
+    let mut guard = lock(&inner);
+    if guard.launch != launch {
+        return;
+    }
+    eprintln!("❌ {}, killing it", message);
+    guard.status.state = SidecarState::Failed;
+    guard.status.last_error = Some(message);
+    // L'arrêt est vu par supervise, qui relance avec backoff
+    if let Some(child) = guard.child.take() {
+        let _ = child.kill();
+    }
+}
+
+fn lock(inner: &Arc<Mutex<Inner>>) -> std::sync::MutexGuard<'_, Inner> {
+    inner.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Attente avant un redémarrage : 1s, 2s, 4s... selon le nombre d'arrêts
+/// récents, plafonnée à 32s.
+fn restart_delay(recent_crashes: usize) -> Duration {
+    Duration::from_secs(1u64 << recent_crashes.saturating_sub(1).min(5))
+}
+
+/// Port annoncé par le sidecar sur sa sortie standard.
+fn announced_port(line: &str) -> Option<u16> {
+    line.trim().strip_prefix(PORT_ANNOUNCE)?.parse().ok().filter(|port| *port != 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn port_is_read_from_announce_line() {
+        assert_eq!(announced_port("SIDECAR_PORT=49152\n"), Some(49152));
+        assert_eq!(announced_port("SIDECAR_PORT=0"), None);
+        assert_eq!(announced_port("SIDECAR_PORT=abc"), None);
+        assert_eq!(announced_port("INFO: started on 8001"), None);
+    }
+
+    #[test]
+    fn restart_delay_doubles_up_to_a_cap() {
+        let delays: Vec<u64> = (1..=8).map(|n| restart_delay(n).as_secs()).collect();
+        assert_eq!(delays, vec![1, 2, 4, 8, 16, 32, 32, 32]);
+        assert_eq!(restart_delay(0), Duration::from_secs(1));
+    }
+
+    #[test]
+    fn spawn_failures_back_off_then_give_up() {
+        let manager = SidecarManager::new();
+        lock(&manager.inner).status.state = SidecarState::Starting;
+        for attempt in 1..=MAX_RESTARTS {
+            let delay = restart_delay_after(&manager.inner, "Failed to spawn sidecar: busy".to_string());
+            assert_eq!(delay, Some(restart_delay(attempt)));
+            assert_eq!(manager.status().state, SidecarState::Restarting);
+        }
+        assert_eq!(restart_delay_after(&manager.inner, "Failed to spawn sidecar: busy".to_string()), None);
+        let status = manager.status();
+        assert_eq!(status.state, SidecarState::Failed);
+        assert_eq!(status.restarts, MAX_RESTARTS as u32);
+        assert_eq!(status.last_error.as_deref(), Some("Failed to spawn sidecar: busy"));
+    }
+}
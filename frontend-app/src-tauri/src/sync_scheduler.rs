@@ -0,0 +1,573 @@
+use crate::commands::sync_commands::{check_server_connectivity, run_sync, SyncResult};
+use crate::db::{queries, DbPool};
+use crate::settings::DEFAULT_SYNC_INTERVAL_SECONDS;
+use crm_schema::{ChangeNotice, CHANGE_EVENT, RESYNC_EVENT};
+use serde::{Deserialize, Serialize};
+use std::sync::atomic::{AtomicI64, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+use tauri::{AppHandle, Manager};
+use tokio::sync::Notify;
+
+const MIN_INTERVAL_SECONDS: u64 = 30;
+/// Fréquence de vérification des échéances (sans accès à la base)
+const POLL_INTERVAL: Duration = Duration::from_secs(2);
+/// Délai sans nouvelle modification locale avant de synchroniser, pour
+/// regrouper une saisie en un seul envoi
+const DEBOUNCE_MS: i64 = 3_000;
+/// Délai après une annonce du serveur, pour regrouper une rafale d'annonces
+/// en une seule synchronisation
+const REMOTE_DEBOUNCE: Duration = Duration::from_secs(1);
+/// Flux d'annonces considéré coupé sans rien recevoir pendant ce délai ; le
+/// serveur envoie un commentaire toutes les 15 s
+const STREAM_TIMEOUT: Duration = Duration::from_secs(45);
+/// Attente après un échec : 5 s, doublée à chaque échec, au plus 15 min
+const BACKOFF_BASE: Duration = Duration::from_secs(5);
+const BACKOFF_MAX: Duration = Duration::from_secs(15 * 60);
+
+/// Instant (ms) de la dernière écriture locale signalée
+static LOCAL_CHANGE_AT: AtomicI64 = AtomicI64::new(0);
+static LOCAL_CHANGE: Notify = Notify::const_new();
+
+/// Signale une écriture d'enregistrement synchronisé ; la synchronisation
+/// suit DEBOUNCE_MS plus tard si elle est active
+pub fn notify_local_change() {
+    LOCAL_CHANGE_AT.fetch_max(chrono::Utc::now().timestamp_millis(), Ordering::Relaxed);
+    LOCAL_CHANGE.notify_one();
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SyncSchedulerConfig {
+    pub enabled: bool,
+    pub server_url: Option<String>,
+    pub interval_seconds: u64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SchedulerState {
+    /// Désactivé, ou aucun serveur configuré
+    Disabled,
+    Idle,
+    Syncing,
+    /// Serveur injoignable : nouvel essai après backoff
+    Offline,
+    /// Serveur joignable mais synchronisation en erreur : nouvel essai après backoff
+    Failing,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SchedulerStatus {
+    pub state: SchedulerState,
+    pub config: SyncSchedulerConfig,
+    /// Début de la dernière synchronisation réussie (ms)
+    pub last_success: Option<i64>,
+    pub last_error: Option<String>,
+    pub consecutive_failures: u32,
+    /// Délai avant le prochain essai, pendant un backoff
+    pub retry_in_seconds: Option<u64>,
+    /// Abonné aux annonces de modifications du serveur
+    pub live: bool,
+}
+
+/// Étape d'une synchronisation, émise avec l'événement `sync:progress`
+#[derive(Debug, Clone, Serialize)]
+pub struct SyncProgress {
+    /// "pushing", "applying" ou "attachments"
+    pub stage: &'static str,
+    pub count: usize,
+}
+
+/// Échec d'une synchronisation, émis avec l'événement `sync:error`
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SyncFailure {
+    pub message: String,
+    pub errors: Vec<String>,
+    pub online: bool,
+    pub retry_in_seconds: u64,
+}
+
+struct Inner {
+    config: SyncSchedulerConfig,
+    state: SchedulerState,
+    last_success: Option<i64>,
+    last_error: Option<String>,
+    failures: u32,
+    retry_at: Option<Instant>,
+    next_periodic: Instant,
+    /// Première annonce du serveur pas encore suivie d'une synchronisation
+    remote_change_at: Option<Instant>,
+    live: bool,
+}
+
+/// Synchronise en tâche de fond : périodiquement, quelques secondes après
+/// une modification locale, et avec un backoff exponentiel tant que le
+/// serveur est injoignable ou en erreur. Les synchronisations manuelles
+/// passent par `sync_now` pour ne jamais se chevaucher avec celles-ci.
+///
+/// Le poste est aussi abonné aux annonces du serveur (`/api/changes`) : une
+/// modification envoyée par un autre poste est récupérée en quelques
+/// secondes, sans attendre la synchronisation périodique.
+///
+/// Événements émis : `sync:started` (adresse du serveur), `sync:progress`
+/// (`SyncProgress`), `sync:success` (`SyncResult`), `sync:error` (`SyncFailure`),
+/// `sync:live` (abonnement aux annonces actif ou non).
+pub struct SyncScheduler {
+    inner: Arc<Mutex<Inner>>,
+    wake: Arc<Notify>,
+    running: Arc<tokio::sync::Mutex<()>>,
+}
+
+impl SyncScheduler {
+    pub fn new() -> Self {
+        Self {
+            inner: Arc::new(Mutex::new(Inner {
+                config: SyncSchedulerConfig {
+                    enabled: false,
+                    server_url: None,
+                    interval_seconds: DEFAULT_SYNC_INTERVAL_SECONDS,
+                },
+                state: SchedulerState::Disabled,
+                last_success: None,
+                last_error: None,
+                failures: 0,
+                retry_at: None,
+                next_periodic: Instant::now(),
+                remote_change_at: None,
+                live: false,
+            })),
+            wake: Arc::new(Notify::new()),
+            running: Arc::new(tokio::sync::Mutex::new(())),
+        }
+    }
+
+    /// Lance la boucle de planification ; rien n'est envoyé tant qu'aucun
+    /// serveur n'est configuré
+    pub fn start(&self, app: AppHandle) {
+        tauri::async_runtime::spawn(schedule(app.clone(), self.inner.clone(), self.wake.clone(), self.running.clone()));
+        tauri::async_runtime::spawn(subscribe(app, self.inner.clone(), self.wake.clone()));
+    }
+
+    /// Applique une configuration. Un changement de serveur oublie les
+    /// échecs précédents ; une synchronisation suit aussitôt si elle est active
+    pub fn configure(&self, mut config: SyncSchedulerConfig) -> SchedulerStatus {
+        config.server_url = config
+            .server_url
+            .map(|url| url.trim().trim_end_matches('/').to_string())
+            .filter(|url| !url.is_empty());
+        config.interval_seconds = config.interval_seconds.max(MIN_INTERVAL_SECONDS);
+        {
+            let mut inner = lock(&self.inner);
+            if inner.config.server_url != config.server_url {
+                inner.failures = 0;
+                inner.retry_at = None;
+                inner.last_error = None;
+            }
+            inner.state = resting_state(&config);
+            inner.next_periodic = Instant::now();
+            inner.config = config;
+        }
+        self.wake.notify_one();
+        self.status()
+    }
+
+    pub fn server_url(&self) -> Option<String> {
+        lock(&self.inner).config.server_url.clone()
+    }
+
+    pub fn status(&self) -> SchedulerStatus {
+        let inner = lock(&self.inner);
+        SchedulerStatus {
+            state: inner.state,
+            config: inner.config.clone(),
+            last_success: inner.last_success,
+            last_error: inner.last_error.clone(),
+            consecutive_failures: inner.failures,
+            retry_in_seconds: inner
+                .retry_at
+                .map(|at| at.saturating_duration_since(Instant::now()).as_secs()),
+            live: inner.live,
+        }
+    }
+
+    /// Arrête les synchronisations automatiques et attend la fin de celle en
+    /// cours ; aucune ne démarre tant que le verrou renvoyé est tenu, la base
+    /// peut alors être fermée sans risque
+    pub async fn suspend(&self) -> tokio::sync::MutexGuard<'_, ()> {
+        {
+            let mut inner = lock(&self.inner);
+            inner.config.enabled = false;
+            inner.state = SchedulerState::Disabled;
+        }
+        self.running.lock().await
+    }
+
+    /// Synchronisation demandée par l'utilisateur : attend la fin de celle en
+    /// cours éventuelle, ignore le backoff
+    pub async fn sync_now(&self, app: &AppHandle, pool: &DbPool, server_url: &str) -> Result<SyncResult, String> {
+        perform(app, pool, server_url, &self.inner, &self.running).await
+    }
+}
+
+fn resting_state(config: &SyncSchedulerConfig) -> SchedulerState {
+    if config.enabled && config.server_url.is_some() {
+        SchedulerState::Idle
+    } else {
+        SchedulerState::Disabled
+    }
+}
+
+fn lock(inner: &Mutex<Inner>) -> MutexGuard<'_, Inner> {
+    inner.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+pub fn emit_progress(app: &AppHandle, stage: &'static str, count: usize) {
+    let _ = app.emit_all("sync:progress", SyncProgress { stage, count });
+}
+
+fn backoff(failures: u32) -> Duration {
+    BACKOFF_BASE
+        .saturating_mul(2u32.saturating_pow(failures.saturating_sub(1)))
+        .min(BACKOFF_MAX)
+}
+
+async fn schedule(app: AppHandle, inner: Arc<Mutex<Inner>>, wake: Arc<Notify>, running: Arc<tokio::sync::Mutex<()>>) {
+    // Modifications faites hors ligne lors d'une session précédente
+    let pool = app.state::<DbPool>().inner().clone();
+    if let Some(latest) = pending_local_change(&pool).await {
+        LOCAL_CHANGE_AT.fetch_max(latest, Ordering::Relaxed);
+    }
+
+    loop {
+        tokio::select! {
+            _ = tokio::time::sleep(POLL_INTERVAL) => {}
+            _ = wake.notified() => {}
+            _ = LOCAL_CHANGE.notified() => {}
+        }
+
+        let (server_url, periodic_due, retry_due, remote_due, backing_off, synced_from) = {
+            let inner = lock(&inner);
+            let now = Instant::now();
+            let server_url = inner.config.server_url.clone().filter(|_| inner.config.enabled);
+            (
+                server_url,
+                now >= inner.next_periodic,
+                inner.retry_at.is_some_and(|at| now >= at),
+                inner.remote_change_at.is_some_and(|at| now >= at + REMOTE_DEBOUNCE),
+                inner.retry_at.is_some_and(|at| now < at),
+                inner.last_success.unwrap_or(0),
+            )
+        };
+        let Some(server_url) = server_url else {
+            continue;
+        };
+        if backing_off {
+            continue;
+        }
+
+        let local_due = local_change_settled(
+            LOCAL_CHANGE_AT.load(Ordering::Relaxed),
+            synced_from,
+            chrono::Utc::now().timestamp_millis(),
+        );
+        if periodic_due || retry_due || remote_due || local_due {
+            let _ = perform(&app, &pool, &server_url, &inner, &running).await;
+        }
+    }
+}
+
+// Dernière modification locale pas encore envoyée, d'après la base
+async fn pending_local_change(pool: &DbPool) -> Option<i64> {
+    let metadata = queries::get_sync_metadata(pool).await.ok()?;
+    match queries::latest_change_since(pool, metadata.last_push_timestamp).await {
+        Ok(latest) => latest,
+        Err(e) => {
+            eprintln!("❌ [SyncScheduler] Failed to check local changes: {}", e);
+            None
+        }
+    }
+}
+
+// Modification locale postérieure au début de la dernière synchronisation
+// réussie, la dernière datant d'au moins DEBOUNCE_MS
+fn local_change_settled(latest_change: i64, synced_from: i64, now: i64) -> bool {
+    latest_change > synced_from && now - latest_change >= DEBOUNCE_MS
+}
+
+async fn perform(
+    app: &AppHandle,
+    pool: &DbPool,
+    server_url: &str,
+    inner: &Mutex<Inner>,
+    running: &tokio::sync::Mutex<()>,
+) -> Result<SyncResult, String> {
+    let _running = running.lock().await;
+    let started_at = chrono::Utc::now().timestamp_millis();
+    let started = Instant::now();
+    lock(inner).state = SchedulerState::Syncing;
+    let _ = app.emit_all("sync:started", server_url);
+
+    let result = run_sync(app, pool, server_url).await;
+    if let Ok(sync_result) = &result {
+        // Seuls les échecs d'échange avec le serveur relancent le backoff ; les
+        // refus et les lignes locales illisibles sont signalés dans le résultat
+        if sync_result.success {
+            {
+                let mut inner = lock(inner);
+                inner.failures = 0;
+                inner.retry_at = None;
+                inner.last_error = None;
+                inner.last_success = Some(started_at);
+                inner.next_periodic = Instant::now() + Duration::from_secs(inner.config.interval_seconds);
+                // Les annonces reçues pendant la synchronisation en appellent une autre
+                if inner.remote_change_at.is_some_and(|at| at <= started) {
+                    inner.remote_change_at = None;
+                }
+                inner.state = resting_state(&inner.config);
+            }
+            println!("✅ [SyncScheduler] {}", sync_result.message);
+            let _ = app.emit_all("sync:success", sync_result);
+            return result;
+        }
+    }
+
+    let (message, errors) = match &result {
+        Ok(sync_result) => (sync_result.message.clone(), sync_result.errors.clone()),
+        Err(e) => (e.clone(), vec![e.clone()]),
+    };
+    let online = check_server_connectivity(server_url).await;
+    let delay = {
+        let mut inner = lock(inner);
+        inner.failures += 1;
+        let delay = backoff(inner.failures);
+        inner.retry_at = Some(Instant::now() + delay);
+        inner.last_error = Some(message.clone());
+        inner.state = if online { SchedulerState::Failing } else { SchedulerState::Offline };
+        delay
+    };
+    eprintln!("❌ [SyncScheduler] {} (retry in {}s)", message, delay.as_secs());
+    let _ = app.emit_all(
+        "sync:error",
+        SyncFailure { message, errors, online, retry_in_seconds: delay.as_secs() },
+    );
+    result
+}
+
+// Annonces du serveur du profil actif, tant que la synchronisation
+// automatique est active. Reconnexion avec backoff après une coupure ; le
+// serveur demande alors une resynchronisation, les annonces manquées
+// n'étant pas conservées.
+async fn subscribe(app: AppHandle, inner: Arc<Mutex<Inner>>, wake: Arc<Notify>) {
+    let mut failures = 0;
+    loop {
+        let server_url = {
+            let inner = lock(&inner);
+            inner.config.server_url.clone().filter(|_| inner.config.enabled)
+        };
+        let Some(server_url) = server_url else {
+            tokio::time::sleep(POLL_INTERVAL).await;
+            continue;
+        };
+
+        let pool = app.state::<DbPool>().inner().clone();
+        let result = match queries::get_device_identity(&pool).await {
+            Ok((device_id, _)) => follow_changes(&app, &server_url, &device_id, &inner, &wake).await,
+            Err(e) => Err(format!("Failed to get device identity: {}", e)),
+        };
+        set_live(&app, &inner, false);
+        let delay = match result {
+            Ok(()) => {
+                failures = 0;
+                POLL_INTERVAL
+            }
+            Err(e) => {
+                failures += 1;
+                let delay = backoff(failures);
+                eprintln!("❌ [SyncScheduler] Change stream: {} (reconnecting in {}s)", e, delay.as_secs());
+                delay
+            }
+        };
+        tokio::time::sleep(delay).await;
+    }
+}
+
+fn set_live(app: &AppHandle, inner: &Mutex<Inner>, live: bool) {
+    let changed = std::mem::replace(&mut lock(inner).live, live) != live;
+    if changed {
+        let _ = app.emit_all("sync:live", live);
+    }
+}
+
+// Suit le flux jusqu'à sa fermeture par le serveur (`Ok`) ou un changement
+// de configuration ; chaque annonce demande une synchronisation au planificateur
+async fn follow_changes(
+    app: &AppHandle,
+    server_url: &str,
+    device_id: &str,
+    inner: &Mutex<Inner>,
+    wake: &Notify,
+) -> Result<(), String> {
+    let mut response = reqwest::Client::new()
+        .get(format!("{}/api/changes", server_url))
+        .query(&[("device_id", device_id)])
+        .header(reqwest::header::ACCEPT, "text/event-stream")
+        .send()
+        .await
+        .map_err(|e| format!("Failed to connect: {}", e))?
+        .error_for_status()
+        .map_err(|e| format!("Failed to subscribe: {}", e))?;
+    println!("📡 [SyncScheduler] Listening for changes on {}", server_url);
+    set_live(app, inner, true);
+
+    let mut parser = EventParser::default();
+    let mut last_received = Instant::now();
+    loop {
+        let chunk = tokio::select! {
+            chunk = response.chunk() => chunk.map_err(|e| format!("Stream interrupted: {}", e))?,
+            // Vérifie régulièrement que le serveur est toujours celui configuré
+            _ = tokio::time::sleep(POLL_INTERVAL) => {
+                let still_current = {
+                    let inner = lock(inner);
+                    inner.config.enabled && inner.config.server_url.as_deref() == Some(server_url)
+                };
+                if !still_current {
+                    return Ok(());
+                }
+                if last_received.elapsed() >= STREAM_TIMEOUT {
+                    return Err("No data from server".to_string());
+                }
+                continue;
+            }
+        };
+        let Some(chunk) = chunk else {
+            return Ok(());
+        };
+        last_received = Instant::now();
+
+        for (name, data) in parser.push(&chunk) {
+            match name.as_str() {
+                CHANGE_EVENT => match serde_json::from_str::<ChangeNotice>(&data) {
+                    Ok(notice) => println!(
+                        "📡 [SyncScheduler] Change #{} on {} {} (received at {})",
+                        notice.sequence, notice.table_name, notice.id, notice.received_at
+                    ),
+                    Err(e) => eprintln!("⚠️ [SyncScheduler] Unreadable change notice: {}", e),
+                },
+                RESYNC_EVENT => {}
+                _ => continue,
+            }
+            lock(inner).remote_change_at.get_or_insert_with(Instant::now);
+            wake.notify_one();
+        }
+    }
+}
+
+/// Découpe un flux Server-Sent Events en événements `(nom, données)`,
+/// quelle que soit la découpe des morceaux reçus. Les lignes `id:`,
+/// `retry:` et les commentaires sont ignorés.
+#[derive(Default)]
+struct EventParser {
+    buffer: Vec<u8>,
+    name: Option<String>,
+    data: Vec<String>,
+}
+
+impl EventParser {
+    fn push(&mut self, chunk: &[u8]) -> Vec<(String, String)> {
+        self.buffer.extend_from_slice(chunk);
+        let mut events = Vec::new();
+        while let Some(end) = self.buffer.iter().position(|&b| b == b'\n') {
+            let line: Vec<u8> = self.buffer.drain(..=end).collect();
+            let line = String::from_utf8_lossy(&line);
+            let line = line.trim_end_matches(['\n', '\r']);
+            if line.is_empty() {
+                if self.name.is_some() || !self.data.is_empty() {
+                    let name = self.name.take().unwrap_or_else(|| "message".to_string());
+                    events.push((name, std::mem::take(&mut self.data).join("\n")));
+                }
+            } else if let Some(value) = line.strip_prefix("event:") {
+                self.name = Some(value.trim_start().to_string());
+            } else if let Some(value) = line.strip_prefix("data:") {
+                self.data.push(value.strip_prefix(' ').unwrap_or(value).to_string());
+            }
+        }
+        events
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn backoff_doubles_from_five_seconds_up_to_fifteen_minutes() {
+        let delays: Vec<u64> = (1..=9).map(|failures| backoff(failures).as_secs()).collect();
+        assert_eq!(delays, [5, 10, 20, 40, 80, 160, 320, 640, 900]);
+        assert_eq!(backoff(0), BACKOFF_BASE);
+        // Pas de dépassement après de très nombreux échecs
+        assert_eq!(backoff(40), BACKOFF_MAX);
+        assert_eq!(backoff(u32::MAX), BACKOFF_MAX);
+    }
+
+    #[test]
+    fn local_changes_wait_for_the_debounce_delay() {
+        // Modification faite à 10 000 ms, dernière synchronisation partie à 5 000 ms
+        assert!(!local_change_settled(10_000, 5_000, 10_000 + DEBOUNCE_MS - 1));
+        assert!(local_change_settled(10_000, 5_000, 10_000 + DEBOUNCE_MS));
+        // Déjà envoyée par une synchronisation partie après elle
+        assert!(!local_change_settled(10_000, 10_000, 60_000));
+        // Aucune modification signalée
+        assert!(!local_change_settled(0, 0, 60_000));
+    }
+
+    fn event(name: &str, data: &str) -> (String, String) {
+        (name.to_string(), data.to_string())
+    }
+
+    #[test]
+    fn events_survive_any_chunk_split() {
+        let stream = "event: change\ndata: {\"table\":\"companies\"}\n\nevent: resync\ndata:\n\n";
+        let expected = vec![event("change", "{\"table\":\"companies\"}"), event("resync", "")];
+        // Chaque découpe possible, y compris au milieu d'une ligne
+        for split in 0..=stream.len() {
+            let mut parser = EventParser::default();
+            let mut events = parser.push(&stream.as_bytes()[..split]);
+            events.extend(parser.push(&stream.as_bytes()[split..]));
+            assert_eq!(events, expected, "split at {}", split);
+        }
+        // Octet par octet, caractère multi-octets compris
+        let mut parser = EventParser::default();
+        let events: Vec<_> = "data: café\n\n".bytes().flat_map(|b| parser.push(&[b])).collect();
+        assert_eq!(events, vec![event("message", "café")]);
+    }
+
+    #[test]
+    fn crlf_lines_are_accepted() {
+        let mut parser = EventParser::default();
+        assert_eq!(
+            parser.push(b"event: change\r\ndata: 1\r\n\r\n"),
+            vec![event("change", "1")]
+        );
+        // CR et LF reçus séparément
+        assert!(parser.push(b"data: 2\r").is_empty());
+        assert!(parser.push(b"\n\r").is_empty());
+        assert_eq!(parser.push(b"\n"), vec![event("message", "2")]);
+    }
+
+    #[test]
+    fn data_lines_are_joined_and_comments_ignored() {
+        let mut parser = EventParser::default();
+        let events = parser.push(
+            b": keep-alive\n\n\
+              : commentaire\nevent: change\nid: 42\nretry: 1000\ndata: first\ndata:second\ndata:  indented\n\n",
+        );
+        // Un commentaire seul ne produit pas d'événement ; seul le premier
+        // espace après `data:` est retiré
+        assert_eq!(events, vec![event("change", "first\nsecond\n indented")]);
+        // Le nom ne déborde pas sur l'événement suivant
+        assert_eq!(parser.push(b"data: next\n\n"), vec![event("message", "next")]);
+    }
+}
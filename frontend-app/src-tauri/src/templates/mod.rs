@@ -0,0 +1,328 @@
+// Bibliothèque de modèles de documents
+//
+// Les modèles importés sont versionnés dans la table `document_templates`
+// (synchronisée avec le serveur) et recopiés dans
+// `<app_data>/templates/library`. La génération choisit le modèle le plus
+// spécifique pour le client, le type de produit et la langue demandés.
+
+use crate::commands::document_generator::{PRODUCTS_GROUP, PRODUCT_FIELDS, PROPOSAL_FIELDS};
+use crate::commands::invoice_commands::INVOICE_NAMES;
+use crate::db::models::DocumentTemplate;
+use crate::docx::DocxTemplate;
+use crate::xlsx::XlsxTemplate;
+use base64::Engine;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::path::{Path, PathBuf};
+
+pub const KIND_PROPOSAL: &str = "proposal";
+pub const KIND_INVOICE: &str = "invoice";
+
+/// Extension de fichier attendue pour un type de modèle.
+pub fn file_extension(kind: &str) -> Result<&'static str, String> {
+    match kind {
+        KIND_PROPOSAL => Ok("docx"),
+        KIND_INVOICE => Ok("xlsx"),
+        _ => Err(format!("Unknown template kind: {}", kind)),
+    }
+}
+
+/// Résultat de la vérification d'un modèle par rapport aux données
+/// envoyées par la génération.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct TemplateValidation {
+    /// Placeholders (docx) ou noms définis (xlsx) trouvés dans le modèle
+    pub placeholders: Vec<String>,
+    /// Présents dans le modèle mais absents du modèle de données ; simple
+    /// avertissement pour un classeur, qui peut définir ses propres noms
+    pub unknown: Vec<String>,
+    /// Fournis par le modèle de données mais inutilisés par le modèle
+    pub missing: Vec<String>,
+    /// Noms indispensables absents d'un classeur à noms définis
+    #[serde(default)]
+    pub missing_required: Vec<String>,
+    pub valid: bool,
+}
+
+/// Noms sans lesquels une facture générée depuis un classeur à noms définis
+/// n'aurait ni numéro ni lignes. Un classeur qui n'en déclare aucun suit la
+/// disposition historique.
+const REQUIRED_INVOICE_NAMES: &[&str] = &["InvoiceNumber", "LineDescription"];
+
+pub fn validate(kind: &str, bytes: &[u8]) -> Result<TemplateValidation, String> {
+    let placeholders = match kind {
+        KIND_PROPOSAL => DocxTemplate::from_bytes(bytes)?.placeholders(),
+        KIND_INVOICE => XlsxTemplate::from_bytes(bytes)?.defined_names(),
+        _ => return Err(format!("Unknown template kind: {}", kind)),
+    };
+    Ok(check_placeholders(kind, placeholders))
+}
+
+fn check_placeholders(kind: &str, placeholders: Vec<String>) -> TemplateValidation {
+    let known: Vec<String> = match kind {
+        KIND_PROPOSAL => {
+            let mut known: Vec<String> = PROPOSAL_FIELDS.iter().map(|f| f.to_string()).collect();
+            for field in PRODUCT_FIELDS {
+                known.push(field.to_string());
+                known.push(format!("{}.{}", PRODUCTS_GROUP, field));
+            }
+            known
+        }
+        _ => INVOICE_NAMES.iter().map(|n| n.to_lowercase()).collect(),
+    };
+
+    let unknown: Vec<String> = placeholders
+        .iter()
+        .filter(|p| !known.contains(p))
+        .cloned()
+        .collect();
+
+    // Un champ produit est utilisé s'il apparaît seul ou dans le groupe
+    let is_used = |field: &str| {
+        placeholders.iter().any(|p| {
+            p == field || p.strip_prefix(PRODUCTS_GROUP).and_then(|r| r.strip_prefix('.')) == Some(field)
+        })
+    };
+    let missing: Vec<String> = match kind {
+        KIND_PROPOSAL => PROPOSAL_FIELDS
+            .iter()
+            .chain(PRODUCT_FIELDS)
+            .filter(|f| !is_used(f))
+            .map(|f| f.to_string())
+            .collect(),
+        _ => INVOICE_NAMES
+            .iter()
+            .filter(|n| !placeholders.contains(&n.to_lowercase()))
+            .map(|n| n.to_string())
+            .collect(),
+    };
+
+    let missing_required: Vec<String> = match kind {
+        KIND_PROPOSAL => Vec::new(),
+        _ if missing.len() == INVOICE_NAMES.len() => Vec::new(),
+        _ => REQUIRED_INVOICE_NAMES
+            .iter()
+            .filter(|n| missing.iter().any(|m| m == *n))
+            .map(|n| n.to_string())
+            .collect(),
+    };
+
+    TemplateValidation {
+        valid: match kind {
+            KIND_PROPOSAL => unknown.is_empty(),
+            _ => missing_required.is_empty(),
+        },
+        placeholders,
+        unknown,
+        missing,
+        missing_required,
+    }
+}
+
+pub fn checksum(bytes: &[u8]) -> String {
+    hex::encode(Sha256::digest(bytes))
+}
+
+pub fn encode(bytes: &[u8]) -> String {
+    base64::engine::general_purpose::STANDARD.encode(bytes)
+}
+
+/// Contenu d'un modèle, vérifié par son empreinte.
+pub fn decode(template: &DocumentTemplate) -> Result<Vec<u8>, String> {
+    let content = template
+        .content
+        .as_deref()
+        .ok_or_else(|| format!("Template {} has no content", template.name))?;
+    let bytes = base64::engine::general_purpose::STANDARD
+        .decode(content)
+        .map_err(|e| format!("Invalid template content: {}", e))?;
+    if checksum(&bytes) != template.checksum {
+        return Err(format!("Template {} is corrupted (checksum mismatch)", template.name));
+    }
+    Ok(bytes)
+}
+
+/// Chemin du modèle dans la bibliothèque locale.
+pub fn library_path(app_data_dir: &Path, template: &DocumentTemplate) -> PathBuf {
+    let name: String = template
+        .name
+        .chars()
+        .map(|c| if c.is_alphanumeric() || c == '-' || c == '_' { c } else { '_' })
+        .collect();
+    let extension = Path::new(&template.file_name)
+        .extension()
+        .and_then(|e| e.to_str())
+        .unwrap_or("bin");
+    app_data_dir
+        .join("templates")
+        .join("library")
+        .join(format!("{}_{}_v{}.{}", template.kind, name, template.template_version, extension))
+}
+
+/// Écrit le modèle dans la bibliothèque locale s'il n'y est pas déjà
+/// (modèles reçus par synchronisation) et renvoie son contenu.
+pub fn materialize(app_data_dir: Option<&Path>, template: &DocumentTemplate) -> Result<Vec<u8>, String> {
+    let bytes = decode(template)?;
+    if let Some(dir) = app_data_dir {
+        let path = library_path(dir, template);
+        let up_to_date = std::fs::read(&path).map(|b| b == bytes).unwrap_or(false);
+        if !up_to_date {
+            if let Some(parent) = path.parent() {
+                std::fs::create_dir_all(parent)
+                    .map_err(|e| format!("Failed to create template library: {}", e))?;
+            }
+            std::fs::write(&path, &bytes)
+                .map_err(|e| format!("Failed to write template {}: {}", path.display(), e))?;
+        }
+    }
+    Ok(bytes)
+}
+
+/// Critères de choix d'un modèle ; `None` accepte n'importe quel modèle.
+#[derive(Debug, Clone, Default)]
+pub struct TemplateCriteria<'a> {
+    pub company_id: Option<&'a str>,
+    pub product_type: Option<&'a str>,
+    pub language: Option<&'a str>,
+}
+
+/// Choisit parmi les modèles d'un type le plus spécifique compatible avec
+/// les critères : un modèle sans client, produit ou langue convient à tous,
+/// un modèle qui en précise un doit correspondre. Seule la dernière version
+/// de chaque modèle est candidate.
+pub fn select<'t>(templates: &'t [DocumentTemplate], kind: &str, criteria: &TemplateCriteria) -> Option<&'t DocumentTemplate> {
+    let matches = |expected: &Option<String>, actual: Option<&str>| match expected {
+        None => Some(0),
+        Some(expected) => actual
+            .filter(|a| a.eq_ignore_ascii_case(expected))
+            .map(|_| 1),
+    };
+
+    templates
+        .iter()
+        .filter(|t| t.kind == kind && t.is_deleted.unwrap_or(0) == 0)
+        .filter(|t| {
+            !templates.iter().any(|other| {
+                other.kind == t.kind
+                    && other.name == t.name
+                    && other.is_deleted.unwrap_or(0) == 0
+                    && other.template_version > t.template_version
+            })
+        })
+        .filter_map(|t| {
+            let company = matches(&t.company_id, criteria.company_id)?;
+            let product = matches(&t.product_type, criteria.product_type)?;
+            let language = matches(&t.language, criteria.language)?;
+            Some((company * 4 + product * 2 + language, t))
+        })
+        .max_by(|(a, ta), (b, tb)| a.cmp(b).then(ta.updated_at.cmp(&tb.updated_at)))
+        .map(|(_, t)| t)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn template(id: &str, name: &str, version: i64, company_id: Option<&str>, language: Option<&str>) -> DocumentTemplate {
+        let bytes = id.as_bytes();
+        DocumentTemplate {
+            id: id.to_string(),
+            name: name.to_string(),
+            kind: KIND_PROPOSAL.to_string(),
+            product_type: None,
+            language: language.map(str::to_string),
+            company_id: company_id.map(str::to_string),
+            template_version: version,
+            file_name: "Offre standard.docx".to_string(),
+            content: Some(encode(bytes)),
+            checksum: checksum(bytes),
+            created_at: "1700000000000".to_string(),
+            updated_at: format!("17000000{:05}", version),
+            version: Some(1),
+            is_deleted: Some(0),
+        }
+    }
+
+    fn builtin(file: &str) -> Vec<u8> {
+        std::fs::read(Path::new(env!("CARGO_MANIFEST_DIR")).join("templates").join(file)).unwrap()
+    }
+
+    #[test]
+    fn most_specific_latest_template_is_selected() {
+        let library = vec![
+            template("generic-1", "Standard", 1, None, None),
+            template("generic-2", "Standard", 2, None, None),
+            template("english", "English", 1, None, Some("en")),
+            template("acme", "Acme", 1, Some("co-acme"), None),
+        ];
+        let select_id = |company_id, language| {
+            let criteria = TemplateCriteria { company_id, product_type: None, language };
+            select(&library, KIND_PROPOSAL, &criteria).map(|t| t.id.as_str())
+        };
+
+        assert_eq!(select_id(None, None), Some("generic-2"));
+        assert_eq!(select_id(None, Some("EN")), Some("english"));
+        assert_eq!(select_id(Some("co-acme"), Some("en")), Some("acme"));
+        assert_eq!(select_id(Some("co-other"), Some("fr")), Some("generic-2"));
+        assert_eq!(select(&library, KIND_INVOICE, &TemplateCriteria::default()).map(|t| t.id.as_str()), None);
+    }
+
+    #[test]
+    fn deleted_version_falls_back_to_previous_one() {
+        let mut latest = template("generic-2", "Standard", 2, None, None);
+        latest.is_deleted = Some(1);
+        let library = vec![template("generic-1", "Standard", 1, None, None), latest];
+        let selected = select(&library, KIND_PROPOSAL, &TemplateCriteria::default());
+        assert_eq!(selected.map(|t| t.id.as_str()), Some("generic-1"));
+    }
+
+    #[test]
+    fn content_is_checked_against_checksum() {
+        let mut stored = template("generic-1", "Standard", 1, None, None);
+        assert_eq!(decode(&stored).unwrap(), b"generic-1");
+        stored.content = Some(encode(b"tampered"));
+        assert!(decode(&stored).unwrap_err().contains("checksum mismatch"));
+        stored.content = None;
+        assert!(decode(&stored).is_err());
+    }
+
+    #[test]
+    fn library_path_is_versioned_and_sanitized() {
+        let path = library_path(Path::new("/data"), &template("t", "Offre/Standard 2025", 3, None, None));
+        assert_eq!(path, Path::new("/data/templates/library/proposal_Offre_Standard_2025_v3.docx"));
+    }
+
+    #[test]
+    fn builtin_templates_are_valid() {
+        let proposal = validate(KIND_PROPOSAL, &builtin("proposal.docx")).unwrap();
+        assert!(proposal.valid, "unknown placeholders: {:?}", proposal.unknown);
+        assert!(!proposal.placeholders.is_empty());
+
+        let invoice = validate(KIND_INVOICE, &builtin("invoice.xlsx")).unwrap();
+        assert!(invoice.valid, "unknown names: {:?}", invoice.unknown);
+
+        assert!(invoice.missing_required.is_empty());
+
+        assert!(validate(KIND_INVOICE, &builtin("proposal.docx")).is_err());
+        assert!(validate("letter", &builtin("proposal.docx")).is_err());
+        assert_eq!(file_extension(KIND_INVOICE), Ok("xlsx"));
+    }
+
+    #[test]
+    fn workbook_names_of_their_own_are_only_warnings() {
+        let names = |names: &[&str]| names.iter().map(|n| n.to_string()).collect::<Vec<_>>();
+
+        let own = check_placeholders(KIND_INVOICE, names(&["invoicenumber", "linedescription", "vatrate"]));
+        assert!(own.valid);
+        assert_eq!(own.unknown, ["vatrate"]);
+
+        let incomplete = check_placeholders(KIND_INVOICE, names(&["invoicenumber", "companyname"]));
+        assert!(!incomplete.valid);
+        assert_eq!(incomplete.missing_required, ["LineDescription"]);
+
+        // Aucun nom de facture : disposition historique
+        assert!(check_placeholders(KIND_INVOICE, names(&["vatrate"])).valid);
+        // Un placeholder inconnu reste une erreur dans un document Word
+        assert!(!check_placeholders(KIND_PROPOSAL, names(&["vatrate"])).valid);
+    }
+}
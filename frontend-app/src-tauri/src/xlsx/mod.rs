@@ -0,0 +1,440 @@
+// Remplissage de classeurs Excel (.xlsx) à partir d'un modèle
+//
+// Seules les cellules ciblées sont réécrites : styles, cellules fusionnées,
+// mise en page et formules du modèle sont conservés. Les cellules cibles
+// sont désignées par un nom défini du classeur ou par une référence A1.
+
+use crate::docx::xml;
+use chrono::NaiveDate;
+use std::collections::HashMap;
+use std::io::{Cursor, Read, Write};
+
+#[derive(Debug, Clone)]
+pub enum CellValue {
+    Text(String),
+    Number(f64),
+    /// Écrite en numéro de série Excel pour garder le format de la cellule
+    Date(NaiveDate),
+}
+
+/// Plage de cellules d'une feuille (bornes incluses, lignes et colonnes à
+/// partir de 1).
+#[derive(Debug, Clone)]
+pub struct CellRange {
+    pub sheet_path: String,
+    pub first_row: u32,
+    pub first_col: u32,
+    pub last_row: u32,
+    pub last_col: u32,
+}
+
+impl CellRange {
+    pub fn rows(&self) -> u32 {
+        self.last_row - self.first_row + 1
+    }
+}
+
+pub struct XlsxTemplate {
+    parts: Vec<(String, Vec<u8>)>,
+    /// (nom de la feuille, chemin de la partie XML) dans l'ordre du classeur
+    sheets: Vec<(String, String)>,
+    /// Noms définis, indexés en minuscules (Excel ne distingue pas la casse)
+    names: HashMap<String, String>,
+}
+
+impl XlsxTemplate {
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, String> {
+        let mut archive = zip::ZipArchive::new(Cursor::new(bytes))
+            .map_err(|e| format!("Failed to open workbook template: {}", e))?;
+        let mut parts = Vec::with_capacity(archive.len());
+        for i in 0..archive.len() {
+            let mut file = archive
+                .by_index(i)
+                .map_err(|e| format!("Failed to read workbook entry: {}", e))?;
+            let mut content = Vec::new();
+            file.read_to_end(&mut content)
+                .map_err(|e| format!("Failed to read workbook entry: {}", e))?;
+            parts.push((file.name().to_string(), content));
+        }
+
+        let mut template = Self { parts, sheets: Vec::new(), names: HashMap::new() };
+        let workbook = template
+            .part("xl/workbook.xml")
+            .ok_or("Template is not an Excel workbook (xl/workbook.xml missing)")?
+            .to_string();
+        let rels = template.part("xl/_rels/workbook.xml.rels").unwrap_or_default().to_string();
+
+        for range in xml::elements(&workbook, "sheet") {
+            let tag = &workbook[range];
+            let (Some(name), Some(rel_id)) = (attribute(tag, "name"), attribute(tag, "r:id")) else {
+                continue;
+            };
+            let target = xml::elements(&rels, "Relationship")
+                .into_iter()
+                .map(|r| &rels[r])
+                .find(|r| attribute(r, "Id").as_deref() == Some(rel_id.as_str()))
+                .and_then(|r| attribute(r, "Target"));
+            if let Some(target) = target {
+                let path = match target.strip_prefix('/') {
+                    Some(absolute) => absolute.to_string(),
+                    None => format!("xl/{}", target),
+                };
+                template.sheets.push((unescape(&name), path));
+            }
+        }
+        if template.sheets.is_empty() {
+            return Err("Workbook template has no worksheet".to_string());
+        }
+
+        for range in xml::elements(&workbook, "definedName") {
+            let element = &workbook[range];
+            let Some(name) = attribute(element, "name") else { continue };
+            if name.starts_with("_xlnm.") {
+                continue;
+            }
+            let start = element.find('>').map(|i| i + 1).unwrap_or(0);
+            let end = element.rfind("</").unwrap_or(element.len());
+            if start < end {
+                template.names.insert(name.to_lowercase(), unescape(&element[start..end]));
+            }
+        }
+
+        Ok(template)
+    }
+
+    fn part(&self, name: &str) -> Option<&str> {
+        self.parts
+            .iter()
+            .find(|(n, _)| n == name)
+            .and_then(|(_, content)| std::str::from_utf8(content).ok())
+    }
+
+    /// Noms définis du classeur, en minuscules.
+    pub fn defined_names(&self) -> Vec<String> {
+        let mut names: Vec<String> = self.names.keys().cloned().collect();
+        names.sort();
+        names
+    }
+
+    /// Plage désignée par un nom défini, si le modèle le déclare.
+    pub fn defined_name(&self, name: &str) -> Option<CellRange> {
+        let formula = self.names.get(&name.to_lowercase())?;
+        let (sheet, reference) = formula.rsplit_once('!')?;
+        let sheet = sheet.trim_matches('\'').replace("''", "'");
+        let sheet_path = self
+            .sheets
+            .iter()
+            .find(|(n, _)| *n == sheet)
+            .map(|(_, path)| path.clone())?;
+        parse_range(&sheet_path, reference)
+    }
+
+    /// Cellule de la première feuille désignée par une référence A1.
+    pub fn cell(&self, reference: &str) -> Result<CellRange, String> {
+        parse_range(&self.sheets[0].1, reference)
+            .ok_or_else(|| format!("Invalid cell reference: {}", reference))
+    }
+
+    /// Écrit une valeur dans la cellule `row_offset` lignes sous le coin
+    /// haut gauche de la plage. Une cellule contenant une formule garde sa
+    /// formule ; seule sa valeur en cache est effacée.
+    pub fn set(&mut self, range: &CellRange, row_offset: u32, value: CellValue) -> Result<(), String> {
+        let row = range.first_row + row_offset;
+        let reference = format!("{}{}", column_name(range.first_col), row);
+        let (_, content) = self
+            .parts
+            .iter_mut()
+            .find(|(name, _)| *name == range.sheet_path)
+            .ok_or_else(|| format!("Worksheet not found: {}", range.sheet_path))?;
+        let sheet = std::str::from_utf8(content)
+            .map_err(|e| format!("Invalid UTF-8 in {}: {}", range.sheet_path, e))?;
+        *content = set_cell(sheet, row, &reference, &value)?.into_bytes();
+        Ok(())
+    }
+
+    /// Classeur final. La chaîne de calcul est retirée et un recalcul complet
+    /// est demandé à l'ouverture pour que les formules reflètent les valeurs.
+    pub fn to_bytes(&self) -> Result<Vec<u8>, String> {
+        let mut buffer = Cursor::new(Vec::new());
+        {
+            let mut writer = zip::ZipWriter::new(&mut buffer);
+            let options = zip::write::FileOptions::default()
+                .compression_method(zip::CompressionMethod::Deflated);
+
+            for (name, content) in &self.parts {
+                let output: Vec<u8> = match name.as_str() {
+                    "xl/calcChain.xml" => continue,
+                    "xl/workbook.xml" => force_recalculation(&String::from_utf8_lossy(content)).into_bytes(),
+                    "xl/_rels/workbook.xml.rels" => {
+                        remove_element_containing(&String::from_utf8_lossy(content), "Relationship", "calcChain")
+                            .into_bytes()
+                    }
+                    "[Content_Types].xml" => {
+                        remove_element_containing(&String::from_utf8_lossy(content), "Override", "calcChain")
+                            .into_bytes()
+                    }
+                    _ => content.clone(),
+                };
+
+                writer
+                    .start_file(name.as_str(), options)
+                    .map_err(|e| format!("Failed to write {}: {}", name, e))?;
+                writer
+                    .write_all(&output)
+                    .map_err(|e| format!("Failed to write {}: {}", name, e))?;
+            }
+
+            writer
+                .finish()
+                .map_err(|e| format!("Failed to finalize workbook: {}", e))?;
+        }
+        Ok(buffer.into_inner())
+    }
+}
+
+fn set_cell(sheet: &str, row: u32, reference: &str, value: &CellValue) -> Result<String, String> {
+    let data = xml::elements(sheet, "sheetData")
+        .into_iter()
+        .next()
+        .ok_or("Worksheet has no sheetData")?;
+    let mut sheet = sheet.to_string();
+    let mut data = data;
+    if sheet[data.clone()].ends_with("/>") {
+        sheet.replace_range(data.clone(), "<sheetData></sheetData>");
+        data = data.start..data.start + "<sheetData></sheetData>".len();
+    }
+
+    let inner_start = data.start + sheet[data.clone()].find('>').unwrap_or(0) + 1;
+    let inner_end = data.end - "</sheetData>".len();
+    let rows = xml::children(&sheet, inner_start..inner_end);
+
+    let mut insert_at = inner_end;
+    for row_range in rows {
+        let row_xml = &sheet[row_range.clone()];
+        let number: u32 = attribute(row_xml, "r").and_then(|r| r.parse().ok()).unwrap_or(0);
+        if number == row {
+            let updated = set_cell_in_row(row_xml, reference, value);
+            sheet.replace_range(row_range, &updated);
+            return Ok(sheet);
+        }
+        if number > row {
+            insert_at = row_range.start;
+            break;
+        }
+    }
+
+    let new_row = format!("<row r=\"{}\">{}</row>", row, cell_xml(reference, None, value));
+    sheet.insert_str(insert_at, &new_row);
+    Ok(sheet)
+}
+
+fn set_cell_in_row(row_xml: &str, reference: &str, value: &CellValue) -> String {
+    let mut row_xml = row_xml.to_string();
+    if row_xml.ends_with("/>") {
+        row_xml = format!("{}></row>", row_xml.trim_end_matches("/>"));
+    }
+    let inner_start = row_xml.find('>').unwrap_or(0) + 1;
+    let inner_end = row_xml.len() - "</row>".len();
+    let target_col = column_index(reference);
+
+    let mut insert_at = inner_end;
+    for cell_range in xml::children(&row_xml, inner_start..inner_end) {
+        let cell = &row_xml[cell_range.clone()];
+        let cell_ref = attribute(cell, "r").unwrap_or_default();
+        if cell_ref == reference {
+            let replacement = if cell.contains("<f>") || cell.contains("<f ") {
+                clear_cached_value(cell)
+            } else {
+                cell_xml(reference, attribute(cell, "s").as_deref(), value)
+            };
+            row_xml.replace_range(cell_range, &replacement);
+            return row_xml;
+        }
+        if column_index(&cell_ref) > target_col {
+            insert_at = cell_range.start;
+            break;
+        }
+    }
+
+    row_xml.insert_str(insert_at, &cell_xml(reference, None, value));
+    row_xml
+}
+
+fn cell_xml(reference: &str, style: Option<&str>, value: &CellValue) -> String {
+    let style = style.map(|s| format!(" s=\"{}\"", s)).unwrap_or_default();
+    match value {
+        CellValue::Number(n) => format!("<c r=\"{}\"{}><v>{}</v></c>", reference, style, n),
+        CellValue::Date(d) => format!("<c r=\"{}\"{}><v>{}</v></c>", reference, style, excel_serial(*d)),
+        CellValue::Text(text) => format!(
+            "<c r=\"{}\"{} t=\"inlineStr\"><is><t xml:space=\"preserve\">{}</t></is></c>",
+            reference,
+            style,
+            xml::escape(text)
+        ),
+    }
+}
+
+fn clear_cached_value(cell: &str) -> String {
+    match (cell.find("<v>"), cell.find("</v>")) {
+        (Some(start), Some(end)) if start < end => {
+            format!("{}{}", &cell[..start], &cell[end + "</v>".len()..])
+        }
+        _ => cell.to_string(),
+    }
+}
+
+/// Éléments de CT_Workbook qui suivent `calcPr` : sans `calcPr` dans le
+/// modèle, il est inséré avant le premier d'entre eux pour respecter l'ordre
+/// du schéma (Excel répare sinon le fichier).
+const AFTER_CALC_PR: &[&str] = &[
+    "oleSize", "customWorkbookViews", "pivotCaches", "smartTagPr", "smartTagTypes",
+    "webPublishing", "fileRecoveryPr", "webPublishObjects", "extLst",
+];
+
+fn force_recalculation(workbook: &str) -> String {
+    if let Some(range) = xml::elements(workbook, "calcPr").into_iter().next() {
+        let tag = &workbook[range.clone()];
+        if tag.contains("fullCalcOnLoad") {
+            return workbook.to_string();
+        }
+        let updated = tag.replacen("<calcPr", "<calcPr fullCalcOnLoad=\"1\"", 1);
+        let mut output = workbook.to_string();
+        output.replace_range(range, &updated);
+        output
+    } else {
+        let position = AFTER_CALC_PR
+            .iter()
+            .filter_map(|name| xml::elements(workbook, name).first().map(|range| range.start))
+            .min()
+            .or_else(|| workbook.rfind("</workbook>"))
+            .unwrap_or(workbook.len());
+        let mut output = workbook.to_string();
+        output.insert_str(position, "<calcPr fullCalcOnLoad=\"1\"/>");
+        output
+    }
+}
+
+fn remove_element_containing(source: &str, name: &str, needle: &str) -> String {
+    let mut output = source.to_string();
+    for range in xml::elements(source, name).into_iter().rev() {
+        if source[range.clone()].contains(needle) {
+            output.replace_range(range, "");
+        }
+    }
+    output
+}
+
+fn attribute(tag: &str, name: &str) -> Option<String> {
+    let open_end = tag.find('>').unwrap_or(tag.len());
+    let open = &tag[..open_end];
+    let pattern = format!(" {}=\"", name);
+    let start = open.find(&pattern)? + pattern.len();
+    let end = open[start..].find('"')? + start;
+    Some(open[start..end].to_string())
+}
+
+fn unescape(value: &str) -> String {
+    value
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&amp;", "&")
+}
+
+fn parse_range(sheet_path: &str, reference: &str) -> Option<CellRange> {
+    let reference = reference.replace('$', "");
+    let (first, last) = reference.split_once(':').unwrap_or((&reference, &reference));
+    let (first_col, first_row) = parse_cell(first)?;
+    let (last_col, last_row) = parse_cell(last)?;
+    Some(CellRange {
+        sheet_path: sheet_path.to_string(),
+        first_row,
+        first_col,
+        last_row: last_row.max(first_row),
+        last_col: last_col.max(first_col),
+    })
+}
+
+fn parse_cell(reference: &str) -> Option<(u32, u32)> {
+    let split = reference.find(|c: char| c.is_ascii_digit())?;
+    let (letters, digits) = reference.split_at(split);
+    if letters.is_empty() || !letters.chars().all(|c| c.is_ascii_alphabetic()) {
+        return None;
+    }
+    Some((column_index(letters), digits.parse().ok()?))
+}
+
+/// Index de colonne (A = 1) d'une référence, chiffres ignorés.
+fn column_index(reference: &str) -> u32 {
+    reference
+        .chars()
+        .take_while(|c| c.is_ascii_alphabetic())
+        .fold(0, |acc, c| acc * 26 + (c.to_ascii_uppercase() as u32 - 'A' as u32 + 1))
+}
+
+fn column_name(mut index: u32) -> String {
+    let mut name = String::new();
+    while index > 0 {
+        let rem = (index - 1) % 26;
+        name.insert(0, (b'A' + rem as u8) as char);
+        index = (index - 1) / 26;
+    }
+    name
+}
+
+/// Numéro de série Excel (système 1900) d'une date.
+fn excel_serial(date: NaiveDate) -> i64 {
+    let epoch = NaiveDate::from_ymd_opt(1899, 12, 30).expect("valid epoch");
+    (date - epoch).num_days()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn calc_pr_is_inserted_before_later_workbook_elements() {
+        let workbook = "<workbook><sheets><sheet name=\"A\"/></sheets><definedNames/>\
+<extLst><ext uri=\"x\"/></extLst></workbook>";
+        assert_eq!(
+            force_recalculation(workbook),
+            "<workbook><sheets><sheet name=\"A\"/></sheets><definedNames/>\
+<calcPr fullCalcOnLoad=\"1\"/><extLst><ext uri=\"x\"/></extLst></workbook>"
+        );
+
+        let workbook = "<workbook><sheets/><oleSize ref=\"A1\"/><extLst/></workbook>";
+        assert_eq!(
+            force_recalculation(workbook),
+            "<workbook><sheets/><calcPr fullCalcOnLoad=\"1\"/><oleSize ref=\"A1\"/><extLst/></workbook>"
+        );
+
+        assert_eq!(
+            force_recalculation("<workbook><sheets/></workbook>"),
+            "<workbook><sheets/><calcPr fullCalcOnLoad=\"1\"/></workbook>"
+        );
+    }
+
+    #[test]
+    fn existing_calc_pr_is_updated_in_place() {
+        let workbook = "<workbook><sheets/><calcPr calcId=\"191029\"/><extLst/></workbook>";
+        let updated = force_recalculation(workbook);
+        assert_eq!(
+            updated,
+            "<workbook><sheets/><calcPr fullCalcOnLoad=\"1\" calcId=\"191029\"/><extLst/></workbook>"
+        );
+        assert_eq!(force_recalculation(&updated), updated);
+    }
+
+    #[test]
+    fn cell_references_round_trip() {
+        assert_eq!(column_index("A1"), 1);
+        assert_eq!(column_index("AA10"), 27);
+        assert_eq!(column_name(28), "AB");
+        assert_eq!(parse_cell("C13"), Some((3, 13)));
+        assert_eq!(parse_cell("13"), None);
+        let range = parse_range("xl/worksheets/sheet1.xml", "$B$41:$B$45").unwrap();
+        assert_eq!((range.first_col, range.first_row, range.rows()), (2, 41, 5));
+        assert_eq!(excel_serial(NaiveDate::from_ymd_opt(2025, 7, 23).unwrap()), 45861);
+    }
+}
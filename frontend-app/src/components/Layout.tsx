@@ -23,7 +23,7 @@ interface LayoutProps {
 const Layout: React.FC<LayoutProps> = ({ children }) => {
   const location = useLocation();
   const { user, logout } = useAuth();
-  const { isOnline, isSyncing, lastSync, pendingChanges, syncWithServer } = useSync();
+  const { isOnline, isSyncing, lastSync, pendingChanges, syncStage, lastError, rejectedChanges, syncWarnings, isLive, syncWithServer } = useSync();
 
   // Debug logs
   console.log('🖥️ [Layout] Sync state:', { isOnline, isSyncing, lastSync, pendingChanges });
@@ -93,6 +93,11 @@ const Layout: React.FC<LayoutProps> = ({ children }) => {
                   <span className="text-sm font-medium text-gray-700">
                     {isOnline ? 'Online' : 'Offline'}
                   </span>
+                  {isOnline && isLive && (
+                    <span className="ml-2 text-xs text-green-600" title="Changes from other devices arrive within seconds">
+                      Live
+                    </span>
+                  )}
                 </div>
                 {pendingChanges > 0 && (
                   <span className="text-xs bg-yellow-100 text-yellow-800 px-2 py-1 rounded-full">
@@ -109,7 +114,10 @@ const Layout: React.FC<LayoutProps> = ({ children }) => {
                 {isSyncing ? (
                   <>
                     <RefreshCw className="h-4 w-4 mr-2 animate-spin" />
-                    Syncing...
+                    {syncStage?.stage === 'pushing' ? `Sending ${syncStage.count}...`
+                      : syncStage?.stage === 'applying' ? `Receiving ${syncStage.count}...`
+                      : syncStage?.stage === 'attachments' ? 'Uploading files...'
+                      : 'Syncing...'}
                   </>
                 ) : (
                   <>
@@ -124,6 +132,22 @@ const Layout: React.FC<LayoutProps> = ({ children }) => {
                   Last sync: {new Date(lastSync).toLocaleString()}
                 </p>
               )}
+              {lastError && !isSyncing && (
+                <p className="text-xs text-red-600 mt-1" title={lastError.errors.join('\n')}>
+                  {lastError.message}
+                  {lastError.retryInSeconds > 0 && ` (retry in ${lastError.retryInSeconds}s)`}
+                </p>
+              )}
+              {rejectedChanges.length > 0 && !isSyncing && (
+                <p className="text-xs text-amber-600 mt-1" title={rejectedChanges.join('\n')}>
+                  {rejectedChanges.length} change(s) rejected by the server
+                </p>
+              )}
+              {syncWarnings.length > 0 && !isSyncing && (
+                <p className="text-xs text-amber-600 mt-1" title={syncWarnings.join('\n')}>
+                  {syncWarnings.length} local record(s) could not be sent
+                </p>
+              )}
             </div>
 
             {/* User info */}
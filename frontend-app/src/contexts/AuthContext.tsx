@@ -60,6 +60,12 @@ export const AuthProvider: React.FC<AuthProviderProps> = ({ children }) => {
         const userData = JSON.parse(storedUser);
         console.log('✅ [AuthContext] Found stored user:', userData.email);
         setUser(userData);
+        // The backend records the signed-in user as the author of deletions
+        if (typeof window.__TAURI_IPC__ === 'function') {
+          invoke('restore_session', { email: userData.email }).catch((error) =>
+            console.error('❌ [AuthContext] Error restoring session:', error)
+          );
+        }
       } else {
         console.log('🔄 [AuthContext] No stored user found');
       }
@@ -1,5 +1,6 @@
 import React, { createContext, useContext, useState, useEffect, ReactNode } from 'react';
 import { invoke } from '@tauri-apps/api/tauri';
+import { listen } from '@tauri-apps/api/event';
 
 interface SyncStatus {
   isOnline: boolean;
@@ -14,14 +15,64 @@ interface SyncResult {
   itemsSynced: number;
   newTimestamp: number;
   errors: string[];
+  // Changes the server refused; they are sent again with every sync
+  rejected: string[];
+  // Local records that could not be read, and were not sent
+  warnings: string[];
 }
 
+interface SyncProgress {
+  stage: 'pushing' | 'applying' | 'attachments';
+  count: number;
+}
+
+interface SyncFailure {
+  message: string;
+  errors: string[];
+  online: boolean;
+  retryInSeconds: number;
+}
+
+// Réglages enregistrés dans le navigateur par les versions précédentes,
+// repris une fois par le profil de serveur actif
+const migrateLegacySyncConfig = async () => {
+  const legacyUrl = localStorage.getItem('serverUrl');
+  const legacyAutoSync = localStorage.getItem('autoSync');
+  const legacyInterval = localStorage.getItem('syncIntervalSeconds');
+  if (legacyUrl === null && legacyAutoSync === null && legacyInterval === null) {
+    return;
+  }
+
+  if (legacyUrl && !(await invoke<string>('get_server_url'))) {
+    await invoke('set_server_url', { url: legacyUrl });
+  }
+  if (legacyAutoSync !== null || legacyInterval !== null) {
+    const settings = await invoke<any>('get_settings');
+    settings.local.sync = {
+      enabled: legacyAutoSync !== 'false',
+      interval_seconds: Number(legacyInterval) || settings.local.sync.interval_seconds,
+    };
+    await invoke('update_settings', { settings });
+  }
+  ['serverUrl', 'autoSync', 'syncIntervalSeconds'].forEach((key) => localStorage.removeItem(key));
+};
+
 interface SyncContextType {
   isOnline: boolean;
   isSyncing: boolean;
   lastSync: number | null;
   pendingChanges: number;
+  serverUrl: string;
+  syncStage: SyncProgress | null;
+  lastError: SyncFailure | null;
+  // Changes refused by the server in the last sync
+  rejectedChanges: string[];
+  // Local problems reported by the last sync
+  syncWarnings: string[];
+  // Abonné aux annonces de modifications du serveur
+  isLive: boolean;
   syncWithServer: () => Promise<SyncResult>;
+  refreshSyncStatus: () => Promise<void>;
   getSyncStatus: () => Promise<SyncStatus>;
   setServerUrl: (url: string) => Promise<void>;
   getServerUrl: () => Promise<string>;
@@ -46,16 +97,57 @@ export const SyncProvider: React.FC<SyncProviderProps> = ({ children }) => {
   const [isSyncing, setIsSyncing] = useState(false);
   const [lastSync, setLastSync] = useState<number | null>(null);
   const [pendingChanges, setPendingChanges] = useState(0);
-  const [serverUrl, setServerUrlState] = useState('http://localhost:8080');
+  const [serverUrl, setServerUrlState] = useState('');
+  const [syncStage, setSyncStage] = useState<SyncProgress | null>(null);
+  const [lastError, setLastError] = useState<SyncFailure | null>(null);
+  const [rejectedChanges, setRejectedChanges] = useState<string[]>([]);
+  const [syncWarnings, setSyncWarnings] = useState<string[]>([]);
+  const [isLive, setIsLive] = useState(false);
 
-  // Load initial sync status
+  // Démarrer la synchronisation en tâche de fond, puis suivre ses événements
   useEffect(() => {
-    loadSyncStatus();
-    
+    // La synchronisation en tâche de fond est configurée au démarrage, d'après
+    // le profil de serveur actif et les réglages du poste
+    migrateLegacySyncConfig()
+      .catch((error) => console.error('❌ [SyncContext] Failed to import previous sync settings:', error))
+      .finally(loadSyncStatus);
+
+    const unlisteners = [
+      listen('sync:started', () => {
+        setIsSyncing(true);
+        setSyncStage(null);
+      }),
+      listen<SyncProgress>('sync:progress', (event) => setSyncStage(event.payload)),
+      listen<SyncResult>('sync:success', (event) => {
+        setIsSyncing(false);
+        setSyncStage(null);
+        setLastError(null);
+        setRejectedChanges(event.payload.rejected);
+        setSyncWarnings(event.payload.warnings ?? []);
+        setIsOnline(true);
+        setLastSync(event.payload.newTimestamp);
+        window.dispatchEvent(new CustomEvent('dataSynced', {
+          detail: { itemsSynced: event.payload.itemsSynced }
+        }));
+        loadSyncStatus();
+      }),
+      listen<SyncFailure>('sync:error', (event) => {
+        console.error('❌ [SyncContext] Background sync failed:', event.payload.message);
+        setIsSyncing(false);
+        setSyncStage(null);
+        setLastError(event.payload);
+        setIsOnline(event.payload.online);
+      }),
+      listen<boolean>('sync:live', (event) => setIsLive(event.payload)),
+    ];
+
     // Check sync status every 30 seconds
     const interval = setInterval(loadSyncStatus, 30000);
-    
-    return () => clearInterval(interval);
+
+    return () => {
+      clearInterval(interval);
+      unlisteners.forEach((unlisten) => unlisten.then((f) => f()));
+    };
   }, []);
 
   const loadSyncStatus = async () => {
@@ -68,6 +160,9 @@ export const SyncProvider: React.FC<SyncProviderProps> = ({ children }) => {
       setLastSync(status.lastSync);
       setPendingChanges(status.pendingChanges);
       setServerUrlState(status.serverUrl);
+      // `sync:live` peut avoir été émis avant l'ouverture de la fenêtre
+      const scheduler = await invoke<{ live: boolean }>('get_sync_scheduler_status');
+      setIsLive(scheduler.live);
       console.log('✅ [SyncContext] Status updated - Online:', status.isOnline);
     } catch (error) {
       console.error('❌ [SyncContext] Failed to load sync status:', error);
@@ -83,6 +178,8 @@ export const SyncProvider: React.FC<SyncProviderProps> = ({ children }) => {
         itemsSynced: 0,
         newTimestamp: lastSync || 0,
         errors: ['Sync already in progress'],
+        rejected: [],
+        warnings: [],
       };
     }
 
@@ -90,17 +187,13 @@ export const SyncProvider: React.FC<SyncProviderProps> = ({ children }) => {
     
     try {
       console.log('🔄 [SyncContext] Starting synchronization...');
-      const result = await invoke<SyncResult>('sync_with_server', { serverUrl });
+      const result = await invoke<SyncResult>('sync_with_server');
       
+      // `dataSynced` est diffusé à la réception de l'événement `sync:success`
       if (result.success) {
         setLastSync(result.newTimestamp);
         setPendingChanges(0);
         console.log('✅ [SyncContext] Synchronization completed successfully');
-        
-        // Emit custom event to notify other components
-        window.dispatchEvent(new CustomEvent('dataSynced', { 
-          detail: { itemsSynced: result.itemsSynced } 
-        }));
       } else {
         console.error('❌ [SyncContext] Synchronization failed:', result.message);
       }
@@ -114,6 +207,8 @@ export const SyncProvider: React.FC<SyncProviderProps> = ({ children }) => {
         itemsSynced: 0,
         newTimestamp: lastSync || 0,
         errors: [String(error)],
+        rejected: [],
+        warnings: [],
       };
       return errorResult;
     } finally {
@@ -135,7 +230,7 @@ export const SyncProvider: React.FC<SyncProviderProps> = ({ children }) => {
   const setServerUrl = async (url: string): Promise<void> => {
     try {
       await invoke('set_server_url', { url });
-      setServerUrlState(url);
+      await loadSyncStatus();
     } catch (error) {
       console.error('Failed to set server URL:', error);
       throw error;
@@ -156,7 +251,14 @@ export const SyncProvider: React.FC<SyncProviderProps> = ({ children }) => {
     isSyncing,
     lastSync,
     pendingChanges,
+    serverUrl,
+    syncStage,
+    lastError,
+    rejectedChanges,
+    syncWarnings,
+    isLive,
     syncWithServer,
+    refreshSyncStatus: loadSyncStatus,
     getSyncStatus,
     setServerUrl,
     getServerUrl,
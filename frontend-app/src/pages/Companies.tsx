@@ -31,6 +31,8 @@ interface Company {
 }
 
 interface ContactFormData {
+  // Missing for contacts added in the form
+  id?: string;
   first_name: string;
   last_name: string;
   email: string;
@@ -121,6 +123,7 @@ const Companies: React.FC = () => {
             id: editingCompany.id,
             ...formData,
             contacts: contacts.map(c => ({
+              id: c.id || null,
               first_name: c.first_name,
               last_name: c.last_name,
               email: c.email,
@@ -140,6 +143,7 @@ const Companies: React.FC = () => {
             country: formData.country || null,
             description: formData.description || null,
             contacts: contacts.map(c => ({
+              id: c.id || null,
               first_name: c.first_name,
               last_name: c.last_name,
               email: c.email,
@@ -182,6 +186,7 @@ const Companies: React.FC = () => {
     });
     setContacts(
       company.contacts.map(c => ({
+        id: c.id,
         first_name: c.first_name,
         last_name: c.last_name,
         email: c.email,
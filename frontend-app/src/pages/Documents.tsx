@@ -1,10 +1,13 @@
 import React, { useState, useEffect } from 'react';
 import { invoke } from '@tauri-apps/api/tauri';
+import { open } from '@tauri-apps/api/shell';
 import { Plus, Search, FileText, Trash2, Download } from 'lucide-react';
+import { useSync } from '../contexts/SyncContext';
 
 interface DocumentItem {
   id: string;
-  customer_id: string;
+  customer_id?: string;
+  company_id?: string;
   title: string;
   document_type: string;
   file_path?: string;
@@ -12,6 +15,14 @@ interface DocumentItem {
   created_at: string;
   updated_at: string;
   sync_status: 'synced' | 'pending' | 'error';
+  sha256?: string;
+}
+
+interface AttachmentState {
+  document_id: string;
+  sha256: string;
+  state: 'synced' | 'pending_upload' | 'remote' | 'error';
+  last_error?: string;
 }
 
 interface Customer {
@@ -22,6 +33,8 @@ interface Customer {
 const Documents: React.FC = () => {
   const [documents, setDocuments] = useState<DocumentItem[]>([]);
   const [customers, setCustomers] = useState<Customer[]>([]);
+  const [attachments, setAttachments] = useState<Record<string, AttachmentState>>({});
+  const { serverUrl } = useSync();
   const [searchTerm, setSearchTerm] = useState('');
   const [isLoading, setIsLoading] = useState(true);
   const [showAddModal, setShowAddModal] = useState(false);
@@ -54,15 +67,17 @@ const Documents: React.FC = () => {
       console.log('✅ [Documents] Tauri is available');
       
       console.log('🔄 [Documents] Calling invoke get_documents and get_customers...');
-      const [docsData, customersData] = await Promise.all([
+      const [docsData, customersData, attachmentData] = await Promise.all([
         invoke<DocumentItem[]>('get_documents'),
         invoke<Customer[]>('get_customers'),
+        invoke<AttachmentState[]>('get_attachment_states'),
       ]);
       console.log('✅ [Documents] Received documents:', docsData);
       console.log('✅ [Documents] Received customers:', customersData);
       
       setDocuments(docsData);
       setCustomers(customersData);
+      setAttachments(Object.fromEntries(attachmentData.map((a) => [a.document_id, a])));
       console.log('✅ [Documents] Successfully loaded data');
     } catch (error) {
       console.error('❌ [Documents] Failed to load data:', error);
@@ -108,7 +123,29 @@ const Documents: React.FC = () => {
     }
   };
 
-  const getCustomerName = (customerId: string) => {
+  const handleOpen = async (doc: DocumentItem) => {
+    try {
+      // Téléchargé depuis le serveur si le fichier n'est pas sur ce poste
+      const path = await invoke<string>('open_document_attachment', { id: doc.id, serverUrl });
+      await open(path);
+      loadData();
+    } catch (error) {
+      console.error('Failed to open document:', error);
+      alert('Failed to open document: ' + error);
+    }
+  };
+
+  const getAttachmentLabel = (attachment: AttachmentState) => {
+    const labels: Record<AttachmentState['state'], [string, string]> = {
+      synced: ['Synced', 'bg-green-100 text-green-800'],
+      pending_upload: ['Upload pending', 'bg-yellow-100 text-yellow-800'],
+      remote: ['On server', 'bg-blue-100 text-blue-800'],
+      error: ['Sync error', 'bg-red-100 text-red-800'],
+    };
+    return labels[attachment.state];
+  };
+
+  const getCustomerName = (customerId?: string) => {
     const customer = customers.find(c => c.id === customerId);
     return customer?.name || 'Unknown';
   };
@@ -202,15 +239,28 @@ const Documents: React.FC = () => {
                     <span className={`inline-flex px-2 py-1 text-xs font-semibold rounded-full ${getDocumentTypeColor(doc.document_type)}`}>
                       {doc.document_type}
                     </span>
+                    {attachments[doc.id] && (
+                      <span
+                        title={attachments[doc.id].last_error}
+                        className={`ml-2 inline-flex px-2 py-1 text-xs font-semibold rounded-full ${getAttachmentLabel(attachments[doc.id])[1]}`}
+                      >
+                        {getAttachmentLabel(attachments[doc.id])[0]}
+                      </span>
+                    )}
                     <p className="mt-2 text-xs text-gray-500">
                       {new Date(parseInt(doc.created_at)).toLocaleDateString()}
                     </p>
                   </div>
                 </div>
                 <div className="bg-gray-50 px-5 py-3 flex justify-end space-x-3">
-                  <button className="text-primary-600 hover:text-primary-900">
-                    <Download className="h-4 w-4" />
-                  </button>
+                  {doc.sha256 && (
+                    <button
+                      onClick={() => handleOpen(doc)}
+                      className="text-primary-600 hover:text-primary-900"
+                    >
+                      <Download className="h-4 w-4" />
+                    </button>
+                  )}
                   <button 
                     onClick={() => handleDelete(doc.id)}
                     className="text-red-600 hover:text-red-900"
@@ -477,7 +477,7 @@ const Invoices: React.FC = () => {
                 <input
                   type="date"
                   value={selectedInvoice.issue_date.split('T')[0]}
-                  onChange={(e) => setSelectedInvoice({...selectedInvoice, issue_date: e.target.value + 'T00:00:00Z'})}
+                  onChange={(e) => setSelectedInvoice({...selectedInvoice, issue_date: e.target.value})}
                   className="w-full px-3 py-2 border border-gray-300 rounded-lg focus:outline-none focus:ring-2 focus:ring-blue-500"
                 />
               </div>
@@ -490,7 +490,7 @@ const Invoices: React.FC = () => {
                 <input
                   type="date"
                   value={selectedInvoice.due_date ? selectedInvoice.due_date.split('T')[0] : ''}
-                  onChange={(e) => setSelectedInvoice({...selectedInvoice, due_date: e.target.value ? e.target.value + 'T00:00:00Z' : undefined})}
+                  onChange={(e) => setSelectedInvoice({...selectedInvoice, due_date: e.target.value || undefined})}
                   className="w-full px-3 py-2 border border-gray-300 rounded-lg focus:outline-none focus:ring-2 focus:ring-blue-500"
                 />
               </div>
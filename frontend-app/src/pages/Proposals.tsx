@@ -59,6 +59,8 @@ const Proposals: React.FC = () => {
   const [isLoading, setIsLoading] = useState(true);
   const [showModal, setShowModal] = useState(false);
   const [editingProposal, setEditingProposal] = useState<Proposal | null>(null);
+  // Devise par défaut des réglages de l'organisation
+  const [defaultCurrency, setDefaultCurrency] = useState('USD');
 
   const [formData, setFormData] = useState({
     company_id: '',
@@ -110,10 +112,15 @@ const Proposals: React.FC = () => {
       console.log('✅ [Proposals] Tauri is available');
       
       console.log('🔄 [Proposals] Calling invoke get_proposals and get_companies...');
-      const [proposalsData, companiesData] = await Promise.all([
+      const [proposalsData, companiesData, settings] = await Promise.all([
         invoke<any[]>('get_proposals'),
         invoke<any[]>('get_companies'),
+        invoke<{ org: { default_currency: string } }>('get_settings'),
       ]);
+      setDefaultCurrency(settings.org.default_currency);
+      if (!showModal) {
+        setFormData((current) => ({ ...current, currency: settings.org.default_currency }));
+      }
       console.log('✅ [Proposals] Received raw proposals data:', proposalsData);
       console.log('✅ [Proposals] Received companies:', companiesData);
       
@@ -374,7 +381,7 @@ const Proposals: React.FC = () => {
     setFormData({
       company_id: '',
       status: 'DRAFT',
-      currency: 'USD',
+      currency: defaultCurrency,
       valid_until: '',
       notes: '',
     });
@@ -612,8 +619,9 @@ const Proposals: React.FC = () => {
                     onChange={(e) => setFormData({ ...formData, currency: e.target.value })}
                     className="mt-1 block w-full"
                   >
-                    <option value="USD">USD</option>
-                    <option value="EUR">EUR</option>
+                    {Array.from(new Set(['USD', 'EUR', defaultCurrency, formData.currency])).map((currency) => (
+                      <option key={currency} value={currency}>{currency}</option>
+                    ))}
                   </select>
                 </div>
 
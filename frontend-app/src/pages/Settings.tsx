@@ -1,42 +1,168 @@
 import React, { useState, useEffect } from 'react';
-import { Settings as SettingsIcon, Server, Save, Check } from 'lucide-react';
+import { invoke } from '@tauri-apps/api/tauri';
+import { Settings as SettingsIcon, Server, Save, Check, FolderOpen, Building, Plus, RefreshCw } from 'lucide-react';
+import { useSync } from '../contexts/SyncContext';
+
+interface ServerProfile {
+  name: string;
+  server_url: string | null;
+  credentials_ref: string | null;
+}
+
+interface ServerProfiles {
+  active: string;
+  profiles: ServerProfile[];
+}
+
+interface AppSettings {
+  local: {
+    output: {
+      directory: string | null;
+      proposal_pattern: string;
+      invoice_pattern: string;
+      auto_attach: boolean;
+    };
+    sync: {
+      enabled: boolean;
+      interval_seconds: number;
+    };
+  };
+  org: {
+    default_currency: string;
+    payment_terms_days: number;
+    invoice_number_format: string;
+    proposal_number_format: string;
+  };
+}
+
+const inputClass = 'mt-1 block w-full px-3 py-2 border border-gray-300 rounded-md shadow-sm focus:ring-blue-500 focus:border-blue-500 sm:text-sm';
 
 const Settings: React.FC = () => {
-  const [serverUrl, setServerUrl] = useState('http://localhost:8080');
+  const { lastError, rejectedChanges, pendingChanges, refreshSyncStatus } = useSync();
+  const [profiles, setProfiles] = useState<ServerProfiles | null>(null);
+  const [activeProfile, setActiveProfile] = useState<ServerProfile | null>(null);
+  const [newProfile, setNewProfile] = useState({ name: '', server_url: '' });
+  const [settings, setSettings] = useState<AppSettings | null>(null);
   const [isSaving, setIsSaving] = useState(false);
   const [isSaved, setIsSaved] = useState(false);
 
   useEffect(() => {
-    // Load saved server URL from localStorage
-    const savedUrl = localStorage.getItem('serverUrl');
-    if (savedUrl) {
-      setServerUrl(savedUrl);
-    }
+    loadSettings();
   }, []);
 
-  const handleSaveServerUrl = async () => {
+  const loadSettings = async () => {
+    try {
+      const [profilesData, settingsData] = await Promise.all([
+        invoke<ServerProfiles>('get_server_profiles'),
+        invoke<AppSettings>('get_settings'),
+      ]);
+      applyProfiles(profilesData);
+      setSettings(settingsData);
+    } catch (error) {
+      console.error('Failed to load settings:', error);
+      alert(`Impossible de charger les paramètres : ${error}`);
+    }
+  };
+
+  const applyProfiles = (data: ServerProfiles) => {
+    setProfiles(data);
+    setActiveProfile(data.profiles.find((p) => p.name === data.active) || null);
+  };
+
+  const markDirty = () => setIsSaved(false);
+
+  const updateLocal = <K extends keyof AppSettings['local']>(section: K, value: Partial<AppSettings['local'][K]>) => {
+    if (!settings) return;
+    setSettings({ ...settings, local: { ...settings.local, [section]: { ...settings.local[section], ...value } } });
+    markDirty();
+  };
+
+  const updateOrg = (value: Partial<AppSettings['org']>) => {
+    if (!settings) return;
+    setSettings({ ...settings, org: { ...settings.org, ...value } });
+    markDirty();
+  };
+
+  const saveProfile = async (profile: ServerProfile) => {
+    if (profile.server_url) {
+      try {
+        new URL(profile.server_url);
+      } catch (error) {
+        alert('URL invalide. Veuillez entrer une URL valide (ex: http://192.168.1.100:8080)');
+        return false;
+      }
+    }
+    try {
+      applyProfiles(await invoke<ServerProfiles>('save_server_profile', { profile }));
+      await refreshSyncStatus();
+      return true;
+    } catch (error) {
+      alert(`Impossible d'enregistrer le profil : ${error}`);
+      return false;
+    }
+  };
+
+  const handleSaveActiveProfile = async () => {
+    if (!activeProfile) return;
+    setIsSaving(true);
+    if (await saveProfile(activeProfile)) {
+      setIsSaved(true);
+      setTimeout(() => setIsSaved(false), 2000);
+    }
+    setIsSaving(false);
+  };
+
+  const handleAddProfile = async () => {
+    if (!newProfile.name.trim()) return;
+    const saved = await saveProfile({
+      name: newProfile.name.trim(),
+      server_url: newProfile.server_url.trim() || null,
+      credentials_ref: null,
+    });
+    if (saved) {
+      setNewProfile({ name: '', server_url: '' });
+    }
+  };
+
+  const handleSwitchProfile = async (name: string) => {
+    const pending = pendingChanges > 0
+      ? `\n\n${pendingChanges} modification(s) non synchronisée(s) resteront dans le profil « ${profiles?.active} » et seront envoyées à son prochain usage.`
+      : '';
+    if (!confirm(`Basculer vers le profil « ${name} » ? L'application va redémarrer sur la base de ce profil.${pending}`)) {
+      return;
+    }
+    try {
+      await invoke('switch_server_profile', { name });
+    } catch (error) {
+      alert(`Impossible de changer de profil : ${error}`);
+    }
+  };
+
+  const handleDeleteProfile = async (name: string) => {
+    if (!confirm(`Retirer le profil « ${name} » ? Sa base de données reste sur le disque.`)) {
+      return;
+    }
+    try {
+      applyProfiles(await invoke<ServerProfiles>('delete_server_profile', { name }));
+    } catch (error) {
+      alert(`Impossible de retirer le profil : ${error}`);
+    }
+  };
+
+  const handleSaveSettings = async () => {
+    if (!settings) return;
     setIsSaving(true);
     try {
-      // Validate URL format
-      new URL(serverUrl);
-      
-      // Save to localStorage
-      localStorage.setItem('serverUrl', serverUrl);
-      
+      setSettings(await invoke<AppSettings>('update_settings', { settings }));
       setIsSaved(true);
       setTimeout(() => setIsSaved(false), 2000);
     } catch (error) {
-      alert('URL invalide. Veuillez entrer une URL valide (ex: http://192.168.1.100:8080)');
+      alert(`Impossible d'enregistrer les paramètres : ${error}`);
     } finally {
       setIsSaving(false);
     }
   };
 
-  const handleUrlChange = (e: React.ChangeEvent<HTMLInputElement>) => {
-    setServerUrl(e.target.value);
-    setIsSaved(false);
-  };
-
   return (
     <div>
       <div className="sm:flex sm:items-center">
@@ -49,55 +175,288 @@ const Settings: React.FC = () => {
       </div>
 
       <div className="mt-8 max-w-2xl">
-        {/* Server Configuration */}
+        {/* Server Profiles */}
         <div className="bg-white shadow rounded-lg">
           <div className="px-4 py-5 sm:p-6">
             <h3 className="text-lg font-medium leading-6 text-gray-900 flex items-center">
               <Server className="h-5 w-5 mr-2 text-gray-500" />
-              Configuration du Serveur
+              Profils de Serveur
             </h3>
-            <div className="mt-5">
-              <div className="space-y-4">
+            <p className="mt-2 text-sm text-gray-500">
+              Chaque profil (production, test...) a sa propre base de données : leurs données ne se mélangent jamais.
+            </p>
+            {profiles && activeProfile && (
+              <div className="mt-5 space-y-4">
                 <div>
                   <label htmlFor="serverUrl" className="block text-sm font-medium text-gray-700">
-                    URL du Serveur
+                    URL du Serveur — profil « {activeProfile.name} »
                   </label>
-                  <div className="mt-1 flex rounded-md shadow-sm">
+                  <input
+                    type="text"
+                    id="serverUrl"
+                    value={activeProfile.server_url || ''}
+                    onChange={(e) => { setActiveProfile({ ...activeProfile, server_url: e.target.value || null }); markDirty(); }}
+                    placeholder="http://192.168.1.100:8080"
+                    className={inputClass}
+                  />
+                  <p className="mt-2 text-sm text-gray-500">
+                    Entrez l'adresse IP et le port de votre serveur backend (ex: http://192.168.1.100:8080)
+                  </p>
+                </div>
+                <div>
+                  <label htmlFor="credentialsRef" className="block text-sm font-medium text-gray-700">
+                    Référence des identifiants
+                  </label>
+                  <input
+                    type="text"
+                    id="credentialsRef"
+                    value={activeProfile.credentials_ref || ''}
+                    onChange={(e) => { setActiveProfile({ ...activeProfile, credentials_ref: e.target.value || null }); markDirty(); }}
+                    placeholder="Entrée du trousseau ou variable d'environnement"
+                    className={inputClass}
+                  />
+                  <p className="mt-2 text-sm text-gray-500">
+                    Seule la référence est enregistrée, jamais le mot de passe lui-même.
+                  </p>
+                </div>
+                <button
+                  type="button"
+                  onClick={handleSaveActiveProfile}
+                  disabled={isSaving}
+                  className="inline-flex items-center px-3 py-2 border border-gray-300 rounded-md text-sm font-medium bg-gray-50 text-gray-700 hover:bg-gray-100"
+                >
+                  {isSaved ? <Check className="h-4 w-4 mr-2" /> : <Save className="h-4 w-4 mr-2" />}
+                  Enregistrer le profil
+                </button>
+
+                <ul className="divide-y divide-gray-200 border border-gray-200 rounded-md">
+                  {profiles.profiles.map((profile) => (
+                    <li key={profile.name} className="flex items-center justify-between px-3 py-2 text-sm">
+                      <span>
+                        <span className="font-medium text-gray-900">{profile.name}</span>
+                        <span className="ml-2 text-gray-500">{profile.server_url || 'sans serveur'}</span>
+                      </span>
+                      {profile.name === profiles.active ? (
+                        <span className="text-green-700">Actif</span>
+                      ) : (
+                        <span className="space-x-3">
+                          <button type="button" onClick={() => handleSwitchProfile(profile.name)} className="inline-flex items-center text-blue-600 hover:text-blue-800">
+                            <RefreshCw className="h-4 w-4 mr-1" />
+                            Activer
+                          </button>
+                          {profile.name !== 'default' && (
+                            <button type="button" onClick={() => handleDeleteProfile(profile.name)} className="text-red-600 hover:text-red-800">
+                              Retirer
+                            </button>
+                          )}
+                        </span>
+                      )}
+                    </li>
+                  ))}
+                </ul>
+
+                <div className="flex space-x-2">
+                  <input
+                    type="text"
+                    value={newProfile.name}
+                    onChange={(e) => setNewProfile({ ...newProfile, name: e.target.value })}
+                    placeholder="Nom (ex: test)"
+                    className="w-32 px-3 py-2 border border-gray-300 rounded-md text-sm"
+                  />
+                  <input
+                    type="text"
+                    value={newProfile.server_url}
+                    onChange={(e) => setNewProfile({ ...newProfile, server_url: e.target.value })}
+                    placeholder="http://test-server:8080"
+                    className="flex-1 px-3 py-2 border border-gray-300 rounded-md text-sm"
+                  />
+                  <button
+                    type="button"
+                    onClick={handleAddProfile}
+                    className="inline-flex items-center px-3 py-2 border border-gray-300 rounded-md text-sm font-medium bg-gray-50 text-gray-700 hover:bg-gray-100"
+                  >
+                    <Plus className="h-4 w-4 mr-1" />
+                    Ajouter
+                  </button>
+                </div>
+              </div>
+            )}
+          </div>
+        </div>
+
+        {settings && (
+          <>
+            {/* Synchronisation */}
+            <div className="mt-6 bg-white shadow rounded-lg">
+              <div className="px-4 py-5 sm:p-6">
+                <div className="flex items-center">
+                  <input
+                    type="checkbox"
+                    id="autoSync"
+                    checked={settings.local.sync.enabled}
+                    onChange={(e) => updateLocal('sync', { enabled: e.target.checked })}
+                    className="h-4 w-4 text-blue-600 border-gray-300 rounded"
+                  />
+                  <label htmlFor="autoSync" className="ml-2 block text-sm text-gray-700">
+                    Synchronisation automatique (après chaque modification et toutes les
+                    <input
+                      type="number"
+                      min={1}
+                      value={Math.max(1, Math.round(settings.local.sync.interval_seconds / 60))}
+                      onChange={(e) => updateLocal('sync', { interval_seconds: Math.max(1, Number(e.target.value)) * 60 })}
+                      disabled={!settings.local.sync.enabled}
+                      className="mx-2 w-16 px-2 py-1 border border-gray-300 rounded-md text-sm"
+                    />
+                    minutes)
+                  </label>
+                </div>
+                {lastError && (
+                  <p className="mt-4 text-sm text-red-600">
+                    {lastError.online ? 'Erreur de synchronisation' : 'Serveur injoignable'} : {lastError.message}
+                    {lastError.retryInSeconds > 0 && ` — nouvel essai dans ${lastError.retryInSeconds} s`}
+                  </p>
+                )}
+                {rejectedChanges.length > 0 && (
+                  <div className="mt-4 text-sm text-amber-700">
+                    <p>Modifications refusées par le serveur, renvoyées à chaque synchronisation :</p>
+                    <ul className="mt-1 list-disc list-inside">
+                      {rejectedChanges.map((rejection) => (
+                        <li key={rejection}>{rejection}</li>
+                      ))}
+                    </ul>
+                  </div>
+                )}
+              </div>
+            </div>
+
+            {/* Documents */}
+            <div className="mt-6 bg-white shadow rounded-lg">
+              <div className="px-4 py-5 sm:p-6">
+                <h3 className="text-lg font-medium leading-6 text-gray-900 flex items-center">
+                  <FolderOpen className="h-5 w-5 mr-2 text-gray-500" />
+                  Documents Générés
+                </h3>
+                <div className="mt-5 space-y-4">
+                  <div>
+                    <label className="block text-sm font-medium text-gray-700">Dossier de sortie</label>
                     <input
                       type="text"
-                      id="serverUrl"
-                      value={serverUrl}
-                      onChange={handleUrlChange}
-                      placeholder="http://192.168.1.100:8080"
-                      className="flex-1 min-w-0 block w-full px-3 py-2 rounded-none rounded-l-md border border-gray-300 focus:ring-blue-500 focus:border-blue-500 sm:text-sm"
+                      value={settings.local.output.directory || ''}
+                      onChange={(e) => updateLocal('output', { directory: e.target.value || null })}
+                      placeholder="Téléchargements"
+                      className={inputClass}
                     />
-                    <button
-                      type="button"
-                      onClick={handleSaveServerUrl}
-                      disabled={isSaving}
-                      className={`inline-flex items-center px-3 py-2 border border-l-0 border-gray-300 rounded-r-md text-sm font-medium ${
-                        isSaved
-                          ? 'bg-green-50 text-green-700 border-green-300'
-                          : 'bg-gray-50 text-gray-700 hover:bg-gray-100'
-                      } focus:outline-none focus:ring-1 focus:ring-blue-500 focus:border-blue-500`}
-                    >
-                      {isSaving ? (
-                        <div className="animate-spin rounded-full h-4 w-4 border-b-2 border-gray-600"></div>
-                      ) : isSaved ? (
-                        <Check className="h-4 w-4" />
-                      ) : (
-                        <Save className="h-4 w-4" />
-                      )}
-                    </button>
                   </div>
-                  <p className="mt-2 text-sm text-gray-500">
-                    Entrez l'adresse IP et le port de votre serveur backend (ex: http://192.168.1.100:8080)
+                  <div className="grid grid-cols-2 gap-4">
+                    <div>
+                      <label className="block text-sm font-medium text-gray-700">Nom des propositions</label>
+                      <input
+                        type="text"
+                        value={settings.local.output.proposal_pattern}
+                        onChange={(e) => updateLocal('output', { proposal_pattern: e.target.value })}
+                        className={inputClass}
+                      />
+                    </div>
+                    <div>
+                      <label className="block text-sm font-medium text-gray-700">Nom des factures</label>
+                      <input
+                        type="text"
+                        value={settings.local.output.invoice_pattern}
+                        onChange={(e) => updateLocal('output', { invoice_pattern: e.target.value })}
+                        className={inputClass}
+                      />
+                    </div>
+                  </div>
+                  <p className="text-sm text-gray-500">
+                    Champs disponibles : {'{company}'}, {'{number}'}, {'{revision}'}, {'{date}'}
                   </p>
+                  <div className="flex items-center">
+                    <input
+                      type="checkbox"
+                      id="autoAttach"
+                      checked={settings.local.output.auto_attach}
+                      onChange={(e) => updateLocal('output', { auto_attach: e.target.checked })}
+                      className="h-4 w-4 text-blue-600 border-gray-300 rounded"
+                    />
+                    <label htmlFor="autoAttach" className="ml-2 block text-sm text-gray-700">
+                      Rattacher chaque document généré à la fiche de la société
+                    </label>
+                  </div>
                 </div>
               </div>
             </div>
-          </div>
-        </div>
+
+            {/* Organisation */}
+            <div className="mt-6 bg-white shadow rounded-lg">
+              <div className="px-4 py-5 sm:p-6">
+                <h3 className="text-lg font-medium leading-6 text-gray-900 flex items-center">
+                  <Building className="h-5 w-5 mr-2 text-gray-500" />
+                  Organisation
+                </h3>
+                <p className="mt-2 text-sm text-gray-500">
+                  Partagés avec tous les postes par la synchronisation.
+                </p>
+                <div className="mt-5 grid grid-cols-2 gap-4">
+                  <div>
+                    <label className="block text-sm font-medium text-gray-700">Devise par défaut</label>
+                    <input
+                      type="text"
+                      maxLength={3}
+                      value={settings.org.default_currency}
+                      onChange={(e) => updateOrg({ default_currency: e.target.value.toUpperCase() })}
+                      className={inputClass}
+                    />
+                  </div>
+                  <div>
+                    <label className="block text-sm font-medium text-gray-700">Échéance des factures (jours)</label>
+                    <input
+                      type="number"
+                      min={0}
+                      max={365}
+                      value={settings.org.payment_terms_days}
+                      onChange={(e) => updateOrg({ payment_terms_days: Math.max(0, Number(e.target.value)) })}
+                      className={inputClass}
+                    />
+                  </div>
+                  <div>
+                    <label className="block text-sm font-medium text-gray-700">Numéro de facture</label>
+                    <input
+                      type="text"
+                      value={settings.org.invoice_number_format}
+                      onChange={(e) => updateOrg({ invoice_number_format: e.target.value })}
+                      className={inputClass}
+                    />
+                  </div>
+                  <div>
+                    <label className="block text-sm font-medium text-gray-700">Numéro de proposition</label>
+                    <input
+                      type="text"
+                      value={settings.org.proposal_number_format}
+                      onChange={(e) => updateOrg({ proposal_number_format: e.target.value })}
+                      className={inputClass}
+                    />
+                  </div>
+                </div>
+                <p className="mt-2 text-sm text-gray-500">
+                  Champs disponibles : {'{YYYY}'}, {'{YY}'}, {'{MM}'}, {'{DD}'}, {'{SEQ}'} (factures, rang dans le mois),
+                  {' '}{'{company}'} et {'{products}'} (propositions)
+                </p>
+              </div>
+            </div>
+
+            <div className="mt-6 flex justify-end">
+              <button
+                type="button"
+                onClick={handleSaveSettings}
+                disabled={isSaving}
+                className="inline-flex items-center px-4 py-2 border border-transparent rounded-md shadow-sm text-sm font-medium text-white bg-blue-600 hover:bg-blue-700"
+              >
+                {isSaved ? <Check className="h-4 w-4 mr-2" /> : <Save className="h-4 w-4 mr-2" />}
+                Enregistrer les paramètres
+              </button>
+            </div>
+          </>
+        )}
 
         {/* Local Application Info */}
         <div className="mt-6 bg-white shadow rounded-lg">
@@ -108,7 +467,7 @@ const Settings: React.FC = () => {
             </h3>
             <div className="mt-5">
               <p className="text-sm text-gray-600">
-                Cette application CRM fonctionne localement avec SQLite pour le stockage des données. 
+                Cette application CRM fonctionne localement avec SQLite pour le stockage des données.
                 Toutes vos données sont stockées sur votre appareil et aucune synchronisation externe n'est requise.
               </p>
             </div>
@@ -119,7 +478,7 @@ const Settings: React.FC = () => {
         <div className="mt-6 bg-blue-50 border border-blue-200 rounded-lg p-4">
           <h4 className="text-sm font-medium text-blue-900 mb-2">À propos du stockage local</h4>
           <p className="text-sm text-blue-700">
-            Vos données sont stockées localement dans une base de données SQLite sur votre appareil. 
+            Vos données sont stockées localement dans une base de données SQLite sur votre appareil.
             Cela garantit un accès rapide et une confidentialité complète de vos données d'entreprise.
           </p>
           <p className="text-sm text-blue-700 mt-2">